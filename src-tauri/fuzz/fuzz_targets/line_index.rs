@@ -0,0 +1,29 @@
+#![no_main]
+
+//! Index arbitrary bytes, whole and in appended pieces, and compare with the naive splitter
+
+use libfuzzer_sys::fuzz_target;
+use log_microscope_lib::indexer::{naive_lines, LogFile};
+
+fn check(file: &LogFile, data: &[u8]) {
+    let expected = naive_lines(data);
+    assert_eq!(file.line_count(), expected.len() as u64);
+    for (i, line) in expected.iter().enumerate() {
+        assert_eq!(file.line_bytes(i as u64), Some(*line));
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    check(&LogFile::from_bytes("fuzz", data.to_vec()), data);
+
+    // The first byte picks where the data is split into appends
+    let Some((&split, rest)) = data.split_first() else {
+        return;
+    };
+    let step = usize::from(split).max(1);
+    let mut live = LogFile::live("fuzz");
+    for piece in rest.chunks(step) {
+        live.append_bytes(piece);
+    }
+    check(&live, rest);
+});
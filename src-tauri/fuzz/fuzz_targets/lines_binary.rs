@@ -0,0 +1,12 @@
+#![no_main]
+
+//! Decode arbitrary buffers without panicking, and re-encode whatever decodes to the same bytes
+
+use libfuzzer_sys::fuzz_target;
+use log_microscope_lib::indexer::{decode_lines_binary, encode_lines_binary};
+
+fuzz_target!(|data: &[u8]| {
+    if let Some(lines) = decode_lines_binary(data) {
+        assert_eq!(encode_lines_binary(&lines), data);
+    }
+});
@@ -0,0 +1,237 @@
+use crate::alerts::Alert;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::io;
+use std::process::{Command, ExitStatus, Stdio};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// Time an action may take before it is abandoned
+const ACTION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Placeholders of a command and the environment variables carrying their values
+const PLACEHOLDERS: [(&str, &str); 5] = [
+    ("{rule}", "ALERT_RULE"),
+    ("{message}", "ALERT_MESSAGE"),
+    ("{file}", "ALERT_FILE"),
+    ("{line}", "ALERT_LINE"),
+    ("{text}", "ALERT_TEXT"),
+];
+
+/// Errors that can occur running an alert action
+#[derive(Debug, Error)]
+pub enum ActionError {
+    #[error("Webhook URLs must start with http:// or https://")]
+    InvalidUrl,
+    #[error("Commands can't be empty")]
+    EmptyCommand,
+    #[error("Webhook request failed: {0}")]
+    Webhook(#[from] Box<ureq::Error>),
+    #[error("Failed to run command: {0}")]
+    Io(#[from] io::Error),
+    #[error("Command exited with {0}")]
+    CommandFailed(ExitStatus),
+    #[error("Command took longer than {} seconds", ACTION_TIMEOUT.as_secs())]
+    TimedOut,
+}
+
+/// Payload shape a webhook expects
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookFormat {
+    /// The alert as a JSON object
+    #[default]
+    Generic,
+    /// Slack incoming webhook, with mrkdwn text
+    Slack,
+    /// Microsoft Teams incoming webhook, with Markdown text
+    Teams,
+}
+
+/// Something done when an alert fires, besides showing it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AlertAction {
+    /// POST the alert to a URL
+    Webhook {
+        url: String,
+        #[serde(default)]
+        format: WebhookFormat,
+    },
+    /// Run a command with the system shell, `sh` or PowerShell on Windows
+    /// `{rule}`, `{message}`, `{file}`, `{line}` and `{text}` stand for the alert's values,
+    /// passed through environment variables so log text can't inject shell syntax
+    Command { command: String },
+}
+
+impl AlertAction {
+    pub fn validate(&self) -> Result<(), ActionError> {
+        match self {
+            AlertAction::Webhook { url, .. }
+                if !(url.starts_with("http://") || url.starts_with("https://")) =>
+            {
+                Err(ActionError::InvalidUrl)
+            }
+            AlertAction::Command { command } if command.trim().is_empty() => {
+                Err(ActionError::EmptyCommand)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Run the action for a fired alert, blocking until it's done
+    pub fn run(&self, alert: &Alert) -> Result<(), ActionError> {
+        match self {
+            AlertAction::Webhook { url, format } => {
+                ureq::post(url)
+                    .timeout(ACTION_TIMEOUT)
+                    .send_json(webhook_body(*format, alert))
+                    .map_err(Box::new)?;
+                Ok(())
+            }
+            AlertAction::Command { command } => run_command(command, alert),
+        }
+    }
+}
+
+/// An action that failed, reported to the UI
+#[derive(Debug, Clone, Serialize)]
+pub struct ActionFailure {
+    pub rule: String,
+    pub message: String,
+}
+
+fn webhook_body(format: WebhookFormat, alert: &Alert) -> serde_json::Value {
+    let location = match alert.line {
+        Some(line) => format!("{}:{}", alert.file, line + 1),
+        None => alert.file.clone(),
+    };
+    match format {
+        WebhookFormat::Generic => json!(alert),
+        WebhookFormat::Slack => json!({
+            "text": format!("*{}*: {}\n`{}`", alert.rule, alert.message, location),
+        }),
+        WebhookFormat::Teams => json!({
+            "text": format!("**{}**: {}  \n`{}`", alert.rule, alert.message, location),
+        }),
+    }
+}
+
+/// Values of the placeholders, in the order of `PLACEHOLDERS`; lines are numbered from 1
+fn placeholder_values(alert: &Alert) -> [String; 5] {
+    [
+        alert.rule.clone(),
+        alert.message.clone(),
+        alert.file.clone(),
+        alert
+            .line
+            .map(|line| (line + 1).to_string())
+            .unwrap_or_default(),
+        alert.text.clone().unwrap_or_default(),
+    ]
+}
+
+/// Replace the placeholders of a command with references to their environment variables
+fn expand_command(command: &str) -> String {
+    PLACEHOLDERS
+        .iter()
+        .fold(command.to_string(), |command, (placeholder, var)| {
+            let reference = if cfg!(windows) {
+                format!("$env:{}", var)
+            } else {
+                format!("\"${}\"", var)
+            };
+            command.replace(placeholder, &reference)
+        })
+}
+
+fn shell(script: &str) -> Command {
+    if cfg!(windows) {
+        let mut command = Command::new("powershell");
+        command.args(["-NoProfile", "-NonInteractive", "-Command", script]);
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.args(["-c", script]);
+        command
+    }
+}
+
+fn run_command(command: &str, alert: &Alert) -> Result<(), ActionError> {
+    let vars = PLACEHOLDERS.iter().map(|(_, var)| *var);
+    let mut child = shell(&expand_command(command))
+        .envs(vars.zip(placeholder_values(alert)))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let deadline = Instant::now() + ACTION_TIMEOUT;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            if !status.success() {
+                return Err(ActionError::CommandFailed(status));
+            }
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            child.kill().ok();
+            child.wait().ok();
+            return Err(ActionError::TimedOut);
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alert(text: &str) -> Alert {
+        Alert {
+            rule: "oom".to_string(),
+            message: "out of memory".to_string(),
+            file: "/var/log/app.log".to_string(),
+            line: Some(41),
+            text: Some(text.to_string()),
+            notify: false,
+            actions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_webhook_body() {
+        let body = webhook_body(WebhookFormat::Slack, &alert("killed"));
+        assert_eq!(
+            body,
+            json!({"text": "*oom*: out of memory\n`/var/log/app.log:42`"})
+        );
+        let body = webhook_body(WebhookFormat::Generic, &alert("killed"));
+        assert_eq!(body["line"], 41);
+        assert_eq!(body["text"], "killed");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_command_gets_alert_values_without_injection() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out.txt");
+        let action = AlertAction::Command {
+            command: format!("printf '%s|%s' {{line}} {{text}} > '{}'", out.display()),
+        };
+        let text = "$(touch injected) 'quoted\"";
+        action.run(&alert(text)).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&out).unwrap(),
+            format!("42|{}", text)
+        );
+        assert!(!std::path::Path::new("injected").exists());
+
+        let failing = AlertAction::Command {
+            command: "exit 3".to_string(),
+        };
+        assert!(matches!(
+            failing.run(&alert(text)),
+            Err(ActionError::CommandFailed(_))
+        ));
+    }
+}
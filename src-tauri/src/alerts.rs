@@ -0,0 +1,431 @@
+use crate::actions::{ActionError, AlertAction};
+use crate::parsers::{load_json, save_json};
+use crate::regex_cache::{Matcher, PatternError, RegexFlavor};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// File alert rules are persisted to, inside the app config directory
+const ALERTS_FILE: &str = "alerts.json";
+
+/// Characters of a triggering line kept in an alert's message
+const MAX_MESSAGE_CHARS: usize = 200;
+
+/// Errors that can occur checking an alert rule
+#[derive(Debug, Error)]
+pub enum AlertError {
+    #[error("Alert rules need a name")]
+    EmptyName,
+    #[error("Invalid pattern of alert \"{name}\": {source}")]
+    Pattern { name: String, source: PatternError },
+    #[error("Error rate threshold of alert \"{0}\" must be between 0 and 1")]
+    InvalidThreshold(String),
+    #[error("Stall time of alert \"{0}\" must be at least a second")]
+    InvalidStall(String),
+    #[error("Invalid action of alert \"{name}\": {source}")]
+    Action { name: String, source: ActionError },
+}
+
+/// What makes an alert fire while following
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AlertCondition {
+    /// An appended line matches a pattern
+    Pattern {
+        pattern: String,
+        #[serde(default)]
+        flavor: RegexFlavor,
+    },
+    /// The share of error lines in the live statistics window exceeds a threshold
+    ErrorRate {
+        /// Share between 0 and 1
+        threshold: f64,
+        /// Lines the window needs before the rate counts, so a single early error doesn't fire
+        #[serde(default = "default_min_lines")]
+        min_lines: u64,
+    },
+    /// No lines were appended for a number of seconds
+    Stalled { seconds: u64 },
+}
+
+fn default_min_lines() -> u64 {
+    20
+}
+
+/// A configured alert
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AlertRule {
+    pub name: String,
+    pub condition: AlertCondition,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Raise a desktop notification, besides showing the alert in the app
+    #[serde(default = "default_true")]
+    pub notify: bool,
+    /// Seconds after firing during which the rule stays quiet
+    #[serde(default = "default_cooldown_secs")]
+    pub cooldown_secs: u64,
+    /// Webhooks and commands run when the rule fires
+    #[serde(default)]
+    pub actions: Vec<AlertAction>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_cooldown_secs() -> u64 {
+    60
+}
+
+impl AlertRule {
+    /// Check the rule, compiling its pattern if it has one
+    pub fn validate(&self) -> Result<Option<Matcher>, AlertError> {
+        if self.name.trim().is_empty() {
+            return Err(AlertError::EmptyName);
+        }
+        for action in &self.actions {
+            action.validate().map_err(|source| AlertError::Action {
+                name: self.name.clone(),
+                source,
+            })?;
+        }
+        match &self.condition {
+            AlertCondition::Pattern { pattern, flavor } => Matcher::new(pattern, *flavor)
+                .map(Some)
+                .map_err(|source| AlertError::Pattern {
+                    name: self.name.clone(),
+                    source,
+                }),
+            AlertCondition::ErrorRate { threshold, .. } if !(0.0..=1.0).contains(threshold) => {
+                Err(AlertError::InvalidThreshold(self.name.clone()))
+            }
+            AlertCondition::Stalled { seconds: 0 } => {
+                Err(AlertError::InvalidStall(self.name.clone()))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Alert rules persisted as JSON, applied to whatever file is followed
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AlertStore {
+    #[serde(skip)]
+    path: PathBuf,
+    #[serde(default)]
+    rules: Vec<AlertRule>,
+}
+
+impl AlertStore {
+    /// Load the rules from a config directory; a missing file has none
+    pub fn load(dir: &Path) -> io::Result<Self> {
+        let path = dir.join(ALERTS_FILE);
+        let mut store: AlertStore = load_json(&path)?;
+        store.path = path;
+        Ok(store)
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        save_json(&self.path, self)
+    }
+
+    pub fn rules(&self) -> &[AlertRule] {
+        &self.rules
+    }
+
+    /// Add a rule, or replace the rule with the same name
+    pub fn upsert(&mut self, rule: AlertRule) {
+        match self.rules.iter_mut().find(|r| r.name == rule.name) {
+            Some(existing) => *existing = rule,
+            None => self.rules.push(rule),
+        }
+    }
+
+    /// Remove a rule, returning whether it existed
+    pub fn remove(&mut self, name: &str) -> bool {
+        let before = self.rules.len();
+        self.rules.retain(|r| r.name != name);
+        self.rules.len() != before
+    }
+}
+
+/// An alert that fired
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Alert {
+    /// Name of the rule that fired
+    pub rule: String,
+    pub message: String,
+    /// Path of the followed file
+    pub file: String,
+    /// Index of the line that triggered the alert, to jump to
+    pub line: Option<u64>,
+    /// Text of the triggering line, for pattern alerts
+    pub text: Option<String>,
+    pub notify: bool,
+    #[serde(skip)]
+    pub actions: Vec<AlertAction>,
+}
+
+/// A rule with its compiled pattern and when it last fired
+struct ArmedRule {
+    rule: AlertRule,
+    matcher: Option<Matcher>,
+    last_fired: Option<Instant>,
+}
+
+impl ArmedRule {
+    fn cooling_down(&self, now: Instant) -> bool {
+        self.last_fired.is_some_and(|fired| {
+            now.saturating_duration_since(fired) < Duration::from_secs(self.rule.cooldown_secs)
+        })
+    }
+
+    fn fire(&mut self, now: Instant, file: &str, message: String, line: Option<u64>) -> Alert {
+        self.last_fired = Some(now);
+        Alert {
+            rule: self.rule.name.clone(),
+            message,
+            file: file.to_string(),
+            line,
+            text: None,
+            notify: self.rule.notify,
+            actions: self.rule.actions.clone(),
+        }
+    }
+}
+
+/// Checks enabled alert rules against the lines appended while following
+#[derive(Default)]
+pub struct AlertMonitor {
+    rules: Vec<ArmedRule>,
+    /// When lines last arrived, or following started
+    last_append: Option<Instant>,
+}
+
+impl AlertMonitor {
+    /// Arm the enabled rules, keeping the cooldown of rules that were armed before
+    /// Rules that fail to compile are left out, and the first failure is returned
+    pub fn set_rules(&mut self, rules: &[AlertRule]) -> Result<(), AlertError> {
+        let mut first_error = None;
+        let mut armed = Vec::new();
+        for rule in rules.iter().filter(|r| r.enabled) {
+            let matcher = match rule.validate() {
+                Ok(matcher) => matcher,
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                    continue;
+                }
+            };
+            let last_fired = self
+                .rules
+                .iter()
+                .find(|r| r.rule.name == rule.name)
+                .and_then(|r| r.last_fired);
+            armed.push(ArmedRule {
+                rule: rule.clone(),
+                matcher,
+                last_fired,
+            });
+        }
+        self.rules = armed;
+        first_error.map_or(Ok(()), Err)
+    }
+
+    /// Start timing stalls from now, e.g. when following starts
+    pub fn restart(&mut self, now: Instant) {
+        self.last_append = Some(now);
+    }
+
+    /// Check lines appended at `now`, the first of them at index `first_line`, along with
+    /// the error lines and all lines of the live statistics window
+    pub fn on_append<S: AsRef<str>>(
+        &mut self,
+        now: Instant,
+        file: &str,
+        first_line: u64,
+        lines: &[S],
+        (window_errors, window_lines): (u64, u64),
+    ) -> Vec<Alert> {
+        self.last_append = Some(now);
+        let last_line = (first_line + lines.len() as u64).checked_sub(1);
+        let mut alerts = Vec::new();
+        for armed in &mut self.rules {
+            if armed.cooling_down(now) {
+                continue;
+            }
+            match &armed.rule.condition {
+                AlertCondition::Pattern { pattern, .. } => {
+                    let Some(matcher) = &armed.matcher else {
+                        continue;
+                    };
+                    let Some((index, text)) = lines
+                        .iter()
+                        .map(AsRef::as_ref)
+                        .enumerate()
+                        .find(|(_, text)| matcher.is_match(text))
+                    else {
+                        continue;
+                    };
+                    let message = format!(
+                        "\"{}\" seen: {}",
+                        pattern,
+                        text.chars().take(MAX_MESSAGE_CHARS).collect::<String>()
+                    );
+                    let line = Some(first_line + index as u64);
+                    let mut alert = armed.fire(now, file, message, line);
+                    alert.text = Some(text.to_string());
+                    alerts.push(alert);
+                }
+                AlertCondition::ErrorRate {
+                    threshold,
+                    min_lines,
+                } => {
+                    if window_lines == 0 || window_lines < *min_lines {
+                        continue;
+                    }
+                    let rate = window_errors as f64 / window_lines as f64;
+                    if rate <= *threshold {
+                        continue;
+                    }
+                    let message = format!(
+                        "{:.0}% of the last {} lines are errors",
+                        rate * 100.0,
+                        window_lines
+                    );
+                    alerts.push(armed.fire(now, file, message, last_line));
+                }
+                AlertCondition::Stalled { .. } => {}
+            }
+        }
+        alerts
+    }
+
+    /// Check for stalled following at `now`; each stall fires a rule once
+    pub fn check_stalled(
+        &mut self,
+        now: Instant,
+        file: &str,
+        last_line: Option<u64>,
+    ) -> Vec<Alert> {
+        let Some(last_append) = self.last_append else {
+            return Vec::new();
+        };
+        let stalled_for = now.saturating_duration_since(last_append);
+        let mut alerts = Vec::new();
+        for armed in &mut self.rules {
+            let AlertCondition::Stalled { seconds } = armed.rule.condition else {
+                continue;
+            };
+            let reported = armed.last_fired.is_some_and(|fired| fired >= last_append);
+            if reported || stalled_for < Duration::from_secs(seconds) {
+                continue;
+            }
+            let message = format!("No new lines for {} seconds", stalled_for.as_secs());
+            alerts.push(armed.fire(now, file, message, last_line));
+        }
+        alerts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(name: &str, condition: AlertCondition) -> AlertRule {
+        AlertRule {
+            name: name.to_string(),
+            condition,
+            enabled: true,
+            notify: true,
+            cooldown_secs: 60,
+            actions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_rules_fire_with_cooldown() {
+        let start = Instant::now();
+        let mut monitor = AlertMonitor::default();
+        monitor
+            .set_rules(&[
+                rule(
+                    "oom",
+                    AlertCondition::Pattern {
+                        pattern: "(?i)out of memory".to_string(),
+                        flavor: RegexFlavor::Standard,
+                    },
+                ),
+                rule(
+                    "errors",
+                    AlertCondition::ErrorRate {
+                        threshold: 0.5,
+                        min_lines: 4,
+                    },
+                ),
+                rule("quiet", AlertCondition::Stalled { seconds: 30 }),
+            ])
+            .unwrap();
+        monitor.restart(start);
+
+        let alerts = monitor.on_append(
+            start,
+            "app.log",
+            10,
+            &["ok", "worker died: Out of memory", "out of memory again"],
+            (3, 4),
+        );
+        assert_eq!(alerts.len(), 2);
+        assert_eq!(alerts[0].rule, "oom");
+        assert_eq!(alerts[0].line, Some(11));
+        assert_eq!(
+            alerts[0].text.as_deref(),
+            Some("worker died: Out of memory")
+        );
+        assert_eq!(alerts[1].rule, "errors");
+        assert_eq!(alerts[1].line, Some(12));
+
+        // Both rules are cooling down; the stall fires once per stall
+        let later = start + Duration::from_secs(40);
+        assert!(monitor
+            .on_append(later, "app.log", 13, &["out of memory"], (4, 5))
+            .is_empty());
+        let stalled = later + Duration::from_secs(31);
+        let alerts = monitor.check_stalled(stalled, "app.log", Some(13));
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].line, Some(13));
+        assert!(monitor
+            .check_stalled(stalled + Duration::from_secs(5), "app.log", Some(13))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_invalid_rules_are_left_out() {
+        let mut monitor = AlertMonitor::default();
+        let result = monitor.set_rules(&[
+            rule(
+                "broken",
+                AlertCondition::Pattern {
+                    pattern: "(".to_string(),
+                    flavor: RegexFlavor::Standard,
+                },
+            ),
+            rule("quiet", AlertCondition::Stalled { seconds: 1 }),
+        ]);
+        assert!(matches!(result, Err(AlertError::Pattern { .. })));
+        assert_eq!(monitor.rules.len(), 1);
+        assert!(matches!(
+            rule(
+                "rate",
+                AlertCondition::ErrorRate {
+                    threshold: 2.0,
+                    min_lines: 1
+                }
+            )
+            .validate(),
+            Err(AlertError::InvalidThreshold(_))
+        ));
+    }
+}
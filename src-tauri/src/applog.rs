@@ -0,0 +1,189 @@
+use chrono::{SecondsFormat, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::sync::OnceLock;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Most entries kept; the oldest are dropped first
+pub const MAX_APP_LOGS: usize = 5_000;
+
+/// Severity of an app log entry, from most to least severe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<&Level> for LogLevel {
+    fn from(level: &Level) -> Self {
+        match *level {
+            Level::ERROR => LogLevel::Error,
+            Level::WARN => LogLevel::Warn,
+            Level::INFO => LogLevel::Info,
+            Level::DEBUG => LogLevel::Debug,
+            Level::TRACE => LogLevel::Trace,
+        }
+    }
+}
+
+/// One diagnostic message from the app itself
+#[derive(Debug, Clone, Serialize)]
+pub struct AppLogEntry {
+    /// Increases by one per entry, so callers can ask only for what they haven't seen
+    pub seq: u64,
+    /// RFC 3339 UTC time the entry was logged
+    pub time: String,
+    pub level: LogLevel,
+    /// Module that logged the entry, e.g. `log_microscope_lib::query_engine`
+    pub target: String,
+    pub message: String,
+    pub fields: BTreeMap<String, String>,
+    /// Names of the spans the entry was logged in, outermost first
+    pub spans: Vec<String>,
+}
+
+#[derive(Default)]
+struct AppLogBuffer {
+    entries: VecDeque<AppLogEntry>,
+    next_seq: u64,
+}
+
+fn buffer() -> &'static Mutex<AppLogBuffer> {
+    static BUFFER: OnceLock<Mutex<AppLogBuffer>> = OnceLock::new();
+    BUFFER.get_or_init(Default::default)
+}
+
+/// Collects an event's message and fields as text
+#[derive(Default)]
+struct FieldVisitor {
+    message: String,
+    fields: BTreeMap<String, String>,
+}
+
+impl Visit for FieldVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            self.fields
+                .insert(field.name().to_string(), value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        } else {
+            self.fields
+                .insert(field.name().to_string(), format!("{:?}", value));
+        }
+    }
+}
+
+/// Layer keeping the most recent events in memory for `get_app_logs`
+pub struct AppLogLayer;
+
+impl<S> Layer<S> for AppLogLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+        let spans = ctx
+            .event_scope(event)
+            .map(|scope| {
+                scope
+                    .from_root()
+                    .map(|span| span.name().to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let mut buffer = buffer().lock();
+        let seq = buffer.next_seq;
+        buffer.next_seq += 1;
+        if buffer.entries.len() >= MAX_APP_LOGS {
+            buffer.entries.pop_front();
+        }
+        buffer.entries.push_back(AppLogEntry {
+            seq,
+            time: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            level: metadata.level().into(),
+            target: metadata.target().to_string(),
+            message: visitor.message,
+            fields: visitor.fields,
+            spans,
+        });
+    }
+}
+
+/// Install the in-memory log sink as the global subscriber, keeping debug and above
+/// Does nothing if a subscriber is already installed
+pub fn init() {
+    let subscriber = tracing_subscriber::registry()
+        .with(LevelFilter::DEBUG)
+        .with(AppLogLayer);
+    tracing::subscriber::set_global_default(subscriber).ok();
+}
+
+/// The newest `limit` entries at `min_level` or more severe, after sequence number `since`
+pub fn entries(min_level: LogLevel, since: Option<u64>, limit: usize) -> Vec<AppLogEntry> {
+    let buffer = buffer().lock();
+    let mut entries: Vec<AppLogEntry> = buffer
+        .entries
+        .iter()
+        .rev()
+        .filter(|e| e.level <= min_level && since.is_none_or(|since| e.seq > since))
+        .take(limit)
+        .cloned()
+        .collect();
+    entries.reverse();
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::registry;
+
+    #[test]
+    fn test_app_log_layer() {
+        let subscriber = registry().with(AppLogLayer);
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("applog_test_open");
+            let _entered = span.enter();
+            tracing::debug!(format = "csv", lines = 20, "applog test detected");
+            tracing::warn!("applog test failed");
+        });
+
+        let debug: Vec<_> = entries(LogLevel::Trace, None, usize::MAX)
+            .into_iter()
+            .filter(|e| e.message.starts_with("applog test"))
+            .collect();
+        assert_eq!(debug.len(), 2);
+        assert_eq!(debug[0].level, LogLevel::Debug);
+        assert_eq!(debug[0].fields["format"], "csv");
+        assert_eq!(debug[0].fields["lines"], "20");
+        assert_eq!(debug[0].spans, vec!["applog_test_open".to_string()]);
+        assert!(debug[1].seq > debug[0].seq);
+
+        let warnings: Vec<_> = entries(LogLevel::Warn, Some(debug[0].seq), usize::MAX)
+            .into_iter()
+            .filter(|e| e.message.starts_with("applog test"))
+            .collect();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].message, "applog test failed");
+    }
+}
@@ -0,0 +1,284 @@
+use crate::indexer::{IndexStats, IndexerError, LogFile};
+use crate::query_engine::{QueryEngine, QueryError};
+use chrono::{DateTime, SecondsFormat};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use thiserror::Error;
+
+/// Largest synthetic file a benchmark may generate
+pub const MAX_BENCH_BYTES: u64 = 4 * 1024 * 1024 * 1024;
+
+/// Errors that can occur while benchmarking
+#[derive(Error, Debug)]
+pub enum BenchError {
+    #[error("Failed to write synthetic log: {0}")]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Indexer(#[from] IndexerError),
+    #[error(transparent)]
+    Query(#[from] QueryError),
+}
+
+/// Line layout of the generated log
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogShape {
+    #[default]
+    Plain,
+    Json,
+    Csv,
+}
+
+/// What to generate and measure
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BenchmarkOptions {
+    /// Size of the synthetic file, capped at `MAX_BENCH_BYTES`
+    pub size_bytes: u64,
+    pub shape: LogShape,
+    /// Seed for the generator, so runs on different machines measure the same file
+    pub seed: u64,
+    pub search_pattern: String,
+    pub sql: String,
+}
+
+impl Default for BenchmarkOptions {
+    fn default() -> Self {
+        BenchmarkOptions {
+            size_bytes: 64 * 1024 * 1024,
+            shape: LogShape::Plain,
+            seed: 1,
+            search_pattern: "ERROR".to_string(),
+            sql: "SELECT COUNT(*) FROM logs WHERE line LIKE '%ERROR%'".to_string(),
+        }
+    }
+}
+
+/// Timing of one benchmark phase
+#[derive(Debug, Clone, Serialize)]
+pub struct PhaseResult {
+    pub name: String,
+    pub millis: f64,
+    pub mb_per_sec: f64,
+    pub lines_per_sec: f64,
+    /// Phase-specific count: matches for search, result rows for SQL
+    pub items: u64,
+}
+
+/// Machine details that explain differences between reports
+#[derive(Debug, Clone, Serialize)]
+pub struct MachineInfo {
+    pub os: String,
+    pub arch: String,
+    pub cpus: usize,
+    /// Threads used for indexing and search
+    pub rayon_threads: usize,
+    pub app_version: String,
+}
+
+/// Results of a benchmark run
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchmarkReport {
+    pub options: BenchmarkOptions,
+    pub machine: MachineInfo,
+    pub bytes: u64,
+    pub lines: u64,
+    pub phases: Vec<PhaseResult>,
+    /// Chunking and thread use of the index phase
+    pub index: Option<IndexStats>,
+}
+
+/// Small deterministic generator, so files don't depend on a random crate's algorithm
+struct XorShift(u64);
+
+impl XorShift {
+    fn new(seed: u64) -> Self {
+        XorShift(seed.max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+}
+
+const MESSAGES: [&str; 6] = [
+    "request completed",
+    "cache miss for key",
+    "connection reset by peer",
+    "retrying upstream call",
+    "session started",
+    "payload validated",
+];
+
+/// Roughly 1% errors and 5% warnings, like a typical service log
+fn level(rng: &mut XorShift) -> &'static str {
+    match rng.below(100) {
+        0 => "ERROR",
+        1..=5 => "WARN",
+        6..=59 => "INFO",
+        _ => "DEBUG",
+    }
+}
+
+/// Write a synthetic log of about `size` bytes, returning its exact size
+pub fn generate(path: &Path, shape: LogShape, size: u64, seed: u64) -> io::Result<u64> {
+    let mut rng = XorShift::new(seed);
+    let mut out = BufWriter::new(File::create(path)?);
+    let mut written = 0u64;
+    let mut line = String::with_capacity(256);
+    if shape == LogShape::Csv {
+        line.push_str("timestamp,level,thread,request_id,duration_ms,message\n");
+    }
+    let mut millis = 1_700_000_000_000i64;
+    while written < size {
+        millis += rng.below(50) as i64;
+        let ts = DateTime::from_timestamp_millis(millis)
+            .unwrap_or_default()
+            .to_rfc3339_opts(SecondsFormat::Millis, true);
+        let level = level(&mut rng);
+        let thread = rng.below(16);
+        let id = rng.below(1_000_000);
+        let duration = rng.below(2_000);
+        let message = MESSAGES[rng.below(MESSAGES.len() as u64) as usize];
+        match shape {
+            LogShape::Plain => write!(
+                line,
+                "{} {:<5} [worker-{}] {} id={} duration={}ms",
+                ts, level, thread, message, id, duration
+            ),
+            LogShape::Json => write!(
+                line,
+                "{{\"ts\":\"{}\",\"level\":\"{}\",\"thread\":\"worker-{}\",\"msg\":\"{}\",\"id\":{},\"duration_ms\":{}}}",
+                ts, level, thread, message, id, duration
+            ),
+            LogShape::Csv => write!(
+                line,
+                "{},{},worker-{},{},{},{}",
+                ts, level, thread, id, duration, message
+            ),
+        }
+        .ok();
+        line.push('\n');
+        out.write_all(line.as_bytes())?;
+        written += line.len() as u64;
+        line.clear();
+    }
+    out.flush()?;
+    Ok(written)
+}
+
+fn phase(name: &str, start: Instant, bytes: u64, lines: u64, items: u64) -> PhaseResult {
+    let secs = start.elapsed().as_secs_f64().max(1e-9);
+    PhaseResult {
+        name: name.to_string(),
+        millis: secs * 1000.0,
+        mb_per_sec: bytes as f64 / (1024.0 * 1024.0) / secs,
+        lines_per_sec: lines as f64 / secs,
+        items,
+    }
+}
+
+/// Removes the synthetic file however the run ends
+struct TempLog(PathBuf);
+
+impl Drop for TempLog {
+    fn drop(&mut self) {
+        std::fs::remove_file(&self.0).ok();
+    }
+}
+
+/// Generate a synthetic log and time indexing, regex search and SQL over it
+/// Runs on a separate file and query engine, so the open file is left untouched
+pub async fn run(mut options: BenchmarkOptions) -> Result<BenchmarkReport, BenchError> {
+    options.size_bytes = options.size_bytes.clamp(1, MAX_BENCH_BYTES);
+    let temp = TempLog(std::env::temp_dir().join(format!(
+        "log-microscope-bench-{}-{}.log",
+        std::process::id(),
+        options.seed
+    )));
+    let mut phases = Vec::new();
+
+    let start = Instant::now();
+    let bytes = generate(&temp.0, options.shape, options.size_bytes, options.seed)?;
+    phases.push(phase("generate", start, bytes, 0, 0));
+
+    let start = Instant::now();
+    let file = LogFile::open(&temp.0)?;
+    let lines = file.line_count();
+    phases.push(phase("index", start, bytes, lines, lines));
+    let index = file.index_stats().cloned();
+
+    let start = Instant::now();
+    let matches = file.search(&options.search_pattern, usize::MAX)?;
+    phases.push(phase("search", start, bytes, lines, matches.len() as u64));
+    drop(file);
+
+    let engine = QueryEngine::new();
+    let start = Instant::now();
+    engine.register_table(&temp.0, "logs").await?;
+    phases.push(phase("sql_load", start, bytes, lines, lines));
+
+    let start = Instant::now();
+    let result = engine.execute_sql(&options.sql).await?;
+    phases.push(phase(
+        "sql_query",
+        start,
+        bytes,
+        lines,
+        result.row_count as u64,
+    ));
+
+    Ok(BenchmarkReport {
+        options,
+        machine: MachineInfo {
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            cpus: num_cpus::get(),
+            rayon_threads: rayon::current_num_threads(),
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+        },
+        bytes,
+        lines,
+        phases,
+        index,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_run_benchmark() {
+        for shape in [LogShape::Plain, LogShape::Json, LogShape::Csv] {
+            let options = BenchmarkOptions {
+                size_bytes: 100_000,
+                shape,
+                seed: 7 + shape as u64,
+                ..Default::default()
+            };
+            let report = run(options).await.unwrap();
+            assert!(report.bytes >= 100_000);
+            assert!(report.lines > 500);
+            let names: Vec<&str> = report.phases.iter().map(|p| p.name.as_str()).collect();
+            assert_eq!(
+                names,
+                ["generate", "index", "search", "sql_load", "sql_query"]
+            );
+            assert!(report.phases[2].items > 0);
+            assert_eq!(report.phases[4].items, 1);
+            assert!(report.index.is_some_and(|index| index.chunks >= 1));
+        }
+    }
+}
@@ -0,0 +1,360 @@
+use crate::actions::AlertAction;
+use crate::alerts::{AlertError, AlertRule, AlertStore};
+use crate::dashboards::{Dashboard, DashboardStore};
+use crate::parsers::library::{ParseProfile, ProfileLibrary};
+use crate::parsers::schema::{OverrideStore, SchemaOverride};
+use crate::views::{is_identifier, SavedView, ViewStore};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+use thiserror::Error;
+
+/// Version written to bundles; bundles of a newer version are refused
+pub const BUNDLE_VERSION: u32 = 1;
+
+/// Errors that can occur reading or applying a configuration bundle
+#[derive(Debug, Error)]
+pub enum BundleError {
+    #[error("Invalid JSON bundle: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Invalid TOML bundle: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("Failed to write TOML: {0}")]
+    TomlWrite(#[from] toml::ser::Error),
+    #[error("Bundle version {0} is newer than this app supports")]
+    UnsupportedVersion(u32),
+    #[error("Parse profile \"{name}\": {message}")]
+    Profile { name: String, message: String },
+    #[error("Invalid saved view name \"{0}\": use letters, digits and underscores")]
+    ViewName(String),
+    #[error(transparent)]
+    Alert(#[from] AlertError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// File format of a bundle, picked by extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BundleFormat {
+    Json,
+    Toml,
+}
+
+impl BundleFormat {
+    /// TOML for `.toml` files, JSON otherwise
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension() {
+            Some(ext) if ext.eq_ignore_ascii_case("toml") => BundleFormat::Toml,
+            _ => BundleFormat::Json,
+        }
+    }
+}
+
+/// How an imported bundle combines with the configuration already there
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportMode {
+    /// Add the bundle's entries, replacing those with the same name
+    #[default]
+    Merge,
+    /// Drop the configuration of every kind first
+    Replace,
+}
+
+/// The app's shareable configuration in one file
+/// Approved directories are left out on purpose, so a shared bundle can't widen access
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ConfigBundle {
+    pub version: u32,
+    #[serde(default)]
+    pub parse_profiles: Vec<ParseProfile>,
+    /// Keyed by file or format, as in the schema override store
+    #[serde(default)]
+    pub schema_overrides: BTreeMap<String, SchemaOverride>,
+    #[serde(default)]
+    pub saved_views: Vec<SavedView>,
+    #[serde(default)]
+    pub alert_rules: Vec<AlertRule>,
+    /// Keyed by parse profile or format, as in the dashboard store
+    #[serde(default)]
+    pub dashboards: BTreeMap<String, Vec<Dashboard>>,
+    /// Settings the UI keeps itself, such as highlight rules and saved searches, carried
+    /// through as is
+    #[serde(default)]
+    pub ui: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Entries of each kind an import brought in
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportSummary {
+    pub parse_profiles: usize,
+    pub schema_overrides: usize,
+    pub saved_views: usize,
+    pub alert_rules: usize,
+    pub dashboards: usize,
+    /// The bundle's UI settings, for the UI to apply
+    pub ui: serde_json::Map<String, serde_json::Value>,
+    /// Webhooks and commands of the bundle's alert rules that weren't saved, since they
+    /// weren't allowed; the rules were saved without them
+    pub skipped_actions: Vec<SkippedAction>,
+}
+
+/// An alert action left out of an import, for the user to review before allowing it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SkippedAction {
+    pub rule: String,
+    pub action: AlertAction,
+}
+
+impl ConfigBundle {
+    /// Gather the configuration saved in a config directory
+    pub fn collect(
+        dir: &Path,
+        ui: serde_json::Map<String, serde_json::Value>,
+    ) -> Result<Self, BundleError> {
+        Ok(ConfigBundle {
+            version: BUNDLE_VERSION,
+            parse_profiles: ProfileLibrary::load(dir)?.profiles().to_vec(),
+            schema_overrides: OverrideStore::load(dir)?.overrides().clone(),
+            saved_views: ViewStore::load(dir)?.views().to_vec(),
+            alert_rules: AlertStore::load(dir)?.rules().to_vec(),
+            dashboards: DashboardStore::load(dir)?.all().clone(),
+            ui,
+        })
+    }
+
+    pub fn to_text(&self, format: BundleFormat) -> Result<String, BundleError> {
+        Ok(match format {
+            BundleFormat::Json => serde_json::to_string_pretty(self)?,
+            BundleFormat::Toml => toml::to_string_pretty(self)?,
+        })
+    }
+
+    /// Read a bundle and check everything in it, so a bad bundle changes nothing
+    pub fn parse(text: &str, format: BundleFormat) -> Result<Self, BundleError> {
+        let bundle: ConfigBundle = match format {
+            BundleFormat::Json => serde_json::from_str(text)?,
+            BundleFormat::Toml => toml::from_str(text)?,
+        };
+        if bundle.version > BUNDLE_VERSION {
+            return Err(BundleError::UnsupportedVersion(bundle.version));
+        }
+        for profile in &bundle.parse_profiles {
+            profile.validate().map_err(|message| BundleError::Profile {
+                name: profile.name.clone(),
+                message,
+            })?;
+        }
+        if let Some(view) = bundle.saved_views.iter().find(|v| !is_identifier(&v.name)) {
+            return Err(BundleError::ViewName(view.name.clone()));
+        }
+        for rule in &bundle.alert_rules {
+            rule.validate()?;
+        }
+        Ok(bundle)
+    }
+
+    /// Take the webhooks and commands off the bundle's alert rules, so what it runs when an
+    /// alert fires is never saved without the user seeing it
+    pub fn strip_actions(&mut self) -> Vec<SkippedAction> {
+        self.alert_rules
+            .iter_mut()
+            .flat_map(|rule| {
+                let name = rule.name.clone();
+                std::mem::take(&mut rule.actions)
+                    .into_iter()
+                    .map(move |action| SkippedAction {
+                        rule: name.clone(),
+                        action,
+                    })
+            })
+            .collect()
+    }
+
+    /// Save the bundle's entries into the stores of a config directory; alert actions are
+    /// left out unless `allow_actions`
+    pub fn apply(
+        mut self,
+        dir: &Path,
+        mode: ImportMode,
+        allow_actions: bool,
+    ) -> Result<ImportSummary, BundleError> {
+        let replace = mode == ImportMode::Replace;
+        let skipped_actions = if allow_actions {
+            Vec::new()
+        } else {
+            self.strip_actions()
+        };
+        let summary = ImportSummary {
+            parse_profiles: self.parse_profiles.len(),
+            schema_overrides: self.schema_overrides.len(),
+            saved_views: self.saved_views.len(),
+            alert_rules: self.alert_rules.len(),
+            dashboards: self.dashboards.values().map(Vec::len).sum(),
+            ui: self.ui,
+            skipped_actions,
+        };
+
+        let mut library = ProfileLibrary::load(dir)?;
+        if replace {
+            let names: Vec<_> = library.profiles().iter().map(|p| p.name.clone()).collect();
+            for name in names {
+                library.remove(&name);
+            }
+        }
+        for profile in self.parse_profiles {
+            library.upsert(profile);
+        }
+        library.save()?;
+
+        let mut overrides = OverrideStore::load(dir)?;
+        if replace {
+            let keys: Vec<_> = overrides.overrides().keys().cloned().collect();
+            for key in keys {
+                overrides.set(key, None);
+            }
+        }
+        for (key, schema_override) in self.schema_overrides {
+            overrides.set(key, Some(schema_override));
+        }
+        overrides.save()?;
+
+        let mut views = ViewStore::load(dir)?;
+        if replace {
+            let names: Vec<_> = views.views().iter().map(|v| v.name.clone()).collect();
+            for name in names {
+                views.remove(&name);
+            }
+        }
+        for view in self.saved_views {
+            views.upsert(view);
+        }
+        views.save()?;
+
+        let mut alerts = AlertStore::load(dir)?;
+        if replace {
+            let names: Vec<_> = alerts.rules().iter().map(|r| r.name.clone()).collect();
+            for name in names {
+                alerts.remove(&name);
+            }
+        }
+        for rule in self.alert_rules {
+            alerts.upsert(rule);
+        }
+        alerts.save()?;
+
+        let mut dashboards = DashboardStore::load(dir)?;
+        if replace {
+            let existing: Vec<_> = dashboards
+                .all()
+                .iter()
+                .flat_map(|(scope, list)| list.iter().map(|d| (scope.clone(), d.name.clone())))
+                .collect();
+            for (scope, name) in existing {
+                dashboards.remove(&scope, &name);
+            }
+        }
+        for (scope, list) in self.dashboards {
+            for dashboard in list {
+                dashboards.upsert(&scope, dashboard);
+            }
+        }
+        dashboards.save()?;
+
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sample() -> ConfigBundle {
+        serde_json::from_value(json!({
+            "version": 1,
+            "parse_profiles": [{
+                "name": "nginx",
+                "globs": ["access.log*"],
+                "parser": {"type": "regex", "pattern": r"(?P<ip>\S+) (?P<status>\d+)"}
+            }],
+            "saved_views": [{"name": "errors", "sql": "SELECT * FROM logs WHERE line LIKE '%ERROR%'"}],
+            "alert_rules": [{
+                "name": "oom",
+                "condition": {"kind": "pattern", "pattern": "out of memory"},
+                "actions": [{"kind": "command", "command": "systemctl restart app"}]
+            }],
+            "dashboards": {"profile:nginx": [{"name": "traffic"}]},
+            "ui": {"highlights": [{"pattern": "ERROR", "color": "#ff0000"}]}
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_round_trip_through_toml_and_import() {
+        let bundle = sample();
+        let text = bundle.to_text(BundleFormat::Toml).unwrap();
+        assert_eq!(
+            ConfigBundle::parse(&text, BundleFormat::Toml).unwrap(),
+            bundle
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut views = ViewStore::load(dir.path()).unwrap();
+        views.upsert(SavedView {
+            name: "old".to_string(),
+            sql: "SELECT 1".to_string(),
+        });
+        views.save().unwrap();
+
+        // Commands come in only when allowed
+        let summary = bundle
+            .clone()
+            .apply(dir.path(), ImportMode::Merge, false)
+            .unwrap();
+        assert_eq!(summary.dashboards, 1);
+        assert_eq!(
+            summary.skipped_actions,
+            [SkippedAction {
+                rule: "oom".to_string(),
+                action: bundle.alert_rules[0].actions[0].clone(),
+            }]
+        );
+        assert_eq!(ViewStore::load(dir.path()).unwrap().views().len(), 2);
+        let collected = ConfigBundle::collect(dir.path(), bundle.ui.clone()).unwrap();
+        assert_eq!(collected.parse_profiles, bundle.parse_profiles);
+        assert!(collected.alert_rules[0].actions.is_empty());
+
+        let summary = bundle
+            .clone()
+            .apply(dir.path(), ImportMode::Replace, true)
+            .unwrap();
+        assert!(summary.skipped_actions.is_empty());
+        let collected = ConfigBundle::collect(dir.path(), bundle.ui.clone()).unwrap();
+        assert_eq!(collected.alert_rules, bundle.alert_rules);
+        assert_eq!(
+            ViewStore::load(dir.path()).unwrap().views(),
+            &bundle.saved_views[..]
+        );
+    }
+
+    #[test]
+    fn test_invalid_bundles_are_refused() {
+        let mut bundle = sample();
+        bundle.version = BUNDLE_VERSION + 1;
+        let text = bundle.to_text(BundleFormat::Json).unwrap();
+        assert!(matches!(
+            ConfigBundle::parse(&text, BundleFormat::Json),
+            Err(BundleError::UnsupportedVersion(_))
+        ));
+
+        let mut bundle = sample();
+        bundle.saved_views[0].name = "not a name".to_string();
+        let text = bundle.to_text(BundleFormat::Json).unwrap();
+        assert!(matches!(
+            ConfigBundle::parse(&text, BundleFormat::Json),
+            Err(BundleError::ViewName(_))
+        ));
+    }
+}
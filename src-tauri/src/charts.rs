@@ -0,0 +1,389 @@
+use crate::field_search::{find_column, quote_identifier, FieldQuery, FieldQueryError};
+use crate::parsers::{Column, ColumnType};
+use crate::query_engine::QueryResult;
+use crate::snippets::ColumnRole;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use thiserror::Error;
+
+/// Series kept by default when a chart is split by a field
+pub const DEFAULT_MAX_SERIES: usize = 10;
+
+/// Errors that can occur compiling a chart spec
+#[derive(Debug, Error, PartialEq)]
+pub enum ChartError {
+    #[error(transparent)]
+    Field(#[from] FieldQueryError),
+    #[error("Field \"{0}\" isn't numeric")]
+    NotNumeric(String),
+    #[error("Field \"{0}\" isn't a timestamp")]
+    NotTimestamp(String),
+    #[error("No timestamp field to bucket by")]
+    NoTimestamp,
+    #[error("Invalid time bucket \"{0}\": use a number and s, m, h or d, e.g. 5m")]
+    InvalidBucket(String),
+    #[error("Percentile must be between 0 and 1, not {0}")]
+    InvalidPercentile(f64),
+}
+
+/// Value a chart plots for each point
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum Metric {
+    Count,
+    /// Number of distinct values of a field
+    Distinct {
+        field: String,
+    },
+    Sum {
+        field: String,
+    },
+    Avg {
+        field: String,
+    },
+    Min {
+        field: String,
+    },
+    Max {
+        field: String,
+    },
+    /// Approximate percentile of a field, e.g. 0.95
+    Percentile {
+        field: String,
+        percentile: f64,
+    },
+}
+
+impl Metric {
+    /// Name of the single series of an unsplit chart
+    fn label(&self) -> String {
+        match self {
+            Metric::Count => "count".to_string(),
+            Metric::Distinct { field } => format!("distinct({})", field),
+            Metric::Sum { field } => format!("sum({})", field),
+            Metric::Avg { field } => format!("avg({})", field),
+            Metric::Min { field } => format!("min({})", field),
+            Metric::Max { field } => format!("max({})", field),
+            Metric::Percentile { field, percentile } => {
+                format!("percentile({}, {})", field, percentile)
+            }
+        }
+    }
+
+    /// SQL aggregate computing the metric over the given columns
+    pub(crate) fn sql(&self, columns: &[Column]) -> Result<String, ChartError> {
+        let numeric = |field: &str| -> Result<String, ChartError> {
+            let column = find_column(columns, field)?;
+            match column.column_type {
+                ColumnType::Int64 | ColumnType::Float64 => Ok(quote_identifier(&column.name)),
+                _ => Err(ChartError::NotNumeric(column.name.clone())),
+            }
+        };
+        Ok(match self {
+            Metric::Count => "COUNT(*)".to_string(),
+            Metric::Distinct { field } => format!(
+                "COUNT(DISTINCT {})",
+                quote_identifier(&find_column(columns, field)?.name)
+            ),
+            Metric::Sum { field } => format!("SUM({})", numeric(field)?),
+            Metric::Avg { field } => format!("AVG({})", numeric(field)?),
+            Metric::Min { field } => format!("MIN({})", numeric(field)?),
+            Metric::Max { field } => format!("MAX({})", numeric(field)?),
+            Metric::Percentile { field, percentile } => {
+                if !(0.0..=1.0).contains(percentile) {
+                    return Err(ChartError::InvalidPercentile(*percentile));
+                }
+                format!(
+                    "approx_percentile_cont(CAST({} AS DOUBLE), {})",
+                    numeric(field)?,
+                    percentile
+                )
+            }
+        })
+    }
+}
+
+/// What to chart: a metric over the rows matching `filters`, along time buckets, split into
+/// a series per value of `group_by`, or both
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChartSpec {
+    pub metric: Metric,
+    #[serde(default)]
+    pub group_by: Option<String>,
+    /// Bucket size such as `30s`, `5m`, `1h` or `1d`
+    #[serde(default)]
+    pub bucket: Option<String>,
+    /// Field bucketed by; the table's timestamp column when not given
+    #[serde(default)]
+    pub time_field: Option<String>,
+    /// Field query selecting the rows, such as `level:ERROR status:>=500`
+    #[serde(default)]
+    pub filters: Option<String>,
+    /// Largest series kept when split by `group_by`, by total
+    #[serde(default)]
+    pub max_series: Option<usize>,
+}
+
+/// Chart results pivoted for plotting: one array per series, aligned with `x`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChartData {
+    /// Start of each bucket in epoch milliseconds when bucketed, otherwise the `group_by`
+    /// values, or a single empty label for a chart with neither
+    pub x: Vec<serde_json::Value>,
+    pub series: Vec<Series>,
+    /// Series left out beyond `max_series`
+    pub omitted_series: usize,
+    pub sql: String,
+    /// Whether the table was still being filled by a background parse
+    pub partial: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Series {
+    pub name: String,
+    /// None where the series has no rows, except for counts, which are 0 there
+    pub values: Vec<Option<f64>>,
+}
+
+/// Seconds in a bucket size such as `5m`
+fn bucket_seconds(bucket: &str) -> Result<u64, ChartError> {
+    let invalid = || ChartError::InvalidBucket(bucket.to_string());
+    let bucket = bucket.trim();
+    let split = bucket
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(invalid)?;
+    let (count, unit) = bucket.split_at(split);
+    let count: u64 = count.parse().map_err(|_| invalid())?;
+    let unit = match unit.trim() {
+        "s" | "sec" => 1,
+        "m" | "min" => 60,
+        "h" => 3600,
+        "d" => 86_400,
+        _ => return Err(invalid()),
+    };
+    match count {
+        0 => Err(invalid()),
+        count => count.checked_mul(unit).ok_or_else(invalid),
+    }
+}
+
+impl ChartSpec {
+    /// SQL of the chart over a table, as rows of `x`, `series` and `value` ordered by `x`
+    pub fn to_sql(&self, table: &str, columns: &[Column]) -> Result<String, ChartError> {
+        let x = match &self.bucket {
+            Some(bucket) => {
+                let seconds = bucket_seconds(bucket)?;
+                let time = match &self.time_field {
+                    Some(field) => find_column(columns, field)?,
+                    None => ColumnRole::Timestamp
+                        .find(columns)
+                        .ok_or(ChartError::NoTimestamp)?,
+                };
+                if time.column_type != ColumnType::Timestamp {
+                    return Err(ChartError::NotTimestamp(time.name.clone()));
+                }
+                format!(
+                    "to_unixtime(date_bin(INTERVAL '{} seconds', {}, \
+                     TIMESTAMP '1970-01-01T00:00:00')) * 1000",
+                    seconds,
+                    quote_identifier(&time.name)
+                )
+            }
+            None => match &self.group_by {
+                Some(field) => quote_identifier(&find_column(columns, field)?.name),
+                None => "''".to_string(),
+            },
+        };
+        // Without buckets the groups are the x axis, leaving a single series
+        let series = match (&self.bucket, &self.group_by) {
+            (Some(_), Some(field)) => format!(
+                "CAST({} AS VARCHAR)",
+                quote_identifier(&find_column(columns, field)?.name)
+            ),
+            _ => "''".to_string(),
+        };
+        let filters = match self.filters.as_deref().map(str::trim) {
+            Some(filters) if !filters.is_empty() => {
+                format!(" WHERE {}", FieldQuery::parse(filters)?.to_sql(columns)?)
+            }
+            _ => String::new(),
+        };
+        let order = match (&self.bucket, &self.group_by) {
+            // Categories come largest first, like a bar chart would show them
+            (None, Some(_)) => "value DESC NULLS LAST, x",
+            _ => "x",
+        };
+        Ok(format!(
+            "SELECT {} AS x, {} AS series, {} AS value FROM {}{} GROUP BY 1, 2 ORDER BY {}",
+            x,
+            series,
+            self.metric.sql(columns)?,
+            table,
+            filters,
+            order
+        ))
+    }
+
+    /// Pivot the rows of the chart's SQL into series aligned with the x axis
+    pub fn pivot(&self, result: &QueryResult, sql: String) -> ChartData {
+        let mut x: Vec<serde_json::Value> = Vec::new();
+        let mut x_index: HashMap<String, usize> = HashMap::new();
+        let mut cells: BTreeMap<String, HashMap<usize, f64>> = BTreeMap::new();
+        for row in &result.rows {
+            let [x_value, series, value] = row.as_slice() else {
+                continue;
+            };
+            let next = x.len();
+            let index = *x_index.entry(x_value.to_string()).or_insert(next);
+            if index == next {
+                x.push(x_value.clone());
+            }
+            let name = match series {
+                _ if self.bucket.is_none() || self.group_by.is_none() => self.metric.label(),
+                serde_json::Value::String(s) => s.clone(),
+                serde_json::Value::Null => "(none)".to_string(),
+                other => other.to_string(),
+            };
+            if let Some(value) = value.as_f64() {
+                cells.entry(name).or_default().insert(index, value);
+            }
+        }
+
+        // Keep the largest series, in order of size
+        let mut totals: Vec<(String, HashMap<usize, f64>, f64)> = cells
+            .into_iter()
+            .map(|(name, values)| {
+                let total = values.values().map(|v| v.abs()).sum();
+                (name, values, total)
+            })
+            .collect();
+        totals.sort_by(|a, b| b.2.total_cmp(&a.2).then_with(|| a.0.cmp(&b.0)));
+        let max_series = self.max_series.unwrap_or(DEFAULT_MAX_SERIES).max(1);
+        let omitted_series = totals.len().saturating_sub(max_series);
+        let missing = match self.metric {
+            Metric::Count => Some(0.0),
+            _ => None,
+        };
+        let series = totals
+            .into_iter()
+            .take(max_series)
+            .map(|(name, values, _)| Series {
+                name,
+                values: (0..x.len())
+                    .map(|i| values.get(&i).copied().or(missing))
+                    .collect(),
+            })
+            .collect();
+        ChartData {
+            x,
+            series,
+            omitted_series,
+            sql,
+            partial: result.partial,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn columns() -> Vec<Column> {
+        [
+            ("time", ColumnType::Timestamp),
+            ("level", ColumnType::Utf8),
+            ("status", ColumnType::Int64),
+            ("duration", ColumnType::Float64),
+        ]
+        .iter()
+        .map(|&(name, column_type)| Column {
+            name: name.to_string(),
+            column_type,
+        })
+        .collect()
+    }
+
+    fn spec(value: serde_json::Value) -> ChartSpec {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_chart_spec_compiles_to_sql() {
+        let chart = spec(json!({
+            "metric": {"op": "avg", "field": "duration"},
+            "group_by": "level",
+            "bucket": "5m",
+            "filters": "status:>=500"
+        }));
+        assert_eq!(
+            chart.to_sql("parsed", &columns()).unwrap(),
+            "SELECT to_unixtime(date_bin(INTERVAL '300 seconds', \"time\", \
+             TIMESTAMP '1970-01-01T00:00:00')) * 1000 AS x, \
+             CAST(\"level\" AS VARCHAR) AS series, AVG(\"duration\") AS value \
+             FROM parsed WHERE \"status\" >= 500 GROUP BY 1, 2 ORDER BY x"
+        );
+        let chart = spec(json!({"metric": {"op": "count"}, "group_by": "status"}));
+        assert_eq!(
+            chart.to_sql("parsed", &columns()).unwrap(),
+            "SELECT \"status\" AS x, '' AS series, COUNT(*) AS value FROM parsed \
+             GROUP BY 1, 2 ORDER BY value DESC NULLS LAST, x"
+        );
+
+        let error = |value| spec(value).to_sql("parsed", &columns()).unwrap_err();
+        assert_eq!(
+            error(json!({"metric": {"op": "sum", "field": "level"}})),
+            ChartError::NotNumeric("level".to_string())
+        );
+        assert_eq!(
+            error(json!({"metric": {"op": "count"}, "bucket": "5 weeks"})),
+            ChartError::InvalidBucket("5 weeks".to_string())
+        );
+        assert_eq!(
+            error(json!({"metric": {"op": "count"}, "bucket": "1h", "time_field": "level"})),
+            ChartError::NotTimestamp("level".to_string())
+        );
+    }
+
+    #[test]
+    fn test_chart_rows_are_pivoted_into_series() {
+        let chart = spec(json!({
+            "metric": {"op": "count"},
+            "group_by": "level",
+            "bucket": "1m",
+            "max_series": 2
+        }));
+        let result = QueryResult {
+            columns: vec!["x".into(), "series".into(), "value".into()],
+            rows: vec![
+                vec![json!(0), json!("INFO"), json!(5)],
+                vec![json!(0), json!("ERROR"), json!(1)],
+                vec![json!(60000), json!("INFO"), json!(7)],
+                vec![json!(60000), json!("WARN"), json!(3)],
+                vec![json!(120000), json!("ERROR"), json!(2)],
+            ],
+            row_count: 5,
+            partial: false,
+            sample: None,
+            stats: None,
+            errors: Vec::new(),
+            result_id: None,
+        };
+        let data = chart.pivot(&result, String::new());
+        assert_eq!(data.x, vec![json!(0), json!(60000), json!(120000)]);
+        assert_eq!(
+            data.series,
+            vec![
+                Series {
+                    name: "INFO".to_string(),
+                    values: vec![Some(5.0), Some(7.0), Some(0.0)],
+                },
+                Series {
+                    name: "ERROR".to_string(),
+                    values: vec![Some(1.0), Some(0.0), Some(2.0)],
+                },
+            ]
+        );
+        assert_eq!(data.omitted_series, 1);
+    }
+}
@@ -0,0 +1,210 @@
+use crate::field_search::quote_identifier;
+use crate::parsers::{Column, ColumnType};
+use crate::query_engine::{QueryEngine, QueryError};
+use futures::StreamExt;
+use serde::Serialize;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use thiserror::Error;
+
+/// Table a columnar file's rows are registered as, with their own types
+pub const COLUMNAR_TABLE: &str = "columnar";
+
+/// Text columns shown alone in the line view by default, as they hold exported log lines
+const LINE_COLUMNS: [&str; 2] = ["line", "raw_line"];
+
+/// Errors that can occur building the line view of a columnar file
+#[derive(Debug, Error, PartialEq)]
+pub enum ColumnarError {
+    #[error("No column named \"{0}\"")]
+    UnknownColumn(String),
+    #[error("No columns to show")]
+    NoColumns,
+}
+
+/// Columnar file formats opened with DataFusion's readers instead of being indexed as text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ColumnarFormat {
+    Parquet,
+    /// Arrow IPC file, also known as Feather v2
+    Arrow,
+}
+
+impl ColumnarFormat {
+    /// Detect a columnar file from its magic bytes, so extensions don't matter
+    pub fn detect(path: &Path) -> Option<Self> {
+        let mut magic = [0u8; 6];
+        File::open(path).ok()?.read_exact(&mut magic).ok()?;
+        if magic.starts_with(b"PAR1") {
+            Some(ColumnarFormat::Parquet)
+        } else if magic.starts_with(b"ARROW1") {
+            Some(ColumnarFormat::Arrow)
+        } else {
+            None
+        }
+    }
+}
+
+/// Columns shown in the line view: the requested ones, in their order, or by default a
+/// `line` or `raw_line` text column when there is one, and every column otherwise
+pub fn projection(
+    columns: &[Column],
+    requested: Option<&[String]>,
+) -> Result<Vec<String>, ColumnarError> {
+    let projection = match requested {
+        Some(requested) => requested
+            .iter()
+            .map(|name| {
+                columns
+                    .iter()
+                    .find(|c| &c.name == name)
+                    .or_else(|| columns.iter().find(|c| c.name.eq_ignore_ascii_case(name)))
+                    .map(|c| c.name.clone())
+                    .ok_or_else(|| ColumnarError::UnknownColumn(name.clone()))
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        None => LINE_COLUMNS
+            .iter()
+            .find_map(|name| {
+                columns
+                    .iter()
+                    .find(|c| c.name == *name && c.column_type == ColumnType::Utf8)
+            })
+            .map_or_else(
+                || columns.iter().map(|c| c.name.clone()).collect(),
+                |c| vec![c.name.clone()],
+            ),
+    };
+    if projection.is_empty() {
+        return Err(ColumnarError::NoColumns);
+    }
+    Ok(projection)
+}
+
+/// Text of the line view of the `columnar` table's projected columns, in the file's row
+/// order. Rows are read a batch at a time, so only the text is held in full
+pub async fn read_lines(
+    engine: &QueryEngine,
+    projection: &[String],
+) -> Result<Vec<u8>, QueryError> {
+    let select = projection
+        .iter()
+        .map(|column| quote_identifier(column))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let (_, mut stream) = engine
+        .query_stream_in_order(&format!("SELECT {} FROM {}", select, COLUMNAR_TABLE))
+        .await?;
+    let mut text = Vec::new();
+    while let Some(batch) = stream.next().await {
+        let (columns, rows, _) = QueryEngine::result_rows(&[batch?]);
+        render_lines(&columns, &rows, &mut text);
+    }
+    Ok(text)
+}
+
+/// Append a line per row to the line view: the value alone for a single column, with line
+/// breaks turned into spaces, and a JSON object of the columns otherwise, so the view
+/// parses back into the same fields
+fn render_lines(columns: &[String], rows: &[Vec<serde_json::Value>], text: &mut Vec<u8>) {
+    for row in rows {
+        let line = match row.as_slice() {
+            [value] => match value {
+                serde_json::Value::Null => String::new(),
+                serde_json::Value::String(s) => s.replace(['\r', '\n'], " "),
+                value => value.to_string(),
+            },
+            values => {
+                let object: serde_json::Map<_, _> = columns
+                    .iter()
+                    .cloned()
+                    .zip(values.iter().cloned())
+                    .collect();
+                serde_json::Value::Object(object).to_string()
+            }
+        };
+        text.extend_from_slice(line.as_bytes());
+        text.push(b'\n');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query_engine::QueryEngine;
+    use serde_json::json;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_projection_defaults_to_line_column() {
+        let columns: Vec<Column> = [
+            ("line_number", ColumnType::Int64),
+            ("raw_line", ColumnType::Utf8),
+            ("status", ColumnType::Int64),
+        ]
+        .iter()
+        .map(|&(name, column_type)| Column {
+            name: name.to_string(),
+            column_type,
+        })
+        .collect();
+        assert_eq!(projection(&columns, None).unwrap(), vec!["raw_line"]);
+        assert_eq!(
+            projection(&columns, Some(&["STATUS".to_string()][..])).unwrap(),
+            vec!["status"]
+        );
+        assert_eq!(
+            projection(&columns, Some(&["host".to_string()][..])),
+            Err(ColumnarError::UnknownColumn("host".to_string()))
+        );
+        assert_eq!(
+            projection(&columns, Some(&[][..])),
+            Err(ColumnarError::NoColumns)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_parquet_round_trip() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "GET /a 200\nPOST /b 500").unwrap();
+        file.flush().unwrap();
+        let engine = QueryEngine::new();
+        engine.register_table(file.path(), "logs").await.unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let exported = dir.path().join("export.data");
+        engine
+            .execute_sql(&format!(
+                "COPY (SELECT line_number, line, \
+                 CAST(split_part(line, ' ', 3) AS BIGINT) AS status FROM logs) \
+                 TO '{}' STORED AS PARQUET",
+                exported.display()
+            ))
+            .await
+            .unwrap();
+        assert_eq!(
+            ColumnarFormat::detect(&exported),
+            Some(ColumnarFormat::Parquet)
+        );
+        assert_eq!(ColumnarFormat::detect(file.path()), None);
+
+        let reader = QueryEngine::new();
+        reader
+            .register_columnar(&exported, ColumnarFormat::Parquet, COLUMNAR_TABLE)
+            .await
+            .unwrap();
+        let columns = reader.table_columns(COLUMNAR_TABLE).await.unwrap();
+        let projection = projection(&columns, None).unwrap();
+        assert_eq!(projection, vec!["line"]);
+        assert_eq!(
+            read_lines(&reader, &projection).await.unwrap(),
+            b"GET /a 200\nPOST /b 500\n"
+        );
+
+        let projection = ["line_number".to_string(), "status".to_string()];
+        let lines = String::from_utf8(read_lines(&reader, &projection).await.unwrap()).unwrap();
+        let first: serde_json::Value = serde_json::from_str(lines.lines().next().unwrap()).unwrap();
+        assert_eq!(first, json!({"line_number": 1, "status": 200}));
+    }
+}
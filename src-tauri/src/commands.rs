@@ -1,27 +1,267 @@
-use crate::indexer::{IndexerError, SharedLogFile};
-use crate::query_engine::{FileFormat, QueryEngine, QueryResult};
+use crate::actions::ActionFailure;
+use crate::alerts::{Alert, AlertError, AlertMonitor, AlertRule, AlertStore};
+use crate::analysis::{
+    self, ActivityHeatmap, DuplicateOptions, DuplicateReport, HeatmapOptions, LineLengthStats,
+    SecretScanOptions, SecretScanReport,
+};
+use crate::applog::{self, AppLogEntry, LogLevel};
+use crate::bench::{self, BenchError, BenchmarkOptions, BenchmarkReport};
+use crate::bundle::{BundleError, BundleFormat, ConfigBundle, ImportMode, ImportSummary};
+use crate::charts::{ChartData, ChartError, ChartSpec, Metric};
+use crate::columnar::{self, ColumnarError, ColumnarFormat, COLUMNAR_TABLE};
+use crate::correlate::{self, CorrelationSource, CorrelationSummary};
+use crate::dashboards::{self, Dashboard, DashboardStore};
+use crate::deep_index::{
+    DeepIndex, DeepIndexError, DeepSearchResult, IndexedSource, DEEP_INDEX_DIR,
+};
+use crate::eventlog::{self, EventLogOptions};
+use crate::export::{self, ExportError, ExportSummary, HighlightRule, HtmlExportOptions};
+use crate::field_search::{FieldQuery, FieldQueryError};
+use crate::funnel::{self, FunnelError, FunnelOptions, FunnelReport};
+use crate::handoff::{self, DatabaseKind, HandoffError, HandoffSummary};
+use crate::indexer::{
+    self, FilePreview, IndexerError, LineEstimate, LineMeta, LogFile, SharedLogFile,
+};
+use crate::integrity::{HashKind, HashManifest, HashRecord, IntegrityCheck};
+use crate::journal::{Journal, JournalEvent, SessionState};
+use crate::lake::{self, LakeExportSummary, LakeSource};
+use crate::layout::{self, OffsetPosition, WrappedLine};
+use crate::listeners::{Listener, ListenerOptions};
+use crate::live::{
+    self, ChunkSender, LiveSource, RecordingSummary, Retention, RetentionOptions, StreamKind,
+    StreamOptions,
+};
+use crate::livestats::{LiveStats, LiveStatsOptions, LiveStatsSnapshot};
+use crate::metrics::{
+    self, LatencyOptions, LatencyReport, MetricError, MetricOptions, MetricSeries,
+};
+use crate::multiscan::{MultiScanner, ScanReport};
+use crate::operations::{
+    Operation, OperationKind, OperationProgress, OperationRegistry, ProgressSink, RunningOperation,
+    OPERATION_PROGRESS_EVENT,
+};
+use crate::packs::{PackError, PackStore, PackSubscription, RefreshOutcome};
+use crate::palette::{self, Palette, PaletteConfig, PaletteError, PaletteStore, Theme};
+use crate::parse_job::{ChunkedParse, ParseJob, ParseJobState, ParseProgress};
+use crate::parsers::library::{ParsePreview, ParseProfile, ProfileLibrary};
+use crate::parsers::schema::{self, OverrideStore, SchemaOverride};
+use crate::parsers::{self, Column, LogParser, ParsedTable, ParserKind};
+use crate::policy::{PathPolicy, PolicyError};
+use crate::profile;
+use crate::query_engine::{
+    splitmix64, FileFormat, ParsedBatches, QueryEngine, QueryError, QueryParam, QueryProgress,
+    QueryResult, SampleOptions, SqlFunctionDoc, SQL_FUNCTIONS,
+};
+use crate::query_language::{self, QueryLanguageError};
+use crate::regex_cache::{Matcher, PatternError, RegexFlags, RegexFlavor};
+use crate::results::{
+    self, Crosstab, PinError, PinStore, PinnedResult, ResultCell, ResultDiff, SortDirection,
+};
+use crate::sanitize::SanitizeOptions;
+use crate::search::{SearchCoordinator, MAX_SEARCH_DEBOUNCE_MS};
+use crate::snippets::{self, SnippetError, SnippetInfo};
+use crate::states::{self, StateError, StateMachineOptions, StateMachineReport};
+use crate::tail::{FollowEvent, FollowFilter, FollowOptions, FollowSession, Follower, LineUpdate};
+use crate::timeline::{
+    self, BurstOptions, BurstReport, FirstOccurrenceOptions, FirstOccurrenceReport, RarityOptions,
+    RarityReport, RateCompareOptions, RateComparison, TimelineError,
+};
+use crate::tokens::{self, LineTokens};
+use crate::trigram::{TrigramIndex, TrigramIndexInfo};
+use crate::unifiedlog::{self, UnifiedLogOptions};
+use crate::views::{SavedView, ViewStore};
+use crate::watch::{Finding, FolderWatch, WatchError, WatchOptions, WatchStatus};
+use futures::StreamExt;
+use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
-use tauri::{AppHandle, Emitter, State};
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_plugin_notification::NotificationExt;
 
 /// Application state shared across commands
 pub struct AppState {
     pub log_file: SharedLogFile,
+    /// Secondary file shown in the split/compare view
+    pub compare_file: SharedLogFile,
     pub query_engine: QueryEngine,
+    /// Separate engine for the compare file, so each file's tables are cleared on their own
+    pub compare_query_engine: QueryEngine,
+    /// Background follower for the main file while tailing
+    pub follower: Mutex<Option<Follower>>,
+    /// Filter, pause state and surfaced range of the follow view
+    pub follow_session: Mutex<FollowSession>,
+    /// Rolling statistics of the lines appended while following
+    pub live_stats: Mutex<LiveStats>,
+    /// Alert rules checked while following
+    pub alerts: Mutex<AlertMonitor>,
+    /// Alert to jump to once the window is focused again, after it fired in the background
+    pub pending_alert_jump: Mutex<Option<Alert>>,
+    /// Unattended watch of a folder of logs
+    pub folder_watch: Mutex<Option<FolderWatch>>,
+    /// Ingest thread while the main view shows a pipe or device
+    pub live_source: Mutex<Option<LiveSource>>,
+    /// How the `parsed` table was produced
+    pub parsed_source: Mutex<Option<ParseSource>>,
+    /// Background parse filling the `parsed` table
+    pub parse_job: Mutex<Option<ParseJob>>,
+    /// How lines are cleaned up before they are shown
+    pub sanitize: Mutex<SanitizeOptions>,
+    /// Record of state changes for restoring after a crash; None until the app is set up
+    pub journal: Mutex<Option<Journal>>,
+    /// Session the previous run left unfinished, until the UI asks for it
+    pub restorable_session: Mutex<Option<SessionState>>,
+    /// Features that failed to initialize, shown by the UI instead of failing startup
+    pub feature_issues: Mutex<Vec<FeatureIssue>>,
+    /// Latest search of each search box
+    pub searches: SearchCoordinator,
+    /// Long-running operations, cancellable by id
+    pub operations: OperationRegistry,
+    /// On-disk full-text index of the main file, once built or first searched
+    pub deep_index: Mutex<Option<DeepIndex>>,
+    /// Hash of the main file taken when it was opened, which integrity checks compare against
+    pub opened_hash: Mutex<Option<HashRecord>>,
+    /// Result snapshots kept for the session whatever file is open
+    pub pins: Mutex<PinStore>,
 }
 
-impl AppState {
-    pub async fn new() -> Self {
-        let query_engine = QueryEngine::new();
-        query_engine.register_udfs().await.ok();
+/// Which open file a command addresses
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileId {
+    #[default]
+    Main,
+    Compare,
+}
+
+/// A feature that couldn't be initialized; the rest of the app keeps working
+#[derive(Debug, Clone, Serialize)]
+pub struct FeatureIssue {
+    pub feature: String,
+    pub message: String,
+}
 
+impl AppState {
+    /// Create the state without any fallible setup, so the window always opens
+    /// Heavier initialization happens when the feature needing it is first used
+    pub fn new() -> Self {
         AppState {
             log_file: SharedLogFile::new(),
-            query_engine,
+            compare_file: SharedLogFile::new(),
+            query_engine: QueryEngine::new(),
+            compare_query_engine: QueryEngine::new(),
+            follower: Mutex::new(None),
+            follow_session: Mutex::new(FollowSession::default()),
+            live_stats: Mutex::new(LiveStats::default()),
+            alerts: Mutex::new(AlertMonitor::default()),
+            pending_alert_jump: Mutex::new(None),
+            folder_watch: Mutex::new(None),
+            live_source: Mutex::new(None),
+            parsed_source: Mutex::new(None),
+            parse_job: Mutex::new(None),
+            sanitize: Mutex::new(SanitizeOptions::default()),
+            journal: Mutex::new(None),
+            restorable_session: Mutex::new(None),
+            feature_issues: Mutex::new(Vec::new()),
+            searches: SearchCoordinator::default(),
+            operations: OperationRegistry::default(),
+            deep_index: Mutex::new(None),
+            opened_hash: Mutex::new(None),
+            pins: Mutex::new(PinStore::default()),
+        }
+    }
+
+    /// Query engine holding the tables of an open file
+    pub fn engine(&self, file: FileId) -> &QueryEngine {
+        match file {
+            FileId::Main => &self.query_engine,
+            FileId::Compare => &self.compare_query_engine,
+        }
+    }
+
+    /// Record that a feature failed to initialize, replacing any earlier issue for it
+    pub fn report_issue(&self, feature: &str, message: impl Into<String>) {
+        let message = message.into();
+        tracing::warn!(feature, error = %message, "feature unavailable");
+        let mut issues = self.feature_issues.lock();
+        issues.retain(|issue| issue.feature != feature);
+        issues.push(FeatureIssue {
+            feature: feature.to_string(),
+            message,
+        });
+    }
+
+    /// Clear a feature's issue once it has initialized
+    pub fn resolve_issue(&self, feature: &str) {
+        self.feature_issues
+            .lock()
+            .retain(|issue| issue.feature != feature);
+    }
+
+    /// Append a state change to the crash journal
+    /// Failures are logged rather than returned, since the change itself already happened
+    pub fn journal(&self, event: JournalEvent) {
+        if let Some(journal) = self.journal.lock().as_mut() {
+            if let Err(e) = journal.record(event) {
+                tracing::warn!(error = %e, "failed to write the session journal");
+            }
         }
     }
 }
 
+impl Default for AppState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Open the crash journal, keeping any session the previous run left unfinished
+pub fn start_journal(app: &AppHandle) {
+    let state = app.state::<Arc<AppState>>();
+    match config_dir(app).and_then(|dir| Ok(Journal::open(&dir)?)) {
+        Ok((journal, previous)) => {
+            if let Some(previous) = &previous {
+                tracing::info!(file = ?previous.file, "previous session ended unexpectedly");
+            }
+            *state.journal.lock() = Some(journal);
+            *state.restorable_session.lock() = previous;
+        }
+        Err(e) => state.report_issue("session journal", e.message),
+    }
+}
+
+/// Give both query engines the saved views, created as the tables they read are registered
+pub fn load_saved_views(app: &AppHandle) {
+    let state = app.state::<Arc<AppState>>();
+    match view_store(app) {
+        Ok(store) => {
+            state.query_engine.set_views(store.views().to_vec());
+            state.compare_query_engine.set_views(store.views().to_vec());
+        }
+        Err(e) => state.report_issue("saved views", e.message),
+    }
+}
+
+/// Remove pinned results left in the temporary directory by runs that didn't exit cleanly,
+/// in the background so startup doesn't wait on it
+pub fn remove_stale_pins() {
+    std::thread::spawn(|| {
+        let removed = PinStore::remove_stale(&std::env::temp_dir());
+        if removed > 0 {
+            tracing::info!(removed, "removed stale pinned results");
+        }
+    });
+}
+
+/// Record a clean shutdown so the next start doesn't offer to restore this session
+pub fn end_journal(app: &AppHandle) {
+    let state = app.state::<Arc<AppState>>();
+    state.journal(JournalEvent::Exited);
+}
+
 /// File information returned when opening a file
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FileInfo {
@@ -29,14 +269,8 @@ pub struct FileInfo {
     pub size: u64,
     pub line_count: u64,
     pub format: String,
-}
-
-/// Progress event for indexing
-#[derive(Clone, Serialize)]
-pub struct IndexProgress {
-    pub phase: String,
-    pub progress: f32,
-    pub message: String,
+    /// Saved parse profile the file is parsed with
+    pub profile: Option<String>,
 }
 
 /// Error type for Tauri commands
@@ -61,6 +295,14 @@ impl From<crate::query_engine::QueryError> for CommandError {
     }
 }
 
+impl From<ExportError> for CommandError {
+    fn from(err: ExportError) -> Self {
+        CommandError {
+            message: err.to_string(),
+        }
+    }
+}
+
 impl From<std::io::Error> for CommandError {
     fn from(err: std::io::Error) -> Self {
         CommandError {
@@ -69,26 +311,218 @@ impl From<std::io::Error> for CommandError {
     }
 }
 
+impl From<PatternError> for CommandError {
+    fn from(err: PatternError) -> Self {
+        CommandError {
+            message: format!("Invalid search pattern: {}", err),
+        }
+    }
+}
+
+impl From<DeepIndexError> for CommandError {
+    fn from(err: DeepIndexError) -> Self {
+        CommandError {
+            message: err.to_string(),
+        }
+    }
+}
+
+impl From<FieldQueryError> for CommandError {
+    fn from(err: FieldQueryError) -> Self {
+        CommandError {
+            message: err.to_string(),
+        }
+    }
+}
+
+impl From<QueryLanguageError> for CommandError {
+    fn from(err: QueryLanguageError) -> Self {
+        CommandError {
+            message: err.to_string(),
+        }
+    }
+}
+
+impl From<SnippetError> for CommandError {
+    fn from(err: SnippetError) -> Self {
+        CommandError {
+            message: err.to_string(),
+        }
+    }
+}
+
+impl From<ColumnarError> for CommandError {
+    fn from(err: ColumnarError) -> Self {
+        CommandError {
+            message: err.to_string(),
+        }
+    }
+}
+
+impl From<PinError> for CommandError {
+    fn from(err: PinError) -> Self {
+        CommandError {
+            message: err.to_string(),
+        }
+    }
+}
+
+impl From<HandoffError> for CommandError {
+    fn from(err: HandoffError) -> Self {
+        CommandError {
+            message: err.to_string(),
+        }
+    }
+}
+
+impl From<AlertError> for CommandError {
+    fn from(err: AlertError) -> Self {
+        CommandError {
+            message: err.to_string(),
+        }
+    }
+}
+
+impl From<WatchError> for CommandError {
+    fn from(err: WatchError) -> Self {
+        CommandError {
+            message: err.to_string(),
+        }
+    }
+}
+
+impl From<BundleError> for CommandError {
+    fn from(err: BundleError) -> Self {
+        CommandError {
+            message: err.to_string(),
+        }
+    }
+}
+
+impl From<FunnelError> for CommandError {
+    fn from(err: FunnelError) -> Self {
+        CommandError {
+            message: err.to_string(),
+        }
+    }
+}
+
+impl From<StateError> for CommandError {
+    fn from(err: StateError) -> Self {
+        CommandError {
+            message: err.to_string(),
+        }
+    }
+}
+
+impl From<MetricError> for CommandError {
+    fn from(err: MetricError) -> Self {
+        CommandError {
+            message: err.to_string(),
+        }
+    }
+}
+
+impl From<TimelineError> for CommandError {
+    fn from(err: TimelineError) -> Self {
+        CommandError {
+            message: err.to_string(),
+        }
+    }
+}
+
+impl From<PaletteError> for CommandError {
+    fn from(err: PaletteError) -> Self {
+        CommandError {
+            message: err.to_string(),
+        }
+    }
+}
+
+impl From<PackError> for CommandError {
+    fn from(err: PackError) -> Self {
+        CommandError {
+            message: err.to_string(),
+        }
+    }
+}
+
+impl From<ChartError> for CommandError {
+    fn from(err: ChartError) -> Self {
+        CommandError {
+            message: err.to_string(),
+        }
+    }
+}
+
+impl From<BenchError> for CommandError {
+    fn from(err: BenchError) -> Self {
+        CommandError {
+            message: err.to_string(),
+        }
+    }
+}
+
+/// Event carrying a file's approximate line count while it is indexed, then the exact one
+const LINE_ESTIMATE_EVENT: &str = "line-count-estimate";
+
 /// Open a log file and build the index
+/// Paths outside the approved directories are refused and `path-approval-required` is
+/// emitted
+/// Parquet and Arrow files show a line per row, built from `columns` when given
 #[tauri::command]
 pub async fn open_file(
     path: String,
+    columns: Option<Vec<String>>,
     state: State<'_, Arc<AppState>>,
     app: AppHandle,
 ) -> Result<FileInfo, CommandError> {
-    // Emit progress event for indexing start
-    app.emit(
-        "index-progress",
-        IndexProgress {
-            phase: "opening".to_string(),
-            progress: 0.0,
-            message: "Opening file...".to_string(),
-        },
-    )
-    .ok();
+    let path = authorize_path(&app, &path)?;
+    let _span = profile::span("command", "open_file").arg("path", path.as_str());
+
+    let operation = start_operation(&app, OperationKind::Index, "opening", "Opening file...");
+
+    // Pipes and devices can't be mapped; read them as a live source instead
+    if let Some(kind) = live::stream_kind(&path) {
+        state.parse_job.lock().take();
+        state.parsed_source.lock().take();
+        state.query_engine.clear().await;
+        operation.finish("Reading stream");
+        return start_stream(path, kind, StreamOptions::default(), state.inner(), app);
+    }
+
+    state.live_source.lock().take();
+    state.parse_job.lock().take();
+    state.parsed_source.lock().take();
+    state.deep_index.lock().take();
+
+    // Hash the file as opened, so integrity checks and exports show later changes to it
+    operation.update("hashing", 0.05, "Hashing file...");
+    let opened = HashRecord::compute(&path, HashKind::Source)?;
+    let mut manifest = hash_manifest(&app)?;
+    manifest.record(opened.clone());
+    manifest.save()?;
+    *state.opened_hash.lock() = Some(opened);
+
+    if let Some(format) = ColumnarFormat::detect(Path::new(&path)) {
+        let info = open_columnar(&state, &app, path, format, columns.as_deref()).await?;
+        operation.finish("File ready");
+        return Ok(info);
+    }
+
+    // A sampled estimate lets the scrollbar size itself while the exact index builds
+    if let Ok(estimate) = indexer::estimate_line_count(&path) {
+        app.emit(LINE_ESTIMATE_EVENT, estimate).ok();
+        operation.update(
+            "indexing",
+            0.1,
+            format!("Indexing about {} lines...", estimate.lines),
+        );
+    }
 
     // Open and index the file
-    state.log_file.open(&path)?;
+    let log_file = LogFile::open_cancellable(&path, || operation.is_cancelled())?;
+    state.log_file.set(log_file);
 
     // Get file info
     let (file_size, line_count) = state
@@ -96,132 +530,3335 @@ pub async fn open_file(
         .with_file(|f| (f.file_size(), f.line_count()))
         .unwrap_or((0, 0));
 
-    app.emit(
-        "index-progress",
-        IndexProgress {
-            phase: "indexing".to_string(),
-            progress: 0.5,
-            message: format!("Indexing {} lines...", line_count),
-        },
-    )
-    .ok();
+    // Reconcile the estimate with the exact count
+    let exact = LineEstimate {
+        lines: line_count,
+        exact: true,
+    };
+    app.emit(LINE_ESTIMATE_EVENT, exact).ok();
+    operation.update("indexing", 0.5, format!("Indexed {} lines", line_count));
 
-    // Detect file format
-    let format = QueryEngine::detect_format(&path).unwrap_or(FileFormat::PlainText);
+    // Detect file format from the mapped contents
+    let format = state
+        .log_file
+        .with_file(|f| QueryEngine::detect_format_bytes(f.data()))
+        .unwrap_or(FileFormat::PlainText);
 
     // Register with query engine
-    state
-        .query_engine
-        .register_table(&path, "logs")
-        .await
-        .ok();
-
-    app.emit(
-        "index-progress",
-        IndexProgress {
-            phase: "complete".to_string(),
-            progress: 1.0,
-            message: "File ready".to_string(),
-        },
-    )
-    .ok();
+    if let Err(e) = register_logs(&state.query_engine, &state.log_file, &path).await {
+        tracing::warn!(path = %path, error = %e, "failed to register the logs table");
+    }
+
+    // A saved profile matching the path parses the file into a typed table; otherwise
+    // delimited and JSON files get one, leaving out plain-text lines of mixed files
+    let profile = profile_library(&app)
+        .ok()
+        .and_then(|library| library.match_path(&path).map(|p| p.name.clone()));
+    let source = match (profile, format) {
+        (Some(name), _) => Some(ParseSource::Profile(name)),
+        (None, FileFormat::Csv) => Some(ParseSource::Format(ParserKind::Csv)),
+        (None, FileFormat::Ndjson | FileFormat::Mixed) => {
+            Some(ParseSource::Format(ParserKind::Json))
+        }
+        (None, FileFormat::PlainText) => None,
+    };
+    tracing::info!(path = %path, ?format, ?source, "file opened");
+    let mut profile = None;
+    if let Some(source) = source {
+        let profile_name = match &source {
+            ParseSource::Profile(name) => Some(name.clone()),
+            ParseSource::Format(_) => None,
+        };
+        // Large files keep parsing in the background once the file is shown
+        match register_or_start_parse(state.inner(), &app, Some(source)).await {
+            Ok(_) => profile = profile_name,
+            Err(e) => tracing::warn!(path = %path, error = %e.message, "failed to parse file"),
+        }
+    }
+
+    state.journal(JournalEvent::Opened { path: path.clone() });
+    operation.finish("File ready");
+
+    Ok(FileInfo {
+        path,
+        size: file_size,
+        line_count,
+        format: format!("{:?}", format),
+        profile,
+    })
+}
+
+/// Register a Parquet or Arrow file's rows as the `columnar` table with their own types,
+/// and show them as a line per row, which also makes up the `logs` table
+async fn open_columnar(
+    state: &AppState,
+    app: &AppHandle,
+    path: String,
+    format: ColumnarFormat,
+    columns: Option<&[String]>,
+) -> Result<FileInfo, CommandError> {
+    let engine = &state.query_engine;
+    engine
+        .register_columnar(Path::new(&path), format, COLUMNAR_TABLE)
+        .await?;
+    let projection = columnar::projection(&engine.table_columns(COLUMNAR_TABLE).await?, columns)?;
+    let text = columnar::read_lines(engine, &projection).await?;
+    engine.register_line_bytes(&text, "logs").await?;
 
+    let mut log_file = LogFile::live(&path);
+    log_file.append_bytes(&text);
+    let (file_size, line_count) = (log_file.file_size(), log_file.line_count());
+    state.log_file.set(log_file);
+    let exact = LineEstimate {
+        lines: line_count,
+        exact: true,
+    };
+    app.emit(LINE_ESTIMATE_EVENT, exact).ok();
+
+    tracing::info!(path = %path, ?format, ?projection, "columnar file opened");
+    state.journal(JournalEvent::Opened { path: path.clone() });
     Ok(FileInfo {
         path,
         size: file_size,
         line_count,
         format: format!("{:?}", format),
+        profile: None,
     })
 }
 
 /// Close the current file
 #[tauri::command]
 pub async fn close_file(state: State<'_, Arc<AppState>>) -> Result<(), CommandError> {
+    state.follower.lock().take();
+    state.live_source.lock().take();
+    state.parse_job.lock().take();
+    state.parsed_source.lock().take();
+    state.deep_index.lock().take();
+    state.opened_hash.lock().take();
     state.log_file.close();
     state.query_engine.clear().await;
+    state.journal(JournalEvent::Closed);
     Ok(())
 }
 
-/// Get a range of lines from the file
+/// Get a range of lines from the file, sanitized for display
 #[tauri::command]
 pub fn get_lines(
     start: u64,
     count: u64,
     state: State<'_, Arc<AppState>>,
 ) -> Result<Vec<String>, CommandError> {
-    state
+    let mut lines = state
         .log_file
         .with_file(|f| f.get_lines(start, count))
         .ok_or_else(|| CommandError {
             message: "No file open".to_string(),
-        })?
-        .map_err(CommandError::from)
+        })??;
+    state.sanitize.lock().apply_all(&mut lines);
+    Ok(lines)
 }
 
-/// Get lines in binary format for efficient transfer
+/// Byte offset and length of a range of lines, to line them up with tools like dd or a
+/// hex editor and to spot lines cut off by truncated writes
 #[tauri::command]
-pub fn get_lines_binary(
+pub fn get_lines_meta(
     start: u64,
     count: u64,
     state: State<'_, Arc<AppState>>,
-) -> Result<Vec<u8>, CommandError> {
+) -> Result<Vec<LineMeta>, CommandError> {
     state
         .log_file
-        .with_file(|f| f.get_lines_binary(start, count))
+        .with_file(|f| f.get_lines_meta(start, count))
+        .ok_or_else(|| CommandError {
+            message: "No file open".to_string(),
+        })
+}
+
+/// Where a range of lines wraps at `width` columns, as displayed after sanitizing, so a
+/// wrapped view can size its rows without fetching and measuring every long line
+#[tauri::command]
+pub fn get_wrapped_lines(
+    start: u64,
+    count: u64,
+    width: usize,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<WrappedLine>, CommandError> {
+    let lines = get_lines(start, count, state)?;
+    Ok((start..)
+        .zip(lines)
+        .map(|(line, text)| WrappedLine {
+            line,
+            breaks: layout::wrap_points(&text, width),
+        })
+        .collect())
+}
+
+/// Colored spans of a range of lines, such as timestamps, levels and quoted strings, as
+/// displayed after sanitizing
+#[tauri::command]
+pub fn get_line_tokens(
+    start: u64,
+    count: u64,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<LineTokens>, CommandError> {
+    let lines = get_lines(start, count, state)?;
+    Ok((start..)
+        .zip(lines)
+        .map(|(line, text)| LineTokens {
+            line,
+            tokens: tokens::tokenize(&text),
+        })
+        .collect())
+}
+
+/// Byte offsets into a line, e.g. of search matches, as character, grapheme, UTF-16 and
+/// column positions for highlighting and caret placement in multibyte text
+/// Offsets are into the line as stored, before sanitizing
+#[tauri::command]
+pub fn map_offsets(
+    line: u64,
+    byte_offsets: Vec<usize>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<OffsetPosition>, CommandError> {
+    let text = state
+        .log_file
+        .with_file(|f| f.line_text(line).map(|text| text.into_owned()))
         .ok_or_else(|| CommandError {
             message: "No file open".to_string(),
         })?
-        .map_err(CommandError::from)
+        .ok_or_else(|| CommandError {
+            message: format!("Line {} is out of range", line + 1),
+        })?;
+    Ok(layout::map_offsets(&text, &byte_offsets))
+}
+
+/// Get lines in binary format for efficient transfer, sanitized like `get_lines`
+#[tauri::command]
+pub fn get_lines_binary(
+    start: u64,
+    count: u64,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<u8>, CommandError> {
+    let lines = get_lines(start, count, state)?;
+    Ok(indexer::encode_lines_binary(&lines))
+}
+
+/// Current display sanitization settings
+#[tauri::command]
+pub fn get_sanitize_options(state: State<'_, Arc<AppState>>) -> SanitizeOptions {
+    *state.sanitize.lock()
+}
+
+/// Change how lines are sanitized for display; the view should reload its lines afterwards
+#[tauri::command]
+pub fn set_sanitize_options(options: SanitizeOptions, state: State<'_, Arc<AppState>>) {
+    *state.sanitize.lock() = options;
 }
 
 /// Get file information
 #[tauri::command]
 pub fn get_file_info(state: State<'_, Arc<AppState>>) -> Result<Option<FileInfo>, CommandError> {
+    let profile = match &*state.parsed_source.lock() {
+        Some(ParseSource::Profile(name)) => Some(name.clone()),
+        _ => None,
+    };
     Ok(state.log_file.with_file(|f| FileInfo {
         path: f.path().to_string(),
         size: f.file_size(),
         line_count: f.line_count(),
         format: "Unknown".to_string(),
+        profile,
     }))
 }
 
+/// First and last lines of a file, read from its two ends without opening or indexing it,
+/// for previewing a file before committing to open it
+#[tauri::command]
+pub fn get_preview(
+    path: String,
+    head_lines: usize,
+    tail_lines: usize,
+    app: AppHandle,
+) -> Result<FilePreview, CommandError> {
+    let path = authorize_path(&app, &path)?;
+    Ok(indexer::read_preview(&path, head_lines, tail_lines)?)
+}
+
 /// Search for a pattern in the file
+/// A newer search in the same session stops this one, which then fails with "Cancelled",
+/// so typing doesn't queue up full scans. With `debounce_ms` the scan waits that long first
+/// and is skipped if a newer search arrives meanwhile. The `fancy` flavor accepts
+/// lookaround and backreferences but can be much slower
 #[tauri::command]
-pub fn search(
+pub async fn search(
     pattern: String,
     max_results: Option<usize>,
+    session: Option<String>,
+    debounce_ms: Option<u64>,
+    flavor: Option<RegexFlavor>,
     state: State<'_, Arc<AppState>>,
+    app: AppHandle,
 ) -> Result<Vec<u64>, CommandError> {
     let max = max_results.unwrap_or(1000);
-    state
-        .log_file
-        .with_file(|f| f.search(&pattern, max))
-        .ok_or_else(|| CommandError {
-            message: "No file open".to_string(),
-        })?
-        .map_err(CommandError::from)
+    let flavor = flavor.unwrap_or_default();
+    let matcher = Matcher::new(&pattern, flavor)?;
+    let ticket = state.searches.begin(session.as_deref().unwrap_or("main"));
+    if let Some(ms) = debounce_ms.filter(|&ms| ms > 0) {
+        tokio::time::sleep(Duration::from_millis(ms.min(MAX_SEARCH_DEBOUNCE_MS))).await;
+    }
+    if ticket.is_superseded() {
+        return Err(IndexerError::Cancelled.into());
+    }
+
+    let state = state.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let message = match flavor {
+            RegexFlavor::Standard => "Searching...",
+            RegexFlavor::Fancy => "Searching with backtracking regex, which can be much slower...",
+        };
+        let operation = start_operation(&app, OperationKind::Search, "scanning", message);
+        let matches = state
+            .log_file
+            .with_file(|f| {
+                f.search_until(
+                    &matcher,
+                    max,
+                    || ticket.is_superseded() || operation.is_cancelled(),
+                    |done, total| {
+                        let message = format!("Searched {} of {} lines", done, total);
+                        operation.update("scanning", done as f64 / total as f64, message);
+                    },
+                )
+            })
+            .ok_or_else(|| CommandError {
+                message: "No file open".to_string(),
+            })??;
+        operation.finish(format!("{} matches", matches.len()));
+        Ok(matches)
+    })
+    .await
+    .map_err(|e| CommandError {
+        message: e.to_string(),
+    })?
 }
 
-/// Execute a SQL query
+/// Build a trigram index of the open file so later literal searches skip the blocks that
+/// can't contain them; worth it for files searched many times
 #[tauri::command]
-pub async fn execute_sql(
-    query: String,
+pub async fn build_search_index(
     state: State<'_, Arc<AppState>>,
-) -> Result<QueryResult, CommandError> {
-    state
-        .query_engine
-        .execute_sql(&query)
-        .await
-        .map_err(CommandError::from)
+    app: AppHandle,
+) -> Result<TrigramIndexInfo, CommandError> {
+    let state = state.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let operation = start_operation(
+            &app,
+            OperationKind::Index,
+            "trigrams",
+            "Building search index...",
+        );
+        // Built under the read lock so the viewer keeps working meanwhile
+        let index = state
+            .log_file
+            .with_file(|f| TrigramIndex::build(f, || operation.is_cancelled()))
+            .ok_or_else(|| CommandError {
+                message: "No file open".to_string(),
+            })??;
+        let info = index.info();
+        if state.log_file.with_file_mut(|f| f.set_trigram_index(index)) != Some(true) {
+            return Err(CommandError {
+                message: "The file changed while its search index was built".to_string(),
+            });
+        }
+        operation.finish(format!("Indexed {} blocks", info.blocks));
+        Ok(info)
+    })
+    .await
+    .map_err(|e| CommandError {
+        message: e.to_string(),
+    })?
 }
 
-/// Get the total line count
+/// Size and source of the main file's deep index
+#[derive(Debug, Clone, Serialize)]
+pub struct DeepIndexStatus {
+    pub source: IndexedSource,
+    pub disk_bytes: u64,
+    /// Lines of the file not indexed yet
+    pub pending_lines: u64,
+}
+
+/// Build or extend an on-disk full-text index of the main file, for token and phrase
+/// queries with `deep_search`
+/// The index is kept in the app data directory and reused when the file is opened again;
+/// only lines appended since the last build are indexed
 #[tauri::command]
-pub fn get_line_count(state: State<'_, Arc<AppState>>) -> Result<u64, CommandError> {
+pub async fn build_deep_index(
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> Result<DeepIndexStatus, CommandError> {
+    let root = data_dir(&app)?.join(DEEP_INDEX_DIR);
+    let state = state.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let operation = start_operation(
+            &app,
+            OperationKind::Index,
+            "deep-index",
+            "Building deep index...",
+        );
+        // Taken out while it is extended, so searches meanwhile don't wait on the build
+        let current = state.deep_index.lock().take();
+        let status = state
+            .log_file
+            .with_file(
+                |f| -> Result<(DeepIndex, DeepIndexStatus), DeepIndexError> {
+                    let mut index = match current {
+                        Some(index) if index.source().path == f.path() => index,
+                        _ => DeepIndex::open(&root, f)?,
+                    };
+                    index.update(
+                        f,
+                        || operation.is_cancelled(),
+                        |done, total| {
+                            let message = format!("Indexed {} of {} lines", done, total);
+                            operation.update("deep-index", done as f64 / total as f64, message);
+                        },
+                    )?;
+                    let status = deep_index_status_of(&index, f);
+                    Ok((index, status))
+                },
+            )
+            .ok_or_else(|| CommandError {
+                message: "No file open".to_string(),
+            })?;
+        let (index, status) = status?;
+        *state.deep_index.lock() = Some(index);
+        operation.finish(format!("Indexed {} lines", status.source.lines));
+        Ok(status)
+    })
+    .await
+    .map_err(|e| CommandError {
+        message: e.to_string(),
+    })?
+}
+
+/// Search the main file's deep index, returning matching lines in file order
+/// Words must all appear unless joined with OR; "quoted words" match as a phrase, and a
+/// leading - excludes a word. Lines appended since the index was built aren't searched
+#[tauri::command]
+pub fn deep_search(
+    query: String,
+    max_results: Option<usize>,
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> Result<DeepSearchResult, CommandError> {
+    let mut deep_index = state.deep_index.lock();
+    let path = state
+        .log_file
+        .with_file(|f| f.path().to_string())
+        .ok_or_else(|| CommandError {
+            message: "No file open".to_string(),
+        })?;
+    if deep_index
+        .as_ref()
+        .map(|index| index.source().path.as_str())
+        != Some(path.as_str())
+    {
+        // Reuse an index built in an earlier session
+        let root = data_dir(&app)?.join(DEEP_INDEX_DIR);
+        if !DeepIndex::dir_for(&root, &path).exists() {
+            return Err(CommandError {
+                message: "No deep index for this file; build one first".to_string(),
+            });
+        }
+        let index = state
+            .log_file
+            .with_file(|f| DeepIndex::open(&root, f))
+            .ok_or_else(|| CommandError {
+                message: "No file open".to_string(),
+            })??;
+        *deep_index = Some(index);
+    }
+    let index = deep_index.as_ref().expect("deep index was just opened");
+    Ok(index.search(&query, max_results.unwrap_or(1000))?)
+}
+
+/// Status of the main file's deep index, or None if it has none loaded
+#[tauri::command]
+pub fn deep_index_status(state: State<'_, Arc<AppState>>) -> Option<DeepIndexStatus> {
+    let deep_index = state.deep_index.lock();
+    let index = deep_index.as_ref()?;
+    // An index left from another file says nothing about this one
     state
         .log_file
-        .with_file(|f| f.line_count())
+        .with_file(|f| (index.source().path == f.path()).then(|| deep_index_status_of(index, f)))
+        .flatten()
+}
+
+/// Delete the main file's deep index from disk, returning whether it had one
+#[tauri::command]
+pub fn delete_deep_index(
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> Result<bool, CommandError> {
+    let path = state
+        .log_file
+        .with_file(|f| f.path().to_string())
+        .ok_or_else(|| CommandError {
+            message: "No file open".to_string(),
+        })?;
+    state.deep_index.lock().take();
+    Ok(DeepIndex::remove(
+        &data_dir(&app)?.join(DEEP_INDEX_DIR),
+        &path,
+    )?)
+}
+
+fn deep_index_status_of(index: &DeepIndex, file: &LogFile) -> DeepIndexStatus {
+    DeepIndexStatus {
+        source: index.source().clone(),
+        disk_bytes: index.disk_bytes(),
+        pending_lines: file
+            .complete_line_count()
+            .saturating_sub(index.source().lines),
+    }
+}
+
+/// Scan the file for many patterns at once, e.g. a rule set of alerts, counting the lines
+/// each pattern matches and keeping the first `max_lines` of each
+/// Runs on vectorscan when built with the `vectorscan` feature and the rules compile for it
+#[tauri::command]
+pub async fn scan_patterns(
+    patterns: Vec<String>,
+    case_insensitive: Option<bool>,
+    max_lines: Option<usize>,
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> Result<ScanReport, CommandError> {
+    let flags = RegexFlags {
+        case_insensitive: case_insensitive.unwrap_or(false),
+    };
+    let scanner = MultiScanner::new(&patterns, flags).map_err(PatternError::from)?;
+    let state = state.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let message = format!("Scanning for {} patterns...", patterns.len());
+        let operation = start_operation(&app, OperationKind::Search, "scanning", &message);
+        let report = state
+            .log_file
+            .with_file(|f| {
+                scanner.scan(
+                    f,
+                    max_lines.unwrap_or(1000),
+                    || operation.is_cancelled(),
+                    |done, total| {
+                        let message = format!("Scanned {} of {} lines", done, total);
+                        operation.update("scanning", done as f64 / total as f64, message);
+                    },
+                )
+            })
+            .ok_or_else(|| CommandError {
+                message: "No file open".to_string(),
+            })??;
+        let matched = report.hits.iter().filter(|hits| hits.count > 0).count();
+        operation.finish(format!(
+            "{} of {} patterns matched",
+            matched,
+            patterns.len()
+        ));
+        Ok(report)
+    })
+    .await
+    .map_err(|e| CommandError {
+        message: e.to_string(),
+    })?
+}
+
+/// Stop the search running in a session
+#[tauri::command]
+pub fn cancel_search(session: Option<String>, state: State<'_, Arc<AppState>>) {
+    state.searches.cancel(session.as_deref().unwrap_or("main"));
+}
+
+/// Execute a SQL query against the tables of the main file, or of the compare file
+/// `params` are bound in order to the query's `$1`, `$2`, ... placeholders; with `sample`
+/// the query reads a random share of a table for a quick estimate
+#[tauri::command]
+pub async fn execute_sql(
+    query: String,
+    params: Option<Vec<QueryParam>>,
+    file: Option<FileId>,
+    sample: Option<SampleOptions>,
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> Result<QueryResult, CommandError> {
+    let operation = start_operation(&app, OperationKind::Sql, "running", "Running query...");
+    let file = file.unwrap_or_default();
+    let engine = state.engine(file);
+    // Queries still run without the custom functions if they can't be registered
+    match engine.ensure_udfs().await {
+        Ok(()) => state.resolve_issue("sql functions"),
+        Err(e) => state.report_issue("sql functions", e.to_string()),
+    }
+    let params = params.unwrap_or_default();
+    // Dropping the query's future on cancellation stops it
+    let token = operation.token().clone();
+    let result = {
+        // Queries running past a second report how far they've got, to tell a slow one that
+        // is advancing from one worth cancelling
+        let on_progress = |progress: QueryProgress| {
+            operation.update(
+                "running",
+                progress.partitions_done as f64 / progress.partitions.max(1) as f64,
+                format!(
+                    "{} of {} partitions read, {} rows processed",
+                    progress.partitions_done, progress.partitions, progress.rows_processed
+                ),
+            );
+        };
+        let query_run = engine.run_query(&query, &params, sample.as_ref(), Some(&on_progress));
+        tokio::select! {
+            result = query_run => result,
+            _ = token.cancelled() => return Err(IndexerError::Cancelled.into()),
+        }
+    };
+    let mut result = result.inspect_err(|e| tracing::debug!(error = %e, "query failed"))?;
+    result.partial = file == FileId::Main && reads_partial_parse(&state, &query).await;
+    operation.finish(format!("{} rows", result.row_count));
+    Ok(result)
+}
+
+/// Whether a query over the main file reads a parsed table, directly or through a view, that
+/// a background parse is still filling, so it sees only what the parse has reached
+async fn reads_partial_parse(state: &AppState, query: &str) -> bool {
+    let parsing = state
+        .parse_job
+        .lock()
+        .as_ref()
+        .is_some_and(ParseJob::is_partial);
+    parsing
+        && state
+            .query_engine
+            .referenced_tables(query)
+            .await
+            .is_ok_and(|tables| tables.contains("parsed") || tables.contains("parse_errors"))
+}
+
+/// Sort the kept batches of an earlier result by a column, rather than running its query
+/// again
+#[tauri::command]
+pub async fn sort_result(
+    result_id: u64,
+    column: String,
+    direction: Option<SortDirection>,
+    file: Option<FileId>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<QueryResult, CommandError> {
+    let engine = state.engine(file.unwrap_or_default());
+    Ok(engine
+        .sort_result(result_id, &column, direction.unwrap_or_default())
+        .await?)
+}
+
+/// Keep the rows of an earlier result matching a SQL condition on its columns, such as
+/// `level = 'ERROR'`, rather than running its query again
+#[tauri::command]
+pub async fn filter_result(
+    result_id: u64,
+    condition: String,
+    file: Option<FileId>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<QueryResult, CommandError> {
+    let engine = state.engine(file.unwrap_or_default());
+    Ok(engine.filter_result(result_id, &condition).await?)
+}
+
+/// Reshape an earlier result into a crosstab of `agg`, a count unless given, by the values
+/// of two of its columns, such as level by hour
+#[tauri::command]
+pub async fn pivot_result(
+    result_id: u64,
+    row_key: String,
+    col_key: String,
+    agg: Option<Metric>,
+    file: Option<FileId>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Crosstab, CommandError> {
+    let engine = state.engine(file.unwrap_or_default());
+    let agg = agg.unwrap_or(Metric::Count);
+    Ok(engine
+        .pivot_result(result_id, &row_key, &col_key, &agg)
+        .await?)
+}
+
+/// The full value of a cell of an earlier result, by row and column index, with JSON
+/// pretty-printed for the detail pane
+#[tauri::command]
+pub fn get_result_cell(
+    result_id: u64,
+    row: usize,
+    column: usize,
+    file: Option<FileId>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<ResultCell, CommandError> {
+    let engine = state.engine(file.unwrap_or_default());
+    Ok(engine.result_cell(result_id, row, column)?)
+}
+
+/// Pin an earlier result for the rest of the session, written to disk so it stays
+/// comparable after its file is closed or tailing moves on
+#[tauri::command]
+pub async fn pin_result(
+    result_id: u64,
+    name: Option<String>,
+    file: Option<FileId>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<PinnedResult, CommandError> {
+    let result = state
+        .engine(file.unwrap_or_default())
+        .cached_result(result_id)?;
+    let state = state.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        Ok(state.pins.lock().pin(result_id, name, &result)?)
+    })
+    .await
+    .map_err(|e| CommandError {
+        message: e.to_string(),
+    })?
+}
+
+#[tauri::command]
+pub fn list_pinned_results(state: State<'_, Arc<AppState>>) -> Vec<PinnedResult> {
+    state.pins.lock().pins().to_vec()
+}
+
+/// Load a pinned result into a file's engine as a result to browse, sort and filter
+#[tauri::command]
+pub fn open_pinned_result(
+    pin_id: u64,
+    file: Option<FileId>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<QueryResult, CommandError> {
+    let result = state.pins.lock().load(pin_id)?;
+    Ok(state.engine(file.unwrap_or_default()).keep_result(result)?)
+}
+
+#[tauri::command]
+pub fn unpin_result(pin_id: u64, state: State<'_, Arc<AppState>>) -> Result<(), CommandError> {
+    Ok(state.pins.lock().unpin(pin_id)?)
+}
+
+/// Compare two pinned results, such as top errors of two files or time windows, matching
+/// rows on the `keys` columns
+#[tauri::command]
+pub async fn diff_results(
+    result_a: u64,
+    result_b: u64,
+    keys: Vec<String>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<ResultDiff, CommandError> {
+    let state = state.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let (before, after) = {
+            let pins = state.pins.lock();
+            (pins.load(result_a)?, pins.load(result_b)?)
+        };
+        Ok(results::diff(&keys, &before, &after)?)
+    })
+    .await
+    .map_err(|e| CommandError {
+        message: e.to_string(),
+    })?
+}
+
+/// Documentation of the custom SQL functions, such as `regex_extract` for join keys
+#[tauri::command]
+pub fn list_sql_functions() -> Vec<SqlFunctionDoc> {
+    SQL_FUNCTIONS.to_vec()
+}
+
+/// Build a view of the lines sharing a correlation key, such as a request or span id,
+/// across the main file and the compare file when one is open
+/// The key is the first capture group of `key_pattern`, or its whole match. The compare
+/// file's lines are shared as `compare_logs` for joins of your own; the view, `correlated`
+/// unless named, holds `correlation_key`, `source`, `line_number` and `line`
+#[tauri::command]
+pub async fn auto_correlate(
+    key_pattern: String,
+    view: Option<String>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<CorrelationSummary, CommandError> {
+    Matcher::new(&key_pattern, RegexFlavor::Standard)?;
+    let view = view.unwrap_or_else(|| correlate::DEFAULT_VIEW.to_string());
+    let label = |f: &LogFile| {
+        Path::new(f.path()).file_name().map_or_else(
+            || f.path().to_string(),
+            |n| n.to_string_lossy().into_owned(),
+        )
+    };
+    let mut sources = vec![CorrelationSource {
+        label: state
+            .log_file
+            .with_file(label)
+            .ok_or_else(|| CommandError {
+                message: "No file open".to_string(),
+            })?,
+        table: "logs".to_string(),
+    }];
+    if let Some(compare) = state.compare_file.with_file(label) {
+        let provider = state.compare_query_engine.table_provider("logs").await?;
+        state
+            .query_engine
+            .register_provider(correlate::COMPARE_TABLE, provider)
+            .await?;
+        sources.push(CorrelationSource {
+            // Files of the same name still need telling apart
+            label: if compare == sources[0].label {
+                format!("{} (compare)", compare)
+            } else {
+                compare
+            },
+            table: correlate::COMPARE_TABLE.to_string(),
+        });
+    }
+
+    state.query_engine.ensure_udfs().await?;
+    let sql = correlate::correlation_view_sql(&sources, &key_pattern);
+    state
+        .query_engine
+        .create_temporary_view(&view, &sql)
+        .await?;
+    let counts = state
+        .query_engine
+        .execute_sql(&format!(
+            "SELECT COUNT(DISTINCT correlation_key), COUNT(*) FROM {}",
+            view
+        ))
+        .await?;
+    let count = |i: usize| {
+        counts
+            .rows
+            .first()
+            .and_then(|row| row.get(i))
+            .and_then(serde_json::Value::as_u64)
+            .unwrap_or(0)
+    };
+    Ok(CorrelationSummary {
+        view,
+        sources: sources.into_iter().map(|source| source.label).collect(),
+        keys: count(0),
+        lines: count(1),
+    })
+}
+
+/// List the saved views
+#[tauri::command]
+pub fn list_views(app: AppHandle) -> Result<Vec<SavedView>, CommandError> {
+    Ok(view_store(&app)?.views().to_vec())
+}
+
+/// Save a view over the main file's tables, replacing any view of the same name
+/// Saved views are usable from both files and kept across restarts
+#[tauri::command]
+pub async fn save_view(
+    name: String,
+    sql: String,
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> Result<(), CommandError> {
+    let view = SavedView { name, sql };
+    state.query_engine.save_view(view.clone()).await?;
+    let mut store = view_store(&app)?;
+    store.upsert(view);
+    store.save()?;
+    state.compare_query_engine.set_views(store.views().to_vec());
+    state.compare_query_engine.refresh_views().await;
+    Ok(())
+}
+
+/// Delete a saved view, returning whether it existed
+#[tauri::command]
+pub async fn delete_view(
+    name: String,
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> Result<bool, CommandError> {
+    state.query_engine.drop_view(&name).await;
+    state.compare_query_engine.drop_view(&name).await;
+    let mut store = view_store(&app)?;
+    let removed = store.remove(&name);
+    store.save()?;
+    Ok(removed)
+}
+
+/// Load a CSV file as a lookup table of the main or compare file's engine, kept when the
+/// file is closed or replaced. Returns the number of rows loaded
+#[tauri::command]
+pub async fn register_lookup_table(
+    name: String,
+    path: String,
+    file: Option<FileId>,
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> Result<usize, CommandError> {
+    let path = authorize_path(&app, &path)?;
+    let engine = state.engine(file.unwrap_or_default());
+    Ok(engine.register_lookup(&name, Path::new(&path)).await?)
+}
+
+/// Drop a lookup table, returning whether it existed
+#[tauri::command]
+pub async fn drop_lookup_table(
+    name: String,
+    file: Option<FileId>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<bool, CommandError> {
+    let engine = state.engine(file.unwrap_or_default());
+    Ok(engine.drop_lookup(&name).await)
+}
+
+/// Names of the lookup tables of the main or compare file's engine
+#[tauri::command]
+pub fn list_lookup_tables(
+    file: Option<FileId>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<String>, CommandError> {
+    Ok(state.engine(file.unwrap_or_default()).lookups())
+}
+
+/// Find the source line a SQL result row came from, as a 0-based line index for the viewer
+/// Uses the row's `line_number`, falling back to the first line whose text equals its
+/// `raw_line` (or `line`); None when the row can't be traced to a line, as with aggregates
+#[tauri::command]
+pub fn resolve_row_to_line(
+    columns: Vec<String>,
+    row: Vec<serde_json::Value>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Option<u64>, CommandError> {
+    state
+        .log_file
+        .with_file(|f| row_line(f, &columns, &row))
         .ok_or_else(|| CommandError {
             message: "No file open".to_string(),
         })
 }
+
+fn row_line(file: &LogFile, columns: &[String], row: &[serde_json::Value]) -> Option<u64> {
+    // Joined tables may qualify names, as in `parsed.line_number`
+    let value = |name: &str| {
+        columns
+            .iter()
+            .position(|c| c.rsplit('.').next() == Some(name))
+            .and_then(|i| row.get(i))
+    };
+
+    let line_count = file.line_count();
+    if let Some(line_number) = value("line_number").and_then(serde_json::Value::as_u64) {
+        if (1..=line_count).contains(&line_number) {
+            return Some(line_number - 1);
+        }
+    }
+    let text = value("raw_line")
+        .or_else(|| value("line"))
+        .and_then(serde_json::Value::as_str)?;
+    (0..line_count).find(|&i| file.line_text(i).is_some_and(|line| line == text))
+}
+
+/// Scroll the viewer to the source line of a SQL result row and highlight it
+/// Emits `reveal-line` with the 0-based line index and returns it, or None when the row
+/// can't be traced to a line
+#[tauri::command]
+pub fn reveal_result_row(
+    columns: Vec<String>,
+    row: Vec<serde_json::Value>,
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> Result<Option<u64>, CommandError> {
+    let line = resolve_row_to_line(columns, row, state)?;
+    if let Some(line) = line {
+        app.emit("reveal-line", line).ok();
+    }
+    Ok(line)
+}
+
+/// Table a viewer line is looked up in: `parsed` once a parsed table exists, else `logs`
+fn line_table(state: &AppState) -> &'static str {
+    if state.parsed_source.lock().is_some() {
+        "parsed"
+    } else {
+        "logs"
+    }
+}
+
+/// SQL selecting the row for a 0-based viewer line, to paste into the query editor
+#[tauri::command]
+pub fn line_query_snippet(
+    line: u64,
+    table: Option<String>,
+    state: State<'_, Arc<AppState>>,
+) -> String {
+    let table = table.unwrap_or_else(|| line_table(&state).to_string());
+    format!("SELECT * FROM {} WHERE line_number = {}", table, line + 1)
+}
+
+/// The row of a parsed table a viewer line belongs to
+#[derive(Debug, Serialize)]
+pub struct LineRecord {
+    /// `parsed`, or `parse_errors` when the line failed to parse
+    pub table: String,
+    pub columns: Vec<String>,
+    pub values: Vec<serde_json::Value>,
+}
+
+/// Fetch the parsed record of a 0-based viewer line for the detail pane
+/// Continuation lines of multi-line records give the record they belong to; None when no
+/// table has been parsed or a background parse hasn't reached the line yet
+#[tauri::command]
+pub async fn get_line_record(
+    line: u64,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Option<LineRecord>, CommandError> {
+    if line_table(&state) != "parsed" {
+        return Ok(None);
+    }
+    let line_number = line + 1;
+    let reached = state.parse_job.lock().as_ref().is_none_or(|job| {
+        let progress = job.progress();
+        !progress.partial || line_number <= progress.lines_parsed
+    });
+    if !reached {
+        return Ok(None);
+    }
+
+    let queries = [
+        (
+            "parse_errors",
+            format!(
+                "SELECT * FROM parse_errors WHERE line_number = {}",
+                line_number
+            ),
+        ),
+        (
+            "parsed",
+            format!(
+                "SELECT * FROM parsed WHERE line_number <= {} ORDER BY line_number DESC LIMIT 1",
+                line_number
+            ),
+        ),
+    ];
+    for (table, query) in queries {
+        let result = state.query_engine.execute_sql(&query).await?;
+        if let Some(values) = result.rows.into_iter().next() {
+            return Ok(Some(LineRecord {
+                table: table.to_string(),
+                columns: result.columns,
+                values,
+            }));
+        }
+    }
+    Ok(None)
+}
+
+/// Records of the `parsed` table a field search matched
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldSearchResult {
+    /// 0-based viewer line of each matching record's first line, in file order, up to the
+    /// requested number
+    pub lines: Vec<u64>,
+    /// Every matching record, including those past the requested number
+    pub total: usize,
+    /// The condition the query was translated to, to carry over into the SQL editor
+    pub sql: String,
+    /// Whether a background parse hadn't reached the end of the file yet
+    pub partial: bool,
+}
+
+/// Search the fields of the `parsed` table, e.g. `level:ERROR path:/api/*`, so values only
+/// match in the column they are meant for; see `FieldQuery` for the syntax
+#[tauri::command]
+pub async fn search_fields(
+    query: String,
+    max_results: Option<usize>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<FieldSearchResult, CommandError> {
+    if line_table(&state) != "parsed" {
+        return Err(CommandError {
+            message: "Parse the file before searching its fields".to_string(),
+        });
+    }
+    let query = FieldQuery::parse(&query)?;
+    let columns = state.query_engine.table_columns("parsed").await?;
+    let condition = query.to_sql(&columns)?;
+
+    let count = state
+        .query_engine
+        .execute_sql(&format!("SELECT COUNT(*) FROM parsed WHERE {}", condition))
+        .await?;
+    let total = count
+        .rows
+        .first()
+        .and_then(|row| row.first())
+        .and_then(serde_json::Value::as_u64)
+        .unwrap_or(0) as usize;
+    let found = state
+        .query_engine
+        .execute_sql(&format!(
+            "SELECT line_number FROM parsed WHERE {} ORDER BY line_number LIMIT {}",
+            condition,
+            max_results.unwrap_or(1000)
+        ))
+        .await?;
+    let lines = found
+        .rows
+        .iter()
+        .filter_map(|row| row.first()?.as_u64())
+        .map(|line_number| line_number - 1)
+        .collect();
+    Ok(FieldSearchResult {
+        lines,
+        total,
+        sql: condition,
+        partial: state
+            .parse_job
+            .lock()
+            .as_ref()
+            .is_some_and(ParseJob::is_partial),
+    })
+}
+
+/// Translate a query in the mini language, e.g. `level=error | stats count by host`, to SQL
+/// over the `parsed` table, or the `logs` table before the file is parsed, to run with
+/// `execute_sql`
+#[tauri::command]
+pub async fn translate_query(
+    query: String,
+    state: State<'_, Arc<AppState>>,
+) -> Result<String, CommandError> {
+    let table = line_table(&state);
+    let columns = state.query_engine.table_columns(table).await?;
+    Ok(query_language::translate(&query, table, &columns)?)
+}
+
+/// List the ready-made SQL snippets, marking those the `parsed` table has the columns for
+#[tauri::command]
+pub async fn list_snippets(
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<SnippetInfo>, CommandError> {
+    let table = line_table(&state);
+    let columns = state.query_engine.table_columns(table).await?;
+    Ok(snippets::list_snippets(&columns))
+}
+
+/// SQL of a snippet adapted to the columns of the `parsed` table, to run with `execute_sql`
+/// Parameters left out take their default
+#[tauri::command]
+pub async fn render_snippet(
+    id: String,
+    params: Option<HashMap<String, String>>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<String, CommandError> {
+    let table = line_table(&state);
+    let columns = state.query_engine.table_columns(table).await?;
+    Ok(snippets::render_snippet(
+        &id,
+        &params.unwrap_or_default(),
+        table,
+        &columns,
+    )?)
+}
+
+/// Aggregate a table for a chart, compiling the spec to SQL and returning the results as
+/// series arrays ready to plot. Charts the open file's table unless another is given
+#[tauri::command]
+pub async fn get_chart_data(
+    spec: ChartSpec,
+    table: Option<String>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<ChartData, CommandError> {
+    let table = table.unwrap_or_else(|| line_table(&state).to_string());
+    let columns = state.query_engine.table_columns(&table).await?;
+    let sql = spec.to_sql(&table, &columns)?;
+    let mut result = state.query_engine.execute_sql(&sql).await?;
+    result.partial = reads_partial_parse(&state, &sql).await;
+    Ok(spec.pivot(&result, sql))
+}
+
+/// Dashboards of a parse profile or format
+#[derive(Debug, Serialize)]
+pub struct DashboardList {
+    /// `profile:<name>`, `format:<format>` or `text` for files that weren't parsed
+    pub scope: String,
+    pub dashboards: Vec<Dashboard>,
+}
+
+/// Dashboard scope of the open file: its parse profile, else the format it was parsed in
+fn dashboard_scope(state: &AppState) -> String {
+    match &*state.parsed_source.lock() {
+        Some(ParseSource::Profile(name)) => format!("profile:{}", name),
+        Some(ParseSource::Format(format)) => schema::format_key(*format),
+        None => dashboards::TEXT_SCOPE.to_string(),
+    }
+}
+
+/// List the dashboards saved for the open file's parse profile or format
+#[tauri::command]
+pub fn list_dashboards(
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> Result<DashboardList, CommandError> {
+    let scope = dashboard_scope(&state);
+    let dashboards = dashboard_store(&app)?.dashboards(&scope).to_vec();
+    Ok(DashboardList { scope, dashboards })
+}
+
+/// Save a dashboard for the open file's parse profile or format, replacing any dashboard of
+/// the same name there. Every panel is checked against the open file's table first
+#[tauri::command]
+pub async fn save_dashboard(
+    dashboard: Dashboard,
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> Result<(), CommandError> {
+    let table = line_table(&state);
+    let columns = state.query_engine.table_columns(table).await?;
+    for panel in &dashboard.panels {
+        panel
+            .spec
+            .to_sql(table, &columns)
+            .map_err(|e| CommandError {
+                message: format!("Panel \"{}\": {}", panel.title, e),
+            })?;
+    }
+    let mut store = dashboard_store(&app)?;
+    store.upsert(&dashboard_scope(&state), dashboard);
+    store.save()?;
+    Ok(())
+}
+
+/// Delete a dashboard of the open file's parse profile or format, returning whether it
+/// existed
+#[tauri::command]
+pub fn delete_dashboard(
+    name: String,
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> Result<bool, CommandError> {
+    let mut store = dashboard_store(&app)?;
+    let removed = store.remove(&dashboard_scope(&state), &name);
+    store.save()?;
+    Ok(removed)
+}
+
+/// Write parse profiles, schema overrides, saved views, alert rules and dashboards to one
+/// file to share, as TOML for a `.toml` path and JSON otherwise. `ui` holds the settings
+/// the UI keeps itself and is written along
+#[tauri::command]
+pub fn export_config(
+    path: String,
+    ui: Option<serde_json::Map<String, serde_json::Value>>,
+    app: AppHandle,
+) -> Result<(), CommandError> {
+    let path = Path::new(&path);
+    let bundle = ConfigBundle::collect(&config_dir(&app)?, ui.unwrap_or_default())?;
+    std::fs::write(path, bundle.to_text(BundleFormat::from_path(path))?)?;
+    Ok(())
+}
+
+/// Import a file written by `export_config`, merged into the configuration or replacing
+/// it. Nothing changes unless every entry in it is valid. Returns the bundle's UI settings
+/// for the UI to apply
+/// Alert rules come in without their webhooks and commands, which the summary lists,
+/// unless `allow_actions` is set after the user has reviewed them
+#[tauri::command]
+pub async fn import_config(
+    path: String,
+    mode: Option<ImportMode>,
+    allow_actions: Option<bool>,
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> Result<ImportSummary, CommandError> {
+    let text = std::fs::read_to_string(&path)?;
+    let bundle = ConfigBundle::parse(&text, BundleFormat::from_path(Path::new(&path)))?;
+    let summary = bundle.apply(
+        &config_dir(&app)?,
+        mode.unwrap_or_default(),
+        allow_actions.unwrap_or(false),
+    )?;
+    reload_config(&state, &app).await?;
+    Ok(summary)
+}
+
+/// Give the query engines, alert monitor and folder watch the configuration saved now,
+/// after an import or a rule pack changed it
+async fn reload_config(state: &AppState, app: &AppHandle) -> Result<(), CommandError> {
+    let views = view_store(app)?.views().to_vec();
+    for engine in [&state.query_engine, &state.compare_query_engine] {
+        for old in engine.views() {
+            if !views.iter().any(|v| v.name == old.name) {
+                engine.drop_view(&old.name).await;
+            }
+        }
+        engine.set_views(views.clone());
+        engine.refresh_views().await;
+    }
+    let rules = alert_store(app)?.rules().to_vec();
+    state.alerts.lock().set_rules(&rules)?;
+    if let Some(watch) = state.folder_watch.lock().as_ref() {
+        watch.set_rules(rules);
+    }
+    Ok(())
+}
+
+/// What refreshing one rule pack did, or why it failed
+#[derive(Debug, Clone, Serialize)]
+pub struct PackRefresh {
+    pub name: String,
+    pub outcome: Option<RefreshOutcome>,
+    pub error: Option<String>,
+}
+
+/// List the rule packs subscribed to, with the version each has installed
+#[tauri::command]
+pub fn list_rule_packs(app: AppHandle) -> Result<Vec<PackSubscription>, CommandError> {
+    Ok(pack_store(&app)?.packs().to_vec())
+}
+
+/// Subscribe to a rule pack served at a URL and install it, replacing any subscription of
+/// the same name
+#[tauri::command]
+pub async fn subscribe_rule_pack(
+    name: String,
+    url: String,
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> Result<RefreshOutcome, CommandError> {
+    let dir = config_dir(&app)?;
+    let mut pack = PackSubscription::new(name, url)?;
+    let outcome = tauri::async_runtime::spawn_blocking(move || {
+        let text = pack.fetch()?;
+        let mut store = PackStore::load(&dir)?;
+        // Taking over what a previous subscription of the name installed, the new content
+        // replaces it only once it has downloaded and parsed; failing before leaves both
+        // the entries and the store as they were
+        if let Some(previous) = store.remove(&pack.name) {
+            pack.installed = previous.installed;
+        }
+        let outcome = pack.install(&dir, &text)?;
+        store.upsert(pack);
+        store.save()?;
+        Ok::<_, CommandError>(outcome)
+    })
+    .await
+    .map_err(|e| CommandError {
+        message: e.to_string(),
+    })??;
+    reload_config(&state, &app).await?;
+    Ok(outcome)
+}
+
+/// Download the subscribed rule packs again, or only the one named, installing new
+/// versions of packs that aren't pinned
+#[tauri::command]
+pub async fn refresh_rule_packs(
+    name: Option<String>,
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> Result<Vec<PackRefresh>, CommandError> {
+    let dir = config_dir(&app)?;
+    let refreshed = tauri::async_runtime::spawn_blocking(move || {
+        let mut store = PackStore::load(&dir)?;
+        if let Some(name) = &name {
+            store.find_mut(name)?;
+        }
+        let mut refreshed = Vec::new();
+        for pack in store.packs_mut() {
+            if name.as_ref().is_some_and(|name| *name != pack.name) {
+                continue;
+            }
+            let result = pack.fetch().and_then(|text| pack.install(&dir, &text));
+            refreshed.push(PackRefresh {
+                name: pack.name.clone(),
+                error: result.as_ref().err().map(ToString::to_string),
+                outcome: result.ok(),
+            });
+        }
+        store.save()?;
+        Ok::<_, CommandError>(refreshed)
+    })
+    .await
+    .map_err(|e| CommandError {
+        message: e.to_string(),
+    })??;
+    reload_config(&state, &app).await?;
+    Ok(refreshed)
+}
+
+/// Pin a rule pack to the version it has installed, so refreshes leave it in place, or
+/// unpin it to follow its URL again
+#[tauri::command]
+pub fn pin_rule_pack(
+    name: String,
+    pin: bool,
+    app: AppHandle,
+) -> Result<PackSubscription, CommandError> {
+    let mut store = pack_store(&app)?;
+    let pack = store.find_mut(&name)?;
+    pack.pinned = match (pin, &pack.installed) {
+        (false, _) => None,
+        (true, Some(installed)) => Some(installed.sha256.clone()),
+        (true, None) => return Err(PackError::NotInstalled(name).into()),
+    };
+    let pack = pack.clone();
+    store.save()?;
+    Ok(pack)
+}
+
+/// Unsubscribe from a rule pack and remove what it installed, returning whether it existed
+#[tauri::command]
+pub async fn unsubscribe_rule_pack(
+    name: String,
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> Result<bool, CommandError> {
+    let dir = config_dir(&app)?;
+    let mut store = PackStore::load(&dir)?;
+    let Some(mut pack) = store.remove(&name) else {
+        return Ok(false);
+    };
+    pack.uninstall(&dir)?;
+    store.save()?;
+    reload_config(&state, &app).await?;
+    Ok(true)
+}
+
+/// List the built-in highlight palettes with their colors for each theme
+#[tauri::command]
+pub fn list_palettes() -> Vec<Palette> {
+    palette::PALETTES.to_vec()
+}
+
+#[tauri::command]
+pub fn get_palette_config(app: AppHandle) -> Result<PaletteConfig, CommandError> {
+    Ok(palette_store(&app)?.config().clone())
+}
+
+/// Choose the highlight palette, theme and per-pattern colors
+#[tauri::command]
+pub fn set_palette_config(config: PaletteConfig, app: AppHandle) -> Result<(), CommandError> {
+    let mut store = palette_store(&app)?;
+    store.set_config(config)?;
+    Ok(store.save()?)
+}
+
+/// Fill in the colors of highlight rules that don't set one, as exports will show them
+#[tauri::command]
+pub fn assign_highlight_colors(
+    mut rules: Vec<HighlightRule>,
+    theme: Option<Theme>,
+    app: AppHandle,
+) -> Result<Vec<HighlightRule>, CommandError> {
+    palette_store(&app)?.config().assign(&mut rules, theme);
+    Ok(rules)
+}
+
+/// Number of lines sampled when detecting a structured format
+const PARSE_DETECT_SAMPLE: u64 = 50;
+
+/// How the `parsed` table was produced, kept to rebuild it when its schema override changes
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseSource {
+    Format(ParserKind),
+    /// A saved parse profile, by name
+    Profile(String),
+}
+
+/// Result of parsing the open file into the `parsed` table
+#[derive(Debug, Serialize)]
+pub struct ParseSummary {
+    pub table: String,
+    /// Built-in format parsed, None for a profile's custom regex or grok pattern
+    pub format: Option<ParserKind>,
+    /// Saved parse profile used, if any
+    pub profile: Option<String>,
+    pub columns: Vec<Column>,
+    pub rows: usize,
+    pub failed_lines: usize,
+    /// Table of the lines that failed to parse, with their error and raw text
+    pub errors_table: String,
+    /// Share of parse attempts that produced a row
+    pub success_rate: f64,
+    /// Whether a saved schema override was applied
+    pub overridden: bool,
+}
+
+/// Parse the open file with a structured parser and register the typed `parsed` table
+/// The format is detected from the first lines when not given
+#[tauri::command]
+pub async fn parse_file(
+    format: Option<ParserKind>,
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> Result<ParseSummary, CommandError> {
+    register_parsed_table(&state, &app, format.map(ParseSource::Format)).await
+}
+
+/// Parse the open file with a saved profile and register the typed `parsed` table
+#[tauri::command]
+pub async fn apply_parse_profile(
+    name: String,
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> Result<ParseSummary, CommandError> {
+    register_parsed_table(&state, &app, Some(ParseSource::Profile(name))).await
+}
+
+/// Parser and schema override resolved for a parse source
+struct ParsePlan {
+    source: ParseSource,
+    format: Option<ParserKind>,
+    parser: Box<dyn LogParser>,
+    schema_override: Option<SchemaOverride>,
+}
+
+/// Resolve how to parse the open file, detecting the format when no source is given
+/// An override saved for the file wins over the profile's own, then one saved for the format
+fn plan_parse(
+    log_file: &SharedLogFile,
+    app: &AppHandle,
+    source: Option<ParseSource>,
+) -> Result<ParsePlan, CommandError> {
+    let (source, format, parser, profile_override) = match source {
+        Some(ParseSource::Profile(name)) => {
+            let library = profile_library(app)?;
+            let profile = library.find(&name).ok_or_else(|| CommandError {
+                message: format!("No parse profile named \"{}\"", name),
+            })?;
+            let parser = profile
+                .parser
+                .create()
+                .map_err(|message| CommandError { message })?;
+            let format = profile.parser.format();
+            let profile_override = profile.schema_override.clone();
+            (ParseSource::Profile(name), format, parser, profile_override)
+        }
+        source => {
+            let format = match source {
+                Some(ParseSource::Format(format)) => Some(format),
+                _ => None,
+            };
+            let (format, parser) = detect_parser(log_file, format)?;
+            (ParseSource::Format(format), Some(format), parser, None)
+        }
+    };
+
+    let store = override_store(app)?;
+    let path = log_file
+        .with_file(|f| f.path().to_string())
+        .unwrap_or_default();
+    let schema_override = store
+        .get(&schema::file_key(&path))
+        .cloned()
+        .or(profile_override)
+        .or_else(|| match &source {
+            ParseSource::Format(format) => store.get(&schema::format_key(*format)).cloned(),
+            ParseSource::Profile(_) => None,
+        });
+    Ok(ParsePlan {
+        source,
+        format,
+        parser,
+        schema_override,
+    })
+}
+
+/// Text of a 1-based line of the open file, for the `raw_line` column of parsed tables
+fn raw_line_of(log_file: &SharedLogFile, line_number: u64) -> Option<String> {
+    log_file
+        .with_file(|f| {
+            f.line_text(line_number.checked_sub(1)?)
+                .map(|l| l.into_owned())
+        })
+        .flatten()
+}
+
+/// Register an opened file's lines as the `logs` table; a gzip file's lines come from the
+/// text it was decompressed to when opened rather than from decompressing it again
+async fn register_logs(
+    engine: &QueryEngine,
+    log_file: &SharedLogFile,
+    path: &str,
+) -> Result<(), QueryError> {
+    if !indexer::is_gzip(&mut File::open(path)?)? {
+        engine.register_table(path, "logs").await?;
+        return Ok(());
+    }
+    let table = log_file
+        .with_file(|f| QueryEngine::line_table(f.data()))
+        .ok_or(QueryError::NoFile)??;
+    engine.register_line_table(table, "logs").await
+}
+
+/// Parse the open file, apply its schema override and register the `parsed` table
+async fn register_parsed_table(
+    state: &AppState,
+    app: &AppHandle,
+    source: Option<ParseSource>,
+) -> Result<ParseSummary, CommandError> {
+    state.parse_job.lock().take();
+    let ParsePlan {
+        source,
+        format,
+        mut parser,
+        schema_override,
+    } = plan_parse(&state.log_file, app, source)?;
+    let mut table = parse_lines_of(&state.log_file, parser.as_mut(), None)?;
+    if let Some(schema_override) = &schema_override {
+        schema_override
+            .apply(&mut table)
+            .map_err(|message| CommandError { message })?;
+    }
+    let raw_line = |line_number| raw_line_of(&state.log_file, line_number);
+    state
+        .query_engine
+        .register_parsed(&table, "parsed", &raw_line)
+        .await?;
+    let errors = ParsedTable::errors_table(&table.failures);
+    state
+        .query_engine
+        .register_parsed(&errors, "parse_errors", &raw_line)
+        .await?;
+
+    let profile = match &source {
+        ParseSource::Profile(name) => Some(name.clone()),
+        ParseSource::Format(_) => None,
+    };
+    *state.parsed_source.lock() = Some(source);
+
+    Ok(ParseSummary {
+        table: "parsed".to_string(),
+        format,
+        profile,
+        rows: table.records.len(),
+        failed_lines: table.failures.len(),
+        errors_table: "parse_errors".to_string(),
+        success_rate: table.success_rate(),
+        columns: table.columns,
+        overridden: schema_override.is_some(),
+    })
+}
+
+/// Files with more lines than this are parsed in the background when a table is
+/// registered for them automatically
+const BACKGROUND_PARSE_LINES: u64 = 1_000_000;
+
+/// Register the `parsed` table now, or start parsing in the background for large files
+/// Returns the summary when the table was registered right away
+async fn register_or_start_parse(
+    state: &Arc<AppState>,
+    app: &AppHandle,
+    source: Option<ParseSource>,
+) -> Result<Option<ParseSummary>, CommandError> {
+    let line_count = state.log_file.with_file(|f| f.line_count()).unwrap_or(0);
+    if line_count > BACKGROUND_PARSE_LINES {
+        start_parse_job(state, app, source)?;
+        Ok(None)
+    } else {
+        register_parsed_table(state, app, source).await.map(Some)
+    }
+}
+
+/// Parse the open file on a background thread, registering the `parsed` and `parse_errors`
+/// tables after every chunk and emitting `parse-progress` events
+fn start_parse_job(
+    state: &Arc<AppState>,
+    app: &AppHandle,
+    source: Option<ParseSource>,
+) -> Result<ParseSource, CommandError> {
+    state.parse_job.lock().take();
+    let plan = plan_parse(&state.log_file, app, source)?;
+    let total_lines = state.log_file.with_file(|f| f.line_count()).unwrap_or(0);
+
+    let read_state = Arc::downgrade(state);
+    let read = move |range: std::ops::Range<u64>, feed: &mut dyn FnMut(u64, &str)| {
+        read_state
+            .upgrade()
+            .and_then(|state| {
+                state.log_file.with_file(|f| {
+                    for i in range {
+                        if let Some(line) = f.line_text(i) {
+                            feed(i + 1, &line);
+                        }
+                    }
+                })
+            })
+            .is_some()
+    };
+
+    let publish_state = Arc::downgrade(state);
+    let mut parsed_built = ParsedBatches::default();
+    let mut errors = ParsedTable::default();
+    let mut errors_built = ParsedBatches::default();
+    let publish = move |table: &ParsedTable, _partial: bool| {
+        let state = publish_state
+            .upgrade()
+            .ok_or_else(|| "The app is shutting down".to_string())?;
+        let new_errors = ParsedTable::errors_table(&table.failures[errors.records.len()..]);
+        errors.columns = new_errors.columns;
+        errors.records.extend(new_errors.records);
+
+        let raw_line = |line_number| raw_line_of(&state.log_file, line_number);
+        tauri::async_runtime::block_on(async {
+            state
+                .query_engine
+                .register_parsed_incremental(table, &mut parsed_built, "parsed", &raw_line)
+                .await?;
+            state
+                .query_engine
+                .register_parsed_incremental(&errors, &mut errors_built, "parse_errors", &raw_line)
+                .await
+        })
+        .map_err(|e| e.to_string())
+    };
+
+    let progress_app = app.clone();
+    let operation = start_operation(app, OperationKind::Parse, "parsing", "Parsing...");
+    let cancel = operation.token().clone();
+    let mut operation = Some(operation);
+    let on_progress = move |progress: ParseProgress| {
+        let message = format!(
+            "Parsed {} of {} lines",
+            progress.lines_parsed, progress.total_lines
+        );
+        match progress.state {
+            ParseJobState::Running => {
+                if let Some(operation) = &operation {
+                    operation.update("parsing", progress.progress as f64, message);
+                }
+            }
+            ParseJobState::Complete => {
+                if let Some(operation) = operation.take() {
+                    operation.finish(message);
+                }
+            }
+            // Dropping the operation reports it stopped
+            ParseJobState::Cancelled | ParseJobState::Failed => operation = None,
+        }
+        progress_app.emit("parse-progress", progress).ok();
+    };
+
+    let job = ParseJob::start(
+        ChunkedParse::new(plan.parser, plan.schema_override),
+        total_lines,
+        cancel,
+        read,
+        publish,
+        on_progress,
+    );
+    *state.parse_job.lock() = Some(job);
+    *state.parsed_source.lock() = Some(plan.source.clone());
+    Ok(plan.source)
+}
+
+/// Parse the open file in the background, registering the typed `parsed` table chunk by
+/// chunk so it can be queried before parsing finishes
+/// A profile takes precedence over a format; with neither, the format is detected
+#[tauri::command]
+pub fn parse_file_background(
+    format: Option<ParserKind>,
+    profile: Option<String>,
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> Result<(), CommandError> {
+    let source = profile
+        .map(ParseSource::Profile)
+        .or(format.map(ParseSource::Format));
+    start_parse_job(state.inner(), &app, source)?;
+    Ok(())
+}
+
+/// Stop a background parse after its current chunk, keeping what was parsed so far
+/// Returns the final progress, or None if no parse was started
+#[tauri::command]
+pub fn cancel_parse(state: State<'_, Arc<AppState>>) -> Option<ParseProgress> {
+    let mut job = state.parse_job.lock();
+    job.as_mut().map(|job| {
+        job.cancel();
+        job.progress()
+    })
+}
+
+/// Progress of the latest background parse
+#[tauri::command]
+pub fn get_parse_progress(state: State<'_, Arc<AppState>>) -> Option<ParseProgress> {
+    state.parse_job.lock().as_ref().map(ParseJob::progress)
+}
+
+/// Directory settings such as schema overrides and parse profiles are saved in
+fn config_dir(app: &AppHandle) -> Result<PathBuf, CommandError> {
+    app.path().app_config_dir().map_err(|e| CommandError {
+        message: e.to_string(),
+    })
+}
+
+/// Directory caches too large for the config directory, such as deep indexes, are kept in
+fn data_dir(app: &AppHandle) -> Result<PathBuf, CommandError> {
+    app.path().app_data_dir().map_err(|e| CommandError {
+        message: e.to_string(),
+    })
+}
+
+/// Start an operation whose progress is emitted as `operation-progress`, cancellable with
+/// `cancel_operation` until it ends
+fn start_operation(app: &AppHandle, kind: OperationKind, phase: &str, message: &str) -> Operation {
+    let emit_app = app.clone();
+    let sink: ProgressSink = Arc::new(move |progress: &OperationProgress| {
+        emit_app.emit(OPERATION_PROGRESS_EVENT, progress).ok();
+    });
+    let state = app.state::<Arc<AppState>>();
+    state.operations.start(kind, phase, message, sink)
+}
+
+/// Cancel a running operation by the id its progress events carry
+/// Returns false if it already ended
+#[tauri::command]
+pub fn cancel_operation(id: u64, state: State<'_, Arc<AppState>>) -> bool {
+    state.operations.cancel(id)
+}
+
+/// Operations still running, for a busy indicator
+#[tauri::command]
+pub fn list_operations(state: State<'_, Arc<AppState>>) -> Vec<RunningOperation> {
+    state.operations.running()
+}
+
+/// Views saved in the app config directory
+fn view_store(app: &AppHandle) -> Result<ViewStore, CommandError> {
+    Ok(ViewStore::load(&config_dir(app)?)?)
+}
+
+/// Dashboards saved in the app config directory
+fn dashboard_store(app: &AppHandle) -> Result<DashboardStore, CommandError> {
+    Ok(DashboardStore::load(&config_dir(app)?)?)
+}
+
+/// Alert rules saved in the app config directory
+fn alert_store(app: &AppHandle) -> Result<AlertStore, CommandError> {
+    Ok(AlertStore::load(&config_dir(app)?)?)
+}
+
+/// Rule packs subscribed to in the app config directory
+fn pack_store(app: &AppHandle) -> Result<PackStore, CommandError> {
+    Ok(PackStore::load(&config_dir(app)?)?)
+}
+
+/// Highlight palette settings in the app config directory
+fn palette_store(app: &AppHandle) -> Result<PaletteStore, CommandError> {
+    Ok(PaletteStore::load(&config_dir(app)?)?)
+}
+
+/// Directories approved for opening files from
+fn path_policy(app: &AppHandle) -> Result<PathPolicy, CommandError> {
+    Ok(PathPolicy::load(&config_dir(app)?)?)
+}
+
+/// A path refused by the policy, emitted so the UI can ask whether to approve its directory
+#[derive(Debug, Clone, Serialize)]
+pub struct PathApproval {
+    pub path: PathBuf,
+    pub directory: PathBuf,
+}
+
+/// Check a path against the approved directories before it is opened, returning it
+/// resolved to open in its place, so a symlink swapped in after the check leads nowhere new
+/// A path outside them is refused; the UI asks whether to approve its directory and calls
+/// `approve_directory` before trying again
+fn authorize_path(app: &AppHandle, path: &str) -> Result<String, CommandError> {
+    match path_policy(app)?.check(Path::new(path)) {
+        Ok(resolved) => resolved
+            .into_os_string()
+            .into_string()
+            .map_err(|resolved| CommandError {
+                message: format!("{} is not a valid UTF-8 path", resolved.to_string_lossy()),
+            }),
+        Err(PolicyError::NotApproved { path, directory }) => {
+            let message = format!("{} is outside the approved directories", path.display());
+            app.emit("path-approval-required", PathApproval { path, directory })
+                .ok();
+            Err(CommandError { message })
+        }
+        Err(err) => Err(CommandError {
+            message: err.to_string(),
+        }),
+    }
+}
+
+/// Directories files may be opened from without confirmation
+#[tauri::command]
+pub fn list_approved_directories(app: AppHandle) -> Result<Vec<PathBuf>, CommandError> {
+    Ok(path_policy(&app)?.approved().to_vec())
+}
+
+/// Approve a directory and everything below it, returning it with symlinks resolved
+#[tauri::command]
+pub fn approve_directory(directory: String, app: AppHandle) -> Result<PathBuf, CommandError> {
+    let mut policy = path_policy(&app)?;
+    let approved = policy.approve(Path::new(&directory))?;
+    policy.save()?;
+    Ok(approved)
+}
+
+/// Withdraw approval of a directory, returning whether it was approved
+#[tauri::command]
+pub fn revoke_directory(directory: String, app: AppHandle) -> Result<bool, CommandError> {
+    let mut policy = path_policy(&app)?;
+    let revoked = policy.revoke(Path::new(&directory));
+    policy.save()?;
+    Ok(revoked)
+}
+
+/// Schema overrides saved in the app config directory
+fn override_store(app: &AppHandle) -> Result<OverrideStore, CommandError> {
+    Ok(OverrideStore::load(&config_dir(app)?)?)
+}
+
+/// Parse profiles saved in the app config directory
+fn profile_library(app: &AppHandle) -> Result<ProfileLibrary, CommandError> {
+    Ok(ProfileLibrary::load(&config_dir(app)?)?)
+}
+
+/// What a schema override applies to
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OverrideScope {
+    /// Only the open file
+    File,
+    /// Every file parsed in the built-in format of the current `parsed` table
+    Format,
+    /// The parse profile that produced the current `parsed` table
+    Profile,
+}
+
+/// Where an override scope is saved for the current file and `parsed` table
+enum OverrideTarget {
+    /// Key in the override store
+    Store(String),
+    /// Name of a parse profile
+    Profile(String),
+}
+
+fn override_target(state: &AppState, scope: OverrideScope) -> Result<OverrideTarget, CommandError> {
+    let source = state.parsed_source.lock().clone();
+    let target = match (scope, source) {
+        (OverrideScope::File, _) => state
+            .log_file
+            .with_file(|f| OverrideTarget::Store(schema::file_key(f.path()))),
+        (OverrideScope::Format, Some(ParseSource::Format(format))) => {
+            Some(OverrideTarget::Store(schema::format_key(format)))
+        }
+        (OverrideScope::Profile, Some(ParseSource::Profile(name))) => {
+            Some(OverrideTarget::Profile(name))
+        }
+        _ => None,
+    };
+    target.ok_or_else(|| CommandError {
+        message: format!("No {:?} to save a schema override for", scope).to_lowercase(),
+    })
+}
+
+/// Get the schema override saved for the open file, its parsed format or its parse profile
+#[tauri::command]
+pub fn get_schema_override(
+    scope: OverrideScope,
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> Result<Option<SchemaOverride>, CommandError> {
+    Ok(match override_target(&state, scope)? {
+        OverrideTarget::Store(key) => override_store(&app)?.get(&key).cloned(),
+        OverrideTarget::Profile(name) => profile_library(&app)?
+            .find(&name)
+            .and_then(|p| p.schema_override.clone()),
+    })
+}
+
+/// Lines parsed to check a schema override before it is saved
+const OVERRIDE_CHECK_LINES: u64 = 1000;
+
+/// Save (or, with None, remove) a schema override and rebuild the `parsed` table with it
+/// Returns the new table summary when the table was rebuilt right away rather than in the
+/// background
+#[tauri::command]
+pub async fn set_schema_override(
+    scope: OverrideScope,
+    schema_override: Option<SchemaOverride>,
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> Result<Option<ParseSummary>, CommandError> {
+    let target = override_target(&state, scope)?;
+    let source = state.parsed_source.lock().clone();
+
+    // Check the override against the start of the current table before saving it
+    if let (Some(source), Some(schema_override)) = (&source, &schema_override) {
+        let mut plan = plan_parse(&state.log_file, &app, Some(source.clone()))?;
+        let mut table = parse_lines_of(
+            &state.log_file,
+            plan.parser.as_mut(),
+            Some(OVERRIDE_CHECK_LINES),
+        )?;
+        schema_override
+            .apply(&mut table)
+            .map_err(|message| CommandError { message })?;
+    }
+
+    match target {
+        OverrideTarget::Store(key) => {
+            let mut store = override_store(&app)?;
+            store.set(key, schema_override);
+            store.save()?;
+        }
+        OverrideTarget::Profile(name) => {
+            let mut library = profile_library(&app)?;
+            let profile = library.find_mut(&name).ok_or_else(|| CommandError {
+                message: format!("No parse profile named \"{}\"", name),
+            })?;
+            profile.schema_override = schema_override;
+            library.save()?;
+        }
+    }
+
+    match source {
+        Some(source) => register_or_start_parse(state.inner(), &app, Some(source)).await,
+        None => Ok(None),
+    }
+}
+
+/// List the saved parse profiles in the order they are matched against paths
+#[tauri::command]
+pub fn list_parse_profiles(app: AppHandle) -> Result<Vec<ParseProfile>, CommandError> {
+    Ok(profile_library(&app)?.profiles().to_vec())
+}
+
+/// Save a parse profile, replacing any profile of the same name
+#[tauri::command]
+pub fn save_parse_profile(profile: ParseProfile, app: AppHandle) -> Result<(), CommandError> {
+    profile
+        .validate()
+        .map_err(|message| CommandError { message })?;
+    let mut library = profile_library(&app)?;
+    library.upsert(profile);
+    Ok(library.save()?)
+}
+
+/// Most lines a parse preview samples
+const MAX_PREVIEW_LINES: u64 = 10_000;
+
+/// Which lines a parse preview samples
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PreviewSampling {
+    /// The first lines of the file
+    #[default]
+    First,
+    /// Lines spread across the file; the same file always gives the same lines, and
+    /// multi-line entries are cut apart
+    Random,
+}
+
+/// Try a candidate parse profile on a sample of the open file without registering a table
+#[tauri::command]
+pub fn preview_parse(
+    profile: ParseProfile,
+    sample_size: u64,
+    sampling: Option<PreviewSampling>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<ParsePreview, CommandError> {
+    state
+        .log_file
+        .with_file(|f| {
+            let count = sample_size.min(MAX_PREVIEW_LINES).min(f.line_count());
+            let indices = match sampling.unwrap_or_default() {
+                PreviewSampling::First => (0..count).collect(),
+                PreviewSampling::Random => random_line_indices(f.line_count(), count),
+            };
+            profile.preview(
+                indices
+                    .into_iter()
+                    .filter_map(|i| Some((i + 1, f.line_text(i)?))),
+            )
+        })
+        .ok_or_else(|| CommandError {
+            message: "No file open".to_string(),
+        })?
+        .map_err(|message| CommandError { message })
+}
+
+/// `count` distinct line indices below `line_count` in ascending order, chosen by a
+/// generator seeded with the line count
+fn random_line_indices(line_count: u64, count: u64) -> Vec<u64> {
+    if count >= line_count {
+        return (0..line_count).collect();
+    }
+    let mut chosen = std::collections::BTreeSet::new();
+    let mut draw = line_count;
+    while (chosen.len() as u64) < count {
+        chosen.insert(splitmix64(draw) % line_count);
+        draw = draw.wrapping_add(1);
+    }
+    chosen.into_iter().collect()
+}
+
+/// Delete a parse profile, returning whether it existed
+#[tauri::command]
+pub fn delete_parse_profile(name: String, app: AppHandle) -> Result<bool, CommandError> {
+    let mut library = profile_library(&app)?;
+    let removed = library.remove(&name);
+    if removed {
+        library.save()?;
+    }
+    Ok(removed)
+}
+
+/// Pick a parser for the open file from its first lines, detecting the format if not given
+fn detect_parser(
+    log_file: &SharedLogFile,
+    format: Option<ParserKind>,
+) -> Result<(ParserKind, Box<dyn LogParser>), CommandError> {
+    log_file
+        .with_file(|f| {
+            let sample: Vec<_> = (0..f.line_count().min(PARSE_DETECT_SAMPLE))
+                .filter_map(|i| f.line_text(i))
+                .collect();
+            let format =
+                format.or_else(|| ParserKind::detect(sample.iter().map(|l| l.as_ref())))?;
+            Some((format, format.create_for_sample(&sample)))
+        })
+        .ok_or_else(|| CommandError {
+            message: "No file open".to_string(),
+        })?
+        .ok_or_else(|| CommandError {
+            message: "Could not detect a structured format".to_string(),
+        })
+}
+
+/// Run a parser over the open file, or only its first `limit` lines
+fn parse_lines_of(
+    log_file: &SharedLogFile,
+    parser: &mut dyn LogParser,
+    limit: Option<u64>,
+) -> Result<ParsedTable, CommandError> {
+    log_file
+        .with_file(|f| {
+            let count = limit.map_or(f.line_count(), |limit| limit.min(f.line_count()));
+            let lines = (0..count).filter_map(|i| Some((i + 1, f.line_text(i)?)));
+            parsers::parse_lines(parser, lines)
+        })
+        .ok_or_else(|| CommandError {
+            message: "No file open".to_string(),
+        })
+}
+
+/// Get the total line count
+#[tauri::command]
+pub fn get_line_count(state: State<'_, Arc<AppState>>) -> Result<u64, CommandError> {
+    state
+        .log_file
+        .with_file(|f| f.line_count())
+        .ok_or_else(|| CommandError {
+            message: "No file open".to_string(),
+        })
+}
+
+/// Distribution of line lengths and the longest lines, to spot dumps that break rendering
+#[tauri::command]
+pub fn get_line_length_stats(
+    top_n: Option<usize>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<LineLengthStats, CommandError> {
+    let top_n = top_n.unwrap_or(20);
+    state
+        .log_file
+        .with_file(|f| analysis::line_length_stats(f, top_n))
+        .ok_or_else(|| CommandError {
+            message: "No file open".to_string(),
+        })
+}
+
+/// Lines or errors per hour of each day, to spot periodic jobs and nightly failures
+#[tauri::command]
+pub fn get_activity_heatmap(
+    options: Option<HeatmapOptions>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<ActivityHeatmap, CommandError> {
+    let options = options.unwrap_or_default();
+    state
+        .log_file
+        .with_file(|f| analysis::activity_heatmap(f, &options))
+        .ok_or_else(|| CommandError {
+            message: "No file open".to_string(),
+        })
+}
+
+/// Compare how often each level and message template occurs in two time ranges, such as
+/// before and after a deploy, listing what rose and fell most
+#[tauri::command]
+pub fn compare_time_windows(
+    options: RateCompareOptions,
+    state: State<'_, Arc<AppState>>,
+) -> Result<RateComparison, CommandError> {
+    Ok(state
+        .log_file
+        .with_file(|f| timeline::compare_rates(f, &options))
+        .ok_or_else(|| CommandError {
+            message: "No file open".to_string(),
+        })??)
+}
+
+/// Find bursts of errors and warnings and suggest the largest as windows to investigate,
+/// with the templates dominating each
+#[tauri::command]
+pub fn find_bursts(
+    options: Option<BurstOptions>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<BurstReport, CommandError> {
+    let options = options.unwrap_or_default();
+    Ok(state
+        .log_file
+        .with_file(|f| timeline::find_bursts(f, &options))
+        .ok_or_else(|| CommandError {
+            message: "No file open".to_string(),
+        })??)
+}
+
+/// First entry of each distinct message template in a time range, optionally only those
+/// that never occurred before it, to see what started going wrong when
+#[tauri::command]
+pub fn find_first_occurrences(
+    options: Option<FirstOccurrenceOptions>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<FirstOccurrenceReport, CommandError> {
+    let options = options.unwrap_or_default();
+    Ok(state
+        .log_file
+        .with_file(|f| timeline::first_occurrences(f, &options))
+        .ok_or_else(|| CommandError {
+            message: "No file open".to_string(),
+        })??)
+}
+
+/// Rarest lines in a time window scored against the whole file, optionally only those never
+/// seen before it
+#[tauri::command]
+pub fn find_rare_lines(
+    options: Option<RarityOptions>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<RarityReport, CommandError> {
+    let options = options.unwrap_or_default();
+    Ok(state
+        .log_file
+        .with_file(|f| timeline::rare_lines(f, &options))
+        .ok_or_else(|| CommandError {
+            message: "No file open".to_string(),
+        })??)
+}
+
+/// Extract a numeric metric, such as queue depth, from the lines holding it by regex or
+/// JSON path, as a time series with its slope and change points
+#[tauri::command]
+pub fn extract_metric(
+    options: MetricOptions,
+    state: State<'_, Arc<AppState>>,
+) -> Result<MetricSeries, CommandError> {
+    Ok(state
+        .log_file
+        .with_file(|f| metrics::extract_metric(f, &options))
+        .ok_or_else(|| CommandError {
+            message: "No file open".to_string(),
+        })??)
+}
+
+/// Latency percentiles per endpoint, customer, host or other dimension read from the same
+/// lines as the durations, with the slowest lines
+#[tauri::command]
+pub fn get_latency_breakdown(
+    options: LatencyOptions,
+    state: State<'_, Arc<AppState>>,
+) -> Result<LatencyReport, CommandError> {
+    Ok(state
+        .log_file
+        .with_file(|f| metrics::latency_breakdown(f, &options))
+        .ok_or_else(|| CommandError {
+            message: "No file open".to_string(),
+        })??)
+}
+
+/// Follow each request or other key through an ordered sequence of patterns and report
+/// completion, where runs stop and how long each stage takes
+#[tauri::command]
+pub fn analyse_funnel(
+    options: FunnelOptions,
+    state: State<'_, Arc<AppState>>,
+) -> Result<FunnelReport, CommandError> {
+    Ok(state
+        .log_file
+        .with_file(|f| funnel::analyse_funnel(f, &options))
+        .ok_or_else(|| CommandError {
+            message: "No file open".to_string(),
+        })??)
+}
+
+/// Rebuild each entity's state timeline and flag invalid transitions and entities stuck in
+/// a state, with the lines to jump to
+#[tauri::command]
+pub fn analyse_states(
+    options: StateMachineOptions,
+    state: State<'_, Arc<AppState>>,
+) -> Result<StateMachineReport, CommandError> {
+    Ok(state
+        .log_file
+        .with_file(|f| states::analyse_states(f, &options))
+        .ok_or_else(|| CommandError {
+            message: "No file open".to_string(),
+        })??)
+}
+
+/// Find the most repeated lines, exactly or after masking numbers and ids, optionally with
+/// the first occurrence of every distinct line for a deduplicated view
+#[tauri::command]
+pub fn find_duplicates(
+    options: Option<DuplicateOptions>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<DuplicateReport, CommandError> {
+    let options = options.unwrap_or_default();
+    state
+        .log_file
+        .with_file(|f| analysis::find_duplicates(f, &options))
+        .ok_or_else(|| CommandError {
+            message: "No file open".to_string(),
+        })
+}
+
+/// Flag high-entropy tokens that look like leaked keys or credentials
+/// Findings carry the line and byte range of each token for the redaction step
+#[tauri::command]
+pub fn scan_secrets(
+    options: Option<SecretScanOptions>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<SecretScanReport, CommandError> {
+    let options = options.unwrap_or_default();
+    state
+        .log_file
+        .with_file(|f| analysis::scan_secrets(f, &options))
+        .ok_or_else(|| CommandError {
+            message: "No file open".to_string(),
+        })
+}
+
+/// Open a second file for the split/compare view
+/// Its lines are queryable as `logs` with `execute_sql` on the compare file
+#[tauri::command]
+pub async fn open_compare_file(
+    path: String,
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> Result<FileInfo, CommandError> {
+    let path = authorize_path(&app, &path)?;
+    state.compare_file.open(&path)?;
+
+    let (file_size, line_count) = state
+        .compare_file
+        .with_file(|f| (f.file_size(), f.line_count()))
+        .unwrap_or((0, 0));
+    let format = state
+        .compare_file
+        .with_file(|f| QueryEngine::detect_format_bytes(f.data()))
+        .unwrap_or(FileFormat::PlainText);
+
+    state.compare_query_engine.clear().await;
+    state
+        .query_engine
+        .drop_provider(correlate::COMPARE_TABLE)
+        .await;
+    if let Err(e) = register_logs(&state.compare_query_engine, &state.compare_file, &path).await {
+        tracing::warn!(path = %path, error = %e, "failed to register the compare logs table");
+    }
+    state.journal(JournalEvent::CompareOpened { path: path.clone() });
+
+    Ok(FileInfo {
+        path,
+        size: file_size,
+        line_count,
+        format: format!("{:?}", format),
+        profile: None,
+    })
+}
+
+/// Close the compare file
+#[tauri::command]
+pub async fn close_compare_file(state: State<'_, Arc<AppState>>) -> Result<(), CommandError> {
+    state.compare_file.close();
+    state.compare_query_engine.clear().await;
+    state
+        .query_engine
+        .drop_provider(correlate::COMPARE_TABLE)
+        .await;
+    state.journal(JournalEvent::CompareClosed);
+    Ok(())
+}
+
+/// Get a range of lines from the compare file, sanitized for display
+#[tauri::command]
+pub fn get_compare_lines(
+    start: u64,
+    count: u64,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<String>, CommandError> {
+    let mut lines = state
+        .compare_file
+        .with_file(|f| f.get_lines(start, count))
+        .ok_or_else(|| CommandError {
+            message: "No compare file open".to_string(),
+        })??;
+    state.sanitize.lock().apply_all(&mut lines);
+    Ok(lines)
+}
+
+/// Map a line in the main file to the nearest-time line in the compare file
+/// Returns None when either side has no parseable timestamps near the position
+#[tauri::command]
+pub fn sync_position(
+    file_a_line: u64,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Option<u64>, CommandError> {
+    let timestamp = state
+        .log_file
+        .with_file(|f| f.effective_timestamp(file_a_line, 1000))
+        .ok_or_else(|| CommandError {
+            message: "No file open".to_string(),
+        })?;
+
+    let Some((_, ts)) = timestamp else {
+        return Ok(None);
+    };
+
+    state
+        .compare_file
+        .with_file(|f| f.find_line_by_time(ts))
+        .ok_or_else(|| CommandError {
+            message: "No compare file open".to_string(),
+        })
+}
+
+/// Export the current view as a standalone HTML file
+#[tauri::command]
+pub fn export_html(
+    output_path: String,
+    mut options: HtmlExportOptions,
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> Result<ExportSummary, CommandError> {
+    let palette = palette_store(&app)?;
+    let config = palette.config();
+    let theme = options.theme.unwrap_or(config.theme);
+    config.assign(&mut options.highlights, Some(theme));
+    options.theme = Some(theme);
+
+    // Live sources have nothing on disk to hash, so their exports go without
+    let mut manifest = hash_manifest(&app)?;
+    options.source_sha256 = state
+        .opened_hash
+        .lock()
+        .as_ref()
+        .map(|record| record.sha256.clone());
+
+    let operation = start_operation(&app, OperationKind::Export, "rendering", "Rendering...");
+    let (html, lines_written, truncated) = state
+        .log_file
+        .with_file(|f| export::render_html(f, &options, || operation.is_cancelled()))
+        .ok_or_else(|| CommandError {
+            message: "No file open".to_string(),
+        })??;
+
+    operation.update(
+        "writing",
+        0.6,
+        format!("Writing {} lines...", lines_written),
+    );
+    std::fs::write(&output_path, html)?;
+    operation.update("hashing", 0.8, "Hashing the export...");
+    let export = HashRecord::compute(&output_path, HashKind::Export)?;
+    let sha256 = export.sha256.clone();
+    manifest.record(export);
+    manifest.save()?;
+    operation.finish(format!("Exported {} lines", lines_written));
+
+    Ok(ExportSummary {
+        path: output_path,
+        lines_written,
+        truncated,
+        sha256,
+        source_sha256: options.source_sha256,
+    })
+}
+
+/// Export parsed log files into a Parquet lake under `output_dir`, partitioned into
+/// `log_date=`/`log_hour=`/`log_source=` directories. Exports to the same directory add to
+/// it, so a lake grows across investigations. Without `paths` the open file is exported
+/// as it is queried; given files are parsed with their detected format, or exported as
+/// plain lines when none is recognised
+#[tauri::command]
+pub async fn export_lake(
+    output_dir: String,
+    paths: Option<Vec<String>>,
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> Result<LakeExportSummary, CommandError> {
+    let output = Path::new(&output_dir);
+    std::fs::create_dir_all(output)?;
+    let operation = start_operation(&app, OperationKind::Export, "writing", "Exporting...");
+    let mut sources = Vec::new();
+    match paths {
+        None => {
+            let path = state
+                .log_file
+                .with_file(|f| f.path().to_string())
+                .ok_or_else(|| CommandError {
+                    message: "No file open".to_string(),
+                })?;
+            let table = line_table(&state);
+            // A lake keeps what it is given, so the parsed rows go in only once all are there
+            let parsing = state
+                .parse_job
+                .lock()
+                .as_ref()
+                .is_some_and(ParseJob::is_partial);
+            if parsing && table == "parsed" {
+                return Err(CommandError {
+                    message: "The file is still being parsed; export it once parsing finishes"
+                        .to_string(),
+                });
+            }
+            sources.push(export_to_lake(&state.query_engine, table, &path, output).await?);
+        }
+        Some(paths) => {
+            for (i, path) in paths.iter().enumerate() {
+                if operation.is_cancelled() {
+                    return Err(IndexerError::Cancelled.into());
+                }
+                let path = &authorize_path(&app, path)?;
+                operation.update(
+                    "writing",
+                    i as f64 / paths.len() as f64,
+                    format!("Exporting {}...", path),
+                );
+                let engine = QueryEngine::new();
+                let table = load_for_export(path, &engine, &app).await?;
+                sources.push(export_to_lake(&engine, table, path, output).await?);
+            }
+        }
+    }
+    let rows = sources.iter().map(|source| source.rows).sum();
+    operation.finish(format!("Exported {} rows", rows));
+    Ok(LakeExportSummary {
+        path: output_dir,
+        sources,
+        rows,
+    })
+}
+
+/// Write a table of an engine to a lake, partitioned as the rows of `path`
+async fn export_to_lake(
+    engine: &QueryEngine,
+    table: &str,
+    path: &str,
+    output: &Path,
+) -> Result<LakeSource, CommandError> {
+    let source = lake::source_name(Path::new(path));
+    let columns = engine.table_columns(table).await?;
+    let (sql, timestamp_column) = lake::partitioned_sql(table, &columns, &source);
+    let rows = engine
+        .write_partitioned_parquet(&sql, output, &lake::PARTITION_COLUMNS)
+        .await?;
+    Ok(LakeSource {
+        path: path.to_string(),
+        source,
+        rows,
+        timestamp_column,
+    })
+}
+
+/// Load a file that isn't open into an engine of its own, as `parsed` when its format is
+/// recognised and as `logs` otherwise, returning the table
+async fn load_for_export(
+    path: &str,
+    engine: &QueryEngine,
+    app: &AppHandle,
+) -> Result<&'static str, CommandError> {
+    let log_file = SharedLogFile::new();
+    log_file.open(path)?;
+    let Ok(ParsePlan {
+        mut parser,
+        schema_override,
+        ..
+    }) = plan_parse(&log_file, app, None)
+    else {
+        register_logs(engine, &log_file, path).await?;
+        return Ok("logs");
+    };
+    let mut table = parse_lines_of(&log_file, parser.as_mut(), None)?;
+    if let Some(schema_override) = &schema_override {
+        schema_override
+            .apply(&mut table)
+            .map_err(|message| CommandError { message })?;
+    }
+    let raw_line = |line_number| raw_line_of(&log_file, line_number);
+    engine.register_parsed(&table, "parsed", &raw_line).await?;
+    Ok("parsed")
+}
+
+/// Open a lake written by `export_lake` as the `lake` table, kept like a lookup table when
+/// other files are opened. Returns its columns
+#[tauri::command]
+pub async fn open_lake(
+    path: String,
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> Result<Vec<Column>, CommandError> {
+    let path = authorize_path(&app, &path)?;
+    state
+        .query_engine
+        .register_parquet_dir(lake::LAKE_TABLE, Path::new(&path), &lake::PARTITION_COLUMNS)
+        .await?;
+    Ok(state.query_engine.table_columns(lake::LAKE_TABLE).await?)
+}
+
+/// Write query results, or without `sql` the open file's table, to a table of a SQLite or
+/// DuckDB database with their column types, to carry on in tools that read databases
+/// The table is named `table`, or after the open file's table, or `results` for a query
+#[tauri::command]
+pub async fn export_database(
+    output_path: String,
+    kind: DatabaseKind,
+    sql: Option<String>,
+    table: Option<String>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<HandoffSummary, CommandError> {
+    let source = line_table(&state);
+    let table = table.unwrap_or_else(|| match sql {
+        Some(_) => "results".to_string(),
+        None => source.to_string(),
+    });
+    // The line tables are scanned in parallel partitions, so file order needs asking for
+    let sql = sql.unwrap_or_else(|| format!("SELECT * FROM {} ORDER BY line_number", source));
+    let (columns, mut stream) = state.query_engine.query_stream(&sql).await?;
+    // Batches go to the database as the query produces them, so memory stays flat however
+    // many rows there are; the writer blocks, so it pulls them from its own thread
+    let runtime = tokio::runtime::Handle::current();
+    let rows = tauri::async_runtime::spawn_blocking({
+        let (output_path, table, columns) = (output_path.clone(), table.clone(), columns.clone());
+        move || {
+            let batches = std::iter::from_fn(|| runtime.block_on(stream.next()));
+            handoff::write_database(kind, Path::new(&output_path), &table, &columns, batches)
+        }
+    })
+    .await
+    .map_err(|e| CommandError {
+        message: e.to_string(),
+    })??;
+    Ok(HandoffSummary {
+        path: output_path,
+        kind,
+        table,
+        columns,
+        rows,
+    })
+}
+
+/// Hashes recorded for opened files and exports
+fn hash_manifest(app: &AppHandle) -> Result<HashManifest, CommandError> {
+    Ok(HashManifest::load(&config_dir(app)?)?)
+}
+
+/// Hash the open file on disk and compare it with the hash taken when it was opened
+#[tauri::command]
+pub fn verify_file_integrity(
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> Result<IntegrityCheck, CommandError> {
+    let recorded = state
+        .opened_hash
+        .lock()
+        .clone()
+        .ok_or_else(|| CommandError {
+            message: "No file open from disk".to_string(),
+        })?;
+    let mut manifest = hash_manifest(&app)?;
+    let check = manifest.verify(recorded)?;
+    manifest.save()?;
+    Ok(check)
+}
+
+/// Every hash recorded so far, oldest first
+#[tauri::command]
+pub fn get_hash_manifest(app: AppHandle) -> Result<Vec<HashRecord>, CommandError> {
+    Ok(hash_manifest(&app)?.records().to_vec())
+}
+
+/// Summary of an exported profiling trace
+#[derive(Debug, Serialize)]
+pub struct TraceSummary {
+    pub path: String,
+    pub events: usize,
+    /// Oldest spans dropped once the session exceeded `profile::MAX_SPANS`
+    pub dropped: u64,
+}
+
+/// Write the session's indexing, search and query timings as a chrome://tracing JSON file
+#[tauri::command]
+pub fn export_trace(output_path: String) -> Result<TraceSummary, CommandError> {
+    let (trace, dropped) = profile::chrome_trace();
+    let events = trace["traceEvents"].as_array().map_or(0, Vec::len);
+    let json = serde_json::to_string(&trace).map_err(|e| CommandError {
+        message: e.to_string(),
+    })?;
+    std::fs::write(&output_path, json)?;
+    Ok(TraceSummary {
+        path: output_path,
+        events,
+        dropped,
+    })
+}
+
+/// Forget recorded timings, e.g. before reproducing a slow operation
+#[tauri::command]
+pub fn clear_trace() {
+    profile::clear();
+}
+
+/// Features that failed to initialize, so the UI can show what is unavailable and why
+#[tauri::command]
+pub fn get_feature_issues(state: State<'_, Arc<AppState>>) -> Vec<FeatureIssue> {
+    state.feature_issues.lock().clone()
+}
+
+/// The app's own recent diagnostics, oldest first
+/// `since` is the last `seq` already shown, so a console can poll for new entries only
+#[tauri::command]
+pub fn get_app_logs(
+    min_level: Option<LogLevel>,
+    since: Option<u64>,
+    limit: Option<usize>,
+) -> Vec<AppLogEntry> {
+    applog::entries(
+        min_level.unwrap_or(LogLevel::Info),
+        since,
+        limit.unwrap_or(applog::MAX_APP_LOGS),
+    )
+}
+
+/// Measure indexing, search and SQL throughput on a generated file of the given size and shape
+#[tauri::command]
+pub async fn run_benchmark(
+    options: Option<BenchmarkOptions>,
+) -> Result<BenchmarkReport, CommandError> {
+    Ok(bench::run(options.unwrap_or_default()).await?)
+}
+
+/// Record where the viewer is and what it filters on, for restoring after a crash
+/// Each call is synced to disk, so the UI should report once scrolling settles
+#[tauri::command]
+pub fn record_view_state(position: u64, filter: Option<String>, state: State<'_, Arc<AppState>>) {
+    state.journal(JournalEvent::View { position, filter });
+}
+
+/// The session the previous run left open when it crashed, if any
+/// Returned only once; the UI offers to restore it by reopening its files and position
+#[tauri::command]
+pub fn take_restorable_session(state: State<'_, Arc<AppState>>) -> Option<SessionState> {
+    state.restorable_session.lock().take()
+}
+
+/// Start following the main file as it grows
+#[tauri::command]
+pub fn start_follow(
+    options: Option<FollowOptions>,
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> Result<(), CommandError> {
+    let path = state
+        .log_file
+        .with_file(|f| f.path().to_string())
+        .ok_or_else(|| CommandError {
+            message: "No file open".to_string(),
+        })?;
+
+    if state.live_source.lock().is_some() {
+        return Err(CommandError {
+            message: "Live sources are followed automatically".to_string(),
+        });
+    }
+
+    // Stop any previous follower before starting a new one
+    state.follower.lock().take();
+
+    let line_count = state.log_file.with_file(|f| f.line_count()).unwrap_or(0);
+    state.follow_session.lock().restart(line_count);
+    state.live_stats.lock().reset();
+    state.alerts.lock().restart(Instant::now());
+
+    let weak_state = Arc::downgrade(state.inner());
+    let event_state = weak_state.clone();
+    let follower = Follower::spawn(
+        PathBuf::from(path),
+        options.unwrap_or_default(),
+        move || match weak_state.upgrade() {
+            Some(state) => state.log_file.refresh(),
+            None => Ok(None),
+        },
+        move |event| emit_follow_event(&event_state, &app, event),
+    );
+
+    *state.follower.lock() = Some(follower);
+    state.journal(JournalEvent::FollowStarted);
+    Ok(())
+}
+
+/// Event carrying rolling statistics while following, at most once a second
+const LIVE_STATS_EVENT: &str = "live-stats";
+
+/// Forward a follow event from a follower or live source to the frontend
+fn emit_follow_event(state: &Weak<AppState>, app: &AppHandle, event: FollowEvent) {
+    match event {
+        FollowEvent::Appended(range) => {
+            let Some(state) = state.upgrade() else {
+                return;
+            };
+            let update = state
+                .log_file
+                .with_file(|f| state.follow_session.lock().on_append(f, range.clone()))
+                .flatten();
+            if let Some(update) = update {
+                app.emit("new-lines", update).ok();
+            }
+            let checked = state.log_file.with_file(|f| {
+                let lines: Vec<_> = range.clone().map_while(|line| f.line_text(line)).collect();
+                let now = Instant::now();
+                let mut stats = state.live_stats.lock();
+                stats.record(now, lines.iter().map(|line| line.as_ref()));
+                let alerts = state.alerts.lock().on_append(
+                    now,
+                    f.path(),
+                    range.start,
+                    &lines,
+                    stats.window_errors(now),
+                );
+                (stats.snapshot_due(now), alerts)
+            });
+            let Some((snapshot, alerts)) = checked else {
+                return;
+            };
+            if let Some(snapshot) = snapshot {
+                app.emit(LIVE_STATS_EVENT, snapshot).ok();
+            }
+            for alert in alerts {
+                raise_alert(app, &state, alert);
+            }
+        }
+        FollowEvent::Rotated(marker_line) => {
+            app.emit("log-rotated", marker_line).ok();
+        }
+        FollowEvent::ModeChanged(mode) => {
+            app.emit("follow-mode-changed", mode).ok();
+        }
+        FollowEvent::Evicted(count) => {
+            if let Some(state) = state.upgrade() {
+                state.follow_session.lock().on_evict(count);
+            }
+            app.emit("lines-evicted", count).ok();
+        }
+        FollowEvent::Error(message) => {
+            app.emit("follow-error", message).ok();
+        }
+    }
+}
+
+/// Event carrying an alert as it fires
+const ALERT_EVENT: &str = "alert-fired";
+
+/// Event asking the UI to jump to an alert's line, after its notification brought the
+/// window back to the front
+const ALERT_JUMP_EVENT: &str = "alert-jump";
+
+/// Event reporting an alert's webhook or command that failed
+const ALERT_ACTION_FAILED_EVENT: &str = "alert-action-failed";
+
+/// How often stalled following is checked for
+const STALL_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Show an alert in the app and, if its rule asks for it, as a desktop notification, and
+/// run its actions in the background
+/// An alert fired while the window is in the background is jumped to once it's focused,
+/// which is what clicking the notification does
+fn raise_alert(app: &AppHandle, state: &AppState, alert: Alert) {
+    tracing::info!(rule = %alert.rule, line = ?alert.line, "alert fired");
+    if alert.notify {
+        let shown = app
+            .notification()
+            .builder()
+            .title(&alert.rule)
+            .body(&alert.message)
+            .show();
+        if let Err(e) = shown {
+            tracing::warn!(error = %e, "failed to show notification");
+        }
+    }
+    let focused = app
+        .get_webview_window("main")
+        .and_then(|window| window.is_focused().ok())
+        .unwrap_or(false);
+    if !focused && alert.line.is_some() {
+        *state.pending_alert_jump.lock() = Some(alert.clone());
+    }
+    if !alert.actions.is_empty() {
+        let app = app.clone();
+        let fired = alert.clone();
+        std::thread::spawn(move || {
+            for action in &fired.actions {
+                if let Err(e) = action.run(&fired) {
+                    tracing::warn!(rule = %fired.rule, error = %e, "alert action failed");
+                    let failure = ActionFailure {
+                        rule: fired.rule.clone(),
+                        message: e.to_string(),
+                    };
+                    app.emit(ALERT_ACTION_FAILED_EVENT, failure).ok();
+                }
+            }
+        });
+    }
+    app.emit(ALERT_EVENT, alert).ok();
+}
+
+/// Jump to the alert that fired while the window was in the background, if any
+pub fn on_window_focused(app: &AppHandle) {
+    let state = app.state::<Arc<AppState>>();
+    let jump = state.pending_alert_jump.lock().take();
+    if let Some(alert) = jump {
+        app.emit(ALERT_JUMP_EVENT, alert).ok();
+    }
+}
+
+/// Arm the saved alert rules and start checking for stalled following in the background
+pub fn start_alerts(app: &AppHandle) {
+    let state = app.state::<Arc<AppState>>();
+    let armed =
+        alert_store(app).and_then(|store| Ok(state.alerts.lock().set_rules(store.rules())?));
+    if let Err(e) = armed {
+        state.report_issue("alerts", e.message);
+    }
+
+    let app = app.clone();
+    std::thread::spawn(move || loop {
+        std::thread::sleep(STALL_CHECK_INTERVAL);
+        let state = app.state::<Arc<AppState>>();
+        let following = state.follower.lock().is_some() || state.live_source.lock().is_some();
+        if !following {
+            continue;
+        }
+        let Some((path, line_count)) = state
+            .log_file
+            .with_file(|f| (f.path().to_string(), f.line_count()))
+        else {
+            continue;
+        };
+        let alerts =
+            state
+                .alerts
+                .lock()
+                .check_stalled(Instant::now(), &path, line_count.checked_sub(1));
+        for alert in alerts {
+            raise_alert(&app, &state, alert);
+        }
+    });
+}
+
+/// List the saved alert rules
+#[tauri::command]
+pub fn list_alert_rules(app: AppHandle) -> Result<Vec<AlertRule>, CommandError> {
+    Ok(alert_store(&app)?.rules().to_vec())
+}
+
+/// Save an alert rule, replacing any rule of the same name, and arm the rules
+#[tauri::command]
+pub fn save_alert_rule(
+    rule: AlertRule,
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> Result<(), CommandError> {
+    rule.validate()?;
+    let mut store = alert_store(&app)?;
+    store.upsert(rule);
+    store.save()?;
+    state.alerts.lock().set_rules(store.rules())?;
+    if let Some(watch) = state.folder_watch.lock().as_ref() {
+        watch.set_rules(store.rules().to_vec());
+    }
+    Ok(())
+}
+
+/// Delete an alert rule, returning whether it existed
+#[tauri::command]
+pub fn delete_alert_rule(
+    name: String,
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> Result<bool, CommandError> {
+    let mut store = alert_store(&app)?;
+    let removed = store.remove(&name);
+    store.save()?;
+    state.alerts.lock().set_rules(store.rules())?;
+    if let Some(watch) = state.folder_watch.lock().as_ref() {
+        watch.set_rules(store.rules().to_vec());
+    }
+    Ok(removed)
+}
+
+/// Event carrying a finding of the folder watch as it's made
+const WATCH_FINDING_EVENT: &str = "watch-finding";
+
+/// Watch a folder of logs unattended, checking what is appended to its matching files
+/// against the alert rules; alerts raised are kept as findings. Replaces any running watch
+#[tauri::command]
+pub fn start_watch(
+    mut options: WatchOptions,
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> Result<WatchStatus, CommandError> {
+    options.directory = authorize_path(&app, &options.directory)?;
+    let rules = alert_store(&app)?.rules().to_vec();
+    state.folder_watch.lock().take();
+
+    let weak_state = Arc::downgrade(state.inner());
+    let event_app = app.clone();
+    let watch = FolderWatch::start(options, rules, move |finding: &Finding| {
+        event_app.emit(WATCH_FINDING_EVENT, finding).ok();
+        if let Some(state) = weak_state.upgrade() {
+            raise_alert(&event_app, &state, finding.alert.clone());
+        }
+    })?;
+    let status = watch.status();
+    *state.folder_watch.lock() = Some(watch);
+    Ok(status)
+}
+
+/// Stop the folder watch; its findings are dropped with it
+#[tauri::command]
+pub fn stop_watch(state: State<'_, Arc<AppState>>) {
+    state.folder_watch.lock().take();
+}
+
+/// State of the folder watch, if one is running
+#[tauri::command]
+pub fn get_watch_status(state: State<'_, Arc<AppState>>) -> Option<WatchStatus> {
+    state.folder_watch.lock().as_ref().map(FolderWatch::status)
+}
+
+/// Findings of the folder watch, oldest first
+#[tauri::command]
+pub fn get_watch_findings(state: State<'_, Arc<AppState>>) -> Vec<Finding> {
+    state
+        .folder_watch
+        .lock()
+        .as_ref()
+        .map(FolderWatch::findings)
+        .unwrap_or_default()
+}
+
+/// Forget the findings of the folder watch, which keeps running
+#[tauri::command]
+pub fn clear_watch_findings(state: State<'_, Arc<AppState>>) {
+    if let Some(watch) = state.folder_watch.lock().as_ref() {
+        watch.clear_findings();
+    }
+}
+
+/// Open a named pipe or character device (e.g. a serial console) as a live source
+#[tauri::command]
+pub async fn open_stream(
+    path: String,
+    options: Option<StreamOptions>,
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> Result<FileInfo, CommandError> {
+    let path = authorize_path(&app, &path)?;
+    let kind = live::stream_kind(&path).ok_or_else(|| CommandError {
+        message: format!("{} is not a pipe or device", path),
+    })?;
+    state.parse_job.lock().take();
+    state.parsed_source.lock().take();
+    state.query_engine.clear().await;
+    start_stream(path, kind, options.unwrap_or_default(), state.inner(), app)
+}
+
+fn start_stream(
+    path: String,
+    kind: StreamKind,
+    options: StreamOptions,
+    state: &Arc<AppState>,
+    app: AppHandle,
+) -> Result<FileInfo, CommandError> {
+    let sender = start_live_source(
+        &path,
+        options.batch_interval_ms,
+        options.retention.clone(),
+        state,
+        app.clone(),
+    )?;
+    live::spawn_stream_reader(
+        PathBuf::from(&path),
+        kind,
+        &options,
+        sender,
+        move |message| {
+            app.emit("follow-error", message).ok();
+        },
+    );
+
+    Ok(FileInfo {
+        path,
+        size: 0,
+        line_count: 0,
+        format: format!("{:?}", FileFormat::PlainText),
+        profile: None,
+    })
+}
+
+/// Listen for GELF or Fluent forward messages; each message becomes a JSON line in a live view
+#[tauri::command]
+pub async fn open_listener(
+    options: ListenerOptions,
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> Result<FileInfo, CommandError> {
+    let listener = Listener::bind(&options)?;
+    let name = listener.display_name();
+    state.parse_job.lock().take();
+    state.parsed_source.lock().take();
+    state.query_engine.clear().await;
+
+    let sender = start_live_source(
+        &name,
+        options.batch_interval_ms,
+        options.retention,
+        state.inner(),
+        app.clone(),
+    )?;
+    listener.spawn(sender, move |message| {
+        app.emit("follow-error", message).ok();
+    });
+
+    Ok(FileInfo {
+        path: name,
+        size: 0,
+        line_count: 0,
+        format: format!("{:?}", FileFormat::Ndjson),
+        profile: None,
+    })
+}
+
+/// Subscribe to live Windows Event Log channels; each event becomes a JSON line in a live view
+#[tauri::command]
+pub async fn open_event_log(
+    options: EventLogOptions,
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> Result<FileInfo, CommandError> {
+    let name = format!("eventlog://{}", options.channels.join(","));
+    state.parse_job.lock().take();
+    state.parsed_source.lock().take();
+    state.query_engine.clear().await;
+
+    let sender = start_live_source(
+        &name,
+        options.batch_interval_ms,
+        options.retention.clone(),
+        state.inner(),
+        app.clone(),
+    )?;
+    let subscribed = eventlog::subscribe(&options, sender, move |message| {
+        app.emit("follow-error", message).ok();
+    });
+    if let Err(err) = subscribed {
+        state.live_source.lock().take();
+        state.log_file.close();
+        return Err(err.into());
+    }
+
+    Ok(FileInfo {
+        path: name,
+        size: 0,
+        line_count: 0,
+        format: format!("{:?}", FileFormat::Ndjson),
+        profile: None,
+    })
+}
+
+/// Read the macOS unified log through `log show`/`log stream` into a live view of JSON lines
+/// An archive read is checked against the approved directories like any opened file
+#[tauri::command]
+pub async fn open_unified_log(
+    mut options: UnifiedLogOptions,
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> Result<FileInfo, CommandError> {
+    if let Some(archive) = &options.archive {
+        options.archive = Some(authorize_path(&app, archive)?);
+    }
+    let name = options.display_name();
+    state.parse_job.lock().take();
+    state.parsed_source.lock().take();
+    state.query_engine.clear().await;
+
+    let sender = start_live_source(
+        &name,
+        options.batch_interval_ms,
+        options.retention.clone(),
+        state.inner(),
+        app.clone(),
+    )?;
+    let spawned = unifiedlog::spawn_log_reader(&options, sender, move |message| {
+        app.emit("follow-error", message).ok();
+    });
+    if let Err(err) = spawned {
+        state.live_source.lock().take();
+        state.log_file.close();
+        return Err(err.into());
+    }
+
+    Ok(FileInfo {
+        path: name,
+        size: 0,
+        line_count: 0,
+        format: format!("{:?}", FileFormat::Ndjson),
+        profile: None,
+    })
+}
+
+/// Replace the main view with an empty live view and start ingesting into it
+fn start_live_source(
+    name: &str,
+    batch_interval_ms: Option<u64>,
+    retention: RetentionOptions,
+    state: &Arc<AppState>,
+    app: AppHandle,
+) -> Result<ChunkSender, CommandError> {
+    let retention = Retention::new(retention)?;
+    state.follower.lock().take();
+    state.live_source.lock().take();
+    state.opened_hash.lock().take();
+    state.log_file.set(LogFile::live(name));
+    state.follow_session.lock().restart(0);
+    state.live_stats.lock().reset();
+    state.alerts.lock().restart(Instant::now());
+
+    let batch_interval = Duration::from_millis(
+        batch_interval_ms.unwrap_or(FollowOptions::default().batch_interval_ms),
+    );
+    let file_state = Arc::downgrade(state);
+    let event_state = file_state.clone();
+    let (source, sender) = LiveSource::start(
+        batch_interval,
+        retention,
+        move |f| {
+            if let Some(state) = file_state.upgrade() {
+                state.log_file.with_file_mut(f);
+            }
+        },
+        move |event| emit_follow_event(&event_state, &app, event),
+    );
+    *state.live_source.lock() = Some(source);
+    Ok(sender)
+}
+
+/// Save the live source's captured buffer to `path` and keep writing incoming data to it
+#[tauri::command]
+pub fn start_recording(
+    path: String,
+    state: State<'_, Arc<AppState>>,
+) -> Result<RecordingSummary, CommandError> {
+    let live_source = state.live_source.lock();
+    let source = live_source.as_ref().ok_or_else(|| CommandError {
+        message: "No live source open".to_string(),
+    })?;
+    let summary = state
+        .log_file
+        .with_file(|f| source.start_recording(&path, f))
+        .ok_or_else(|| CommandError {
+            message: "No file open".to_string(),
+        })??;
+    Ok(summary)
+}
+
+/// Stop recording the live source; the file stays on disk as a normal log
+#[tauri::command]
+pub fn stop_recording(
+    state: State<'_, Arc<AppState>>,
+) -> Result<Option<RecordingSummary>, CommandError> {
+    Ok(state
+        .live_source
+        .lock()
+        .as_ref()
+        .and_then(|source| source.stop_recording()))
+}
+
+/// Rolling statistics of the lines appended while following, also pushed with the
+/// `live-stats` event while lines keep arriving
+#[tauri::command]
+pub fn get_live_stats(state: State<'_, Arc<AppState>>) -> LiveStatsSnapshot {
+    state.live_stats.lock().snapshot(Instant::now())
+}
+
+/// Change the window and number of templates of live statistics
+#[tauri::command]
+pub fn set_live_stats_options(options: LiveStatsOptions, state: State<'_, Arc<AppState>>) {
+    state.live_stats.lock().set_options(options);
+}
+
+/// Stop following the main file
+#[tauri::command]
+pub fn stop_follow(state: State<'_, Arc<AppState>>) -> Result<(), CommandError> {
+    state.follower.lock().take();
+    state.journal(JournalEvent::FollowStopped);
+    Ok(())
+}
+
+/// Attach a search to follow mode, or detach it with None
+#[tauri::command]
+pub fn set_follow_filter(
+    pattern: Option<String>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<(), CommandError> {
+    let filter = pattern
+        .filter(|p| !p.is_empty())
+        .map(|p| FollowFilter::new(&p))
+        .transpose()
+        .map_err(|e| CommandError {
+            message: format!("Invalid filter pattern: {}", e),
+        })?;
+    let pattern = filter.as_ref().map(|f| f.pattern().to_string());
+    state.follow_session.lock().set_filter(filter);
+    state.journal(JournalEvent::FollowFilter { pattern });
+    Ok(())
+}
+
+/// Freeze the follow view while new data keeps being indexed
+#[tauri::command]
+pub fn pause_follow(state: State<'_, Arc<AppState>>) -> Result<(), CommandError> {
+    state.follow_session.lock().pause();
+    Ok(())
+}
+
+/// Resume the follow view, returning the range of lines that arrived while paused
+#[tauri::command]
+pub fn resume_follow(state: State<'_, Arc<AppState>>) -> Result<Option<LineUpdate>, CommandError> {
+    Ok(state
+        .log_file
+        .with_file(|f| state.follow_session.lock().resume(f))
+        .flatten())
+}
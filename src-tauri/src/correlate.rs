@@ -0,0 +1,142 @@
+use crate::field_search::quote_text;
+use serde::Serialize;
+
+/// Table the compare file's lines are shared as in the main file's engine, for joins
+pub const COMPARE_TABLE: &str = "compare_logs";
+
+/// View `auto_correlate` builds unless given another name
+pub const DEFAULT_VIEW: &str = "correlated";
+
+/// A line table taking part in a correlation
+#[derive(Debug, Clone)]
+pub struct CorrelationSource {
+    /// Value of the view's `source` column for the table's lines
+    pub label: String,
+    pub table: String,
+}
+
+/// What a correlation view holds
+#[derive(Debug, Clone, Serialize)]
+pub struct CorrelationSummary {
+    pub view: String,
+    pub sources: Vec<String>,
+    /// Distinct keys the view holds
+    pub keys: u64,
+    /// Lines carrying those keys
+    pub lines: u64,
+}
+
+/// SQL of a view of the lines whose key, extracted with `key_pattern` by `regex_extract`,
+/// is shared: found in more than one source, or on more than one line with a single
+/// source. Rows are `correlation_key`, `source`, `line_number` and `line`, grouped by key
+pub fn correlation_view_sql(sources: &[CorrelationSource], key_pattern: &str) -> String {
+    let keyed = sources
+        .iter()
+        .map(|source| {
+            format!(
+                "SELECT regex_extract(line, {}) AS correlation_key, {} AS source, \
+                 line_number, line FROM {}",
+                quote_text(key_pattern),
+                quote_text(&source.label),
+                source.table
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" UNION ALL ");
+    let shared = if sources.len() > 1 {
+        "COUNT(DISTINCT source) > 1"
+    } else {
+        "COUNT(*) > 1"
+    };
+    format!(
+        "WITH keyed AS ({}) \
+         SELECT correlation_key, source, line_number, line FROM keyed \
+         WHERE correlation_key IN (SELECT correlation_key FROM keyed \
+         WHERE correlation_key IS NOT NULL GROUP BY correlation_key HAVING {}) \
+         ORDER BY correlation_key, source, line_number",
+        keyed, shared
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query_engine::QueryEngine;
+    use serde_json::json;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn log_file(lines: &[&str]) -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
+        for line in lines {
+            writeln!(file, "{}", line).unwrap();
+        }
+        file.flush().unwrap();
+        file
+    }
+
+    #[tokio::test]
+    async fn test_correlate_across_files() {
+        let gateway = log_file(&["GET /cart req=a1", "GET /home req=b2", "healthcheck"]);
+        let backend = log_file(&["cart lookup req=a1", "cart saved req=a1", "job req=c3"]);
+        let main = QueryEngine::new();
+        let compare = QueryEngine::new();
+        main.register_udfs().await.unwrap();
+        main.register_table(gateway.path(), "logs").await.unwrap();
+        compare
+            .register_table(backend.path(), "logs")
+            .await
+            .unwrap();
+        main.register_provider(COMPARE_TABLE, compare.table_provider("logs").await.unwrap())
+            .await
+            .unwrap();
+
+        let sources = [
+            CorrelationSource {
+                label: "gateway".to_string(),
+                table: "logs".to_string(),
+            },
+            CorrelationSource {
+                label: "backend".to_string(),
+                table: COMPARE_TABLE.to_string(),
+            },
+        ];
+        let sql = correlation_view_sql(&sources, r"req=(\w+)");
+        main.create_temporary_view(DEFAULT_VIEW, &sql)
+            .await
+            .unwrap();
+        let result = main
+            .execute_sql(
+                "SELECT correlation_key, source, line_number FROM correlated \
+                 ORDER BY source, line_number",
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            result.rows,
+            vec![
+                vec![json!("a1"), json!("backend"), json!(1)],
+                vec![json!("a1"), json!("backend"), json!(2)],
+                vec![json!("a1"), json!("gateway"), json!(1)],
+            ]
+        );
+
+        // On its own a file correlates lines sharing a key
+        let sql = correlation_view_sql(&sources[1..], r"req=(\w+)");
+        main.create_temporary_view(DEFAULT_VIEW, &sql)
+            .await
+            .unwrap();
+        let result = main
+            .execute_sql("SELECT COUNT(*) FROM correlated")
+            .await
+            .unwrap();
+        assert_eq!(result.rows, vec![vec![json!(2)]]);
+
+        // Closing the compare file takes the view over it along
+        assert!(main.drop_provider(COMPARE_TABLE).await);
+        assert!(main
+            .execute_sql("SELECT COUNT(*) FROM correlated")
+            .await
+            .is_err());
+    }
+}
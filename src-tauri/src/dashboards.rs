@@ -0,0 +1,138 @@
+use crate::charts::ChartSpec;
+use crate::parsers::{load_json, save_json};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// File dashboards are persisted to, inside the app config directory
+const DASHBOARDS_FILE: &str = "dashboards.json";
+
+/// Scope of dashboards for files that weren't parsed into a table
+pub const TEXT_SCOPE: &str = "text";
+
+/// How a panel shows its query
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PanelKind {
+    Chart,
+    /// A single number, from a spec with neither buckets nor groups
+    Stat,
+}
+
+/// One panel of a dashboard, a chart spec run against the open file's table
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Panel {
+    pub title: String,
+    pub kind: PanelKind,
+    pub spec: ChartSpec,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Dashboard {
+    pub name: String,
+    #[serde(default)]
+    pub panels: Vec<Panel>,
+}
+
+/// Dashboards persisted as JSON, keyed by the parse profile or format of the files they
+/// were made for, so every file parsed the same way gets them
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DashboardStore {
+    #[serde(skip)]
+    path: PathBuf,
+    #[serde(default)]
+    dashboards: BTreeMap<String, Vec<Dashboard>>,
+}
+
+impl DashboardStore {
+    /// Load the store from a config directory; a missing file has no dashboards
+    pub fn load(dir: &Path) -> io::Result<Self> {
+        let path = dir.join(DASHBOARDS_FILE);
+        let mut store: DashboardStore = load_json(&path)?;
+        store.path = path;
+        Ok(store)
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        save_json(&self.path, self)
+    }
+
+    /// Dashboards of every scope
+    pub fn all(&self) -> &BTreeMap<String, Vec<Dashboard>> {
+        &self.dashboards
+    }
+
+    /// Dashboards of a scope, in the order they were first saved
+    pub fn dashboards(&self, scope: &str) -> &[Dashboard] {
+        self.dashboards
+            .get(scope)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Add a dashboard to a scope, or replace the one with the same name
+    pub fn upsert(&mut self, scope: &str, dashboard: Dashboard) {
+        let dashboards = self.dashboards.entry(scope.to_string()).or_default();
+        match dashboards.iter_mut().find(|d| d.name == dashboard.name) {
+            Some(existing) => *existing = dashboard,
+            None => dashboards.push(dashboard),
+        }
+    }
+
+    /// Remove a dashboard from a scope, returning whether it existed
+    pub fn remove(&mut self, scope: &str, name: &str) -> bool {
+        let Some(dashboards) = self.dashboards.get_mut(scope) else {
+            return false;
+        };
+        let before = dashboards.len();
+        dashboards.retain(|d| d.name != name);
+        let removed = dashboards.len() != before;
+        if dashboards.is_empty() {
+            self.dashboards.remove(scope);
+        }
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn dashboard(name: &str, title: &str) -> Dashboard {
+        Dashboard {
+            name: name.to_string(),
+            panels: vec![Panel {
+                title: title.to_string(),
+                kind: PanelKind::Chart,
+                spec: serde_json::from_value(json!({
+                    "metric": {"op": "count"},
+                    "group_by": "status",
+                    "bucket": "1m"
+                }))
+                .unwrap(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_dashboards_are_kept_per_scope() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = DashboardStore::load(dir.path()).unwrap();
+        store.upsert("profile:nginx", dashboard("traffic", "Requests"));
+        store.upsert("profile:nginx", dashboard("traffic", "Requests by status"));
+        store.upsert("format:json", dashboard("errors", "Errors"));
+        store.save().unwrap();
+
+        let mut store = DashboardStore::load(dir.path()).unwrap();
+        assert_eq!(
+            store.dashboards("profile:nginx"),
+            &[dashboard("traffic", "Requests by status")]
+        );
+        assert!(store.dashboards(TEXT_SCOPE).is_empty());
+        assert!(store.remove("format:json", "errors"));
+        assert!(!store.remove("format:json", "errors"));
+        assert!(store.dashboards("format:json").is_empty());
+    }
+}
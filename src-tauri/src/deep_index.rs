@@ -0,0 +1,279 @@
+use crate::indexer::LogFile;
+use crate::integrity::sha256_hex;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::{Path, PathBuf};
+use tantivy::collector::{Count, TopDocs};
+use tantivy::directory::MmapDirectory;
+use tantivy::query::{QueryParser, QueryParserError};
+use tantivy::schema::{Field, Schema, FAST, INDEXED, TEXT};
+use tantivy::{Index, IndexReader, IndexWriter, Order, ReloadPolicy, TantivyDocument};
+use thiserror::Error;
+
+/// Directory inside the app data directory holding one deep index per log file
+pub const DEEP_INDEX_DIR: &str = "deep_index";
+
+/// Leading bytes hashed to notice a file replaced by a different one at the same path
+const HEAD_BYTES: usize = 64 * 1024;
+/// Memory the index writer buffers across its threads before writing a segment
+const WRITER_HEAP_BYTES: usize = 256 * 1024 * 1024;
+/// Lines indexed between progress reports and cancellation checks
+const PROGRESS_LINES: u64 = 100_000;
+
+/// Errors that can occur building or querying a deep index
+#[derive(Debug, Error)]
+pub enum DeepIndexError {
+    #[error("Deep index error: {0}")]
+    Index(#[from] tantivy::TantivyError),
+    #[error("Invalid query: {0}")]
+    Query(#[from] QueryParserError),
+    #[error("Failed to access the deep index: {0}")]
+    Io(#[from] io::Error),
+    #[error("Failed to record the deep index's source: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Cancelled")]
+    Cancelled,
+}
+
+/// What a deep index was built from
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexedSource {
+    pub path: String,
+    /// Complete lines indexed; lines appended after them are added by the next update
+    pub lines: u64,
+    /// Bytes of those lines
+    pub bytes: u64,
+    /// SHA-256 of the first indexed bytes
+    pub head_sha256: String,
+}
+
+/// Lines a deep index query matched
+#[derive(Debug, Clone, Serialize)]
+pub struct DeepSearchResult {
+    /// Matching lines in file order, up to the requested number
+    pub lines: Vec<u64>,
+    /// Every matching line, including those past the requested number
+    pub total: usize,
+}
+
+/// Full-text index of a log file's lines, kept on disk so it is reused across sessions
+/// Lines are tokenized into lowercased words with positions, for token and phrase queries;
+/// the text itself isn't stored, since it is read back from the file
+pub struct DeepIndex {
+    dir: PathBuf,
+    index: Index,
+    reader: IndexReader,
+    line: Field,
+    text: Field,
+    source: IndexedSource,
+}
+
+impl DeepIndex {
+    /// Directory of a file's index under `root`, named by a hash of the file's path
+    pub fn dir_for(root: &Path, path: &str) -> PathBuf {
+        root.join(&sha256_hex(path.as_bytes())[..16])
+    }
+
+    /// Open the index of a file under `root`, starting a new one if there is none or the
+    /// existing one was built from different contents
+    pub fn open(root: &Path, file: &LogFile) -> Result<Self, DeepIndexError> {
+        let dir = Self::dir_for(root, file.path());
+        let mut schema = Schema::builder();
+        let line = schema.add_u64_field("line", INDEXED | FAST);
+        let text = schema.add_text_field("text", TEXT);
+        let schema = schema.build();
+
+        std::fs::create_dir_all(&dir)?;
+        let mut index = open_index(&dir, schema.clone())?;
+        // Each commit carries the source it brings the index up to, so the two always agree
+        let stored = index
+            .load_metas()?
+            .payload
+            .and_then(|payload| serde_json::from_str::<IndexedSource>(&payload).ok());
+        let fresh = IndexedSource {
+            path: file.path().to_string(),
+            ..IndexedSource::default()
+        };
+        let source = match stored {
+            Some(stored) if Self::still_matches(&stored, file) => stored,
+            None if index.searchable_segment_ids()?.is_empty() => fresh,
+            _ => {
+                drop(index);
+                std::fs::remove_dir_all(&dir)?;
+                std::fs::create_dir_all(&dir)?;
+                index = open_index(&dir, schema)?;
+                fresh
+            }
+        };
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()?;
+        Ok(DeepIndex {
+            dir,
+            index,
+            reader,
+            line,
+            text,
+            source,
+        })
+    }
+
+    /// Whether a file still starts with the lines an index was built from
+    fn still_matches(source: &IndexedSource, file: &LogFile) -> bool {
+        source.path == file.path()
+            && source.lines <= file.complete_line_count()
+            && file.byte_range(0..source.lines).end as u64 == source.bytes
+            && source.head_sha256 == head_sha256(&file.data()[..source.bytes as usize])
+    }
+
+    pub fn source(&self) -> &IndexedSource {
+        &self.source
+    }
+
+    /// Index the complete lines of `file` added since the last update, returning how many
+    /// Gives up with `Cancelled` once `cancelled` returns true, leaving the index as it was;
+    /// `progress` is passed the lines indexed so far and the number to index
+    pub fn update(
+        &mut self,
+        file: &LogFile,
+        cancelled: impl Fn() -> bool,
+        progress: impl Fn(u64, u64),
+    ) -> Result<u64, DeepIndexError> {
+        let (start, end) = (self.source.lines, file.complete_line_count());
+        if end <= start {
+            return Ok(0);
+        }
+        let mut writer: IndexWriter = self.index.writer(WRITER_HEAP_BYTES)?;
+        for line in start..end {
+            if (line - start) % PROGRESS_LINES == 0 {
+                if cancelled() {
+                    // Dropping the writer discards the uncommitted lines
+                    return Err(DeepIndexError::Cancelled);
+                }
+                progress(line - start, end - start);
+            }
+            let mut doc = TantivyDocument::default();
+            doc.add_u64(self.line, line);
+            doc.add_text(self.text, file.line_text(line).unwrap_or_default());
+            writer.add_document(doc)?;
+        }
+        let bytes = file.byte_range(0..end).end;
+        let source = IndexedSource {
+            path: self.source.path.clone(),
+            lines: end,
+            bytes: bytes as u64,
+            head_sha256: head_sha256(&file.data()[..bytes]),
+        };
+        let mut commit = writer.prepare_commit()?;
+        commit.set_payload(&serde_json::to_string(&source)?);
+        commit.commit()?;
+        self.reader.reload()?;
+        self.source = source;
+        Ok(end - start)
+    }
+
+    /// Lines matching a query, in file order
+    /// Words must all appear unless joined with OR; "quoted words" match as a phrase, and
+    /// a leading - excludes a word
+    pub fn search(
+        &self,
+        query: &str,
+        max_results: usize,
+    ) -> Result<DeepSearchResult, DeepIndexError> {
+        let mut parser = QueryParser::for_index(&self.index, vec![self.text]);
+        parser.set_conjunction_by_default();
+        let query = parser.parse_query(query)?;
+        let top =
+            TopDocs::with_limit(max_results.max(1)).order_by_fast_field::<u64>("line", Order::Asc);
+        let (total, docs) = self.reader.searcher().search(&query, &(Count, top))?;
+        Ok(DeepSearchResult {
+            lines: docs
+                .into_iter()
+                .take(max_results)
+                .map(|(line, _)| line)
+                .collect(),
+            total,
+        })
+    }
+
+    /// Bytes the index takes on disk
+    pub fn disk_bytes(&self) -> u64 {
+        std::fs::read_dir(&self.dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok()?.metadata().ok())
+                    .map(|metadata| metadata.len())
+                    .sum()
+            })
+            .unwrap_or(0)
+    }
+
+    /// Delete a file's index from disk, returning whether there was one
+    pub fn remove(root: &Path, path: &str) -> io::Result<bool> {
+        let dir = Self::dir_for(root, path);
+        if !dir.exists() {
+            return Ok(false);
+        }
+        std::fs::remove_dir_all(dir)?;
+        Ok(true)
+    }
+}
+
+/// Open the index in `dir`, creating an empty one if there is none
+fn open_index(dir: &Path, schema: Schema) -> Result<Index, DeepIndexError> {
+    let directory = MmapDirectory::open(dir).map_err(tantivy::TantivyError::from)?;
+    Ok(Index::open_or_create(directory, schema)?)
+}
+
+/// SHA-256 of the first `HEAD_BYTES` of indexed text
+fn head_sha256(data: &[u8]) -> String {
+    sha256_hex(&data[..data.len().min(HEAD_BYTES)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deep_index_reused_and_extended() {
+        let root = tempfile::tempdir().unwrap();
+        let mut data = Vec::new();
+        for i in 0..1000 {
+            data.extend_from_slice(format!("{} request served in {}ms\n", i, i % 7).as_bytes());
+        }
+        data.extend_from_slice(b"ERROR disk quota exceeded for /var/log\nheartbeat ok\n");
+        let mut file = LogFile::from_bytes("app.log", data);
+
+        let mut index = DeepIndex::open(root.path(), &file).unwrap();
+        assert_eq!(index.update(&file, || false, |_, _| {}).unwrap(), 1002);
+        let found = index.search("\"quota exceeded\"", 10).unwrap();
+        assert_eq!((found.lines, found.total), (vec![1000], 1));
+        assert_eq!(index.search("exceeded quota -disk", 10).unwrap().total, 0);
+        let found = index.search("served 3ms", 2).unwrap();
+        assert_eq!((found.lines, found.total), (vec![3, 10], 143));
+        assert!(index.search("\"unclosed", 10).is_err());
+        drop(index);
+
+        // Reopening finds the index on disk and only indexes what was appended
+        file.append_bytes(b"ERROR quota exceeded again\n");
+        let mut index = DeepIndex::open(root.path(), &file).unwrap();
+        assert_eq!(index.source().lines, 1002);
+        assert_eq!(index.update(&file, || false, |_, _| {}).unwrap(), 1);
+        assert_eq!(index.search("quota", 10).unwrap().lines, [1000, 1002]);
+        file.append_bytes(b"late line\n");
+        assert!(matches!(
+            index.update(&file, || true, |_, _| {}),
+            Err(DeepIndexError::Cancelled)
+        ));
+        assert_eq!(index.source().lines, 1003);
+        drop(index);
+
+        // A different file at the same path starts over
+        let other = LogFile::from_bytes("app.log", b"fresh start\n".to_vec());
+        let index = DeepIndex::open(root.path(), &other).unwrap();
+        assert_eq!(index.source().lines, 0);
+        assert_eq!(index.search("quota", 10).unwrap().total, 0);
+        assert!(DeepIndex::remove(root.path(), "app.log").unwrap());
+    }
+}
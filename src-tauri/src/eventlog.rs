@@ -0,0 +1,402 @@
+use crate::listeners::json_line;
+use crate::live::{ChunkSender, RetentionOptions};
+use crate::timestamp::{format_timestamp, parse_timestamp};
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+use std::sync::OnceLock;
+
+/// Options for subscribing to Windows Event Log channels
+#[derive(Debug, Clone, Deserialize)]
+pub struct EventLogOptions {
+    /// Channels such as `System`, `Application` or `Microsoft-Windows-Sysmon/Operational`
+    #[serde(default = "default_channels")]
+    pub channels: Vec<String>,
+    /// XPath filter applied to every channel; defaults to all events
+    #[serde(default)]
+    pub query: Option<String>,
+    #[serde(default)]
+    pub batch_interval_ms: Option<u64>,
+    #[serde(default)]
+    pub retention: RetentionOptions,
+}
+
+fn default_channels() -> Vec<String> {
+    vec!["System".to_string(), "Application".to_string()]
+}
+
+fn system_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"<(EventID|Level|Channel|Computer|Task|Keywords)(?:\s[^>]*)?>([^<]*)</")
+            .unwrap()
+    })
+}
+
+fn provider_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r#"<Provider\s[^>]*Name=['"]([^'"]*)['"]"#).unwrap())
+}
+
+fn time_created_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r#"<TimeCreated\s[^>]*SystemTime=['"]([^'"]*)['"]"#).unwrap())
+}
+
+fn data_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r#"<Data(?:\s+Name=['"]([^'"]*)['"])?\s*(?:/>|>([^<]*)</Data>)"#).unwrap()
+    })
+}
+
+/// Provider name from rendered event XML, used to look up message templates
+pub fn event_provider(xml: &str) -> Option<String> {
+    provider_regex()
+        .captures(xml)
+        .map(|caps| unescape_xml(&caps[1]))
+}
+
+/// Flatten rendered event XML into a JSON line with timestamp, level and provider columns
+/// `EventData` values become additional columns; unnamed values are numbered `data_0`, `data_1`, ...
+pub fn event_xml_to_line(xml: &str, message: Option<&str>) -> String {
+    let timestamp = time_created_regex()
+        .captures(xml)
+        .and_then(|caps| parse_timestamp(&caps[1]))
+        .map_or(JsonValue::Null, |ms| JsonValue::from(format_timestamp(ms)));
+
+    let mut system = std::collections::HashMap::new();
+    for caps in system_regex().captures_iter(xml) {
+        system
+            .entry(caps.get(1).map_or("", |m| m.as_str()))
+            .or_insert_with(|| unescape_xml(&caps[2]));
+    }
+    let level = system
+        .get("Level")
+        .map_or(JsonValue::Null, |level| JsonValue::from(level_name(level)));
+    let text = |key: &str| {
+        system
+            .get(key)
+            .map_or(JsonValue::Null, |v| JsonValue::from(v.as_str()))
+    };
+
+    let mut fields = vec![
+        ("timestamp".to_string(), timestamp),
+        ("level".to_string(), level),
+        (
+            "provider".to_string(),
+            event_provider(xml).map_or(JsonValue::Null, JsonValue::from),
+        ),
+        (
+            "event_id".to_string(),
+            system
+                .get("EventID")
+                .and_then(|id| id.parse::<u64>().ok())
+                .map_or(JsonValue::Null, JsonValue::from),
+        ),
+        ("channel".to_string(), text("Channel")),
+        ("computer".to_string(), text("Computer")),
+        (
+            "message".to_string(),
+            message.map_or(JsonValue::Null, |m| JsonValue::from(m.trim())),
+        ),
+    ];
+
+    for (index, caps) in data_regex().captures_iter(xml).enumerate() {
+        let name = caps
+            .get(1)
+            .map_or_else(|| format!("data_{}", index), |m| unescape_xml(m.as_str()));
+        let value = caps
+            .get(2)
+            .map_or(String::new(), |m| unescape_xml(m.as_str()));
+        fields.push((name, JsonValue::from(value)));
+    }
+    json_line(fields)
+}
+
+/// Standard event levels; providers may define their own above 5
+fn level_name(level: &str) -> String {
+    match level {
+        "0" => "Information".to_string(),
+        "1" => "Critical".to_string(),
+        "2" => "Error".to_string(),
+        "3" => "Warning".to_string(),
+        "4" => "Information".to_string(),
+        "5" => "Verbose".to_string(),
+        other => other.to_string(),
+    }
+}
+
+fn unescape_xml(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Subscribe to future events on each channel, sending one JSON line per event until the source stops
+#[cfg(windows)]
+pub fn subscribe<F>(
+    options: &EventLogOptions,
+    sender: ChunkSender,
+    on_error: F,
+) -> std::io::Result<()>
+where
+    F: Fn(String) + Send + Sync + 'static,
+{
+    subscription::subscribe(options, sender, on_error)
+}
+
+#[cfg(not(windows))]
+pub fn subscribe<F>(
+    _options: &EventLogOptions,
+    _sender: ChunkSender,
+    _on_error: F,
+) -> std::io::Result<()>
+where
+    F: Fn(String) + Send + Sync + 'static,
+{
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "Windows Event Log is only available on Windows",
+    ))
+}
+
+#[cfg(windows)]
+mod subscription {
+    use super::{event_provider, event_xml_to_line, EventLogOptions};
+    use crate::live::ChunkSender;
+    use parking_lot::Mutex;
+    use std::collections::HashMap;
+    use std::ffi::c_void;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use windows_sys::Win32::Foundation::{GetLastError, ERROR_INSUFFICIENT_BUFFER};
+    use windows_sys::Win32::System::EventLog::{
+        EvtClose, EvtFormatMessage, EvtFormatMessageEvent, EvtOpenPublisherMetadata, EvtRender,
+        EvtRenderEventXml, EvtSubscribe, EvtSubscribeActionError, EvtSubscribeToFutureEvents,
+        EVT_HANDLE, EVT_SUBSCRIBE_NOTIFY_ACTION,
+    };
+
+    /// How often the owning thread checks whether the live source was stopped
+    const STOP_POLL: Duration = Duration::from_millis(200);
+
+    struct Context {
+        channel: String,
+        sender: ChunkSender,
+        on_error: Arc<dyn Fn(String) + Send + Sync>,
+        /// Publisher metadata handles used to format messages, opened on first use
+        publishers: Mutex<HashMap<String, EVT_HANDLE>>,
+    }
+
+    pub fn subscribe<F>(
+        options: &EventLogOptions,
+        sender: ChunkSender,
+        on_error: F,
+    ) -> std::io::Result<()>
+    where
+        F: Fn(String) + Send + Sync + 'static,
+    {
+        let on_error: Arc<dyn Fn(String) + Send + Sync> = Arc::new(on_error);
+        let query = wide(options.query.as_deref().unwrap_or("*"));
+        let mut subscriptions: Vec<(EVT_HANDLE, Box<Context>)> = Vec::new();
+
+        for channel in &options.channels {
+            let context = Box::new(Context {
+                channel: channel.clone(),
+                sender: sender.clone(),
+                on_error: on_error.clone(),
+                publishers: Mutex::new(HashMap::new()),
+            });
+            let channel_path = wide(channel);
+            // SAFETY: the context box outlives the subscription; both are released together below
+            let handle = unsafe {
+                EvtSubscribe(
+                    0,
+                    std::ptr::null_mut(),
+                    channel_path.as_ptr(),
+                    query.as_ptr(),
+                    0,
+                    &*context as *const Context as *const c_void,
+                    Some(on_event),
+                    EvtSubscribeToFutureEvents as u32,
+                )
+            };
+            if handle == 0 {
+                let err = std::io::Error::last_os_error();
+                close_all(subscriptions);
+                return Err(std::io::Error::new(
+                    err.kind(),
+                    format!("Failed to subscribe to {}: {}", channel, err),
+                ));
+            }
+            subscriptions.push((handle, context));
+        }
+
+        std::thread::spawn(move || {
+            while !sender.is_stopped() {
+                std::thread::sleep(STOP_POLL);
+            }
+            close_all(subscriptions);
+        });
+        Ok(())
+    }
+
+    fn close_all(subscriptions: Vec<(EVT_HANDLE, Box<Context>)>) {
+        for (handle, context) in subscriptions {
+            // SAFETY: closing the subscription waits for in-flight callbacks before the context drops
+            unsafe {
+                EvtClose(handle);
+                for metadata in context.publishers.lock().values() {
+                    EvtClose(*metadata);
+                }
+            }
+        }
+    }
+
+    unsafe extern "system" fn on_event(
+        action: EVT_SUBSCRIBE_NOTIFY_ACTION,
+        context: *const c_void,
+        event: EVT_HANDLE,
+    ) -> u32 {
+        let context = &*(context as *const Context);
+        if context.sender.is_stopped() {
+            return 0;
+        }
+        if action == EvtSubscribeActionError {
+            // On error the event handle carries the Win32 error code
+            let err = std::io::Error::from_raw_os_error(event as i32);
+            (context.on_error)(format!("{}: {}", context.channel, err));
+            return 0;
+        }
+
+        match render_xml(event) {
+            Ok(xml) => {
+                let message = event_provider(&xml)
+                    .and_then(|provider| format_message(context, &provider, event));
+                let mut line = event_xml_to_line(&xml, message.as_deref());
+                line.push('\n');
+                context.sender.send(line.into_bytes());
+            }
+            Err(err) => (context.on_error)(format!("{}: {}", context.channel, err)),
+        }
+        0
+    }
+
+    unsafe fn render_xml(event: EVT_HANDLE) -> std::io::Result<String> {
+        let mut used = 0u32;
+        let mut property_count = 0u32;
+        let sized = EvtRender(
+            0,
+            event,
+            EvtRenderEventXml as u32,
+            0,
+            std::ptr::null_mut(),
+            &mut used,
+            &mut property_count,
+        );
+        if sized == 0 && GetLastError() != ERROR_INSUFFICIENT_BUFFER {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        let mut buf = vec![0u16; (used as usize).div_ceil(2)];
+        let rendered = EvtRender(
+            0,
+            event,
+            EvtRenderEventXml as u32,
+            used,
+            buf.as_mut_ptr() as *mut c_void,
+            &mut used,
+            &mut property_count,
+        );
+        if rendered == 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(from_wide(&buf))
+    }
+
+    unsafe fn format_message(
+        context: &Context,
+        provider: &str,
+        event: EVT_HANDLE,
+    ) -> Option<String> {
+        let metadata = {
+            let mut publishers = context.publishers.lock();
+            *publishers.entry(provider.to_string()).or_insert_with(|| {
+                let name = wide(provider);
+                EvtOpenPublisherMetadata(0, name.as_ptr(), std::ptr::null(), 0, 0)
+            })
+        };
+        if metadata == 0 {
+            return None;
+        }
+
+        let mut used = 0u32;
+        EvtFormatMessage(
+            metadata,
+            event,
+            0,
+            0,
+            std::ptr::null(),
+            EvtFormatMessageEvent as u32,
+            0,
+            std::ptr::null_mut(),
+            &mut used,
+        );
+        if used == 0 {
+            return None;
+        }
+        let mut buf = vec![0u16; used as usize];
+        let formatted = EvtFormatMessage(
+            metadata,
+            event,
+            0,
+            0,
+            std::ptr::null(),
+            EvtFormatMessageEvent as u32,
+            used,
+            buf.as_mut_ptr(),
+            &mut used,
+        );
+        (formatted != 0).then(|| from_wide(&buf))
+    }
+
+    fn wide(text: &str) -> Vec<u16> {
+        text.encode_utf16().chain(std::iter::once(0)).collect()
+    }
+
+    fn from_wide(buf: &[u16]) -> String {
+        let end = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+        String::from_utf16_lossy(&buf[..end])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_xml_to_line() {
+        let xml = r#"<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System><Provider Name='Service Control Manager' Guid='{555908d1}' EventSourceName='Service Control Manager'/><EventID Qualifiers='16384'>7036</EventID><Level>4</Level><TimeCreated SystemTime='2024-01-01T00:00:00.1234567Z'/><Channel>System</Channel><Computer>host-1</Computer></System><EventData><Data Name='param1'>Windows Update</Data><Data Name='param2'>running &amp; healthy</Data></EventData></Event>"#;
+
+        assert_eq!(
+            event_xml_to_line(
+                xml,
+                Some("The Windows Update service entered the running state.\r\n")
+            ),
+            r#"{"timestamp":"2024-01-01T00:00:00.123Z","level":"Information","provider":"Service Control Manager","event_id":7036,"channel":"System","computer":"host-1","message":"The Windows Update service entered the running state.","param1":"Windows Update","param2":"running & healthy"}"#
+        );
+    }
+
+    #[test]
+    fn test_unnamed_event_data() {
+        let xml = "<Event><System><Provider Name='App'/><Level>2</Level></System><EventData><Data>first</Data><Data/></EventData></Event>";
+        let line = event_xml_to_line(xml, None);
+        assert!(line.contains(r#""level":"Error""#));
+        assert!(line.contains(r#""data_0":"first","data_1":"""#));
+    }
+}
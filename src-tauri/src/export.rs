@@ -0,0 +1,334 @@
+use crate::indexer::{IndexerError, LogFile};
+use crate::palette::{self, Theme};
+use crate::regex_cache::{self, RegexFlags};
+use regex::{Regex, RegexSet};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Default cap on the number of lines written to an HTML export
+pub const DEFAULT_HTML_LINE_LIMIT: usize = 10_000;
+
+/// Lines rendered between cancellation checks
+const CANCEL_CHECK_LINES: usize = 1_000;
+
+/// Errors that can occur while exporting
+#[derive(Error, Debug)]
+pub enum ExportError {
+    #[error("Failed to write export: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid highlight pattern: {0}")]
+    InvalidPattern(#[from] regex::Error),
+    #[error(transparent)]
+    Indexer(#[from] IndexerError),
+}
+
+/// A highlight rule applied to exported lines
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HighlightRule {
+    pub pattern: String,
+    /// CSS color, either `#rrggbb`-style hex or a named color; None takes one from the palette
+    #[serde(default)]
+    pub color: Option<String>,
+}
+
+/// A user note attached to a line
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LineNote {
+    pub line: u64,
+    pub text: String,
+}
+
+/// Options describing the view to export
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct HtmlExportOptions {
+    /// Line numbers in the filtered view; None exports the file from the top
+    #[serde(default)]
+    pub lines: Option<Vec<u64>>,
+    #[serde(default)]
+    pub highlights: Vec<HighlightRule>,
+    #[serde(default)]
+    pub bookmarks: Vec<u64>,
+    #[serde(default)]
+    pub notes: Vec<LineNote>,
+    #[serde(default)]
+    pub max_lines: Option<usize>,
+    #[serde(default)]
+    pub title: Option<String>,
+    /// Theme of the document; None uses the palette settings' theme
+    #[serde(default)]
+    pub theme: Option<Theme>,
+    /// SHA-256 of the source file, stated in the export so the excerpt can be traced to it
+    #[serde(skip)]
+    pub source_sha256: Option<String>,
+}
+
+/// Summary of a completed export
+#[derive(Debug, Serialize)]
+pub struct ExportSummary {
+    pub path: String,
+    pub lines_written: usize,
+    pub truncated: bool,
+    /// SHA-256 of the written export
+    pub sha256: String,
+    pub source_sha256: Option<String>,
+}
+
+/// Render the given view of a log file into a standalone HTML document
+/// Returns the document and the number of lines it contains; gives up with `Cancelled`
+/// once `cancelled` returns true
+pub fn render_html(
+    file: &LogFile,
+    options: &HtmlExportOptions,
+    cancelled: impl Fn() -> bool,
+) -> Result<(String, usize, bool), ExportError> {
+    let limit = options.max_lines.unwrap_or(DEFAULT_HTML_LINE_LIMIT);
+    let patterns: Vec<&str> = options
+        .highlights
+        .iter()
+        .map(|r| r.pattern.as_str())
+        .collect();
+    let rules = HighlightRules {
+        set: regex_cache::regex_set(&patterns, RegexFlags::default())?,
+        rules: options
+            .highlights
+            .iter()
+            .map(|rule| {
+                Ok((
+                    regex_cache::regex(&rule.pattern)?,
+                    sanitize_color(rule.color.as_deref().unwrap_or_default()),
+                ))
+            })
+            .collect::<Result<_, ExportError>>()?,
+    };
+    let bookmarks: BTreeSet<u64> = options.bookmarks.iter().copied().collect();
+    let mut notes: BTreeMap<u64, Vec<&str>> = BTreeMap::new();
+    for note in &options.notes {
+        notes.entry(note.line).or_default().push(&note.text);
+    }
+
+    let (line_numbers, total): (Vec<u64>, usize) = match &options.lines {
+        Some(lines) => (lines.iter().copied().take(limit).collect(), lines.len()),
+        None => {
+            let total = file.line_count() as usize;
+            ((0..total.min(limit) as u64).collect(), total)
+        }
+    };
+    let truncated = total > line_numbers.len();
+
+    let title = options
+        .title
+        .clone()
+        .unwrap_or_else(|| format!("Log excerpt: {}", file.path()));
+
+    let mut html = String::with_capacity(line_numbers.len() * 128);
+    html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    let _ = writeln!(html, "<title>{}</title>", escape_html(&title));
+    html.push_str(match options.theme.unwrap_or_default() {
+        Theme::Dark => STYLE,
+        Theme::Light => LIGHT_STYLE,
+    });
+    html.push_str("</head>\n<body>\n");
+    let _ = writeln!(html, "<h1>{}</h1>", escape_html(&title));
+    let _ = writeln!(
+        html,
+        "<p class=\"meta\">{} &middot; {} of {} lines{}</p>",
+        escape_html(file.path()),
+        line_numbers.len(),
+        total,
+        if truncated { " (truncated)" } else { "" }
+    );
+    if let Some(sha256) = &options.source_sha256 {
+        let _ = writeln!(
+            html,
+            "<p class=\"meta\">Source SHA-256: {}</p>",
+            escape_html(sha256)
+        );
+    }
+    html.push_str("<table>\n");
+
+    for (i, &line) in line_numbers.iter().enumerate() {
+        if i % CANCEL_CHECK_LINES == 0 && cancelled() {
+            return Err(IndexerError::Cancelled.into());
+        }
+        let text = file
+            .get_lines(line, 1)?
+            .into_iter()
+            .next()
+            .unwrap_or_default();
+        let class = if bookmarks.contains(&line) {
+            " class=\"bookmark\""
+        } else {
+            ""
+        };
+        let _ = writeln!(
+            html,
+            "<tr{}><td class=\"ln\">{}</td><td class=\"text\">{}</td></tr>",
+            class,
+            line + 1,
+            highlight_line(&text, &rules)
+        );
+        if let Some(line_notes) = notes.get(&line) {
+            for note in line_notes {
+                let _ = writeln!(
+                    html,
+                    "<tr class=\"note\"><td></td><td>{}</td></tr>",
+                    escape_html(note)
+                );
+            }
+        }
+    }
+
+    html.push_str("</table>\n</body>\n</html>\n");
+    Ok((html, line_numbers.len(), truncated))
+}
+
+/// Escape text for inclusion in HTML
+pub fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Compiled highlight rules with their colors
+struct HighlightRules {
+    /// Every rule's pattern, to skip rules that can't match a line
+    set: Arc<RegexSet>,
+    rules: Vec<(Arc<Regex>, String)>,
+}
+
+/// Wrap regex matches in colored spans; earlier rules win where matches overlap
+fn highlight_line(text: &str, highlights: &HighlightRules) -> String {
+    let rules = &highlights.rules;
+    let mut spans: Vec<(usize, usize, usize)> = Vec::new();
+    for rule_idx in &highlights.set.matches(text) {
+        for m in rules[rule_idx].0.find_iter(text) {
+            if m.start() < m.end() {
+                spans.push((m.start(), m.end(), rule_idx));
+            }
+        }
+    }
+    spans.sort_by_key(|&(start, _, rule_idx)| (start, rule_idx));
+
+    let mut out = String::with_capacity(text.len() + spans.len() * 48);
+    let mut pos = 0;
+    for (start, end, rule_idx) in spans {
+        if start < pos {
+            continue;
+        }
+        out.push_str(&escape_html(&text[pos..start]));
+        let _ = write!(
+            out,
+            "<span style=\"background-color:{}\">{}</span>",
+            rules[rule_idx].1,
+            escape_html(&text[start..end])
+        );
+        pos = end;
+    }
+    out.push_str(&escape_html(&text[pos..]));
+    out
+}
+
+/// Only allow hex and named colors so rule colors can't inject markup
+fn sanitize_color(color: &str) -> String {
+    if palette::is_css_color(color) {
+        color.to_string()
+    } else {
+        "yellow".to_string()
+    }
+}
+
+const STYLE: &str = "<style>
+body { background: #1e1e1e; color: #d4d4d4; font-family: sans-serif; margin: 16px; }
+h1 { font-size: 16px; }
+.meta { color: #888; font-size: 12px; }
+table { border-collapse: collapse; font-family: monospace; font-size: 12px; width: 100%; }
+td { padding: 0 8px; vertical-align: top; white-space: pre-wrap; word-break: break-all; }
+td.ln { color: #858585; text-align: right; user-select: none; width: 1%; white-space: nowrap; }
+tr.bookmark td.ln { color: #1e1e1e; background: #e2c08d; }
+tr.note td { color: #9cdcfe; font-style: italic; padding-bottom: 4px; }
+span { color: #1e1e1e; }
+</style>
+";
+
+const LIGHT_STYLE: &str = "<style>
+body { background: #ffffff; color: #1f1f1f; font-family: sans-serif; margin: 16px; }
+h1 { font-size: 16px; }
+.meta { color: #6e6e6e; font-size: 12px; }
+table { border-collapse: collapse; font-family: monospace; font-size: 12px; width: 100%; }
+td { padding: 0 8px; vertical-align: top; white-space: pre-wrap; word-break: break-all; }
+td.ln { color: #8a8a8a; text-align: right; user-select: none; width: 1%; white-space: nowrap; }
+tr.bookmark td.ln { color: #1f1f1f; background: #f2d59b; }
+tr.note td { color: #0451a5; font-style: italic; padding-bottom: 4px; }
+span { color: #1f1f1f; }
+</style>
+";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexer::open_test_file;
+
+    #[test]
+    fn test_render_highlights_and_escapes() {
+        let (_tmp, log_file) = open_test_file("ok <b>\nERROR failed\n");
+        let options = HtmlExportOptions {
+            highlights: vec![HighlightRule {
+                pattern: "ERROR".to_string(),
+                color: Some("#ff0000".to_string()),
+            }],
+            bookmarks: vec![1],
+            notes: vec![LineNote {
+                line: 1,
+                text: "root cause".to_string(),
+            }],
+            source_sha256: Some("ab12".to_string()),
+            ..Default::default()
+        };
+
+        let (html, written, truncated) = render_html(&log_file, &options, || false).unwrap();
+        assert_eq!(written, 2);
+        assert!(!truncated);
+        assert!(html.contains("ok &lt;b&gt;"));
+        assert!(html.contains("<span style=\"background-color:#ff0000\">ERROR</span>"));
+        assert!(html.contains("<tr class=\"bookmark\">"));
+        assert!(html.contains("root cause"));
+        assert!(html.contains("Source SHA-256: ab12"));
+        assert!(matches!(
+            render_html(&log_file, &options, || true),
+            Err(ExportError::Indexer(IndexerError::Cancelled))
+        ));
+    }
+
+    #[test]
+    fn test_render_respects_line_limit() {
+        let (_tmp, log_file) = open_test_file("a\nb\nc\nd\n");
+        let options = HtmlExportOptions {
+            lines: Some(vec![0, 2, 3]),
+            max_lines: Some(2),
+            ..Default::default()
+        };
+
+        let (_, written, truncated) = render_html(&log_file, &options, || false).unwrap();
+        assert_eq!(written, 2);
+        assert!(truncated);
+    }
+
+    #[test]
+    fn test_sanitize_color() {
+        assert_eq!(sanitize_color("#abc"), "#abc");
+        assert_eq!(sanitize_color("orange"), "orange");
+        assert_eq!(sanitize_color("red\"><script>"), "yellow");
+    }
+}
@@ -0,0 +1,329 @@
+use crate::parsers::{Column, ColumnType};
+use thiserror::Error;
+
+/// Errors that can occur reading a field query
+#[derive(Debug, Error, PartialEq)]
+pub enum FieldQueryError {
+    #[error("Empty query")]
+    Empty,
+    #[error("Unclosed quote in query")]
+    UnclosedQuote,
+    #[error("Missing value for field \"{0}\"")]
+    MissingValue(String),
+    #[error("Unknown field \"{0}\"")]
+    UnknownField(String),
+    #[error("Field \"{field}\" holds {expected}, not \"{value}\"")]
+    InvalidValue {
+        field: String,
+        value: String,
+        expected: &'static str,
+    },
+}
+
+/// How a term compares a field with its value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Comparison {
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    fn sql(self) -> &'static str {
+        match self {
+            Comparison::Eq => "=",
+            Comparison::Lt => "<",
+            Comparison::Le => "<=",
+            Comparison::Gt => ">",
+            Comparison::Ge => ">=",
+        }
+    }
+}
+
+/// One whitespace-separated term of a field query
+#[derive(Debug, Clone, PartialEq)]
+struct Term {
+    negated: bool,
+    /// None for a bare word, matched anywhere in the raw line
+    field: Option<String>,
+    comparison: Comparison,
+    value: String,
+}
+
+/// A search over the fields of a parsed table, such as `level:ERROR path:/api/* status:>=500`
+/// Every term must hold: `field:value` matches a field equal to the value, ignoring case
+/// for text, where `*` stands for any run of characters and a lone `*` for any value;
+/// `field:>value` and the like compare numbers, timestamps and text; a bare word matches
+/// anywhere in the raw line and a leading `-` negates a term. Values with spaces are quoted
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldQuery {
+    terms: Vec<Term>,
+}
+
+impl FieldQuery {
+    pub fn parse(query: &str) -> Result<Self, FieldQueryError> {
+        let terms = split_terms(query)?
+            .into_iter()
+            .map(|token| parse_term(&token))
+            .collect::<Result<Vec<_>, _>>()?;
+        if terms.is_empty() {
+            return Err(FieldQueryError::Empty);
+        }
+        Ok(FieldQuery { terms })
+    }
+
+    /// SQL condition selecting the matching rows of a table with the given columns
+    /// Field names are looked up exactly, then ignoring case; values are checked against
+    /// the column type so a typo fails instead of matching nothing
+    pub fn to_sql(&self, columns: &[Column]) -> Result<String, FieldQueryError> {
+        let conditions = self
+            .terms
+            .iter()
+            .map(|term| {
+                let condition = match &term.field {
+                    None => format!(
+                        "raw_line ILIKE {}",
+                        quote_text(&contains_pattern(&term.value))
+                    ),
+                    Some(field) => {
+                        let column = find_column(columns, field)?;
+                        field_condition(column, term.comparison, &term.value)?
+                    }
+                };
+                Ok(if term.negated {
+                    // A missing value doesn't match, so negating it should select the row
+                    format!("({}) IS NOT TRUE", condition)
+                } else {
+                    condition
+                })
+            })
+            .collect::<Result<Vec<_>, FieldQueryError>>()?;
+        Ok(conditions.join(" AND "))
+    }
+}
+
+/// Split a query on whitespace outside double quotes, dropping the quotes
+fn split_terms(query: &str) -> Result<Vec<String>, FieldQueryError> {
+    let mut terms = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut started = false;
+    for c in query.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                started = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if started {
+                    terms.push(std::mem::take(&mut current));
+                    started = false;
+                }
+            }
+            c => {
+                current.push(c);
+                started = true;
+            }
+        }
+    }
+    if quoted {
+        return Err(FieldQueryError::UnclosedQuote);
+    }
+    if started {
+        terms.push(current);
+    }
+    Ok(terms)
+}
+
+fn parse_term(token: &str) -> Result<Term, FieldQueryError> {
+    let (negated, token) = match token.strip_prefix('-') {
+        Some(rest) if !rest.is_empty() => (true, rest),
+        _ => (false, token),
+    };
+    let Some((field, value)) = token.split_once(':').filter(|(field, _)| !field.is_empty()) else {
+        return Ok(Term {
+            negated,
+            field: None,
+            comparison: Comparison::Eq,
+            value: token.to_string(),
+        });
+    };
+    let (comparison, value) = [
+        (">=", Comparison::Ge),
+        ("<=", Comparison::Le),
+        (">", Comparison::Gt),
+        ("<", Comparison::Lt),
+        ("=", Comparison::Eq),
+    ]
+    .iter()
+    .find_map(|&(op, comparison)| value.strip_prefix(op).map(|rest| (comparison, rest)))
+    .unwrap_or((Comparison::Eq, value));
+    if value.is_empty() {
+        return Err(FieldQueryError::MissingValue(field.to_string()));
+    }
+    Ok(Term {
+        negated,
+        field: Some(field.to_string()),
+        comparison,
+        value: value.to_string(),
+    })
+}
+
+pub(crate) fn find_column<'a>(
+    columns: &'a [Column],
+    field: &str,
+) -> Result<&'a Column, FieldQueryError> {
+    columns
+        .iter()
+        .find(|c| c.name == field)
+        .or_else(|| columns.iter().find(|c| c.name.eq_ignore_ascii_case(field)))
+        .ok_or_else(|| FieldQueryError::UnknownField(field.to_string()))
+}
+
+/// SQL condition comparing a column with a value typed for it
+pub(crate) fn field_condition(
+    column: &Column,
+    comparison: Comparison,
+    value: &str,
+) -> Result<String, FieldQueryError> {
+    let name = quote_identifier(&column.name);
+    let invalid = |expected| FieldQueryError::InvalidValue {
+        field: column.name.clone(),
+        value: value.to_string(),
+        expected,
+    };
+    if comparison == Comparison::Eq && value == "*" {
+        return Ok(format!("{} IS NOT NULL", name));
+    }
+    if comparison == Comparison::Eq
+        && (value.contains('*') || column.column_type == ColumnType::Utf8)
+    {
+        // Text matches ignoring case, and wildcards match the text of any column, e.g.
+        // `status:5*` for every 5xx
+        let text = match column.column_type {
+            ColumnType::Utf8 => name,
+            _ => format!("CAST({} AS VARCHAR)", name),
+        };
+        return Ok(format!(
+            "{} ILIKE {}",
+            text,
+            quote_text(&like_pattern(value))
+        ));
+    }
+    let literal = match column.column_type {
+        ColumnType::Utf8 => quote_text(value),
+        ColumnType::Int64 => value
+            .parse::<i64>()
+            .map_err(|_| invalid("whole numbers"))?
+            .to_string(),
+        ColumnType::Float64 => value
+            .parse::<f64>()
+            .ok()
+            .filter(|v| v.is_finite())
+            .ok_or_else(|| invalid("numbers"))?
+            .to_string(),
+        ColumnType::Boolean => match value.to_ascii_lowercase().as_str() {
+            "true" => "true".to_string(),
+            "false" => "false".to_string(),
+            _ => return Err(invalid("true or false")),
+        },
+        ColumnType::Timestamp => format!("CAST({} AS TIMESTAMP)", quote_text(value)),
+    };
+    Ok(format!("{} {} {}", name, comparison.sql(), literal))
+}
+
+/// LIKE pattern for a value where `*` matches any run of characters
+pub(crate) fn like_pattern(value: &str) -> String {
+    let mut pattern = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '*' => pattern.push('%'),
+            '%' | '_' | '\\' => {
+                pattern.push('\\');
+                pattern.push(c);
+            }
+            c => pattern.push(c),
+        }
+    }
+    pattern
+}
+
+fn contains_pattern(value: &str) -> String {
+    format!("%{}%", like_pattern(value))
+}
+
+pub(crate) fn quote_text(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+pub(crate) fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn columns() -> Vec<Column> {
+        [
+            ("raw_line", ColumnType::Utf8),
+            ("level", ColumnType::Utf8),
+            ("cs-uri-stem", ColumnType::Utf8),
+            ("status", ColumnType::Int64),
+            ("duration", ColumnType::Float64),
+            ("cached", ColumnType::Boolean),
+            ("time", ColumnType::Timestamp),
+        ]
+        .iter()
+        .map(|&(name, column_type)| Column {
+            name: name.to_string(),
+            column_type,
+        })
+        .collect()
+    }
+
+    fn sql(query: &str) -> Result<String, FieldQueryError> {
+        FieldQuery::parse(query)?.to_sql(&columns())
+    }
+
+    #[test]
+    fn test_field_terms_become_typed_predicates() {
+        assert_eq!(
+            sql("level:ERROR CS-URI-STEM:/api/* status:>=500").unwrap(),
+            "\"level\" ILIKE 'ERROR' AND \"cs-uri-stem\" ILIKE '/api/%' AND \"status\" >= 500"
+        );
+        assert_eq!(
+            sql("status:5* -cached:true time:>2024-01-01T00:00:00").unwrap(),
+            "CAST(\"status\" AS VARCHAR) ILIKE '5%' AND (\"cached\" = true) IS NOT TRUE \
+             AND \"time\" > CAST('2024-01-01T00:00:00' AS TIMESTAMP)"
+        );
+        assert_eq!(
+            sql("level:\"it's 100%\" timeout duration:*").unwrap(),
+            "\"level\" ILIKE 'it''s 100\\%' AND raw_line ILIKE '%timeout%' \
+             AND \"duration\" IS NOT NULL"
+        );
+    }
+
+    #[test]
+    fn test_field_query_errors() {
+        assert_eq!(sql("  "), Err(FieldQueryError::Empty));
+        assert_eq!(sql("level:\"ERROR"), Err(FieldQueryError::UnclosedQuote));
+        assert_eq!(
+            sql("status:>"),
+            Err(FieldQueryError::MissingValue("status".to_string()))
+        );
+        assert_eq!(
+            sql("host:web01"),
+            Err(FieldQueryError::UnknownField("host".to_string()))
+        );
+        assert!(matches!(
+            sql("status:ok"),
+            Err(FieldQueryError::InvalidValue {
+                expected: "whole numbers",
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,262 @@
+use crate::analysis::preview;
+use crate::indexer::LogFile;
+use crate::metrics::{Extractor, MetricError};
+use crate::regex_cache;
+use crate::timeline::TimeRange;
+use crate::timestamp::parse_timestamp_bytes;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Errors that can occur setting up a funnel
+#[derive(Debug, Error)]
+pub enum FunnelError {
+    #[error("A funnel needs at least two stages")]
+    TooFewStages,
+    #[error("Invalid pattern for stage \"{stage}\": {source}")]
+    Pattern { stage: String, source: regex::Error },
+    #[error(transparent)]
+    Metric(#[from] MetricError),
+}
+
+/// A step of a funnel, matched by a regex
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FunnelStage {
+    pub name: String,
+    pub pattern: String,
+}
+
+fn default_max_examples() -> usize {
+    20
+}
+
+/// Stages in order and how lines are tied to one run through them
+#[derive(Debug, Clone, Deserialize)]
+pub struct FunnelOptions {
+    pub stages: Vec<FunnelStage>,
+    /// Where the key tying a run's lines together is, such as a request id
+    pub key: Extractor,
+    #[serde(default)]
+    pub range: Option<TimeRange>,
+    /// Runs that stopped early listed as examples
+    #[serde(default = "default_max_examples")]
+    pub max_examples: usize,
+}
+
+/// How many runs got to a stage
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StageReport {
+    pub name: String,
+    pub reached: u64,
+    /// Share of the runs reaching the previous stage that reached this one
+    pub conversion: f64,
+    /// Runs that reached this stage and no further
+    pub stopped: u64,
+    /// Median time from the previous stage, over runs with timestamps on both
+    pub median_ms: Option<f64>,
+}
+
+/// A run that stopped before the last stage
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StalledRun {
+    pub key: String,
+    /// Last stage reached
+    pub stage: String,
+    /// 0-based index of the line matching it
+    pub line: u64,
+    pub text: String,
+}
+
+/// Completion of the runs through a funnel
+#[derive(Debug, Clone, Serialize)]
+pub struct FunnelReport {
+    /// Runs that reached the first stage
+    pub runs: u64,
+    pub completed: u64,
+    pub stages: Vec<StageReport>,
+    /// Earliest first
+    pub stalled: Vec<StalledRun>,
+}
+
+/// A run's progress: the next stage it's waiting for, the line of the last one reached and
+/// the time of each reached
+struct Run {
+    next: usize,
+    line: u64,
+    times: Vec<Option<i64>>,
+}
+
+/// Follow each key through the stages in order and report where runs stop
+/// A key's run starts at its first line matching the first stage; later lines move it on
+/// when they match the stage it's waiting for
+pub fn analyse_funnel(
+    file: &LogFile,
+    options: &FunnelOptions,
+) -> Result<FunnelReport, FunnelError> {
+    if options.stages.len() < 2 {
+        return Err(FunnelError::TooFewStages);
+    }
+    if let Some(range) = &options.range {
+        range.validate().map_err(MetricError::from)?;
+    }
+    let patterns = options
+        .stages
+        .iter()
+        .map(|stage| {
+            regex_cache::regex(&stage.pattern).map_err(|source| FunnelError::Pattern {
+                stage: stage.name.clone(),
+                source,
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let key = options.key.compile()?;
+
+    let mut runs: HashMap<String, Run> = HashMap::new();
+    for line in 0..file.line_count() {
+        let Some(bytes) = file.line_bytes(line) else {
+            continue;
+        };
+        let time = parse_timestamp_bytes(bytes);
+        if let Some(range) = options.range {
+            if !time.is_some_and(|time| range.contains(time)) {
+                continue;
+            }
+        }
+        let text = String::from_utf8_lossy(bytes);
+        let Some(id) = key.extract(&text) else {
+            continue;
+        };
+        match runs.get_mut(&id) {
+            Some(run) => {
+                if run.next < patterns.len() && patterns[run.next].is_match(&text) {
+                    run.next += 1;
+                    run.line = line;
+                    run.times.push(time);
+                }
+            }
+            None if patterns[0].is_match(&text) => {
+                let run = Run {
+                    next: 1,
+                    line,
+                    times: vec![time],
+                };
+                runs.insert(id, run);
+            }
+            None => {}
+        }
+    }
+
+    let stage_count = patterns.len();
+    let mut reached = vec![0u64; stage_count];
+    let mut durations: Vec<Vec<f64>> = vec![Vec::new(); stage_count];
+    for run in runs.values() {
+        for count in &mut reached[..run.next] {
+            *count += 1;
+        }
+        for (stage, pair) in run.times.windows(2).enumerate() {
+            if let [Some(from), Some(to)] = pair {
+                durations[stage + 1].push((to - from) as f64);
+            }
+        }
+    }
+    let stages = options
+        .stages
+        .iter()
+        .enumerate()
+        .map(|(index, stage)| {
+            let previous = reached[index.saturating_sub(1)];
+            let durations = &mut durations[index];
+            durations.sort_unstable_by(f64::total_cmp);
+            StageReport {
+                name: stage.name.clone(),
+                reached: reached[index],
+                conversion: if previous == 0 {
+                    0.0
+                } else {
+                    reached[index] as f64 / previous as f64
+                },
+                stopped: reached[index] - reached.get(index + 1).copied().unwrap_or(0),
+                median_ms: durations.get(durations.len() / 2).copied(),
+            }
+        })
+        .collect();
+
+    let mut stalled: Vec<(&String, &Run)> = runs
+        .iter()
+        .filter(|(_, run)| run.next < stage_count)
+        .collect();
+    stalled.sort_unstable_by_key(|(_, run)| run.line);
+    let stalled = stalled
+        .into_iter()
+        .take(options.max_examples)
+        .map(|(key, run)| StalledRun {
+            key: key.clone(),
+            stage: options.stages[run.next - 1].name.clone(),
+            line: run.line,
+            text: file.line_bytes(run.line).map(preview).unwrap_or_default(),
+        })
+        .collect();
+
+    Ok(FunnelReport {
+        runs: reached[0],
+        completed: reached[stage_count - 1],
+        stages,
+        stalled,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexer::open_test_file;
+
+    #[test]
+    fn test_analyse_funnel() {
+        let (_file, log_file) = open_test_file(
+            "2024-05-01T10:00:00.000Z req=a received\n\
+             2024-05-01T10:00:00.100Z req=b received\n\
+             2024-05-01T10:00:00.150Z req=a auth ok\n\
+             2024-05-01T10:00:00.200Z req=c auth ok\n\
+             2024-05-01T10:00:00.300Z req=b auth ok\n\
+             2024-05-01T10:00:00.400Z req=a handler done\n\
+             2024-05-01T10:00:01.000Z req=d received\n",
+        );
+
+        let stage = |name: &str, pattern: &str| FunnelStage {
+            name: name.to_string(),
+            pattern: pattern.to_string(),
+        };
+        let options = FunnelOptions {
+            stages: vec![
+                stage("received", "received"),
+                stage("auth", "auth ok"),
+                stage("done", "handler done"),
+            ],
+            key: Extractor::Regex {
+                pattern: r"req=(\w+)".to_string(),
+            },
+            range: None,
+            max_examples: 10,
+        };
+        let report = analyse_funnel(&log_file, &options).unwrap();
+        // c never arrived, so it isn't a run
+        assert_eq!((report.runs, report.completed), (3, 1));
+        let stages: Vec<_> = report
+            .stages
+            .iter()
+            .map(|s| (s.reached, s.stopped, s.median_ms))
+            .collect();
+        assert_eq!(
+            stages,
+            [(3, 1, None), (2, 1, Some(200.0)), (1, 1, Some(250.0))]
+        );
+        assert_eq!(report.stages[1].conversion, 2.0 / 3.0);
+
+        let stalled: Vec<_> = report
+            .stalled
+            .iter()
+            .map(|s| (s.key.as_str(), s.stage.as_str(), s.line))
+            .collect();
+        assert_eq!(stalled, [("b", "auth", 4), ("d", "received", 6)]);
+    }
+}
@@ -0,0 +1,361 @@
+use crate::field_search::quote_identifier;
+use crate::parsers::{Column, ColumnType};
+use crate::views::is_identifier;
+use datafusion::arrow::array::{Array, ArrayRef, AsArray};
+use datafusion::arrow::compute::cast;
+use datafusion::arrow::datatypes::{DataType, Float64Type, Int64Type};
+use datafusion::arrow::error::ArrowError;
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::error::DataFusionError;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use thiserror::Error;
+
+/// Errors that can occur handing results off to a database
+#[derive(Debug, Error)]
+pub enum HandoffError {
+    #[error("Failed to convert results: {0}")]
+    Arrow(#[from] ArrowError),
+    #[error("Query failed: {0}")]
+    Query(#[from] DataFusionError),
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[cfg(feature = "duckdb")]
+    #[error("DuckDB error: {0}")]
+    Duckdb(#[from] duckdb::Error),
+    #[error("DuckDB databases need a build with the `duckdb` feature")]
+    DuckdbUnavailable,
+    #[error("Invalid table name \"{0}\": use letters, digits and underscores")]
+    InvalidName(String),
+}
+
+/// Database file format results are handed off to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DatabaseKind {
+    Sqlite,
+    /// Needs the `duckdb` feature
+    Duckdb,
+}
+
+impl DatabaseKind {
+    fn column_type(self, column_type: ColumnType) -> &'static str {
+        match (self, column_type) {
+            (DatabaseKind::Sqlite, ColumnType::Utf8) => "TEXT",
+            (DatabaseKind::Sqlite, ColumnType::Int64) => "INTEGER",
+            (DatabaseKind::Sqlite, ColumnType::Float64) => "REAL",
+            (DatabaseKind::Duckdb, ColumnType::Utf8) => "VARCHAR",
+            (DatabaseKind::Duckdb, ColumnType::Int64) => "BIGINT",
+            (DatabaseKind::Duckdb, ColumnType::Float64) => "DOUBLE",
+            (_, ColumnType::Boolean) => "BOOLEAN",
+            (_, ColumnType::Timestamp) => "TIMESTAMP",
+        }
+    }
+
+    /// Placeholder binding a value of the column type; timestamps are bound as ISO 8601
+    /// text, which SQLite keeps as is and DuckDB has to be told to convert
+    fn placeholder(self, column_type: ColumnType) -> &'static str {
+        match (self, column_type) {
+            (DatabaseKind::Duckdb, ColumnType::Timestamp) => "CAST(? AS TIMESTAMP)",
+            _ => "?",
+        }
+    }
+}
+
+/// What a handoff wrote
+#[derive(Debug, Clone, Serialize)]
+pub struct HandoffSummary {
+    pub path: String,
+    pub kind: DatabaseKind,
+    pub table: String,
+    pub columns: Vec<Column>,
+    pub rows: u64,
+}
+
+/// One cell, typed for binding into either database
+#[derive(Debug, Clone, PartialEq)]
+enum SqlValue {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Boolean(bool),
+    Text(String),
+}
+
+impl rusqlite::ToSql for SqlValue {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        use rusqlite::types::{Null, ToSqlOutput};
+        Ok(match self {
+            SqlValue::Null => ToSqlOutput::from(Null),
+            SqlValue::Integer(v) => ToSqlOutput::from(*v),
+            SqlValue::Real(v) => ToSqlOutput::from(*v),
+            SqlValue::Boolean(v) => ToSqlOutput::from(*v),
+            SqlValue::Text(v) => ToSqlOutput::from(v.as_str()),
+        })
+    }
+}
+
+#[cfg(feature = "duckdb")]
+impl duckdb::ToSql for SqlValue {
+    fn to_sql(&self) -> duckdb::Result<duckdb::types::ToSqlOutput<'_>> {
+        use duckdb::types::{Null, ToSqlOutput};
+        Ok(match self {
+            SqlValue::Null => ToSqlOutput::from(Null),
+            SqlValue::Integer(v) => ToSqlOutput::from(*v),
+            SqlValue::Real(v) => ToSqlOutput::from(*v),
+            SqlValue::Boolean(v) => ToSqlOutput::from(*v),
+            SqlValue::Text(v) => ToSqlOutput::from(v.as_str()),
+        })
+    }
+}
+
+/// Write results to a table of a database file, created if missing, replacing any table of
+/// the same name. The table is written in one transaction. Returns the number of rows
+/// Batches are written as they come, so a query's stream can be exported without holding
+/// all of it; a failed batch rolls the whole table back
+pub fn write_database(
+    kind: DatabaseKind,
+    path: &Path,
+    table: &str,
+    columns: &[Column],
+    batches: impl IntoIterator<Item = Result<RecordBatch, DataFusionError>>,
+) -> Result<u64, HandoffError> {
+    if !is_identifier(table) {
+        return Err(HandoffError::InvalidName(table.to_string()));
+    }
+    let statements = Statements::new(kind, table, columns);
+    match kind {
+        DatabaseKind::Sqlite => write_sqlite(path, &statements, columns, batches),
+        DatabaseKind::Duckdb => write_duckdb(path, &statements, columns, batches),
+    }
+}
+
+/// SQL replacing a table and filling it
+struct Statements {
+    drop: String,
+    create: String,
+    insert: String,
+}
+
+impl Statements {
+    fn new(kind: DatabaseKind, table: &str, columns: &[Column]) -> Self {
+        let table = quote_identifier(table);
+        let definitions = columns
+            .iter()
+            .map(|c| {
+                format!(
+                    "{} {}",
+                    quote_identifier(&c.name),
+                    kind.column_type(c.column_type)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let placeholders = columns
+            .iter()
+            .map(|c| kind.placeholder(c.column_type))
+            .collect::<Vec<_>>()
+            .join(", ");
+        Statements {
+            drop: format!("DROP TABLE IF EXISTS {}", table),
+            create: format!("CREATE TABLE {} ({})", table, definitions),
+            insert: format!("INSERT INTO {} VALUES ({})", table, placeholders),
+        }
+    }
+}
+
+fn write_sqlite(
+    path: &Path,
+    statements: &Statements,
+    columns: &[Column],
+    batches: impl IntoIterator<Item = Result<RecordBatch, DataFusionError>>,
+) -> Result<u64, HandoffError> {
+    let mut conn = rusqlite::Connection::open(path)?;
+    let tx = conn.transaction()?;
+    tx.execute(&statements.drop, [])?;
+    tx.execute(&statements.create, [])?;
+    let mut rows = 0;
+    {
+        let mut insert = tx.prepare(&statements.insert)?;
+        for batch in batches {
+            for row in batch_rows(&batch?, columns)? {
+                insert.execute(rusqlite::params_from_iter(row.iter()))?;
+                rows += 1;
+            }
+        }
+    }
+    tx.commit()?;
+    Ok(rows)
+}
+
+#[cfg(feature = "duckdb")]
+fn write_duckdb(
+    path: &Path,
+    statements: &Statements,
+    columns: &[Column],
+    batches: impl IntoIterator<Item = Result<RecordBatch, DataFusionError>>,
+) -> Result<u64, HandoffError> {
+    let mut conn = duckdb::Connection::open(path)?;
+    let tx = conn.transaction()?;
+    tx.execute(&statements.drop, [])?;
+    tx.execute(&statements.create, [])?;
+    let mut rows = 0;
+    {
+        let mut insert = tx.prepare(&statements.insert)?;
+        for batch in batches {
+            for row in batch_rows(&batch?, columns)? {
+                insert.execute(duckdb::params_from_iter(row.iter()))?;
+                rows += 1;
+            }
+        }
+    }
+    tx.commit()?;
+    Ok(rows)
+}
+
+#[cfg(not(feature = "duckdb"))]
+fn write_duckdb(
+    _path: &Path,
+    _statements: &Statements,
+    _columns: &[Column],
+    _batches: impl IntoIterator<Item = Result<RecordBatch, DataFusionError>>,
+) -> Result<u64, HandoffError> {
+    Err(HandoffError::DuckdbUnavailable)
+}
+
+/// Rows of a batch as typed cells; timestamps, and any type a parse never produces, are
+/// written as text
+fn batch_rows(batch: &RecordBatch, columns: &[Column]) -> Result<Vec<Vec<SqlValue>>, ArrowError> {
+    let arrays = batch
+        .columns()
+        .iter()
+        .zip(columns)
+        .map(|(array, column)| {
+            let data_type = match column.column_type {
+                ColumnType::Int64 => DataType::Int64,
+                ColumnType::Float64 => DataType::Float64,
+                ColumnType::Boolean => DataType::Boolean,
+                ColumnType::Utf8 | ColumnType::Timestamp => DataType::Utf8,
+            };
+            cast(array, &data_type)
+        })
+        .collect::<Result<Vec<ArrayRef>, _>>()?;
+    Ok((0..batch.num_rows())
+        .map(|row| {
+            arrays
+                .iter()
+                .zip(columns)
+                .map(|(array, column)| cell(array, row, column.column_type))
+                .collect()
+        })
+        .collect())
+}
+
+fn cell(array: &ArrayRef, row: usize, column_type: ColumnType) -> SqlValue {
+    if array.is_null(row) {
+        return SqlValue::Null;
+    }
+    match column_type {
+        ColumnType::Int64 => SqlValue::Integer(array.as_primitive::<Int64Type>().value(row)),
+        ColumnType::Float64 => SqlValue::Real(array.as_primitive::<Float64Type>().value(row)),
+        ColumnType::Boolean => SqlValue::Boolean(array.as_boolean().value(row)),
+        ColumnType::Utf8 | ColumnType::Timestamp => {
+            SqlValue::Text(array.as_string::<i32>().value(row).to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query_engine::QueryEngine;
+    use futures::StreamExt;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[tokio::test]
+    async fn test_sqlite_handoff_keeps_types() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "GET /a 12.5\nPOST /b").unwrap();
+        file.flush().unwrap();
+        let engine = QueryEngine::new();
+        engine.register_table(file.path(), "logs").await.unwrap();
+        let (columns, batches) = engine
+            .query_batches(
+                "SELECT line_number, line, \
+                 TRY_CAST(NULLIF(split_part(line, ' ', 3), '') AS DOUBLE) AS ms, \
+                 line LIKE 'GET%' AS is_get, \
+                 CAST('2024-03-01T09:15:00' AS TIMESTAMP) AS ts FROM logs ORDER BY line_number",
+            )
+            .await
+            .unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("handoff.sqlite");
+        // Writing again replaces the table rather than adding to it
+        for _ in 0..2 {
+            let rows = write_database(
+                DatabaseKind::Sqlite,
+                &db,
+                "results",
+                &columns,
+                batches.iter().cloned().map(Ok),
+            )
+            .unwrap();
+            assert_eq!(rows, 2);
+        }
+
+        let conn = rusqlite::Connection::open(&db).unwrap();
+        let rows: Vec<(String, String, Option<f64>, bool, String)> = conn
+            .prepare("SELECT typeof(line_number), line, ms, is_get, ts FROM results")
+            .unwrap()
+            .query_map([], |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                ))
+            })
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                (
+                    "integer".to_string(),
+                    "GET /a 12.5".to_string(),
+                    Some(12.5),
+                    true,
+                    "2024-03-01T09:15:00".to_string()
+                ),
+                (
+                    "integer".to_string(),
+                    "POST /b".to_string(),
+                    None,
+                    false,
+                    "2024-03-01T09:15:00".to_string()
+                ),
+            ]
+        );
+
+        assert!(matches!(
+            write_database(DatabaseKind::Sqlite, &db, "no such", &columns, []),
+            Err(HandoffError::InvalidName(_))
+        ));
+
+        // A query's stream is written batch by batch as it's pulled
+        let (columns, mut stream) = engine.query_stream("SELECT line FROM logs").await.unwrap();
+        let handle = tokio::runtime::Handle::current();
+        let rows = tokio::task::spawn_blocking(move || {
+            let batches = std::iter::from_fn(|| handle.block_on(stream.next()));
+            write_database(DatabaseKind::Sqlite, &db, "streamed", &columns, batches)
+        })
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(rows, 2);
+    }
+}
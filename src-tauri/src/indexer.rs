@@ -1,12 +1,25 @@
-use memchr::memchr_iter;
+use flate2::bufread;
+use flate2::read::MultiGzDecoder;
+use memchr::{memchr, memchr_iter};
 use memmap2::Mmap;
 use parking_lot::RwLock;
 use rayon::prelude::*;
-use std::fs::File;
+use serde::Serialize;
+use std::borrow::Cow;
+use std::fs::{File, Metadata};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
 use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Instant;
 use thiserror::Error;
 
+use crate::profile;
+use crate::regex_cache::{Matcher, RegexFlavor};
+use crate::timestamp;
+use crate::trigram::TrigramIndex;
+
 /// Errors that can occur during log file operations
 #[derive(Error, Debug)]
 pub enum IndexerError {
@@ -16,108 +29,545 @@ pub enum IndexerError {
     EmptyFile,
     #[error("Invalid line range: start={0}, count={1}, total_lines={2}")]
     InvalidRange(u64, u64, u64),
+    #[error("Cancelled")]
+    Cancelled,
+    #[error("Decompressed file is larger than {0} bytes")]
+    TooLarge(u64),
+}
+
+/// Bytes every gzip stream starts with
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Most bytes a gzip file may decompress to, so an archive that expands without end can't
+/// fill the disk
+pub const MAX_GZIP_BYTES: u64 = 32 * 1024 * 1024 * 1024;
+/// Bytes decompressed between checks for cancellation
+const GZIP_BLOCK_BYTES: usize = 1024 * 1024;
+
+/// Whether a file is gzip-compressed, by its first bytes rather than its name, such as a
+/// rotated `app.log.3.gz`. Leaves the file positioned at its start
+pub fn is_gzip(file: &mut File) -> io::Result<bool> {
+    let mut magic = [0u8; 2];
+    let read = file.read(&mut magic)?;
+    file.seek(SeekFrom::Start(0))?;
+    Ok(read == 2 && magic == GZIP_MAGIC)
 }
 
-/// Result of chunk processing during parallel indexing
-struct ChunkResult {
-    offsets: Vec<u64>,
+/// Work units per indexing thread, so threads that finish early can take over the rest
+const INDEX_UNITS_PER_THREAD: usize = 8;
+/// Bounds on the bytes per indexing work unit: small units cost scheduling overhead, and
+/// large ones leave cores idle at the end
+const MIN_INDEX_CHUNK: usize = 1024 * 1024;
+const MAX_INDEX_CHUNK: usize = 16 * 1024 * 1024;
+
+/// Bytes per indexing work unit for `len` bytes over `threads` threads
+fn index_chunk_size(len: usize, threads: usize) -> usize {
+    (len / (threads.max(1) * INDEX_UNITS_PER_THREAD)).clamp(MIN_INDEX_CHUNK, MAX_INDEX_CHUNK)
+}
+
+/// How well an index build used the available threads
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct IndexStats {
+    pub chunks: usize,
+    pub chunk_bytes: usize,
+    pub threads: usize,
+    pub wall_ms: f64,
+    /// Time spent scanning chunks, summed over all threads
+    pub busy_ms: f64,
+    /// `busy_ms` over `wall_ms` times `threads`: 1.0 when every thread scanned the whole time
+    pub efficiency: f64,
+}
+
+/// Bytes backing a log file view
+enum Backing {
+    /// Zero-copy mapping of a single file on disk
+    Mapped(Mmap),
+    /// Owned buffer, used once the view no longer corresponds to one file (e.g. after rotation)
+    Owned(Vec<u8>),
+}
+
+impl std::ops::Deref for Backing {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Backing::Mapped(mmap) => mmap,
+            Backing::Owned(buf) => buf,
+        }
+    }
+}
+
+/// Where a line sits in the file, for lining it up with byte-oriented tools like dd
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct LineMeta {
+    pub line: u64,
+    pub byte_offset: u64,
+    /// Length without the line terminator
+    pub byte_len: u64,
+    /// False for a last line with no newline, e.g. one cut off by a truncated write
+    pub terminated: bool,
+}
+
+/// Lines picked up by a refresh of a growing file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Appended {
+    /// Lines that are new or were extended
+    pub lines: Range<u64>,
+    /// Line number of the marker inserted when the file was rotated
+    pub rotation_marker: Option<u64>,
 }
 
 /// A memory-mapped log file with pre-built line index for O(1) access
 pub struct LogFile {
-    mmap: Mmap,
+    data: Backing,
     /// Line offsets - each entry is the byte offset where a line starts
     line_offsets: Vec<u64>,
     /// File size in bytes
     file_size: u64,
     /// File path
     path: String,
+    /// Handle of the file currently being read, kept open so a rotated file can be drained
+    /// None for live sources whose data is pushed in with `append_bytes`
+    source: Option<File>,
+    /// Bytes of `source` already included in `data`
+    source_pos: u64,
+    /// Measurements of the initial index build; None for live views
+    index_stats: Option<IndexStats>,
+    /// Optional block filters that let literal searches skip most of the file
+    trigrams: Option<TrigramIndex>,
 }
 
 impl LogFile {
     /// Open a log file and build the line index
     /// Uses memory mapping for zero-copy access and parallel indexing for speed
+    /// Gzip-compressed files are decompressed to a temporary file and indexed like plain ones
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, IndexerError> {
+        Self::open_cancellable(path, || false)
+    }
+
+    /// Open a log file like `open`, stopping with `Cancelled` once `cancelled` returns true
+    /// while a gzip file is decompressed
+    pub fn open_cancellable<P: AsRef<Path>>(
+        path: P,
+        cancelled: impl Fn() -> bool,
+    ) -> Result<Self, IndexerError> {
         let path_str = path.as_ref().to_string_lossy().to_string();
-        let file = File::open(&path)?;
+        let mut file = File::open(&path)?;
         let metadata = file.metadata()?;
         let file_size = metadata.len();
 
         if file_size == 0 {
             return Err(IndexerError::EmptyFile);
         }
+        if is_gzip(&mut file)? {
+            return Self::open_gzip(path_str, file, cancelled);
+        }
+
+        let mut span = profile::span("index", "open_file").arg("bytes", file_size);
 
         // Safety: We're opening in read-only mode and the file exists
         let mmap = unsafe { Mmap::map(&file)? };
 
         // Build the line index using parallel processing
-        let line_offsets = Self::build_index(&mmap);
+        let (line_offsets, stats) = Self::build_index(&mmap);
+        span.record("lines", line_offsets.len());
+        span.record("chunks", stats.chunks);
+        span.record("efficiency", stats.efficiency);
+        tracing::debug!(
+            path = %path_str,
+            bytes = file_size,
+            lines = line_offsets.len(),
+            chunks = stats.chunks,
+            efficiency = stats.efficiency,
+            "indexed file"
+        );
 
         Ok(LogFile {
-            mmap,
+            data: Backing::Mapped(mmap),
             line_offsets,
             file_size,
             path: path_str,
+            source: Some(file),
+            source_pos: file_size,
+            index_stats: Some(stats),
+            trigrams: None,
+        })
+    }
+
+    /// Index the decompressed contents of a gzip file, every member of it for logs that
+    /// were appended to while compressed. They are written to an anonymous temporary file
+    /// and mapped like a plain file, up to `MAX_GZIP_BYTES`
+    /// A compressed file isn't followed, since it isn't written to while it's read
+    fn open_gzip(
+        path: String,
+        file: File,
+        cancelled: impl Fn() -> bool,
+    ) -> Result<Self, IndexerError> {
+        let mut span = profile::span("index", "open_gzip").arg("path", path.as_str());
+        let mut decoder = MultiGzDecoder::new(BufReader::new(file));
+        let mut temp = tempfile::tempfile()?;
+        let mut block = vec![0u8; GZIP_BLOCK_BYTES];
+        let mut written = 0u64;
+        loop {
+            if cancelled() {
+                return Err(IndexerError::Cancelled);
+            }
+            let read = match decoder.read(&mut block) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+            written += read as u64;
+            if written > MAX_GZIP_BYTES {
+                return Err(IndexerError::TooLarge(MAX_GZIP_BYTES));
+            }
+            temp.write_all(&block[..read])?;
+        }
+        if written == 0 {
+            return Err(IndexerError::EmptyFile);
+        }
+        // Safety: the temporary file is private to this process and no longer written to
+        let data = unsafe { Mmap::map(&temp)? };
+        let (line_offsets, stats) = Self::build_index(&data);
+        span.record("bytes", data.len());
+        span.record("lines", line_offsets.len());
+        tracing::debug!(
+            path = %path,
+            bytes = data.len(),
+            lines = line_offsets.len(),
+            "indexed gzip file"
+        );
+
+        Ok(LogFile {
+            file_size: data.len() as u64,
+            data: Backing::Mapped(data),
+            line_offsets,
+            path,
+            source: None,
+            source_pos: 0,
+            index_stats: Some(stats),
+            trigrams: None,
         })
     }
 
     /// Build line index using parallel SIMD-accelerated scanning
     /// Divides the file into chunks and processes them in parallel using rayon
-    fn build_index(data: &[u8]) -> Vec<u64> {
-        let data_len = data.len();
-        if data_len == 0 {
-            return vec![0];
-        }
-
-        // Determine optimal chunk size based on CPU cores
-        // Target ~64MB chunks for good parallelism without excessive overhead
-        let num_cores = rayon::current_num_threads();
-        let chunk_size = std::cmp::max(64 * 1024 * 1024, data_len / num_cores);
-
-        // Calculate chunk boundaries
-        let chunks: Vec<(usize, usize)> = (0..data_len)
-            .step_by(chunk_size)
-            .map(|start| {
-                let end = std::cmp::min(start + chunk_size, data_len);
-                (start, end)
+    fn build_index(data: &[u8]) -> (Vec<u64>, IndexStats) {
+        let threads = rayon::current_num_threads();
+        Self::build_index_chunked(data, index_chunk_size(data.len(), threads))
+    }
+
+    /// Build the line index scanning chunks of `chunk_size` bytes in parallel
+    /// Rayon splits the chunks between threads adaptively, stealing work from busy threads.
+    /// A first pass counts each chunk's newlines so the index is allocated once at its exact
+    /// size and every chunk writes its offsets straight into its own part of it, in order;
+    /// scanning twice costs less than holding every offset twice on billion-line files
+    fn build_index_chunked(data: &[u8], chunk_size: usize) -> (Vec<u64>, IndexStats) {
+        let chunk_size = chunk_size.max(1);
+        let threads = rayon::current_num_threads();
+        let started = Instant::now();
+        let busy_nanos = AtomicU64::new(0);
+        let timed = |chunk_started: Instant| {
+            busy_nanos.fetch_add(chunk_started.elapsed().as_nanos() as u64, Ordering::Relaxed);
+        };
+
+        // Use SIMD-accelerated newline search (memchr processes 32 bytes at a time)
+        let counts: Vec<usize> = data
+            .par_chunks(chunk_size)
+            .map(|chunk| {
+                let chunk_started = Instant::now();
+                let count = memchr_iter(b'\n', chunk).count();
+                timed(chunk_started);
+                count
             })
             .collect();
 
-        // Process chunks in parallel using SIMD-accelerated memchr
-        let chunk_results: Vec<ChunkResult> = chunks
-            .par_iter()
-            .map(|&(start, end)| {
-                let chunk = &data[start..end];
-                let mut offsets = Vec::new();
-
-                // Use SIMD-accelerated newline search (memchr processes 32 bytes at a time)
-                for pos in memchr_iter(b'\n', chunk) {
+        // First line always starts at offset 0; each newline starts another
+        let mut global_index = vec![0u64; 1 + counts.iter().sum::<usize>()];
+        let mut parts = Vec::with_capacity(counts.len());
+        let mut rest = &mut global_index[1..];
+        for &count in &counts {
+            let (part, tail) = rest.split_at_mut(count);
+            parts.push(part);
+            rest = tail;
+        }
+        data.par_chunks(chunk_size)
+            .zip(parts)
+            .enumerate()
+            .for_each(|(i, (chunk, part))| {
+                let chunk_started = Instant::now();
+                let start = (i * chunk_size) as u64;
+                for (slot, pos) in part.iter_mut().zip(memchr_iter(b'\n', chunk)) {
                     // Store the position after the newline (start of next line)
-                    let absolute_pos = start as u64 + pos as u64 + 1;
-                    if absolute_pos < data_len as u64 {
-                        offsets.push(absolute_pos);
-                    }
+                    *slot = start + pos as u64 + 1;
                 }
+                timed(chunk_started);
+            });
 
-                ChunkResult {
-                    offsets,
-                }
-            })
-            .collect();
+        // A final newline ends the last line rather than starting a new one
+        if data.last() == Some(&b'\n') {
+            global_index.pop();
+        }
+
+        let wall_ms = started.elapsed().as_secs_f64() * 1000.0;
+        let busy_ms = busy_nanos.load(Ordering::Relaxed) as f64 / 1e6;
+        let stats = IndexStats {
+            chunks: counts.len(),
+            chunk_bytes: chunk_size,
+            threads,
+            wall_ms,
+            busy_ms,
+            efficiency: (busy_ms / (wall_ms * threads as f64).max(f64::MIN_POSITIVE)).min(1.0),
+        };
+        (global_index, stats)
+    }
+
+    /// Pick up data appended to the file since it was opened or last refreshed
+    /// Follows rotation like `tail -F`: when the path is renamed, recreated or truncated, the rest of
+    /// the old file is read, a marker line is inserted, and the new file continues the same view
+    pub fn refresh(&mut self) -> Result<Option<Appended>, IndexerError> {
+        let old_count = self.line_count();
+        let old_terminated = self.data.last().is_none_or(|&b| b == b'\n');
+
+        // Drain whatever was appended to the file we're currently reading
+        let mut grew = self.read_source()?;
+
+        let rotation_marker = match self.detect_rotation() {
+            Some(new_source) => {
+                let marker = self.append_rotation(new_source)?;
+                grew = true;
+                Some(marker)
+            }
+            None => None,
+        };
+
+        if !grew {
+            return Ok(None);
+        }
+
+        let first_changed = if old_terminated {
+            old_count
+        } else {
+            old_count - 1
+        };
+        Ok(Some(Appended {
+            lines: first_changed..self.line_count(),
+            rotation_marker,
+        }))
+    }
+
+    /// Read new bytes from the current source file, returning whether anything was added
+    fn read_source(&mut self) -> Result<bool, IndexerError> {
+        let Some(source) = &self.source else {
+            return Ok(false);
+        };
+        let source_len = source.metadata()?.len();
+        if source_len <= self.source_pos {
+            return Ok(false);
+        }
+
+        let old_size = self.data.len();
+        match &mut self.data {
+            Backing::Mapped(mmap) => {
+                // Safety: same as in `open`; the file is only ever read
+                *mmap = unsafe { Mmap::map(source)? };
+            }
+            Backing::Owned(buf) => {
+                let mut reader = source;
+                reader.seek(SeekFrom::Start(self.source_pos))?;
+                reader.take(source_len - self.source_pos).read_to_end(buf)?;
+            }
+        }
+        self.source_pos = source_len;
+        self.index_from(old_size);
+        Ok(self.data.len() > old_size)
+    }
+
+    /// Index newlines in data added after `old_size`
+    fn index_from(&mut self, old_size: usize) {
+        let new_size = self.data.len() as u64;
+        // Start at the old final byte so a newline there yields the first appended line
+        let scan_from = old_size.saturating_sub(1);
+        for pos in memchr_iter(b'\n', &self.data[scan_from..]) {
+            let absolute_pos = (scan_from + pos + 1) as u64;
+            if absolute_pos < new_size {
+                self.line_offsets.push(absolute_pos);
+            }
+        }
+        self.file_size = new_size;
+    }
 
-        // Reconcile chunk results into global index
-        let mut global_index = Vec::with_capacity(data_len / 100); // Estimate ~100 bytes per line
-        global_index.push(0); // First line always starts at offset 0
+    /// Check whether the path now refers to a different file than the one being read
+    fn detect_rotation(&self) -> Option<File> {
+        let current = self.source.as_ref()?.metadata().ok()?;
+        let on_disk = std::fs::metadata(&self.path).ok()?;
 
-        for result in chunk_results {
-            global_index.extend(result.offsets);
+        let replaced = match (file_identity(&current), file_identity(&on_disk)) {
+            (Some(a), Some(b)) => a != b,
+            _ => false,
+        };
+        // copytruncate-style rotation keeps the same file but shrinks it
+        let truncated = on_disk.len() < self.source_pos;
+
+        if replaced || truncated {
+            File::open(&self.path).ok()
+        } else {
+            None
         }
+    }
 
-        // Sort to ensure correct order (chunks may complete out of order)
-        global_index.sort_unstable();
-        global_index.dedup();
+    /// Switch to a new source file after rotation, returning the marker line number
+    fn append_rotation(&mut self, new_source: File) -> Result<u64, IndexerError> {
+        // The stitched view no longer maps to a single file, so move it into an owned buffer
+        let mut buf = match std::mem::replace(&mut self.data, Backing::Owned(Vec::new())) {
+            Backing::Mapped(mmap) => mmap.to_vec(),
+            Backing::Owned(buf) => buf,
+        };
+        let old_size = buf.len();
+        if buf.last().is_some_and(|&b| b != b'\n') {
+            buf.push(b'\n');
+        }
+        let marker = format!("--- log rotated: continuing with new {} ---\n", self.path);
+        buf.extend_from_slice(marker.as_bytes());
+        self.data = Backing::Owned(buf);
+        self.index_from(old_size);
+        let marker_line = self.line_count() - 1;
 
-        global_index
+        self.source = Some(new_source);
+        self.source_pos = 0;
+        self.read_source()?;
+        Ok(marker_line)
+    }
+
+    /// Create an empty view for a live source (pipe, device, listener) that grows via `append_bytes`
+    pub fn live(name: &str) -> Self {
+        LogFile {
+            data: Backing::Owned(Vec::new()),
+            line_offsets: Vec::new(),
+            file_size: 0,
+            path: name.to_string(),
+            source: None,
+            source_pos: 0,
+            index_stats: None,
+            trigrams: None,
+        }
+    }
+
+    /// Index an in-memory buffer the way `open` indexes a file, for tests and fuzz targets
+    #[cfg(any(test, feature = "fuzzing"))]
+    pub fn from_bytes(name: &str, data: Vec<u8>) -> Self {
+        let (line_offsets, index_stats) = if data.is_empty() {
+            (Vec::new(), None)
+        } else {
+            let (offsets, stats) = Self::build_index(&data);
+            (offsets, Some(stats))
+        };
+        LogFile {
+            file_size: data.len() as u64,
+            data: Backing::Owned(data),
+            line_offsets,
+            path: name.to_string(),
+            source: None,
+            source_pos: 0,
+            index_stats,
+            trigrams: None,
+        }
+    }
+
+    /// Append raw bytes to a live view and index them
+    pub fn append_bytes(&mut self, bytes: &[u8]) -> Option<Appended> {
+        if bytes.is_empty() {
+            return None;
+        }
+        let old_count = self.line_count();
+        let old_terminated = self.data.last().is_none_or(|&b| b == b'\n');
+        let old_size = self.data.len();
+
+        if let Backing::Mapped(mmap) = &self.data {
+            let owned = mmap.to_vec();
+            self.data = Backing::Owned(owned);
+        }
+        if let Backing::Owned(buf) = &mut self.data {
+            buf.extend_from_slice(bytes);
+        }
+
+        if self.line_offsets.is_empty() {
+            self.line_offsets.push(0);
+        }
+        self.index_from(old_size);
+
+        let first_changed = if old_terminated {
+            old_count
+        } else {
+            old_count - 1
+        };
+        Some(Appended {
+            lines: first_changed..self.line_count(),
+            rotation_marker: None,
+        })
+    }
+
+    /// Drop up to `count` complete lines from the front of the view, returning the removed bytes
+    /// Remaining lines are renumbered from zero; an unterminated last line is never evicted
+    pub fn evict_front(&mut self, count: u64) -> Vec<u8> {
+        let terminated = self.data.last().is_none_or(|&b| b == b'\n');
+        let evictable = if terminated {
+            self.line_count()
+        } else {
+            self.line_count().saturating_sub(1)
+        };
+        let count = count.min(evictable) as usize;
+        if count == 0 {
+            return Vec::new();
+        }
+
+        if let Backing::Mapped(mmap) = &self.data {
+            let owned = mmap.to_vec();
+            self.data = Backing::Owned(owned);
+        }
+        let cut = self
+            .line_offsets
+            .get(count)
+            .map_or(self.data.len(), |&offset| offset as usize);
+        let evicted = match &mut self.data {
+            Backing::Owned(buf) => buf.drain(..cut).collect(),
+            Backing::Mapped(_) => Vec::new(),
+        };
+
+        self.line_offsets.drain(..count);
+        for offset in &mut self.line_offsets {
+            *offset -= cut as u64;
+        }
+        self.file_size = self.data.len() as u64;
+        // Its blocks are numbered from the old first line
+        self.trigrams = None;
+        evicted
+    }
+
+    /// Index of the first line starting at or after byte `offset`
+    pub fn first_line_from_offset(&self, offset: u64) -> u64 {
+        self.line_offsets.partition_point(|&start| start < offset) as u64
+    }
+
+    /// Lines that end in a newline, leaving out a last line that may still be written to
+    pub fn complete_line_count(&self) -> u64 {
+        let terminated = self.data.last().is_none_or(|&b| b == b'\n');
+        if terminated {
+            self.line_count()
+        } else {
+            self.line_count().saturating_sub(1)
+        }
+    }
+
+    /// Bytes spanned by a range of lines, terminators included
+    pub fn byte_range(&self, lines: Range<u64>) -> Range<usize> {
+        let offset = |line: u64| {
+            self.line_offsets
+                .get(line as usize)
+                .map_or(self.data.len(), |&start| start as usize)
+        };
+        offset(lines.start)..offset(lines.end)
     }
 
     /// Get the total number of lines in the file
@@ -130,57 +580,37 @@ impl LogFile {
         self.file_size
     }
 
+    /// How the initial index build went, for files opened from disk
+    pub fn index_stats(&self) -> Option<&IndexStats> {
+        self.index_stats.as_ref()
+    }
+
+    pub fn trigram_index(&self) -> Option<&TrigramIndex> {
+        self.trigrams.as_ref()
+    }
+
+    /// Attach a trigram index built from this file, returning whether it still matches it;
+    /// the file may have changed while the index was built without holding it
+    pub fn set_trigram_index(&mut self, index: TrigramIndex) -> bool {
+        let matches = index.matches(self);
+        if matches {
+            self.trigrams = Some(index);
+        }
+        matches
+    }
+
     /// Get the file path
     pub fn path(&self) -> &str {
         &self.path
     }
 
     /// Get a range of lines from the file
-    /// Returns a vector of strings for each line
+    /// Returns a vector of strings for each line, without terminators
     pub fn get_lines(&self, start: u64, count: u64) -> Result<Vec<String>, IndexerError> {
-        let total_lines = self.line_count();
-        
-        if start >= total_lines {
-            return Ok(vec![]);
-        }
-
-        let actual_count = std::cmp::min(count, total_lines - start);
-        let mut lines = Vec::with_capacity(actual_count as usize);
-
-        for i in 0..actual_count {
-            let line_idx = (start + i) as usize;
-            let line_start = self.line_offsets[line_idx] as usize;
-            
-            // Determine line end (next line start - 1, or end of file)
-            let line_end = if line_idx + 1 < self.line_offsets.len() {
-                self.line_offsets[line_idx + 1] as usize - 1 // Exclude newline
-            } else {
-                self.mmap.len() // Last line goes to end of file
-            };
-
-            // Handle potential \r\n line endings
-            let actual_end = if line_end > line_start && line_end <= self.mmap.len() {
-                let end = std::cmp::min(line_end, self.mmap.len());
-                if end > 0 && self.mmap[end - 1] == b'\r' {
-                    end - 1
-                } else {
-                    end
-                }
-            } else {
-                line_start
-            };
-
-            // Extract the line bytes and convert to string
-            if line_start <= actual_end && actual_end <= self.mmap.len() {
-                let line_bytes = &self.mmap[line_start..actual_end];
-                // Use lossy conversion to handle potential invalid UTF-8
-                lines.push(String::from_utf8_lossy(line_bytes).to_string());
-            } else {
-                lines.push(String::new());
-            }
-        }
-
-        Ok(lines)
+        let end = start.saturating_add(count).min(self.line_count());
+        Ok((start..end)
+            .map(|line| self.line_text(line).unwrap_or_default().into_owned())
+            .collect())
     }
 
     /// Get lines as binary data with a header containing line lengths
@@ -188,49 +618,82 @@ impl LogFile {
     /// This is more efficient than JSON for large data transfers
     pub fn get_lines_binary(&self, start: u64, count: u64) -> Result<Vec<u8>, IndexerError> {
         let lines = self.get_lines(start, count)?;
-        
-        // Calculate total size needed
-        let header_size = 4 + (lines.len() * 4); // num_lines + lengths
-        let data_size: usize = lines.iter().map(|l| l.len()).sum();
-        let total_size = header_size + data_size;
-
-        let mut buffer = Vec::with_capacity(total_size);
-
-        // Write number of lines
-        buffer.extend_from_slice(&(lines.len() as u32).to_le_bytes());
-
-        // Write line lengths
-        for line in &lines {
-            buffer.extend_from_slice(&(line.len() as u32).to_le_bytes());
-        }
-
-        // Write line data
-        for line in &lines {
-            buffer.extend_from_slice(line.as_bytes());
-        }
+        Ok(encode_lines_binary(&lines))
+    }
 
-        Ok(buffer)
+    /// Byte offset and length of a range of lines
+    pub fn get_lines_meta(&self, start: u64, count: u64) -> Vec<LineMeta> {
+        let end = start.saturating_add(count).min(self.line_count());
+        (start..end)
+            .filter_map(|line| {
+                let (from, to) = self.line_bounds(line)?;
+                let next = self
+                    .line_offsets
+                    .get(line as usize + 1)
+                    .map_or(self.data.len(), |&next| next as usize);
+                Some(LineMeta {
+                    line,
+                    byte_offset: from as u64,
+                    byte_len: (to - from) as u64,
+                    terminated: self.data[..next].ends_with(b"\n"),
+                })
+            })
+            .collect()
     }
 
     /// Search for a pattern in the file using parallel regex matching
     /// Returns line numbers that match the pattern
     pub fn search(&self, pattern: &str, max_results: usize) -> Result<Vec<u64>, IndexerError> {
-        let regex = regex::Regex::new(pattern)
+        let matcher = Matcher::new(pattern, RegexFlavor::Standard)
             .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e.to_string()))?;
+        self.search_until(&matcher, max_results, || false, |_, _| {})
+    }
+
+    /// Search like `search` with a compiled pattern of any flavor, giving up with `Cancelled`
+    /// once `cancelled` returns true
+    /// `progress` is passed the lines scanned so far and the total after each chunk
+    pub fn search_until(
+        &self,
+        matcher: &Matcher,
+        max_results: usize,
+        cancelled: impl Fn() -> bool + Sync,
+        progress: impl Fn(u64, u64) + Sync,
+    ) -> Result<Vec<u64>, IndexerError> {
+        let mut span = profile::span("search", "search").arg("pattern", matcher.as_str());
 
-        let total_lines = self.line_count();
+        // A literal only needs the blocks the trigram index says may contain it
+        let ranges = self
+            .trigrams
+            .as_ref()
+            .zip(matcher.literal())
+            .and_then(|(index, (literal, case_insensitive))| {
+                index.candidates(literal, case_insensitive, self.line_count())
+            })
+            .unwrap_or_else(|| std::iter::once(0..self.line_count()).collect());
+        let total_lines: u64 = ranges.iter().map(|r| r.end - r.start).sum();
+        span.record("lines", total_lines);
         let results = Arc::new(RwLock::new(Vec::new()));
+        let scanned = AtomicU64::new(0);
 
         // Process lines in parallel chunks
         let chunk_size = 10000;
-        let chunks: Vec<u64> = (0..total_lines).step_by(chunk_size).collect();
+        let chunks: Vec<(u64, u64)> = ranges
+            .iter()
+            .flat_map(|range| {
+                (range.start..range.end)
+                    .step_by(chunk_size)
+                    .map(move |start| (start, (start + chunk_size as u64).min(range.end)))
+            })
+            .collect();
 
-        chunks.par_iter().for_each(|&chunk_start| {
-            let chunk_end = std::cmp::min(chunk_start + chunk_size as u64, total_lines);
+        chunks.par_iter().for_each(|&(chunk_start, chunk_end)| {
             let mut local_results = Vec::new();
 
             for line_num in chunk_start..chunk_end {
-                // Early exit if we have enough results
+                // Early exit if we have enough results or the search was abandoned
+                if cancelled() {
+                    return;
+                }
                 {
                     let r = results.read();
                     if r.len() >= max_results {
@@ -238,21 +701,13 @@ impl LogFile {
                     }
                 }
 
-                let line_idx = line_num as usize;
-                let line_start = self.line_offsets[line_idx] as usize;
-                let line_end = if line_idx + 1 < self.line_offsets.len() {
-                    self.line_offsets[line_idx + 1] as usize
-                } else {
-                    self.mmap.len()
-                };
-
-                if line_start < line_end && line_end <= self.mmap.len() {
-                    let line_bytes = &self.mmap[line_start..line_end];
-                    if let Ok(line_str) = std::str::from_utf8(line_bytes) {
-                        if regex.is_match(line_str) {
-                            local_results.push(line_num);
-                        }
-                    }
+                // Match without the terminator, so `$` anchors at the end of the text, and
+                // decode invalid UTF-8 lossily like the viewer does rather than skipping the line
+                if self
+                    .line_text(line_num)
+                    .is_some_and(|text| matcher.is_match(&text))
+                {
+                    local_results.push(line_num);
                 }
             }
 
@@ -261,22 +716,432 @@ impl LogFile {
                 let mut r = results.write();
                 r.extend(local_results);
             }
+            let done = scanned.fetch_add(chunk_end - chunk_start, Ordering::Relaxed);
+            progress(done + chunk_end - chunk_start, total_lines);
         });
 
         let mut final_results = Arc::try_unwrap(results)
             .map(|rw| rw.into_inner())
             .unwrap_or_else(|arc| arc.read().clone());
         
+        if cancelled() {
+            span.record("cancelled", true);
+            return Err(IndexerError::Cancelled);
+        }
         final_results.sort_unstable();
         final_results.truncate(max_results);
+        span.record("matches", final_results.len());
         
         Ok(final_results)
     }
 
+    /// Byte range of a line, excluding the trailing newline and carriage return
+    fn line_bounds(&self, line: u64) -> Option<(usize, usize)> {
+        let line_idx = line as usize;
+        let start = *self.line_offsets.get(line_idx)? as usize;
+        let mut end = match self.line_offsets.get(line_idx + 1) {
+            Some(&next) => next as usize - 1,
+            None => self.data.len(),
+        };
+        if end > start && self.data[end - 1] == b'\n' {
+            end -= 1;
+        }
+        if end > start && self.data[end - 1] == b'\r' {
+            end -= 1;
+        }
+        Some((start, end.max(start)))
+    }
+
+    /// Bytes of a single line without its terminator
+    pub fn line_bytes(&self, line: u64) -> Option<&[u8]> {
+        let (start, end) = self.line_bounds(line)?;
+        Some(&self.data[start..end])
+    }
+
+    /// Text of a single line without its terminator, decoding invalid UTF-8 lossily
+    pub fn line_text(&self, line: u64) -> Option<Cow<'_, str>> {
+        let (start, end) = self.line_bounds(line)?;
+        Some(String::from_utf8_lossy(&self.data[start..end]))
+    }
+
+    /// Parse the timestamp of a single line, if it has one
+    pub fn line_timestamp(&self, line: u64) -> Option<i64> {
+        let (start, end) = self.line_bounds(line)?;
+        timestamp::parse_timestamp_bytes(&self.data[start..end])
+    }
+
+    /// Find the closest timestamped line at or before `line`, scanning back at most `max_scan` lines
+    /// Continuation lines (stack traces, wrapped messages) inherit the time of their parent entry
+    pub fn effective_timestamp(&self, line: u64, max_scan: u64) -> Option<(u64, i64)> {
+        let line = line.min(self.line_count().saturating_sub(1));
+        let floor = line.saturating_sub(max_scan);
+        (floor..=line)
+            .rev()
+            .find_map(|l| self.line_timestamp(l).map(|ts| (l, ts)))
+    }
+
+    /// Find the line whose timestamp is nearest to `target` (epoch millis)
+    /// Assumes timestamps are mostly non-decreasing, as in an ordinary log file
+    pub fn find_line_by_time(&self, target: i64) -> Option<u64> {
+        const MAX_SCAN: u64 = 1000;
+        let total = self.line_count();
+        let next_timestamped = |from: u64| {
+            (from..std::cmp::min(from + MAX_SCAN, total))
+                .find_map(|l| self.line_timestamp(l).map(|ts| (l, ts)))
+        };
+
+        // Binary search for the first timestamped line at or after the target
+        let mut lo = 0u64;
+        let mut hi = total;
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            match next_timestamped(mid) {
+                Some((_, ts)) if ts < target => lo = mid + 1,
+                // A window without timestamps is treated as lying after the target
+                _ => hi = mid,
+            }
+        }
+
+        let after = next_timestamped(lo);
+        let before = if lo > 0 {
+            self.effective_timestamp(lo - 1, MAX_SCAN)
+        } else {
+            None
+        };
+
+        match (before, after) {
+            (Some((bl, bts)), Some((al, ats))) => {
+                if target - bts <= ats - target {
+                    Some(bl)
+                } else {
+                    Some(al)
+                }
+            }
+            (Some((bl, _)), None) => Some(bl),
+            (None, Some((al, _))) => Some(al),
+            (None, None) => None,
+        }
+    }
+
+    /// Return the lines in `range` that match `regex`
+    pub fn matching_lines(&self, regex: &regex::Regex, range: Range<u64>) -> Vec<u64> {
+        let end = range.end.min(self.line_count());
+        (range.start..end)
+            .filter(|&line| {
+                self.line_bounds(line).is_some_and(|(start, end)| {
+                    regex.is_match(&String::from_utf8_lossy(&self.data[start..end]))
+                })
+            })
+            .collect()
+    }
+
     /// Get raw access to the memory-mapped data (for DataFusion integration)
     pub fn data(&self) -> &[u8] {
-        &self.mmap
+        &self.data
+    }
+}
+
+/// A temporary file holding `content` and the file opened from it, which stays readable
+/// while the temporary file is kept
+#[cfg(test)]
+pub(crate) fn open_test_file(content: &str) -> (tempfile::NamedTempFile, LogFile) {
+    use std::io::Write;
+
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    file.write_all(content.as_bytes()).unwrap();
+    file.flush().unwrap();
+    let log_file = LogFile::open(file.path()).unwrap();
+    (file, log_file)
+}
+
+/// Reference line splitter the index is checked against: lines end at `\n`, one `\r`
+/// before it is dropped, a lone `\r` is ordinary text, and a final unterminated line counts
+#[cfg(any(test, feature = "fuzzing"))]
+pub fn naive_lines(data: &[u8]) -> Vec<&[u8]> {
+    let mut lines: Vec<&[u8]> = data.split(|&b| b == b'\n').collect();
+    if data.last().is_none_or(|&b| b == b'\n') {
+        lines.pop();
+    }
+    lines
+        .into_iter()
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .collect()
+}
+
+/// Decode the `get_lines_binary` format, returning None if the buffer is malformed
+pub fn decode_lines_binary(buffer: &[u8]) -> Option<Vec<String>> {
+    let read_u32 = |pos: usize| -> Option<usize> {
+        let bytes = buffer.get(pos..pos + 4)?;
+        Some(u32::from_le_bytes(bytes.try_into().ok()?) as usize)
+    };
+    let count = read_u32(0)?;
+    let mut pos = 4usize.checked_add(count.checked_mul(4)?)?;
+    let mut lines = Vec::with_capacity(count.min(buffer.len() / 4));
+    for i in 0..count {
+        let len = read_u32(4 + i * 4)?;
+        let text = buffer.get(pos..pos.checked_add(len)?)?;
+        lines.push(String::from_utf8(text.to_vec()).ok()?);
+        pos += len;
+    }
+    (pos == buffer.len()).then_some(lines)
+}
+
+/// Encode lines in the `get_lines_binary` format
+pub fn encode_lines_binary(lines: &[String]) -> Vec<u8> {
+    let header_size = 4 + (lines.len() * 4); // num_lines + lengths
+    let data_size: usize = lines.iter().map(|l| l.len()).sum();
+    let total_size = header_size + data_size;
+
+    let mut buffer = Vec::with_capacity(total_size);
+
+    // Write number of lines
+    buffer.extend_from_slice(&(lines.len() as u32).to_le_bytes());
+
+    // Write line lengths
+    for line in lines {
+        buffer.extend_from_slice(&(line.len() as u32).to_le_bytes());
+    }
+
+    // Write line data
+    for line in lines {
+        buffer.extend_from_slice(line.as_bytes());
+    }
+
+    buffer
+}
+
+/// Blocks sampled when estimating a file's line count, and their size
+const ESTIMATE_BLOCKS: u64 = 16;
+const ESTIMATE_BLOCK_BYTES: u64 = 64 * 1024;
+
+/// Approximate line count of a file, available long before its index
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct LineEstimate {
+    pub lines: u64,
+    /// Whether the whole file was small enough to count exactly
+    pub exact: bool,
+}
+
+/// Estimate a file's line count from the newline density of evenly spaced blocks
+/// Reads at most `ESTIMATE_BLOCKS` blocks, so it takes milliseconds on any file size
+pub fn estimate_line_count<P: AsRef<Path>>(path: P) -> Result<LineEstimate, IndexerError> {
+    let mut file = File::open(path)?;
+    let size = file.metadata()?.len();
+    if is_gzip(&mut file)? {
+        return estimate_gzip_line_count(file, size);
+    }
+    let mut block = Vec::with_capacity(ESTIMATE_BLOCK_BYTES as usize);
+    if size <= ESTIMATE_BLOCKS * ESTIMATE_BLOCK_BYTES {
+        file.read_to_end(&mut block)?;
+        return Ok(LineEstimate {
+            lines: naive_line_count(&block),
+            exact: true,
+        });
+    }
+
+    let stride = (size - ESTIMATE_BLOCK_BYTES) / (ESTIMATE_BLOCKS - 1);
+    let (mut sampled, mut newlines) = (0u64, 0u64);
+    for i in 0..ESTIMATE_BLOCKS {
+        block.clear();
+        file.seek(SeekFrom::Start(i * stride))?;
+        (&mut file)
+            .take(ESTIMATE_BLOCK_BYTES)
+            .read_to_end(&mut block)?;
+        sampled += block.len() as u64;
+        newlines += memchr_iter(b'\n', &block).count() as u64;
+    }
+    let lines = (size as f64 * newlines as f64 / sampled.max(1) as f64).round() as u64;
+    Ok(LineEstimate {
+        lines: lines.max(1),
+        exact: false,
+    })
+}
+
+/// Estimate a gzip file's line count from the newlines in as much as `ESTIMATE_BLOCKS`
+/// blocks of it decompressed, scaled by the share of the compressed file they took
+/// A compressed file can only be read from its start, so the blocks aren't spread out
+fn estimate_gzip_line_count(file: File, size: u64) -> Result<LineEstimate, IndexerError> {
+    let mut decoder = bufread::MultiGzDecoder::new(BufReader::new(file));
+    let mut block = Vec::new();
+    (&mut decoder)
+        .take(ESTIMATE_BLOCKS * ESTIMATE_BLOCK_BYTES)
+        .read_to_end(&mut block)?;
+    if decoder.read(&mut [0u8; 1])? == 0 {
+        return Ok(LineEstimate {
+            lines: naive_line_count(&block),
+            exact: true,
+        });
+    }
+    let consumed = decoder.get_mut().stream_position()?;
+    let newlines = memchr_iter(b'\n', &block).count() as u64;
+    let lines = (size as f64 * newlines as f64 / consumed.max(1) as f64).round() as u64;
+    Ok(LineEstimate {
+        lines: lines.max(1),
+        exact: false,
+    })
+}
+
+/// Lines in a buffer, counting a final unterminated line
+fn naive_line_count(data: &[u8]) -> u64 {
+    let newlines = memchr_iter(b'\n', data).count() as u64;
+    newlines + u64::from(data.last().is_some_and(|&b| b != b'\n'))
+}
+
+/// Most bytes a preview reads from each end of a file, so one huge line can't make it
+/// read the whole file; a line cut by the limit is shown cut
+const MAX_PREVIEW_BYTES: u64 = 1024 * 1024;
+const PREVIEW_BLOCK_BYTES: u64 = 64 * 1024;
+
+/// First and last lines of a file, read without indexing it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FilePreview {
+    pub head: Vec<String>,
+    pub tail: Vec<String>,
+    pub file_size: u64,
+    /// Whether `head` and `tail` together hold every line, with nothing between them
+    pub complete: bool,
+}
+
+/// Read the first `head_lines` and last `tail_lines` lines of a file from its two ends
+/// A line shown in `head` is never repeated in `tail`
+pub fn read_preview<P: AsRef<Path>>(
+    path: P,
+    head_lines: usize,
+    tail_lines: usize,
+) -> Result<FilePreview, IndexerError> {
+    let mut file = File::open(path)?;
+    if is_gzip(&mut file)? {
+        return read_gzip_preview(file, head_lines, tail_lines);
+    }
+    let file_size = file.metadata()?.len();
+
+    let buf = read_preview_head(&mut file, head_lines)?;
+    let (head, head_end) = preview_head(&buf, head_lines);
+    let head_end = head_end as u64;
+
+    // Read backward to the end of the head until the tail's lines are in
+    let mut start = file_size;
+    let mut buf = Vec::new();
+    while tail_lines > 0 && start > head_end && (buf.len() as u64) < MAX_PREVIEW_BYTES {
+        let from = start.saturating_sub(PREVIEW_BLOCK_BYTES).max(head_end);
+        let mut block = Vec::with_capacity((start - from) as usize);
+        file.seek(SeekFrom::Start(from))?;
+        (&mut file).take(start - from).read_to_end(&mut block)?;
+        block.extend_from_slice(&buf);
+        buf = block;
+        start = from;
+        let body = buf.strip_suffix(b"\n").unwrap_or(&buf);
+        if memchr_iter(b'\n', body).count() >= tail_lines {
+            break;
+        }
+    }
+    let (tail, complete) = preview_tail(&buf, start > head_end, tail_lines);
+
+    Ok(FilePreview {
+        head,
+        tail,
+        file_size,
+        complete,
+    })
+}
+
+/// Preview of a gzip file, which can only be read from its start, so only as far as
+/// `MAX_PREVIEW_BYTES` past the head is decompressed. The tail comes from there when the
+/// contents end within it and is left empty otherwise; `file_size` is then the compressed
+/// size on disk rather than the decompressed size of the opened file
+fn read_gzip_preview(
+    file: File,
+    head_lines: usize,
+    tail_lines: usize,
+) -> Result<FilePreview, IndexerError> {
+    let compressed_size = file.metadata()?.len();
+    let mut decoder = MultiGzDecoder::new(BufReader::new(file));
+    let mut buf = read_preview_head(&mut decoder, head_lines)?;
+    let (head, head_end) = preview_head(&buf, head_lines);
+    let mut rest = buf.split_off(head_end);
+    let limit = MAX_PREVIEW_BYTES.saturating_sub(rest.len() as u64);
+    decoder.by_ref().take(limit).read_to_end(&mut rest)?;
+    if decoder.read(&mut [0u8; 1])? > 0 {
+        return Ok(FilePreview {
+            head,
+            tail: Vec::new(),
+            file_size: compressed_size,
+            complete: false,
+        });
     }
+    let (tail, complete) = preview_tail(&rest, false, tail_lines);
+
+    Ok(FilePreview {
+        head,
+        tail,
+        file_size: (head_end + rest.len()) as u64,
+        complete,
+    })
+}
+
+/// Read forward until the head's lines are in, the input ends or the limit is hit
+fn read_preview_head(reader: &mut impl Read, head_lines: usize) -> io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    while memchr_iter(b'\n', &buf).count() < head_lines && (buf.len() as u64) < MAX_PREVIEW_BYTES {
+        let read = reader
+            .by_ref()
+            .take(PREVIEW_BLOCK_BYTES)
+            .read_to_end(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+    }
+    Ok(buf)
+}
+
+/// The first `head_lines` lines of the start of a file, and where they end
+fn preview_head(buf: &[u8], head_lines: usize) -> (Vec<String>, usize) {
+    let mut head = Vec::new();
+    let mut pos = 0;
+    while head.len() < head_lines && pos < buf.len() {
+        let end = memchr(b'\n', &buf[pos..]).map_or(buf.len(), |i| pos + i);
+        head.push(preview_line(&buf[pos..end]));
+        pos = (end + 1).min(buf.len());
+    }
+    (head, pos)
+}
+
+/// The last `tail_lines` lines of the end of a file read back to the head, or to partway
+/// into a line when `cut`, and whether there were no others
+fn preview_tail(buf: &[u8], cut: bool, tail_lines: usize) -> (Vec<String>, bool) {
+    let body = buf.strip_suffix(b"\n").unwrap_or(buf);
+    let mut lines: Vec<&[u8]> = body.split(|&b| b == b'\n').collect();
+    if body.is_empty() {
+        lines.clear();
+    }
+    // The first piece starts mid-line unless the scan reached the head; keep it only when
+    // it is all there is of a line longer than the limit
+    if cut && lines.len() > 1 {
+        lines.remove(0);
+    }
+    let complete = !cut && lines.len() <= tail_lines;
+    let skip = lines.len().saturating_sub(tail_lines);
+    let tail = lines[skip..].iter().map(|line| preview_line(line));
+    (tail.collect(), complete)
+}
+
+/// A previewed line without its carriage return
+fn preview_line(line: &[u8]) -> String {
+    String::from_utf8_lossy(line.strip_suffix(b"\r").unwrap_or(line)).into_owned()
+}
+
+/// Identity of a file on disk, used to notice when a path starts pointing at a different file
+#[cfg(unix)]
+fn file_identity(metadata: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_identity(metadata: &Metadata) -> Option<(u64, u64)> {
+    let created = metadata.created().ok()?;
+    let since_epoch = created.duration_since(std::time::UNIX_EPOCH).ok()?;
+    Some((since_epoch.as_secs(), since_epoch.subsec_nanos() as u64))
 }
 
 /// Thread-safe wrapper for LogFile that can be shared across threads
@@ -297,10 +1162,31 @@ impl SharedLogFile {
         Ok(())
     }
 
+    /// Replace the open file with an already constructed view (e.g. a live source)
+    pub fn set(&self, log_file: LogFile) {
+        *self.inner.write() = Some(log_file);
+    }
+
     pub fn close(&self) {
         *self.inner.write() = None;
     }
 
+    /// Re-index data appended to the open file, if any
+    pub fn refresh(&self) -> Result<Option<Appended>, IndexerError> {
+        match self.inner.write().as_mut() {
+            Some(file) => file.refresh(),
+            None => Ok(None),
+        }
+    }
+
+    /// Mutate the open file in place (e.g. evicting from a live view)
+    pub fn with_file_mut<F, R>(&self, f: F) -> Option<R>
+    where
+        F: FnOnce(&mut LogFile) -> R,
+    {
+        self.inner.write().as_mut().map(f)
+    }
+
     pub fn is_open(&self) -> bool {
         self.inner.read().is_some()
     }
@@ -322,6 +1208,9 @@ impl Default for SharedLogFile {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+    use proptest::sample::Index;
     use std::io::Write;
     use tempfile::NamedTempFile;
 
@@ -332,6 +1221,21 @@ mod tests {
         file
     }
 
+    /// A gzip file of one member per part
+    fn create_gzip_file(parts: &[&str]) -> NamedTempFile {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+
+        let mut file = NamedTempFile::new().unwrap();
+        for part in parts {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(part.as_bytes()).unwrap();
+            file.write_all(&encoder.finish().unwrap()).unwrap();
+        }
+        file.flush().unwrap();
+        file
+    }
+
     #[test]
     fn test_basic_indexing() {
         let content = "line1\nline2\nline3\n";
@@ -341,6 +1245,134 @@ mod tests {
         assert_eq!(log_file.line_count(), 3);
     }
 
+    #[test]
+    fn test_open_gzip() {
+        // Two members, as when a compressed log was appended to
+        let file = create_gzip_file(&["first\nsecond\n", "third\n"]);
+
+        let mut log_file = LogFile::open(file.path()).unwrap();
+        assert_eq!(log_file.line_count(), 3);
+        assert_eq!(
+            log_file.get_lines(0, 3).unwrap(),
+            vec!["first", "second", "third"]
+        );
+        assert_eq!(log_file.file_size(), 19);
+        assert_eq!(log_file.refresh().unwrap(), None);
+        assert!(matches!(
+            LogFile::open_cancellable(file.path(), || true),
+            Err(IndexerError::Cancelled)
+        ));
+    }
+
+    #[test]
+    fn test_index_chunking() {
+        // A mid-size file still gets several work units per thread
+        assert_eq!(index_chunk_size(200 * 1024 * 1024, 16), 1600 * 1024);
+        assert_eq!(index_chunk_size(1000, 16), MIN_INDEX_CHUNK);
+        assert_eq!(index_chunk_size(usize::MAX / 2, 1), MAX_INDEX_CHUNK);
+
+        let file = create_test_file(&"line\n".repeat(10_000));
+        let log_file = LogFile::open(file.path()).unwrap();
+        let stats = log_file.index_stats().unwrap();
+        assert_eq!(stats.chunks, 1);
+        assert!((0.0..=1.0).contains(&stats.efficiency));
+
+        let data = "line\n".repeat(10_000).into_bytes();
+        let (offsets, stats) = LogFile::build_index_chunked(&data, 4096);
+        assert_eq!(offsets, log_file.line_offsets);
+        assert_eq!(stats.chunks, data.len().div_ceil(4096));
+    }
+
+    #[test]
+    fn test_estimate_line_count() {
+        let small = create_test_file("a\nb\nc");
+        assert_eq!(
+            estimate_line_count(small.path()).unwrap(),
+            LineEstimate {
+                lines: 3,
+                exact: true
+            }
+        );
+
+        let content: String = (0..100_000)
+            .map(|i| format!("{} {}\n", i, "x".repeat(i % 40)))
+            .collect();
+        let large = create_test_file(&content);
+        let estimate = estimate_line_count(large.path()).unwrap();
+        assert!(!estimate.exact);
+        assert!(
+            (90_000..=110_000).contains(&estimate.lines),
+            "{:?}",
+            estimate
+        );
+    }
+
+    #[test]
+    fn test_estimate_gzip_line_count() {
+        // Lines are counted in the decompressed text, not the compressed bytes
+        let small = create_gzip_file(&["a\nb\n", "c"]);
+        assert_eq!(
+            estimate_line_count(small.path()).unwrap(),
+            LineEstimate {
+                lines: 3,
+                exact: true
+            }
+        );
+
+        let content: String = (0..100_000)
+            .map(|i| format!("{} {}\n", i, "x".repeat(i % 40)))
+            .collect();
+        let large = create_gzip_file(&[&content]);
+        let estimate = estimate_line_count(large.path()).unwrap();
+        assert!(!estimate.exact);
+        assert!(
+            (80_000..=120_000).contains(&estimate.lines),
+            "{:?}",
+            estimate
+        );
+    }
+
+    #[test]
+    fn test_read_preview() {
+        let small = create_test_file("a\r\nb\nc\nd\ne");
+        let preview = read_preview(small.path(), 3, 3).unwrap();
+        assert_eq!(preview.head, ["a", "b", "c"]);
+        assert_eq!(preview.tail, ["d", "e"]);
+        assert!(preview.complete);
+
+        let content: String = (0..200_000).map(|i| format!("line {}\n", i)).collect();
+        let large = create_test_file(&content);
+        let preview = read_preview(large.path(), 2, 3).unwrap();
+        assert_eq!(preview.head, ["line 0", "line 1"]);
+        assert_eq!(preview.tail, ["line 199997", "line 199998", "line 199999"]);
+        assert_eq!(preview.file_size, content.len() as u64);
+        assert!(!preview.complete);
+
+        let empty = create_test_file("");
+        let preview = read_preview(empty.path(), 5, 5).unwrap();
+        assert!(preview.head.is_empty() && preview.tail.is_empty() && preview.complete);
+    }
+
+    #[test]
+    fn test_read_gzip_preview() {
+        let small = create_gzip_file(&["a\r\nb\nc\n", "d\ne"]);
+        let preview = read_preview(small.path(), 3, 3).unwrap();
+        assert_eq!(preview.head, ["a", "b", "c"]);
+        assert_eq!(preview.tail, ["d", "e"]);
+        assert_eq!(preview.file_size, 11);
+        assert!(preview.complete);
+
+        // Contents running past the bytes read leave the tail out rather than decompressing
+        // the whole file
+        let content: String = (0..400_000).map(|i| format!("line {}\n", i)).collect();
+        let large = create_gzip_file(&[&content]);
+        let preview = read_preview(large.path(), 2, 3).unwrap();
+        assert_eq!(preview.head, ["line 0", "line 1"]);
+        assert!(preview.tail.is_empty());
+        assert_eq!(preview.file_size, large.as_file().metadata().unwrap().len());
+        assert!(!preview.complete);
+    }
+
     #[test]
     fn test_get_lines() {
         let content = "line1\nline2\nline3\n";
@@ -351,6 +1383,20 @@ mod tests {
         assert_eq!(lines, vec!["line1", "line2", "line3"]);
     }
 
+    #[test]
+    fn test_get_lines_meta() {
+        let file = create_test_file("ab\r\n\ncut");
+        let log_file = LogFile::open(file.path()).unwrap();
+
+        let meta = log_file.get_lines_meta(0, 10);
+        let spans: Vec<(u64, u64, bool)> = meta
+            .iter()
+            .map(|m| (m.byte_offset, m.byte_len, m.terminated))
+            .collect();
+        assert_eq!(spans, [(0, 2, true), (4, 0, true), (5, 3, false)]);
+        assert_eq!(log_file.get_lines_meta(1, 1)[0].line, 1);
+    }
+
     #[test]
     fn test_get_lines_partial() {
         let content = "line1\nline2\nline3\nline4\nline5\n";
@@ -378,6 +1424,112 @@ mod tests {
         assert!(matches!(result, Err(IndexerError::EmptyFile)));
     }
 
+    #[test]
+    fn test_find_line_by_time() {
+        let content = "2024-01-01T00:00:00Z start\n\
+                       2024-01-01T00:00:10Z step\n\
+                       \tat continuation\n\
+                       2024-01-01T00:00:20Z step\n\
+                       2024-01-01T00:00:30Z end\n";
+        let file = create_test_file(content);
+        let log_file = LogFile::open(file.path()).unwrap();
+
+        let base = 1_704_067_200_000;
+        assert_eq!(log_file.find_line_by_time(base - 5000), Some(0));
+        assert_eq!(log_file.find_line_by_time(base + 11_000), Some(1));
+        assert_eq!(log_file.find_line_by_time(base + 19_000), Some(3));
+        assert_eq!(log_file.find_line_by_time(base + 60_000), Some(4));
+        assert_eq!(log_file.effective_timestamp(2, 10), Some((1, base + 10_000)));
+    }
+
+    #[test]
+    fn test_refresh_appended_lines() {
+        let mut file = create_test_file("line1\nline2\n");
+        let mut log_file = LogFile::open(file.path()).unwrap();
+        assert_eq!(log_file.refresh().unwrap(), None);
+
+        file.write_all(b"line3\nline4").unwrap();
+        file.flush().unwrap();
+        let appended = log_file.refresh().unwrap().unwrap();
+        assert_eq!(appended.lines, 2..4);
+        assert_eq!(log_file.line_count(), 4);
+
+        // Completing the unterminated last line reports it as changed
+        file.write_all(b" done\nline5\n").unwrap();
+        file.flush().unwrap();
+        let appended = log_file.refresh().unwrap().unwrap();
+        assert_eq!(appended.lines, 3..5);
+        assert_eq!(appended.rotation_marker, None);
+        assert_eq!(log_file.get_lines(3, 1).unwrap(), vec!["line4 done"]);
+    }
+
+    #[test]
+    fn test_refresh_follows_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        std::fs::write(&path, "old1\nold2\n").unwrap();
+        let mut log_file = LogFile::open(&path).unwrap();
+
+        // Late write to the old file, then rotate and start a new one
+        let mut old = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        old.write_all(b"old3\n").unwrap();
+        std::fs::rename(&path, dir.path().join("app.log.1")).unwrap();
+        std::fs::write(&path, "new1\n").unwrap();
+
+        let appended = log_file.refresh().unwrap().unwrap();
+        assert_eq!(appended.rotation_marker, Some(3));
+        assert_eq!(appended.lines, 2..5);
+        let lines = log_file.get_lines(0, 4).unwrap();
+        assert_eq!(&lines[..3], &["old1", "old2", "old3"]);
+        assert!(lines[3].starts_with("--- log rotated"));
+
+        // Appends to the new file keep extending the stitched view
+        let mut new = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        new.write_all(b"new2\n").unwrap();
+        let appended = log_file.refresh().unwrap().unwrap();
+        assert_eq!(appended.lines, 5..6);
+        assert_eq!(log_file.get_lines(4, 2).unwrap()[0], "new1");
+    }
+
+    #[test]
+    fn test_live_append_bytes() {
+        let mut log_file = LogFile::live("pipe");
+        assert_eq!(log_file.line_count(), 0);
+        assert!(log_file.get_lines(0, 10).unwrap().is_empty());
+
+        let appended = log_file.append_bytes(b"boot ok\npart").unwrap();
+        assert_eq!(appended.lines, 0..2);
+        let appended = log_file.append_bytes(b"ial\nnext\n").unwrap();
+        assert_eq!(appended.lines, 1..3);
+        assert_eq!(
+            log_file.get_lines(0, 2).unwrap(),
+            vec!["boot ok", "partial"]
+        );
+    }
+
+    #[test]
+    fn test_evict_front() {
+        let mut log_file = LogFile::live("pipe");
+        log_file.append_bytes(b"one\ntwo\nthree\npart");
+        assert_eq!(log_file.first_line_from_offset(5), 2);
+
+        assert_eq!(log_file.evict_front(2), b"one\ntwo\n");
+        assert_eq!(log_file.line_count(), 2);
+        assert_eq!(log_file.get_lines(0, 2).unwrap(), vec!["three", "part"]);
+
+        // The unterminated last line stays so later bytes can complete it
+        assert_eq!(log_file.evict_front(10), b"three\n");
+        log_file.append_bytes(b"ial\nnext");
+        assert_eq!(log_file.get_lines(0, 1).unwrap(), vec!["partial"]);
+        assert_eq!(log_file.file_size(), 12);
+    }
+
     #[test]
     fn test_binary_transfer() {
         let content = "line1\nline2\n";
@@ -390,4 +1542,87 @@ mod tests {
         let num_lines = u32::from_le_bytes(binary[0..4].try_into().unwrap());
         assert_eq!(num_lines, 2);
     }
+
+    /// Short byte strings weighted toward terminators and bytes that aren't valid UTF-8
+    fn byte_soup() -> impl Strategy<Value = Vec<u8>> {
+        vec(
+            prop_oneof![
+                3 => Just(b'\n'),
+                2 => Just(b'\r'),
+                1 => Just(0xFF),
+                1 => Just(0xC3),
+                6 => b'a'..=b'c',
+                1 => any::<u8>(),
+            ],
+            0..200,
+        )
+    }
+
+    fn assert_matches_naive(file: &LogFile, data: &[u8]) -> Result<(), TestCaseError> {
+        let expected = naive_lines(data);
+        prop_assert_eq!(file.line_count(), expected.len() as u64);
+        for (i, line) in expected.iter().enumerate() {
+            prop_assert_eq!(file.line_bytes(i as u64), Some(*line));
+        }
+        let text: Vec<String> = expected
+            .iter()
+            .map(|line| String::from_utf8_lossy(line).into_owned())
+            .collect();
+        prop_assert_eq!(file.get_lines(0, u64::MAX).unwrap(), text);
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn prop_index_matches_naive_splitter(data in byte_soup(), chunk_size in 1usize..16) {
+            let file = LogFile::from_bytes("soup", data.clone());
+            assert_matches_naive(&file, &data)?;
+            if !data.is_empty() {
+                prop_assert_eq!(
+                    LogFile::build_index_chunked(&data, chunk_size).0,
+                    file.line_offsets.clone()
+                );
+            }
+        }
+
+        #[test]
+        fn prop_appends_match_naive_splitter(
+            data in byte_soup(),
+            cuts in vec(any::<Index>(), 0..8)
+        ) {
+            let mut cuts: Vec<usize> = cuts.iter().map(|cut| cut.index(data.len() + 1)).collect();
+            cuts.sort_unstable();
+            let mut file = LogFile::live("soup");
+            let mut from = 0;
+            for cut in cuts.into_iter().chain([data.len()]) {
+                file.append_bytes(&data[from..cut]);
+                from = cut;
+            }
+            assert_matches_naive(&file, &data)?;
+        }
+
+        #[test]
+        fn prop_search_sees_displayed_text(data in byte_soup()) {
+            let file = LogFile::from_bytes("soup", data.clone());
+            let expected: Vec<u64> = naive_lines(&data)
+                .iter()
+                .enumerate()
+                .filter(|(_, line)| String::from_utf8_lossy(line).ends_with('a'))
+                .map(|(i, _)| i as u64)
+                .collect();
+            prop_assert_eq!(file.search("a$", usize::MAX).unwrap(), expected);
+        }
+
+        #[test]
+        fn prop_lines_binary_round_trip(lines in vec(any::<String>(), 0..20)) {
+            prop_assert_eq!(decode_lines_binary(&encode_lines_binary(&lines)), Some(lines));
+        }
+
+        #[test]
+        fn prop_decode_rejects_truncated(lines in vec(".+", 1..5), cut in any::<Index>()) {
+            let encoded = encode_lines_binary(&lines);
+            let cut = cut.index(encoded.len());
+            prop_assert_eq!(decode_lines_binary(&encoded[..cut]), None);
+        }
+    }
 }
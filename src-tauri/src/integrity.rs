@@ -0,0 +1,163 @@
+use crate::parsers::{load_json, save_json};
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+/// File the hash records are persisted to, inside the app config directory
+const MANIFEST_FILE: &str = "hash_manifest.json";
+
+/// Read buffer used while hashing files
+const HASH_BUFFER: usize = 1024 * 1024;
+
+/// Whether a hashed file was analyzed or produced by an export
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HashKind {
+    Source,
+    Export,
+}
+
+/// SHA-256 of a file as it was on disk at a point in time
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HashRecord {
+    pub path: String,
+    pub kind: HashKind,
+    pub sha256: String,
+    pub size: u64,
+    /// RFC 3339 UTC time the hash was computed
+    pub computed_at: String,
+}
+
+impl HashRecord {
+    /// Hash a file as it currently is on disk
+    pub fn compute(path: &str, kind: HashKind) -> io::Result<Self> {
+        let (sha256, size) = sha256_file(Path::new(path))?;
+        Ok(HashRecord {
+            path: path.to_string(),
+            kind,
+            sha256,
+            size,
+            computed_at: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+        })
+    }
+}
+
+/// Lowercase hex SHA-256 of a byte slice
+pub fn sha256_hex(data: &[u8]) -> String {
+    hex(&Sha256::digest(data))
+}
+
+/// Stream a file through SHA-256, returning the hex digest and the number of bytes read
+/// Reads the file itself rather than the mapped view, so lines appended since opening count
+pub fn sha256_file(path: &Path) -> io::Result<(String, u64)> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; HASH_BUFFER];
+    let mut size = 0u64;
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        size += n as u64;
+    }
+    Ok((hex(&hasher.finalize()), size))
+}
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Outcome of re-hashing a file against the hash taken when it was opened
+#[derive(Debug, Clone, Serialize)]
+pub struct IntegrityCheck {
+    pub recorded: HashRecord,
+    pub current: HashRecord,
+    pub unchanged: bool,
+}
+
+/// Append-only record of every hash computed, kept across sessions
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct HashManifest {
+    #[serde(skip)]
+    path: PathBuf,
+    #[serde(default)]
+    records: Vec<HashRecord>,
+}
+
+impl HashManifest {
+    /// Load the manifest from a config directory; a missing file starts empty
+    pub fn load(dir: &Path) -> io::Result<Self> {
+        let path = dir.join(MANIFEST_FILE);
+        let mut manifest: HashManifest = load_json(&path)?;
+        manifest.path = path;
+        Ok(manifest)
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        save_json(&self.path, self)
+    }
+
+    pub fn records(&self) -> &[HashRecord] {
+        &self.records
+    }
+
+    pub fn record(&mut self, record: HashRecord) {
+        self.records.push(record);
+    }
+
+    /// Re-hash a source file and compare it with the hash taken when it was opened,
+    /// recording the new hash
+    pub fn verify(&mut self, recorded: HashRecord) -> io::Result<IntegrityCheck> {
+        let current = HashRecord::compute(&recorded.path, HashKind::Source)?;
+        self.record(current.clone());
+        Ok(IntegrityCheck {
+            unchanged: recorded.sha256 == current.sha256,
+            recorded,
+            current,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sha256_hex() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_manifest_verify() {
+        let config = tempfile::tempdir().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("app.log");
+        std::fs::write(&log, "abc").unwrap();
+        let log = log.to_str().unwrap();
+
+        let opened = HashRecord::compute(log, HashKind::Source).unwrap();
+        let mut manifest = HashManifest::load(config.path()).unwrap();
+        manifest.record(opened.clone());
+        let first = manifest.verify(opened.clone()).unwrap();
+        assert!(first.unchanged);
+        assert_eq!(first.current.sha256, sha256_hex(b"abc"));
+        assert_eq!(first.current.size, 3);
+        manifest.save().unwrap();
+
+        std::fs::write(log, "abd").unwrap();
+        let mut manifest = HashManifest::load(config.path()).unwrap();
+        let second = manifest.verify(opened.clone()).unwrap();
+        assert!(!second.unchanged);
+        assert_eq!(second.recorded, opened);
+        assert_eq!(second.current.sha256, sha256_hex(b"abd"));
+        assert_eq!(manifest.records().len(), 3);
+    }
+}
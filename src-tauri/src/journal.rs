@@ -0,0 +1,224 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+/// File the journal is appended to, inside the app config directory
+const JOURNAL_FILE: &str = "session_journal.jsonl";
+
+/// Events appended before the journal is rewritten as a single snapshot
+const COMPACT_AFTER: usize = 1_000;
+
+/// A state change worth restoring after a crash
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum JournalEvent {
+    Opened {
+        path: String,
+    },
+    Closed,
+    CompareOpened {
+        path: String,
+    },
+    CompareClosed,
+    FollowStarted,
+    FollowStopped,
+    FollowFilter {
+        pattern: Option<String>,
+    },
+    /// Where the viewer is and what it filters on, reported by the UI
+    View {
+        position: u64,
+        filter: Option<String>,
+    },
+    /// The whole state, written when the journal is compacted
+    Snapshot {
+        state: SessionState,
+    },
+    /// The app shut down normally, so there is nothing to restore
+    Exited,
+}
+
+/// What a session had open, rebuilt by replaying the journal
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SessionState {
+    pub file: Option<String>,
+    pub compare_file: Option<String>,
+    /// First visible line in the viewer
+    pub position: u64,
+    pub filter: Option<String>,
+    pub following: bool,
+    pub follow_filter: Option<String>,
+}
+
+impl SessionState {
+    fn apply(&mut self, event: &JournalEvent) {
+        match event {
+            JournalEvent::Opened { path } => {
+                *self = SessionState {
+                    file: Some(path.clone()),
+                    compare_file: self.compare_file.take(),
+                    ..Default::default()
+                };
+            }
+            JournalEvent::Closed => {
+                *self = SessionState {
+                    compare_file: self.compare_file.take(),
+                    ..Default::default()
+                };
+            }
+            JournalEvent::CompareOpened { path } => self.compare_file = Some(path.clone()),
+            JournalEvent::CompareClosed => self.compare_file = None,
+            JournalEvent::FollowStarted => self.following = true,
+            JournalEvent::FollowStopped => self.following = false,
+            JournalEvent::FollowFilter { pattern } => self.follow_filter = pattern.clone(),
+            JournalEvent::View { position, filter } => {
+                self.position = *position;
+                self.filter = filter.clone();
+            }
+            JournalEvent::Snapshot { state } => *self = state.clone(),
+            JournalEvent::Exited => *self = SessionState::default(),
+        }
+    }
+}
+
+/// Append-only log of state changes, synced to disk after every event so a crash or power
+/// loss loses at most the event being written
+pub struct Journal {
+    path: PathBuf,
+    file: File,
+    state: SessionState,
+    events: usize,
+}
+
+impl Journal {
+    /// Open the journal in a config directory and start a new session
+    /// Returns the previous session if it had a file open and never recorded a clean exit
+    pub fn open(dir: &Path) -> io::Result<(Journal, Option<SessionState>)> {
+        fs::create_dir_all(dir)?;
+        let path = dir.join(JOURNAL_FILE);
+        let previous = match File::open(&path) {
+            Ok(file) => replay(BufReader::new(file)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e),
+        };
+
+        let file = File::create(&path)?;
+        let journal = Journal {
+            path,
+            file,
+            state: SessionState::default(),
+            events: 0,
+        };
+        Ok((journal, previous.filter(|state| state.file.is_some())))
+    }
+
+    pub fn state(&self) -> &SessionState {
+        &self.state
+    }
+
+    /// Apply an event and append it to the journal
+    pub fn record(&mut self, event: JournalEvent) -> io::Result<()> {
+        self.state.apply(&event);
+        if self.events >= COMPACT_AFTER {
+            return self.compact();
+        }
+        self.append(&event)
+    }
+
+    fn append(&mut self, event: &JournalEvent) -> io::Result<()> {
+        let mut line = serde_json::to_vec(event)?;
+        line.push(b'\n');
+        self.file.write_all(&line)?;
+        self.file.sync_data()?;
+        self.events += 1;
+        Ok(())
+    }
+
+    /// Replace the journal with a snapshot of the current state
+    /// Written to a temporary file first, so a crash mid-way leaves the old journal intact
+    fn compact(&mut self) -> io::Result<()> {
+        let tmp = self.path.with_extension("jsonl.tmp");
+        let mut file = File::create(&tmp)?;
+        let mut line = serde_json::to_vec(&JournalEvent::Snapshot {
+            state: self.state.clone(),
+        })?;
+        line.push(b'\n');
+        file.write_all(&line)?;
+        file.sync_all()?;
+        fs::rename(&tmp, &self.path)?;
+        self.file = OpenOptions::new().append(true).open(&self.path)?;
+        self.events = 1;
+        Ok(())
+    }
+}
+
+/// Rebuild the state a journal describes, or None if it ends with a clean exit
+/// Replay stops at the first unreadable line, which a crash mid-write can leave behind
+fn replay(reader: impl BufRead) -> Option<SessionState> {
+    let mut state = SessionState::default();
+    let mut exited = false;
+    for line in reader.lines() {
+        let Some(event) = line
+            .ok()
+            .and_then(|line| serde_json::from_str::<JournalEvent>(&line).ok())
+        else {
+            break;
+        };
+        exited = event == JournalEvent::Exited;
+        state.apply(&event);
+    }
+    (!exited).then_some(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recover_unfinished_session() {
+        let config = tempfile::tempdir().unwrap();
+        let (mut journal, previous) = Journal::open(config.path()).unwrap();
+        assert!(previous.is_none());
+        journal
+            .record(JournalEvent::Opened {
+                path: "/var/log/app.log".to_string(),
+            })
+            .unwrap();
+        journal.record(JournalEvent::FollowStarted).unwrap();
+        journal
+            .record(JournalEvent::View {
+                position: 1200,
+                filter: Some("ERROR".to_string()),
+            })
+            .unwrap();
+        for position in 0..COMPACT_AFTER as u64 + 5 {
+            journal
+                .record(JournalEvent::View {
+                    position,
+                    filter: None,
+                })
+                .unwrap();
+        }
+        drop(journal);
+
+        // A torn final line from the crash is ignored
+        let path = config.path().join(JOURNAL_FILE);
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"event\":\"opened\",\"pa").unwrap();
+        drop(file);
+        assert!(fs::read_to_string(&path).unwrap().lines().count() < 10);
+
+        let (mut journal, previous) = Journal::open(config.path()).unwrap();
+        let previous = previous.unwrap();
+        assert_eq!(previous.file.as_deref(), Some("/var/log/app.log"));
+        assert!(previous.following);
+        assert_eq!(previous.position, COMPACT_AFTER as u64 + 4);
+
+        // A clean exit leaves nothing to restore
+        journal.record(JournalEvent::Exited).unwrap();
+        drop(journal);
+        let (_, previous) = Journal::open(config.path()).unwrap();
+        assert!(previous.is_none());
+    }
+}
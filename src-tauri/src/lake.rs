@@ -0,0 +1,168 @@
+use crate::field_search::{quote_identifier, quote_text};
+use crate::parsers::Column;
+use crate::snippets::ColumnRole;
+use serde::Serialize;
+use std::path::Path;
+
+/// Columns a lake is partitioned by, in directory order
+pub const PARTITION_COLUMNS: [&str; 3] = ["log_date", "log_hour", "log_source"];
+
+/// Table a lake is opened as
+pub const LAKE_TABLE: &str = "lake";
+
+/// `log_date` and `log_hour` of lines without a timestamp
+pub const UNDATED: &str = "undated";
+
+/// One log file written to a lake
+#[derive(Debug, Clone, Serialize)]
+pub struct LakeSource {
+    pub path: String,
+    /// Its `log_source` partition
+    pub source: String,
+    pub rows: u64,
+    /// Column the date and hour partitions were taken from, None when every row is undated
+    pub timestamp_column: Option<String>,
+}
+
+/// Summary of an export to a lake
+#[derive(Debug, Clone, Serialize)]
+pub struct LakeExportSummary {
+    pub path: String,
+    pub sources: Vec<LakeSource>,
+    pub rows: u64,
+}
+
+/// `log_source` partition of a log file: its file name, with characters that don't belong
+/// in a directory name replaced
+pub fn source_name(path: &Path) -> String {
+    let name: String = path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    match name.trim_start_matches('.') {
+        "" => "unnamed".to_string(),
+        name => name.to_string(),
+    }
+}
+
+/// SQL selecting a table's rows with the partition columns added, dated by its timestamp
+/// column when it has one. Columns named like a partition column are left out, as the
+/// partitions take their place. Returns the SQL and the timestamp column used
+pub fn partitioned_sql(table: &str, columns: &[Column], source: &str) -> (String, Option<String>) {
+    let timestamp = ColumnRole::Timestamp.find(columns);
+    let partition = |format: &str| match timestamp {
+        Some(column) => format!(
+            "COALESCE(to_char({}, {}), {})",
+            quote_identifier(&column.name),
+            quote_text(format),
+            quote_text(UNDATED)
+        ),
+        None => quote_text(UNDATED),
+    };
+    let mut select: Vec<String> = columns
+        .iter()
+        .filter(|c| {
+            !PARTITION_COLUMNS
+                .iter()
+                .any(|p| c.name.eq_ignore_ascii_case(p))
+        })
+        .map(|c| quote_identifier(&c.name))
+        .collect();
+    select.push(format!("{} AS log_date", partition("%Y-%m-%d")));
+    select.push(format!("{} AS log_hour", partition("%H")));
+    select.push(format!("{} AS log_source", quote_text(source)));
+    (
+        format!("SELECT {} FROM {}", select.join(", "), table),
+        timestamp.map(|c| c.name.clone()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query_engine::QueryEngine;
+    use serde_json::json;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_source_name() {
+        assert_eq!(source_name(Path::new("/var/log/api gw.log")), "api_gw.log");
+        assert_eq!(source_name(Path::new("/var/log/.hidden")), "hidden");
+        assert_eq!(source_name(Path::new("/")), "unnamed");
+    }
+
+    #[tokio::test]
+    async fn test_lake_partitions_and_accumulates() {
+        let mut file = NamedTempFile::new().unwrap();
+        for line in [
+            "2024-03-01T09:15:00 GET /a",
+            "2024-03-01T09:45:00 GET /b",
+            "2024-03-01T10:05:00 GET /c",
+            "no timestamp here",
+        ] {
+            writeln!(file, "{}", line).unwrap();
+        }
+        file.flush().unwrap();
+        let engine = QueryEngine::new();
+        engine.register_table(file.path(), "logs").await.unwrap();
+        engine
+            .create_temporary_view(
+                "events",
+                "SELECT line_number, TRY_CAST(split_part(line, ' ', 1) AS TIMESTAMP) AS ts, \
+                 line FROM logs",
+            )
+            .await
+            .unwrap();
+
+        let columns = engine.table_columns("events").await.unwrap();
+        let (sql, timestamp) = partitioned_sql("events", &columns, "web.log");
+        assert_eq!(timestamp.as_deref(), Some("ts"));
+        let lake = tempfile::tempdir().unwrap();
+        for _ in 0..2 {
+            let rows = engine
+                .write_partitioned_parquet(&sql, lake.path(), &PARTITION_COLUMNS)
+                .await
+                .unwrap();
+            assert_eq!(rows, 4);
+        }
+        assert!(lake
+            .path()
+            .join("log_date=2024-03-01/log_hour=09/log_source=web.log")
+            .is_dir());
+
+        let reader = QueryEngine::new();
+        reader
+            .register_parquet_dir(LAKE_TABLE, lake.path(), &PARTITION_COLUMNS)
+            .await
+            .unwrap();
+        let result = reader
+            .execute_sql(
+                "SELECT log_date, log_hour, COUNT(*) FROM lake \
+                 WHERE log_source = 'web.log' GROUP BY log_date, log_hour \
+                 ORDER BY log_date, log_hour",
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            result.rows,
+            vec![
+                vec![json!("2024-03-01"), json!("09"), json!(4)],
+                vec![json!("2024-03-01"), json!("10"), json!(2)],
+                vec![json!(UNDATED), json!(UNDATED), json!(2)],
+            ]
+        );
+        // The lake stays registered when the open file changes
+        reader.clear().await;
+        assert!(reader.lookups().contains(&LAKE_TABLE.to_string()));
+    }
+}
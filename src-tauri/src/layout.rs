@@ -0,0 +1,137 @@
+use serde::Serialize;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// Widest wrap width accepted, in columns
+pub const MAX_WRAP_WIDTH: usize = 10_000;
+
+/// Where a displayed line wraps
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct WrappedLine {
+    pub line: u64,
+    /// Start of each row after the first, in UTF-16 code units as JavaScript indexes strings
+    pub breaks: Vec<usize>,
+}
+
+/// Start of each row after the first when `text` is wrapped at `width` columns
+/// Rows break after whitespace where they can and otherwise between graphemes, never inside
+/// one; wide characters take two columns, and whitespace may hang past the edge
+pub fn wrap_points(text: &str, width: usize) -> Vec<usize> {
+    let width = width.clamp(1, MAX_WRAP_WIDTH);
+    let mut breaks = Vec::new();
+    let (mut offset, mut column, mut row_start) = (0, 0, 0);
+    // Offset and column just past the last whitespace of the row
+    let mut opportunity: Option<(usize, usize)> = None;
+
+    for grapheme in text.graphemes(true) {
+        let grapheme_width = grapheme.width();
+        let is_space = grapheme.chars().all(char::is_whitespace);
+        while !is_space && column > 0 && column + grapheme_width > width {
+            match opportunity.take().filter(|&(at, _)| at > row_start) {
+                Some((at, at_column)) => {
+                    breaks.push(at);
+                    row_start = at;
+                    column -= at_column;
+                }
+                None => {
+                    breaks.push(offset);
+                    row_start = offset;
+                    column = 0;
+                }
+            }
+        }
+        offset += grapheme.encode_utf16().count();
+        column += grapheme_width;
+        if is_space {
+            opportunity = Some((offset, column));
+        }
+    }
+    breaks
+}
+
+/// A byte offset into a line, counted in the units other consumers index text by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct OffsetPosition {
+    pub byte: usize,
+    /// Characters (Unicode scalar values) before the offset
+    pub char: usize,
+    pub grapheme: usize,
+    /// UTF-16 code units before the offset, as JavaScript indexes strings
+    pub utf16: usize,
+    /// Display columns before the offset, with wide characters taking two
+    pub column: usize,
+}
+
+/// Positions of byte offsets into `text`, in the order given
+/// An offset inside a grapheme maps to where the grapheme starts, and one past the end
+/// of the text to its end
+pub fn map_offsets(text: &str, byte_offsets: &[usize]) -> Vec<OffsetPosition> {
+    let mut order: Vec<usize> = (0..byte_offsets.len()).collect();
+    order.sort_by_key(|&i| byte_offsets[i]);
+
+    let mut positions = vec![None; byte_offsets.len()];
+    let mut graphemes = text.grapheme_indices(true).peekable();
+    let mut at = OffsetPosition {
+        byte: 0,
+        char: 0,
+        grapheme: 0,
+        utf16: 0,
+        column: 0,
+    };
+    for i in order {
+        let byte = byte_offsets[i];
+        while let Some((_, grapheme)) =
+            graphemes.next_if(|(start, grapheme)| start + grapheme.len() <= byte)
+        {
+            at.char += grapheme.chars().count();
+            at.grapheme += 1;
+            at.utf16 += grapheme.encode_utf16().count();
+            at.column += grapheme.width();
+        }
+        positions[i] = Some(OffsetPosition { byte, ..at });
+    }
+    positions.into_iter().flatten().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap_points() {
+        assert!(wrap_points("short line", 20).is_empty());
+        // Breaks after the space, leaving it hanging at the end of the first row
+        assert_eq!(wrap_points("hello world", 8), [6]);
+        assert_eq!(wrap_points("abcdefghij", 4), [4, 8]);
+        // Wide characters take two columns, and the flag is one grapheme of four UTF-16 units
+        assert_eq!(wrap_points("日本語", 4), [2]);
+        assert_eq!(wrap_points("a🇯🇵b", 3), [5]);
+        // A word longer than the row is split after the row's break
+        assert_eq!(wrap_points("ab cdefgh", 4), [3, 7]);
+    }
+
+    #[test]
+    fn test_map_offsets() {
+        // "é" is two bytes, "👍🏽" one grapheme of two characters, eight bytes and four
+        // UTF-16 units, and "語" three bytes wide two columns
+        let text = "é👍🏽語x";
+        let positions = map_offsets(text, &[14, 0, 2, 5, 10, 13, 99]);
+        let units: Vec<(usize, usize, usize, usize)> = positions
+            .iter()
+            .map(|p| (p.char, p.grapheme, p.utf16, p.column))
+            .collect();
+        assert_eq!(
+            units,
+            [
+                (5, 4, 7, 6),
+                (0, 0, 0, 0),
+                (1, 1, 1, 1),
+                (1, 1, 1, 1),
+                (3, 2, 5, 3),
+                (4, 3, 6, 5),
+                (5, 4, 7, 6),
+            ]
+        );
+        assert_eq!(positions[2].byte, 2);
+    }
+}
@@ -1,31 +1,218 @@
+pub mod actions;
+pub mod alerts;
+pub mod analysis;
+pub mod applog;
+pub mod bench;
+pub mod bundle;
+pub mod charts;
+pub mod columnar;
 pub mod commands;
+pub mod correlate;
+pub mod dashboards;
+pub mod deep_index;
+pub mod eventlog;
+pub mod export;
+pub mod field_search;
+pub mod funnel;
+pub mod handoff;
 pub mod indexer;
+pub mod integrity;
+pub mod journal;
+pub mod lake;
+pub mod layout;
+pub mod listeners;
+pub mod live;
+pub mod livestats;
+pub mod metrics;
+pub mod multiscan;
+pub mod operations;
+pub mod packs;
+pub mod palette;
+mod parse_job;
+pub mod parsers;
+pub mod policy;
+pub mod profile;
 pub mod query_engine;
+pub mod query_language;
+pub mod regex_cache;
+pub mod results;
+pub mod sanitize;
+pub mod search;
+pub mod snippets;
+pub mod states;
+pub mod tail;
+pub mod timeline;
+pub mod timestamp;
+pub mod tokens;
+pub mod trigram;
+pub mod unifiedlog;
+pub mod views;
+pub mod watch;
+pub mod zones;
 
 use commands::AppState;
 use std::sync::Arc;
+use tauri::Manager;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    // Create a runtime for async initialization
-    let rt = tokio::runtime::Runtime::new().expect("Failed to create runtime");
-    let app_state = rt.block_on(async { Arc::new(AppState::new().await) });
+    applog::init();
+
+    let app_state = Arc::new(AppState::new());
 
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
+        .plugin(tauri_plugin_notification::init())
         .manage(app_state)
         .invoke_handler(tauri::generate_handler![
             commands::open_file,
             commands::close_file,
+            commands::list_approved_directories,
+            commands::approve_directory,
+            commands::revoke_directory,
             commands::get_lines,
             commands::get_lines_binary,
+            commands::get_lines_meta,
+            commands::get_wrapped_lines,
+            commands::get_line_tokens,
+            commands::map_offsets,
+            commands::get_sanitize_options,
+            commands::set_sanitize_options,
             commands::get_file_info,
+            commands::get_preview,
             commands::search,
+            commands::cancel_search,
+            commands::scan_patterns,
+            commands::build_search_index,
+            commands::build_deep_index,
+            commands::deep_search,
+            commands::deep_index_status,
+            commands::delete_deep_index,
+            commands::cancel_operation,
+            commands::list_operations,
             commands::execute_sql,
+            commands::sort_result,
+            commands::filter_result,
+            commands::pivot_result,
+            commands::get_result_cell,
+            commands::pin_result,
+            commands::list_pinned_results,
+            commands::open_pinned_result,
+            commands::unpin_result,
+            commands::diff_results,
+            commands::list_views,
+            commands::save_view,
+            commands::delete_view,
+            commands::register_lookup_table,
+            commands::drop_lookup_table,
+            commands::list_lookup_tables,
+            commands::list_sql_functions,
+            commands::auto_correlate,
+            commands::resolve_row_to_line,
+            commands::reveal_result_row,
+            commands::line_query_snippet,
+            commands::get_line_record,
+            commands::search_fields,
+            commands::translate_query,
+            commands::list_snippets,
+            commands::render_snippet,
+            commands::get_chart_data,
+            commands::list_dashboards,
+            commands::save_dashboard,
+            commands::delete_dashboard,
             commands::get_line_count,
+            commands::get_line_length_stats,
+            commands::get_activity_heatmap,
+            commands::compare_time_windows,
+            commands::find_bursts,
+            commands::find_first_occurrences,
+            commands::find_rare_lines,
+            commands::extract_metric,
+            commands::get_latency_breakdown,
+            commands::analyse_funnel,
+            commands::analyse_states,
+            commands::find_duplicates,
+            commands::scan_secrets,
+            commands::open_compare_file,
+            commands::close_compare_file,
+            commands::get_compare_lines,
+            commands::sync_position,
+            commands::export_html,
+            commands::export_lake,
+            commands::open_lake,
+            commands::export_database,
+            commands::verify_file_integrity,
+            commands::get_hash_manifest,
+            commands::export_trace,
+            commands::clear_trace,
+            commands::get_feature_issues,
+            commands::get_app_logs,
+            commands::run_benchmark,
+            commands::record_view_state,
+            commands::take_restorable_session,
+            commands::start_follow,
+            commands::stop_follow,
+            commands::get_live_stats,
+            commands::set_live_stats_options,
+            commands::list_alert_rules,
+            commands::save_alert_rule,
+            commands::delete_alert_rule,
+            commands::start_watch,
+            commands::stop_watch,
+            commands::get_watch_status,
+            commands::get_watch_findings,
+            commands::clear_watch_findings,
+            commands::export_config,
+            commands::import_config,
+            commands::list_rule_packs,
+            commands::subscribe_rule_pack,
+            commands::refresh_rule_packs,
+            commands::pin_rule_pack,
+            commands::unsubscribe_rule_pack,
+            commands::list_palettes,
+            commands::get_palette_config,
+            commands::set_palette_config,
+            commands::assign_highlight_colors,
+            commands::set_follow_filter,
+            commands::pause_follow,
+            commands::resume_follow,
+            commands::open_stream,
+            commands::open_listener,
+            commands::open_event_log,
+            commands::open_unified_log,
+            commands::start_recording,
+            commands::stop_recording,
+            commands::parse_file,
+            commands::get_schema_override,
+            commands::set_schema_override,
+            commands::apply_parse_profile,
+            commands::list_parse_profiles,
+            commands::save_parse_profile,
+            commands::delete_parse_profile,
+            commands::preview_parse,
+            commands::parse_file_background,
+            commands::cancel_parse,
+            commands::get_parse_progress,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .setup(|app| {
+            commands::start_journal(app.handle());
+            commands::load_saved_views(app.handle());
+            commands::start_alerts(app.handle());
+            commands::remove_stale_pins();
+            Ok(())
+        })
+        .on_window_event(|window, event| {
+            if let tauri::WindowEvent::Focused(true) = event {
+                commands::on_window_focused(window.app_handle());
+            }
+        })
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                commands::end_journal(app);
+            }
+        });
 }
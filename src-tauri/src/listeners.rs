@@ -0,0 +1,706 @@
+use crate::live::{ChunkSender, RetentionOptions};
+use crate::timestamp::format_timestamp;
+use flate2::read::{MultiGzDecoder, ZlibDecoder};
+use rmpv::Value as MsgValue;
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use std::io::{Cursor, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// How often blocked sockets wake up to check whether the source was stopped
+const STOP_POLL: Duration = Duration::from_millis(200);
+/// GELF chunked messages that aren't complete after this long are discarded
+const GELF_CHUNK_TIMEOUT: Duration = Duration::from_secs(5);
+/// GELF allows at most this many chunks per message
+const GELF_MAX_CHUNKS: u8 = 128;
+/// Largest GELF frame or Fluent message buffered before a connection is dropped, and
+/// largest a compressed GELF message may inflate to
+const MAX_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
+
+/// Errors that can occur while decoding shipped log messages
+#[derive(Error, Debug)]
+pub enum ListenerError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Invalid MessagePack: {0}")]
+    MsgPack(#[from] rmpv::decode::Error),
+    #[error("Protocol error: {0}")]
+    Protocol(String),
+}
+
+/// Wire protocol a listener accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ListenerProtocol {
+    /// Graylog GELF over UDP, optionally chunked and gzip/zlib compressed
+    GelfUdp,
+    /// Graylog GELF over TCP, null-byte delimited
+    GelfTcp,
+    /// Fluentd / Fluent Bit forward protocol over TCP
+    Fluent,
+}
+
+impl ListenerProtocol {
+    fn default_bind(self) -> &'static str {
+        match self {
+            ListenerProtocol::GelfUdp | ListenerProtocol::GelfTcp => "127.0.0.1:12201",
+            ListenerProtocol::Fluent => "127.0.0.1:24224",
+        }
+    }
+
+    fn scheme(self) -> &'static str {
+        match self {
+            ListenerProtocol::GelfUdp => "gelf+udp",
+            ListenerProtocol::GelfTcp => "gelf+tcp",
+            ListenerProtocol::Fluent => "fluent",
+        }
+    }
+}
+
+/// Options for receiving logs from a shipper
+#[derive(Debug, Clone, Deserialize)]
+pub struct ListenerOptions {
+    pub protocol: ListenerProtocol,
+    /// Address to bind; defaults to the protocol's standard port on the loopback interface,
+    /// so shippers on other hosts are only accepted when bound to e.g. `0.0.0.0` on purpose
+    #[serde(default)]
+    pub bind: Option<String>,
+    #[serde(default)]
+    pub batch_interval_ms: Option<u64>,
+    #[serde(default)]
+    pub retention: RetentionOptions,
+}
+
+enum Socket {
+    Udp(UdpSocket),
+    Tcp(TcpListener),
+}
+
+/// A bound socket waiting to be attached to a live source
+/// Each received message becomes one JSON line whose keys are the structured fields
+pub struct Listener {
+    protocol: ListenerProtocol,
+    socket: Socket,
+    local_addr: SocketAddr,
+}
+
+impl Listener {
+    /// Bind the socket so address errors surface before the view is replaced
+    pub fn bind(options: &ListenerOptions) -> std::io::Result<Self> {
+        let addr = options
+            .bind
+            .as_deref()
+            .unwrap_or(options.protocol.default_bind());
+        let socket = match options.protocol {
+            ListenerProtocol::GelfUdp => {
+                let socket = UdpSocket::bind(addr)?;
+                socket.set_read_timeout(Some(STOP_POLL))?;
+                Socket::Udp(socket)
+            }
+            ListenerProtocol::GelfTcp | ListenerProtocol::Fluent => {
+                let listener = TcpListener::bind(addr)?;
+                listener.set_nonblocking(true)?;
+                Socket::Tcp(listener)
+            }
+        };
+        let local_addr = match &socket {
+            Socket::Udp(socket) => socket.local_addr()?,
+            Socket::Tcp(listener) => listener.local_addr()?,
+        };
+        Ok(Listener {
+            protocol: options.protocol,
+            socket,
+            local_addr,
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Name shown for the live view, e.g. `fluent://127.0.0.1:24224`
+    pub fn display_name(&self) -> String {
+        format!("{}://{}", self.protocol.scheme(), self.local_addr)
+    }
+
+    /// Start receiving into `sender` until the live source is stopped
+    pub fn spawn<F>(self, sender: ChunkSender, on_error: F)
+    where
+        F: Fn(String) + Send + Sync + 'static,
+    {
+        let on_error = std::sync::Arc::new(on_error);
+        std::thread::spawn(move || match self.socket {
+            Socket::Udp(socket) => receive_gelf_udp(socket, &sender, &*on_error),
+            Socket::Tcp(listener) => {
+                let protocol = self.protocol;
+                accept_loop(listener, &sender, &*on_error, |stream, sender| {
+                    let on_error = on_error.clone();
+                    std::thread::spawn(move || {
+                        let result = match protocol {
+                            ListenerProtocol::Fluent => receive_fluent(stream, &sender),
+                            _ => receive_gelf_tcp(stream, &sender),
+                        };
+                        if let Err(err) = result {
+                            if !sender.is_stopped() {
+                                on_error(format!("Connection closed: {}", err));
+                            }
+                        }
+                    });
+                });
+            }
+        });
+    }
+}
+
+fn accept_loop<F, C>(
+    listener: TcpListener,
+    sender: &ChunkSender,
+    on_error: &F,
+    mut on_connection: C,
+) where
+    F: Fn(String) + ?Sized,
+    C: FnMut(TcpStream, ChunkSender),
+{
+    while !sender.is_stopped() {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                let configured = stream
+                    .set_nonblocking(false)
+                    .and_then(|_| stream.set_read_timeout(Some(STOP_POLL)));
+                match configured {
+                    Ok(()) => on_connection(stream, sender.clone()),
+                    Err(err) => on_error(format!("Failed to accept connection: {}", err)),
+                }
+            }
+            Err(err) if err.kind() == ErrorKind::WouldBlock => std::thread::sleep(STOP_POLL),
+            Err(err) => {
+                on_error(format!("Failed to accept connection: {}", err));
+                std::thread::sleep(STOP_POLL);
+            }
+        }
+    }
+}
+
+fn is_timeout(err: &std::io::Error) -> bool {
+    matches!(
+        err.kind(),
+        ErrorKind::WouldBlock | ErrorKind::TimedOut | ErrorKind::Interrupted
+    )
+}
+
+/// Push decoded lines to the live source; returns false once it has been stopped
+fn send_lines(sender: &ChunkSender, lines: Vec<String>) -> bool {
+    if lines.is_empty() {
+        return true;
+    }
+    let mut chunk = String::with_capacity(lines.iter().map(|l| l.len() + 1).sum());
+    for line in lines {
+        chunk.push_str(&line);
+        chunk.push('\n');
+    }
+    sender.send(chunk.into_bytes())
+}
+
+fn receive_gelf_udp<F>(socket: UdpSocket, sender: &ChunkSender, on_error: &F)
+where
+    F: Fn(String) + ?Sized,
+{
+    let mut buf = vec![0u8; 65_536];
+    let mut chunks = GelfChunks::default();
+    while !sender.is_stopped() {
+        let len = match socket.recv(&mut buf) {
+            Ok(len) => len,
+            Err(err) if is_timeout(&err) => {
+                chunks.expire(Instant::now());
+                continue;
+            }
+            Err(err) => {
+                on_error(format!("Failed to receive: {}", err));
+                return;
+            }
+        };
+        let payload = match chunks.accept(&buf[..len], Instant::now()) {
+            Ok(Some(payload)) => payload,
+            Ok(None) => continue,
+            Err(err) => {
+                on_error(err.to_string());
+                continue;
+            }
+        };
+        match decode_gelf(&payload) {
+            Ok(line) => {
+                if !send_lines(sender, vec![line]) {
+                    return;
+                }
+            }
+            Err(err) => on_error(err.to_string()),
+        }
+    }
+}
+
+fn receive_gelf_tcp(mut stream: TcpStream, sender: &ChunkSender) -> Result<(), ListenerError> {
+    let mut pending = Vec::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    while !sender.is_stopped() {
+        let len = match stream.read(&mut buf) {
+            Ok(0) => break,
+            Ok(len) => len,
+            Err(err) if is_timeout(&err) => continue,
+            Err(err) => return Err(err.into()),
+        };
+        pending.extend_from_slice(&buf[..len]);
+
+        let mut lines = Vec::new();
+        let mut consumed = 0;
+        for end in memchr::memchr_iter(0, &pending) {
+            let frame = &pending[consumed..end];
+            if !frame.is_empty() {
+                lines.push(decode_gelf(frame)?);
+            }
+            consumed = end + 1;
+        }
+        pending.drain(..consumed);
+        if pending.len() > MAX_MESSAGE_SIZE {
+            return Err(ListenerError::Protocol("GELF frame too large".to_string()));
+        }
+        if !send_lines(sender, lines) {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Reassembles chunked GELF datagrams
+#[derive(Default)]
+struct GelfChunks {
+    pending: HashMap<[u8; 8], PendingMessage>,
+}
+
+struct PendingMessage {
+    parts: Vec<Option<Vec<u8>>>,
+    received: usize,
+    started: Instant,
+}
+
+impl GelfChunks {
+    /// Accept a datagram, returning a complete message payload when one is available
+    fn accept(&mut self, datagram: &[u8], now: Instant) -> Result<Option<Vec<u8>>, ListenerError> {
+        if !datagram.starts_with(&[0x1e, 0x0f]) {
+            return Ok(Some(datagram.to_vec()));
+        }
+        if datagram.len() < 12 {
+            return Err(ListenerError::Protocol(
+                "Truncated GELF chunk header".to_string(),
+            ));
+        }
+        let id: [u8; 8] = datagram[2..10].try_into().unwrap_or_default();
+        let seq = datagram[10];
+        let count = datagram[11];
+        if count == 0 || count > GELF_MAX_CHUNKS || seq >= count {
+            return Err(ListenerError::Protocol(format!(
+                "Invalid GELF chunk {} of {}",
+                seq, count
+            )));
+        }
+
+        self.expire(now);
+        let message = self.pending.entry(id).or_insert_with(|| PendingMessage {
+            parts: vec![None; count as usize],
+            received: 0,
+            started: now,
+        });
+        if message.parts.len() != count as usize {
+            self.pending.remove(&id);
+            return Err(ListenerError::Protocol(
+                "GELF chunk count changed mid-message".to_string(),
+            ));
+        }
+        let part = &mut message.parts[seq as usize];
+        if part.is_none() {
+            *part = Some(datagram[12..].to_vec());
+            message.received += 1;
+        }
+        if message.received < count as usize {
+            return Ok(None);
+        }
+
+        let message = self
+            .pending
+            .remove(&id)
+            .map(|m| m.parts)
+            .unwrap_or_default();
+        Ok(Some(message.into_iter().flatten().flatten().collect()))
+    }
+
+    /// Drop partially received messages that timed out
+    fn expire(&mut self, now: Instant) {
+        self.pending
+            .retain(|_, message| now.duration_since(message.started) < GELF_CHUNK_TIMEOUT);
+    }
+}
+
+/// Decode a GELF payload (plain, gzip or zlib) into a JSON line
+/// `short_message` becomes `message` and the underscore prefix is stripped from additional fields
+pub fn decode_gelf(payload: &[u8]) -> Result<String, ListenerError> {
+    let json = if payload.starts_with(&[0x1f, 0x8b]) {
+        inflate(MultiGzDecoder::new(payload))?
+    } else if payload.first() == Some(&0x78) {
+        inflate(ZlibDecoder::new(payload))?
+    } else {
+        payload.to_vec()
+    };
+
+    let JsonValue::Object(message) = serde_json::from_slice(&json)? else {
+        return Err(ListenerError::Protocol(
+            "GELF message is not an object".to_string(),
+        ));
+    };
+
+    let mut fields = Vec::with_capacity(message.len());
+    let timestamp = message
+        .get("timestamp")
+        .and_then(JsonValue::as_f64)
+        .map(|secs| JsonValue::from(format_timestamp((secs * 1000.0) as i64)));
+    fields.push((
+        "timestamp".to_string(),
+        timestamp.unwrap_or(JsonValue::Null),
+    ));
+    for key in ["host", "level"] {
+        if let Some(value) = message.get(key) {
+            fields.push((key.to_string(), value.clone()));
+        }
+    }
+    if let Some(value) = message.get("short_message") {
+        fields.push(("message".to_string(), value.clone()));
+    }
+    for (key, value) in message {
+        match key.as_str() {
+            "version" | "timestamp" | "host" | "level" | "short_message" => {}
+            _ => fields.push((key.trim_start_matches('_').to_string(), value)),
+        }
+    }
+    Ok(json_line(fields))
+}
+
+/// Decompress a GELF payload, refusing one that inflates past `MAX_MESSAGE_SIZE`
+fn inflate(decoder: impl Read) -> Result<Vec<u8>, ListenerError> {
+    let mut out = Vec::new();
+    decoder
+        .take(MAX_MESSAGE_SIZE as u64 + 1)
+        .read_to_end(&mut out)?;
+    if out.len() > MAX_MESSAGE_SIZE {
+        return Err(ListenerError::Protocol(format!(
+            "GELF message inflates past {} bytes",
+            MAX_MESSAGE_SIZE
+        )));
+    }
+    Ok(out)
+}
+
+fn receive_fluent(mut stream: TcpStream, sender: &ChunkSender) -> Result<(), ListenerError> {
+    let mut pending = Vec::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    while !sender.is_stopped() {
+        let len = match stream.read(&mut buf) {
+            Ok(0) => break,
+            Ok(len) => len,
+            Err(err) if is_timeout(&err) => continue,
+            Err(err) => return Err(err.into()),
+        };
+        pending.extend_from_slice(&buf[..len]);
+
+        let mut lines = Vec::new();
+        loop {
+            let mut cursor = Cursor::new(&pending[..]);
+            let message = match rmpv::decode::read_value(&mut cursor) {
+                Ok(message) => message,
+                Err(err) if is_incomplete(&err) => break,
+                Err(err) => return Err(err.into()),
+            };
+            let consumed = cursor.position() as usize;
+            pending.drain(..consumed);
+
+            let ack = decode_fluent(&message, &mut lines)?;
+            if let Some(chunk) = ack {
+                let reply = MsgValue::Map(vec![(MsgValue::from("ack"), chunk)]);
+                let mut bytes = Vec::new();
+                rmpv::encode::write_value(&mut bytes, &reply)
+                    .map_err(|err| ListenerError::Protocol(err.to_string()))?;
+                stream.write_all(&bytes)?;
+            }
+        }
+        if pending.len() > MAX_MESSAGE_SIZE {
+            return Err(ListenerError::Protocol(
+                "Fluent message too large".to_string(),
+            ));
+        }
+        if !send_lines(sender, lines) {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn is_incomplete(err: &rmpv::decode::Error) -> bool {
+    match err {
+        rmpv::decode::Error::InvalidMarkerRead(io) | rmpv::decode::Error::InvalidDataRead(io) => {
+            io.kind() == ErrorKind::UnexpectedEof
+        }
+        _ => false,
+    }
+}
+
+/// Decode one forward-protocol message (Message, Forward or PackedForward mode) into JSON lines
+/// Returns the chunk id to acknowledge when the client asked for one
+pub fn decode_fluent(
+    message: &MsgValue,
+    lines: &mut Vec<String>,
+) -> Result<Option<MsgValue>, ListenerError> {
+    let invalid = || ListenerError::Protocol("Unrecognized forward protocol message".to_string());
+    let parts = message.as_array().ok_or_else(invalid)?;
+    let tag = parts
+        .first()
+        .and_then(MsgValue::as_str)
+        .ok_or_else(invalid)?;
+    let entries = parts.get(1).ok_or_else(invalid)?;
+
+    let (option, events): (Option<&MsgValue>, Vec<(&MsgValue, &MsgValue)>);
+    let unpacked;
+    match entries {
+        // Forward mode: [tag, [[time, record], ...], option?]
+        MsgValue::Array(items) => {
+            option = parts.get(2);
+            events = items
+                .iter()
+                .map(|entry| fluent_entry(entry).ok_or_else(invalid))
+                .collect::<Result<_, _>>()?;
+        }
+        // PackedForward mode: [tag, bin(concatenated [time, record]), option?]
+        MsgValue::Binary(_) | MsgValue::String(_) => {
+            option = parts.get(2);
+            let packed = match entries {
+                MsgValue::Binary(bytes) => bytes.as_slice(),
+                _ => entries.as_str().map(str::as_bytes).unwrap_or_default(),
+            };
+            let compressed = option
+                .and_then(|o| map_get(o, "compressed"))
+                .and_then(MsgValue::as_str)
+                == Some("gzip");
+            let bytes = if compressed {
+                let mut out = Vec::new();
+                MultiGzDecoder::new(packed).read_to_end(&mut out)?;
+                out
+            } else {
+                packed.to_vec()
+            };
+            let mut cursor = Cursor::new(&bytes[..]);
+            let mut values = Vec::new();
+            while (cursor.position() as usize) < bytes.len() {
+                values.push(rmpv::decode::read_value(&mut cursor)?);
+            }
+            unpacked = values;
+            events = unpacked
+                .iter()
+                .map(|entry| fluent_entry(entry).ok_or_else(invalid))
+                .collect::<Result<_, _>>()?;
+        }
+        // Message mode: [tag, time, record, option?]
+        _ => {
+            let record = parts.get(2).ok_or_else(invalid)?;
+            option = parts.get(3);
+            events = vec![(entries, record)];
+        }
+    }
+
+    for (time, record) in events {
+        let mut fields = vec![
+            ("timestamp".to_string(), fluent_time(time)),
+            ("tag".to_string(), JsonValue::from(tag)),
+        ];
+        if let MsgValue::Map(entries) = record {
+            fields.extend(entries.iter().map(|(k, v)| (map_key(k), to_json(v))));
+        }
+        lines.push(json_line(fields));
+    }
+
+    Ok(option.and_then(|o| map_get(o, "chunk")).cloned())
+}
+
+fn fluent_entry(entry: &MsgValue) -> Option<(&MsgValue, &MsgValue)> {
+    match entry.as_array()?.as_slice() {
+        [time, record, ..] => Some((time, record)),
+        _ => None,
+    }
+}
+
+/// Fluent times are integer seconds or an EventTime extension (u32 seconds, u32 nanoseconds)
+fn fluent_time(time: &MsgValue) -> JsonValue {
+    let millis = match time {
+        MsgValue::Integer(secs) => secs.as_i64().map(|s| s * 1000),
+        MsgValue::F64(secs) => Some((secs * 1000.0) as i64),
+        MsgValue::Ext(0, data) if data.len() == 8 => {
+            let secs = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as i64;
+            let nanos = u32::from_be_bytes([data[4], data[5], data[6], data[7]]) as i64;
+            Some(secs * 1000 + nanos / 1_000_000)
+        }
+        _ => None,
+    };
+    millis.map_or(JsonValue::Null, |ms| JsonValue::from(format_timestamp(ms)))
+}
+
+fn map_get<'a>(map: &'a MsgValue, key: &str) -> Option<&'a MsgValue> {
+    match map {
+        MsgValue::Map(entries) => entries
+            .iter()
+            .find(|(k, _)| k.as_str() == Some(key))
+            .map(|(_, v)| v),
+        _ => None,
+    }
+}
+
+fn map_key(key: &MsgValue) -> String {
+    match key {
+        MsgValue::String(s) => String::from_utf8_lossy(s.as_bytes()).to_string(),
+        MsgValue::Binary(bytes) => String::from_utf8_lossy(bytes).to_string(),
+        other => other.to_string(),
+    }
+}
+
+fn to_json(value: &MsgValue) -> JsonValue {
+    match value {
+        MsgValue::Nil => JsonValue::Null,
+        MsgValue::Boolean(b) => JsonValue::Bool(*b),
+        MsgValue::Integer(i) => i
+            .as_i64()
+            .map(JsonValue::from)
+            .or_else(|| i.as_u64().map(JsonValue::from))
+            .unwrap_or(JsonValue::Null),
+        MsgValue::F32(f) => JsonValue::from(*f as f64),
+        MsgValue::F64(f) => JsonValue::from(*f),
+        MsgValue::String(s) => JsonValue::from(String::from_utf8_lossy(s.as_bytes())),
+        MsgValue::Binary(bytes) => JsonValue::from(String::from_utf8_lossy(bytes)),
+        MsgValue::Array(items) => JsonValue::Array(items.iter().map(to_json).collect()),
+        MsgValue::Map(entries) => JsonValue::Object(
+            entries
+                .iter()
+                .map(|(k, v)| (map_key(k), to_json(v)))
+                .collect(),
+        ),
+        MsgValue::Ext(_, data) => JsonValue::from(String::from_utf8_lossy(data)),
+    }
+}
+
+/// Serialize fields as a JSON object, keeping their order so the timestamp leads the line
+pub fn json_line(fields: Vec<(String, JsonValue)>) -> String {
+    let mut line = String::from("{");
+    for (i, (key, value)) in fields.into_iter().enumerate() {
+        if i > 0 {
+            line.push(',');
+        }
+        line.push_str(&JsonValue::from(key).to_string());
+        line.push(':');
+        line.push_str(&value.to_string());
+    }
+    line.push('}');
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    #[test]
+    fn test_decode_gelf_compressed_and_chunked() {
+        let message = br#"{"version":"1.1","host":"web-1","short_message":"disk full","timestamp":1704067200.5,"level":3,"_user":"bob"}"#;
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(message).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        // Split the compressed payload across two chunks delivered out of order
+        let (first, second) = compressed.split_at(compressed.len() / 2);
+        let chunk = |seq: u8, data: &[u8]| {
+            let mut datagram = vec![0x1e, 0x0f, 1, 2, 3, 4, 5, 6, 7, 8, seq, 2];
+            datagram.extend_from_slice(data);
+            datagram
+        };
+        let mut chunks = GelfChunks::default();
+        let now = Instant::now();
+        assert!(chunks.accept(&chunk(1, second), now).unwrap().is_none());
+        let payload = chunks.accept(&chunk(0, first), now).unwrap().unwrap();
+
+        assert_eq!(
+            decode_gelf(&payload).unwrap(),
+            r#"{"timestamp":"2024-01-01T00:00:00.500Z","host":"web-1","level":3,"message":"disk full","user":"bob"}"#
+        );
+
+        // A small payload inflating past the limit is refused rather than buffered
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        let padding = vec![b' '; 1024 * 1024];
+        for _ in 0..=MAX_MESSAGE_SIZE / padding.len() {
+            encoder.write_all(&padding).unwrap();
+        }
+        let bomb = encoder.finish().unwrap();
+        assert!(matches!(
+            decode_gelf(&bomb),
+            Err(ListenerError::Protocol(_))
+        ));
+    }
+
+    #[test]
+    fn test_gelf_chunks_expire() {
+        let mut chunks = GelfChunks::default();
+        let now = Instant::now();
+        let datagram = [0x1e, 0x0f, 9, 9, 9, 9, 9, 9, 9, 9, 0, 2, b'{'];
+        assert!(chunks.accept(&datagram, now).unwrap().is_none());
+        chunks.expire(now + GELF_CHUNK_TIMEOUT);
+        assert!(chunks.pending.is_empty());
+    }
+
+    #[test]
+    fn test_decode_fluent_modes() {
+        let record = MsgValue::Map(vec![(MsgValue::from("log"), MsgValue::from("hello"))]);
+        let event_time = MsgValue::Ext(0, vec![0x65, 0x92, 0x00, 0x80, 0, 0, 0, 0]);
+
+        // Message mode with an ack request
+        let message = MsgValue::Array(vec![
+            MsgValue::from("app.web"),
+            MsgValue::from(1_704_067_200),
+            record.clone(),
+            MsgValue::Map(vec![(MsgValue::from("chunk"), MsgValue::from("abc"))]),
+        ]);
+        let mut lines = Vec::new();
+        let ack = decode_fluent(&message, &mut lines).unwrap();
+        assert_eq!(ack, Some(MsgValue::from("abc")));
+        assert_eq!(
+            lines,
+            vec![r#"{"timestamp":"2024-01-01T00:00:00.000Z","tag":"app.web","log":"hello"}"#]
+        );
+
+        // PackedForward mode with EventTime entries
+        let mut packed = Vec::new();
+        for _ in 0..2 {
+            let entry = MsgValue::Array(vec![event_time.clone(), record.clone()]);
+            rmpv::encode::write_value(&mut packed, &entry).unwrap();
+        }
+        let message = MsgValue::Array(vec![MsgValue::from("app.db"), MsgValue::Binary(packed)]);
+        let mut lines = Vec::new();
+        assert_eq!(decode_fluent(&message, &mut lines).unwrap(), None);
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains(r#""tag":"app.db""#));
+    }
+
+    #[test]
+    fn test_incomplete_msgpack_waits_for_more_data() {
+        let mut bytes = Vec::new();
+        rmpv::encode::write_value(&mut bytes, &MsgValue::from("a long enough string")).unwrap();
+        let err = rmpv::decode::read_value(&mut Cursor::new(&bytes[..5])).unwrap_err();
+        assert!(is_incomplete(&err));
+    }
+}
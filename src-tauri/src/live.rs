@@ -0,0 +1,606 @@
+use crate::indexer::LogFile;
+use crate::tail::{AppendBatcher, FollowEvent};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Size of a single read from a pipe or device
+const READ_CHUNK: usize = 64 * 1024;
+
+/// Kind of non-regular file that can be opened as a live source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamKind {
+    Fifo,
+    CharDevice,
+}
+
+/// Options for opening a pipe or device as a live source
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct StreamOptions {
+    /// Baud rate for serial consoles; when set the device is configured as a serial port
+    #[serde(default)]
+    pub baud_rate: Option<u32>,
+    /// Minimum time between `new-lines` events
+    #[serde(default)]
+    pub batch_interval_ms: Option<u64>,
+    #[serde(default)]
+    pub retention: RetentionOptions,
+}
+
+/// Limits on how much of an unbounded live source is kept in memory
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RetentionOptions {
+    #[serde(default)]
+    pub max_lines: Option<u64>,
+    #[serde(default)]
+    pub max_mb: Option<u64>,
+    #[serde(default)]
+    pub max_age_secs: Option<u64>,
+    /// Append evicted data to this file instead of dropping it
+    #[serde(default)]
+    pub spill_path: Option<String>,
+}
+
+/// Ring-buffer retention for a live view: decides how many of the oldest lines to evict
+pub struct Retention {
+    options: RetentionOptions,
+    /// Arrival time of ingested data and the line count it brought the view to, oldest first
+    arrivals: VecDeque<(Instant, u64)>,
+    spill: Option<File>,
+}
+
+impl Retention {
+    /// Create the policy, opening the spill file if one is configured
+    pub fn new(options: RetentionOptions) -> std::io::Result<Self> {
+        let spill = match &options.spill_path {
+            Some(path) => Some(OpenOptions::new().create(true).append(true).open(path)?),
+            None => None,
+        };
+        Ok(Retention {
+            options,
+            arrivals: VecDeque::new(),
+            spill,
+        })
+    }
+
+    fn is_unbounded(&self) -> bool {
+        self.options.max_lines.is_none()
+            && self.options.max_mb.is_none()
+            && self.options.max_age_secs.is_none()
+    }
+
+    /// Note that the view grew to `line_count` lines at `now`
+    fn record(&mut self, now: Instant, line_count: u64) {
+        if self.options.max_age_secs.is_none() {
+            return;
+        }
+        // Coalesce arrivals within a second so a chatty source doesn't grow this queue
+        match self.arrivals.back_mut() {
+            Some((at, count)) if now.duration_since(*at) < Duration::from_secs(1) => {
+                *count = line_count
+            }
+            _ => self.arrivals.push_back((now, line_count)),
+        }
+    }
+
+    /// Number of lines from the front that exceed any of the limits
+    fn excess(&self, file: &LogFile, now: Instant) -> u64 {
+        let line_count = file.line_count();
+        let by_lines = self
+            .options
+            .max_lines
+            .map_or(0, |max| line_count.saturating_sub(max));
+        let by_size = self.options.max_mb.map_or(0, |max| {
+            let cutoff = file.file_size().saturating_sub(max * 1024 * 1024);
+            file.first_line_from_offset(cutoff)
+        });
+        let by_age = self.options.max_age_secs.map_or(0, |max| {
+            let max_age = Duration::from_secs(max);
+            self.arrivals
+                .iter()
+                .take_while(|(at, _)| now.duration_since(*at) > max_age)
+                .last()
+                .map_or(0, |&(_, count)| count)
+        });
+        by_lines.max(by_size).max(by_age)
+    }
+
+    /// Evict lines over the limits, spilling them to disk if configured; returns the lines removed
+    fn enforce(&mut self, file: &mut LogFile, now: Instant) -> std::io::Result<u64> {
+        let excess = self.excess(file, now);
+        if excess == 0 {
+            return Ok(0);
+        }
+        let before = file.line_count();
+        let evicted = file.evict_front(excess);
+        let removed = before - file.line_count();
+
+        for (_, count) in self.arrivals.iter_mut() {
+            *count = count.saturating_sub(removed);
+        }
+        while self.arrivals.front().is_some_and(|&(_, count)| count == 0) {
+            self.arrivals.pop_front();
+        }
+
+        if let Some(spill) = self.spill.as_mut() {
+            spill.write_all(&evicted)?;
+        }
+        Ok(removed)
+    }
+}
+
+/// Detect whether a path is a named pipe or character device rather than a regular file
+#[cfg(unix)]
+pub fn stream_kind<P: AsRef<Path>>(path: P) -> Option<StreamKind> {
+    use std::os::unix::fs::FileTypeExt;
+    let file_type = std::fs::metadata(path).ok()?.file_type();
+    if file_type.is_fifo() {
+        Some(StreamKind::Fifo)
+    } else if file_type.is_char_device() {
+        Some(StreamKind::CharDevice)
+    } else {
+        None
+    }
+}
+
+/// Detect whether a path names a serial port (`COM3`, `\\.\COM10`) or a named pipe (`\\.\pipe\x`)
+#[cfg(windows)]
+pub fn stream_kind<P: AsRef<Path>>(path: P) -> Option<StreamKind> {
+    let name = path.as_ref().to_string_lossy().to_ascii_uppercase();
+    let name = name.trim_start_matches(r"\\.\");
+    if name.starts_with(r"PIPE\") {
+        Some(StreamKind::Fifo)
+    } else if name.len() > 3
+        && name.starts_with("COM")
+        && name[3..].chars().all(|c| c.is_ascii_digit())
+    {
+        Some(StreamKind::CharDevice)
+    } else {
+        None
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn stream_kind<P: AsRef<Path>>(_path: P) -> Option<StreamKind> {
+    None
+}
+
+/// Handle producers use to push captured bytes into a live source
+#[derive(Clone)]
+pub struct ChunkSender {
+    tx: Sender<Vec<u8>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl ChunkSender {
+    /// Push a chunk; returns false once the live source has been stopped
+    pub fn send(&self, chunk: Vec<u8>) -> bool {
+        !self.is_stopped() && self.tx.send(chunk).is_ok()
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stop.load(Ordering::SeqCst)
+    }
+}
+
+/// A growing in-memory source fed by producer threads (pipes, devices, network listeners)
+/// Ingested chunks are appended to the view and surfaced as batched follow events
+pub struct LiveSource {
+    stop: Arc<AtomicBool>,
+    ingest: Option<JoinHandle<()>>,
+    recording: Arc<Mutex<Option<Recording>>>,
+}
+
+/// A file the live view is being written to as data arrives
+struct Recording {
+    path: PathBuf,
+    file: File,
+    bytes_written: u64,
+}
+
+impl Recording {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        self.file.write_all(bytes)?;
+        self.bytes_written += bytes.len() as u64;
+        Ok(())
+    }
+
+    fn summary(&self) -> RecordingSummary {
+        RecordingSummary {
+            path: self.path.to_string_lossy().to_string(),
+            bytes_written: self.bytes_written,
+        }
+    }
+}
+
+/// State of a live session recording
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordingSummary {
+    pub path: String,
+    pub bytes_written: u64,
+}
+
+impl LiveSource {
+    /// Start the ingest thread
+    /// `with_file` runs a closure against the live view; `on_event` receives the same events as file following
+    pub fn start<W, E>(
+        batch_interval: Duration,
+        retention: Retention,
+        with_file: W,
+        on_event: E,
+    ) -> (Self, ChunkSender)
+    where
+        W: FnMut(&mut dyn FnMut(&mut LogFile)) + Send + 'static,
+        E: FnMut(FollowEvent) + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let recording = Arc::new(Mutex::new(None));
+        let thread_stop = stop.clone();
+        let thread_recording = recording.clone();
+        let ingest = std::thread::spawn(move || {
+            ingest_loop(
+                rx,
+                thread_stop,
+                batch_interval,
+                retention,
+                thread_recording,
+                with_file,
+                on_event,
+            );
+        });
+
+        let sender = ChunkSender {
+            tx,
+            stop: stop.clone(),
+        };
+        (
+            LiveSource {
+                stop,
+                ingest: Some(ingest),
+                recording,
+            },
+            sender,
+        )
+    }
+
+    /// Write the captured buffer to `path` and keep appending incoming data until stopped
+    /// `file` must be the live view, borrowed so no chunk can land between the snapshot and the first append
+    pub fn start_recording<P: AsRef<Path>>(
+        &self,
+        path: P,
+        file: &LogFile,
+    ) -> std::io::Result<RecordingSummary> {
+        let mut recording = Recording {
+            path: path.as_ref().to_path_buf(),
+            file: File::create(path)?,
+            bytes_written: 0,
+        };
+        recording.write(file.data())?;
+        let summary = recording.summary();
+        *self.recording.lock() = Some(recording);
+        Ok(summary)
+    }
+
+    /// Stop recording, returning what was written
+    pub fn stop_recording(&self) -> Option<RecordingSummary> {
+        self.recording.lock().take().map(|r| r.summary())
+    }
+
+    pub fn recording(&self) -> Option<RecordingSummary> {
+        self.recording.lock().as_ref().map(|r| r.summary())
+    }
+
+    /// Stop ingesting; producers notice on their next chunk and exit
+    pub fn stop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(handle) = self.ingest.take() {
+            handle.join().ok();
+        }
+    }
+}
+
+impl Drop for LiveSource {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn ingest_loop<W, E>(
+    rx: Receiver<Vec<u8>>,
+    stop: Arc<AtomicBool>,
+    batch_interval: Duration,
+    mut retention: Retention,
+    recording: Arc<Mutex<Option<Recording>>>,
+    mut with_file: W,
+    mut on_event: E,
+) where
+    W: FnMut(&mut dyn FnMut(&mut LogFile)),
+    E: FnMut(FollowEvent),
+{
+    const IDLE_WAKEUP: Duration = Duration::from_millis(250);
+    let mut batcher = AppendBatcher::new(batch_interval);
+
+    while !stop.load(Ordering::SeqCst) {
+        let wait = batcher
+            .time_until_due(Instant::now())
+            .map_or(IDLE_WAKEUP, |due| due.min(IDLE_WAKEUP));
+        match rx.recv_timeout(wait) {
+            Ok(chunk) => {
+                let mut appended = None;
+                let mut record_result = Ok(());
+                with_file(&mut |file| {
+                    appended = file.append_bytes(&chunk);
+                    retention.record(Instant::now(), file.line_count());
+                    // Written under the view's lock so it stays in step with `start_recording`
+                    let mut recording = recording.lock();
+                    if let Some(active) = recording.as_mut() {
+                        record_result = active.write(&chunk);
+                        if record_result.is_err() {
+                            *recording = None;
+                        }
+                    }
+                });
+                if let Err(err) = record_result {
+                    on_event(FollowEvent::Error(format!("Recording stopped: {}", err)));
+                }
+                if let Some(appended) = appended {
+                    batcher.push(appended.lines);
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        let now = Instant::now();
+        if let Some(range) = batcher.take_due(now) {
+            on_event(FollowEvent::Appended(range));
+        }
+
+        // Evict only once pending ranges are surfaced so their line numbers stay valid
+        if retention.is_unbounded() || batcher.time_until_due(now).is_some() {
+            continue;
+        }
+        let mut result = Ok(0);
+        with_file(&mut |file| result = retention.enforce(file, now));
+        match result {
+            Ok(0) => {}
+            Ok(removed) => on_event(FollowEvent::Evicted(removed)),
+            Err(err) => on_event(FollowEvent::Error(format!(
+                "Failed to spill evicted lines: {}",
+                err
+            ))),
+        }
+    }
+
+    if let Some(range) = batcher.take() {
+        on_event(FollowEvent::Appended(range));
+    }
+}
+
+/// Start a producer thread reading a named pipe or character device into `sender`
+/// With a baud rate the device is opened as a serial port; errors are reported through `on_error`
+pub fn spawn_stream_reader<F>(
+    path: PathBuf,
+    kind: StreamKind,
+    options: &StreamOptions,
+    sender: ChunkSender,
+    on_error: F,
+) where
+    F: Fn(String) + Send + 'static,
+{
+    let baud_rate = options.baud_rate;
+    std::thread::spawn(move || {
+        let result = match baud_rate {
+            Some(baud) => read_serial(&path, baud, &sender),
+            None => read_stream(&path, kind, &sender),
+        };
+        if let Err(err) = result {
+            if !sender.is_stopped() {
+                on_error(format!("{}: {}", path.display(), err));
+            }
+        }
+    });
+}
+
+fn read_stream(path: &Path, kind: StreamKind, sender: &ChunkSender) -> std::io::Result<()> {
+    let mut buf = vec![0u8; READ_CHUNK];
+    while !sender.is_stopped() {
+        // Opening a FIFO blocks until a writer connects; it is reopened whenever the writer leaves
+        let mut file = std::fs::File::open(path)?;
+        loop {
+            match file.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => {
+                    if !sender.send(buf[..n].to_vec()) {
+                        return Ok(());
+                    }
+                }
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+        if kind != StreamKind::Fifo {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn read_serial(path: &Path, baud_rate: u32, sender: &ChunkSender) -> std::io::Result<()> {
+    let mut port = serialport::new(path.to_string_lossy(), baud_rate)
+        .timeout(Duration::from_millis(200))
+        .open()
+        .map_err(std::io::Error::from)?;
+
+    let mut buf = vec![0u8; READ_CHUNK];
+    while !sender.is_stopped() {
+        match port.read(&mut buf) {
+            Ok(0) => continue,
+            Ok(n) => {
+                if !sender.send(buf[..n].to_vec()) {
+                    break;
+                }
+            }
+            Err(err) if matches!(err.kind(), ErrorKind::TimedOut | ErrorKind::Interrupted) => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestSource = (
+        LiveSource,
+        ChunkSender,
+        Arc<Mutex<LogFile>>,
+        Arc<Mutex<Vec<FollowEvent>>>,
+    );
+
+    fn start_test_source(retention: RetentionOptions) -> TestSource {
+        let log_file = Arc::new(Mutex::new(LogFile::live("test")));
+        let events = Arc::new(Mutex::new(Vec::new()));
+
+        let view = log_file.clone();
+        let sink = events.clone();
+        let (source, sender) = LiveSource::start(
+            Duration::from_millis(10),
+            Retention::new(retention).unwrap(),
+            move |f| f(&mut view.lock()),
+            move |event| sink.lock().push(event),
+        );
+        (source, sender, log_file, events)
+    }
+
+    #[test]
+    fn test_live_source_ingests_chunks() {
+        let (mut source, sender, log_file, events) = start_test_source(RetentionOptions::default());
+
+        assert!(sender.send(b"one\ntwo\n".to_vec()));
+        assert!(sender.send(b"three\n".to_vec()));
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while log_file.lock().line_count() < 3 && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        source.stop();
+        assert!(!sender.send(b"late\n".to_vec()));
+
+        assert_eq!(log_file.lock().line_count(), 3);
+        let covered = events.lock().iter().fold(0..0, |acc, event| match event {
+            FollowEvent::Appended(range) => acc.start.min(range.start)..acc.end.max(range.end),
+            _ => acc,
+        });
+        assert_eq!(covered, 0..3);
+    }
+
+    #[test]
+    fn test_retention_evicts_and_spills() {
+        let dir = tempfile::tempdir().unwrap();
+        let spill_path = dir.path().join("spill.log");
+        let (mut source, sender, log_file, events) = start_test_source(RetentionOptions {
+            max_lines: Some(2),
+            spill_path: Some(spill_path.to_string_lossy().to_string()),
+            ..Default::default()
+        });
+
+        assert!(sender.send(b"a\nb\nc\nd\n".to_vec()));
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !events.lock().contains(&FollowEvent::Evicted(2)) && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        source.stop();
+
+        assert_eq!(log_file.lock().data(), b"c\nd\n");
+        assert_eq!(std::fs::read(&spill_path).unwrap(), b"a\nb\n");
+    }
+
+    #[test]
+    fn test_recording_captures_buffer_and_new_data() {
+        let dir = tempfile::tempdir().unwrap();
+        let record_path = dir.path().join("session.log");
+        let (mut source, sender, log_file, _events) =
+            start_test_source(RetentionOptions::default());
+
+        assert!(sender.send(b"before\n".to_vec()));
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while log_file.lock().line_count() < 1 && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        source
+            .start_recording(&record_path, &log_file.lock())
+            .unwrap();
+
+        assert!(sender.send(b"after\n".to_vec()));
+        while log_file.lock().line_count() < 2 && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let summary = source.stop_recording().unwrap();
+        assert!(sender.send(b"ignored\n".to_vec()));
+        source.stop();
+
+        assert_eq!(summary.bytes_written, 13);
+        assert_eq!(std::fs::read(&record_path).unwrap(), b"before\nafter\n");
+    }
+
+    #[test]
+    fn test_retention_by_age() {
+        let mut retention = Retention::new(RetentionOptions {
+            max_age_secs: Some(60),
+            ..Default::default()
+        })
+        .unwrap();
+        let mut file = LogFile::live("test");
+        let start = Instant::now();
+
+        file.append_bytes(b"old\n");
+        retention.record(start, file.line_count());
+        file.append_bytes(b"new\n");
+        retention.record(start + Duration::from_secs(30), file.line_count());
+
+        assert_eq!(
+            retention
+                .enforce(&mut file, start + Duration::from_secs(45))
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            retention
+                .enforce(&mut file, start + Duration::from_secs(75))
+                .unwrap(),
+            1
+        );
+        assert_eq!(file.data(), b"new\n");
+        assert_eq!(
+            retention.arrivals.front(),
+            Some(&(start + Duration::from_secs(30), 1))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_stream_kind() {
+        let dir = tempfile::tempdir().unwrap();
+        let regular = dir.path().join("regular.log");
+        std::fs::write(&regular, "x\n").unwrap();
+        assert_eq!(stream_kind(&regular), None);
+        assert_eq!(stream_kind("/dev/null"), Some(StreamKind::CharDevice));
+
+        let fifo = dir.path().join("pipe");
+        let created = std::process::Command::new("mkfifo").arg(&fifo).status();
+        if created.is_ok_and(|status| status.success()) {
+            assert_eq!(stream_kind(&fifo), Some(StreamKind::Fifo));
+        }
+    }
+}
@@ -0,0 +1,320 @@
+use crate::analysis::normalize_line;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Interval at which statistics are pushed while lines keep arriving
+const PUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Recent seconds the ingest rate is measured over, so it follows bursts
+const RATE_SECONDS: u64 = 10;
+
+/// Distinct templates counted per second; rarer ones beyond it go uncounted
+const MAX_TEMPLATES_PER_SECOND: usize = 1_000;
+
+/// Levels at or above error, counted into the error rate
+const ERROR_LEVELS: [&str; 2] = ["error", "fatal"];
+
+/// How live statistics are gathered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LiveStatsOptions {
+    /// Minutes the level rates and top templates cover
+    #[serde(default = "default_window_minutes")]
+    pub window_minutes: u64,
+    #[serde(default = "default_top_templates")]
+    pub top_templates: usize,
+}
+
+fn default_window_minutes() -> u64 {
+    5
+}
+
+fn default_top_templates() -> usize {
+    5
+}
+
+impl Default for LiveStatsOptions {
+    fn default() -> Self {
+        LiveStatsOptions {
+            window_minutes: default_window_minutes(),
+            top_templates: default_top_templates(),
+        }
+    }
+}
+
+/// A line template and how often it occurred in the window
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TemplateCount {
+    /// The line with numbers and ids masked, as `normalize_line` does
+    pub template: String,
+    pub count: u64,
+}
+
+/// Rolling statistics of the lines appended while following
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LiveStatsSnapshot {
+    pub window_minutes: u64,
+    /// Lines per second over the last few seconds
+    pub lines_per_sec: f64,
+    pub bytes_per_sec: f64,
+    /// Lines appended within the window
+    pub window_lines: u64,
+    /// Lines per second of each level seen in the window, lowercase
+    pub level_rates: BTreeMap<String, f64>,
+    /// Share of the window's lines at error level or above
+    pub error_rate: f64,
+    /// Most frequent templates in the window
+    pub top_templates: Vec<TemplateCount>,
+}
+
+/// Counts of one second of appended lines
+#[derive(Debug, Default)]
+struct Second {
+    /// Seconds since statistics started
+    at: u64,
+    lines: u64,
+    bytes: u64,
+    levels: HashMap<&'static str, u64>,
+    templates: HashMap<String, u64>,
+}
+
+/// Per-second counts of appended lines over a rolling window
+#[derive(Debug)]
+pub struct LiveStats {
+    options: LiveStatsOptions,
+    started: Option<Instant>,
+    seconds: VecDeque<Second>,
+    /// When a snapshot was last pushed
+    pushed: Option<Instant>,
+}
+
+impl Default for LiveStats {
+    fn default() -> Self {
+        LiveStats::new(LiveStatsOptions::default())
+    }
+}
+
+impl LiveStats {
+    pub fn new(options: LiveStatsOptions) -> Self {
+        LiveStats {
+            options,
+            started: None,
+            seconds: VecDeque::new(),
+            pushed: None,
+        }
+    }
+
+    /// Change the options, keeping what was counted
+    pub fn set_options(&mut self, options: LiveStatsOptions) {
+        self.options = options;
+    }
+
+    /// Forget what was counted, e.g. when following another file
+    pub fn reset(&mut self) {
+        self.started = None;
+        self.seconds.clear();
+        self.pushed = None;
+    }
+
+    fn window_seconds(&self) -> u64 {
+        self.options.window_minutes.max(1) * 60
+    }
+
+    fn elapsed(&mut self, now: Instant) -> u64 {
+        let started = *self.started.get_or_insert(now);
+        now.saturating_duration_since(started).as_secs()
+    }
+
+    /// Count lines appended at `now`
+    pub fn record<'a>(&mut self, now: Instant, lines: impl IntoIterator<Item = &'a str>) {
+        let at = self.elapsed(now);
+        self.expire(at);
+        if self.seconds.back().is_none_or(|s| s.at != at) {
+            self.seconds.push_back(Second {
+                at,
+                ..Second::default()
+            });
+        }
+        let Some(second) = self.seconds.back_mut() else {
+            return;
+        };
+        for line in lines {
+            second.lines += 1;
+            second.bytes += line.len() as u64;
+            if let Some(level) = line_level(line) {
+                *second.levels.entry(level).or_default() += 1;
+            }
+            let template = normalize_line(line);
+            if let Some(count) = second.templates.get_mut(&template) {
+                *count += 1;
+            } else if second.templates.len() < MAX_TEMPLATES_PER_SECOND {
+                second.templates.insert(template, 1);
+            }
+        }
+    }
+
+    /// Drop seconds that fell out of the window
+    fn expire(&mut self, at: u64) {
+        let window = self.window_seconds();
+        while self.seconds.front().is_some_and(|s| s.at + window <= at) {
+            self.seconds.pop_front();
+        }
+    }
+
+    /// A snapshot to push, unless one was pushed less than a second ago
+    pub fn snapshot_due(&mut self, now: Instant) -> Option<LiveStatsSnapshot> {
+        if self
+            .pushed
+            .is_some_and(|pushed| now.saturating_duration_since(pushed) < PUSH_INTERVAL)
+        {
+            return None;
+        }
+        self.pushed = Some(now);
+        Some(self.snapshot(now))
+    }
+
+    /// Lines at error level or above in the window, and all lines in it
+    pub fn window_errors(&mut self, now: Instant) -> (u64, u64) {
+        let at = self.elapsed(now);
+        self.expire(at);
+        self.seconds.iter().fold((0, 0), |(errors, lines), s| {
+            let second_errors: u64 = ERROR_LEVELS
+                .iter()
+                .filter_map(|level| s.levels.get(level))
+                .sum();
+            (errors + second_errors, lines + s.lines)
+        })
+    }
+
+    pub fn snapshot(&mut self, now: Instant) -> LiveStatsSnapshot {
+        let at = self.elapsed(now);
+        self.expire(at);
+        // Rates are over the time actually covered, so they aren't diluted right after a start
+        let covered = |seconds: u64| (at + 1).min(seconds) as f64;
+        let recent = self.seconds.iter().filter(|s| s.at + RATE_SECONDS > at);
+        let (recent_lines, recent_bytes) = recent.fold((0, 0), |(lines, bytes), s| {
+            (lines + s.lines, bytes + s.bytes)
+        });
+
+        let window = self.window_seconds();
+        let mut levels: BTreeMap<String, u64> = BTreeMap::new();
+        let mut templates: HashMap<&str, u64> = HashMap::new();
+        let mut window_lines = 0;
+        for second in &self.seconds {
+            window_lines += second.lines;
+            for (level, count) in &second.levels {
+                *levels.entry(level.to_string()).or_default() += count;
+            }
+            for (template, count) in &second.templates {
+                *templates.entry(template).or_default() += count;
+            }
+        }
+        let errors: u64 = ERROR_LEVELS
+            .iter()
+            .filter_map(|level| levels.get(*level))
+            .sum();
+        let mut top_templates: Vec<TemplateCount> = templates
+            .into_iter()
+            .map(|(template, count)| TemplateCount {
+                template: template.to_string(),
+                count,
+            })
+            .collect();
+        top_templates.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then_with(|| a.template.cmp(&b.template))
+        });
+        top_templates.truncate(self.options.top_templates);
+
+        LiveStatsSnapshot {
+            window_minutes: self.options.window_minutes,
+            lines_per_sec: recent_lines as f64 / covered(RATE_SECONDS),
+            bytes_per_sec: recent_bytes as f64 / covered(RATE_SECONDS),
+            window_lines,
+            level_rates: levels
+                .into_iter()
+                .map(|(level, count)| (level, count as f64 / covered(window)))
+                .collect(),
+            error_rate: match window_lines {
+                0 => 0.0,
+                lines => errors as f64 / lines as f64,
+            },
+            top_templates,
+        }
+    }
+}
+
+/// Level named by a line: the first level word in it, such as `ERROR`, `[warn]` or
+/// `"level":"info"`, ignoring case
+pub fn line_level(line: &str) -> Option<&'static str> {
+    line.split(|c: char| !c.is_ascii_alphabetic())
+        .take(32)
+        .find_map(word_level)
+}
+
+/// Level a word names, whatever its case
+pub fn word_level(word: &str) -> Option<&'static str> {
+    match word.to_ascii_lowercase().as_str() {
+        "fatal" | "critical" | "crit" | "panic" | "emerg" => Some("fatal"),
+        "error" | "err" => Some("error"),
+        "warn" | "warning" => Some("warn"),
+        "info" | "notice" => Some("info"),
+        "debug" => Some("debug"),
+        "trace" => Some("trace"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_level() {
+        assert_eq!(
+            line_level("2024-01-01 12:00:00 ERROR db down"),
+            Some("error")
+        );
+        assert_eq!(line_level("[warn] disk 91% full"), Some("warn"));
+        assert_eq!(
+            line_level(r#"{"level":"INFO","msg":"started"}"#),
+            Some("info")
+        );
+        assert_eq!(line_level("GET /errors 200"), None);
+    }
+
+    #[test]
+    fn test_rolling_window() {
+        let start = Instant::now();
+        let mut stats = LiveStats::new(LiveStatsOptions {
+            window_minutes: 1,
+            top_templates: 2,
+        });
+        stats.record(start, ["INFO user 17 logged in", "ERROR timeout after 30s"]);
+        stats.record(
+            start + Duration::from_secs(1),
+            ["INFO user 18 logged in", "INFO user 19 logged in"],
+        );
+
+        let snapshot = stats.snapshot(start + Duration::from_secs(1));
+        assert_eq!(snapshot.window_lines, 4);
+        assert_eq!(snapshot.lines_per_sec, 2.0);
+        assert_eq!(snapshot.error_rate, 0.25);
+        assert_eq!(snapshot.level_rates.get("info"), Some(&1.5));
+        assert_eq!(stats.window_errors(start + Duration::from_secs(1)), (1, 4));
+        assert_eq!(
+            snapshot.top_templates[0],
+            TemplateCount {
+                template: "INFO user ? logged in".to_string(),
+                count: 3,
+            }
+        );
+
+        // A minute later the first second has left the window
+        let snapshot = stats.snapshot(start + Duration::from_secs(60));
+        assert_eq!(snapshot.window_lines, 2);
+        assert_eq!(snapshot.error_rate, 0.0);
+        assert_eq!(snapshot.lines_per_sec, 0.0);
+    }
+}
@@ -0,0 +1,650 @@
+use crate::analysis::{preview, CHUNK_LINES};
+use crate::indexer::LogFile;
+use crate::parsers::json::{classify_line, LineFormat};
+use crate::regex_cache;
+use crate::timeline::{TimeRange, TimelineError};
+use crate::timestamp::parse_timestamp_bytes;
+use rayon::prelude::*;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Least number of points on either side of a change point
+const MIN_SEGMENT: usize = 5;
+
+/// How far a segment's mean must move, in noise deviations scaled by segment size, to count
+/// as a change
+const CHANGE_THRESHOLD: f64 = 4.0;
+
+const HOUR_MILLIS: f64 = 3_600_000.0;
+
+/// Errors that can occur extracting values from lines
+#[derive(Debug, Error)]
+pub enum MetricError {
+    #[error("Invalid pattern: {0}")]
+    Pattern(#[from] regex::Error),
+    #[error("JSON paths can't be empty")]
+    EmptyPath,
+    #[error(transparent)]
+    Timeline(#[from] TimelineError),
+}
+
+/// Where a value is read from in a line
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Extractor {
+    /// A regex: the value is the group named `value`, else the first group, else the match
+    Regex { pattern: String },
+    /// A dotted path into a JSON line, such as `queue.depth`; numbers index into arrays
+    Json { path: String },
+}
+
+impl Extractor {
+    pub fn compile(&self) -> Result<CompiledExtractor, MetricError> {
+        match self {
+            Extractor::Regex { pattern } => {
+                Ok(CompiledExtractor::Regex(regex_cache::regex(pattern)?))
+            }
+            Extractor::Json { path } if path.trim().is_empty() => Err(MetricError::EmptyPath),
+            Extractor::Json { path } => Ok(CompiledExtractor::Json(
+                path.split('.').map(str::to_string).collect(),
+            )),
+        }
+    }
+}
+
+/// An extractor ready to apply to lines
+#[derive(Debug, Clone)]
+pub enum CompiledExtractor {
+    Regex(Arc<Regex>),
+    Json(Vec<String>),
+}
+
+impl CompiledExtractor {
+    /// The value in a line, as text
+    pub fn extract(&self, line: &str) -> Option<String> {
+        match self {
+            CompiledExtractor::Regex(re) => {
+                let caps = re.captures(line)?;
+                let value = caps
+                    .name("value")
+                    .or_else(|| caps.get(1))
+                    .or_else(|| caps.get(0))?;
+                Some(value.as_str().to_string())
+            }
+            CompiledExtractor::Json(path) => {
+                if classify_line(line) != LineFormat::Json {
+                    return None;
+                }
+                let root: JsonValue = serde_json::from_str(line.trim()).ok()?;
+                let value = path.iter().try_fold(&root, |value, key| match value {
+                    JsonValue::Array(items) => items.get(key.parse::<usize>().ok()?),
+                    _ => value.get(key),
+                })?;
+                match value {
+                    JsonValue::Null => None,
+                    JsonValue::String(s) => Some(s.clone()),
+                    other => Some(other.to_string()),
+                }
+            }
+        }
+    }
+}
+
+/// Split off the number a value starts with
+fn split_number(text: &str) -> (&str, &str) {
+    let text = text.trim_start();
+    let end = text
+        .char_indices()
+        .find(|&(i, c)| !(c.is_ascii_digit() || c == '.' || (i == 0 && matches!(c, '-' | '+'))))
+        .map_or(text.len(), |(i, _)| i);
+    text.split_at(end)
+}
+
+/// The number a value starts with, so units such as `MB` after it are ignored
+pub fn leading_number(text: &str) -> Option<f64> {
+    split_number(text).0.parse().ok()
+}
+
+/// Lines of a file, in order, with their timestamps and the value extracted from them
+/// Lines the extractor finds nothing in, or that lie outside `range`, are left out
+pub(crate) fn extract_values<T: Send>(
+    file: &LogFile,
+    extractor: &CompiledExtractor,
+    range: Option<TimeRange>,
+    convert: impl Fn(&str, String) -> Option<T> + Sync,
+) -> Vec<(u64, Option<i64>, T)> {
+    let line_count = file.line_count();
+    (0..line_count.div_ceil(CHUNK_LINES))
+        .into_par_iter()
+        .flat_map_iter(|chunk| {
+            let end = ((chunk + 1) * CHUNK_LINES).min(line_count);
+            let mut values = Vec::new();
+            for line in chunk * CHUNK_LINES..end {
+                let Some(bytes) = file.line_bytes(line) else {
+                    continue;
+                };
+                let time = parse_timestamp_bytes(bytes);
+                if let Some(range) = range {
+                    if !time.is_some_and(|time| range.contains(time)) {
+                        continue;
+                    }
+                }
+                let text = String::from_utf8_lossy(bytes);
+                let Some(value) = extractor
+                    .extract(&text)
+                    .and_then(|value| convert(&text, value))
+                else {
+                    continue;
+                };
+                values.push((line, time, value));
+            }
+            values
+        })
+        .collect()
+}
+
+fn default_max_points() -> usize {
+    2_000
+}
+
+fn default_max_change_points() -> usize {
+    5
+}
+
+/// Which metric to extract
+#[derive(Debug, Clone, Deserialize)]
+pub struct MetricOptions {
+    pub extractor: Extractor,
+    /// Time looked at; None looks at the whole file, lines without timestamps included
+    #[serde(default)]
+    pub range: Option<TimeRange>,
+    /// Points returned; longer series are thinned evenly, while statistics use every point
+    #[serde(default = "default_max_points")]
+    pub max_points: usize,
+    #[serde(default = "default_max_change_points")]
+    pub max_change_points: usize,
+}
+
+/// A value of the metric
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MetricPoint {
+    /// 0-based index of the line it came from
+    pub line: u64,
+    pub time: Option<i64>,
+    pub value: f64,
+}
+
+/// Where the metric's level shifted
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChangePoint {
+    /// First point of the new level
+    pub line: u64,
+    pub time: Option<i64>,
+    pub mean_before: f64,
+    pub mean_after: f64,
+}
+
+/// Summary of a metric's values
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TrendStats {
+    pub count: usize,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub first: f64,
+    pub last: f64,
+    /// Least-squares change per hour, when every point has a timestamp
+    pub slope_per_hour: Option<f64>,
+    /// In order
+    pub change_points: Vec<ChangePoint>,
+}
+
+/// A metric recovered from log lines
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricSeries {
+    pub points: Vec<MetricPoint>,
+    /// Whether `points` was thinned to `max_points`
+    pub sampled: bool,
+    /// None when no line held a number
+    pub stats: Option<TrendStats>,
+}
+
+/// Extract a numeric metric from every line that holds one, with its trend
+pub fn extract_metric(
+    file: &LogFile,
+    options: &MetricOptions,
+) -> Result<MetricSeries, MetricError> {
+    if let Some(range) = &options.range {
+        range.validate()?;
+    }
+    let extractor = options.extractor.compile()?;
+    let points: Vec<MetricPoint> = extract_values(file, &extractor, options.range, |_, value| {
+        leading_number(&value).filter(|n| n.is_finite())
+    })
+    .into_iter()
+    .map(|(line, time, value)| MetricPoint { line, time, value })
+    .collect();
+
+    let stats = trend_stats(&points, options.max_change_points);
+    let max_points = options.max_points.max(2);
+    let sampled = points.len() > max_points;
+    let points = if sampled {
+        // Evenly spaced, keeping the first and last
+        let last = points.len() - 1;
+        (0..max_points)
+            .map(|i| points[i * last / (max_points - 1)].clone())
+            .collect()
+    } else {
+        points
+    };
+    Ok(MetricSeries {
+        points,
+        sampled,
+        stats,
+    })
+}
+
+fn trend_stats(points: &[MetricPoint], max_change_points: usize) -> Option<TrendStats> {
+    let (first, last) = (points.first()?, points.last()?);
+    let values: Vec<f64> = points.iter().map(|p| p.value).collect();
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+
+    let times: Option<Vec<f64>> = points
+        .iter()
+        .map(|p| {
+            p.time
+                .map(|t| (t - first.time.unwrap_or(0)) as f64 / HOUR_MILLIS)
+        })
+        .collect();
+    let slope_per_hour = times.and_then(|times| {
+        let mean_t = times.iter().sum::<f64>() / n;
+        let (mut cov, mut var) = (0.0, 0.0);
+        for (t, v) in times.iter().zip(&values) {
+            cov += (t - mean_t) * (v - mean);
+            var += (t - mean_t).powi(2);
+        }
+        (var > 0.0).then(|| cov / var)
+    });
+
+    let change_points = change_points(&values, max_change_points)
+        .into_iter()
+        .map(|(index, mean_before, mean_after)| ChangePoint {
+            line: points[index].line,
+            time: points[index].time,
+            mean_before,
+            mean_after,
+        })
+        .collect();
+
+    Some(TrendStats {
+        count: values.len(),
+        min: values.iter().copied().fold(f64::INFINITY, f64::min),
+        max: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        mean,
+        first: first.value,
+        last: last.value,
+        slope_per_hour,
+        change_points,
+    })
+}
+
+/// Shifts in the mean found by binary segmentation: the segment split that moves the mean
+/// most, against the noise between neighbouring points, is taken while it stands out
+/// Returns the index starting each new level with the means of the segments either side
+fn change_points(values: &[f64], max: usize) -> Vec<(usize, f64, f64)> {
+    if values.len() < 2 * MIN_SEGMENT {
+        return Vec::new();
+    }
+    // Noise from the median step between neighbours, which level shifts barely move
+    let mut steps: Vec<f64> = values.windows(2).map(|w| (w[1] - w[0]).abs()).collect();
+    steps.sort_unstable_by(f64::total_cmp);
+    let sigma = steps[steps.len() / 2] / (0.6745 * std::f64::consts::SQRT_2);
+    let mut prefix = vec![0.0];
+    for value in values {
+        prefix.push(prefix.last().unwrap() + value);
+    }
+    let mean = |start: usize, end: usize| (prefix[end] - prefix[start]) / (end - start) as f64;
+
+    let mut bounds = vec![0, values.len()];
+    while bounds.len() - 2 < max {
+        let best = bounds
+            .windows(2)
+            .flat_map(|w| {
+                (w[0] + MIN_SEGMENT..=w[1].saturating_sub(MIN_SEGMENT)).map(|k| (w[0], k, w[1]))
+            })
+            .map(|(start, k, end)| {
+                let (left, right) = ((k - start) as f64, (end - k) as f64);
+                let shift = (mean(start, k) - mean(k, end)).abs();
+                (shift * (left * right / (left + right)).sqrt(), k)
+            })
+            .max_by(|a, b| a.0.total_cmp(&b.0));
+        match best {
+            Some((score, k)) if score > CHANGE_THRESHOLD * sigma => {
+                bounds.insert(bounds.partition_point(|&b| b < k), k);
+            }
+            _ => break,
+        }
+    }
+    bounds
+        .windows(3)
+        .map(|w| (w[1], mean(w[0], w[1]), mean(w[1], w[2])))
+        .collect()
+}
+
+/// Unit of a duration, assumed for numbers that don't state one
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DurationUnit {
+    Ns,
+    Us,
+    #[default]
+    Ms,
+    S,
+}
+
+impl DurationUnit {
+    fn millis(self) -> f64 {
+        match self {
+            DurationUnit::Ns => 1e-6,
+            DurationUnit::Us => 1e-3,
+            DurationUnit::Ms => 1.0,
+            DurationUnit::S => 1e3,
+        }
+    }
+}
+
+/// A duration such as `250ms`, `1.5s` or `2m` in milliseconds; bare numbers are in `unit`
+pub fn parse_duration_ms(text: &str, unit: DurationUnit) -> Option<f64> {
+    let (number, rest) = split_number(text);
+    let number: f64 = number.parse().ok()?;
+    let suffix = rest.trim_start().split(|c: char| !c.is_alphabetic()).next();
+    let millis = match suffix.unwrap_or_default().to_ascii_lowercase().as_str() {
+        "" => unit.millis(),
+        "ns" => 1e-6,
+        "us" | "µs" | "μs" => 1e-3,
+        "ms" => 1.0,
+        "s" | "sec" | "secs" => 1e3,
+        "m" | "min" | "mins" => 60e3,
+        "h" | "hr" | "hrs" => 3_600e3,
+        _ => return None,
+    };
+    Some(number * millis).filter(|ms| ms.is_finite() && *ms >= 0.0)
+}
+
+fn default_top_dimensions() -> usize {
+    50
+}
+
+fn default_slowest() -> usize {
+    20
+}
+
+/// How a latency breakdown reads its lines
+#[derive(Debug, Clone, Deserialize)]
+pub struct LatencyOptions {
+    /// Where the duration is, such as `took (\d+)ms` or `duration_ms`
+    pub duration: Extractor,
+    /// Where the value latencies are grouped by is, such as an endpoint, customer or host
+    pub dimension: Extractor,
+    #[serde(default)]
+    pub unit: DurationUnit,
+    #[serde(default)]
+    pub range: Option<TimeRange>,
+    /// Dimension values listed, slowest at the 95th percentile first
+    #[serde(default = "default_top_dimensions")]
+    pub top_dimensions: usize,
+    /// Slowest lines listed
+    #[serde(default = "default_slowest")]
+    pub slowest: usize,
+}
+
+/// Percentiles of a set of durations, in milliseconds
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LatencyStats {
+    pub count: usize,
+    pub mean: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub p95: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+impl LatencyStats {
+    /// Stats of durations sorted ascending; None when there are none
+    fn of_sorted(durations: &[f64]) -> Option<Self> {
+        let max = *durations.last()?;
+        let rank = |p: f64| durations[((p * durations.len() as f64).ceil() as usize).max(1) - 1];
+        Some(LatencyStats {
+            count: durations.len(),
+            mean: durations.iter().sum::<f64>() / durations.len() as f64,
+            p50: rank(0.5),
+            p90: rank(0.9),
+            p95: rank(0.95),
+            p99: rank(0.99),
+            max,
+        })
+    }
+}
+
+/// Latency of the lines sharing one dimension value
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DimensionLatency {
+    pub value: String,
+    pub stats: LatencyStats,
+}
+
+/// One of the slowest lines
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SlowLine {
+    /// 0-based line index
+    pub line: u64,
+    pub time: Option<i64>,
+    pub duration_ms: f64,
+    pub dimension: Option<String>,
+    pub text: String,
+}
+
+/// Latency percentiles overall and per dimension value, with the slowest lines
+#[derive(Debug, Clone, Serialize)]
+pub struct LatencyReport {
+    /// None when no line held a duration
+    pub overall: Option<LatencyStats>,
+    pub dimensions: Vec<DimensionLatency>,
+    /// Distinct dimension values, including those beyond `top_dimensions`
+    pub dimension_count: usize,
+    /// Lines with a duration but no dimension value, counted only overall
+    pub missing_dimension: usize,
+    /// Slowest first
+    pub slowest: Vec<SlowLine>,
+}
+
+/// Break down the durations lines report by a dimension read from the same lines
+pub fn latency_breakdown(
+    file: &LogFile,
+    options: &LatencyOptions,
+) -> Result<LatencyReport, MetricError> {
+    if let Some(range) = &options.range {
+        range.validate()?;
+    }
+    let duration = options.duration.compile()?;
+    let dimension = options.dimension.compile()?;
+    let mut samples = extract_values(file, &duration, options.range, |text, value| {
+        Some((
+            parse_duration_ms(&value, options.unit)?,
+            dimension.extract(text),
+        ))
+    });
+
+    let mut by_dimension: HashMap<&str, Vec<f64>> = HashMap::new();
+    for (_, _, (ms, value)) in &samples {
+        if let Some(value) = value {
+            by_dimension.entry(value.as_str()).or_default().push(*ms);
+        }
+    }
+    let missing_dimension = samples.iter().filter(|(_, _, (_, v))| v.is_none()).count();
+    let dimension_count = by_dimension.len();
+    let mut dimensions: Vec<DimensionLatency> = by_dimension
+        .into_iter()
+        .filter_map(|(value, mut durations)| {
+            durations.sort_unstable_by(f64::total_cmp);
+            Some(DimensionLatency {
+                value: value.to_string(),
+                stats: LatencyStats::of_sorted(&durations)?,
+            })
+        })
+        .collect();
+    dimensions.sort_by(|a, b| {
+        b.stats
+            .p95
+            .total_cmp(&a.stats.p95)
+            .then_with(|| a.value.cmp(&b.value))
+    });
+    dimensions.truncate(options.top_dimensions);
+
+    let mut overall: Vec<f64> = samples.iter().map(|(_, _, (ms, _))| *ms).collect();
+    overall.sort_unstable_by(f64::total_cmp);
+    let overall = LatencyStats::of_sorted(&overall);
+
+    samples.sort_by(|(a_line, _, (a, _)), (b_line, _, (b, _))| {
+        b.total_cmp(a).then(a_line.cmp(b_line))
+    });
+    let slowest = samples
+        .into_iter()
+        .take(options.slowest)
+        .map(|(line, time, (duration_ms, dimension))| SlowLine {
+            line,
+            time,
+            duration_ms,
+            dimension,
+            text: file.line_bytes(line).map(preview).unwrap_or_default(),
+        })
+        .collect();
+
+    Ok(LatencyReport {
+        overall,
+        dimensions,
+        dimension_count,
+        missing_dimension,
+        slowest,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexer::open_test_file;
+
+    #[test]
+    fn test_extractors() {
+        let json = Extractor::Json {
+            path: "queue.depths.1".to_string(),
+        }
+        .compile()
+        .unwrap();
+        assert_eq!(
+            json.extract(r#"{"queue": {"depths": [3, 7.5]}}"#)
+                .as_deref(),
+            Some("7.5")
+        );
+        assert_eq!(json.extract("queue depth 7"), None);
+
+        let regex = Extractor::Regex {
+            pattern: r"mem=(\S+) (?P<value>\d+)MB".to_string(),
+        }
+        .compile()
+        .unwrap();
+        assert_eq!(regex.extract("mem=heap 512MB").as_deref(), Some("512"));
+        assert_eq!(leading_number(" 42.5MB"), Some(42.5));
+        assert_eq!(leading_number("-3"), Some(-3.0));
+        assert_eq!(leading_number("MB"), None);
+    }
+
+    #[test]
+    fn test_extract_metric_finds_trend_and_shift() {
+        // Queue depth hovers around 10 for half an hour, then around 50
+        let mut log = String::new();
+        for minute in 0..60 {
+            let depth = (if minute < 30 { 10 } else { 50 }) + minute % 2;
+            log.push_str(&format!(
+                "2024-05-01T10:{:02}:00Z queue depth={}\n",
+                minute, depth
+            ));
+            log.push_str("unrelated line\n");
+        }
+        let (_file, log_file) = open_test_file(&log);
+        let options = MetricOptions {
+            extractor: Extractor::Regex {
+                pattern: r"depth=(\d+)".to_string(),
+            },
+            range: None,
+            max_points: 10,
+            max_change_points: 5,
+        };
+        let series = extract_metric(&log_file, &options).unwrap();
+        assert!(series.sampled);
+        assert_eq!(series.points.len(), 10);
+        assert_eq!(series.points[9].line, 118);
+
+        let stats = series.stats.unwrap();
+        assert_eq!((stats.count, stats.min, stats.max), (60, 10.0, 51.0));
+        assert!(stats.slope_per_hour.unwrap() > 0.0);
+        assert_eq!(
+            stats.change_points,
+            vec![ChangePoint {
+                line: 60,
+                time: Some(1_714_559_400_000),
+                mean_before: 10.5,
+                mean_after: 50.5,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_latency_breakdown() {
+        let (_file, log_file) = open_test_file(
+            "GET /users took 120ms\n\
+             GET /orders took 1.5s\n\
+             GET /users took 80ms\n\
+             healthcheck ok\n\
+             GET /orders took 900ms\n\
+             took 5ms\n",
+        );
+        let options = LatencyOptions {
+            duration: Extractor::Regex {
+                pattern: r"took (\S+)".to_string(),
+            },
+            dimension: Extractor::Regex {
+                pattern: r"GET (\S+)".to_string(),
+            },
+            unit: DurationUnit::Ms,
+            range: None,
+            top_dimensions: 10,
+            slowest: 2,
+        };
+        let report = latency_breakdown(&log_file, &options).unwrap();
+        assert_eq!(report.overall.as_ref().unwrap().count, 5);
+        assert_eq!(report.missing_dimension, 1);
+
+        let dimensions: Vec<_> = report
+            .dimensions
+            .iter()
+            .map(|d| (d.value.as_str(), d.stats.p50, d.stats.max))
+            .collect();
+        assert_eq!(
+            dimensions,
+            [("/orders", 900.0, 1500.0), ("/users", 80.0, 120.0)]
+        );
+        let slowest: Vec<_> = report.slowest.iter().map(|s| s.line).collect();
+        assert_eq!(slowest, [1, 4]);
+
+        assert_eq!(parse_duration_ms("2 m", DurationUnit::Ms), Some(120_000.0));
+        assert_eq!(parse_duration_ms("3", DurationUnit::S), Some(3_000.0));
+        assert_eq!(parse_duration_ms("5 apples", DurationUnit::Ms), None);
+    }
+}
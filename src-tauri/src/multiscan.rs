@@ -0,0 +1,250 @@
+use crate::indexer::{IndexerError, LogFile};
+use crate::regex_cache::{self, RegexFlags};
+use rayon::prelude::*;
+use regex::RegexSet;
+use serde::Serialize;
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Lines per parallel work unit of a scan
+const SCAN_CHUNK_LINES: u64 = 50_000;
+
+/// Engine a multi-pattern scan runs on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScanEngine {
+    /// Every pattern in one pass over the raw bytes; needs the `vectorscan` feature
+    Vectorscan,
+    /// Every line matched against a `RegexSet`
+    RegexSet,
+}
+
+/// Lines one pattern matched
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PatternHits {
+    pub count: u64,
+    /// First matching lines, up to the scan's limit
+    pub lines: Vec<u64>,
+}
+
+/// Result of scanning a file for many patterns at once, one entry per pattern
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanReport {
+    pub engine: ScanEngine,
+    pub hits: Vec<PatternHits>,
+}
+
+/// Patterns compiled for scanning a whole file at once, e.g. a rule set of alert patterns
+pub struct MultiScanner {
+    set: Arc<RegexSet>,
+    #[cfg(feature = "vectorscan")]
+    vectorscan: Option<vectorscan::Database>,
+}
+
+impl MultiScanner {
+    /// Compile patterns; with the `vectorscan` feature they are compiled for vectorscan too,
+    /// and a rule set it can't compile falls back to the `RegexSet`
+    pub fn new<S: AsRef<str>>(patterns: &[S], flags: RegexFlags) -> Result<Self, regex::Error> {
+        Ok(MultiScanner {
+            set: regex_cache::regex_set(patterns, flags)?,
+            #[cfg(feature = "vectorscan")]
+            vectorscan: vectorscan::Database::compile(patterns, flags),
+        })
+    }
+
+    pub fn engine(&self) -> ScanEngine {
+        #[cfg(feature = "vectorscan")]
+        if self.vectorscan.is_some() {
+            return ScanEngine::Vectorscan;
+        }
+        ScanEngine::RegexSet
+    }
+
+    /// Count the lines each pattern matches, keeping the first `max_lines` of each
+    /// Gives up with `Cancelled` once `cancelled` returns true; `progress` is passed the
+    /// lines scanned so far and the total after each chunk
+    pub fn scan(
+        &self,
+        file: &LogFile,
+        max_lines: usize,
+        cancelled: impl Fn() -> bool + Sync,
+        progress: impl Fn(u64, u64) + Sync,
+    ) -> Result<ScanReport, IndexerError> {
+        let total = file.line_count();
+        let scanned = AtomicU64::new(0);
+        let chunks: Vec<u64> = (0..total).step_by(SCAN_CHUNK_LINES as usize).collect();
+        // Chunks come back in file order, so each pattern's lines stay sorted when merged
+        let per_chunk: Vec<Vec<PatternHits>> = chunks
+            .par_iter()
+            .map(|&chunk_start| {
+                let mut hits = vec![PatternHits::default(); self.set.len()];
+                if !cancelled() {
+                    let chunk_end = (chunk_start + SCAN_CHUNK_LINES).min(total);
+                    self.scan_chunk(file, chunk_start..chunk_end, max_lines, &mut hits);
+                    let done = scanned.fetch_add(chunk_end - chunk_start, Ordering::Relaxed);
+                    progress(done + chunk_end - chunk_start, total);
+                }
+                hits
+            })
+            .collect();
+        if cancelled() {
+            return Err(IndexerError::Cancelled);
+        }
+
+        let mut hits = vec![PatternHits::default(); self.set.len()];
+        for chunk in per_chunk {
+            for (merged, found) in hits.iter_mut().zip(chunk) {
+                merged.count += found.count;
+                let room = max_lines.saturating_sub(merged.lines.len());
+                merged.lines.extend(found.lines.into_iter().take(room));
+            }
+        }
+        Ok(ScanReport {
+            engine: self.engine(),
+            hits,
+        })
+    }
+
+    fn scan_chunk(
+        &self,
+        file: &LogFile,
+        lines: Range<u64>,
+        max_lines: usize,
+        hits: &mut [PatternHits],
+    ) {
+        #[cfg(feature = "vectorscan")]
+        if let Some(database) = &self.vectorscan {
+            if database.scan(file, lines.clone(), max_lines, hits) {
+                return;
+            }
+            hits.fill(PatternHits::default());
+        }
+        for line in lines {
+            let Some(text) = file.line_text(line) else {
+                continue;
+            };
+            for pattern in self.set.matches(&text).iter() {
+                record(&mut hits[pattern], line, max_lines);
+            }
+        }
+    }
+}
+
+/// Count a matching line for a pattern, keeping it if there is room
+fn record(hits: &mut PatternHits, line: u64, max_lines: usize) {
+    hits.count += 1;
+    if hits.lines.len() < max_lines {
+        hits.lines.push(line);
+    }
+}
+
+#[cfg(feature = "vectorscan")]
+mod vectorscan {
+    use super::{record, PatternHits};
+    use crate::indexer::LogFile;
+    use crate::regex_cache::RegexFlags;
+    use std::ops::Range;
+    use vectorscan_rs::{BlockDatabase, BlockScanner, Flag, Pattern, Scan};
+
+    /// Patterns compiled for vectorscan's block mode
+    /// Compiled as bytes rather than UTF-8, since vectorscan's UTF-8 mode requires valid
+    /// input and log files often aren't
+    pub struct Database(BlockDatabase);
+
+    impl Database {
+        /// None when vectorscan can't compile a pattern, e.g. one using syntax it lacks
+        pub fn compile<S: AsRef<str>>(patterns: &[S], flags: RegexFlags) -> Option<Self> {
+            let mut flag = Flag::MULTILINE;
+            if flags.case_insensitive {
+                flag |= Flag::CASELESS;
+            }
+            let patterns = patterns
+                .iter()
+                .enumerate()
+                .map(|(id, p)| {
+                    let expression = p.as_ref().as_bytes().to_vec();
+                    Pattern::new(expression, flag, Some(id as u32))
+                })
+                .collect();
+            match BlockDatabase::new(patterns) {
+                Ok(database) => Some(Database(database)),
+                Err(e) => {
+                    tracing::info!(error = %e, "vectorscan rejected the rules, using RegexSet");
+                    None
+                }
+            }
+        }
+
+        /// Scan a range of lines, returning false if vectorscan failed and the lines need
+        /// scanning another way
+        /// Each line is scanned on its own without its terminator, as the `RegexSet` sees it,
+        /// so no match runs across lines and `$` matches before a `\r\n`
+        pub fn scan(
+            &self,
+            file: &LogFile,
+            lines: Range<u64>,
+            max_lines: usize,
+            hits: &mut [PatternHits],
+        ) -> bool {
+            let Ok(mut scanner) = BlockScanner::new(&self.0) else {
+                return false;
+            };
+            // A pattern matching a line more than once counts it once
+            let mut matched = vec![false; hits.len()];
+            for line in lines {
+                let Some(bytes) = file.line_bytes(line) else {
+                    continue;
+                };
+                matched.fill(false);
+                let result = scanner.scan(bytes, |id: u32, _from: u64, _to: u64, _| {
+                    matched[id as usize] = true;
+                    Scan::Continue
+                });
+                if result.is_err() {
+                    return false;
+                }
+                for (id, _) in matched.iter().enumerate().filter(|(_, &m)| m) {
+                    record(&mut hits[id], line, max_lines);
+                }
+            }
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multi_pattern_scan() {
+        let data = "ERROR disk full\nINFO ok\nWARN disk slow\nERROR timeout\n".repeat(30_000);
+        let file = LogFile::from_bytes("app.log", data.into_bytes());
+        let scanner =
+            MultiScanner::new(&["^ERROR", "disk", "nothing"], RegexFlags::default()).unwrap();
+        let report = scanner.scan(&file, 3, || false, |_, _| {}).unwrap();
+
+        assert_eq!(report.hits[0].count, 60_000);
+        assert_eq!(report.hits[0].lines, [0, 3, 4]);
+        assert_eq!(report.hits[1].count, 60_000);
+        assert_eq!(report.hits[1].lines, [0, 2, 4]);
+        assert_eq!(report.hits[2], PatternHits::default());
+
+        assert!(matches!(
+            scanner.scan(&file, 3, || true, |_, _| {}),
+            Err(IndexerError::Cancelled)
+        ));
+    }
+
+    #[test]
+    fn test_scan_matches_within_lines() {
+        let file = LogFile::from_bytes("app.log", b"disk full\r\nok\r\ndisk\r\n".to_vec());
+        let scanner =
+            MultiScanner::new(&["full$", r"full\s+ok", "^disk$"], RegexFlags::default()).unwrap();
+        let report = scanner.scan(&file, 10, || false, |_, _| {}).unwrap();
+        assert_eq!(report.hits[0].lines, [0]);
+        assert_eq!(report.hits[1], PatternHits::default());
+        assert_eq!(report.hits[2].lines, [2]);
+    }
+}
@@ -0,0 +1,282 @@
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+/// Event every long-running operation reports its progress on
+pub const OPERATION_PROGRESS_EVENT: &str = "operation-progress";
+
+/// Least time between two progress updates of an operation; its first and last always go out
+const MIN_UPDATE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What a long-running operation does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OperationKind {
+    Index,
+    Search,
+    Sql,
+    Export,
+    Parse,
+}
+
+/// Progress of one operation
+#[derive(Debug, Clone, Serialize)]
+pub struct OperationProgress {
+    /// Unique per operation for the life of the app
+    pub id: u64,
+    pub kind: OperationKind,
+    pub phase: String,
+    /// Fraction done, from 0 to 1
+    pub progress: f64,
+    pub message: String,
+    pub elapsed_ms: u64,
+    /// Time left at the rate so far, once there is a rate to go by
+    pub eta_ms: Option<u64>,
+    /// Set on the operation's last event, whether it finished or stopped early
+    pub done: bool,
+}
+
+/// Receives an operation's progress, e.g. by emitting `OPERATION_PROGRESS_EVENT`
+pub type ProgressSink = Arc<dyn Fn(&OperationProgress) + Send + Sync>;
+
+/// Asks an operation to stop; clones share the same state
+#[derive(Clone, Default)]
+pub struct CancellationToken(Arc<TokenState>);
+
+#[derive(Default)]
+struct TokenState {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::Release);
+        self.0.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::Acquire)
+    }
+
+    /// Wait until the token is cancelled
+    pub async fn cancelled(&self) {
+        loop {
+            // Created before the check, so a cancel in between still wakes it
+            let notified = self.0.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+/// An operation still running
+#[derive(Debug, Clone, Serialize)]
+pub struct RunningOperation {
+    pub id: u64,
+    pub kind: OperationKind,
+}
+
+/// Operations still running by id, so any of them can be cancelled the same way
+/// Operations remove themselves when they end
+#[derive(Clone, Default)]
+pub struct OperationRegistry {
+    running: Arc<Mutex<BTreeMap<u64, (OperationKind, CancellationToken)>>>,
+}
+
+impl OperationRegistry {
+    /// Start an operation that can be cancelled through the registry until it ends
+    pub fn start(
+        &self,
+        kind: OperationKind,
+        phase: &str,
+        message: &str,
+        sink: ProgressSink,
+    ) -> Operation {
+        let mut operation = Operation::start(kind, phase, message, sink);
+        self.running
+            .lock()
+            .insert(operation.id, (kind, operation.token.clone()));
+        operation.registry = Some(self.clone());
+        operation
+    }
+
+    /// Cancel a running operation, returning whether it was still running
+    pub fn cancel(&self, id: u64) -> bool {
+        match self.running.lock().get(&id) {
+            Some((_, token)) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn running(&self) -> Vec<RunningOperation> {
+        self.running
+            .lock()
+            .iter()
+            .map(|(&id, &(kind, _))| RunningOperation { id, kind })
+            .collect()
+    }
+}
+
+/// A running operation, shareable across the threads doing its work
+/// Dropping it without `finish` reports it as stopped
+pub struct Operation {
+    id: u64,
+    kind: OperationKind,
+    started: Instant,
+    sink: ProgressSink,
+    /// When the last update went out, and its phase and progress
+    last: Mutex<(Instant, String, f64)>,
+    finished: bool,
+    token: CancellationToken,
+    /// Registry the operation was started through, left when it ends
+    registry: Option<OperationRegistry>,
+}
+
+impl Operation {
+    /// Start an operation and report it at 0%
+    pub fn start(kind: OperationKind, phase: &str, message: &str, sink: ProgressSink) -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+        let started = Instant::now();
+        let operation = Operation {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            kind,
+            started,
+            sink,
+            last: Mutex::new((started, phase.to_string(), 0.0)),
+            finished: false,
+            token: CancellationToken::new(),
+            registry: None,
+        };
+        operation.send(phase, 0.0, message.to_string(), false);
+        operation
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// Report progress; updates closer together than `MIN_UPDATE_INTERVAL` are dropped
+    /// unless they start a new phase
+    pub fn update(&self, phase: &str, progress: f64, message: impl Into<String>) {
+        {
+            let mut last = self.last.lock();
+            let now = Instant::now();
+            if last.1 == phase && now.duration_since(last.0) < MIN_UPDATE_INTERVAL {
+                return;
+            }
+            *last = (now, phase.to_string(), progress);
+        }
+        self.send(phase, progress, message.into(), false);
+    }
+
+    /// Report the operation as complete
+    pub fn finish(mut self, message: impl Into<String>) {
+        self.finished = true;
+        self.send("complete", 1.0, message.into(), true);
+    }
+
+    fn send(&self, phase: &str, progress: f64, message: String, done: bool) {
+        let progress = progress.clamp(0.0, 1.0);
+        let elapsed = self.started.elapsed();
+        let eta_ms = (!done && progress > 0.0)
+            .then(|| (elapsed.as_secs_f64() * (1.0 - progress) / progress * 1000.0) as u64);
+        (self.sink)(&OperationProgress {
+            id: self.id,
+            kind: self.kind,
+            phase: phase.to_string(),
+            progress,
+            message,
+            elapsed_ms: elapsed.as_millis() as u64,
+            eta_ms,
+            done,
+        });
+    }
+}
+
+impl Drop for Operation {
+    fn drop(&mut self) {
+        if let Some(registry) = &self.registry {
+            registry.running.lock().remove(&self.id);
+        }
+        if !self.finished {
+            let progress = self.last.lock().2;
+            self.send("stopped", progress, "Stopped".to_string(), true);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_operation_progress() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink_events = events.clone();
+        let sink: ProgressSink = Arc::new(move |p: &OperationProgress| {
+            sink_events.lock().push(p.clone());
+        });
+
+        let first = Operation::start(OperationKind::Search, "scanning", "", sink.clone());
+        first.update("scanning", 0.1, "");
+        first.update("scanning", 0.2, "");
+        first.update("collecting", 0.9, "");
+        drop(first);
+        let second = Operation::start(OperationKind::Sql, "running", "", sink);
+        second.finish("3 rows");
+
+        let events = events.lock();
+        let phases: Vec<&str> = events.iter().map(|e| e.phase.as_str()).collect();
+        // Updates to the starting phase came too soon after the start to be sent
+        assert_eq!(
+            phases,
+            ["scanning", "collecting", "stopped", "running", "complete"]
+        );
+        assert!(events[1].eta_ms.is_some());
+        assert_eq!(events[2].progress, 0.9);
+        assert!(events[2].done && events[4].done && !events[3].done);
+        assert_ne!(events[0].id, events[3].id);
+        assert_eq!(events[4].kind, OperationKind::Sql);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_registered_operation() {
+        let registry = OperationRegistry::default();
+        let sink: ProgressSink = Arc::new(|_: &OperationProgress| {});
+        let operation = registry.start(OperationKind::Sql, "running", "", sink);
+        let id = operation.id();
+        assert_eq!(registry.running()[0].id, id);
+
+        let token = operation.token().clone();
+        let waiter = tokio::spawn(async move { token.cancelled().await });
+        assert!(registry.cancel(id));
+        waiter.await.unwrap();
+        assert!(operation.is_cancelled());
+
+        drop(operation);
+        assert!(registry.running().is_empty());
+        assert!(!registry.cancel(id));
+    }
+}
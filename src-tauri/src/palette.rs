@@ -0,0 +1,242 @@
+use crate::export::HighlightRule;
+use crate::parsers::{load_json, save_json};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// File the palette settings are persisted to, inside the app config directory
+const PALETTE_FILE: &str = "palette.json";
+
+/// Palette used until another is chosen
+pub const DEFAULT_PALETTE: &str = "classic";
+
+/// Errors that can occur choosing highlight colors
+#[derive(Debug, Error, PartialEq)]
+pub enum PaletteError {
+    #[error("No palette named \"{0}\"")]
+    UnknownPalette(String),
+    #[error("Invalid color \"{0}\": use #rgb, #rrggbb, #rrggbbaa or a color name")]
+    InvalidColor(String),
+}
+
+/// Theme highlights are shown on; each palette has colors suited to either
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    #[default]
+    Dark,
+    Light,
+}
+
+/// A named set of highlight background colors, assigned to rules in order
+#[derive(Debug, Clone, Serialize)]
+pub struct Palette {
+    pub name: &'static str,
+    pub label: &'static str,
+    /// Colors stay distinguishable with the common color vision deficiencies
+    pub color_blind_safe: bool,
+    pub dark: &'static [&'static str],
+    pub light: &'static [&'static str],
+}
+
+impl Palette {
+    pub fn colors(&self, theme: Theme) -> &'static [&'static str] {
+        match theme {
+            Theme::Dark => self.dark,
+            Theme::Light => self.light,
+        }
+    }
+}
+
+/// Built-in palettes; the color-blind-safe ones follow Okabe & Ito and Paul Tol
+pub const PALETTES: [Palette; 3] = [
+    Palette {
+        name: "classic",
+        label: "Classic",
+        color_blind_safe: false,
+        dark: &[
+            "#f9e79f", "#f5b041", "#ec7063", "#82e0aa", "#85c1e9", "#c39bd3", "#f0b27a",
+        ],
+        light: &[
+            "#fff59d", "#ffcc80", "#ef9a9a", "#a5d6a7", "#90caf9", "#ce93d8", "#ffab91",
+        ],
+    },
+    Palette {
+        name: "okabe-ito",
+        label: "Okabe-Ito (color-blind safe)",
+        color_blind_safe: true,
+        dark: &[
+            "#e69f00", "#56b4e9", "#009e73", "#f0e442", "#0072b2", "#d55e00", "#cc79a7",
+        ],
+        light: &[
+            "#f5c566", "#9ad2f2", "#66c5ab", "#f6ef8e", "#66aad1", "#e69e66", "#e0afca",
+        ],
+    },
+    Palette {
+        name: "tol",
+        label: "Paul Tol (color-blind safe)",
+        color_blind_safe: true,
+        dark: &[
+            "#ccbb44", "#66ccee", "#ee6677", "#228833", "#4477aa", "#aa3377", "#bbbbbb",
+        ],
+        light: &[
+            "#eedd88", "#99ddff", "#ffaabb", "#44bb99", "#77aadd", "#ee8866", "#dddddd",
+        ],
+    },
+];
+
+pub fn find_palette(name: &str) -> Option<&'static Palette> {
+    PALETTES.iter().find(|p| p.name == name)
+}
+
+/// Whether a color is hex or a plain name, so it can't inject markup into exports
+pub fn is_css_color(color: &str) -> bool {
+    let valid_hex = color.starts_with('#')
+        && matches!(color.len(), 4 | 7 | 9)
+        && color[1..].chars().all(|c| c.is_ascii_hexdigit());
+    let valid_name = !color.is_empty() && color.chars().all(|c| c.is_ascii_alphabetic());
+    valid_hex || valid_name
+}
+
+/// The chosen palette and theme, and colors pinned to particular patterns
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PaletteConfig {
+    #[serde(default = "default_palette")]
+    pub palette: String,
+    #[serde(default)]
+    pub theme: Theme,
+    /// Colors by rule pattern, used over the palette's
+    #[serde(default)]
+    pub overrides: BTreeMap<String, String>,
+}
+
+fn default_palette() -> String {
+    DEFAULT_PALETTE.to_string()
+}
+
+impl Default for PaletteConfig {
+    fn default() -> Self {
+        PaletteConfig {
+            palette: default_palette(),
+            theme: Theme::default(),
+            overrides: BTreeMap::new(),
+        }
+    }
+}
+
+impl PaletteConfig {
+    pub fn validate(&self) -> Result<(), PaletteError> {
+        if find_palette(&self.palette).is_none() {
+            return Err(PaletteError::UnknownPalette(self.palette.clone()));
+        }
+        match self.overrides.values().find(|color| !is_css_color(color)) {
+            Some(color) => Err(PaletteError::InvalidColor(color.clone())),
+            None => Ok(()),
+        }
+    }
+
+    /// Give every rule without a color of its own one: its pattern's override, or else the
+    /// palette color of its position, so a rule keeps its color as others are added after it
+    pub fn assign(&self, rules: &mut [HighlightRule], theme: Option<Theme>) {
+        let palette = find_palette(&self.palette).unwrap_or(&PALETTES[0]);
+        let colors = palette.colors(theme.unwrap_or(self.theme));
+        for (index, rule) in rules.iter_mut().enumerate() {
+            if rule.color.is_some() {
+                continue;
+            }
+            let color = match self.overrides.get(&rule.pattern) {
+                Some(color) => color.clone(),
+                None => colors[index % colors.len()].to_string(),
+            };
+            rule.color = Some(color);
+        }
+    }
+}
+
+/// Palette settings persisted as JSON
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PaletteStore {
+    #[serde(skip)]
+    path: PathBuf,
+    #[serde(default)]
+    config: PaletteConfig,
+}
+
+impl PaletteStore {
+    /// Load the settings from a config directory; a missing file has the defaults
+    pub fn load(dir: &Path) -> io::Result<Self> {
+        let path = dir.join(PALETTE_FILE);
+        let mut store: PaletteStore = load_json(&path)?;
+        store.path = path;
+        Ok(store)
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        save_json(&self.path, self)
+    }
+
+    pub fn config(&self) -> &PaletteConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: PaletteConfig) -> Result<(), PaletteError> {
+        config.validate()?;
+        self.config = config;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(pattern: &str, color: Option<&str>) -> HighlightRule {
+        HighlightRule {
+            pattern: pattern.to_string(),
+            color: color.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_assign_prefers_rule_color_then_override() {
+        let mut config = PaletteConfig {
+            palette: "okabe-ito".to_string(),
+            ..PaletteConfig::default()
+        };
+        config
+            .overrides
+            .insert("WARN".to_string(), "orange".to_string());
+        let mut rules = vec![
+            rule("ERROR", None),
+            rule("WARN", None),
+            rule("INFO", Some("#00ff00")),
+            rule("DEBUG", None),
+        ];
+        config.assign(&mut rules, Some(Theme::Light));
+        let colors: Vec<_> = rules.iter().map(|r| r.color.as_deref().unwrap()).collect();
+        assert_eq!(colors, vec!["#f5c566", "orange", "#00ff00", "#f6ef8e"]);
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut config = PaletteConfig::default();
+        assert_eq!(config.validate(), Ok(()));
+        config
+            .overrides
+            .insert("ERROR".to_string(), "red\"><script>".to_string());
+        assert!(matches!(
+            config.validate(),
+            Err(PaletteError::InvalidColor(_))
+        ));
+        config.palette = "neon".to_string();
+        assert_eq!(
+            config.validate(),
+            Err(PaletteError::UnknownPalette("neon".to_string()))
+        );
+        assert!(PALETTES
+            .iter()
+            .all(|p| p.dark.iter().chain(p.light).all(|c| is_css_color(c))));
+    }
+}
@@ -0,0 +1,317 @@
+use crate::operations::CancellationToken;
+use crate::parsers::schema::SchemaOverride;
+use crate::parsers::{infer_schema, LogParser, ParseSink, ParsedTable, SchemaBuilder};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::ops::Range;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+/// Lines parsed between registrations of the growing table
+pub const CHUNK_LINES: u64 = 250_000;
+
+/// A parse fed line by line whose table can be taken at chunk boundaries
+/// Multi-line records may span chunks since the parser carries over between them
+pub struct ChunkedParse {
+    parser: Box<dyn LogParser>,
+    schema_override: Option<SchemaOverride>,
+    sink: ParseSink,
+    schema: SchemaBuilder,
+    pub table: ParsedTable,
+}
+
+impl ChunkedParse {
+    pub fn new(parser: Box<dyn LogParser>, schema_override: Option<SchemaOverride>) -> Self {
+        ChunkedParse {
+            parser,
+            schema_override,
+            sink: ParseSink::default(),
+            schema: SchemaBuilder::default(),
+            table: ParsedTable::default(),
+        }
+    }
+
+    pub fn feed(&mut self, line_number: u64, line: &str) {
+        self.parser.feed(line_number, line, &mut self.sink);
+    }
+
+    /// Move the records completed so far into the table, applying the schema override
+    pub fn flush(&mut self) -> Result<(), String> {
+        let sink = std::mem::take(&mut self.sink);
+        let mut chunk = ParsedTable {
+            columns: infer_schema(&sink.records),
+            records: sink.records,
+            failures: sink.failures,
+        };
+        if let Some(schema_override) = &self.schema_override {
+            schema_override.apply(&mut chunk)?;
+        }
+        self.schema.add(&chunk.records);
+        self.table.columns = self.schema.columns();
+        self.table.records.append(&mut chunk.records);
+        self.table.failures.append(&mut chunk.failures);
+        Ok(())
+    }
+
+    /// Flush records still being assembled at end of input
+    pub fn finish(&mut self) -> Result<(), String> {
+        self.parser.finish(&mut self.sink);
+        self.flush()
+    }
+}
+
+/// Where a background parse stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ParseJobState {
+    Running,
+    Complete,
+    Cancelled,
+    Failed,
+}
+
+/// Progress of a background parse, emitted after each chunk
+#[derive(Debug, Clone, Serialize)]
+pub struct ParseProgress {
+    pub state: ParseJobState,
+    pub lines_parsed: u64,
+    pub total_lines: u64,
+    pub progress: f32,
+    pub rows: usize,
+    pub failed_lines: usize,
+    /// Whether the registered table covers only part of the file
+    pub partial: bool,
+    pub error: Option<String>,
+}
+
+/// A parse running on a background thread, publishing the table after every chunk
+pub struct ParseJob {
+    cancel: CancellationToken,
+    handle: Option<JoinHandle<()>>,
+    progress: Arc<Mutex<ParseProgress>>,
+}
+
+impl ParseJob {
+    /// Parse `total_lines` lines on a background thread
+    /// `read` passes the lines with the given 0-based indices to its callback as
+    /// `(1-based number, text)` and returns false if the file is gone; `publish` registers
+    /// the table parsed so far, flagged partial until the last chunk. Cancelling `cancel`
+    /// stops the parse after its current chunk
+    pub fn start<R, P, E>(
+        parse: ChunkedParse,
+        total_lines: u64,
+        cancel: CancellationToken,
+        read: R,
+        publish: P,
+        on_progress: E,
+    ) -> Self
+    where
+        R: FnMut(Range<u64>, &mut dyn FnMut(u64, &str)) -> bool + Send + 'static,
+        P: FnMut(&ParsedTable, bool) -> Result<(), String> + Send + 'static,
+        E: FnMut(ParseProgress) + Send + 'static,
+    {
+        let progress = Arc::new(Mutex::new(ParseProgress {
+            state: ParseJobState::Running,
+            lines_parsed: 0,
+            total_lines,
+            progress: 0.0,
+            rows: 0,
+            failed_lines: 0,
+            partial: true,
+            error: None,
+        }));
+        let thread_cancel = cancel.clone();
+        let thread_progress = progress.clone();
+        let handle = std::thread::spawn(move || {
+            run_job(
+                parse,
+                total_lines,
+                &thread_cancel,
+                &thread_progress,
+                read,
+                publish,
+                on_progress,
+            );
+        });
+        ParseJob {
+            cancel,
+            handle: Some(handle),
+            progress,
+        }
+    }
+
+    pub fn progress(&self) -> ParseProgress {
+        self.progress.lock().clone()
+    }
+
+    /// Whether the registered table holds only part of the file, either because the parse
+    /// is still running or because it was stopped early
+    pub fn is_partial(&self) -> bool {
+        self.progress.lock().partial
+    }
+
+    /// Stop after the current chunk; the table registered so far stays queryable
+    pub fn cancel(&mut self) {
+        self.cancel.cancel();
+        if let Some(handle) = self.handle.take() {
+            handle.join().ok();
+        }
+    }
+}
+
+impl Drop for ParseJob {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+fn run_job<R, P, E>(
+    mut parse: ChunkedParse,
+    total_lines: u64,
+    cancel: &CancellationToken,
+    progress: &Mutex<ParseProgress>,
+    mut read: R,
+    mut publish: P,
+    mut on_progress: E,
+) where
+    R: FnMut(Range<u64>, &mut dyn FnMut(u64, &str)) -> bool,
+    P: FnMut(&ParsedTable, bool) -> Result<(), String>,
+    E: FnMut(ParseProgress),
+{
+    let mut start = 0;
+    loop {
+        let (state, error) = if cancel.is_cancelled() {
+            (ParseJobState::Cancelled, None)
+        } else {
+            let end = (start + CHUNK_LINES).min(total_lines);
+            let result = if read(start..end, &mut |n, line| parse.feed(n, line)) {
+                start = end;
+                let done = start >= total_lines;
+                let flushed = if done { parse.finish() } else { parse.flush() };
+                flushed
+                    .and_then(|_| publish(&parse.table, !done))
+                    .map(|_| done)
+            } else {
+                Err("The file was closed".to_string())
+            };
+            match result {
+                Ok(false) => (ParseJobState::Running, None),
+                Ok(true) => (ParseJobState::Complete, None),
+                Err(err) => (ParseJobState::Failed, Some(err)),
+            }
+        };
+
+        let update = {
+            let mut progress = progress.lock();
+            progress.state = state;
+            progress.lines_parsed = start;
+            progress.progress = if total_lines == 0 {
+                1.0
+            } else {
+                start as f32 / total_lines as f32
+            };
+            progress.rows = parse.table.records.len();
+            progress.failed_lines = parse.table.failures.len();
+            progress.partial = state != ParseJobState::Complete;
+            progress.error = error;
+            progress.clone()
+        };
+        on_progress(update);
+        if state != ParseJobState::Running {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::json::JsonParser;
+    use crate::parsers::ColumnType;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_chunked_parse_widens_across_chunks() {
+        let mut parse = ChunkedParse::new(Box::new(JsonParser), None);
+        parse.feed(1, r#"{"n":1}"#);
+        parse.flush().unwrap();
+        parse.feed(2, r#"{"n":1.5,"extra":"x"}"#);
+        parse.feed(3, "text");
+        parse.finish().unwrap();
+
+        let names: Vec<_> = parse
+            .table
+            .columns
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["n", "extra"]);
+        assert_eq!(parse.table.columns[0].column_type, ColumnType::Float64);
+        assert_eq!(parse.table.records.len(), 2);
+        assert_eq!(parse.table.failures[0].line_number, 3);
+    }
+
+    #[test]
+    fn test_job_publishes_chunks_until_complete() {
+        let total = CHUNK_LINES + 10;
+        let (tx, rx) = mpsc::channel();
+        let job = ParseJob::start(
+            ChunkedParse::new(Box::new(JsonParser), None),
+            total,
+            CancellationToken::new(),
+            |range, feed| {
+                for i in range {
+                    feed(i + 1, &format!("{{\"i\":{}}}", i));
+                }
+                true
+            },
+            move |table, partial| {
+                tx.send((table.records.len(), partial)).unwrap();
+                Ok(())
+            },
+            |_| {},
+        );
+
+        assert_eq!(rx.recv().unwrap(), (CHUNK_LINES as usize, true));
+        assert_eq!(rx.recv().unwrap(), (total as usize, false));
+        drop(job);
+    }
+
+    #[test]
+    fn test_job_cancellation_keeps_partial_table() {
+        let (started_tx, started_rx) = mpsc::channel();
+        let (resume_tx, resume_rx) = mpsc::channel::<()>();
+        let (progress_tx, progress_rx) = mpsc::channel();
+        let cancel = CancellationToken::new();
+        let mut job = ParseJob::start(
+            ChunkedParse::new(Box::new(JsonParser), None),
+            CHUNK_LINES * 4,
+            cancel.clone(),
+            move |range, feed| {
+                // Hold the first chunk until the test has asked to cancel
+                if range.start == 0 {
+                    started_tx.send(()).unwrap();
+                    resume_rx.recv().ok();
+                }
+                for i in range {
+                    feed(i + 1, r#"{"a":1}"#);
+                }
+                true
+            },
+            |_, _| Ok(()),
+            move |progress| progress_tx.send(progress).unwrap(),
+        );
+
+        started_rx.recv().unwrap();
+        cancel.cancel();
+        resume_tx.send(()).unwrap();
+        job.cancel();
+
+        assert_eq!(progress_rx.recv().unwrap().state, ParseJobState::Running);
+        let last = progress_rx.recv().unwrap();
+        assert_eq!(last.state, ParseJobState::Cancelled);
+        assert_eq!(last.rows, CHUNK_LINES as usize);
+        assert!(last.error.is_none());
+        assert!(job.is_partial());
+    }
+}
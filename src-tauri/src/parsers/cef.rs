@@ -0,0 +1,225 @@
+use super::{split_key_values, FieldValue, LogParser, ParseSink};
+use crate::timestamp::parse_timestamp;
+use chrono::NaiveDateTime;
+
+/// Extension keys that carry timestamps
+const TIME_KEYS: &[&str] = &[
+    "rt",
+    "start",
+    "end",
+    "art",
+    "deviceCustomDate1",
+    "deviceCustomDate2",
+    "fileCreateTime",
+    "fileModificationTime",
+];
+
+/// Header column names, in header order after the version
+const HEADER_COLUMNS: [&str; 6] = [
+    "device_vendor",
+    "device_product",
+    "device_version",
+    "signature_id",
+    "name",
+    "severity",
+];
+
+/// Parser for ArcSight CEF: `CEF:Version|Vendor|Product|Version|SignatureID|Name|Severity|Extension`
+/// A syslog header before `CEF:` is kept in a `prefix` column
+pub struct CefParser;
+
+/// Byte offset of the `CEF:` header in a line, if present
+pub fn find_cef(line: &str) -> Option<usize> {
+    let start = line.find("CEF:")?;
+    let rest = &line[start + 4..];
+    let has_version = rest.starts_with(|c: char| c.is_ascii_digit());
+    (has_version && split_header(rest, 7).len() == 8).then_some(start)
+}
+
+impl LogParser for CefParser {
+    fn feed(&mut self, line_number: u64, line: &str, sink: &mut ParseSink) {
+        if line.trim().is_empty() {
+            return;
+        }
+        let Some(start) = find_cef(line) else {
+            sink.fail(line_number, "No CEF header");
+            return;
+        };
+        let parts = split_header(&line[start + 4..], 7);
+
+        let mut fields = Vec::with_capacity(16);
+        let prefix = line[..start].trim();
+        if !prefix.is_empty() {
+            fields.push(("prefix".to_string(), FieldValue::Str(prefix.to_string())));
+        }
+        fields.push(("cef_version".to_string(), FieldValue::infer(parts[0])));
+        for (name, value) in HEADER_COLUMNS.iter().zip(&parts[1..7]) {
+            let value = unescape(value);
+            let typed = if *name == "severity" {
+                FieldValue::infer(&value)
+            } else if value.is_empty() {
+                FieldValue::Null
+            } else {
+                FieldValue::Str(value)
+            };
+            fields.push((name.to_string(), typed));
+        }
+
+        for (key, value) in split_key_values(parts[7]) {
+            let value = unescape(value);
+            let typed = if TIME_KEYS.contains(&key) {
+                parse_event_time(&value)
+                    .map_or_else(|| FieldValue::infer(&value), FieldValue::Timestamp)
+            } else {
+                FieldValue::infer(&value)
+            };
+            fields.push((key.to_string(), typed));
+        }
+        sink.record(line_number, fields);
+    }
+}
+
+/// Split on unescaped pipes into at most `max_pipes + 1` parts; the last part keeps any pipes
+fn split_header(text: &str, max_pipes: usize) -> Vec<&str> {
+    let mut parts = Vec::with_capacity(max_pipes + 1);
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, b) in text.bytes().enumerate() {
+        if parts.len() == max_pipes {
+            break;
+        }
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match b {
+            b'\\' => escaped = true,
+            b'|' => {
+                parts.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+/// Resolve CEF escapes (`\|`, `\\`, `\=`, `\n`, `\r`)
+fn unescape(text: &str) -> String {
+    if !text.contains('\\') {
+        return text.to_string();
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// Parse the timestamp forms used by CEF and LEEF devices: epoch milliseconds,
+/// `MMM dd yyyy HH:mm:ss[.SSS]`, or anything the general timestamp parser understands
+pub fn parse_event_time(text: &str) -> Option<i64> {
+    let text = text.trim();
+    if !text.is_empty() && text.bytes().all(|b| b.is_ascii_digit()) {
+        let value: i64 = text.parse().ok()?;
+        // Ten digits or fewer is seconds
+        return Some(if text.len() <= 10 {
+            value * 1000
+        } else {
+            value
+        });
+    }
+    // Devices often append a zone name; parse the date part only
+    let date_part = text
+        .split_whitespace()
+        .take(4)
+        .collect::<Vec<_>>()
+        .join(" ");
+    for format in ["%b %d %Y %H:%M:%S%.f", "%b %d %Y %H:%M:%S"] {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(&date_part, format) {
+            return Some(naive.and_utc().timestamp_millis());
+        }
+    }
+    parse_timestamp(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cef_with_syslog_prefix() {
+        let line = r"Jan 18 11:07:53 fw01 CEF:0|Security|threatmanager|1.0|100|worm successfully stopped|10|src=10.0.0.1 dst=2.1.2.2 spt=1232 msg=Detected a threat\=worm rt=1704067200000 cs1Label=Policy cs1=Default \| strict";
+        let mut sink = ParseSink::default();
+        CefParser.feed(3, line, &mut sink);
+        let record = &sink.records[0];
+
+        assert_eq!(record.line_number, 3);
+        assert_eq!(
+            record.get("prefix"),
+            Some(&FieldValue::Str("Jan 18 11:07:53 fw01".to_string()))
+        );
+        assert_eq!(record.get("cef_version"), Some(&FieldValue::Int(0)));
+        assert_eq!(
+            record.get("name"),
+            Some(&FieldValue::Str("worm successfully stopped".to_string()))
+        );
+        assert_eq!(record.get("severity"), Some(&FieldValue::Int(10)));
+        assert_eq!(record.get("spt"), Some(&FieldValue::Int(1232)));
+        assert_eq!(
+            record.get("msg"),
+            Some(&FieldValue::Str("Detected a threat=worm".to_string()))
+        );
+        assert_eq!(
+            record.get("rt"),
+            Some(&FieldValue::Timestamp(1_704_067_200_000))
+        );
+        assert_eq!(
+            record.get("cs1"),
+            Some(&FieldValue::Str("Default | strict".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_escaped_pipe_in_header_and_failures() {
+        let mut sink = ParseSink::default();
+        CefParser.feed(1, r"CEF:1|Vendor|Prod\|uct|2.0|sig|Name|High|", &mut sink);
+        CefParser.feed(2, "not a cef line", &mut sink);
+
+        let record = &sink.records[0];
+        assert_eq!(
+            record.get("device_product"),
+            Some(&FieldValue::Str("Prod|uct".to_string()))
+        );
+        assert_eq!(
+            record.get("severity"),
+            Some(&FieldValue::Str("High".to_string()))
+        );
+        assert_eq!(sink.failures.len(), 1);
+        assert_eq!(sink.failures[0].line_number, 2);
+    }
+
+    #[test]
+    fn test_parse_event_time() {
+        assert_eq!(parse_event_time("1704067200"), Some(1_704_067_200_000));
+        assert_eq!(
+            parse_event_time("Jan 01 2024 00:00:01 UTC"),
+            Some(1_704_067_201_000)
+        );
+        assert_eq!(
+            parse_event_time("Jan 01 2024 00:00:01.250"),
+            Some(1_704_067_201_250)
+        );
+    }
+}
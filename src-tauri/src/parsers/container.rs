@@ -0,0 +1,261 @@
+use super::{FieldValue, LogParser, ParseSink};
+use crate::timestamp::parse_timestamp;
+use regex::Regex;
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+fn cri_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"^(?P<time>\d{4}-\d{2}-\d{2}T\S+) (?P<stream>stdout|stderr) (?P<tag>[PF])(?: (?P<message>.*))?$")
+            .unwrap()
+    })
+}
+
+/// Whether a line is in the CRI format written by containerd and CRI-O
+pub fn is_cri_line(line: &str) -> bool {
+    cri_regex().is_match(line)
+}
+
+/// Whether a line looks like a Docker json-file driver entry
+pub fn is_docker_json_line(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with('{') && line.contains("\"log\":") && line.contains("\"stream\":")
+}
+
+/// A message being reassembled from partial lines of one stream
+struct Fragment {
+    line_number: u64,
+    timestamp: Option<i64>,
+    message: String,
+    pieces: u32,
+}
+
+/// Partial lines waiting for their final piece, per stream
+#[derive(Default)]
+struct Reassembler {
+    pending: HashMap<String, Fragment>,
+}
+
+impl Reassembler {
+    /// Add a piece; when it completes the message, push the record
+    fn push(
+        &mut self,
+        line_number: u64,
+        stream: &str,
+        timestamp: Option<i64>,
+        message: &str,
+        complete: bool,
+        sink: &mut ParseSink,
+    ) {
+        let fragment = self
+            .pending
+            .entry(stream.to_string())
+            .or_insert_with(|| Fragment {
+                line_number,
+                timestamp,
+                message: String::new(),
+                pieces: 0,
+            });
+        fragment.message.push_str(message);
+        fragment.pieces += 1;
+        if complete {
+            if let Some(fragment) = self.pending.remove(stream) {
+                Self::emit(stream, fragment, sink);
+            }
+        }
+    }
+
+    /// Emit messages whose final piece never arrived, in source order
+    fn flush(&mut self, sink: &mut ParseSink) {
+        let mut pending: Vec<(String, Fragment)> = self.pending.drain().collect();
+        pending.sort_by_key(|(_, f)| f.line_number);
+        for (stream, fragment) in pending {
+            Self::emit(&stream, fragment, sink);
+        }
+    }
+
+    fn emit(stream: &str, fragment: Fragment, sink: &mut ParseSink) {
+        sink.record(
+            fragment.line_number,
+            vec![
+                (
+                    "timestamp".to_string(),
+                    fragment
+                        .timestamp
+                        .map_or(FieldValue::Null, FieldValue::Timestamp),
+                ),
+                ("stream".to_string(), FieldValue::Str(stream.to_string())),
+                ("message".to_string(), FieldValue::Str(fragment.message)),
+                ("partial".to_string(), FieldValue::Bool(fragment.pieces > 1)),
+            ],
+        );
+    }
+}
+
+/// Parser for the Kubernetes CRI log format: `<RFC 3339 time> <stream> <P|F> <message>`
+/// `P` lines are joined with the following pieces of the same stream up to the `F` line
+#[derive(Default)]
+pub struct CriParser {
+    reassembler: Reassembler,
+}
+
+impl LogParser for CriParser {
+    fn feed(&mut self, line_number: u64, line: &str, sink: &mut ParseSink) {
+        if line.is_empty() {
+            return;
+        }
+        let Some(caps) = cri_regex().captures(line) else {
+            sink.fail(line_number, "Not a CRI log line");
+            return;
+        };
+        self.reassembler.push(
+            line_number,
+            &caps["stream"],
+            parse_timestamp(&caps["time"]),
+            caps.name("message").map_or("", |m| m.as_str()),
+            &caps["tag"] == "F",
+            sink,
+        );
+    }
+
+    fn finish(&mut self, sink: &mut ParseSink) {
+        self.reassembler.flush(sink);
+    }
+}
+
+/// Parser for Docker's json-file driver: `{"log":"...\n","stream":"stdout","time":"..."}`
+/// Docker splits long lines into entries without a trailing newline; these are joined
+#[derive(Default)]
+pub struct DockerJsonParser {
+    reassembler: Reassembler,
+}
+
+impl LogParser for DockerJsonParser {
+    fn feed(&mut self, line_number: u64, line: &str, sink: &mut ParseSink) {
+        if line.trim().is_empty() {
+            return;
+        }
+        let entry = match serde_json::from_str::<JsonValue>(line) {
+            Ok(JsonValue::Object(entry)) => entry,
+            Ok(_) => {
+                sink.fail(line_number, "Expected a JSON object");
+                return;
+            }
+            Err(err) => {
+                sink.fail(line_number, err.to_string());
+                return;
+            }
+        };
+        let Some(log) = entry.get("log").and_then(JsonValue::as_str) else {
+            sink.fail(line_number, "Missing \"log\" field");
+            return;
+        };
+        let stream = entry
+            .get("stream")
+            .and_then(JsonValue::as_str)
+            .unwrap_or("stdout");
+        let timestamp = entry
+            .get("time")
+            .and_then(JsonValue::as_str)
+            .and_then(parse_timestamp);
+
+        let message = log.strip_suffix('\n');
+        let complete = message.is_some();
+        let message = message.unwrap_or(log);
+        self.reassembler.push(
+            line_number,
+            stream,
+            timestamp,
+            message.strip_suffix('\r').unwrap_or(message),
+            complete,
+            sink,
+        );
+    }
+
+    fn finish(&mut self, sink: &mut ParseSink) {
+        self.reassembler.flush(sink);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::parse_lines;
+
+    #[test]
+    fn test_cri_reassembles_partial_lines() {
+        let lines = [
+            "2024-01-01T00:00:00.000000001Z stdout P first half ",
+            "2024-01-01T00:00:00.100000000Z stderr F error: boom",
+            "2024-01-01T00:00:00.200000000Z stdout F second half",
+            "2024-01-01T00:00:01Z stdout F",
+            "garbage",
+        ];
+        let table = parse_lines(
+            &mut CriParser::default(),
+            lines.iter().enumerate().map(|(i, l)| (i as u64 + 1, *l)),
+        );
+
+        assert_eq!(table.records.len(), 3);
+        let stderr = &table.records[0];
+        assert_eq!(stderr.line_number, 2);
+        assert_eq!(
+            stderr.get("stream"),
+            Some(&FieldValue::Str("stderr".to_string()))
+        );
+
+        let joined = &table.records[1];
+        assert_eq!(joined.line_number, 1);
+        assert_eq!(
+            joined.get("timestamp"),
+            Some(&FieldValue::Timestamp(1_704_067_200_000))
+        );
+        assert_eq!(
+            joined.get("message"),
+            Some(&FieldValue::Str("first half second half".to_string()))
+        );
+        assert_eq!(joined.get("partial"), Some(&FieldValue::Bool(true)));
+
+        assert_eq!(
+            table.records[2].get("message"),
+            Some(&FieldValue::Str(String::new()))
+        );
+        assert_eq!(table.failures.len(), 1);
+    }
+
+    #[test]
+    fn test_docker_json_reassembles_split_lines() {
+        let lines = [
+            r#"{"log":"GET /health 200\n","stream":"stdout","time":"2024-01-01T00:00:00.5Z"}"#,
+            r#"{"log":"very long ","stream":"stdout","time":"2024-01-01T00:00:01Z"}"#,
+            r#"{"log":"line\r\n","stream":"stdout","time":"2024-01-01T00:00:01Z"}"#,
+            r#"{"log":"trailing","stream":"stderr","time":"2024-01-01T00:00:02Z"}"#,
+        ];
+        let table = parse_lines(
+            &mut DockerJsonParser::default(),
+            lines.iter().enumerate().map(|(i, l)| (i as u64 + 1, *l)),
+        );
+
+        assert_eq!(table.records.len(), 3);
+        assert_eq!(
+            table.records[0].get("timestamp"),
+            Some(&FieldValue::Timestamp(1_704_067_200_500))
+        );
+        assert_eq!(
+            table.records[0].get("partial"),
+            Some(&FieldValue::Bool(false))
+        );
+        assert_eq!(
+            table.records[1].get("message"),
+            Some(&FieldValue::Str("very long line".to_string()))
+        );
+        // An unterminated piece at end of input is still kept
+        assert_eq!(table.records[2].line_number, 4);
+        assert_eq!(
+            table.records[2].get("message"),
+            Some(&FieldValue::Str("trailing".to_string()))
+        );
+    }
+}
@@ -0,0 +1,317 @@
+use super::{FieldValue, LogParser, ParseSink};
+use serde::{Deserialize, Serialize};
+
+/// Delimiters tried when sniffing, in order of preference on ties
+const CANDIDATE_DELIMITERS: [char; 4] = [',', '\t', ';', '|'];
+
+/// Longest a quoted field may run on before the record is abandoned
+const MAX_RECORD_LINES: usize = 1000;
+
+/// How a delimited file is laid out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CsvDialect {
+    pub delimiter: char,
+    pub quote: char,
+    /// Whether the first record names the columns
+    pub has_header: bool,
+}
+
+impl Default for CsvDialect {
+    fn default() -> Self {
+        CsvDialect {
+            delimiter: ',',
+            quote: '"',
+            has_header: true,
+        }
+    }
+}
+
+impl CsvDialect {
+    /// Guess the dialect from the first lines of a file
+    /// The delimiter must split every complete record into the same number (at least two)
+    /// of fields; quoted fields may contain delimiters and newlines
+    pub fn sniff<S: AsRef<str>>(lines: &[S]) -> Option<CsvDialect> {
+        let text = lines
+            .iter()
+            .map(AsRef::as_ref)
+            .filter(|l| !l.trim().is_empty())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let (delimiter, records) = CANDIDATE_DELIMITERS
+            .iter()
+            .filter_map(|&delimiter| {
+                let records = split_records(&text, delimiter, '"');
+                let width = records.first()?.len();
+                let consistent =
+                    records.len() >= 2 && width >= 2 && records.iter().all(|r| r.len() == width);
+                consistent.then_some((delimiter, records))
+            })
+            .max_by_key(|(_, records)| records[0].len())?;
+
+        Some(CsvDialect {
+            delimiter,
+            quote: '"',
+            has_header: looks_like_header(&records),
+        })
+    }
+}
+
+/// Whether a field value is numeric, which header names never are
+fn is_numeric(value: &str) -> bool {
+    matches!(
+        FieldValue::infer(value.trim()),
+        FieldValue::Int(_) | FieldValue::Float(_)
+    )
+}
+
+/// A header has distinct, non-empty, non-numeric names that are either typed differently
+/// from the values below them or never repeated among them
+fn looks_like_header(records: &[Vec<String>]) -> bool {
+    let (first, rest) = match records.split_first() {
+        Some(split) => split,
+        None => return false,
+    };
+    let mut names: Vec<&str> = first.iter().map(|s| s.trim()).collect();
+    if names.iter().any(|n| n.is_empty() || is_numeric(n)) {
+        return false;
+    }
+    names.sort_unstable();
+    names.dedup();
+    if names.len() != first.len() {
+        return false;
+    }
+
+    let numeric_column = (0..first.len())
+        .any(|col| rest.iter().filter(|r| is_numeric(&r[col])).count() * 2 > rest.len());
+    let repeated = (0..first.len()).any(|col| rest.iter().any(|r| r[col] == first[col]));
+    numeric_column || !repeated
+}
+
+/// Split complete records out of `text`, dropping a trailing record cut off inside quotes
+fn split_records(text: &str, delimiter: char, quote: char) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        match parse_record(rest, delimiter, quote) {
+            Some((fields, consumed)) => {
+                records.push(fields);
+                rest = &rest[consumed..];
+            }
+            None => break,
+        }
+    }
+    records
+}
+
+/// Parse one record from the start of `text`, returning its fields and the bytes consumed
+/// (including the terminating newline); None if a quoted field is unterminated
+fn parse_record(text: &str, delimiter: char, quote: char) -> Option<(Vec<String>, usize)> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if in_quotes {
+            if c == quote {
+                if chars.peek().map(|&(_, next)| next) == Some(quote) {
+                    chars.next();
+                    field.push(quote);
+                } else {
+                    in_quotes = false;
+                }
+            } else {
+                field.push(c);
+            }
+        } else if c == quote && field.is_empty() {
+            in_quotes = true;
+        } else if c == delimiter {
+            fields.push(std::mem::take(&mut field));
+        } else if c == '\n' {
+            if field.ends_with('\r') {
+                field.pop();
+            }
+            fields.push(field);
+            return Some((fields, i + 1));
+        } else {
+            field.push(c);
+        }
+    }
+    if in_quotes {
+        return None;
+    }
+    if field.ends_with('\r') {
+        field.pop();
+    }
+    fields.push(field);
+    Some((fields, text.len()))
+}
+
+/// Parser for delimited files in a given dialect
+/// Records whose quoted fields span several lines are numbered by their first line
+pub struct CsvParser {
+    dialect: CsvDialect,
+    columns: Option<Vec<String>>,
+    /// Text of a record still inside a quoted field
+    buffer: String,
+    buffer_start: u64,
+    buffer_lines: usize,
+}
+
+impl CsvParser {
+    pub fn new(dialect: CsvDialect) -> Self {
+        CsvParser {
+            dialect,
+            columns: None,
+            buffer: String::new(),
+            buffer_start: 0,
+            buffer_lines: 0,
+        }
+    }
+
+    fn record(&mut self, line_number: u64, values: Vec<String>, sink: &mut ParseSink) {
+        if self.columns.is_none() && self.dialect.has_header {
+            self.columns = Some(header_names(values));
+            return;
+        }
+        let columns = self.columns.get_or_insert_with(Vec::new);
+        let fields = values
+            .into_iter()
+            .enumerate()
+            .map(|(i, value)| {
+                let name = columns
+                    .get(i)
+                    .cloned()
+                    .unwrap_or_else(|| format!("column_{}", i + 1));
+                (name, FieldValue::infer(&value))
+            })
+            .collect();
+        sink.record(line_number, fields);
+    }
+}
+
+/// Column names from a header record: trimmed, with blanks and duplicates renamed
+fn header_names(values: Vec<String>) -> Vec<String> {
+    let mut names: Vec<String> = Vec::with_capacity(values.len());
+    for (i, value) in values.into_iter().enumerate() {
+        let base = match value.trim() {
+            "" => format!("column_{}", i + 1),
+            name => name.to_string(),
+        };
+        let mut name = base.clone();
+        let mut suffix = 2;
+        while names.contains(&name) {
+            name = format!("{}_{}", base, suffix);
+            suffix += 1;
+        }
+        names.push(name);
+    }
+    names
+}
+
+impl LogParser for CsvParser {
+    fn feed(&mut self, line_number: u64, line: &str, sink: &mut ParseSink) {
+        if self.buffer_lines == 0 {
+            if line.trim().is_empty() {
+                return;
+            }
+            self.buffer_start = line_number;
+        } else {
+            self.buffer.push('\n');
+        }
+        self.buffer.push_str(line);
+        self.buffer_lines += 1;
+
+        match parse_record(&self.buffer, self.dialect.delimiter, self.dialect.quote) {
+            Some((values, _)) => {
+                self.buffer.clear();
+                self.buffer_lines = 0;
+                self.record(self.buffer_start, values, sink);
+            }
+            None if self.buffer_lines >= MAX_RECORD_LINES => {
+                sink.fail(self.buffer_start, "Unterminated quoted field");
+                self.buffer.clear();
+                self.buffer_lines = 0;
+            }
+            None => {}
+        }
+    }
+
+    fn finish(&mut self, sink: &mut ParseSink) {
+        if self.buffer_lines > 0 {
+            sink.fail(self.buffer_start, "Unterminated quoted field");
+            self.buffer.clear();
+            self.buffer_lines = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::parse_lines;
+
+    #[test]
+    fn test_sniff_dialects() {
+        let semicolon = ["time;level;message", "1;INFO;\"a;b\"", "2;WARN;c"];
+        assert_eq!(
+            CsvDialect::sniff(&semicolon),
+            Some(CsvDialect {
+                delimiter: ';',
+                quote: '"',
+                has_header: true
+            })
+        );
+
+        let tabs = ["10.0.0.1\tGET\t200", "10.0.0.2\tPOST\t500"];
+        let dialect = CsvDialect::sniff(&tabs).unwrap();
+        assert_eq!(dialect.delimiter, '\t');
+        assert!(!dialect.has_header);
+
+        // A quoted field spanning lines still counts as one record
+        let multiline = [
+            "id,message",
+            "1,\"first line",
+            "second line, with comma\"",
+            "2,ok",
+        ];
+        assert_eq!(
+            CsvDialect::sniff(&multiline).map(|d| d.delimiter),
+            Some(',')
+        );
+
+        assert_eq!(CsvDialect::sniff(&["plain text", "more text"]), None);
+        assert_eq!(CsvDialect::sniff(&["a,b", "c,d,e"]), None);
+    }
+
+    #[test]
+    fn test_parse_multiline_records() {
+        let lines = [
+            "id|level|message",
+            "1|INFO|\"said \"\"hi\"\"",
+            "twice\"",
+            "2|WARN|plain|extra",
+        ];
+        let dialect = CsvDialect {
+            delimiter: '|',
+            ..Default::default()
+        };
+        let table = parse_lines(
+            &mut CsvParser::new(dialect),
+            lines.iter().enumerate().map(|(i, l)| (i as u64 + 1, *l)),
+        );
+
+        assert!(table.failures.is_empty());
+        assert_eq!(table.records.len(), 2);
+        assert_eq!(table.records[0].line_number, 2);
+        assert_eq!(table.records[0].get("id"), Some(&FieldValue::Int(1)));
+        assert_eq!(
+            table.records[0].get("message"),
+            Some(&FieldValue::Str("said \"hi\"\ntwice".to_string()))
+        );
+        assert_eq!(
+            table.records[1].get("column_4"),
+            Some(&FieldValue::Str("extra".to_string()))
+        );
+    }
+}
@@ -0,0 +1,580 @@
+use super::{FieldValue, LogParser, ParseSink};
+use crate::timestamp::parse_timestamp;
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// PostgreSQL line with a `log_line_prefix` starting with `%m` or `%t`, optionally `[%p]`
+fn postgres_line_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            r"^(?P<timestamp>\d{4}-\d{2}-\d{2}[ T]\d{2}:\d{2}:\d{2}(?:\.\d+)?(?: ?[A-Z]{2,5}|[+-]\d{2}(?::?\d{2})?)?)\s+(?:\[(?P<pid>\d+)\](?:-\d+)?:?\s+)?(?P<prefix>.*?)(?P<level>LOG|ERROR|WARNING|FATAL|PANIC|DETAIL|HINT|STATEMENT|CONTEXT|NOTICE|INFO|DEBUG[1-5]?|QUERY|LOCATION):\s+(?P<message>.*)$",
+        )
+        .unwrap()
+    })
+}
+
+/// `duration: 12.3 ms  statement: ...` and the extended-protocol `execute <name>:` variants
+fn postgres_duration_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"(?s)^duration: (?P<duration>[\d.]+) ms(?:\s+(?:statement|(?:execute|parse|bind) [^:]*): (?P<query>.*))?$")
+            .unwrap()
+    })
+}
+
+fn postgres_setting_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\b(user|db|app|client|host)=([^,\s]*)").unwrap())
+}
+
+fn mysql_user_host_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"^# User@Host: (?P<user>[^\[\s]*)\[[^\]]*\]\s*@\s*(?P<host>[^\s\[]*)\s*\[(?P<ip>[^\]]*)\](?:\s+Id:\s*(?P<id>\d+))?")
+            .unwrap()
+    })
+}
+
+fn mysql_pair_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"(\w+): +(\S+)").unwrap())
+}
+
+/// Whether a line starts a PostgreSQL log entry
+pub fn is_postgres_line(line: &str) -> bool {
+    postgres_line_regex().is_match(line)
+}
+
+/// Whether a line belongs to the header block of a MySQL slow query log entry
+pub fn is_mysql_slow_line(line: &str) -> bool {
+    ["# Time:", "# User@Host:", "# Query_time:", "SET timestamp="]
+        .iter()
+        .any(|p| line.starts_with(p))
+}
+
+/// Reduce a statement to its shape for grouping: literals become `?`, value lists collapse,
+/// whitespace is collapsed and the text is lowercased
+pub fn normalize_query(sql: &str) -> String {
+    static LIST: OnceLock<Regex> = OnceLock::new();
+    let list = LIST.get_or_init(|| Regex::new(r"\(\s*\?(?:\s*,\s*\?)+\s*\)").unwrap());
+
+    let mut out = String::with_capacity(sql.len());
+    let mut chars = sql.chars().peekable();
+    let mut prev = ' ';
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                // String literal, with '' and backslash escapes
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => {
+                            chars.next();
+                        }
+                        '\'' if chars.peek() == Some(&'\'') => {
+                            chars.next();
+                        }
+                        '\'' => break,
+                        _ => {}
+                    }
+                }
+                out.push('?');
+                prev = '?';
+            }
+            '$' if chars.peek().is_some_and(char::is_ascii_digit) => {
+                while chars.peek().is_some_and(char::is_ascii_digit) {
+                    chars.next();
+                }
+                out.push('?');
+                prev = '?';
+            }
+            c if c.is_ascii_digit() && !(prev.is_alphanumeric() || prev == '_') => {
+                while chars
+                    .peek()
+                    .is_some_and(|c| c.is_ascii_alphanumeric() || *c == '.')
+                {
+                    chars.next();
+                }
+                out.push('?');
+                prev = '?';
+            }
+            c if c.is_whitespace() => {
+                if !out.is_empty() && prev != ' ' {
+                    out.push(' ');
+                }
+                prev = ' ';
+            }
+            c => {
+                out.extend(c.to_lowercase());
+                prev = c;
+            }
+        }
+    }
+    let out = out.trim_end().trim_end_matches(';').trim_end();
+    list.replace_all(out, "(?)").into_owned()
+}
+
+/// Query, normalized query and duration columns shared by both databases
+fn push_query_fields(
+    fields: &mut Vec<(String, FieldValue)>,
+    duration_ms: Option<f64>,
+    query: Option<&str>,
+) {
+    fields.push((
+        "duration_ms".to_string(),
+        duration_ms.map_or(FieldValue::Null, FieldValue::Float),
+    ));
+    let query = query.map(str::trim).filter(|q| !q.is_empty());
+    fields.push((
+        "query".to_string(),
+        query.map_or(FieldValue::Null, |q| FieldValue::Str(q.to_string())),
+    ));
+    fields.push((
+        "normalized_query".to_string(),
+        query.map_or(FieldValue::Null, |q| FieldValue::Str(normalize_query(q))),
+    ));
+}
+
+fn text_or_null(text: &str) -> FieldValue {
+    if text.is_empty() || text == "[unknown]" {
+        FieldValue::Null
+    } else {
+        FieldValue::Str(text.to_string())
+    }
+}
+
+/// A PostgreSQL entry being assembled from its first line and continuations
+struct PostgresEntry {
+    line_number: u64,
+    pid: Option<String>,
+    head: Vec<(String, FieldValue)>,
+    /// `message` followed by attached DETAIL/HINT/CONTEXT/STATEMENT sections
+    sections: Vec<(String, String)>,
+}
+
+/// Parser for PostgreSQL server logs whose `log_line_prefix` starts with a timestamp
+/// Tab-indented continuation lines extend the entry, and DETAIL/HINT/CONTEXT/STATEMENT
+/// lines from the same process attach to the entry they follow
+#[derive(Default)]
+pub struct PostgresParser {
+    pending: Option<PostgresEntry>,
+}
+
+const POSTGRES_ATTACHED_LEVELS: &[&str] = &["DETAIL", "HINT", "CONTEXT", "STATEMENT", "QUERY"];
+
+impl PostgresParser {
+    fn flush(&mut self, sink: &mut ParseSink) {
+        let Some(entry) = self.pending.take() else {
+            return;
+        };
+        let mut fields = entry.head;
+        let section = |name: &str| {
+            entry
+                .sections
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, text)| text.as_str())
+        };
+        let message = section("message").unwrap_or_default();
+
+        let (duration_ms, query) = match postgres_duration_regex().captures(message) {
+            Some(caps) => (
+                caps["duration"].parse().ok(),
+                caps.name("query").map(|m| m.as_str()),
+            ),
+            None => (None, message.strip_prefix("statement: ")),
+        };
+        push_query_fields(&mut fields, duration_ms, query.or(section("statement")));
+        fields.push(("message".to_string(), FieldValue::Str(message.to_string())));
+        for name in ["detail", "hint", "context"] {
+            fields.push((
+                name.to_string(),
+                section(name).map_or(FieldValue::Null, text_or_null),
+            ));
+        }
+        sink.record(entry.line_number, fields);
+    }
+}
+
+impl LogParser for PostgresParser {
+    fn feed(&mut self, line_number: u64, line: &str, sink: &mut ParseSink) {
+        let Some(caps) = postgres_line_regex().captures(line) else {
+            if line.trim().is_empty() {
+                return;
+            }
+            match self.pending.as_mut().and_then(|e| e.sections.last_mut()) {
+                Some((_, text)) => {
+                    text.push('\n');
+                    text.push_str(line.strip_prefix('\t').unwrap_or(line));
+                }
+                None => sink.fail(line_number, "No PostgreSQL log prefix"),
+            }
+            return;
+        };
+
+        let pid = caps.name("pid").map(|m| m.as_str().to_string());
+        let level = &caps["level"];
+        let message = caps["message"].to_string();
+        if POSTGRES_ATTACHED_LEVELS.contains(&level) {
+            if let Some(entry) = self.pending.as_mut().filter(|e| e.pid == pid) {
+                entry.sections.push((level.to_ascii_lowercase(), message));
+                return;
+            }
+        }
+        self.flush(sink);
+
+        let mut head = Vec::with_capacity(16);
+        head.push((
+            "timestamp".to_string(),
+            parse_timestamp(&caps["timestamp"]).map_or(FieldValue::Null, FieldValue::Timestamp),
+        ));
+        head.push((
+            "pid".to_string(),
+            pid.as_deref().map_or(FieldValue::Null, FieldValue::infer),
+        ));
+
+        // `user=..,db=..` style prefixes, or the common `%u@%d`
+        let prefix = caps["prefix"].trim();
+        let mut user = None;
+        let mut database = None;
+        let mut application = None;
+        let mut client = None;
+        for setting in postgres_setting_regex().captures_iter(prefix) {
+            let value = setting.get(2).map_or("", |m| m.as_str());
+            match &setting[1] {
+                "user" => user = Some(value),
+                "db" => database = Some(value),
+                "app" => application = Some(value),
+                _ => client = Some(value),
+            }
+        }
+        if user.is_none() {
+            if let Some((u, d)) = prefix
+                .split_whitespace()
+                .find_map(|word| word.split_once('@'))
+            {
+                user = Some(u);
+                database = Some(d);
+            }
+        }
+        for (name, value) in [
+            ("user", user),
+            ("database", database),
+            ("application", application),
+            ("client", client),
+        ] {
+            head.push((
+                name.to_string(),
+                value.map_or(FieldValue::Null, text_or_null),
+            ));
+        }
+        head.push(("level".to_string(), FieldValue::Str(level.to_string())));
+
+        self.pending = Some(PostgresEntry {
+            line_number,
+            pid,
+            head,
+            sections: vec![("message".to_string(), message)],
+        });
+    }
+
+    fn finish(&mut self, sink: &mut ParseSink) {
+        self.flush(sink);
+    }
+}
+
+/// A MySQL slow query entry being assembled
+#[derive(Default)]
+struct MysqlEntry {
+    line_number: u64,
+    time: Option<i64>,
+    set_timestamp: Option<i64>,
+    header: Vec<(String, FieldValue)>,
+    query_lines: Vec<String>,
+}
+
+/// Parser for MySQL and MariaDB slow query logs
+/// Each entry is a block of `# Time:`, `# User@Host:` and `# Query_time:` comments followed by
+/// the statement, which may span several lines
+#[derive(Default)]
+pub struct MysqlSlowParser {
+    pending: Option<MysqlEntry>,
+    /// Schema from the last `use` statement, which MySQL only logs when it changes
+    database: Option<String>,
+}
+
+impl MysqlSlowParser {
+    fn flush(&mut self, sink: &mut ParseSink) {
+        let Some(entry) = self.pending.take() else {
+            return;
+        };
+        let mut fields = Vec::with_capacity(entry.header.len() + 6);
+        fields.push((
+            "timestamp".to_string(),
+            entry
+                .time
+                .or(entry.set_timestamp)
+                .map_or(FieldValue::Null, FieldValue::Timestamp),
+        ));
+        let mut duration_ms = None;
+        let mut has_database = false;
+        for (name, value) in entry.header {
+            match (name.as_str(), &value) {
+                ("query_time", FieldValue::Float(secs)) => duration_ms = Some(secs * 1000.0),
+                ("query_time", FieldValue::Int(secs)) => duration_ms = Some(*secs as f64 * 1000.0),
+                ("lock_time", FieldValue::Float(secs)) => {
+                    fields.push(("lock_time_ms".to_string(), FieldValue::Float(secs * 1000.0)))
+                }
+                ("lock_time", FieldValue::Int(secs)) => fields.push((
+                    "lock_time_ms".to_string(),
+                    FieldValue::Float(*secs as f64 * 1000.0),
+                )),
+                _ => {
+                    has_database |= name == "database";
+                    fields.push((name, value));
+                }
+            }
+        }
+        if !has_database {
+            fields.push((
+                "database".to_string(),
+                self.database
+                    .as_deref()
+                    .map_or(FieldValue::Null, text_or_null),
+            ));
+        }
+        let query = entry.query_lines.join("\n");
+        push_query_fields(&mut fields, duration_ms, Some(&query));
+        sink.record(entry.line_number, fields);
+    }
+
+    fn entry(&mut self, line_number: u64, sink: &mut ParseSink) -> &mut MysqlEntry {
+        // A header after statement text starts the next entry
+        if self
+            .pending
+            .as_ref()
+            .is_some_and(|e| !e.query_lines.is_empty())
+        {
+            self.flush(sink);
+        }
+        self.pending.get_or_insert_with(|| MysqlEntry {
+            line_number,
+            ..Default::default()
+        })
+    }
+}
+
+impl LogParser for MysqlSlowParser {
+    fn feed(&mut self, line_number: u64, line: &str, sink: &mut ParseSink) {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            return;
+        }
+
+        if let Some(time) = trimmed.strip_prefix("# Time:") {
+            let time = parse_timestamp(time.trim());
+            self.entry(line_number, sink).time = time;
+        } else if let Some(caps) = mysql_user_host_regex().captures(trimmed) {
+            let host = caps
+                .name("host")
+                .map(|m| m.as_str())
+                .filter(|h| !h.is_empty())
+                .or(caps.name("ip").map(|m| m.as_str()))
+                .unwrap_or_default();
+            let mut header = vec![
+                ("user".to_string(), text_or_null(&caps["user"])),
+                ("host".to_string(), text_or_null(host)),
+            ];
+            if let Some(id) = caps.name("id") {
+                header.push(("thread_id".to_string(), FieldValue::infer(id.as_str())));
+            }
+            self.entry(line_number, sink).header.extend(header);
+        } else if let Some(comment) = trimmed.strip_prefix('#') {
+            let pairs: Vec<(String, FieldValue)> = mysql_pair_regex()
+                .captures_iter(comment)
+                .map(|caps| {
+                    let name = match caps[1].to_ascii_lowercase().as_str() {
+                        "schema" => "database".to_string(),
+                        "id" => "thread_id".to_string(),
+                        name => name.to_string(),
+                    };
+                    (name, FieldValue::infer(&caps[2]))
+                })
+                .collect();
+            if !pairs.is_empty() {
+                let entry = self.entry(line_number, sink);
+                for (name, value) in pairs {
+                    if !entry.header.iter().any(|(n, _)| *n == name) {
+                        entry.header.push((name, value));
+                    }
+                }
+            }
+        } else if self.pending.is_none() {
+            // Server banner lines printed at startup
+            let banner = trimmed.contains(", Version: ")
+                || trimmed.starts_with("Tcp port:")
+                || trimmed.starts_with("Time ");
+            if !banner {
+                sink.fail(line_number, "Statement outside a slow query entry");
+            }
+        } else if let Some(seconds) = trimmed
+            .strip_prefix("SET timestamp=")
+            .and_then(|s| s.trim_end_matches(';').parse::<i64>().ok())
+        {
+            if let Some(entry) = self.pending.as_mut() {
+                entry.set_timestamp = Some(seconds * 1000);
+            }
+        } else if let Some(database) = trimmed.strip_prefix("use ").filter(|_| {
+            self.pending
+                .as_ref()
+                .is_some_and(|e| e.query_lines.is_empty())
+        }) {
+            self.database = Some(database.trim_end_matches(';').trim_matches('`').to_string());
+        } else if let Some(entry) = self.pending.as_mut() {
+            entry.query_lines.push(line.to_string());
+        }
+    }
+
+    fn finish(&mut self, sink: &mut ParseSink) {
+        self.flush(sink);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::parse_lines;
+
+    fn parse(parser: &mut dyn LogParser, text: &str) -> crate::parsers::ParsedTable {
+        parse_lines(
+            parser,
+            text.lines().enumerate().map(|(i, l)| (i as u64 + 1, l)),
+        )
+    }
+
+    #[test]
+    fn test_normalize_query() {
+        assert_eq!(
+            normalize_query(
+                "SELECT *  FROM users\n WHERE id = 42 AND name = 'O''Brien' AND t2.x IN (1, 2,3);"
+            ),
+            "select * from users where id = ? and name = ? and t2.x in (?)"
+        );
+        assert_eq!(
+            normalize_query("UPDATE t SET v = $1 WHERE k = -1.5"),
+            "update t set v = ? where k = -?"
+        );
+    }
+
+    #[test]
+    fn test_postgres_entries() {
+        let text = "2024-01-01 00:00:00.100 UTC [4242] app@shop LOG:  duration: 1502.250 ms  statement: SELECT *\n\tFROM orders WHERE id = 7\n2024-01-01 00:00:01 UTC [4243] user=bob,db=shop,app=psql,client=10.0.0.9 ERROR:  relation \"nope\" does not exist at character 15\n2024-01-01 00:00:01 UTC [4243] user=bob,db=shop,app=psql,client=10.0.0.9 STATEMENT:  SELECT * FROM nope\nstray line";
+        let table = parse(&mut PostgresParser::default(), text);
+        assert_eq!(table.records.len(), 2);
+
+        let slow = &table.records[0];
+        assert_eq!(slow.line_number, 1);
+        assert_eq!(
+            slow.get("timestamp"),
+            Some(&FieldValue::Timestamp(1_704_067_200_100))
+        );
+        assert_eq!(slow.get("user"), Some(&FieldValue::Str("app".to_string())));
+        assert_eq!(
+            slow.get("database"),
+            Some(&FieldValue::Str("shop".to_string()))
+        );
+        assert_eq!(slow.get("duration_ms"), Some(&FieldValue::Float(1502.25)));
+        assert_eq!(
+            slow.get("query"),
+            Some(&FieldValue::Str(
+                "SELECT *\nFROM orders WHERE id = 7".to_string()
+            ))
+        );
+        assert_eq!(
+            slow.get("normalized_query"),
+            Some(&FieldValue::Str(
+                "select * from orders where id = ?".to_string()
+            ))
+        );
+
+        // The STATEMENT line attaches to the error, and the stray line continues it
+        let error = &table.records[1];
+        assert_eq!(
+            error.get("level"),
+            Some(&FieldValue::Str("ERROR".to_string()))
+        );
+        assert_eq!(
+            error.get("client"),
+            Some(&FieldValue::Str("10.0.0.9".to_string()))
+        );
+        assert_eq!(
+            error.get("query"),
+            Some(&FieldValue::Str(
+                "SELECT * FROM nope\nstray line".to_string()
+            ))
+        );
+        assert!(table.failures.is_empty());
+    }
+
+    #[test]
+    fn test_mysql_slow_entries() {
+        let text = "/usr/sbin/mysqld, Version: 8.0.36 (MySQL Community Server - GPL). started with:
+Tcp port: 3306  Unix socket: /var/run/mysqld/mysqld.sock
+Time                 Id Command    Argument
+# Time: 2024-01-01T00:00:00.500000Z
+# User@Host: app[app] @ web01 [10.0.0.5]  Id:    12
+# Query_time: 2.500000  Lock_time: 0.000100 Rows_sent: 1  Rows_examined: 50000
+use shop;
+SET timestamp=1704067200;
+SELECT *
+  FROM orders WHERE customer = 'acme';
+# User@Host: root[root] @ localhost []  Id:    13
+# Query_time: 0.75  Lock_time: 0 Rows_sent: 0  Rows_examined: 10
+SET timestamp=1704067260;
+DELETE FROM carts WHERE id = 9;";
+        let table = parse(&mut MysqlSlowParser::default(), text);
+        assert!(table.failures.is_empty());
+        assert_eq!(table.records.len(), 2);
+
+        let first = &table.records[0];
+        assert_eq!(first.line_number, 4);
+        assert_eq!(
+            first.get("timestamp"),
+            Some(&FieldValue::Timestamp(1_704_067_200_500))
+        );
+        assert_eq!(first.get("user"), Some(&FieldValue::Str("app".to_string())));
+        assert_eq!(
+            first.get("host"),
+            Some(&FieldValue::Str("web01".to_string()))
+        );
+        assert_eq!(first.get("thread_id"), Some(&FieldValue::Int(12)));
+        assert_eq!(first.get("duration_ms"), Some(&FieldValue::Float(2500.0)));
+        assert_eq!(first.get("rows_examined"), Some(&FieldValue::Int(50000)));
+        assert_eq!(
+            first.get("database"),
+            Some(&FieldValue::Str("shop".to_string()))
+        );
+        assert_eq!(
+            first.get("normalized_query"),
+            Some(&FieldValue::Str(
+                "select * from orders where customer = ?".to_string()
+            ))
+        );
+
+        let second = &table.records[1];
+        assert_eq!(second.line_number, 11);
+        assert_eq!(
+            second.get("timestamp"),
+            Some(&FieldValue::Timestamp(1_704_067_260_000))
+        );
+        assert_eq!(
+            second.get("host"),
+            Some(&FieldValue::Str("localhost".to_string()))
+        );
+        assert_eq!(second.get("lock_time_ms"), Some(&FieldValue::Float(0.0)));
+        assert_eq!(
+            second.get("database"),
+            Some(&FieldValue::Str("shop".to_string()))
+        );
+    }
+}
@@ -0,0 +1,117 @@
+use super::{FieldValue, LogParser, ParseSink};
+use crate::timestamp::parse_timestamp;
+use serde_json::Value as JsonValue;
+
+/// Keys whose string values are parsed as timestamps
+const TIME_KEYS: &[&str] = &["timestamp", "time", "ts", "@timestamp", "datetime", "date"];
+
+/// How a single line is structured
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineFormat {
+    Json,
+    Text,
+}
+
+impl LineFormat {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineFormat::Json => "json",
+            LineFormat::Text => "text",
+        }
+    }
+}
+
+/// Cheaply classify a line by its shape; JSON lines still need to parse to produce a record
+pub fn classify_line(line: &str) -> LineFormat {
+    let trimmed = line.trim();
+    if trimmed.starts_with('{') && trimmed.ends_with('}') {
+        LineFormat::Json
+    } else {
+        LineFormat::Text
+    }
+}
+
+/// Parser for JSON lines, tolerating plain-text lines (banners, stack traces) between them
+/// Top-level keys become columns; nested objects and arrays are kept as JSON text
+pub struct JsonParser;
+
+impl LogParser for JsonParser {
+    fn feed(&mut self, line_number: u64, line: &str, sink: &mut ParseSink) {
+        if line.trim().is_empty() {
+            return;
+        }
+        if classify_line(line) == LineFormat::Text {
+            sink.fail(line_number, "Not a JSON line");
+            return;
+        }
+        let object = match serde_json::from_str::<JsonValue>(line) {
+            Ok(JsonValue::Object(object)) => object,
+            Ok(_) => {
+                sink.fail(line_number, "Expected a JSON object");
+                return;
+            }
+            Err(err) => {
+                sink.fail(line_number, err.to_string());
+                return;
+            }
+        };
+
+        let fields = object
+            .into_iter()
+            .map(|(key, value)| {
+                let typed = match value {
+                    JsonValue::Null => FieldValue::Null,
+                    JsonValue::Bool(b) => FieldValue::Bool(b),
+                    JsonValue::Number(n) => n
+                        .as_i64()
+                        .map(FieldValue::Int)
+                        .or_else(|| n.as_f64().map(FieldValue::Float))
+                        .unwrap_or(FieldValue::Null),
+                    JsonValue::String(s) if TIME_KEYS.contains(&key.as_str()) => {
+                        parse_timestamp(&s).map_or(FieldValue::Str(s), FieldValue::Timestamp)
+                    }
+                    JsonValue::String(s) => FieldValue::Str(s),
+                    nested => FieldValue::Str(nested.to_string()),
+                };
+                (key, typed)
+            })
+            .collect();
+        sink.record(line_number, fields);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::{parse_lines, ColumnType};
+
+    #[test]
+    fn test_mixed_json_and_text() {
+        let lines = [
+            "Starting server v1.2",
+            r#"{"ts":"2024-01-01T00:00:00Z","level":"info","latency":12,"tags":["a"]}"#,
+            "java.lang.IllegalStateException: boom",
+            "\tat com.example.Main.run(Main.java:42)",
+            r#"{"ts":"2024-01-01T00:00:01Z","level":"error","latency":3.5}"#,
+        ];
+        let table = parse_lines(
+            &mut JsonParser,
+            lines.iter().enumerate().map(|(i, l)| (i as u64 + 1, *l)),
+        );
+
+        assert_eq!(table.records.len(), 2);
+        assert_eq!(table.failures.len(), 3);
+        assert_eq!(table.records[1].line_number, 5);
+        assert_eq!(
+            table.records[0].get("ts"),
+            Some(&FieldValue::Timestamp(1_704_067_200_000))
+        );
+        assert_eq!(
+            table.records[0].get("tags"),
+            Some(&FieldValue::Str(r#"["a"]"#.to_string()))
+        );
+
+        let latency = table.columns.iter().find(|c| c.name == "latency").unwrap();
+        assert_eq!(latency.column_type, ColumnType::Float64);
+    }
+}
@@ -0,0 +1,144 @@
+use super::cef::parse_event_time;
+use super::{FieldValue, LogParser, ParseSink};
+
+/// Header column names, in header order after the version
+const HEADER_COLUMNS: [&str; 4] = ["vendor", "product", "product_version", "event_id"];
+
+/// Parser for QRadar LEEF 1.0 (tab-delimited attributes) and 2.0 (custom delimiter)
+/// `LEEF:Version|Vendor|Product|Version|EventID|[Delimiter|]key=value<delim>key=value...`
+pub struct LeefParser;
+
+/// Byte offset of the `LEEF:` header in a line, if present
+pub fn find_leef(line: &str) -> Option<usize> {
+    let start = line.find("LEEF:")?;
+    let rest = &line[start + 5..];
+    let has_version = rest.starts_with(|c: char| c.is_ascii_digit());
+    (has_version && rest.matches('|').count() >= 5).then_some(start)
+}
+
+impl LogParser for LeefParser {
+    fn feed(&mut self, line_number: u64, line: &str, sink: &mut ParseSink) {
+        if line.trim().is_empty() {
+            return;
+        }
+        let Some(start) = find_leef(line) else {
+            sink.fail(line_number, "No LEEF header");
+            return;
+        };
+        let header: Vec<&str> = line[start + 5..].splitn(6, '|').collect();
+        let version = header[0];
+        let mut attributes = header[5];
+
+        // LEEF 2.0 may name its delimiter in an extra header field
+        let mut delimiter = '\t';
+        if version.starts_with('2') {
+            if let Some((field, rest)) = attributes.split_once('|') {
+                if let Some(parsed) = parse_delimiter(field) {
+                    delimiter = parsed;
+                    attributes = rest;
+                }
+            }
+        }
+
+        let mut fields = Vec::with_capacity(16);
+        let prefix = line[..start].trim();
+        if !prefix.is_empty() {
+            fields.push(("prefix".to_string(), FieldValue::Str(prefix.to_string())));
+        }
+        fields.push((
+            "leef_version".to_string(),
+            FieldValue::Str(version.to_string()),
+        ));
+        for (name, value) in HEADER_COLUMNS.iter().zip(&header[1..5]) {
+            let value = if value.is_empty() {
+                FieldValue::Null
+            } else {
+                FieldValue::Str(value.to_string())
+            };
+            fields.push((name.to_string(), value));
+        }
+
+        for attribute in attributes.split(delimiter) {
+            let Some((key, value)) = attribute.split_once('=') else {
+                continue;
+            };
+            let key = key.trim();
+            if key.is_empty() {
+                continue;
+            }
+            let typed = if key == "devTime" {
+                parse_event_time(value)
+                    .map_or_else(|| FieldValue::infer(value), FieldValue::Timestamp)
+            } else {
+                FieldValue::infer(value)
+            };
+            fields.push((key.to_string(), typed));
+        }
+        sink.record(line_number, fields);
+    }
+}
+
+/// A LEEF 2.0 delimiter: a single character or a hex code such as `0x5E` or `x09`
+fn parse_delimiter(field: &str) -> Option<char> {
+    let mut chars = field.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => {
+            let hex = field
+                .strip_prefix("0x")
+                .or_else(|| field.strip_prefix('x'))?;
+            u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_leef_1() {
+        let line = "LEEF:1.0|Microsoft|MSExchange|4.0 SP1|15345|src=192.0.2.0\tdst=172.50.123.1\tsev=5\tdevTime=Jan 01 2024 00:00:00\tusrName=joe";
+        let mut sink = ParseSink::default();
+        LeefParser.feed(1, line, &mut sink);
+        let record = &sink.records[0];
+
+        assert_eq!(
+            record.get("product_version"),
+            Some(&FieldValue::Str("4.0 SP1".to_string()))
+        );
+        assert_eq!(
+            record.get("event_id"),
+            Some(&FieldValue::Str("15345".to_string()))
+        );
+        assert_eq!(record.get("sev"), Some(&FieldValue::Int(5)));
+        assert_eq!(
+            record.get("devTime"),
+            Some(&FieldValue::Timestamp(1_704_067_200_000))
+        );
+        assert_eq!(
+            record.get("usrName"),
+            Some(&FieldValue::Str("joe".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_leef_2_custom_delimiter() {
+        let line = "<13>Jan 18 11:07:53 host LEEF:2.0|Lancope|StealthWatch|1.0|41|^|src=10.0.1.8^dst=10.0.0.5^dstPort=443";
+        let mut sink = ParseSink::default();
+        LeefParser.feed(1, line, &mut sink);
+        let record = &sink.records[0];
+
+        assert_eq!(
+            record.get("prefix"),
+            Some(&FieldValue::Str("<13>Jan 18 11:07:53 host".to_string()))
+        );
+        assert_eq!(
+            record.get("dst"),
+            Some(&FieldValue::Str("10.0.0.5".to_string()))
+        );
+        assert_eq!(record.get("dstPort"), Some(&FieldValue::Int(443)));
+        assert_eq!(parse_delimiter("0x5E"), Some('^'));
+        assert_eq!(parse_delimiter("x09"), Some('\t'));
+    }
+}
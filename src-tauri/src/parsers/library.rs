@@ -0,0 +1,305 @@
+use super::csv::{CsvDialect, CsvParser};
+use super::pattern::PatternParser;
+use super::schema::SchemaOverride;
+use super::{
+    load_json, parse_lines, save_json, Column, LogParser, ParseFailure, ParserKind, Record,
+};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// File the profiles are persisted to, inside the app config directory
+const LIBRARY_FILE: &str = "parse_profiles.json";
+
+/// How a profile splits lines into fields
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ParserSpec {
+    /// One of the built-in formats
+    Builtin { format: ParserKind },
+    /// A regex whose named groups become columns
+    Regex { pattern: String },
+    /// A grok pattern, expanded to a regex
+    Grok { pattern: String },
+    /// Delimited values in a fixed dialect
+    Csv { dialect: CsvDialect },
+}
+
+impl ParserSpec {
+    pub fn create(&self) -> Result<Box<dyn LogParser>, String> {
+        Ok(match self {
+            ParserSpec::Builtin { format } => format.create(),
+            ParserSpec::Regex { pattern } => Box::new(PatternParser::regex(pattern)?),
+            ParserSpec::Grok { pattern } => Box::new(PatternParser::grok(pattern)?),
+            ParserSpec::Csv { dialect } => Box::new(CsvParser::new(*dialect)),
+        })
+    }
+
+    /// The built-in format the spec parses, if any
+    pub fn format(&self) -> Option<ParserKind> {
+        match self {
+            ParserSpec::Builtin { format } => Some(*format),
+            ParserSpec::Csv { .. } => Some(ParserKind::Csv),
+            ParserSpec::Regex { .. } | ParserSpec::Grok { .. } => None,
+        }
+    }
+}
+
+/// A named, saved way of parsing files, applied automatically to paths matching its globs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParseProfile {
+    pub name: String,
+    /// Globs such as `*/nginx/access.log*`; `*` also matches across `/`, and globs without
+    /// a `/` are matched against the file name alone
+    #[serde(default)]
+    pub globs: Vec<String>,
+    pub parser: ParserSpec,
+    #[serde(default)]
+    pub schema_override: Option<SchemaOverride>,
+}
+
+impl ParseProfile {
+    /// Check that the profile is named and its parser and globs compile
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("A parse profile needs a name".to_string());
+        }
+        self.parser.create()?;
+        for glob in &self.globs {
+            glob_regex(glob)?;
+        }
+        Ok(())
+    }
+
+    pub fn matches_path(&self, path: &str) -> bool {
+        let path = path.replace('\\', "/");
+        let file_name = path.rsplit('/').next().unwrap_or(&path);
+        self.globs.iter().any(|glob| {
+            let target = if glob.contains('/') { &path } else { file_name };
+            glob_regex(glob).is_ok_and(|re| re.is_match(target))
+        })
+    }
+}
+
+/// Sample lines parsed with a candidate profile, before any table is registered
+#[derive(Debug, Serialize)]
+pub struct ParsePreview {
+    pub columns: Vec<Column>,
+    pub records: Vec<Record>,
+    /// Why each sampled line that produced no record failed
+    pub failures: Vec<ParseFailure>,
+    pub sampled_lines: usize,
+    pub success_rate: f64,
+}
+
+impl ParseProfile {
+    /// Parse `(line_number, line)` samples with the profile and apply its schema override
+    pub fn preview<I, S>(&self, lines: I) -> Result<ParsePreview, String>
+    where
+        I: IntoIterator<Item = (u64, S)>,
+        S: AsRef<str>,
+    {
+        let mut parser = self.parser.create()?;
+        let mut sampled_lines = 0;
+        let lines = lines.into_iter().inspect(|_| sampled_lines += 1);
+        let mut table = parse_lines(parser.as_mut(), lines);
+        if let Some(schema_override) = &self.schema_override {
+            schema_override.apply(&mut table)?;
+        }
+        Ok(ParsePreview {
+            success_rate: table.success_rate(),
+            columns: table.columns,
+            records: table.records,
+            failures: table.failures,
+            sampled_lines,
+        })
+    }
+}
+
+/// Translate a glob into an anchored regex: `*` is any run of characters, `?` any single
+/// character and `[...]` a character class
+pub(crate) fn glob_regex(glob: &str) -> Result<Regex, String> {
+    let mut pattern = String::from("^");
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => {
+                while chars.peek() == Some(&'*') {
+                    chars.next();
+                }
+                pattern.push_str(".*");
+            }
+            '?' => pattern.push('.'),
+            '[' => {
+                let mut class = String::new();
+                for c in chars.by_ref() {
+                    if c == ']' {
+                        break;
+                    }
+                    class.push(c);
+                }
+                let class = class
+                    .strip_prefix('!')
+                    .map_or(class.clone(), |rest| format!("^{}", rest));
+                pattern.push_str(&format!("[{}]", class.replace('\\', r"\\")));
+            }
+            c => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    pattern.push('$');
+    Regex::new(&pattern).map_err(|e| format!("Invalid glob \"{}\": {}", glob, e))
+}
+
+/// Saved parse profiles, in the order they are tried against a path
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ProfileLibrary {
+    #[serde(skip)]
+    path: PathBuf,
+    profiles: Vec<ParseProfile>,
+}
+
+impl ProfileLibrary {
+    /// Load the library from a config directory; a missing file is an empty library
+    pub fn load(dir: &Path) -> io::Result<Self> {
+        let path = dir.join(LIBRARY_FILE);
+        let mut library: ProfileLibrary = load_json(&path)?;
+        library.path = path;
+        Ok(library)
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        save_json(&self.path, self)
+    }
+
+    pub fn profiles(&self) -> &[ParseProfile] {
+        &self.profiles
+    }
+
+    pub fn find(&self, name: &str) -> Option<&ParseProfile> {
+        self.profiles.iter().find(|p| p.name == name)
+    }
+
+    pub fn find_mut(&mut self, name: &str) -> Option<&mut ParseProfile> {
+        self.profiles.iter_mut().find(|p| p.name == name)
+    }
+
+    /// Add a profile, replacing any profile of the same name in place
+    pub fn upsert(&mut self, profile: ParseProfile) {
+        match self.find_mut(&profile.name) {
+            Some(existing) => *existing = profile,
+            None => self.profiles.push(profile),
+        }
+    }
+
+    /// Remove a profile, returning whether it existed
+    pub fn remove(&mut self, name: &str) -> bool {
+        let before = self.profiles.len();
+        self.profiles.retain(|p| p.name != name);
+        self.profiles.len() != before
+    }
+
+    /// First profile with a glob matching the path
+    pub fn match_path(&self, path: &str) -> Option<&ParseProfile> {
+        self.profiles.iter().find(|p| p.matches_path(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::schema::ColumnOverride;
+
+    fn profile(name: &str, globs: &[&str]) -> ParseProfile {
+        ParseProfile {
+            name: name.to_string(),
+            globs: globs.iter().map(|g| g.to_string()).collect(),
+            parser: ParserSpec::Grok {
+                pattern: "%{COMMONAPACHELOG}".to_string(),
+            },
+            schema_override: None,
+        }
+    }
+
+    #[test]
+    fn test_glob_matching() {
+        let nginx = profile("nginx", &["*/nginx/access.log*"]);
+        assert!(nginx.matches_path("/var/log/nginx/access.log"));
+        assert!(nginx.matches_path("/var/log/nginx/access.log.2.gz"));
+        assert!(nginx.matches_path(r"C:\logs\nginx\access.log"));
+        assert!(!nginx.matches_path("/var/log/nginx/error.log"));
+
+        let by_name = profile("app", &["app-[0-9]?.log"]);
+        assert!(by_name.matches_path("/srv/app-12.log"));
+        assert!(!by_name.matches_path("/srv/app-x1.log"));
+    }
+
+    #[test]
+    fn test_library_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut library = ProfileLibrary::load(dir.path()).unwrap();
+        library.upsert(profile("nginx", &["*/nginx/access.log*"]));
+        library.upsert(profile("catch-all", &["*.log"]));
+        library.upsert(ParseProfile {
+            parser: ParserSpec::Builtin {
+                format: ParserKind::Json,
+            },
+            ..profile("nginx", &["*/nginx/*.json"])
+        });
+        library.save().unwrap();
+
+        let mut library = ProfileLibrary::load(dir.path()).unwrap();
+        assert_eq!(library.profiles().len(), 2);
+        assert_eq!(
+            library
+                .match_path("/var/log/nginx/app.json")
+                .map(|p| &p.name),
+            Some(&"nginx".to_string())
+        );
+        assert_eq!(
+            library
+                .match_path("/var/log/nginx/access.log")
+                .map(|p| &p.name),
+            Some(&"catch-all".to_string())
+        );
+        assert!(library.remove("catch-all"));
+        assert!(library.match_path("/tmp/x.log").is_none());
+
+        let invalid = ParseProfile {
+            parser: ParserSpec::Regex {
+                pattern: "(".to_string(),
+            },
+            ..profile("broken", &[])
+        };
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn test_preview_reports_failures() {
+        let profile = ParseProfile {
+            parser: ParserSpec::Regex {
+                pattern: r"^(?P<level>[A-Z]+) (?P<ms>\d+)ms$".to_string(),
+            },
+            schema_override: Some(SchemaOverride {
+                columns: vec![ColumnOverride {
+                    name: "ms".to_string(),
+                    rename: Some("elapsed_ms".to_string()),
+                    column_type: None,
+                    exclude: false,
+                }],
+                timestamp_column: None,
+            }),
+            ..profile("timings", &[])
+        };
+        let lines = ["INFO 12ms", "oops", "WARN 7ms"];
+        let preview = profile
+            .preview(lines.iter().enumerate().map(|(i, l)| (i as u64 + 1, *l)))
+            .unwrap();
+
+        assert_eq!(preview.sampled_lines, 3);
+        assert_eq!(preview.records.len(), 2);
+        assert_eq!(preview.columns[1].name, "elapsed_ms");
+        assert_eq!(preview.failures[0].line_number, 2);
+        assert!((preview.success_rate - 2.0 / 3.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,561 @@
+pub mod cef;
+pub mod container;
+pub mod csv;
+pub mod database;
+pub mod json;
+pub mod leef;
+pub mod library;
+pub mod pattern;
+pub mod profile;
+pub mod schema;
+pub mod w3c;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::Path;
+
+/// A sample counts as mixed JSON and text when at least 1 in this many lines is JSON
+pub const MIXED_JSON_RATIO: usize = 5;
+
+/// Structured log formats that can be split into typed columns
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ParserKind {
+    /// ArcSight Common Event Format
+    Cef,
+    /// QRadar Log Event Extended Format
+    Leef,
+    /// W3C extended log format (IIS and proxies), laid out by `#Fields:` directives
+    W3c,
+    /// HAProxy HTTP log (`option httplog`) with its timer breakdown
+    Haproxy,
+    /// Envoy default access log
+    Envoy,
+    /// PostgreSQL server log with a timestamped `log_line_prefix`
+    Postgres,
+    /// MySQL/MariaDB slow query log
+    #[serde(rename = "mysql_slow")]
+    MysqlSlow,
+    /// Kubernetes CRI container log (containerd, CRI-O)
+    Cri,
+    /// JSON lines, possibly interleaved with plain text
+    Json,
+    /// Docker json-file logging driver
+    #[serde(rename = "docker_json")]
+    DockerJson,
+    /// Delimited values; the dialect is sniffed from the sample
+    Csv,
+}
+
+impl ParserKind {
+    /// Every parser, in the order detection tries them
+    pub const ALL: &'static [ParserKind] = &[
+        ParserKind::Cef,
+        ParserKind::Leef,
+        ParserKind::W3c,
+        ParserKind::Haproxy,
+        ParserKind::Envoy,
+        ParserKind::Postgres,
+        ParserKind::MysqlSlow,
+        ParserKind::Cri,
+        ParserKind::Json,
+        ParserKind::DockerJson,
+        ParserKind::Csv,
+    ];
+
+    /// Create a fresh parser instance
+    pub fn create(self) -> Box<dyn LogParser> {
+        match self {
+            ParserKind::Cef => Box::new(cef::CefParser),
+            ParserKind::Leef => Box::new(leef::LeefParser),
+            ParserKind::W3c => Box::<w3c::W3cParser>::default(),
+            ParserKind::Haproxy => Box::new(profile::ProfileParser::new(&profile::HAPROXY)),
+            ParserKind::Envoy => Box::new(profile::ProfileParser::new(&profile::ENVOY)),
+            ParserKind::Postgres => Box::<database::PostgresParser>::default(),
+            ParserKind::MysqlSlow => Box::<database::MysqlSlowParser>::default(),
+            ParserKind::Cri => Box::<container::CriParser>::default(),
+            ParserKind::Json => Box::new(json::JsonParser),
+            ParserKind::DockerJson => Box::<container::DockerJsonParser>::default(),
+            ParserKind::Csv => Box::new(csv::CsvParser::new(csv::CsvDialect::default())),
+        }
+    }
+
+    /// Create a parser tuned to a sample of the input, such as a CSV parser in the sniffed dialect
+    pub fn create_for_sample<S: AsRef<str>>(self, sample: &[S]) -> Box<dyn LogParser> {
+        match self {
+            ParserKind::Csv => Box::new(csv::CsvParser::new(
+                csv::CsvDialect::sniff(sample).unwrap_or_default(),
+            )),
+            kind => kind.create(),
+        }
+    }
+
+    /// Whether a single line looks like this format
+    pub fn sniff(self, line: &str) -> bool {
+        match self {
+            ParserKind::Cef => cef::find_cef(line).is_some(),
+            ParserKind::Leef => leef::find_leef(line).is_some(),
+            // Entries carry no marker of their own; only the directive identifies the format
+            ParserKind::W3c => w3c::is_fields_directive(line),
+            ParserKind::Haproxy => profile::HAPROXY.matches(line),
+            ParserKind::Envoy => profile::ENVOY.matches(line),
+            // Multi-line statements continue on tab-indented lines
+            ParserKind::Postgres => database::is_postgres_line(line) || line.starts_with('\t'),
+            ParserKind::MysqlSlow => database::is_mysql_slow_line(line),
+            ParserKind::Cri => container::is_cri_line(line),
+            ParserKind::Json => json::classify_line(line) == json::LineFormat::Json,
+            ParserKind::DockerJson => container::is_docker_json_line(line),
+            // Delimiters are only meaningful across several lines; see `detect`
+            ParserKind::Csv => false,
+        }
+    }
+
+    /// Pick the format most sample lines match; a majority is required unless the
+    /// sample contains a W3C `#Fields:` directive. Samples with a minority of JSON lines
+    /// among plain text fall back to JSON, and consistently delimited samples to CSV
+    pub fn detect<'a, I>(sample: I) -> Option<ParserKind>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let lines: Vec<&str> = sample
+            .into_iter()
+            .filter(|l| !l.trim().is_empty())
+            .collect();
+        if lines.is_empty() {
+            return None;
+        }
+        if lines.iter().any(|l| w3c::is_fields_directive(l)) {
+            return Some(ParserKind::W3c);
+        }
+        Self::ALL
+            .iter()
+            .map(|&kind| (kind, lines.iter().filter(|l| kind.sniff(l)).count()))
+            .filter(|&(_, matched)| matched * 2 > lines.len())
+            .max_by_key(|&(_, matched)| matched)
+            .map(|(kind, _)| kind)
+            .or_else(|| {
+                let json_lines = lines.iter().filter(|l| ParserKind::Json.sniff(l)).count();
+                (json_lines * MIXED_JSON_RATIO >= lines.len()).then_some(ParserKind::Json)
+            })
+            .or_else(|| csv::CsvDialect::sniff(&lines).map(|_| ParserKind::Csv))
+    }
+}
+
+/// A typed field value
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum FieldValue {
+    Null,
+    Str(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    /// Milliseconds since the Unix epoch
+    Timestamp(i64),
+}
+
+impl FieldValue {
+    /// Type a raw text value: integers and decimals become numbers, everything else stays text
+    pub fn infer(text: &str) -> FieldValue {
+        if text.is_empty() {
+            return FieldValue::Null;
+        }
+        let digits = text.strip_prefix('-').unwrap_or(text);
+        // Keep zero-padded identifiers like "007" as text
+        let padded = digits.len() > 1 && digits.starts_with('0') && !digits.starts_with("0.");
+        if !padded && !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) {
+            if let Ok(value) = text.parse() {
+                return FieldValue::Int(value);
+            }
+        }
+        if !padded && digits.bytes().filter(|&b| b == b'.').count() == 1 {
+            if let Ok(value) = text.parse::<f64>() {
+                if value.is_finite() {
+                    return FieldValue::Float(value);
+                }
+            }
+        }
+        FieldValue::Str(text.to_string())
+    }
+
+    pub fn column_type(&self) -> Option<ColumnType> {
+        match self {
+            FieldValue::Null => None,
+            FieldValue::Str(_) => Some(ColumnType::Utf8),
+            FieldValue::Int(_) => Some(ColumnType::Int64),
+            FieldValue::Float(_) => Some(ColumnType::Float64),
+            FieldValue::Bool(_) => Some(ColumnType::Boolean),
+            FieldValue::Timestamp(_) => Some(ColumnType::Timestamp),
+        }
+    }
+
+    /// Text rendering used when a column falls back to Utf8
+    pub fn to_text(&self) -> Option<String> {
+        match self {
+            FieldValue::Null => None,
+            FieldValue::Str(s) => Some(s.clone()),
+            FieldValue::Int(v) => Some(v.to_string()),
+            FieldValue::Float(v) => Some(v.to_string()),
+            FieldValue::Bool(v) => Some(v.to_string()),
+            FieldValue::Timestamp(ms) => Some(crate::timestamp::format_timestamp(*ms)),
+        }
+    }
+}
+
+/// Column type of a parsed table
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnType {
+    Utf8,
+    Int64,
+    Float64,
+    Boolean,
+    Timestamp,
+}
+
+impl ColumnType {
+    /// Widest type able to hold values of both types
+    pub fn unify(self, other: ColumnType) -> ColumnType {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (ColumnType::Int64, ColumnType::Float64) | (ColumnType::Float64, ColumnType::Int64) => {
+                ColumnType::Float64
+            }
+            _ => ColumnType::Utf8,
+        }
+    }
+}
+
+/// A column of a parsed table
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Column {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub column_type: ColumnType,
+}
+
+/// One parsed entry, which may span several source lines
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Record {
+    /// 1-based number of the first source line, matching the `logs` table
+    pub line_number: u64,
+    pub fields: Vec<(String, FieldValue)>,
+}
+
+impl Record {
+    pub fn get(&self, name: &str) -> Option<&FieldValue> {
+        self.fields.iter().find(|(n, _)| n == name).map(|(_, v)| v)
+    }
+}
+
+/// A line the parser could not make sense of
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ParseFailure {
+    pub line_number: u64,
+    pub error: String,
+}
+
+/// Collects the output of a parser
+#[derive(Debug, Default)]
+pub struct ParseSink {
+    pub records: Vec<Record>,
+    pub failures: Vec<ParseFailure>,
+}
+
+impl ParseSink {
+    pub fn record(&mut self, line_number: u64, fields: Vec<(String, FieldValue)>) {
+        self.records.push(Record {
+            line_number,
+            fields,
+        });
+    }
+
+    pub fn fail(&mut self, line_number: u64, error: impl Into<String>) {
+        self.failures.push(ParseFailure {
+            line_number,
+            error: error.into(),
+        });
+    }
+}
+
+/// A line-fed parser; stateful so formats with directives or multi-line entries can be supported
+pub trait LogParser: Send {
+    /// Feed the next line (1-based `line_number`), pushing completed records and failures to `sink`
+    fn feed(&mut self, line_number: u64, line: &str, sink: &mut ParseSink);
+
+    /// Flush anything still being assembled at end of input
+    fn finish(&mut self, _sink: &mut ParseSink) {}
+}
+
+/// Parsed records with their inferred schema
+#[derive(Debug, Default)]
+pub struct ParsedTable {
+    pub columns: Vec<Column>,
+    pub records: Vec<Record>,
+    pub failures: Vec<ParseFailure>,
+}
+
+impl ParsedTable {
+    /// Share of parse attempts (records plus failed lines) that produced a record; 1 when
+    /// there was nothing to parse
+    pub fn success_rate(&self) -> f64 {
+        let attempts = self.records.len() + self.failures.len();
+        if attempts == 0 {
+            1.0
+        } else {
+            self.records.len() as f64 / attempts as f64
+        }
+    }
+
+    /// Table of failed lines with their error, for the `parse_errors` table
+    /// Like every parsed table it gets the raw text of each line when registered
+    pub fn errors_table(failures: &[ParseFailure]) -> ParsedTable {
+        let records = failures
+            .iter()
+            .map(|failure| Record {
+                line_number: failure.line_number,
+                fields: vec![("error".to_string(), FieldValue::Str(failure.error.clone()))],
+            })
+            .collect();
+        ParsedTable {
+            columns: vec![Column {
+                name: "error".to_string(),
+                column_type: ColumnType::Utf8,
+            }],
+            records,
+            failures: Vec::new(),
+        }
+    }
+}
+
+/// Run a parser over `(line_number, line)` pairs and infer the schema of the result
+pub fn parse_lines<I, S>(parser: &mut dyn LogParser, lines: I) -> ParsedTable
+where
+    I: IntoIterator<Item = (u64, S)>,
+    S: AsRef<str>,
+{
+    let mut sink = ParseSink::default();
+    for (line_number, line) in lines {
+        parser.feed(line_number, line.as_ref(), &mut sink);
+    }
+    parser.finish(&mut sink);
+
+    ParsedTable {
+        columns: infer_schema(&sink.records),
+        records: sink.records,
+        failures: sink.failures,
+    }
+}
+
+/// Union of the fields across records in first-seen order, with types widened to fit every value
+pub fn infer_schema(records: &[Record]) -> Vec<Column> {
+    let mut schema = SchemaBuilder::default();
+    schema.add(records);
+    schema.columns()
+}
+
+/// Schema inference that can be fed records in batches as they are parsed
+#[derive(Debug, Default)]
+pub struct SchemaBuilder {
+    columns: Vec<(String, Option<ColumnType>)>,
+    index: std::collections::HashMap<String, usize>,
+}
+
+impl SchemaBuilder {
+    pub fn add(&mut self, records: &[Record]) {
+        for record in records {
+            for (name, value) in &record.fields {
+                let slot = match self.index.get(name) {
+                    Some(&slot) => slot,
+                    None => {
+                        self.columns.push((name.clone(), None));
+                        self.index.insert(name.clone(), self.columns.len() - 1);
+                        self.columns.len() - 1
+                    }
+                };
+                if let Some(ty) = value.column_type() {
+                    let current = &mut self.columns[slot].1;
+                    *current = Some(current.map_or(ty, |c| c.unify(ty)));
+                }
+            }
+        }
+    }
+
+    /// Columns seen so far; columns with only NULLs are text
+    pub fn columns(&self) -> Vec<Column> {
+        self.columns
+            .iter()
+            .map(|(name, ty)| Column {
+                name: name.clone(),
+                column_type: ty.unwrap_or(ColumnType::Utf8),
+            })
+            .collect()
+    }
+}
+
+/// Split `key=value` pairs where values may contain spaces: each key is the word before an
+/// unescaped `=`, and its value runs up to the start of the next key
+pub fn split_key_values(text: &str) -> Vec<(&str, &str)> {
+    let bytes = text.as_bytes();
+    let mut equals = Vec::new();
+    let mut escaped = false;
+    for (i, &b) in bytes.iter().enumerate() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match b {
+            b'\\' => escaped = true,
+            b'=' => equals.push(i),
+            _ => {}
+        }
+    }
+
+    // Keys start after the last whitespace before their `=`
+    let mut keys: Vec<(usize, usize)> = Vec::new();
+    let mut floor = 0;
+    for &eq in &equals {
+        let start = bytes[floor..eq]
+            .iter()
+            .rposition(|b| b.is_ascii_whitespace())
+            .map_or(floor, |p| floor + p + 1);
+        if start < eq {
+            keys.push((start, eq));
+        }
+        floor = eq + 1;
+    }
+
+    keys.iter()
+        .enumerate()
+        .map(|(i, &(start, eq))| {
+            let end = keys.get(i + 1).map_or(text.len(), |&(next, _)| next);
+            (&text[start..eq], text[eq + 1..end].trim_end())
+        })
+        .collect()
+}
+
+/// Read a JSON settings file; a missing file gives the default value
+pub(crate) fn load_json<T: DeserializeOwned + Default>(path: &Path) -> io::Result<T> {
+    match std::fs::read(path) {
+        Ok(bytes) => serde_json::from_slice(&bytes)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(T::default()),
+        Err(err) => Err(err),
+    }
+}
+
+/// Write a JSON settings file, replacing the previous one only once the new one is complete
+pub(crate) fn save_json<T: Serialize>(path: &Path, value: &T) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let temp = path.with_extension("json.tmp");
+    std::fs::write(&temp, serde_json::to_vec_pretty(value)?)?;
+    std::fs::rename(&temp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_infer_values() {
+        assert_eq!(FieldValue::infer("443"), FieldValue::Int(443));
+        assert_eq!(FieldValue::infer("-2"), FieldValue::Int(-2));
+        assert_eq!(FieldValue::infer("0.25"), FieldValue::Float(0.25));
+        assert_eq!(FieldValue::infer("007"), FieldValue::Str("007".to_string()));
+        assert_eq!(
+            FieldValue::infer("10.0.0.1"),
+            FieldValue::Str("10.0.0.1".to_string())
+        );
+        assert_eq!(FieldValue::infer(""), FieldValue::Null);
+    }
+
+    #[test]
+    fn test_infer_schema_unions_and_widens() {
+        let records = vec![
+            Record {
+                line_number: 1,
+                fields: vec![
+                    ("a".to_string(), FieldValue::Int(1)),
+                    ("b".to_string(), FieldValue::Null),
+                ],
+            },
+            Record {
+                line_number: 2,
+                fields: vec![
+                    ("a".to_string(), FieldValue::Float(1.5)),
+                    ("c".to_string(), FieldValue::Str("x".to_string())),
+                    ("b".to_string(), FieldValue::Int(3)),
+                ],
+            },
+        ];
+        let columns = infer_schema(&records);
+        let summary: Vec<(&str, ColumnType)> = columns
+            .iter()
+            .map(|c| (c.name.as_str(), c.column_type))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("a", ColumnType::Float64),
+                ("b", ColumnType::Int64),
+                ("c", ColumnType::Utf8)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split_key_values() {
+        assert_eq!(
+            split_key_values(r"src=10.0.0.1 msg=Login failed for admin act=block\=deny x="),
+            vec![
+                ("src", "10.0.0.1"),
+                ("msg", "Login failed for admin"),
+                ("act", r"block\=deny"),
+                ("x", "")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_detect_requires_majority() {
+        let cef = "CEF:0|Vendor|Product|1.0|100|Blocked|5|src=10.0.0.1";
+        assert_eq!(
+            ParserKind::detect([cef, cef, "plain text"]),
+            Some(ParserKind::Cef)
+        );
+        assert_eq!(ParserKind::detect([cef, "plain", "text"]), None);
+        assert_eq!(
+            ParserKind::detect(["#Version: 1.0", "#Fields: date time", "2024-01-01 00:00:00"]),
+            Some(ParserKind::W3c)
+        );
+        assert_eq!(
+            ParserKind::detect(["host\tstatus", "web01\t200", "web02\t503"]),
+            Some(ParserKind::Csv)
+        );
+        assert_eq!(
+            ParserKind::detect(["banner", "more banner", r#"{"a":1}"#, "trace"]),
+            Some(ParserKind::Json)
+        );
+    }
+
+    #[test]
+    fn test_errors_table() {
+        let lines = ["CEF:0|V|P|1|100|Blocked|5|src=10.0.0.1", "garbage"];
+        let table = parse_lines(
+            &mut cef::CefParser,
+            lines.iter().enumerate().map(|(i, l)| (i as u64 + 1, *l)),
+        );
+        assert_eq!(table.success_rate(), 0.5);
+
+        let errors = ParsedTable::errors_table(&table.failures);
+        assert_eq!(errors.records.len(), 1);
+        assert_eq!(errors.records[0].line_number, 2);
+        assert!(matches!(
+            errors.records[0].get("error"),
+            Some(FieldValue::Str(_))
+        ));
+    }
+}
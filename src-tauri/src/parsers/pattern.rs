@@ -0,0 +1,257 @@
+use super::{ColumnType, FieldValue, LogParser, ParseSink};
+use crate::timestamp::parse_timestamp;
+use chrono::DateTime;
+use regex::Regex;
+use std::collections::HashMap;
+
+/// Grok patterns available to `%{NAME}` references, after the Logstash core set
+const GROK_PATTERNS: &[(&str, &str)] = &[
+    ("USERNAME", r"[a-zA-Z0-9._-]+"),
+    ("USER", r"%{USERNAME}"),
+    ("INT", r"[+-]?\d+"),
+    ("POSINT", r"\b[1-9]\d*\b"),
+    ("NONNEGINT", r"\b\d+\b"),
+    ("BASE10NUM", r"[+-]?(?:\d+(?:\.\d+)?|\.\d+)"),
+    ("NUMBER", r"%{BASE10NUM}"),
+    ("WORD", r"\b\w+\b"),
+    ("NOTSPACE", r"\S+"),
+    ("SPACE", r"\s*"),
+    ("DATA", r".*?"),
+    ("GREEDYDATA", r".*"),
+    ("QUOTEDSTRING", r#""(?:[^"\\]|\\.)*""#),
+    (
+        "UUID",
+        r"[A-Fa-f0-9]{8}-(?:[A-Fa-f0-9]{4}-){3}[A-Fa-f0-9]{12}",
+    ),
+    ("IPV4", r"(?:\d{1,3}\.){3}\d{1,3}"),
+    ("IPV6", r"[0-9A-Fa-f]{0,4}:[0-9A-Fa-f:.]*[0-9A-Fa-f]"),
+    ("IP", r"(?:%{IPV6}|%{IPV4})"),
+    (
+        "HOSTNAME",
+        r"\b[0-9A-Za-z][0-9A-Za-z-]{0,62}(?:\.[0-9A-Za-z][0-9A-Za-z-]{0,62})*\.?\b",
+    ),
+    ("IPORHOST", r"(?:%{IP}|%{HOSTNAME})"),
+    ("HOSTPORT", r"%{IPORHOST}:%{POSINT}"),
+    ("PATH", r"(?:/[^\s?#]*)+"),
+    ("URIPATHPARAM", r"/[^\s?#]*(?:\?[^\s#]*)?"),
+    ("URI", r"[A-Za-z][A-Za-z0-9+.-]*://\S+"),
+    (
+        "MONTH",
+        r"\b(?:Jan(?:uary)?|Feb(?:ruary)?|Mar(?:ch)?|Apr(?:il)?|May|Jun(?:e)?|Jul(?:y)?|Aug(?:ust)?|Sep(?:tember)?|Oct(?:ober)?|Nov(?:ember)?|Dec(?:ember)?)\b",
+    ),
+    ("MONTHNUM", r"(?:0?[1-9]|1[0-2])"),
+    ("MONTHDAY", r"(?:0?[1-9]|[12]\d|3[01])"),
+    ("YEAR", r"\d{2}(?:\d{2})?"),
+    ("HOUR", r"(?:2[0-3]|[01]?\d)"),
+    ("MINUTE", r"[0-5]\d"),
+    ("SECOND", r"(?:[0-5]?\d|60)(?:[:.,]\d+)?"),
+    ("TIME", r"%{HOUR}:%{MINUTE}(?::%{SECOND})?"),
+    ("ISO8601_TIMEZONE", r"(?:Z|[+-]%{HOUR}(?::?%{MINUTE}))"),
+    (
+        "TIMESTAMP_ISO8601",
+        r"%{YEAR}-%{MONTHNUM}-%{MONTHDAY}[T ]%{HOUR}:?%{MINUTE}(?::?%{SECOND})?%{ISO8601_TIMEZONE}?",
+    ),
+    (
+        "HTTPDATE",
+        r"%{MONTHDAY}/%{MONTH}/%{YEAR}:%{TIME} [+-]\d{4}",
+    ),
+    ("SYSLOGTIMESTAMP", r"%{MONTH} +%{MONTHDAY} %{TIME}"),
+    (
+        "LOGLEVEL",
+        r"(?i:trace|debug|info|notice|warn(?:ing)?|err(?:or)?|crit(?:ical)?|fatal|severe|alert|emerg(?:ency)?)",
+    ),
+    (
+        "COMMONAPACHELOG",
+        r#"%{IPORHOST:clientip} %{USER:ident} %{USER:auth} \[%{HTTPDATE:timestamp}\] "(?:%{WORD:verb} %{NOTSPACE:request}(?: HTTP/%{NUMBER:httpversion})?|%{DATA:rawrequest})" %{NUMBER:response} (?:%{NUMBER:bytes}|-)"#,
+    ),
+    (
+        "COMBINEDAPACHELOG",
+        r"%{COMMONAPACHELOG} %{QUOTEDSTRING:referrer} %{QUOTEDSTRING:agent}",
+    ),
+];
+
+/// Zoned formats tried for `timestamp` values the general parser doesn't know, such as a bare
+/// `%{HTTPDATE}` captured without its brackets
+const TIMESTAMP_FORMATS: &[&str] = &["%d/%b/%Y:%H:%M:%S %z", "%d/%b/%Y:%H:%M:%S%.f %z"];
+
+/// Deepest nesting of `%{NAME}` references followed before giving up
+const MAX_GROK_DEPTH: usize = 16;
+
+/// Column name for a grok field: `[http][method]` and `http.method` become `http_method`
+fn field_column(field: &str) -> String {
+    field
+        .split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+/// Expand `%{NAME}`, `%{NAME:field}` and `%{NAME:field:int|float}` references into a regex,
+/// collecting the type hints of named fields
+fn expand_grok(
+    pattern: &str,
+    depth: usize,
+    types: &mut HashMap<String, ColumnType>,
+) -> Result<String, String> {
+    if depth > MAX_GROK_DEPTH {
+        return Err("Grok patterns are nested too deeply".to_string());
+    }
+    let mut expanded = String::with_capacity(pattern.len());
+    let mut rest = pattern;
+    while let Some(start) = rest.find("%{") {
+        expanded.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("Unterminated reference in \"{}\"", &rest[start..]))?;
+        let reference = &rest[start + 2..start + end];
+        rest = &rest[start + end + 1..];
+
+        let mut parts = reference.splitn(3, ':');
+        let name = parts.next().unwrap_or_default();
+        let definition = GROK_PATTERNS
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, definition)| *definition)
+            .ok_or_else(|| format!("Unknown grok pattern %{{{}}}", name))?;
+        let inner = expand_grok(definition, depth + 1, types)?;
+        match parts.next().map(field_column) {
+            Some(column) if !column.is_empty() => {
+                match parts.next() {
+                    Some("int") => types.insert(column.clone(), ColumnType::Int64),
+                    Some("float") => types.insert(column.clone(), ColumnType::Float64),
+                    Some(other) => return Err(format!("Unknown grok type \"{}\"", other)),
+                    None => None,
+                };
+                expanded.push_str(&format!("(?P<{}>{})", column, inner));
+            }
+            _ => expanded.push_str(&format!("(?:{})", inner)),
+        }
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+fn parse_pattern_timestamp(text: &str) -> Option<i64> {
+    parse_timestamp(text).or_else(|| {
+        TIMESTAMP_FORMATS
+            .iter()
+            .find_map(|format| DateTime::parse_from_str(text, format).ok())
+            .map(|dt| dt.timestamp_millis())
+    })
+}
+
+/// Parser turning the named groups of a user-supplied regex (or grok pattern) into columns
+/// A group named `timestamp` is parsed as a time; other values are typed by inference
+pub struct PatternParser {
+    regex: Regex,
+    types: HashMap<String, ColumnType>,
+}
+
+impl PatternParser {
+    /// Parser for a regex with named capture groups
+    pub fn regex(pattern: &str) -> Result<Self, String> {
+        Self::build(pattern, HashMap::new())
+    }
+
+    /// Parser for a grok pattern such as `%{IP:client} %{WORD:method} %{NUMBER:bytes:int}`
+    pub fn grok(pattern: &str) -> Result<Self, String> {
+        let mut types = HashMap::new();
+        let expanded = expand_grok(pattern, 0, &mut types)?;
+        Self::build(&expanded, types)
+    }
+
+    fn build(pattern: &str, types: HashMap<String, ColumnType>) -> Result<Self, String> {
+        let regex = Regex::new(pattern).map_err(|e| e.to_string())?;
+        if regex.capture_names().flatten().next().is_none() {
+            return Err("The pattern has no named groups to use as columns".to_string());
+        }
+        Ok(PatternParser { regex, types })
+    }
+}
+
+impl LogParser for PatternParser {
+    fn feed(&mut self, line_number: u64, line: &str, sink: &mut ParseSink) {
+        if line.trim().is_empty() {
+            return;
+        }
+        let Some(captures) = self.regex.captures(line) else {
+            sink.fail(line_number, "Line does not match the pattern");
+            return;
+        };
+
+        let fields = self
+            .regex
+            .capture_names()
+            .flatten()
+            .map(|name| {
+                let value = captures.name(name).map_or("", |m| m.as_str());
+                let typed = if value.is_empty() {
+                    FieldValue::Null
+                } else if name == "timestamp" {
+                    parse_pattern_timestamp(value)
+                        .map_or_else(|| FieldValue::Str(value.to_string()), FieldValue::Timestamp)
+                } else if let Some(&column_type) = self.types.get(name) {
+                    FieldValue::Str(value.to_string()).cast(column_type)
+                } else {
+                    FieldValue::infer(value)
+                };
+                (name.to_string(), typed)
+            })
+            .collect();
+        sink.record(line_number, fields);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::parse_lines;
+
+    #[test]
+    fn test_grok_combined_log() {
+        let lines = [
+            r#"203.0.113.9 - alice [10/Oct/2023:13:55:36 +0000] "GET /index.html?x=1 HTTP/1.1" 200 2326 "-" "curl/8.0""#,
+            "not an access log line",
+        ];
+        let mut parser = PatternParser::grok("%{COMBINEDAPACHELOG}").unwrap();
+        let table = parse_lines(
+            &mut parser,
+            lines.iter().enumerate().map(|(i, l)| (i as u64 + 1, *l)),
+        );
+
+        assert_eq!(table.records.len(), 1);
+        assert_eq!(table.failures.len(), 1);
+        let record = &table.records[0];
+        assert_eq!(
+            record.get("clientip"),
+            Some(&FieldValue::Str("203.0.113.9".to_string()))
+        );
+        assert_eq!(
+            record.get("timestamp"),
+            Some(&FieldValue::Timestamp(1_696_946_136_000))
+        );
+        assert_eq!(record.get("response"), Some(&FieldValue::Int(200)));
+        assert_eq!(
+            record.get("agent"),
+            Some(&FieldValue::Str("\"curl/8.0\"".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_grok_field_names_and_types() {
+        let mut parser =
+            PatternParser::grok("%{LOGLEVEL:[log][level]} took %{NUMBER:elapsed:float}ms").unwrap();
+        let mut sink = ParseSink::default();
+        parser.feed(1, "WARN took 12ms", &mut sink);
+
+        let record = &sink.records[0];
+        assert_eq!(
+            record.get("log_level"),
+            Some(&FieldValue::Str("WARN".to_string()))
+        );
+        assert_eq!(record.get("elapsed"), Some(&FieldValue::Float(12.0)));
+
+        assert!(PatternParser::grok("%{NOPE:x}").is_err());
+        assert!(PatternParser::regex(r"\d+").is_err());
+    }
+}
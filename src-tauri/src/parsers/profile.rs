@@ -0,0 +1,222 @@
+use super::{FieldValue, LogParser, ParseSink};
+use crate::timestamp::parse_timestamp;
+use chrono::NaiveDateTime;
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// A built-in regex profile for a well-known access log layout
+/// Named capture groups become columns, in pattern order
+pub struct BuiltinProfile {
+    pub name: &'static str,
+    pattern: &'static str,
+    /// Group parsed into the `timestamp` column
+    timestamp_group: &'static str,
+    /// chrono formats tried before the general timestamp parser
+    timestamp_formats: &'static [&'static str],
+    /// Groups kept as text even when they look numeric
+    text_groups: &'static [&'static str],
+    /// Placeholders logged for missing values, read as NULL in typed columns
+    null_values: &'static [&'static str],
+    regex: OnceLock<Regex>,
+}
+
+/// HAProxy `option httplog`: timers Tq/Tw/Tc/Tr/Tt in milliseconds, -1 when a phase never ran
+pub static HAPROXY: BuiltinProfile = BuiltinProfile {
+    name: "haproxy",
+    pattern: r#"(?x)
+        (?:(?P<process>[\w.-]+)\[(?P<pid>\d+)\]:\s+)?
+        (?P<client_ip>[\da-fA-F.:]+):(?P<client_port>\d+)\s
+        \[(?P<timestamp>[^\]]+)\]\s
+        (?P<frontend>\S+)\s
+        (?P<backend>[^/\s]+)/(?P<server>\S+)\s
+        (?P<tq>-?\d+)/(?P<tw>-?\d+)/(?P<tc>-?\d+)/(?P<tr>-?\d+)/\+?(?P<tt>-?\d+)\s
+        (?P<status>-?\d+)\s
+        \+?(?P<bytes_read>\d+)\s
+        (?P<request_cookie>\S+)\s(?P<response_cookie>\S+)\s
+        (?P<termination_state>\S+)\s
+        (?P<actconn>\d+)/(?P<feconn>\d+)/(?P<beconn>\d+)/(?P<srv_conn>\d+)/\+?(?P<retries>\d+)\s
+        (?P<srv_queue>\d+)/(?P<backend_queue>\d+)
+        (?:\s\{(?P<request_headers>[^}]*)\})?
+        (?:\s\{(?P<response_headers>[^}]*)\})?
+        (?:\s"(?P<method>[^\s"]+)(?:\s(?P<path>[^\s"]+))?(?:\s(?P<protocol>[^"]+))?"?)?
+    "#,
+    timestamp_group: "timestamp",
+    timestamp_formats: &["%d/%b/%Y:%H:%M:%S%.f", "%d/%b/%Y:%H:%M:%S"],
+    text_groups: &["client_ip", "request_headers", "response_headers"],
+    null_values: &["-", "-1"],
+    regex: OnceLock::new(),
+};
+
+/// Envoy's default access log format; `duration` and `upstream_service_time` are milliseconds
+pub static ENVOY: BuiltinProfile = BuiltinProfile {
+    name: "envoy",
+    pattern: r#"(?x)
+        ^\[(?P<timestamp>[^\]]+)\]\s
+        "(?P<method>\S+)\s(?P<path>\S+)\s(?P<protocol>[^"]+)"\s
+        (?P<response_code>\d+)\s
+        (?P<response_flags>\S+)\s
+        (?P<bytes_received>\d+)\s
+        (?P<bytes_sent>\d+)\s
+        (?P<duration>\d+|-)\s
+        (?P<upstream_service_time>\d+|-)\s
+        "(?P<x_forwarded_for>[^"]*)"\s
+        "(?P<user_agent>[^"]*)"\s
+        "(?P<request_id>[^"]*)"\s
+        "(?P<authority>[^"]*)"\s
+        "(?P<upstream_host>[^"]*)"
+    "#,
+    timestamp_group: "timestamp",
+    timestamp_formats: &[],
+    text_groups: &["x_forwarded_for", "request_id"],
+    null_values: &["-"],
+    regex: OnceLock::new(),
+};
+
+impl BuiltinProfile {
+    pub fn regex(&self) -> &Regex {
+        self.regex
+            .get_or_init(|| Regex::new(self.pattern).expect("built-in profile pattern is valid"))
+    }
+
+    /// Whether a single line matches the profile
+    pub fn matches(&self, line: &str) -> bool {
+        self.regex().is_match(line)
+    }
+
+    fn timestamp(&self, text: &str) -> Option<i64> {
+        self.timestamp_formats
+            .iter()
+            .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+            .map(|naive| naive.and_utc().timestamp_millis())
+            .or_else(|| parse_timestamp(text))
+    }
+}
+
+/// Parser applying a built-in profile to each line
+pub struct ProfileParser {
+    profile: &'static BuiltinProfile,
+}
+
+impl ProfileParser {
+    pub fn new(profile: &'static BuiltinProfile) -> Self {
+        ProfileParser { profile }
+    }
+}
+
+impl LogParser for ProfileParser {
+    fn feed(&mut self, line_number: u64, line: &str, sink: &mut ParseSink) {
+        if line.trim().is_empty() {
+            return;
+        }
+        let profile = self.profile;
+        let regex = profile.regex();
+        let Some(captures) = regex.captures(line) else {
+            sink.fail(
+                line_number,
+                format!("Line does not match the {} format", profile.name),
+            );
+            return;
+        };
+
+        let mut fields = Vec::with_capacity(regex.captures_len());
+        let prefix = line[..captures.get(0).map_or(0, |m| m.start())].trim();
+        if !prefix.is_empty() {
+            fields.push(("prefix".to_string(), FieldValue::Str(prefix.to_string())));
+        }
+        for name in regex.capture_names().flatten() {
+            let value = captures.name(name).map_or("", |m| m.as_str());
+            let typed = if value.is_empty() {
+                FieldValue::Null
+            } else if profile.text_groups.contains(&name) {
+                FieldValue::Str(value.to_string())
+            } else if profile.null_values.contains(&value) {
+                FieldValue::Null
+            } else if name == profile.timestamp_group {
+                profile
+                    .timestamp(value)
+                    .map_or_else(|| FieldValue::Str(value.to_string()), FieldValue::Timestamp)
+            } else {
+                FieldValue::infer(value)
+            };
+            fields.push((name.to_string(), typed));
+        }
+        sink.record(line_number, fields);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(profile: &'static BuiltinProfile, line: &str) -> ParseSink {
+        let mut sink = ParseSink::default();
+        ProfileParser::new(profile).feed(1, line, &mut sink);
+        sink
+    }
+
+    #[test]
+    fn test_haproxy_timers() {
+        let line = r#"Feb  6 12:14:14 localhost haproxy[14389]: 10.0.1.2:33317 [06/Feb/2009:12:14:14.655] http-in static/srv1 10/0/30/69/109 200 2750 - - ---- 1/1/1/1/0 0/0 {1wt.eu} {} "GET /index.html HTTP/1.1""#;
+        let sink = parse(&HAPROXY, line);
+        let record = &sink.records[0];
+
+        assert_eq!(
+            record.get("prefix"),
+            Some(&FieldValue::Str("Feb  6 12:14:14 localhost".to_string()))
+        );
+        assert_eq!(record.get("pid"), Some(&FieldValue::Int(14389)));
+        assert_eq!(
+            record.get("timestamp"),
+            Some(&FieldValue::Timestamp(1_233_922_454_655))
+        );
+        assert_eq!(record.get("tc"), Some(&FieldValue::Int(30)));
+        assert_eq!(record.get("tt"), Some(&FieldValue::Int(109)));
+        assert_eq!(record.get("status"), Some(&FieldValue::Int(200)));
+        assert_eq!(record.get("request_cookie"), Some(&FieldValue::Null));
+        assert_eq!(
+            record.get("request_headers"),
+            Some(&FieldValue::Str("1wt.eu".to_string()))
+        );
+        assert_eq!(
+            record.get("path"),
+            Some(&FieldValue::Str("/index.html".to_string()))
+        );
+
+        // Aborted requests log -1 for phases that never ran
+        let line = r#"10.0.1.2:33319 [06/Feb/2009:12:14:15.001] http-in static/<NOSRV> 5/-1/-1/-1/+5 503 212 - - SC-- 0/0/0/0/0 0/0 "GET /x HTTP/1.1""#;
+        let sink = parse(&HAPROXY, line);
+        let record = &sink.records[0];
+        assert_eq!(record.get("tw"), Some(&FieldValue::Null));
+        assert_eq!(record.get("tt"), Some(&FieldValue::Int(5)));
+        assert_eq!(
+            record.get("server"),
+            Some(&FieldValue::Str("<NOSRV>".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_envoy_default_format() {
+        let line = r#"[2016-04-15T20:17:00.310Z] "POST /api/v1/locations HTTP/2" 204 - 154 0 226 100 "10.0.35.28" "nsq2http" "cc21d9b0-cf5c-432b-8c7e-98aeb7988cd2" "locations" "tcp://10.0.2.1:80""#;
+        let sink = parse(&ENVOY, line);
+        let record = &sink.records[0];
+
+        assert_eq!(
+            record.get("timestamp"),
+            Some(&FieldValue::Timestamp(1_460_751_420_310))
+        );
+        assert_eq!(record.get("response_code"), Some(&FieldValue::Int(204)));
+        assert_eq!(record.get("response_flags"), Some(&FieldValue::Null));
+        assert_eq!(record.get("duration"), Some(&FieldValue::Int(226)));
+        assert_eq!(
+            record.get("upstream_service_time"),
+            Some(&FieldValue::Int(100))
+        );
+        assert_eq!(
+            record.get("x_forwarded_for"),
+            Some(&FieldValue::Str("10.0.35.28".to_string()))
+        );
+
+        let sink = parse(&ENVOY, "not an access log");
+        assert_eq!(sink.failures.len(), 1);
+    }
+}
@@ -0,0 +1,335 @@
+use super::{
+    cef::parse_event_time, load_json, save_json, Column, ColumnType, FieldValue, ParsedTable,
+    ParserKind,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// File the overrides are persisted to, inside the app config directory
+const STORE_FILE: &str = "schema_overrides.json";
+
+/// User changes to one inferred column, addressed by its inferred name
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColumnOverride {
+    pub name: String,
+    #[serde(default)]
+    pub rename: Option<String>,
+    #[serde(default, rename = "type")]
+    pub column_type: Option<ColumnType>,
+    #[serde(default)]
+    pub exclude: bool,
+}
+
+/// User changes applied to an inferred schema before the table is registered
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SchemaOverride {
+    #[serde(default)]
+    pub columns: Vec<ColumnOverride>,
+    /// Inferred name of the column holding each record's time; it is typed as a
+    /// timestamp and moved first
+    #[serde(default)]
+    pub timestamp_column: Option<String>,
+}
+
+impl SchemaOverride {
+    /// Rename, retype, drop and reorder columns of a parsed table in place
+    /// Values that can't be converted to an overridden type become NULL
+    pub fn apply(&self, table: &mut ParsedTable) -> Result<(), String> {
+        let overrides: HashMap<&str, &ColumnOverride> =
+            self.columns.iter().map(|c| (c.name.as_str(), c)).collect();
+        let timestamp = self.timestamp_column.as_deref();
+
+        // Target name and type for every column kept, keyed by inferred name
+        let mut targets: HashMap<String, (String, ColumnType)> = HashMap::new();
+        let mut columns = Vec::with_capacity(table.columns.len());
+        for column in &table.columns {
+            let column_override = overrides.get(column.name.as_str());
+            if column_override.is_some_and(|o| o.exclude) {
+                continue;
+            }
+            let name = match column_override.and_then(|o| o.rename.as_deref()) {
+                Some(rename) if rename.trim().is_empty() => {
+                    return Err(format!(
+                        "Column \"{}\" can't be renamed to nothing",
+                        column.name
+                    ));
+                }
+                Some(rename) => rename.trim().to_string(),
+                None => column.name.clone(),
+            };
+            let column_type = if timestamp == Some(column.name.as_str()) {
+                ColumnType::Timestamp
+            } else {
+                column_override
+                    .and_then(|o| o.column_type)
+                    .unwrap_or(column.column_type)
+            };
+            targets.insert(column.name.clone(), (name.clone(), column_type));
+            columns.push(Column { name, column_type });
+        }
+
+        let mut seen = HashSet::new();
+        if let Some(duplicate) = columns.iter().find(|c| !seen.insert(c.name.as_str())) {
+            return Err(format!(
+                "More than one column is named \"{}\"",
+                duplicate.name
+            ));
+        }
+        // Like overrides of other absent columns, a timestamp column the table lacks is
+        // ignored; tables parsed in chunks may not have seen it yet
+        if let Some((target, _)) = timestamp.and_then(|name| targets.get(name)) {
+            if let Some(index) = columns.iter().position(|c| c.name == *target) {
+                let column = columns.remove(index);
+                columns.insert(0, column);
+            }
+        }
+
+        for record in &mut table.records {
+            let mut fields = Vec::with_capacity(record.fields.len());
+            for (name, value) in record.fields.drain(..) {
+                let Some((target, column_type)) = targets.get(&name) else {
+                    continue;
+                };
+                let field = (target.clone(), value.cast(*column_type));
+                if timestamp == Some(name.as_str()) {
+                    fields.insert(0, field);
+                } else {
+                    fields.push(field);
+                }
+            }
+            record.fields = fields;
+        }
+        table.columns = columns;
+        Ok(())
+    }
+}
+
+impl FieldValue {
+    /// Convert to another column type, or NULL when the value doesn't fit
+    pub fn cast(self, column_type: ColumnType) -> FieldValue {
+        match (column_type, self) {
+            (_, FieldValue::Null) => FieldValue::Null,
+            (ColumnType::Utf8, value) => value.to_text().map_or(FieldValue::Null, FieldValue::Str),
+            (ColumnType::Int64, FieldValue::Int(v)) => FieldValue::Int(v),
+            (ColumnType::Int64, FieldValue::Float(v)) if v.is_finite() => FieldValue::Int(v as i64),
+            (ColumnType::Int64, FieldValue::Bool(v)) => FieldValue::Int(v as i64),
+            (ColumnType::Int64, FieldValue::Timestamp(ms)) => FieldValue::Int(ms),
+            (ColumnType::Int64, FieldValue::Str(s)) => {
+                let s = s.trim();
+                s.parse()
+                    .ok()
+                    .or_else(|| {
+                        s.parse::<f64>()
+                            .ok()
+                            .filter(|v| v.is_finite())
+                            .map(|v| v as i64)
+                    })
+                    .map_or(FieldValue::Null, FieldValue::Int)
+            }
+            (ColumnType::Float64, FieldValue::Float(v)) => FieldValue::Float(v),
+            (ColumnType::Float64, FieldValue::Int(v)) => FieldValue::Float(v as f64),
+            (ColumnType::Float64, FieldValue::Bool(v)) => FieldValue::Float(v as i64 as f64),
+            (ColumnType::Float64, FieldValue::Timestamp(ms)) => FieldValue::Float(ms as f64),
+            (ColumnType::Float64, FieldValue::Str(s)) => s
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|v| v.is_finite())
+                .map_or(FieldValue::Null, FieldValue::Float),
+            (ColumnType::Boolean, FieldValue::Bool(v)) => FieldValue::Bool(v),
+            (ColumnType::Boolean, FieldValue::Int(v)) => FieldValue::Bool(v != 0),
+            (ColumnType::Boolean, FieldValue::Str(s)) => {
+                match s.trim().to_ascii_lowercase().as_str() {
+                    "true" | "yes" | "y" | "on" | "1" => FieldValue::Bool(true),
+                    "false" | "no" | "n" | "off" | "0" => FieldValue::Bool(false),
+                    _ => FieldValue::Null,
+                }
+            }
+            (ColumnType::Boolean, _) => FieldValue::Null,
+            (ColumnType::Timestamp, FieldValue::Timestamp(ms)) => FieldValue::Timestamp(ms),
+            // Epoch numbers: seconds unless too large to be a plausible date in seconds
+            (ColumnType::Timestamp, FieldValue::Int(v)) => {
+                FieldValue::Timestamp(if v.abs() < 100_000_000_000 {
+                    v * 1000
+                } else {
+                    v
+                })
+            }
+            (ColumnType::Timestamp, FieldValue::Float(v)) if v.is_finite() => {
+                FieldValue::Timestamp(if v.abs() < 1e11 {
+                    (v * 1000.0) as i64
+                } else {
+                    v as i64
+                })
+            }
+            (ColumnType::Timestamp, FieldValue::Str(s)) => {
+                parse_event_time(&s).map_or(FieldValue::Null, FieldValue::Timestamp)
+            }
+            _ => FieldValue::Null,
+        }
+    }
+}
+
+/// Store key for overrides that apply to one file
+pub fn file_key(path: &str) -> String {
+    format!("file:{}", path)
+}
+
+/// Store key for overrides that apply to every file parsed in a format
+pub fn format_key(kind: ParserKind) -> String {
+    let name = serde_json::to_value(kind)
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_string))
+        .unwrap_or_default();
+    format!("format:{}", name)
+}
+
+/// Schema overrides persisted as JSON, keyed by file or format
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct OverrideStore {
+    #[serde(skip)]
+    path: PathBuf,
+    overrides: BTreeMap<String, SchemaOverride>,
+}
+
+impl OverrideStore {
+    /// Load the store from a config directory; a missing file is an empty store
+    pub fn load(dir: &Path) -> io::Result<Self> {
+        let path = dir.join(STORE_FILE);
+        let mut store: OverrideStore = load_json(&path)?;
+        store.path = path;
+        Ok(store)
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        save_json(&self.path, self)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&SchemaOverride> {
+        self.overrides.get(key)
+    }
+
+    pub fn overrides(&self) -> &BTreeMap<String, SchemaOverride> {
+        &self.overrides
+    }
+
+    /// Set or, with None, remove the override for a key
+    pub fn set(&mut self, key: String, schema_override: Option<SchemaOverride>) {
+        match schema_override {
+            Some(schema_override) => self.overrides.insert(key, schema_override),
+            None => self.overrides.remove(&key),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::{infer_schema, Record};
+
+    fn sample_table() -> ParsedTable {
+        let records = vec![
+            Record {
+                line_number: 1,
+                fields: vec![
+                    ("level".to_string(), FieldValue::Str("info".to_string())),
+                    ("code".to_string(), FieldValue::Str("200".to_string())),
+                    ("when".to_string(), FieldValue::Int(1_704_067_200)),
+                    ("noise".to_string(), FieldValue::Str("x".to_string())),
+                ],
+            },
+            Record {
+                line_number: 2,
+                fields: vec![
+                    ("level".to_string(), FieldValue::Str("warn".to_string())),
+                    ("code".to_string(), FieldValue::Str("n/a".to_string())),
+                    ("when".to_string(), FieldValue::Int(1_704_067_201)),
+                ],
+            },
+        ];
+        ParsedTable {
+            columns: infer_schema(&records),
+            records,
+            failures: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_apply_override() {
+        let mut table = sample_table();
+        let schema_override = SchemaOverride {
+            columns: vec![
+                ColumnOverride {
+                    name: "code".to_string(),
+                    rename: Some("status".to_string()),
+                    column_type: Some(ColumnType::Int64),
+                    exclude: false,
+                },
+                ColumnOverride {
+                    name: "noise".to_string(),
+                    rename: None,
+                    column_type: None,
+                    exclude: true,
+                },
+            ],
+            timestamp_column: Some("when".to_string()),
+        };
+        schema_override.apply(&mut table).unwrap();
+
+        let columns: Vec<(&str, ColumnType)> = table
+            .columns
+            .iter()
+            .map(|c| (c.name.as_str(), c.column_type))
+            .collect();
+        assert_eq!(
+            columns,
+            vec![
+                ("when", ColumnType::Timestamp),
+                ("level", ColumnType::Utf8),
+                ("status", ColumnType::Int64),
+            ]
+        );
+        assert_eq!(
+            table.records[0].fields[0],
+            ("when".to_string(), FieldValue::Timestamp(1_704_067_200_000))
+        );
+        assert_eq!(table.records[0].get("status"), Some(&FieldValue::Int(200)));
+        assert_eq!(table.records[0].get("noise"), None);
+        assert_eq!(table.records[1].get("status"), Some(&FieldValue::Null));
+    }
+
+    #[test]
+    fn test_apply_rejects_duplicate_names() {
+        let mut table = sample_table();
+        let schema_override = SchemaOverride {
+            columns: vec![ColumnOverride {
+                name: "code".to_string(),
+                rename: Some("level".to_string()),
+                column_type: None,
+                exclude: false,
+            }],
+            timestamp_column: None,
+        };
+        assert!(schema_override.apply(&mut table).is_err());
+    }
+
+    #[test]
+    fn test_store_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = OverrideStore::load(dir.path()).unwrap();
+        assert!(store.get("file:/var/log/app.log").is_none());
+
+        let schema_override = SchemaOverride {
+            timestamp_column: Some("ts".to_string()),
+            ..Default::default()
+        };
+        store.set(file_key("/var/log/app.log"), Some(schema_override.clone()));
+        store.save().unwrap();
+
+        let store = OverrideStore::load(dir.path()).unwrap();
+        assert_eq!(store.get("file:/var/log/app.log"), Some(&schema_override));
+        assert_eq!(format_key(ParserKind::MysqlSlow), "format:mysql_slow");
+    }
+}
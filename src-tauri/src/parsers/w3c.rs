@@ -0,0 +1,223 @@
+use super::{FieldValue, LogParser, ParseSink};
+use crate::timestamp::parse_timestamp;
+
+/// Parser for W3C extended logs as written by IIS and many proxies
+/// Columns follow the most recent `#Fields:` directive; a mid-file change starts a new
+/// layout and the table schema becomes the union of every layout seen
+#[derive(Debug, Default)]
+pub struct W3cParser {
+    /// Column names from the current `#Fields:` directive
+    fields: Vec<String>,
+    /// Index of the `date` and `time` fields, combined into a `timestamp` column
+    date_index: Option<usize>,
+    time_index: Option<usize>,
+    /// Date from a `#Date:` directive, used when entries only carry a time
+    directive_date: Option<String>,
+}
+
+/// Whether a line is the `#Fields:` directive
+pub fn is_fields_directive(line: &str) -> bool {
+    line.trim_start().starts_with("#Fields:")
+}
+
+impl LogParser for W3cParser {
+    fn feed(&mut self, line_number: u64, line: &str, sink: &mut ParseSink) {
+        let line = line.trim();
+        if line.is_empty() {
+            return;
+        }
+        if let Some(directive) = line.strip_prefix('#') {
+            self.directive(directive);
+            return;
+        }
+        if self.fields.is_empty() {
+            sink.fail(line_number, "Entry before any #Fields directive");
+            return;
+        }
+
+        let values = split_values(line);
+        if values.len() != self.fields.len() {
+            sink.fail(
+                line_number,
+                format!(
+                    "Expected {} fields but found {}",
+                    self.fields.len(),
+                    values.len()
+                ),
+            );
+            return;
+        }
+
+        let mut fields = Vec::with_capacity(values.len() + 1);
+        if let Some(ms) = self.timestamp(&values) {
+            fields.push(("timestamp".to_string(), FieldValue::Timestamp(ms)));
+        }
+        for (i, (name, value)) in self.fields.iter().zip(values).enumerate() {
+            if Some(i) == self.date_index || Some(i) == self.time_index {
+                continue;
+            }
+            let value = if value == "-" {
+                FieldValue::Null
+            } else {
+                FieldValue::infer(&value)
+            };
+            fields.push((name.clone(), value));
+        }
+        sink.record(line_number, fields);
+    }
+}
+
+impl W3cParser {
+    fn directive(&mut self, directive: &str) {
+        let (name, value) = directive.split_once(':').unwrap_or((directive, ""));
+        match name.trim() {
+            "Fields" => {
+                self.fields = value.split_whitespace().map(column_name).collect();
+                self.date_index = self.fields.iter().position(|f| f == "date");
+                self.time_index = self.fields.iter().position(|f| f == "time");
+            }
+            "Date" => {
+                self.directive_date = value.split_whitespace().next().map(str::to_string);
+            }
+            _ => {}
+        }
+    }
+
+    /// W3C times are UTC; the date comes from the entry or the `#Date:` directive
+    fn timestamp(&self, values: &[String]) -> Option<i64> {
+        let time = &values[self.time_index?];
+        let date = match self.date_index {
+            Some(i) => &values[i],
+            None => self.directive_date.as_ref()?,
+        };
+        parse_timestamp(&format!("{} {}", date, time))
+    }
+}
+
+/// SQL-friendly column name: `cs(User-Agent)` becomes `cs_user_agent`, `s-ip` becomes `s_ip`
+fn column_name(field: &str) -> String {
+    let mut name = String::with_capacity(field.len());
+    for c in field.chars() {
+        if c.is_ascii_alphanumeric() {
+            name.push(c.to_ascii_lowercase());
+        } else if !name.is_empty() && !name.ends_with('_') {
+            name.push('_');
+        }
+    }
+    while name.ends_with('_') {
+        name.pop();
+    }
+    name
+}
+
+/// Split an entry on whitespace, keeping double-quoted values (with `""` escapes) together
+fn split_values(line: &str) -> Vec<String> {
+    let mut values = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let mut value = String::new();
+        if c == '"' {
+            chars.next();
+            while let Some(c) = chars.next() {
+                if c == '"' {
+                    if chars.peek() == Some(&'"') {
+                        chars.next();
+                        value.push('"');
+                        continue;
+                    }
+                    break;
+                }
+                value.push(c);
+            }
+        } else {
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                value.push(c);
+                chars.next();
+            }
+        }
+        values.push(value);
+    }
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::{parse_lines, ColumnType};
+
+    #[test]
+    fn test_parse_iis_log() {
+        let lines = [
+            "#Software: Microsoft Internet Information Services 10.0",
+            "#Version: 1.0",
+            "#Fields: date time s-ip cs-method cs-uri-stem sc-status time-taken cs(User-Agent)",
+            "2024-01-01 00:00:01 10.0.0.5 GET /index.html 200 15 Mozilla/5.0+(Windows)",
+            "2024-01-01 00:00:02 10.0.0.5 POST /api - 3",
+        ];
+        let table = parse_lines(
+            &mut W3cParser::default(),
+            lines.iter().enumerate().map(|(i, l)| (i as u64 + 1, *l)),
+        );
+
+        assert_eq!(table.records.len(), 1);
+        assert_eq!(table.failures.len(), 1);
+        assert_eq!(table.failures[0].line_number, 5);
+
+        let record = &table.records[0];
+        assert_eq!(record.line_number, 4);
+        assert_eq!(
+            record.get("timestamp"),
+            Some(&FieldValue::Timestamp(1_704_067_201_000))
+        );
+        assert_eq!(record.get("sc_status"), Some(&FieldValue::Int(200)));
+        assert_eq!(
+            record.get("cs_user_agent"),
+            Some(&FieldValue::Str("Mozilla/5.0+(Windows)".to_string()))
+        );
+        assert_eq!(record.get("date"), None);
+    }
+
+    #[test]
+    fn test_fields_change_unions_schema() {
+        let lines = [
+            "#Date: 2024-01-01 00:00:00",
+            "#Fields: time c-ip sc-status",
+            "00:00:01 10.0.0.1 200",
+            "#Fields: time c-ip sc-status sc-bytes cs-referer",
+            "00:00:02 10.0.0.2 404 512 \"https://example.com/a b\"",
+        ];
+        let table = parse_lines(
+            &mut W3cParser::default(),
+            lines.iter().enumerate().map(|(i, l)| (i as u64 + 1, *l)),
+        );
+
+        assert!(table.failures.is_empty());
+        let columns: Vec<(&str, ColumnType)> = table
+            .columns
+            .iter()
+            .map(|c| (c.name.as_str(), c.column_type))
+            .collect();
+        assert_eq!(
+            columns,
+            vec![
+                ("timestamp", ColumnType::Timestamp),
+                ("c_ip", ColumnType::Utf8),
+                ("sc_status", ColumnType::Int64),
+                ("sc_bytes", ColumnType::Int64),
+                ("cs_referer", ColumnType::Utf8),
+            ]
+        );
+        assert_eq!(table.records[0].get("sc_bytes"), None);
+        assert_eq!(
+            table.records[1].get("cs_referer"),
+            Some(&FieldValue::Str("https://example.com/a b".to_string()))
+        );
+    }
+}
@@ -0,0 +1,124 @@
+use crate::parsers::{load_json, save_json};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// File the approved directories are persisted to, inside the app config directory
+const POLICY_FILE: &str = "path_policy.json";
+
+/// Errors from checking a path against the policy
+#[derive(Error, Debug)]
+pub enum PolicyError {
+    #[error("{path} is outside the approved directories; approve {directory} to open it")]
+    NotApproved { path: PathBuf, directory: PathBuf },
+    #[error("Failed to resolve path: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Directories the user has approved for opening files from
+/// Paths are compared after resolving symlinks and `..`, so neither can reach outside them
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PathPolicy {
+    #[serde(skip)]
+    path: PathBuf,
+    #[serde(default)]
+    approved: Vec<PathBuf>,
+}
+
+impl PathPolicy {
+    /// Load the policy from a config directory; a missing file approves nothing
+    pub fn load(dir: &Path) -> io::Result<Self> {
+        let path = dir.join(POLICY_FILE);
+        let mut policy: PathPolicy = load_json(&path)?;
+        policy.path = path;
+        Ok(policy)
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        save_json(&self.path, self)
+    }
+
+    pub fn approved(&self) -> &[PathBuf] {
+        &self.approved
+    }
+
+    /// Resolve a path and check that it lies under an approved directory
+    /// On refusal the error names the directory that would have to be approved
+    pub fn check(&self, path: &Path) -> Result<PathBuf, PolicyError> {
+        let resolved = path.canonicalize()?;
+        if self.approved.iter().any(|dir| resolved.starts_with(dir)) {
+            return Ok(resolved);
+        }
+        let directory = resolved
+            .parent()
+            .map_or_else(|| resolved.clone(), Path::to_path_buf);
+        Err(PolicyError::NotApproved {
+            path: resolved,
+            directory,
+        })
+    }
+
+    /// Approve a directory and everything below it, returning it resolved
+    pub fn approve(&mut self, dir: &Path) -> io::Result<PathBuf> {
+        let resolved = dir.canonicalize()?;
+        if !resolved.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not a directory", resolved.display()),
+            ));
+        }
+        if !self.approved.contains(&resolved) {
+            self.approved.push(resolved.clone());
+        }
+        Ok(resolved)
+    }
+
+    /// Withdraw approval of a directory, returning whether it was approved
+    pub fn revoke(&mut self, dir: &Path) -> bool {
+        let resolved = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+        let before = self.approved.len();
+        self.approved.retain(|d| *d != resolved);
+        self.approved.len() != before
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_and_approve() {
+        let config = tempfile::tempdir().unwrap();
+        let root = tempfile::tempdir().unwrap();
+        let logs = root.path().join("logs");
+        let secrets = root.path().join("secrets");
+        std::fs::create_dir_all(&logs).unwrap();
+        std::fs::create_dir_all(&secrets).unwrap();
+        std::fs::write(logs.join("app.log"), "x\n").unwrap();
+        std::fs::write(secrets.join("key"), "x\n").unwrap();
+
+        let mut policy = PathPolicy::load(config.path()).unwrap();
+        match policy.check(&logs.join("app.log")) {
+            Err(PolicyError::NotApproved { directory, .. }) => {
+                assert_eq!(directory, logs.canonicalize().unwrap())
+            }
+            other => panic!("expected refusal, got {:?}", other),
+        }
+
+        policy.approve(&logs).unwrap();
+        policy.save().unwrap();
+        let mut policy = PathPolicy::load(config.path()).unwrap();
+        assert!(policy.check(&logs.join("app.log")).is_ok());
+        assert!(policy.check(&logs.join("../secrets/key")).is_err());
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(secrets.join("key"), logs.join("link.log")).unwrap();
+            assert!(policy.check(&logs.join("link.log")).is_err());
+        }
+        assert!(policy.approve(&logs.join("app.log")).is_err());
+
+        assert!(policy.revoke(&logs));
+        assert!(policy.check(&logs.join("app.log")).is_err());
+    }
+}
@@ -0,0 +1,196 @@
+use parking_lot::Mutex;
+use serde_json::{json, Map, Value};
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::Instant;
+
+/// Most spans kept; the oldest are dropped first so a long session can't grow without bound
+pub const MAX_SPANS: usize = 100_000;
+
+/// A finished span
+#[derive(Debug, Clone)]
+struct SpanRecord {
+    category: &'static str,
+    name: &'static str,
+    /// Microseconds since the profiler started
+    start: u64,
+    duration: u64,
+    thread: u64,
+    args: Map<String, Value>,
+}
+
+/// Spans recorded this session, shared by every thread
+struct Profiler {
+    epoch: Instant,
+    spans: Mutex<VecDeque<SpanRecord>>,
+    /// Names of the threads spans were recorded on, by trace thread id
+    threads: Mutex<Vec<(u64, String)>>,
+    dropped: AtomicU64,
+}
+
+fn profiler() -> &'static Profiler {
+    static PROFILER: OnceLock<Profiler> = OnceLock::new();
+    PROFILER.get_or_init(|| Profiler {
+        epoch: Instant::now(),
+        spans: Mutex::new(VecDeque::new()),
+        threads: Mutex::new(Vec::new()),
+        dropped: AtomicU64::new(0),
+    })
+}
+
+/// Small stable id for the current thread, registering its name on first use
+fn thread_id() -> u64 {
+    static NEXT: AtomicU64 = AtomicU64::new(1);
+    thread_local! {
+        static ID: Cell<u64> = const { Cell::new(0) };
+    }
+    ID.with(|id| {
+        if id.get() == 0 {
+            let new_id = NEXT.fetch_add(1, Ordering::Relaxed);
+            let current = std::thread::current();
+            let name = current
+                .name()
+                .map_or_else(|| format!("thread-{}", new_id), str::to_string);
+            profiler().threads.lock().push((new_id, name));
+            id.set(new_id);
+        }
+        id.get()
+    })
+}
+
+/// A timed phase, recorded when dropped
+#[must_use = "a span is recorded when it is dropped"]
+pub struct Span {
+    category: &'static str,
+    name: &'static str,
+    start: Instant,
+    args: Map<String, Value>,
+}
+
+/// Start timing a phase; `category` groups related phases such as "index" or "query"
+pub fn span(category: &'static str, name: &'static str) -> Span {
+    Span {
+        category,
+        name,
+        start: Instant::now(),
+        args: Map::new(),
+    }
+}
+
+impl Span {
+    /// Attach a value shown with the span in the trace viewer
+    pub fn arg(mut self, key: &str, value: impl Into<Value>) -> Self {
+        self.args.insert(key.to_string(), value.into());
+        self
+    }
+
+    /// Attach a value once it is known, e.g. the number of results
+    pub fn record(&mut self, key: &str, value: impl Into<Value>) {
+        self.args.insert(key.to_string(), value.into());
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let profiler = profiler();
+        let record = SpanRecord {
+            category: self.category,
+            name: self.name,
+            start: self
+                .start
+                .saturating_duration_since(profiler.epoch)
+                .as_micros() as u64,
+            duration: self.start.elapsed().as_micros() as u64,
+            thread: thread_id(),
+            args: std::mem::take(&mut self.args),
+        };
+        let mut spans = profiler.spans.lock();
+        if spans.len() >= MAX_SPANS {
+            spans.pop_front();
+            profiler.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        spans.push_back(record);
+    }
+}
+
+/// The session's spans in Chrome trace event format, loadable in chrome://tracing or Perfetto
+/// Returns the document and the number of spans dropped for exceeding `MAX_SPANS`
+pub fn chrome_trace() -> (Value, u64) {
+    let profiler = profiler();
+    let pid = std::process::id();
+    let mut events: Vec<Value> = profiler
+        .threads
+        .lock()
+        .iter()
+        .map(|(tid, name)| {
+            json!({
+                "name": "thread_name",
+                "ph": "M",
+                "pid": pid,
+                "tid": tid,
+                "args": { "name": name },
+            })
+        })
+        .collect();
+    events.extend(profiler.spans.lock().iter().map(|span| {
+        json!({
+            "name": span.name,
+            "cat": span.category,
+            "ph": "X",
+            "ts": span.start,
+            "dur": span.duration,
+            "pid": pid,
+            "tid": span.thread,
+            "args": span.args,
+        })
+    }));
+    let dropped = profiler.dropped.load(Ordering::Relaxed);
+    let trace = json!({
+        "traceEvents": events,
+        "displayTimeUnit": "ms",
+        "otherData": { "version": env!("CARGO_PKG_VERSION"), "droppedSpans": dropped },
+    });
+    (trace, dropped)
+}
+
+/// Forget the spans recorded so far
+pub fn clear() {
+    let profiler = profiler();
+    profiler.spans.lock().clear();
+    profiler.dropped.store(0, Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chrome_trace() {
+        {
+            let mut span = span("test", "profile_outer").arg("pattern", "ERROR");
+            let _inner = super::span("test", "profile_inner");
+            span.record("matches", 3);
+        }
+
+        let (trace, _) = chrome_trace();
+        let events = trace["traceEvents"].as_array().unwrap();
+        let find = |name: &str| {
+            events
+                .iter()
+                .find(|e| e["name"] == name && e["ph"] == "X")
+                .unwrap()
+        };
+        let outer = find("profile_outer");
+        let inner = find("profile_inner");
+        assert_eq!(outer["cat"], "test");
+        assert_eq!(outer["args"]["pattern"], "ERROR");
+        assert_eq!(outer["args"]["matches"], 3);
+        assert_eq!(outer["tid"], inner["tid"]);
+        assert!(outer["ts"].as_u64() <= inner["ts"].as_u64());
+        assert!(events
+            .iter()
+            .any(|e| e["ph"] == "M" && e["tid"] == outer["tid"]));
+    }
+}
@@ -1,20 +1,55 @@
-use datafusion::arrow::array::{ArrayRef, Int64Array, StringArray};
-use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use crate::charts::Metric;
+use crate::columnar::{ColumnarFormat, COLUMNAR_TABLE};
+use crate::correlate::COMPARE_TABLE;
+use crate::field_search::{find_column, quote_identifier};
+use crate::indexer::is_gzip;
+use crate::lake::LAKE_TABLE;
+use crate::parsers::csv::CsvDialect;
+use crate::parsers::json::{classify_line, LineFormat};
+use crate::parsers::{Column, ColumnType, FieldValue, ParsedTable, Record, MIXED_JSON_RATIO};
+use crate::profile;
+use crate::regex_cache;
+use crate::results::{crosstab, CachedResult, Crosstab, ResultCache, ResultCell, SortDirection};
+use crate::views::{is_identifier, SavedView};
+use crate::zones::ZonedTable;
+use chrono::SecondsFormat;
+use datafusion::arrow::array::{
+    Array, ArrayRef, BooleanArray, DictionaryArray, Float64Array, Int64Array, StringArray,
+    TimestampMillisecondArray, UInt64Array,
+};
+use datafusion::arrow::compute::cast;
+use datafusion::arrow::datatypes::{DataType, Field, Int32Type, Schema, SchemaRef, TimeUnit};
 use datafusion::arrow::record_batch::RecordBatch;
-use datafusion::datasource::MemTable;
+use datafusion::common::stats::Precision;
+use datafusion::common::tree_node::{TreeNode, TreeNodeRecursion};
+use datafusion::dataframe::DataFrameWriteOptions;
+use datafusion::datasource::{MemTable, TableProvider};
 use datafusion::error::DataFusionError;
 use datafusion::execution::context::SessionContext;
-use datafusion::logical_expr::{create_udf, ColumnarValue, Volatility};
+use datafusion::execution::memory_pool::{MemoryPool, MemoryReservation, UnboundedMemoryPool};
+use datafusion::execution::options::ArrowReadOptions;
+use datafusion::execution::runtime_env::RuntimeEnvBuilder;
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::logical_expr::{create_udf, ColumnarValue, LogicalPlan, Volatility};
+use datafusion::physical_plan::metrics::MetricValue;
+use datafusion::physical_plan::{collect, ExecutionPlan};
 use datafusion::prelude::*;
+use datafusion::scalar::ScalarValue;
+use flate2::read::MultiGzDecoder;
+use parking_lot::RwLock;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashSet};
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use datafusion::arrow::error::ArrowError;
 use thiserror::Error;
 use tokio::sync::Mutex;
+use tracing::debug;
 
 /// Errors that can occur during query operations
 #[derive(Error, Debug)]
@@ -31,6 +66,16 @@ pub enum QueryError {
     InvalidQuery(String),
     #[error("JSON parsing error: {0}")]
     JsonError(#[from] serde_json::Error),
+    #[error("Invalid table name \"{0}\": use letters, digits and underscores")]
+    InvalidName(String),
+    #[error("\"{0}\" is the name of a table the app registers; choose another")]
+    ReservedName(String),
+    #[error("Unexpected value: {0}")]
+    UnexpectedValue(String),
+    #[error("Result {0} is no longer kept; run its query again")]
+    UnknownResult(u64),
+    #[error("No cell at row {row}, column {column} of the result")]
+    NoSuchCell { row: usize, column: usize },
 }
 
 /// File format detected for a log file
@@ -39,6 +84,8 @@ pub enum FileFormat {
     PlainText,
     Ndjson,
     Csv,
+    /// JSON lines interleaved with plain text such as banners and stack traces
+    Mixed,
 }
 
 /// Result of a SQL query execution
@@ -47,12 +94,300 @@ pub struct QueryResult {
     pub columns: Vec<String>,
     pub rows: Vec<Vec<serde_json::Value>>,
     pub row_count: usize,
+    /// Whether a queried table was still being filled by a background parse
+    #[serde(default)]
+    pub partial: bool,
+    /// Set when the query ran over a sample of a table rather than all of it
+    #[serde(default)]
+    pub sample: Option<SampleInfo>,
+    /// What running the query took
+    #[serde(default)]
+    pub stats: Option<QueryStats>,
+    /// Values that couldn't be read, shown as null in their rows
+    #[serde(default)]
+    pub errors: Vec<RowError>,
+    /// Id the result's batches are kept under, to sort and filter them again without
+    /// rerunning the query while they're among the most recent
+    #[serde(default)]
+    pub result_id: Option<u64>,
 }
 
+/// A value of a result row that couldn't be converted
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RowError {
+    /// Index into the result's rows
+    pub row: usize,
+    pub column: String,
+    pub message: String,
+}
+
+/// What running a query took, to show which query shapes are cheap on the data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueryStats {
+    /// From planning until the last row was collected
+    pub elapsed_ms: u64,
+    /// Most memory reserved at once by operators that account for it, such as sorts,
+    /// aggregations and joins
+    pub peak_memory_bytes: usize,
+    /// Rows read from the tables; None when a scan can't tell
+    pub rows_scanned: Option<usize>,
+    pub rows_returned: usize,
+    /// Whether a scan skipped rows, pages or files through a pushed-down filter
+    pub scan_pruned: bool,
+}
+
+/// Add the tables a plan scans to `tables`, including those read by subqueries and views
+fn collect_table_names(
+    plan: &LogicalPlan,
+    tables: &mut HashSet<String>,
+) -> Result<(), DataFusionError> {
+    plan.apply_with_subqueries(|node| {
+        if let LogicalPlan::TableScan(scan) = node {
+            tables.insert(scan.table_name.table().to_string());
+            if let Some(view) = scan.source.get_logical_plan() {
+                collect_table_names(&view, tables)?;
+            }
+        }
+        Ok(TreeNodeRecursion::Continue)
+    })?;
+    Ok(())
+}
+
+/// Memory pool without a limit that remembers the most reserved at once since it was reset
+#[derive(Debug, Default)]
+struct PeakMemoryPool {
+    inner: UnboundedMemoryPool,
+    peak: AtomicUsize,
+}
+
+impl PeakMemoryPool {
+    fn reset_peak(&self) {
+        self.peak.store(self.inner.reserved(), Ordering::Relaxed);
+    }
+
+    fn peak(&self) -> usize {
+        self.peak.load(Ordering::Relaxed)
+    }
+
+    fn record(&self) {
+        self.peak
+            .fetch_max(self.inner.reserved(), Ordering::Relaxed);
+    }
+}
+
+impl MemoryPool for PeakMemoryPool {
+    fn grow(&self, reservation: &MemoryReservation, additional: usize) {
+        self.inner.grow(reservation, additional);
+        self.record();
+    }
+
+    fn shrink(&self, reservation: &MemoryReservation, shrink: usize) {
+        self.inner.shrink(reservation, shrink);
+    }
+
+    fn try_grow(
+        &self,
+        reservation: &MemoryReservation,
+        additional: usize,
+    ) -> datafusion::error::Result<()> {
+        self.inner.try_grow(reservation, additional)?;
+        self.record();
+        Ok(())
+    }
+
+    fn reserved(&self) -> usize {
+        self.inner.reserved()
+    }
+}
+
+/// A random share of a table's rows to query instead of all of them, like `TABLESAMPLE`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SampleOptions {
+    /// Share of the rows kept, above 0 and at most 100
+    pub percent: f64,
+    /// Picks the rows; the same seed picks the same rows
+    #[serde(default)]
+    pub seed: u64,
+    /// Table sampled; None samples the file's line table
+    #[serde(default)]
+    pub table: Option<String>,
+}
+
+impl SampleOptions {
+    /// Whether the row with this line number is in the sample, however the rows are read
+    fn keeps(&self, line_number: i64) -> bool {
+        let hash = splitmix64(self.seed.wrapping_add(line_number as u64));
+        (hash >> 11) as f64 / (1u64 << 53) as f64 * 100.0 < self.percent
+    }
+}
+
+/// How a sampled result relates to the whole table
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SampleInfo {
+    pub table: String,
+    pub percent: f64,
+    pub sampled_rows: usize,
+    pub total_rows: usize,
+    /// Factor turning counts and sums over the sample into estimates for the whole table
+    pub scale: f64,
+}
+
+/// How far a running query has got
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct QueryProgress {
+    /// Partitions of the table scans read to the end
+    pub partitions_done: usize,
+    pub partitions: usize,
+    /// Rows out of the operators reading the tables so far
+    pub rows_processed: usize,
+}
+
+/// Receives the progress of a running query
+pub type QueryProgressFn<'a> = dyn Fn(QueryProgress) + Send + Sync + 'a;
+
+/// How long a query runs before its progress is reported unless set otherwise, and how
+/// often after that
+pub const PROGRESS_DELAY: Duration = Duration::from_secs(1);
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Puts a table back as it was when a sampled query ends, including when it's cancelled
+struct SampleSwap<'a> {
+    ctx: &'a SessionContext,
+    table: String,
+    original: Option<Arc<dyn TableProvider>>,
+}
+
+impl Drop for SampleSwap<'_> {
+    fn drop(&mut self) {
+        if let Some(original) = self.original.take() {
+            self.ctx.deregister_table(self.table.as_str()).ok();
+            self.ctx.register_table(self.table.as_str(), original).ok();
+        }
+    }
+}
+
+/// A value bound to a `$n` placeholder of a query
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "lowercase")]
+pub enum QueryParam {
+    Null,
+    Text(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    /// Any timestamp format the viewer recognizes in lines, such as RFC 3339
+    Timestamp(String),
+}
+
+impl QueryParam {
+    fn to_scalar(&self) -> Result<ScalarValue, QueryError> {
+        Ok(match self {
+            QueryParam::Null => ScalarValue::Null,
+            QueryParam::Text(text) => ScalarValue::Utf8(Some(text.clone())),
+            QueryParam::Int(value) => ScalarValue::Int64(Some(*value)),
+            QueryParam::Float(value) => ScalarValue::Float64(Some(*value)),
+            QueryParam::Bool(value) => ScalarValue::Boolean(Some(*value)),
+            QueryParam::Timestamp(text) => {
+                let millis = crate::timestamp::parse_timestamp(text).ok_or_else(|| {
+                    QueryError::InvalidQuery(format!("Unrecognized timestamp \"{}\"", text))
+                })?;
+                ScalarValue::TimestampMillisecond(Some(millis), None)
+            }
+        })
+    }
+}
+
+/// Documentation of a custom SQL function, for the query editor's reference
+#[derive(Debug, Clone, Serialize)]
+pub struct SqlFunctionDoc {
+    pub name: &'static str,
+    pub signature: &'static str,
+    pub description: &'static str,
+    pub example: &'static str,
+}
+
+/// Custom SQL functions registered with every engine
+pub const SQL_FUNCTIONS: &[SqlFunctionDoc] = &[
+    SqlFunctionDoc {
+        name: "regex_match",
+        signature: "regex_match(text, pattern) -> boolean",
+        description: "Whether the text matches the regular expression",
+        example: "SELECT * FROM logs WHERE regex_match(line, '^ERROR')",
+    },
+    SqlFunctionDoc {
+        name: "regex_extract",
+        signature: "regex_extract(text, pattern) -> text",
+        description: "The first capture group of the first match, or the whole match \
+                      without a group; NULL when nothing matches. Extracts correlation \
+                      keys such as request ids to join files on",
+        example: "SELECT a.line, b.line FROM logs a JOIN compare_logs b \
+                  ON regex_extract(a.line, 'req=(\\w+)') \
+                  = regex_extract(b.line, 'req=(\\w+)')",
+    },
+    SqlFunctionDoc {
+        name: "json_extract",
+        signature: "json_extract(json, key) -> text",
+        description: "A top-level value of a JSON line as JSON text; NULL for plain-text lines",
+        example: "SELECT json_extract(line, 'level') AS level FROM logs",
+    },
+];
+
+/// Arrow batches built so far for a parsed table that grows while it is parsed
+#[derive(Debug, Default)]
+pub struct ParsedBatches {
+    columns: Vec<Column>,
+    schema: Option<SchemaRef>,
+    batches: Vec<RecordBatch>,
+    rows: usize,
+}
+
+/// Tables the app registers itself, which lookup tables and views can't be named after
+const RESERVED_TABLES: [&str; 6] = [
+    "logs",
+    "parsed",
+    "parse_errors",
+    COMPARE_TABLE,
+    COLUMNAR_TABLE,
+    LAKE_TABLE,
+];
+
+/// Check a name given to a lookup table or view: unquoted, and not one of the app's tables
+/// Names are compared without case, as SQL reads them
+fn check_table_name(name: &str) -> Result<(), QueryError> {
+    if !is_identifier(name) {
+        return Err(QueryError::InvalidName(name.to_string()));
+    }
+    if RESERVED_TABLES.iter().any(|t| t.eq_ignore_ascii_case(name)) {
+        return Err(QueryError::ReservedName(name.to_string()));
+    }
+    Ok(())
+}
+
+/// Name a kept result is queried under when it's sorted or filtered again
+const KEPT_RESULT_TABLE: &str = "kept_result";
+
+/// Columns every parsed table starts with, tying its rows to source lines
+pub const SOURCE_COLUMNS: [&str; 2] = ["line_number", "raw_line"];
+
+/// Looks up the text of a 1-based source line
+pub type RawLineFn<'a> = dyn Fn(u64) -> Option<String> + Sync + 'a;
+
 /// SQL query engine powered by Apache DataFusion
 pub struct QueryEngine {
     ctx: Mutex<SessionContext>,
     registered_table: Mutex<Option<String>>,
+    /// Whether the custom SQL functions are registered, done on first use
+    udfs_registered: AtomicBool,
+    /// Tables kept by `clear`, loaded by the user to join against
+    lookups: RwLock<BTreeSet<String>>,
+    /// Views re-created whenever a table is registered, so they read the current data
+    views: RwLock<Vec<SavedView>>,
+    /// The session's memory pool, for the peak memory of each query
+    memory: Arc<PeakMemoryPool>,
+    /// Batches of the latest results, by result id
+    results: RwLock<ResultCache>,
+    /// How long a query runs before its progress is reported
+    progress_delay: RwLock<Duration>,
 }
 
 impl QueryEngine {
@@ -63,50 +398,140 @@ impl QueryEngine {
             .with_target_partitions(num_cpus::get())
             .with_information_schema(true);
 
-        let ctx = SessionContext::new_with_config(config);
+        let memory = Arc::new(PeakMemoryPool::default());
+        let runtime = RuntimeEnvBuilder::new()
+            .with_memory_pool(memory.clone())
+            .build_arc()
+            .expect("default runtime environment");
+        let ctx = SessionContext::new_with_config_rt(config, runtime);
 
         QueryEngine {
             ctx: Mutex::new(ctx),
+            memory,
             registered_table: Mutex::new(None),
+            udfs_registered: AtomicBool::new(false),
+            lookups: RwLock::new(BTreeSet::new()),
+            views: RwLock::new(Vec::new()),
+            results: RwLock::new(ResultCache::default()),
+            progress_delay: RwLock::new(PROGRESS_DELAY),
         }
     }
 
-    /// Detect the format of a file by examining its content
+    /// Report the progress of queries once they have run this long rather than
+    /// `PROGRESS_DELAY`
+    pub fn set_progress_delay(&self, delay: Duration) {
+        *self.progress_delay.write() = delay;
+    }
+
+    /// Detect the format of a file by sampling its start and a few interior blocks, or
+    /// only the start of a gzip-compressed file's contents
     pub fn detect_format<P: AsRef<Path>>(path: P) -> Result<FileFormat, QueryError> {
-        let content = std::fs::read_to_string(&path)?;
-        let first_lines: Vec<&str> = content.lines().take(10).collect();
+        let mut file = File::open(path)?;
+        let file_len = file.metadata()?.len();
+        if is_gzip(&mut file)? {
+            let mut prefix = Vec::new();
+            MultiGzDecoder::new(BufReader::new(file))
+                .take(DETECT_PREFIX_BYTES)
+                .read_to_end(&mut prefix)?;
+            // A full prefix may end inside a line, like that of a large plain file
+            let len = match prefix.len() as u64 {
+                DETECT_PREFIX_BYTES => u64::MAX,
+                len => len,
+            };
+            return Ok(Self::detect_format_blocks(&[prefix], len));
+        }
+
+        let mut blocks = Vec::new();
+        for range in sample_ranges(file_len) {
+            file.seek(SeekFrom::Start(range.start))?;
+            let mut block = Vec::with_capacity((range.end - range.start) as usize);
+            (&mut file)
+                .take(range.end - range.start)
+                .read_to_end(&mut block)?;
+            blocks.push(block);
+        }
+        Ok(Self::detect_format_blocks(&blocks, file_len))
+    }
+
+    /// Detect the format of already-loaded file contents, such as an open file's mmap
+    pub fn detect_format_bytes(data: &[u8]) -> FileFormat {
+        let blocks: Vec<&[u8]> = sample_ranges(data.len() as u64)
+            .map(|range| &data[range.start as usize..range.end as usize])
+            .collect();
+        Self::detect_format_blocks(&blocks, data.len() as u64)
+    }
+
+    /// Classify sampled blocks; the first is the file's prefix, the rest start mid-line
+    fn detect_format_blocks<B: AsRef<[u8]>>(blocks: &[B], file_len: u64) -> FileFormat {
+        let Some((prefix, interior)) = blocks.split_first() else {
+            return FileFormat::PlainText;
+        };
+        let prefix = String::from_utf8_lossy(prefix.as_ref());
+        let prefix_truncated = (prefix.len() as u64) < file_len;
+        let first_lines: Vec<&str> = complete_lines(&prefix, false, prefix_truncated)
+            .take(DETECT_SAMPLE_LINES)
+            .collect();
 
         if first_lines.is_empty() {
-            return Ok(FileFormat::PlainText);
+            return FileFormat::PlainText;
         }
 
+        let interior: Vec<_> = interior
+            .iter()
+            .map(|block| String::from_utf8_lossy(block.as_ref()))
+            .collect();
+        let sample: Vec<&str> = first_lines
+            .iter()
+            .copied()
+            .chain(
+                interior
+                    .iter()
+                    .flat_map(|block| complete_lines(block, true, true).take(DETECT_SAMPLE_LINES)),
+            )
+            .collect();
+
         // Check for NDJSON (lines starting with { and ending with })
-        let json_lines = first_lines
+        let json_lines = sample
             .iter()
-            .filter(|line| {
-                let trimmed = line.trim();
-                trimmed.starts_with('{') && trimmed.ends_with('}')
-            })
+            .filter(|line| classify_line(line) == LineFormat::Json)
             .count();
 
-        if json_lines > first_lines.len() / 2 {
-            return Ok(FileFormat::Ndjson);
+        if json_lines > sample.len() / 2 {
+            debug!(
+                json_lines,
+                sampled = sample.len(),
+                "detected NDJSON: most sampled lines are JSON"
+            );
+            return FileFormat::Ndjson;
         }
 
-        // Check for CSV (consistent comma count across lines)
-        let comma_counts: Vec<usize> = first_lines
-            .iter()
-            .map(|line| line.matches(',').count())
-            .collect();
+        if json_lines > 0 && json_lines * MIXED_JSON_RATIO >= sample.len() {
+            debug!(
+                json_lines,
+                sampled = sample.len(),
+                "detected mixed JSON and plain text"
+            );
+            return FileFormat::Mixed;
+        }
 
-        if comma_counts.len() > 1 {
-            let first_count = comma_counts[0];
-            if first_count > 0 && comma_counts.iter().all(|&c| c == first_count) {
-                return Ok(FileFormat::Csv);
-            }
+        // Check for CSV (comma, tab, semicolon or pipe splitting every record evenly)
+        // Only the prefix is used, since interior blocks may start inside a quoted field
+        if let Some(dialect) = CsvDialect::sniff(&first_lines) {
+            debug!(
+                delimiter = ?dialect.delimiter,
+                has_header = dialect.has_header,
+                sampled = first_lines.len(),
+                "detected CSV: every sampled line splits into the same number of fields"
+            );
+            return FileFormat::Csv;
         }
 
-        Ok(FileFormat::PlainText)
+        debug!(
+            json_lines,
+            sampled = sample.len(),
+            "no structured format detected, using plain text"
+        );
+        FileFormat::PlainText
     }
 
     /// Register a table from a file path
@@ -117,108 +542,321 @@ impl QueryEngine {
     ) -> Result<FileFormat, QueryError> {
         let path = path.as_ref();
         let format = Self::detect_format(path)?;
-        let path_str = path.to_string_lossy().to_string();
-        let table_name = table_name.to_string();
+        let mut file = File::open(path)?;
+        if is_gzip(&mut file)? {
+            let reader = BufReader::new(MultiGzDecoder::new(BufReader::new(file)));
+            self.register_lines(reader, table_name).await?;
+        } else {
+            self.register_lines(BufReader::new(file), table_name)
+                .await?;
+        }
+        Ok(format)
+    }
 
-        let ctx = self.ctx.lock().await;
+    /// Register a line table from text already in memory, such as the line view of a
+    /// columnar file
+    pub async fn register_line_bytes(
+        &self,
+        data: &[u8],
+        table_name: &str,
+    ) -> Result<(), QueryError> {
+        self.register_lines(data, table_name).await
+    }
+
+    async fn register_lines<R: BufRead + Send>(
+        &self,
+        reader: R,
+        table_name: &str,
+    ) -> Result<(), QueryError> {
+        let table = Self::line_table(reader)?;
+        self.register_line_table(table, table_name).await
+    }
+
+    /// Read text into a line table, such as the decompressed contents of an open gzip file,
+    /// to register with `register_line_table` without holding the file across the await
+    pub fn line_table<R: BufRead>(mut reader: R) -> Result<ZonedTable, QueryError> {
+        let mut span = profile::span("query", "line_table");
 
         // For all formats, we create an in-memory table with line_number and line columns
         // This gives us consistent querying regardless of format
-        let file = File::open(&path_str)?;
-        let reader = BufReader::new(file);
         
         // Read lines in batches to create Arrow arrays
         const BATCH_SIZE: usize = 100_000;
         let mut all_batches = Vec::new();
         
+        // line_format tags each line as json or text so structured queries on mixed
+        // files can skip the plain-text lines; byte_offset and byte_len (without the line
+        // terminator) place each line in the file for byte-oriented tools
         let schema = Arc::new(Schema::new(vec![
             Field::new("line_number", DataType::Int64, false),
             Field::new("line", DataType::Utf8, true),
+            Field::new("line_format", DataType::Utf8, false),
+            Field::new("byte_offset", DataType::Int64, false),
+            Field::new("byte_len", DataType::Int64, false),
         ]));
         
         let mut line_numbers: Vec<i64> = Vec::with_capacity(BATCH_SIZE);
         let mut lines: Vec<String> = Vec::with_capacity(BATCH_SIZE);
+        let mut offsets: Vec<i64> = Vec::with_capacity(BATCH_SIZE);
+        let mut lengths: Vec<i64> = Vec::with_capacity(BATCH_SIZE);
         let mut current_line: i64 = 1;
-        
-        for line_result in reader.lines() {
-            let line = line_result.unwrap_or_default();
+        let mut offset: i64 = 0;
+        let mut buf = Vec::new();
+
+        loop {
+            buf.clear();
+            let read = reader.read_until(b'\n', &mut buf)?;
+            if read == 0 {
+                break;
+            }
+            let line = buf.strip_suffix(b"\n").unwrap_or(&buf);
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
             line_numbers.push(current_line);
-            lines.push(line);
+            lines.push(String::from_utf8_lossy(line).into_owned());
+            offsets.push(offset);
+            lengths.push(line.len() as i64);
             current_line += 1;
-            
+            offset += read as i64;
+
             if line_numbers.len() >= BATCH_SIZE {
-                let batch = RecordBatch::try_new(
-                    schema.clone(),
-                    vec![
-                        Arc::new(Int64Array::from(std::mem::take(&mut line_numbers))) as ArrayRef,
-                        Arc::new(StringArray::from(std::mem::take(&mut lines))) as ArrayRef,
-                    ],
-                )?;
-                all_batches.push(batch);
-                line_numbers = Vec::with_capacity(BATCH_SIZE);
-                lines = Vec::with_capacity(BATCH_SIZE);
+                all_batches.push(line_batch(
+                    &schema,
+                    std::mem::take(&mut line_numbers),
+                    std::mem::take(&mut lines),
+                    std::mem::take(&mut offsets),
+                    std::mem::take(&mut lengths),
+                )?);
             }
         }
         
         // Don't forget the last batch
         if !line_numbers.is_empty() {
-            let batch = RecordBatch::try_new(
-                schema.clone(),
-                vec![
-                    Arc::new(Int64Array::from(line_numbers)) as ArrayRef,
-                    Arc::new(StringArray::from(lines)) as ArrayRef,
-                ],
-            )?;
-            all_batches.push(batch);
+            all_batches.push(line_batch(&schema, line_numbers, lines, offsets, lengths)?);
         }
         
-        // Create a MemTable from the batches
-        let mem_table = MemTable::try_new(schema, vec![all_batches])?;
-        ctx.register_table(&table_name, Arc::new(mem_table))?;
+        span.record("rows", current_line - 1);
+        debug!(rows = current_line - 1, "read line table");
+
+        // Zoned so filters on line_number and byte_offset skip the rest of the file
+        Ok(ZonedTable::try_new(schema, all_batches)?)
+    }
+
+    /// Register a table read by `line_table` as the engine's line table
+    pub async fn register_line_table(
+        &self,
+        table: ZonedTable,
+        table_name: &str,
+    ) -> Result<(), QueryError> {
+        let table_name = table_name.to_string();
+        let _span = profile::span("query", "register_table").arg("table", table_name.as_str());
+
+        let ctx = self.ctx.lock().await;
+        ctx.register_table(&table_name, Arc::new(table))?;
+        debug!(table = %table_name, "registered line table");
+        self.restore_views(&ctx).await;
 
         drop(ctx);
         *self.registered_table.lock().await = Some(table_name);
 
-        Ok(format)
+        Ok(())
+    }
+
+    /// Register parsed records as a typed table
+    /// The table starts with the 1-based `line_number` of each record's first source line and
+    /// that line's text as `raw_line`, looked up with `raw_line`, so any row leads back to
+    /// the viewer
+    pub async fn register_parsed(
+        &self,
+        table: &ParsedTable,
+        table_name: &str,
+        raw_line: &RawLineFn<'_>,
+    ) -> Result<(), QueryError> {
+        self.register_parsed_incremental(table, &mut ParsedBatches::default(), table_name, raw_line)
+            .await
+    }
+
+    /// Register a parsed table that grows between calls, converting only the records added
+    /// since `built` was last used unless the schema changed
+    pub async fn register_parsed_incremental(
+        &self,
+        table: &ParsedTable,
+        built: &mut ParsedBatches,
+        table_name: &str,
+        raw_line: &RawLineFn<'_>,
+    ) -> Result<(), QueryError> {
+        const BATCH_SIZE: usize = 100_000;
+        let _span = profile::span("query", "register_parsed")
+            .arg("table", table_name)
+            .arg("rows", table.records.len());
+
+        // Parsed fields can't shadow the columns every parsed table starts with
+        let columns: Vec<&Column> = table
+            .columns
+            .iter()
+            .filter(|c| !SOURCE_COLUMNS.contains(&c.name.as_str()))
+            .collect();
+        let schema = match &built.schema {
+            Some(schema) if built.columns == table.columns && built.rows <= table.records.len() => {
+                schema.clone()
+            }
+            _ => {
+                let mut fields = vec![
+                    Field::new("line_number", DataType::Int64, false),
+                    Field::new("raw_line", DataType::Utf8, true),
+                ];
+                fields.extend(columns.iter().map(|c| {
+                    let data_type = match c.column_type {
+                        ColumnType::Utf8 if low_cardinality(&c.name, &table.records) => {
+                            dictionary_type()
+                        }
+                        column_type => arrow_type(column_type),
+                    };
+                    Field::new(&c.name, data_type, true)
+                }));
+                let schema = Arc::new(Schema::new(fields));
+                *built = ParsedBatches {
+                    columns: table.columns.clone(),
+                    schema: Some(schema.clone()),
+                    batches: Vec::new(),
+                    rows: 0,
+                };
+                schema
+            }
+        };
+
+        for chunk in table.records[built.rows..].chunks(BATCH_SIZE) {
+            built.batches.push(Self::parsed_batch(
+                schema.clone(),
+                &columns,
+                chunk,
+                raw_line,
+            )?);
+        }
+        built.rows = table.records.len();
+
+        let table = ZonedTable::try_new(schema, built.batches.clone())?;
+        let ctx = self.ctx.lock().await;
+        ctx.deregister_table(table_name)?;
+        ctx.register_table(table_name, Arc::new(table))?;
+        self.restore_views(&ctx).await;
+        Ok(())
+    }
+
+    fn parsed_batch(
+        schema: Arc<Schema>,
+        columns: &[&Column],
+        records: &[Record],
+        raw_line: &RawLineFn<'_>,
+    ) -> Result<RecordBatch, QueryError> {
+        let slots: std::collections::HashMap<&str, usize> = columns
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (c.name.as_str(), i))
+            .collect();
+
+        // Lay each record out in column order so every column has one entry per row
+        let mut cells: Vec<Vec<Option<&FieldValue>>> =
+            vec![vec![None; records.len()]; columns.len()];
+        for (row, record) in records.iter().enumerate() {
+            for (name, value) in &record.fields {
+                if let Some(&slot) = slots.get(name.as_str()) {
+                    cells[slot][row] = Some(value);
+                }
+            }
+        }
+
+        let mut arrays: Vec<ArrayRef> = Vec::with_capacity(columns.len() + 2);
+        arrays.push(Arc::new(Int64Array::from_iter_values(
+            records.iter().map(|r| r.line_number as i64),
+        )));
+        arrays.push(Arc::new(StringArray::from_iter(
+            records.iter().map(|r| raw_line(r.line_number)),
+        )));
+        for ((column, values), field) in columns.iter().zip(cells).zip(&schema.fields()[2..]) {
+            let array: ArrayRef = match column.column_type {
+                ColumnType::Utf8 if field.data_type() == &dictionary_type() => {
+                    let texts: Vec<Option<String>> = values
+                        .into_iter()
+                        .map(|v| v.and_then(FieldValue::to_text))
+                        .collect();
+                    Arc::new(
+                        texts
+                            .iter()
+                            .map(Option::as_deref)
+                            .collect::<DictionaryArray<Int32Type>>(),
+                    )
+                }
+                ColumnType::Utf8 => Arc::new(StringArray::from_iter(
+                    values.into_iter().map(|v| v.and_then(FieldValue::to_text)),
+                )),
+                ColumnType::Int64 => {
+                    Arc::new(Int64Array::from_iter(values.into_iter().map(|v| match v {
+                        Some(FieldValue::Int(i)) => Some(*i),
+                        _ => None,
+                    })))
+                }
+                ColumnType::Float64 => Arc::new(Float64Array::from_iter(values.into_iter().map(
+                    |v| match v {
+                        Some(FieldValue::Float(f)) => Some(*f),
+                        Some(FieldValue::Int(i)) => Some(*i as f64),
+                        _ => None,
+                    },
+                ))),
+                ColumnType::Boolean => Arc::new(BooleanArray::from_iter(values.into_iter().map(
+                    |v| match v {
+                        Some(FieldValue::Bool(b)) => Some(*b),
+                        _ => None,
+                    },
+                ))),
+                ColumnType::Timestamp => Arc::new(TimestampMillisecondArray::from_iter(
+                    values.into_iter().map(|v| match v {
+                        Some(FieldValue::Timestamp(ms)) => Some(*ms),
+                        _ => None,
+                    }),
+                )),
+            };
+            arrays.push(array);
+        }
+
+        Ok(RecordBatch::try_new(schema, arrays)?)
+    }
+
+    /// Register the custom SQL functions unless already done; a failure is retried next call
+    pub async fn ensure_udfs(&self) -> Result<(), QueryError> {
+        if !self.udfs_registered.load(Ordering::Acquire) {
+            self.register_udfs().await?;
+            self.udfs_registered.store(true, Ordering::Release);
+        }
+        Ok(())
     }
 
     /// Register custom UDFs for log analysis
     pub async fn register_udfs(&self) -> Result<(), QueryError> {
-        let ctx = self.ctx.lock().await;
+        Self::register_udfs_in(&*self.ctx.lock().await);
+        Ok(())
+    }
 
-        // regex_match UDF
+    fn register_udfs_in(ctx: &SessionContext) {
+        // regex_match UDF; the pattern may differ per row, e.g. when read from a lookup table
         let regex_match = create_udf(
             "regex_match",
             vec![DataType::Utf8, DataType::Utf8],
             DataType::Boolean,
             Volatility::Immutable,
             Arc::new(|args: &[ColumnarValue]| {
-                let text_array = match &args[0] {
-                    ColumnarValue::Array(arr) => arr
-                        .as_any()
-                        .downcast_ref::<StringArray>()
-                        .ok_or_else(|| DataFusionError::Internal("Expected string array".into()))?
-                        .clone(),
-                    ColumnarValue::Scalar(scalar) => {
-                        let s = scalar.to_string();
-                        StringArray::from(vec![s.as_str()])
-                    }
-                };
-
-                let pattern = match &args[1] {
-                    ColumnarValue::Scalar(scalar) => scalar.to_string(),
-                    _ => return Err(DataFusionError::Internal("Pattern must be scalar".into())),
-                };
-
-                let regex = Regex::new(&pattern)
-                    .map_err(|e| DataFusionError::Internal(format!("Invalid regex: {}", e)))?;
-
-                let result: datafusion::arrow::array::BooleanArray = text_array
-                    .iter()
-                    .map(|opt| opt.map(|s| regex.is_match(s)))
-                    .collect();
-
-                Ok(ColumnarValue::Array(Arc::new(result)))
+                let text = StrArg::new(&args[0])?;
+                let pattern = StrArg::new(&args[1])?;
+                let mut last: Option<(&str, Arc<Regex>)> = None;
+                let result = (0..udf_rows(args))
+                    .map(|row| {
+                        let (Some(text), Some(pattern)) = (text.get(row), pattern.get(row)) else {
+                            return Ok(None);
+                        };
+                        let regex = row_regex(&mut last, pattern)?;
+                        Ok(Some(regex.is_match(text)))
+                    })
+                    .collect::<Result<BooleanArray, DataFusionError>>()?;
+                udf_result(args, Arc::new(result))
             }),
         );
 
@@ -231,171 +869,1260 @@ impl QueryEngine {
             DataType::Utf8,
             Volatility::Immutable,
             Arc::new(|args: &[ColumnarValue]| {
-                let json_array = match &args[0] {
-                    ColumnarValue::Array(arr) => arr
-                        .as_any()
-                        .downcast_ref::<StringArray>()
-                        .ok_or_else(|| DataFusionError::Internal("Expected string array".into()))?
-                        .clone(),
-                    ColumnarValue::Scalar(scalar) => {
-                        let s = scalar.to_string();
-                        StringArray::from(vec![s.as_str()])
-                    }
-                };
-
-                let key = match &args[1] {
-                    ColumnarValue::Scalar(scalar) => scalar.to_string().trim_matches('"').to_string(),
-                    _ => return Err(DataFusionError::Internal("Key must be scalar".into())),
-                };
+                let json = StrArg::new(&args[0])?;
+                let key = StrArg::new(&args[1])?;
 
-                let result: StringArray = json_array
-                    .iter()
-                    .map(|opt| {
-                        opt.and_then(|s| {
-                            serde_json::from_str::<serde_json::Value>(s)
-                                .ok()
-                                .and_then(|v| v.get(&key).map(|v| v.to_string()))
-                        })
+                // Plain-text lines in mixed files are NULL without attempting a parse
+                let result: StringArray = (0..udf_rows(args))
+                    .map(|row| {
+                        let (json, key) = (json.get(row)?, key.get(row)?);
+                        if classify_line(json) != LineFormat::Json {
+                            return None;
+                        }
+                        serde_json::from_str::<serde_json::Value>(json)
+                            .ok()
+                            .and_then(|v| v.get(key).map(|v| v.to_string()))
                     })
                     .collect();
-
-                Ok(ColumnarValue::Array(Arc::new(result)))
+                udf_result(args, Arc::new(result))
             }),
         );
 
         ctx.register_udf(json_extract);
 
-        Ok(())
+        // regex_extract UDF: the first capture group, or the whole match without one
+        let regex_extract = create_udf(
+            "regex_extract",
+            vec![DataType::Utf8, DataType::Utf8],
+            DataType::Utf8,
+            Volatility::Immutable,
+            Arc::new(|args: &[ColumnarValue]| {
+                let text = StrArg::new(&args[0])?;
+                let pattern = StrArg::new(&args[1])?;
+                let mut last: Option<(&str, Arc<Regex>)> = None;
+                let result = (0..udf_rows(args))
+                    .map(|row| {
+                        let (Some(text), Some(pattern)) = (text.get(row), pattern.get(row)) else {
+                            return Ok(None);
+                        };
+                        let regex = row_regex(&mut last, pattern)?;
+                        Ok(regex.captures(text).and_then(|captures| {
+                            captures
+                                .get(1)
+                                .or_else(|| captures.get(0))
+                                .map(|m| m.as_str().to_string())
+                        }))
+                    })
+                    .collect::<Result<StringArray, DataFusionError>>()?;
+                udf_result(args, Arc::new(result))
+            }),
+        );
+
+        ctx.register_udf(regex_extract);
     }
 
-    /// Execute a SQL query and return the results
-    pub async fn execute_sql(&self, query: &str) -> Result<QueryResult, QueryError> {
+    /// Load a CSV file as a lookup table, kept by `clear` so it can be joined against any
+    /// file opened later. Returns the number of rows loaded
+    pub async fn register_lookup(&self, name: &str, path: &Path) -> Result<usize, QueryError> {
+        check_table_name(name)?;
         let ctx = self.ctx.lock().await;
-        let df = ctx.sql(query).await?;
+        let df = ctx
+            .read_csv(path.to_string_lossy().as_ref(), CsvReadOptions::new())
+            .await?;
+        let schema = Arc::new(df.schema().as_arrow().clone());
+        // Read into memory, so the table stays usable if the CSV file changes or goes away
         let batches = df.collect().await?;
+        let rows = batches.iter().map(RecordBatch::num_rows).sum();
+        let mem_table = MemTable::try_new(schema, vec![batches])?;
+        ctx.deregister_table(name)?;
+        ctx.register_table(name, Arc::new(mem_table))?;
+        self.lookups.write().insert(name.to_string());
+        self.restore_views(&ctx).await;
+        Ok(rows)
+    }
 
-        if batches.is_empty() {
-            return Ok(QueryResult {
-                columns: vec![],
-                rows: vec![],
-                row_count: 0,
-            });
+    /// Drop a lookup table, returning whether it existed
+    pub async fn drop_lookup(&self, name: &str) -> bool {
+        if !self.lookups.write().remove(name) {
+            return false;
         }
+        self.ctx.lock().await.deregister_table(name).ok();
+        true
+    }
 
-        // Get column names from schema
-        let schema = batches[0].schema();
-        let columns: Vec<String> = schema.fields().iter().map(|f| f.name().clone()).collect();
+    /// A registered table, to register in another engine with `register_provider`
+    pub async fn table_provider(&self, name: &str) -> Result<Arc<dyn TableProvider>, QueryError> {
+        Ok(self.ctx.lock().await.table_provider(name).await?)
+    }
 
-        // Convert record batches to rows
-        let mut rows: Vec<Vec<serde_json::Value>> = Vec::new();
+    /// Register a table taken from another engine, such as the compare file's lines, so
+    /// queries can join against it
+    pub async fn register_provider(
+        &self,
+        name: &str,
+        provider: Arc<dyn TableProvider>,
+    ) -> Result<(), QueryError> {
+        let ctx = self.ctx.lock().await;
+        ctx.deregister_table(name)?;
+        ctx.register_table(name, provider)?;
+        self.restore_views(&ctx).await;
+        Ok(())
+    }
 
-        for batch in &batches {
-            for row_idx in 0..batch.num_rows() {
-                let mut row: Vec<serde_json::Value> = Vec::new();
-                for col_idx in 0..batch.num_columns() {
-                    let value = Self::extract_value(batch.column(col_idx), row_idx);
-                    row.push(value);
-                }
-                rows.push(row);
+    /// Drop a table registered with `register_provider`, returning whether it existed
+    /// Views reading it, such as a correlation view over both files, go with it; saved ones
+    /// come back once it is registered again
+    pub async fn drop_provider(&self, name: &str) -> bool {
+        let ctx = self.ctx.lock().await;
+        if !matches!(ctx.deregister_table(name), Ok(Some(_))) {
+            return false;
+        }
+        for table in table_names(&ctx) {
+            let Ok(provider) = ctx.table_provider(table.as_str()).await else {
+                continue;
+            };
+            let mut read = HashSet::new();
+            let reads_it = provider.get_logical_plan().is_some_and(|plan| {
+                collect_table_names(&plan, &mut read).is_ok() && read.contains(name)
+            });
+            if reads_it {
+                ctx.deregister_table(table.as_str()).ok();
             }
         }
+        true
+    }
 
-        let row_count = rows.len();
-
-        Ok(QueryResult {
-            columns,
-            rows,
-            row_count,
-        })
+    /// Create or replace a view that isn't saved, such as one built by a command; it goes
+    /// away with the tables it reads
+    pub async fn create_temporary_view(&self, name: &str, sql: &str) -> Result<(), QueryError> {
+        check_table_name(name)?;
+        let view = SavedView {
+            name: name.to_string(),
+            sql: sql.to_string(),
+        };
+        self.create_view(&*self.ctx.lock().await, &view).await
     }
 
-    /// Extract a value from an Arrow array at a specific index
-    fn extract_value(array: &ArrayRef, index: usize) -> serde_json::Value {
-        use datafusion::arrow::array::*;
-        use datafusion::arrow::datatypes::DataType;
+    /// Write a query's rows as Parquet files under `dir`, in Hive-style `column=value`
+    /// directories for each of `partition_by`. File names are unique per write, so writing
+    /// to the same directory again adds to it. Returns the number of rows written
+    pub async fn write_partitioned_parquet(
+        &self,
+        sql: &str,
+        dir: &Path,
+        partition_by: &[&str],
+    ) -> Result<u64, QueryError> {
+        let _span = profile::span("query", "write_parquet").arg("sql", sql);
+        let ctx = self.ctx.lock().await;
+        let df = ctx.sql(sql).await?;
+        let options = DataFrameWriteOptions::new()
+            .with_partition_by(partition_by.iter().map(|c| c.to_string()).collect());
+        let batches = df.write_parquet(&directory_url(dir), options, None).await?;
+        // The write reports its row count as a single `count` value
+        Ok(batches
+            .iter()
+            .filter_map(|batch| batch.column(0).as_any().downcast_ref::<UInt64Array>())
+            .flat_map(|counts| counts.iter().flatten())
+            .sum())
+    }
 
-        if array.is_null(index) {
-            return serde_json::Value::Null;
+    /// Register a directory of Parquet files, such as one written by
+    /// `write_partitioned_parquet`, reading its `column=value` directories as text columns
+    /// Kept by `clear` like a lookup table
+    pub async fn register_parquet_dir(
+        &self,
+        name: &str,
+        dir: &Path,
+        partition_cols: &[&str],
+    ) -> Result<(), QueryError> {
+        if !is_identifier(name) {
+            return Err(QueryError::InvalidName(name.to_string()));
         }
+        let options = ParquetReadOptions::default().table_partition_cols(
+            partition_cols
+                .iter()
+                .map(|c| (c.to_string(), DataType::Utf8))
+                .collect(),
+        );
+        let ctx = self.ctx.lock().await;
+        ctx.deregister_table(name)?;
+        ctx.register_parquet(name, &directory_url(dir), options)
+            .await?;
+        self.lookups.write().insert(name.to_string());
+        self.restore_views(&ctx).await;
+        Ok(())
+    }
 
-        match array.data_type() {
-            DataType::Utf8 => {
-                let arr = array.as_any().downcast_ref::<StringArray>().unwrap();
-                serde_json::Value::String(arr.value(index).to_string())
-            }
-            DataType::LargeUtf8 => {
-                let arr = array.as_any().downcast_ref::<LargeStringArray>().unwrap();
-                serde_json::Value::String(arr.value(index).to_string())
-            }
-            DataType::Int8 => {
-                let arr = array.as_any().downcast_ref::<Int8Array>().unwrap();
-                serde_json::json!(arr.value(index))
-            }
-            DataType::Int16 => {
-                let arr = array.as_any().downcast_ref::<Int16Array>().unwrap();
-                serde_json::json!(arr.value(index))
-            }
-            DataType::Int32 => {
-                let arr = array.as_any().downcast_ref::<Int32Array>().unwrap();
-                serde_json::json!(arr.value(index))
-            }
-            DataType::Int64 => {
-                let arr = array.as_any().downcast_ref::<Int64Array>().unwrap();
-                serde_json::json!(arr.value(index))
-            }
-            DataType::UInt8 => {
-                let arr = array.as_any().downcast_ref::<UInt8Array>().unwrap();
-                serde_json::json!(arr.value(index))
-            }
-            DataType::UInt16 => {
-                let arr = array.as_any().downcast_ref::<UInt16Array>().unwrap();
-                serde_json::json!(arr.value(index))
-            }
-            DataType::UInt32 => {
-                let arr = array.as_any().downcast_ref::<UInt32Array>().unwrap();
-                serde_json::json!(arr.value(index))
-            }
-            DataType::UInt64 => {
-                let arr = array.as_any().downcast_ref::<UInt64Array>().unwrap();
-                serde_json::json!(arr.value(index))
-            }
-            DataType::Float32 => {
-                let arr = array.as_any().downcast_ref::<Float32Array>().unwrap();
-                serde_json::json!(arr.value(index))
-            }
-            DataType::Float64 => {
-                let arr = array.as_any().downcast_ref::<Float64Array>().unwrap();
-                serde_json::json!(arr.value(index))
+    /// Register a Parquet or Arrow IPC file as a table with DataFusion's own readers, whatever
+    /// its extension. Rows are read in file order
+    pub async fn register_columnar(
+        &self,
+        path: &Path,
+        format: ColumnarFormat,
+        table_name: &str,
+    ) -> Result<(), QueryError> {
+        let path = path.to_string_lossy();
+        let ctx = self.ctx.lock().await;
+        ctx.deregister_table(table_name)?;
+        match format {
+            ColumnarFormat::Parquet => {
+                let options = ParquetReadOptions {
+                    file_extension: "",
+                    ..Default::default()
+                };
+                ctx.register_parquet(table_name, &path, options).await?;
             }
-            DataType::Boolean => {
-                let arr = array.as_any().downcast_ref::<BooleanArray>().unwrap();
-                serde_json::json!(arr.value(index))
+            ColumnarFormat::Arrow => {
+                let options = ArrowReadOptions {
+                    file_extension: "",
+                    ..Default::default()
+                };
+                ctx.register_arrow(table_name, &path, options).await?;
             }
-            _ => serde_json::Value::String(format!("{:?}", array.data_type())),
         }
+        self.restore_views(&ctx).await;
+        Ok(())
     }
 
-    /// Clear all registered tables
-    pub async fn clear(&self) {
-        *self.registered_table.lock().await = None;
-        *self.ctx.lock().await = SessionContext::new();
+    pub fn lookups(&self) -> Vec<String> {
+        self.lookups.read().iter().cloned().collect()
     }
-}
 
-impl Default for QueryEngine {
-    fn default() -> Self {
-        Self::new()
+    pub fn views(&self) -> Vec<SavedView> {
+        self.views.read().clone()
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::io::Write;
-    use tempfile::NamedTempFile;
+    /// Replace the saved views; they are created as the tables they read are registered
+    pub fn set_views(&self, views: Vec<SavedView>) {
+        *self.views.write() = views;
+    }
 
-    fn create_test_json_file() -> NamedTempFile {
-        let mut file = NamedTempFile::new().unwrap();
+    /// Save a view and create it now
+    /// While a file is registered the query is checked against it, and a failing view isn't
+    /// saved; otherwise it is created once the tables it reads are registered
+    pub async fn save_view(&self, view: SavedView) -> Result<(), QueryError> {
+        check_table_name(&view.name)?;
+        let ctx = self.ctx.lock().await;
+        if self.registered_table.lock().await.is_some() {
+            self.create_view(&ctx, &view).await?;
+        }
+        let mut views = self.views.write();
+        match views.iter_mut().find(|v| v.name == view.name) {
+            Some(existing) => *existing = view,
+            None => views.push(view),
+        }
+        Ok(())
+    }
+
+    /// Drop a saved view, returning whether it existed
+    pub async fn drop_view(&self, name: &str) -> bool {
+        let removed = {
+            let mut views = self.views.write();
+            let before = views.len();
+            views.retain(|v| v.name != name);
+            views.len() != before
+        };
+        if removed {
+            self.ctx.lock().await.deregister_table(name).ok();
+        }
+        removed
+    }
+
+    /// Re-create the saved views against the tables registered now
+    pub async fn refresh_views(&self) {
+        self.restore_views(&*self.ctx.lock().await).await;
+    }
+
+    async fn create_view(&self, ctx: &SessionContext, view: &SavedView) -> Result<(), QueryError> {
+        // Views may call the custom functions before any query has registered them
+        if !self.udfs_registered.swap(true, Ordering::AcqRel) {
+            Self::register_udfs_in(ctx);
+        }
+        let sql = format!("CREATE OR REPLACE VIEW {} AS {}", view.name, view.sql);
+        ctx.sql(&sql).await?.collect().await?;
+        Ok(())
+    }
+
+    /// A view holds the table it was created from rather than its name, so each view is
+    /// re-created after a table is registered; views whose tables are missing are skipped
+    async fn restore_views(&self, ctx: &SessionContext) {
+        let views = self.views.read().clone();
+        for view in &views {
+            if let Err(e) = self.create_view(ctx, view).await {
+                debug!(view = %view.name, error = %e, "saved view not created");
+            }
+        }
+    }
+
+    /// Execute a SQL query and return the results
+    pub async fn execute_sql(&self, query: &str) -> Result<QueryResult, QueryError> {
+        self.execute_sql_with_params(query, &[]).await
+    }
+
+    /// Execute a SQL query with `$1`, `$2`, ... placeholders bound to `params` in order
+    /// Values are bound as typed literals rather than spliced into the text, so they need
+    /// no quoting or escaping
+    pub async fn execute_sql_with_params(
+        &self,
+        query: &str,
+        params: &[QueryParam],
+    ) -> Result<QueryResult, QueryError> {
+        let (_, batches, _, stats) = self.collect_query(query, params, None, None).await?;
+        Ok(Self::query_result(&batches, None, stats))
+    }
+
+    /// Execute a SQL query with parameters over a random sample of a table's rows, for a
+    /// quick approximate answer on a huge file before the exact one
+    /// Views keep reading the whole table
+    pub async fn execute_sql_sampled(
+        &self,
+        query: &str,
+        params: &[QueryParam],
+        sample: &SampleOptions,
+    ) -> Result<QueryResult, QueryError> {
+        let (_, batches, sample, stats) = self
+            .collect_query(query, params, Some(sample), None)
+            .await?;
+        Ok(Self::query_result(&batches, sample, stats))
+    }
+
+    /// Execute a query the user typed, with parameters and optionally over a sample,
+    /// reporting progress to `on_progress` while it runs once it has taken the progress delay
+    /// Only these results are kept under a result id to sort, filter and pin; the app's own
+    /// queries run through `execute_sql` and keep nothing
+    pub async fn run_query(
+        &self,
+        query: &str,
+        params: &[QueryParam],
+        sample: Option<&SampleOptions>,
+        on_progress: Option<&QueryProgressFn<'_>>,
+    ) -> Result<QueryResult, QueryError> {
+        let (schema, batches, sample, stats) = self
+            .collect_query(query, params, sample, on_progress)
+            .await?;
+        self.finish_result(schema, batches, sample, stats)
+    }
+
+    /// Plan and run a query, returning its batches and how the run went
+    async fn collect_query(
+        &self,
+        query: &str,
+        params: &[QueryParam],
+        sample: Option<&SampleOptions>,
+        on_progress: Option<&QueryProgressFn<'_>>,
+    ) -> Result<(SchemaRef, Vec<RecordBatch>, Option<SampleInfo>, QueryStats), QueryError> {
+        let mut span = profile::span("query", "execute_sql").arg("sql", query);
+        self.ensure_udfs().await?;
+        let values = params
+            .iter()
+            .map(QueryParam::to_scalar)
+            .collect::<Result<Vec<_>, _>>()?;
+        let ctx = self.ctx.lock().await;
+        let (_swap, sample) = match sample {
+            Some(sample) => {
+                let (swap, info) = self.swap_in_sample(&ctx, sample).await?;
+                (Some(swap), Some(info))
+            }
+            None => (None, None),
+        };
+        let started = Instant::now();
+        self.memory.reset_peak();
+        let (plan, task_ctx) = {
+            let _plan = profile::span("query", "plan");
+            let df = ctx.sql(query).await?;
+            let df = if values.is_empty() {
+                df
+            } else {
+                df.with_param_values(values)?
+            };
+            let task_ctx = Arc::new(df.task_ctx());
+            (df.create_physical_plan().await?, task_ctx)
+        };
+        let batches = {
+            let _collect = profile::span("query", "collect");
+            match on_progress {
+                Some(on_progress) => {
+                    let delay = *self.progress_delay.read();
+                    collect_with_progress(&plan, task_ctx, delay, on_progress).await?
+                }
+                None => collect(plan.clone(), task_ctx).await?,
+            }
+        };
+        let stats = QueryStats {
+            elapsed_ms: started.elapsed().as_millis() as u64,
+            peak_memory_bytes: self.memory.peak(),
+            rows_scanned: rows_scanned(plan.as_ref()),
+            rows_returned: batches.iter().map(RecordBatch::num_rows).sum(),
+            scan_pruned: scan_pruned(plan.as_ref()),
+        };
+        span.record("rows", stats.rows_returned);
+        Ok((plan.schema(), batches, sample, stats))
+    }
+
+    /// Sort a kept result by a column without rerunning its query; the sorted rows are kept
+    /// as a result of their own
+    pub async fn sort_result(
+        &self,
+        id: u64,
+        column: &str,
+        direction: SortDirection,
+    ) -> Result<QueryResult, QueryError> {
+        let result = self.cached_result(id)?;
+        if result.schema.index_of(column).is_err() {
+            return Err(QueryError::InvalidQuery(format!(
+                "No column \"{}\" in the result",
+                column
+            )));
+        }
+        let sql = format!(
+            "SELECT * FROM {} ORDER BY {} {}",
+            KEPT_RESULT_TABLE,
+            quote_identifier(column),
+            direction.sql()
+        );
+        let (schema, batches, stats) = self.query_kept(&result, &sql).await?;
+        self.finish_result(schema, batches, None, stats)
+    }
+
+    /// Keep the rows of a kept result that a SQL condition on its columns holds for, such as
+    /// `status >= 500`, without rerunning its query; they're kept as a result of their own
+    pub async fn filter_result(&self, id: u64, condition: &str) -> Result<QueryResult, QueryError> {
+        let result = self.cached_result(id)?;
+        let sql = format!("SELECT * FROM {} WHERE ({})", KEPT_RESULT_TABLE, condition);
+        let (schema, batches, stats) = self.query_kept(&result, &sql).await?;
+        self.finish_result(schema, batches, None, stats)
+    }
+
+    /// The full value of one cell of a kept result, for a detail pane, with JSON values
+    /// pretty-printed
+    pub fn result_cell(
+        &self,
+        id: u64,
+        row: usize,
+        column: usize,
+    ) -> Result<ResultCell, QueryError> {
+        let result = self.cached_result(id)?;
+        let no_cell = || QueryError::NoSuchCell { row, column };
+        let name = result
+            .schema
+            .fields()
+            .get(column)
+            .ok_or_else(no_cell)?
+            .name();
+        let mut index = row;
+        for batch in &result.batches {
+            if index < batch.num_rows() {
+                let value = Self::extract_value(batch.column(column), index)?;
+                return Ok(ResultCell::new(name, value));
+            }
+            index -= batch.num_rows();
+        }
+        Err(no_cell())
+    }
+
+    /// The batches of a result while they're kept
+    pub fn cached_result(&self, id: u64) -> Result<Arc<CachedResult>, QueryError> {
+        self.results
+            .read()
+            .get(id)
+            .ok_or(QueryError::UnknownResult(id))
+    }
+
+    /// Reshape a kept result into a crosstab of `agg` over the rows sharing each pair of
+    /// `row_key` and `col_key` values, such as a level by hour matrix of counts
+    pub async fn pivot_result(
+        &self,
+        id: u64,
+        row_key: &str,
+        col_key: &str,
+        agg: &Metric,
+    ) -> Result<Crosstab, QueryError> {
+        let result = self.cached_result(id)?;
+        let columns: Vec<Column> = result
+            .schema
+            .fields()
+            .iter()
+            .map(|field| Column {
+                name: field.name().clone(),
+                column_type: column_type(field.data_type()),
+            })
+            .collect();
+        let invalid = |e: &dyn std::fmt::Display| QueryError::InvalidQuery(e.to_string());
+        let row = find_column(&columns, row_key).map_err(|e| invalid(&e))?;
+        let col = find_column(&columns, col_key).map_err(|e| invalid(&e))?;
+        let value = agg.sql(&columns).map_err(|e| invalid(&e))?;
+        let sql = format!(
+            "SELECT {}, {}, {} FROM {} GROUP BY 1, 2 ORDER BY 1, 2",
+            quote_identifier(&row.name),
+            quote_identifier(&col.name),
+            value,
+            KEPT_RESULT_TABLE
+        );
+        let (_, batches, _) = self.query_kept(&result, &sql).await?;
+        let rows = if batches.is_empty() {
+            Vec::new()
+        } else {
+            Self::result_rows(&batches).1
+        };
+        Ok(crosstab(&row.name, &col.name, agg, &rows))
+    }
+
+    /// Run a query over a kept result alone, in a session of its own so it can't reach the
+    /// file's tables
+    async fn query_kept(
+        &self,
+        result: &CachedResult,
+        sql: &str,
+    ) -> Result<(SchemaRef, Vec<RecordBatch>, QueryStats), QueryError> {
+        let _span = profile::span("query", "query_kept").arg("sql", sql);
+        let started = Instant::now();
+        let ctx = SessionContext::new();
+        Self::register_udfs_in(&ctx);
+        let table = MemTable::try_new(result.schema.clone(), vec![result.batches.clone()])?;
+        ctx.register_table(KEPT_RESULT_TABLE, Arc::new(table))?;
+        let df = ctx.sql(sql).await?;
+        let task_ctx = Arc::new(df.task_ctx());
+        let plan = df.create_physical_plan().await?;
+        let batches = collect(plan.clone(), task_ctx).await?;
+        let stats = QueryStats {
+            elapsed_ms: started.elapsed().as_millis() as u64,
+            peak_memory_bytes: 0,
+            rows_scanned: Some(result.rows()),
+            rows_returned: batches.iter().map(RecordBatch::num_rows).sum(),
+            scan_pruned: false,
+        };
+        Ok((plan.schema(), batches, stats))
+    }
+
+    /// Keep batches from elsewhere, such as a pinned result, as a result to browse, sort and
+    /// filter like a query's
+    pub fn keep_result(&self, result: CachedResult) -> Result<QueryResult, QueryError> {
+        let stats = QueryStats {
+            elapsed_ms: 0,
+            peak_memory_bytes: 0,
+            rows_scanned: None,
+            rows_returned: result.rows(),
+            scan_pruned: false,
+        };
+        self.finish_result(result.schema, result.batches, None, stats)
+    }
+
+    /// Keep a query's batches under a new result id and convert them to JSON rows
+    fn finish_result(
+        &self,
+        schema: SchemaRef,
+        batches: Vec<RecordBatch>,
+        sample: Option<SampleInfo>,
+        stats: QueryStats,
+    ) -> Result<QueryResult, QueryError> {
+        let mut result = Self::query_result(&batches, sample, stats);
+        let kept = CachedResult { schema, batches }.compacted()?;
+        result.result_id = Some(self.results.write().insert(kept));
+        Ok(result)
+    }
+
+    /// A query's batches as JSON rows, not kept
+    fn query_result(
+        batches: &[RecordBatch],
+        sample: Option<SampleInfo>,
+        stats: QueryStats,
+    ) -> QueryResult {
+        let (columns, rows, errors) = if batches.is_empty() {
+            (Vec::new(), Vec::new(), Vec::new())
+        } else {
+            Self::result_rows(batches)
+        };
+        QueryResult {
+            columns,
+            row_count: rows.len(),
+            rows,
+            partial: false,
+            sample,
+            stats: Some(stats),
+            errors,
+            result_id: None,
+        }
+    }
+
+    /// Column names and JSON rows of non-empty results; a value that can't be read is null,
+    /// with the reason kept for its row
+    pub(crate) fn result_rows(
+        batches: &[RecordBatch],
+    ) -> (Vec<String>, Vec<Vec<serde_json::Value>>, Vec<RowError>) {
+        let schema = batches[0].schema();
+        let columns: Vec<String> = schema.fields().iter().map(|f| f.name().clone()).collect();
+
+        let mut rows: Vec<Vec<serde_json::Value>> = Vec::new();
+        let mut errors = Vec::new();
+
+        for batch in batches {
+            for row_idx in 0..batch.num_rows() {
+                let mut row: Vec<serde_json::Value> = Vec::new();
+                for col_idx in 0..batch.num_columns() {
+                    let value = match Self::extract_value(batch.column(col_idx), row_idx) {
+                        Ok(value) => value,
+                        Err(e) => {
+                            errors.push(RowError {
+                                row: rows.len(),
+                                column: columns.get(col_idx).cloned().unwrap_or_default(),
+                                message: e.to_string(),
+                            });
+                            serde_json::Value::Null
+                        }
+                    };
+                    row.push(value);
+                }
+                rows.push(row);
+            }
+        }
+        (columns, rows, errors)
+    }
+
+    /// Replace a table with a random sample of its rows until the returned swap is dropped
+    async fn swap_in_sample<'a>(
+        &self,
+        ctx: &'a SessionContext,
+        sample: &SampleOptions,
+    ) -> Result<(SampleSwap<'a>, SampleInfo), QueryError> {
+        if !(sample.percent > 0.0 && sample.percent <= 100.0) {
+            return Err(QueryError::InvalidQuery(
+                "Sample percent must be above 0 and at most 100".to_string(),
+            ));
+        }
+        let table = match &sample.table {
+            Some(table) => table.clone(),
+            None => self
+                .registered_table
+                .lock()
+                .await
+                .clone()
+                .ok_or(QueryError::NoFile)?,
+        };
+        let _span = profile::span("query", "sample").arg("table", table.as_str());
+        let provider = ctx.table_provider(table.as_str()).await?;
+        let Some(zoned) = provider.as_any().downcast_ref::<ZonedTable>() else {
+            return Err(QueryError::InvalidQuery(format!(
+                "Table {} can't be sampled",
+                table
+            )));
+        };
+        let table_sample = zoned.sample(|line_number| sample.keeps(line_number))?;
+        let total_rows = zoned.num_rows();
+        let sampled_rows = table_sample.num_rows();
+        let scale = if sampled_rows == 0 {
+            100.0 / sample.percent
+        } else {
+            total_rows as f64 / sampled_rows as f64
+        };
+
+        ctx.deregister_table(table.as_str())?;
+        let swap = SampleSwap {
+            ctx,
+            table: table.clone(),
+            original: Some(provider),
+        };
+        ctx.register_table(table.as_str(), Arc::new(table_sample))?;
+        debug!(table = %table, total_rows, sampled_rows, "sampled table");
+        let info = SampleInfo {
+            table,
+            percent: sample.percent,
+            sampled_rows,
+            total_rows,
+            scale,
+        };
+        Ok((swap, info))
+    }
+
+    /// Extract a value from an Arrow array at a specific index
+    /// Fails rather than panics when the array isn't the kind its data type promises
+    fn extract_value(array: &ArrayRef, index: usize) -> Result<serde_json::Value, QueryError> {
+        use datafusion::arrow::array::*;
+        use datafusion::arrow::datatypes::DataType;
+        use datafusion::arrow::util::display::array_value_to_string;
+
+        if index >= array.len() {
+            return Err(QueryError::UnexpectedValue(format!(
+                "row {index} past the end of a {}-row column",
+                array.len()
+            )));
+        }
+        if array.is_null(index) {
+            return Ok(serde_json::Value::Null);
+        }
+
+        let value = match array.data_type() {
+            // The value a key points at, by decoding just this row
+            DataType::Dictionary(_, value_type) => {
+                return Self::extract_value(&cast(&array.slice(index, 1), value_type)?, 0);
+            }
+            DataType::Utf8 => downcast::<StringArray>(array)?.value(index).into(),
+            DataType::LargeUtf8 => downcast::<LargeStringArray>(array)?.value(index).into(),
+            DataType::Int8 => downcast::<Int8Array>(array)?.value(index).into(),
+            DataType::Int16 => downcast::<Int16Array>(array)?.value(index).into(),
+            DataType::Int32 => downcast::<Int32Array>(array)?.value(index).into(),
+            DataType::Int64 => downcast::<Int64Array>(array)?.value(index).into(),
+            DataType::UInt8 => downcast::<UInt8Array>(array)?.value(index).into(),
+            DataType::UInt16 => downcast::<UInt16Array>(array)?.value(index).into(),
+            DataType::UInt32 => downcast::<UInt32Array>(array)?.value(index).into(),
+            DataType::UInt64 => downcast::<UInt64Array>(array)?.value(index).into(),
+            DataType::Float32 => downcast::<Float32Array>(array)?.value(index).into(),
+            DataType::Float64 => downcast::<Float64Array>(array)?.value(index).into(),
+            DataType::Boolean => downcast::<BooleanArray>(array)?.value(index).into(),
+            DataType::Timestamp(TimeUnit::Millisecond, _) => {
+                let arr = downcast::<TimestampMillisecondArray>(array)?;
+                crate::timestamp::format_timestamp(arr.value(index)).into()
+            }
+            // Other units as ISO strings at their own precision, in UTC whatever the zone
+            DataType::Timestamp(unit, _) => {
+                let value = cast(&array.slice(index, 1), &DataType::Int64)?;
+                format_timestamp_in(downcast::<Int64Array>(&value)?.value(0), unit).into()
+            }
+            DataType::Date32 => downcast::<Date32Array>(array)?
+                .value_as_date(index)
+                .map_or(serde_json::Value::Null, |date| date.to_string().into()),
+            DataType::Date64 => downcast::<Date64Array>(array)?
+                .value_as_date(index)
+                .map_or(serde_json::Value::Null, |date| date.to_string().into()),
+            // Decimals stay text so no digits are lost to a float
+            DataType::Decimal128(_, _) => downcast::<Decimal128Array>(array)?
+                .value_as_string(index)
+                .into(),
+            DataType::Decimal256(_, _) => downcast::<Decimal256Array>(array)?
+                .value_as_string(index)
+                .into(),
+            DataType::List(_) => Self::extract_list(&downcast::<ListArray>(array)?.value(index))?,
+            DataType::LargeList(_) => {
+                Self::extract_list(&downcast::<LargeListArray>(array)?.value(index))?
+            }
+            DataType::FixedSizeList(_, _) => {
+                Self::extract_list(&downcast::<FixedSizeListArray>(array)?.value(index))?
+            }
+            DataType::Struct(_) => {
+                let arr = downcast::<StructArray>(array)?;
+                let fields = arr.fields().iter().zip(arr.columns());
+                fields
+                    .map(|(field, column)| {
+                        Ok((field.name().clone(), Self::extract_value(column, index)?))
+                    })
+                    .collect::<Result<serde_json::Map<_, _>, QueryError>>()?
+                    .into()
+            }
+            // Entries as an object, with keys that aren't strings written as JSON text
+            DataType::Map(_, _) => {
+                let entries = downcast::<MapArray>(array)?.value(index);
+                (0..entries.len())
+                    .map(|row| {
+                        let key = match Self::extract_value(entries.column(0), row)? {
+                            serde_json::Value::String(key) => key,
+                            key => key.to_string(),
+                        };
+                        Ok((key, Self::extract_value(entries.column(1), row)?))
+                    })
+                    .collect::<Result<serde_json::Map<_, _>, QueryError>>()?
+                    .into()
+            }
+            DataType::Binary => base64(downcast::<BinaryArray>(array)?.value(index)).into(),
+            DataType::LargeBinary => {
+                base64(downcast::<LargeBinaryArray>(array)?.value(index)).into()
+            }
+            DataType::FixedSizeBinary(_) => {
+                base64(downcast::<FixedSizeBinaryArray>(array)?.value(index)).into()
+            }
+            // Times, durations, intervals and string views as Arrow displays them
+            _ => array_value_to_string(array, index)?.into(),
+        };
+        Ok(value)
+    }
+
+    /// The elements of a list value as a JSON array
+    fn extract_list(values: &ArrayRef) -> Result<serde_json::Value, QueryError> {
+        (0..values.len())
+            .map(|index| Self::extract_value(values, index))
+            .collect::<Result<Vec<_>, _>>()
+            .map(serde_json::Value::Array)
+    }
+
+    /// Run a query and keep its results as Arrow batches, with the columns typed as parsed
+    /// columns, for writers that need typed values rather than JSON
+    pub async fn query_batches(
+        &self,
+        query: &str,
+    ) -> Result<(Vec<Column>, Vec<RecordBatch>), QueryError> {
+        let _span = profile::span("query", "query_batches").arg("sql", query);
+        self.ensure_udfs().await?;
+        let ctx = self.ctx.lock().await;
+        let df = ctx.sql(query).await?;
+        Ok((result_columns(&df), df.collect().await?))
+    }
+
+    /// Run a query and hand back its batches as they're produced rather than all at once,
+    /// so writers can export more rows than fit in memory
+    /// The engine is free for other queries as soon as this returns
+    pub async fn query_stream(
+        &self,
+        query: &str,
+    ) -> Result<(Vec<Column>, SendableRecordBatchStream), QueryError> {
+        let _span = profile::span("query", "query_stream").arg("sql", query);
+        self.ensure_udfs().await?;
+        let ctx = self.ctx.lock().await;
+        let df = ctx.sql(query).await?;
+        Ok((result_columns(&df), df.execute_stream().await?))
+    }
+
+    /// Stream a query's batches in the order its tables hold their rows, planning it to run
+    /// as one partition so file scans aren't split and merged out of order
+    pub async fn query_stream_in_order(
+        &self,
+        query: &str,
+    ) -> Result<(Vec<Column>, SendableRecordBatchStream), QueryError> {
+        let _span = profile::span("query", "query_stream_in_order").arg("sql", query);
+        self.ensure_udfs().await?;
+        let ctx = self.ctx.lock().await;
+        let mut state = ctx.state();
+        state.config_mut().options_mut().execution.target_partitions = 1;
+        let plan = state.create_logical_plan(query).await?;
+        let df = DataFrame::new(state, plan);
+        Ok((result_columns(&df), df.execute_stream().await?))
+    }
+
+    /// Columns of a registered table or view, typed as parsed columns; types a parse never
+    /// produces are treated as text
+    pub async fn table_columns(&self, table_name: &str) -> Result<Vec<Column>, QueryError> {
+        let ctx = self.ctx.lock().await;
+        let schema = ctx.table_provider(table_name).await?.schema();
+        Ok(schema
+            .fields()
+            .iter()
+            .map(|field| Column {
+                name: field.name().clone(),
+                column_type: column_type(field.data_type()),
+            })
+            .collect())
+    }
+
+    /// Tables a query reads, following views through to the tables behind them
+    pub async fn referenced_tables(&self, query: &str) -> Result<HashSet<String>, QueryError> {
+        let ctx = self.ctx.lock().await;
+        let plan = ctx.state().create_logical_plan(query).await?;
+        let mut tables = HashSet::new();
+        collect_table_names(&plan, &mut tables)?;
+        Ok(tables)
+    }
+
+    /// Drop every registered table except lookup tables, keeping the session's configuration
+    /// and SQL functions. Saved views come back as the tables they read are registered again
+    pub async fn clear(&self) {
+        *self.registered_table.lock().await = None;
+        let ctx = self.ctx.lock().await;
+        let lookups = self.lookups.read().clone();
+        for table in table_names(&ctx).iter().filter(|t| !lookups.contains(*t)) {
+            ctx.deregister_table(table.as_str()).ok();
+        }
+    }
+}
+
+/// Names of the tables and views registered in a session
+fn table_names(ctx: &SessionContext) -> Vec<String> {
+    let config = ctx.copied_config();
+    let defaults = &config.options().catalog;
+    ctx.catalog(&defaults.default_catalog)
+        .and_then(|catalog| catalog.schema(&defaults.default_schema))
+        .map(|schema| schema.table_names())
+        .unwrap_or_default()
+}
+
+/// Lines sampled from the prefix and from each interior block when detecting a format
+const DETECT_SAMPLE_LINES: usize = 10;
+/// Bytes read from the start of a file when detecting its format
+const DETECT_PREFIX_BYTES: u64 = 64 * 1024;
+/// Size and number of interior blocks sampled in addition to the prefix
+const DETECT_BLOCK_BYTES: u64 = 16 * 1024;
+const DETECT_INTERIOR_BLOCKS: u64 = 3;
+
+/// Collect a query's results, reporting how far its table scans have got every
+/// `PROGRESS_INTERVAL` once it has run for `delay`
+/// Dropping the future stops the query
+async fn collect_with_progress(
+    plan: &Arc<dyn ExecutionPlan>,
+    task_ctx: Arc<TaskContext>,
+    delay: Duration,
+    on_progress: &QueryProgressFn<'_>,
+) -> Result<Vec<RecordBatch>, QueryError> {
+    let started = Instant::now();
+    let mut ticker = tokio::time::interval(PROGRESS_INTERVAL);
+    let collecting = collect(plan.clone(), task_ctx);
+    tokio::pin!(collecting);
+    loop {
+        tokio::select! {
+            batches = &mut collecting => {
+                // A query that ran long enough to report ends on a complete report
+                if started.elapsed() >= delay {
+                    on_progress(scan_progress(plan.as_ref()));
+                }
+                return Ok(batches?);
+            }
+            _ = ticker.tick() => {
+                if started.elapsed() >= delay {
+                    on_progress(scan_progress(plan.as_ref()));
+                }
+            }
+        }
+    }
+}
+
+/// Partitions of the plan's table scans, how many have been read to the end and the rows
+/// out of them so far, from the metrics of the scans or, for scans that keep none, of the
+/// operators directly above them
+fn scan_progress(plan: &dyn ExecutionPlan) -> QueryProgress {
+    let metered = |plan: &dyn ExecutionPlan| {
+        let metrics = plan.metrics()?;
+        let partitions_done = metrics
+            .iter()
+            .filter(|metric| {
+                matches!(metric.value(), MetricValue::EndTimestamp(end) if end.value().is_some())
+            })
+            .count();
+        Some(QueryProgress {
+            partitions_done,
+            partitions: plan.properties().output_partitioning().partition_count(),
+            rows_processed: metrics.output_rows().unwrap_or(0),
+        })
+    };
+    let unmetered = |plan: &dyn ExecutionPlan| QueryProgress {
+        partitions: plan.properties().output_partitioning().partition_count(),
+        ..QueryProgress::default()
+    };
+    if plan.children().is_empty() {
+        return metered(plan).unwrap_or_else(|| unmetered(plan));
+    }
+    plan.children()
+        .into_iter()
+        .map(|child| {
+            if child.children().is_empty() {
+                metered(child.as_ref())
+                    .or_else(|| metered(plan))
+                    .unwrap_or_else(|| unmetered(child.as_ref()))
+            } else {
+                scan_progress(child.as_ref())
+            }
+        })
+        .fold(QueryProgress::default(), |total, scan| QueryProgress {
+            partitions_done: total.partitions_done + scan.partitions_done,
+            partitions: total.partitions + scan.partitions,
+            rows_processed: total.rows_processed + scan.rows_processed,
+        })
+}
+
+/// Rows the plan's table scans read: their output rows when they keep metrics, else the
+/// exact row count of their data, as in-memory scans know it
+fn rows_scanned(plan: &dyn ExecutionPlan) -> Option<usize> {
+    if plan.children().is_empty() {
+        return plan.metrics().and_then(|m| m.output_rows()).or_else(|| {
+            match plan.statistics().ok()?.num_rows {
+                Precision::Exact(rows) => Some(rows),
+                _ => None,
+            }
+        });
+    }
+    plan.children()
+        .into_iter()
+        .map(|child| rows_scanned(child.as_ref()))
+        .sum()
+}
+
+/// Whether any table scan of the plan counted rows, pages, row groups or files as pruned
+fn scan_pruned(plan: &dyn ExecutionPlan) -> bool {
+    if !plan.children().is_empty() {
+        return plan
+            .children()
+            .into_iter()
+            .any(|child| scan_pruned(child.as_ref()));
+    }
+    plan.metrics().is_some_and(|metrics| {
+        metrics.iter().any(|metric| match metric.value() {
+            MetricValue::Count { name, count } => name.contains("pruned") && count.value() > 0,
+            _ => false,
+        })
+    })
+}
+
+/// A query's output columns, typed as parsed columns
+fn result_columns(df: &DataFrame) -> Vec<Column> {
+    df.schema()
+        .fields()
+        .iter()
+        .map(|field| Column {
+            name: field.name().clone(),
+            column_type: column_type(field.data_type()),
+        })
+        .collect()
+}
+
+/// An array as the concrete type its data type calls for
+fn downcast<T: Array + 'static>(array: &ArrayRef) -> Result<&T, QueryError> {
+    array.as_any().downcast_ref::<T>().ok_or_else(|| {
+        QueryError::UnexpectedValue(format!(
+            "{} column has an array of another type",
+            array.data_type()
+        ))
+    })
+}
+
+/// ISO form of a timestamp in `unit` since the epoch, with as many fractional digits as the
+/// unit carries
+fn format_timestamp_in(value: i64, unit: &TimeUnit) -> String {
+    let (per_second, format) = match unit {
+        TimeUnit::Second => (1, SecondsFormat::Secs),
+        TimeUnit::Millisecond => (1_000, SecondsFormat::Millis),
+        TimeUnit::Microsecond => (1_000_000, SecondsFormat::Micros),
+        TimeUnit::Nanosecond => (1_000_000_000, SecondsFormat::Nanos),
+    };
+    let nanos = value.rem_euclid(per_second) * (1_000_000_000 / per_second);
+    chrono::DateTime::from_timestamp(value.div_euclid(per_second), nanos as u32)
+        .map(|time| time.to_rfc3339_opts(format, true))
+        .unwrap_or_else(|| value.to_string())
+}
+
+/// Standard padded base64 of binary cell values
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &b)| {
+            group | (u32::from(b) << (16 - 8 * i))
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[((group >> (18 - 6 * i)) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Mixes a number into a well-spread 64-bit value (SplitMix64), to pick rows, lines and
+/// blocks reproducibly from a seed
+pub fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Byte ranges sampled for format detection: the prefix, then interior blocks at
+/// pseudo-random offsets seeded by the file length, so a file always gets the same sample
+fn sample_ranges(file_len: u64) -> impl Iterator<Item = std::ops::Range<u64>> {
+    let prefix = 0..file_len.min(DETECT_PREFIX_BYTES);
+    let interior_span = file_len.saturating_sub(DETECT_PREFIX_BYTES + DETECT_BLOCK_BYTES);
+    let interior = (0..DETECT_INTERIOR_BLOCKS)
+        .filter(move |_| interior_span > 0)
+        .map(move |block| {
+            let start =
+                DETECT_PREFIX_BYTES + splitmix64(file_len.wrapping_add(block)) % interior_span;
+            start..start + DETECT_BLOCK_BYTES
+        });
+    std::iter::once(prefix).chain(interior)
+}
+
+/// Non-empty lines of a sampled block, skipping a partial first or last line
+fn complete_lines(block: &str, skip_first: bool, skip_last: bool) -> impl Iterator<Item = &str> {
+    let mut lines: Vec<&str> = block.split('\n').collect();
+    if skip_last {
+        lines.pop();
+    }
+    let skip = usize::from(skip_first && !lines.is_empty());
+    lines
+        .into_iter()
+        .skip(skip)
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .filter(|line| !line.trim().is_empty())
+}
+
+/// Path of a directory ending in a separator, which DataFusion needs to read it as a
+/// directory of files rather than a single file
+fn directory_url(dir: &Path) -> String {
+    let mut url = dir.to_string_lossy().into_owned();
+    if !url.ends_with(std::path::MAIN_SEPARATOR) {
+        url.push(std::path::MAIN_SEPARATOR);
+    }
+    url
+}
+
+/// `line_format` column values for a batch of lines
+fn line_formats(lines: &[String]) -> ArrayRef {
+    Arc::new(StringArray::from_iter_values(
+        lines.iter().map(|line| classify_line(line).as_str()),
+    ))
+}
+
+/// One batch of the line table
+fn line_batch(
+    schema: &SchemaRef,
+    line_numbers: Vec<i64>,
+    lines: Vec<String>,
+    offsets: Vec<i64>,
+    lengths: Vec<i64>,
+) -> Result<RecordBatch, ArrowError> {
+    let formats = line_formats(&lines);
+    RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(Int64Array::from(line_numbers)) as ArrayRef,
+            Arc::new(StringArray::from(lines)) as ArrayRef,
+            formats,
+            Arc::new(Int64Array::from(offsets)) as ArrayRef,
+            Arc::new(Int64Array::from(lengths)) as ArrayRef,
+        ],
+    )
+}
+
+/// A string argument of a SQL function: one value for every row, or a value per row
+enum StrArg<'a> {
+    Scalar(Option<&'a str>),
+    Array(&'a StringArray),
+}
+
+impl<'a> StrArg<'a> {
+    fn new(arg: &'a ColumnarValue) -> Result<Self, DataFusionError> {
+        match arg {
+            ColumnarValue::Scalar(
+                ScalarValue::Utf8(value)
+                | ScalarValue::LargeUtf8(value)
+                | ScalarValue::Utf8View(value),
+            ) => Ok(StrArg::Scalar(value.as_deref())),
+            ColumnarValue::Scalar(value) if value.is_null() => Ok(StrArg::Scalar(None)),
+            ColumnarValue::Array(array) => array
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .map(StrArg::Array)
+                .ok_or_else(|| DataFusionError::Internal("Expected string array".into())),
+            ColumnarValue::Scalar(value) => Err(DataFusionError::Internal(format!(
+                "Expected string, got {}",
+                value.data_type()
+            ))),
+        }
+    }
+
+    fn get(&self, row: usize) -> Option<&'a str> {
+        match self {
+            StrArg::Scalar(value) => *value,
+            StrArg::Array(array) => (!array.is_null(row)).then(|| array.value(row)),
+        }
+    }
+}
+
+/// Compiled pattern of a SQL function row, reusing the previous row's while it repeats
+fn row_regex<'a>(
+    last: &mut Option<(&'a str, Arc<Regex>)>,
+    pattern: &'a str,
+) -> Result<Arc<Regex>, DataFusionError> {
+    match last {
+        Some((p, regex)) if *p == pattern => Ok(regex.clone()),
+        _ => {
+            let regex = regex_cache::regex(pattern)
+                .map_err(|e| DataFusionError::Execution(format!("Invalid regex: {}", e)))?;
+            *last = Some((pattern, regex.clone()));
+            Ok(regex)
+        }
+    }
+}
+
+/// Rows a SQL function call covers: the length of its array arguments, or one if all are
+/// scalars
+fn udf_rows(args: &[ColumnarValue]) -> usize {
+    args.iter()
+        .find_map(|arg| match arg {
+            ColumnarValue::Array(array) => Some(array.len()),
+            ColumnarValue::Scalar(_) => None,
+        })
+        .unwrap_or(1)
+}
+
+/// A SQL function's result, as a scalar when every argument was a scalar
+fn udf_result(args: &[ColumnarValue], result: ArrayRef) -> Result<ColumnarValue, DataFusionError> {
+    let all_scalar = args
+        .iter()
+        .all(|arg| matches!(arg, ColumnarValue::Scalar(_)));
+    if all_scalar {
+        let value = ScalarValue::try_from_array(&result, 0)?;
+        return Ok(ColumnarValue::Scalar(value));
+    }
+    Ok(ColumnarValue::Array(result))
+}
+
+/// Arrow type used for a parsed column
+fn arrow_type(column_type: ColumnType) -> DataType {
+    match column_type {
+        ColumnType::Utf8 => DataType::Utf8,
+        ColumnType::Int64 => DataType::Int64,
+        ColumnType::Float64 => DataType::Float64,
+        ColumnType::Boolean => DataType::Boolean,
+        ColumnType::Timestamp => DataType::Timestamp(TimeUnit::Millisecond, None),
+    }
+}
+
+/// Parsed text columns with at most this many distinct values among the first
+/// `DICTIONARY_SAMPLE_ROWS` records, each seen `DICTIONARY_MIN_REPEATS` times on average,
+/// are dictionary encoded
+const DICTIONARY_MAX_VALUES: usize = 1000;
+const DICTIONARY_MIN_REPEATS: usize = 10;
+const DICTIONARY_SAMPLE_ROWS: usize = 10_000;
+
+/// Type of the parsed text columns repeating few values, such as level, logger or host,
+/// storing each distinct value once to save memory and speed up grouping
+fn dictionary_type() -> DataType {
+    DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8))
+}
+
+/// Whether a text column repeats few values, judged from the first records
+fn low_cardinality(name: &str, records: &[Record]) -> bool {
+    let mut values = HashSet::new();
+    let mut count = 0;
+    for value in records
+        .iter()
+        .take(DICTIONARY_SAMPLE_ROWS)
+        .filter_map(|record| record.get(name)?.to_text())
+    {
+        values.insert(value);
+        if values.len() > DICTIONARY_MAX_VALUES {
+            return false;
+        }
+        count += 1;
+    }
+    count > 0 && values.len() * DICTIONARY_MIN_REPEATS <= count
+}
+
+/// Parsed column type of an Arrow type, the inverse of `arrow_type`
+fn column_type(data_type: &DataType) -> ColumnType {
+    match data_type {
+        DataType::Dictionary(_, value_type) => column_type(value_type),
+        DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Int64 => ColumnType::Int64,
+        DataType::UInt8 | DataType::UInt16 | DataType::UInt32 | DataType::UInt64 => {
+            ColumnType::Int64
+        }
+        DataType::Float32 | DataType::Float64 => ColumnType::Float64,
+        DataType::Boolean => ColumnType::Boolean,
+        DataType::Timestamp(_, _) => ColumnType::Timestamp,
+        _ => ColumnType::Utf8,
+    }
+}
+
+impl Default for QueryEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn create_test_json_file() -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
         writeln!(file, r#"{{"level":"info","message":"test1"}}"#).unwrap();
         writeln!(file, r#"{{"level":"error","message":"test2"}}"#).unwrap();
         writeln!(file, r#"{{"level":"info","message":"test3"}}"#).unwrap();
@@ -432,4 +2159,629 @@ mod tests {
         let format = QueryEngine::detect_format(file.path()).unwrap();
         assert_eq!(format, FileFormat::Csv);
     }
+
+    #[test]
+    fn test_detect_format_invalid_utf8() {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(b"{\"message\":\"caf\xe9\"}\n{\"message\":\"ok\"}\n")
+            .unwrap();
+        file.flush().unwrap();
+
+        let format = QueryEngine::detect_format(file.path()).unwrap();
+        assert_eq!(format, FileFormat::Ndjson);
+    }
+
+    #[test]
+    fn test_detect_format_samples_large_files() {
+        let mut data = b"starting up\n".repeat(8);
+        while data.len() < 4 * 1024 * 1024 {
+            data.extend_from_slice(br#"{"level":"info","message":"request served"}"#);
+            data.push(b'\n');
+        }
+        assert_eq!(QueryEngine::detect_format_bytes(&data), FileFormat::Ndjson);
+
+        let ranges: Vec<_> = sample_ranges(data.len() as u64).collect();
+        assert_eq!(ranges.len(), 4);
+        assert_eq!(ranges[0], 0..DETECT_PREFIX_BYTES);
+        assert!(ranges.iter().all(|r| r.end <= data.len() as u64));
+        assert_eq!(sample_ranges(100).collect::<Vec<_>>(), vec![0..100]);
+    }
+
+    #[test]
+    fn test_detect_format_mixed() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "Server starting").unwrap();
+        writeln!(file, r#"{{"level":"info","message":"ready"}}"#).unwrap();
+        writeln!(file, "java.lang.RuntimeException: boom").unwrap();
+        writeln!(file, "\tat com.example.Main.run(Main.java:42)").unwrap();
+        file.flush().unwrap();
+
+        let format = QueryEngine::detect_format(file.path()).unwrap();
+        assert_eq!(format, FileFormat::Mixed);
+    }
+
+    #[tokio::test]
+    async fn test_line_format_column() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "Server starting").unwrap();
+        writeln!(file, r#"{{"level":"info","message":"ready"}}"#).unwrap();
+        file.flush().unwrap();
+
+        let engine = QueryEngine::new();
+        engine.register_udfs().await.unwrap();
+        engine.register_table(file.path(), "logs").await.unwrap();
+        let result = engine
+            .execute_sql(
+                "SELECT line_number, line_format, json_extract(line, 'level') AS level \
+                 FROM logs ORDER BY line_number",
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            result.rows,
+            vec![
+                vec![
+                    serde_json::json!(1),
+                    serde_json::json!("text"),
+                    serde_json::Value::Null
+                ],
+                vec![
+                    serde_json::json!(2),
+                    serde_json::json!("json"),
+                    serde_json::json!("\"info\"")
+                ],
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sort_and_filter_kept_result() {
+        let file = create_test_json_file();
+        let engine = QueryEngine::new();
+        engine.register_table(file.path(), "logs").await.unwrap();
+        let query = "SELECT line_number, length(line) AS len FROM logs ORDER BY line_number";
+        let result = engine.run_query(query, &[], None, None).await.unwrap();
+        let id = result.result_id.unwrap();
+        // Only the queries the user runs are kept
+        let internal = engine.execute_sql(query).await.unwrap();
+        assert_eq!(internal.rows, result.rows);
+        assert!(internal.result_id.is_none());
+        assert_eq!(engine.cached_result(id).unwrap().rows(), 3);
+
+        let sorted = engine
+            .sort_result(id, "line_number", SortDirection::Desc)
+            .await
+            .unwrap();
+        let lines: Vec<_> = sorted.rows.iter().map(|row| row[0].clone()).collect();
+        assert_eq!(lines, [3, 2, 1].map(|n| serde_json::json!(n)));
+
+        // Filters apply to the sorted rows, which are a result of their own
+        let filtered = engine
+            .filter_result(sorted.result_id.unwrap(), "line_number <> 2")
+            .await
+            .unwrap();
+        let lines: Vec<_> = filtered.rows.iter().map(|row| row[0].clone()).collect();
+        assert_eq!(lines, [3, 1].map(|n| serde_json::json!(n)));
+
+        assert!(matches!(
+            engine.sort_result(id, "missing", SortDirection::Asc).await,
+            Err(QueryError::InvalidQuery(_))
+        ));
+        assert!(matches!(
+            engine.filter_result(id + 100, "true").await,
+            Err(QueryError::UnknownResult(_))
+        ));
+
+        let cell = engine.result_cell(id, 1, 0).unwrap();
+        assert_eq!(
+            (cell.column.as_str(), cell.value),
+            ("line_number", serde_json::json!(2))
+        );
+        assert!(matches!(
+            engine.result_cell(id, 3, 0),
+            Err(QueryError::NoSuchCell { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_query_params() {
+        let file = create_test_json_file();
+        let engine = QueryEngine::new();
+        engine.register_udfs().await.unwrap();
+        engine.register_table(file.path(), "logs").await.unwrap();
+        let query = "SELECT line_number FROM logs \
+                     WHERE json_extract(line, 'level') = $1 AND line_number > $2";
+        let params = [QueryParam::Text("\"info\"".to_string()), QueryParam::Int(1)];
+        let result = engine
+            .execute_sql_with_params(query, &params)
+            .await
+            .unwrap();
+        assert_eq!(result.rows, vec![vec![serde_json::json!(3)]]);
+
+        // Quotes in a value are data, not SQL
+        let params = [
+            QueryParam::Text("' OR '1'='1".to_string()),
+            QueryParam::Int(0),
+        ];
+        let result = engine
+            .execute_sql_with_params(query, &params)
+            .await
+            .unwrap();
+        assert_eq!(result.row_count, 0);
+
+        let param: QueryParam =
+            serde_json::from_str(r#"{"type":"timestamp","value":"2024-01-01T00:00:00Z"}"#).unwrap();
+        assert!(engine
+            .execute_sql_with_params("SELECT $1 AS t", &[param])
+            .await
+            .is_ok());
+        let param = QueryParam::Timestamp("yesterday".to_string());
+        assert!(engine
+            .execute_sql_with_params("SELECT $1 AS t", &[param])
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sampled_query() {
+        let mut file = NamedTempFile::new().unwrap();
+        for i in 0..1000 {
+            writeln!(file, "request {} served", i).unwrap();
+        }
+        file.flush().unwrap();
+        let engine = QueryEngine::new();
+        engine.register_table(file.path(), "logs").await.unwrap();
+
+        let query = "SELECT COUNT(*) AS n FROM logs";
+        let sample = SampleOptions {
+            percent: 10.0,
+            seed: 7,
+            table: None,
+        };
+        let result = engine
+            .execute_sql_sampled(query, &[], &sample)
+            .await
+            .unwrap();
+        let info = result.sample.unwrap();
+        assert_eq!(info.total_rows, 1000);
+        assert!((50..150).contains(&info.sampled_rows));
+        assert_eq!(
+            result.rows,
+            vec![vec![serde_json::json!(info.sampled_rows)]]
+        );
+        assert_eq!(info.scale, 1000.0 / info.sampled_rows as f64);
+
+        // The same seed picks the same rows, and the whole table is back afterwards
+        let again = engine
+            .execute_sql_sampled(query, &[], &sample)
+            .await
+            .unwrap();
+        assert_eq!(again.rows, result.rows);
+        let full = engine.execute_sql(query).await.unwrap();
+        assert_eq!(full.rows, vec![vec![serde_json::json!(1000)]]);
+        assert!(full.sample.is_none());
+
+        let empty = SampleOptions {
+            percent: 0.0,
+            ..sample
+        };
+        assert!(engine
+            .execute_sql_sampled(query, &[], &empty)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_query_with_progress() {
+        let file = create_test_json_file();
+        let engine = QueryEngine::new();
+        engine.register_udfs().await.unwrap();
+        engine.register_table(file.path(), "logs").await.unwrap();
+
+        // Results match a plain run; a query this quick finishes before any report
+        let query = "SELECT json_extract(line, 'level') AS level, COUNT(*) AS n \
+                     FROM logs GROUP BY level ORDER BY level";
+        let reports = parking_lot::Mutex::new(Vec::new());
+        let on_progress = |progress: QueryProgress| reports.lock().push(progress);
+        let result = engine
+            .run_query(query, &[], None, Some(&on_progress))
+            .await
+            .unwrap();
+        let plain = engine.execute_sql(query).await.unwrap();
+        assert_eq!(result.rows, plain.rows);
+        assert!(reports.lock().is_empty());
+
+        // Without a delay even a quick query reports, ending with its scans read through;
+        // progress counts scan partitions, not the single partition the sort puts out
+        engine.set_progress_delay(Duration::ZERO);
+        engine
+            .run_query(query, &[], None, Some(&on_progress))
+            .await
+            .unwrap();
+        let last = *reports.lock().last().expect("a progress report");
+        assert!(last.partitions >= 1);
+        assert_eq!(last.partitions_done, last.partitions);
+        assert_eq!(last.rows_processed, 3);
+
+        let stats = plain.stats.unwrap();
+        assert_eq!(stats.rows_scanned, Some(3));
+        assert_eq!(stats.rows_returned, 2);
+        assert!(!stats.scan_pruned);
+    }
+
+    #[tokio::test]
+    async fn test_byte_offset_columns() {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(b"first\r\n\nlast").unwrap();
+        file.flush().unwrap();
+
+        let engine = QueryEngine::new();
+        engine.register_table(file.path(), "logs").await.unwrap();
+        let result = engine
+            .execute_sql("SELECT byte_offset, byte_len FROM logs ORDER BY line_number")
+            .await
+            .unwrap();
+        let expected: Vec<Vec<serde_json::Value>> = [(0, 5), (7, 0), (8, 4)]
+            .iter()
+            .map(|&(offset, len)| vec![offset.into(), len.into()])
+            .collect();
+        assert_eq!(result.rows, expected);
+    }
+
+    #[tokio::test]
+    async fn test_udf_arguments() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, r#"{{"level":"error","code":7}}"#).unwrap();
+        writeln!(file, r#"{{"level":"info"}}"#).unwrap();
+        writeln!(file, "plain text").unwrap();
+        file.flush().unwrap();
+
+        let engine = QueryEngine::new();
+        engine.register_udfs().await.unwrap();
+        engine.register_table(file.path(), "logs").await.unwrap();
+
+        // Scalar arguments give one value, not the text of a quoted literal
+        let result = engine
+            .execute_sql(
+                "SELECT regex_match('ab', '^ab$') AS m, \
+                 json_extract('{\"k\":1}', 'k') AS j, \
+                 regex_match(NULL, 'a') AS n",
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            result.rows,
+            vec![vec![
+                serde_json::json!(true),
+                serde_json::json!("1"),
+                serde_json::Value::Null
+            ]]
+        );
+
+        // A scalar pattern and key apply to every row of a multi-row batch
+        let result = engine
+            .execute_sql(
+                "SELECT regex_match(line, 'error'), json_extract(line, 'code') \
+                 FROM logs ORDER BY line_number",
+            )
+            .await
+            .unwrap();
+        let column = |i: usize| {
+            serde_json::Value::Array(result.rows.iter().map(|row| row[i].clone()).collect())
+        };
+        assert_eq!(column(0), serde_json::json!([true, false, false]));
+        assert_eq!(column(1), serde_json::json!(["7", null, null]));
+
+        // Patterns and keys may come from another column, row by row
+        let result = engine
+            .execute_sql(
+                "SELECT regex_match(t, p), json_extract(j, k) FROM (VALUES \
+                 ('abc', '^a', '{\"x\":1}', 'x'), \
+                 ('abc', '^b', '{\"y\":2}', 'y'), \
+                 ('abc', NULL, '{\"x\":3}', 'y')) AS v(t, p, j, k)",
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            result.rows,
+            vec![
+                vec![serde_json::json!(true), serde_json::json!("1")],
+                vec![serde_json::json!(false), serde_json::json!("2")],
+                vec![serde_json::Value::Null, serde_json::Value::Null],
+            ]
+        );
+
+        assert!(engine
+            .execute_sql("SELECT regex_match(line, '(') FROM logs")
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_clear_keeps_functions_and_engines_isolated() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "ERROR disk full").unwrap();
+        file.flush().unwrap();
+
+        let main = QueryEngine::new();
+        let compare = QueryEngine::new();
+        for engine in [&main, &compare] {
+            engine.ensure_udfs().await.unwrap();
+            engine.register_table(file.path(), "logs").await.unwrap();
+        }
+
+        // Views and subqueries count the tables they read
+        let tables = engine
+            .referenced_tables("SELECT * FROM hosts WHERE host IN (SELECT line FROM errors)")
+            .await
+            .unwrap();
+        assert_eq!(
+            tables,
+            HashSet::from(["hosts", "errors", "logs"].map(String::from))
+        );
+
+        main.clear().await;
+        assert!(main.execute_sql("SELECT * FROM logs").await.is_err());
+        let result = main
+            .execute_sql("SELECT regex_match('ERROR x', 'ERR') AS m")
+            .await
+            .unwrap();
+        assert_eq!(result.rows, vec![vec![serde_json::json!(true)]]);
+
+        let result = compare
+            .execute_sql("SELECT COUNT(*) FROM logs WHERE regex_match(line, '^ERROR')")
+            .await
+            .unwrap();
+        assert_eq!(result.rows, vec![vec![serde_json::json!(1)]]);
+    }
+
+    #[tokio::test]
+    async fn test_lookups_and_views_survive_clear() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "ERROR disk full on host-a").unwrap();
+        writeln!(file, "INFO started on host-b").unwrap();
+        file.flush().unwrap();
+        let mut hosts = NamedTempFile::with_suffix(".csv").unwrap();
+        writeln!(hosts, "host,team").unwrap();
+        writeln!(hosts, "host-a,storage").unwrap();
+        hosts.flush().unwrap();
+
+        let engine = QueryEngine::new();
+        engine.set_views(vec![SavedView {
+            name: "errors".to_string(),
+            sql: "SELECT line FROM logs WHERE regex_match(line, '^ERROR')".to_string(),
+        }]);
+        assert_eq!(
+            engine.register_lookup("hosts", hosts.path()).await.unwrap(),
+            1
+        );
+        assert!(engine
+            .register_lookup("bad name", hosts.path())
+            .await
+            .is_err());
+        // The app's own tables can't be shadowed
+        assert!(matches!(
+            engine.register_lookup("logs", hosts.path()).await,
+            Err(QueryError::ReservedName(_))
+        ));
+        assert!(engine
+            .create_temporary_view("Parsed", "SELECT 1")
+            .await
+            .is_err());
+        engine.register_table(file.path(), "logs").await.unwrap();
+
+        let count = "SELECT COUNT(*) FROM errors JOIN hosts ON errors.line LIKE '%' || hosts.host";
+        for _ in 0..2 {
+            let result = engine.execute_sql(count).await.unwrap();
+            assert_eq!(result.rows, vec![vec![serde_json::json!(1)]]);
+            // The view follows the newly registered table rather than the one it was made from
+            engine.clear().await;
+            engine.register_table(file.path(), "logs").await.unwrap();
+        }
+
+        // With a file registered, a view that can't be planned is refused
+        let missing = SavedView {
+            name: "missing".to_string(),
+            sql: "SELECT * FROM nowhere".to_string(),
+        };
+        assert!(engine.save_view(missing).await.is_err());
+        assert_eq!(engine.views().len(), 1);
+
+        assert!(engine.drop_lookup("hosts").await);
+        assert!(engine.execute_sql(count).await.is_err());
+        assert!(engine.drop_view("errors").await);
+        assert!(engine.execute_sql("SELECT * FROM errors").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_parsed_table_keeps_source_columns() {
+        let lines = ["first", r#"{"level":"warn","raw_line":"shadowed"}"#];
+        let mut parser = crate::parsers::json::JsonParser;
+        let table = crate::parsers::parse_lines(
+            &mut parser,
+            lines.iter().enumerate().map(|(i, l)| (i as u64 + 1, *l)),
+        );
+
+        let engine = QueryEngine::new();
+        let raw_line = |n: u64| lines.get(n as usize - 1).map(|l| l.to_string());
+        engine
+            .register_parsed(&table, "parsed", &raw_line)
+            .await
+            .unwrap();
+        let result = engine.execute_sql("SELECT * FROM parsed").await.unwrap();
+        assert_eq!(result.columns, vec!["line_number", "raw_line", "level"]);
+        assert_eq!(
+            result.rows,
+            vec![vec![
+                serde_json::json!(2),
+                serde_json::json!(lines[1]),
+                serde_json::json!("warn")
+            ]]
+        );
+        let columns = engine.table_columns("parsed").await.unwrap();
+        assert_eq!(columns[0].column_type, ColumnType::Int64);
+        assert_eq!(columns[2].name, "level");
+    }
+
+    #[tokio::test]
+    async fn test_parsed_table_dictionary_encodes_repeated_text() {
+        let lines: Vec<String> = (0..40)
+            .map(|i| {
+                let level = if i % 4 == 0 { "warn" } else { "info" };
+                format!(r#"{{"level":"{level}","msg":"request {i}"}}"#)
+            })
+            .collect();
+        let mut parser = crate::parsers::json::JsonParser;
+        let table = crate::parsers::parse_lines(
+            &mut parser,
+            lines
+                .iter()
+                .enumerate()
+                .map(|(i, l)| (i as u64 + 1, l.as_str())),
+        );
+
+        let engine = QueryEngine::new();
+        let raw_line = |n: u64| lines.get(n as usize - 1).cloned();
+        engine
+            .register_parsed(&table, "parsed", &raw_line)
+            .await
+            .unwrap();
+        let ctx = engine.ctx.lock().await;
+        let schema = ctx.table_provider("parsed").await.unwrap().schema();
+        assert_eq!(
+            schema.field_with_name("level").unwrap().data_type(),
+            &dictionary_type()
+        );
+        assert_eq!(
+            schema.field_with_name("msg").unwrap().data_type(),
+            &DataType::Utf8
+        );
+        drop(ctx);
+
+        let result = engine
+            .execute_sql("SELECT level, COUNT(*) FROM parsed GROUP BY level ORDER BY level")
+            .await
+            .unwrap();
+        assert_eq!(
+            result.rows,
+            vec![
+                vec![serde_json::json!("info"), serde_json::json!(30)],
+                vec![serde_json::json!("warn"), serde_json::json!(10)],
+            ]
+        );
+        let columns = engine.table_columns("parsed").await.unwrap();
+        let level = columns.iter().find(|c| c.name == "level").unwrap();
+        assert_eq!(level.column_type, ColumnType::Utf8);
+    }
+
+    #[test]
+    fn test_extract_value_structured_types() {
+        use datafusion::arrow::array::{
+            BinaryArray, Date32Array, Decimal128Array, ListArray, StructArray,
+            TimestampMicrosecondArray,
+        };
+        use datafusion::arrow::datatypes::Int64Type;
+
+        let value = |array: ArrayRef| QueryEngine::extract_value(&array, 0).unwrap();
+        assert_eq!(
+            value(Arc::new(TimestampMicrosecondArray::from(vec![
+                1_714_557_600_123_456
+            ]))),
+            serde_json::json!("2024-05-01T10:00:00.123456Z")
+        );
+        assert_eq!(
+            value(Arc::new(Date32Array::from(vec![19_844]))),
+            serde_json::json!("2024-05-01")
+        );
+        let decimal = Decimal128Array::from(vec![12_345])
+            .with_precision_and_scale(10, 2)
+            .unwrap();
+        assert_eq!(value(Arc::new(decimal)), serde_json::json!("123.45"));
+        let list = ListArray::from_iter_primitive::<Int64Type, _, _>(vec![Some(vec![
+            Some(1),
+            None,
+            Some(3),
+        ])]);
+        assert_eq!(value(Arc::new(list)), serde_json::json!([1, null, 3]));
+        let record = StructArray::from(vec![
+            (
+                Arc::new(Field::new("code", DataType::Int64, false)),
+                Arc::new(Int64Array::from(vec![503])) as ArrayRef,
+            ),
+            (
+                Arc::new(Field::new("host", DataType::Utf8, false)),
+                Arc::new(StringArray::from(vec!["db1"])) as ArrayRef,
+            ),
+        ]);
+        assert_eq!(
+            value(Arc::new(record)),
+            serde_json::json!({"code": 503, "host": "db1"})
+        );
+        let binary = BinaryArray::from(vec![b"log".as_ref(), b"lo".as_ref()]);
+        let binary: ArrayRef = Arc::new(binary);
+        assert_eq!(
+            QueryEngine::extract_value(&binary, 0).unwrap(),
+            serde_json::json!("bG9n")
+        );
+        assert_eq!(
+            QueryEngine::extract_value(&binary, 1).unwrap(),
+            serde_json::json!("bG8=")
+        );
+
+        // A bad index is an error rather than a panic
+        assert!(matches!(
+            QueryEngine::extract_value(&binary, 2),
+            Err(QueryError::UnexpectedValue(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_register_gzip_table() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        writeln!(encoder, r#"{{"level":"info","message":"test1"}}"#).unwrap();
+        writeln!(encoder, r#"{{"level":"error","message":"test2"}}"#).unwrap();
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(&encoder.finish().unwrap()).unwrap();
+        file.flush().unwrap();
+
+        let engine = QueryEngine::new();
+        let format = engine.register_table(file.path(), "logs").await.unwrap();
+        assert_eq!(format, FileFormat::Ndjson);
+        let result = engine
+            .execute_sql("SELECT line FROM logs WHERE line_number = 2")
+            .await
+            .unwrap();
+        assert_eq!(
+            result.rows,
+            vec![vec![serde_json::json!(
+                r#"{"level":"error","message":"test2"}"#
+            )]]
+        );
+
+        // An opened file's decompressed text registers the same table
+        let log_file = crate::indexer::LogFile::open(file.path()).unwrap();
+        let table = QueryEngine::line_table(log_file.data()).unwrap();
+        let engine = QueryEngine::new();
+        engine.register_line_table(table, "logs").await.unwrap();
+        let same = engine
+            .execute_sql("SELECT line FROM logs WHERE line_number = 2")
+            .await
+            .unwrap();
+        assert_eq!(same.rows, result.rows);
+    }
+
+    #[test]
+    fn test_detect_format_semicolon_csv() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "timestamp;level;message").unwrap();
+        writeln!(file, "2024-01-01;INFO;\"a; b\"").unwrap();
+        writeln!(file, "2024-01-01;ERROR;test2").unwrap();
+        file.flush().unwrap();
+
+        let format = QueryEngine::detect_format(file.path()).unwrap();
+        assert_eq!(format, FileFormat::Csv);
+    }
 }
@@ -0,0 +1,629 @@
+use crate::field_search::{
+    field_condition, find_column, like_pattern, quote_identifier, quote_text, Comparison,
+    FieldQueryError,
+};
+use crate::parsers::{Column, ColumnType};
+use thiserror::Error;
+
+/// Errors that can occur translating a query to SQL
+#[derive(Debug, Error, PartialEq)]
+pub enum QueryLanguageError {
+    #[error("{0}")]
+    Field(#[from] FieldQueryError),
+    #[error("Unclosed quote in query")]
+    UnclosedQuote,
+    #[error("Expected {expected}, found {found}")]
+    Unexpected {
+        expected: &'static str,
+        found: String,
+    },
+    #[error("Unknown command \"{0}\"; use where, stats, sort, head or fields")]
+    UnknownCommand(String),
+    #[error("Unknown function \"{0}\"; use count, sum, avg, min, max or distinct")]
+    UnknownFunction(String),
+}
+
+type Result<T> = std::result::Result<T, QueryLanguageError>;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// Field name, keyword or unquoted value
+    Word(String),
+    /// Double-quoted text, with `\"` and `\\` unescaped
+    Text(String),
+    Op(&'static str),
+    Open,
+    Close,
+    Comma,
+    Pipe,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Word(word) => format!("\"{}\"", word),
+            Token::Text(text) => format!("\"{}\"", text),
+            Token::Op(op) => format!("\"{}\"", op),
+            Token::Open => "\"(\"".to_string(),
+            Token::Close => "\")\"".to_string(),
+            Token::Comma => "\",\"".to_string(),
+            Token::Pipe => "\"|\"".to_string(),
+        }
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self, Token::Word(word) if word.eq_ignore_ascii_case(keyword))
+    }
+}
+
+/// Comparison operators, longest first so `>=` isn't read as `>`
+const OPERATORS: [&str; 8] = ["!=", "!~", ">=", "<=", "=", "~", ">", "<"];
+
+fn tokenize(query: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut rest = query;
+    while let Some(c) = rest.chars().next() {
+        if c.is_whitespace() {
+            rest = &rest[c.len_utf8()..];
+            continue;
+        }
+        if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+            tokens.push(Token::Op(*op));
+            rest = &rest[op.len()..];
+            continue;
+        }
+        let punctuation = match c {
+            '(' => Some(Token::Open),
+            ')' => Some(Token::Close),
+            ',' => Some(Token::Comma),
+            '|' => Some(Token::Pipe),
+            _ => None,
+        };
+        if let Some(token) = punctuation {
+            tokens.push(token);
+            rest = &rest[1..];
+            continue;
+        }
+        if c == '"' {
+            let mut text = String::new();
+            let mut chars = rest[1..].char_indices();
+            let end = loop {
+                match chars.next() {
+                    Some((i, '"')) => break i + 2,
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, escaped)) => text.push(escaped),
+                        None => return Err(QueryLanguageError::UnclosedQuote),
+                    },
+                    Some((_, c)) => text.push(c),
+                    None => return Err(QueryLanguageError::UnclosedQuote),
+                }
+            };
+            tokens.push(Token::Text(text));
+            rest = &rest[end..];
+            continue;
+        }
+        let end = rest
+            .find(|c: char| c.is_whitespace() || "()|,\"=!~<>".contains(c))
+            .unwrap_or(rest.len())
+            .max(c.len_utf8());
+        tokens.push(Token::Word(rest[..end].to_string()));
+        rest = &rest[end..];
+    }
+    Ok(tokens)
+}
+
+/// A SELECT being built up stage by stage; a stage that can't be merged into it wraps it
+/// as a subquery
+struct Select {
+    from: String,
+    /// Columns of the rows this select produces, checked by later stages
+    columns: Vec<Column>,
+    projection: Option<Vec<String>>,
+    filters: Vec<String>,
+    group_by: Vec<String>,
+    order_by: Vec<String>,
+    limit: Option<u64>,
+    /// Subqueries wrapped so far, to name the next one
+    depth: usize,
+}
+
+impl Select {
+    fn new(table: &str, columns: &[Column]) -> Self {
+        Select {
+            from: quote_identifier(table),
+            columns: columns.to_vec(),
+            projection: None,
+            filters: Vec::new(),
+            group_by: Vec::new(),
+            order_by: Vec::new(),
+            limit: None,
+            depth: 0,
+        }
+    }
+
+    fn sql(&self) -> String {
+        let mut sql = format!(
+            "SELECT {} FROM {}",
+            self.projection
+                .as_ref()
+                .map_or("*".to_string(), |columns| columns.join(", ")),
+            self.from
+        );
+        if !self.filters.is_empty() {
+            sql.push_str(&format!(" WHERE {}", self.filters.join(" AND ")));
+        }
+        if !self.group_by.is_empty() {
+            sql.push_str(&format!(" GROUP BY {}", self.group_by.join(", ")));
+        }
+        if !self.order_by.is_empty() {
+            sql.push_str(&format!(" ORDER BY {}", self.order_by.join(", ")));
+        }
+        if let Some(limit) = self.limit {
+            sql.push_str(&format!(" LIMIT {}", limit));
+        }
+        sql
+    }
+
+    /// Order unsorted rows by line, so they come back (and get cut off by a limit) in
+    /// file order
+    fn keep_file_order(&mut self) {
+        if self.group_by.is_empty()
+            && self.order_by.is_empty()
+            && self.column("line_number").is_ok()
+        {
+            self.order_by.push(quote_identifier("line_number"));
+        }
+    }
+
+    /// Turn what has been built so far into the table the next stage reads
+    fn wrap(&mut self) {
+        self.depth += 1;
+        *self = Select {
+            from: format!("({}) AS stage{}", self.sql(), self.depth),
+            columns: std::mem::take(&mut self.columns),
+            projection: None,
+            filters: Vec::new(),
+            group_by: Vec::new(),
+            order_by: Vec::new(),
+            limit: None,
+            depth: self.depth,
+        };
+    }
+
+    fn column(&self, field: &str) -> Result<&Column> {
+        Ok(find_column(&self.columns, field)?)
+    }
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn advance(&mut self) -> Option<&'a Token> {
+        let token = self.peek();
+        self.position += 1;
+        token
+    }
+
+    fn at_end(&self) -> bool {
+        self.position >= self.tokens.len()
+    }
+
+    fn unexpected<T>(&self, expected: &'static str) -> Result<T> {
+        Err(QueryLanguageError::Unexpected {
+            expected,
+            found: self
+                .peek()
+                .map_or("the end of the query".to_string(), Token::describe),
+        })
+    }
+
+    fn word(&mut self, expected: &'static str) -> Result<&'a str> {
+        match self.peek() {
+            Some(Token::Word(word)) => {
+                self.position += 1;
+                Ok(word)
+            }
+            _ => self.unexpected(expected),
+        }
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        let matched = self.peek() == Some(token);
+        if matched {
+            self.position += 1;
+        }
+        matched
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let matched = self.peek().is_some_and(|t| t.is_keyword(keyword));
+        if matched {
+            self.position += 1;
+        }
+        matched
+    }
+
+    /// Words separated by commas, e.g. the fields of `by host, status`
+    fn word_list(&mut self, expected: &'static str) -> Result<Vec<&'a str>> {
+        let mut words = vec![self.word(expected)?];
+        while self.eat(&Token::Comma) {
+            words.push(self.word(expected)?);
+        }
+        Ok(words)
+    }
+
+    /// `a OR b`, where OR binds more loosely than AND
+    fn or(&mut self, select: &Select) -> Result<String> {
+        let mut terms = vec![self.and(select)?];
+        while self.eat_keyword("or") {
+            terms.push(self.and(select)?);
+        }
+        Ok(join(terms, " OR "))
+    }
+
+    /// `a AND b`, or just `a b`
+    fn and(&mut self, select: &Select) -> Result<String> {
+        let mut terms = vec![self.not(select)?];
+        loop {
+            if self.eat_keyword("and") {
+                terms.push(self.not(select)?);
+            } else if self
+                .peek()
+                .is_some_and(|t| !t.is_keyword("or") && !matches!(t, Token::Close | Token::Pipe))
+            {
+                terms.push(self.not(select)?);
+            } else {
+                return Ok(join(terms, " AND "));
+            }
+        }
+    }
+
+    fn not(&mut self, select: &Select) -> Result<String> {
+        if self.eat_keyword("not") {
+            // A missing value doesn't match, so negating it should select the row
+            return Ok(format!("({}) IS NOT TRUE", self.not(select)?));
+        }
+        self.condition(select)
+    }
+
+    fn condition(&mut self, select: &Select) -> Result<String> {
+        match self.advance() {
+            Some(Token::Open) => {
+                let condition = self.or(select)?;
+                if !self.eat(&Token::Close) {
+                    return self.unexpected("\")\"");
+                }
+                Ok(condition)
+            }
+            // Quoted text on its own is looked for anywhere in the line
+            Some(Token::Text(text)) => {
+                let line = ["raw_line", "line"]
+                    .iter()
+                    .find_map(|name| select.column(name).ok())
+                    .ok_or_else(|| FieldQueryError::UnknownField("raw_line".to_string()))?;
+                Ok(format!(
+                    "{} ILIKE {}",
+                    quote_identifier(&line.name),
+                    quote_text(&format!("%{}%", like_pattern(text)))
+                ))
+            }
+            Some(Token::Word(field)) => {
+                let column = select.column(field)?;
+                let op = match self.advance() {
+                    Some(Token::Op(op)) => *op,
+                    _ => {
+                        self.position -= 1;
+                        return self.unexpected("a comparison such as = or ~");
+                    }
+                };
+                let value = match self.advance() {
+                    Some(Token::Word(value) | Token::Text(value)) => value,
+                    _ => {
+                        self.position -= 1;
+                        return self.unexpected("a value");
+                    }
+                };
+                comparison(column, op, value)
+            }
+            _ => {
+                self.position = self.position.saturating_sub(1);
+                self.unexpected("a condition")
+            }
+        }
+    }
+
+    /// One `| command ...` stage applied to `select`
+    fn stage(&mut self, select: &mut Select) -> Result<()> {
+        let command = self.word("a command")?.to_ascii_lowercase();
+        match command.as_str() {
+            "where" => {
+                if !select.group_by.is_empty() || select.limit.is_some() {
+                    select.wrap();
+                }
+                let condition = self.or(select)?;
+                select.filters.push(condition);
+            }
+            "stats" => self.stats(select)?,
+            "sort" => {
+                if select.limit.is_some() {
+                    select.wrap();
+                }
+                select.order_by = self
+                    .word_list("a field to sort by")?
+                    .into_iter()
+                    .map(|field| {
+                        let (field, direction) = match field.strip_prefix('-') {
+                            Some(field) => (field, " DESC"),
+                            None => (field, ""),
+                        };
+                        let column = select.column(field)?;
+                        Ok(format!("{}{}", quote_identifier(&column.name), direction))
+                    })
+                    .collect::<Result<_>>()?;
+            }
+            "head" | "limit" => {
+                let rows = self.word("a number of rows")?.parse::<u64>().or_else(|_| {
+                    self.position -= 1;
+                    self.unexpected("a number of rows")
+                })?;
+                select.keep_file_order();
+                select.limit = Some(select.limit.map_or(rows, |limit| limit.min(rows)));
+            }
+            "fields" => {
+                if !select.group_by.is_empty() || select.limit.is_some() {
+                    select.wrap();
+                }
+                let mut columns: Vec<Column> = self
+                    .word_list("a field")?
+                    .into_iter()
+                    .map(|field| select.column(field).cloned())
+                    .collect::<Result<_>>()?;
+                // Rows keep leading back to their line in the viewer
+                if let Ok(line_number) = select.column("line_number") {
+                    if !columns.contains(line_number) {
+                        columns.insert(0, line_number.clone());
+                    }
+                }
+                select.projection = Some(
+                    columns
+                        .iter()
+                        .map(|column| quote_identifier(&column.name))
+                        .collect(),
+                );
+                select.columns = columns;
+            }
+            _ => return Err(QueryLanguageError::UnknownCommand(command)),
+        }
+        Ok(())
+    }
+
+    /// `stats count, avg(duration) as mean by host`, ordered by the first aggregate,
+    /// largest first
+    fn stats(&mut self, select: &mut Select) -> Result<()> {
+        if !select.group_by.is_empty() || select.limit.is_some() || select.projection.is_some() {
+            select.wrap();
+        }
+        let mut aggregates = Vec::new();
+        loop {
+            let function = self
+                .word("an aggregate such as count")?
+                .to_ascii_lowercase();
+            let field = if self.eat(&Token::Open) {
+                let field = select.column(self.word("a field")?)?.clone();
+                if !self.eat(&Token::Close) {
+                    return self.unexpected("\")\"");
+                }
+                Some(field)
+            } else {
+                None
+            };
+            let (expression, column_type) = match (function.as_str(), &field) {
+                ("count", None) => ("COUNT(*)".to_string(), ColumnType::Int64),
+                ("count", Some(field)) => (call("COUNT", field), ColumnType::Int64),
+                ("distinct", Some(field)) => (
+                    format!("COUNT(DISTINCT {})", quote_identifier(&field.name)),
+                    ColumnType::Int64,
+                ),
+                ("sum", Some(field)) => (call("SUM", field), field.column_type),
+                ("avg", Some(field)) => (call("AVG", field), ColumnType::Float64),
+                ("min", Some(field)) => (call("MIN", field), field.column_type),
+                ("max", Some(field)) => (call("MAX", field), field.column_type),
+                ("distinct" | "sum" | "avg" | "min" | "max", None) => {
+                    return self.unexpected("\"(\" and a field");
+                }
+                _ => return Err(QueryLanguageError::UnknownFunction(function)),
+            };
+            let name = if self.eat_keyword("as") {
+                self.word("a column name")?.to_string()
+            } else {
+                field.map_or(function.clone(), |field| {
+                    format!("{}_{}", function, field.name)
+                })
+            };
+            aggregates.push((expression, Column { name, column_type }));
+            if !self.eat(&Token::Comma) {
+                break;
+            }
+        }
+        let keys: Vec<Column> = if self.eat_keyword("by") {
+            self.word_list("a field to group by")?
+                .into_iter()
+                .map(|field| select.column(field).cloned())
+                .collect::<Result<_>>()?
+        } else {
+            Vec::new()
+        };
+
+        let key_names: Vec<String> = keys.iter().map(|c| quote_identifier(&c.name)).collect();
+        let mut projection = key_names.clone();
+        projection.extend(aggregates.iter().map(|(expression, column)| {
+            format!("{} AS {}", expression, quote_identifier(&column.name))
+        }));
+        select.projection = Some(projection);
+        select.group_by = key_names;
+        select.order_by = vec![format!("{} DESC", quote_identifier(&aggregates[0].1.name))];
+        select.columns = keys
+            .into_iter()
+            .chain(aggregates.into_iter().map(|(_, column)| column))
+            .collect();
+        Ok(())
+    }
+}
+
+fn call(function: &str, field: &Column) -> String {
+    format!("{}({})", function, quote_identifier(&field.name))
+}
+
+fn join(terms: Vec<String>, separator: &str) -> String {
+    if terms.len() == 1 {
+        terms.into_iter().next().unwrap_or_default()
+    } else {
+        format!("({})", terms.join(separator))
+    }
+}
+
+fn comparison(column: &Column, op: &str, value: &str) -> Result<String> {
+    let text = match column.column_type {
+        ColumnType::Utf8 => quote_identifier(&column.name),
+        _ => format!("CAST({} AS VARCHAR)", quote_identifier(&column.name)),
+    };
+    let condition = match op {
+        "~" => format!("{} ~ {}", text, quote_text(value)),
+        "!~" => format!("({} ~ {}) IS NOT TRUE", text, quote_text(value)),
+        "!=" => format!(
+            "({}) IS NOT TRUE",
+            field_condition(column, Comparison::Eq, value)?
+        ),
+        "=" => field_condition(column, Comparison::Eq, value)?,
+        "<" => field_condition(column, Comparison::Lt, value)?,
+        "<=" => field_condition(column, Comparison::Le, value)?,
+        ">" => field_condition(column, Comparison::Gt, value)?,
+        _ => field_condition(column, Comparison::Ge, value)?,
+    };
+    Ok(condition)
+}
+
+/// Translate a query in the mini language to SQL over `table`, whose columns are given
+///
+/// A query is a filter followed by `| command` stages, e.g.
+/// `level=error AND msg~"timeout" | stats count by host`. The filter compares fields with
+/// `=`, `!=`, `<`, `<=`, `>`, `>=`, `~` (regex) and `!~`, combined with AND (or just a
+/// space), OR, NOT and parentheses; quoted text on its own matches anywhere in the line.
+/// `=` ignores case for text and takes `*` wildcards, as in field search. The commands are
+/// `where <filter>`, `stats <aggregates> [by <fields>]` with count, count(f), distinct(f),
+/// sum, avg, min and max, `sort [-]<field>, ...`, `head <n>` and `fields <field>, ...`
+pub fn translate(query: &str, table: &str, columns: &[Column]) -> Result<String> {
+    let tokens = tokenize(query)?;
+    let mut parser = Parser {
+        tokens: &tokens,
+        position: 0,
+    };
+    let mut select = Select::new(table, columns);
+    if !parser.at_end() && parser.peek() != Some(&Token::Pipe) {
+        let condition = parser.or(&select)?;
+        select.filters.push(condition);
+    }
+    while !parser.at_end() {
+        if !parser.eat(&Token::Pipe) {
+            return parser.unexpected("\"|\" or the end of the query");
+        }
+        parser.stage(&mut select)?;
+    }
+    select.keep_file_order();
+    Ok(select.sql())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn columns() -> Vec<Column> {
+        [
+            ("line_number", ColumnType::Int64),
+            ("raw_line", ColumnType::Utf8),
+            ("level", ColumnType::Utf8),
+            ("msg", ColumnType::Utf8),
+            ("host", ColumnType::Utf8),
+            ("duration", ColumnType::Float64),
+        ]
+        .iter()
+        .map(|&(name, column_type)| Column {
+            name: name.to_string(),
+            column_type,
+        })
+        .collect()
+    }
+
+    fn sql(query: &str) -> Result<String> {
+        translate(query, "parsed", &columns())
+    }
+
+    #[test]
+    fn test_filters_and_stages() {
+        assert_eq!(
+            sql(r#"level=error AND msg~"time(d )?out" | stats count by host"#).unwrap(),
+            "SELECT \"host\", COUNT(*) AS \"count\" FROM \"parsed\" \
+             WHERE (\"level\" ILIKE 'error' AND \"msg\" ~ 'time(d )?out') \
+             GROUP BY \"host\" ORDER BY \"count\" DESC"
+        );
+        assert_eq!(
+            sql(r#"(level=warn OR level=error) NOT host=web* "disk full""#).unwrap(),
+            "SELECT * FROM \"parsed\" WHERE ((\"level\" ILIKE 'warn' OR \"level\" ILIKE 'error') \
+             AND (\"host\" ILIKE 'web%') IS NOT TRUE AND \"raw_line\" ILIKE '%disk full%') \
+             ORDER BY \"line_number\""
+        );
+        assert_eq!(
+            sql("duration>1.5 | fields host, msg | sort -duration | head 10").unwrap_err(),
+            QueryLanguageError::Field(FieldQueryError::UnknownField("duration".to_string()))
+        );
+        assert_eq!(
+            sql("duration>1.5 | sort -duration | head 10 | fields host").unwrap(),
+            "SELECT \"line_number\", \"host\" FROM (SELECT * FROM \"parsed\" \
+             WHERE \"duration\" > 1.5 ORDER BY \"duration\" DESC LIMIT 10) AS stage1 \
+             ORDER BY \"line_number\""
+        );
+    }
+
+    #[test]
+    fn test_stats_output_feeds_later_stages() {
+        assert_eq!(
+            sql("| stats avg(duration) as mean, count by host | where count>=5 | head 3").unwrap(),
+            "SELECT * FROM (SELECT \"host\", AVG(\"duration\") AS \"mean\", COUNT(*) AS \"count\" \
+             FROM \"parsed\" GROUP BY \"host\" ORDER BY \"mean\" DESC) AS stage1 \
+             WHERE \"count\" >= 5 LIMIT 3"
+        );
+        assert_eq!(
+            sql("| head 5 | stats count by host").unwrap(),
+            "SELECT \"host\", COUNT(*) AS \"count\" FROM (SELECT * FROM \"parsed\" \
+             ORDER BY \"line_number\" LIMIT 5) AS stage1 GROUP BY \"host\" ORDER BY \"count\" DESC"
+        );
+    }
+
+    #[test]
+    fn test_query_errors() {
+        assert_eq!(sql("msg=\"open"), Err(QueryLanguageError::UnclosedQuote));
+        assert_eq!(
+            sql("level"),
+            Err(QueryLanguageError::Unexpected {
+                expected: "a comparison such as = or ~",
+                found: "the end of the query".to_string(),
+            })
+        );
+        assert_eq!(
+            sql("| tally"),
+            Err(QueryLanguageError::UnknownCommand("tally".to_string()))
+        );
+        assert_eq!(
+            sql("| stats median(duration)"),
+            Err(QueryLanguageError::UnknownFunction("median".to_string()))
+        );
+        assert!(sql("level=error)").is_err());
+    }
+}
@@ -0,0 +1,235 @@
+use parking_lot::Mutex;
+use regex::{Regex, RegexBuilder, RegexSet, RegexSetBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+use thiserror::Error;
+
+/// Most compiled patterns kept; the least recently used is dropped first
+pub const REGEX_CACHE_SIZE: usize = 256;
+
+/// Options a pattern is compiled with; the same pattern with other flags is cached apart
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct RegexFlags {
+    pub case_insensitive: bool,
+}
+
+/// Regex engine a pattern is compiled for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RegexFlavor {
+    /// The regex crate: always linear time, without lookaround or backreferences
+    #[default]
+    Standard,
+    /// fancy-regex: adds lookaround and backreferences as in PCRE and grep -P, at the cost
+    /// of backtracking, which can be much slower on large files
+    Fancy,
+}
+
+/// A pattern that failed to compile with its flavor
+#[derive(Debug, Error)]
+pub enum PatternError {
+    #[error(transparent)]
+    Standard(#[from] regex::Error),
+    #[error(transparent)]
+    Fancy(#[from] Box<fancy_regex::Error>),
+}
+
+/// A compiled pattern of either flavor
+#[derive(Clone)]
+pub enum Matcher {
+    Standard(Arc<Regex>),
+    Fancy(Arc<fancy_regex::Regex>),
+}
+
+impl Matcher {
+    /// Compile a pattern with a flavor, sharing it with every other caller of the same pattern
+    pub fn new(pattern: &str, flavor: RegexFlavor) -> Result<Self, PatternError> {
+        match flavor {
+            RegexFlavor::Standard => Ok(Matcher::Standard(regex(pattern)?)),
+            RegexFlavor::Fancy => Ok(Matcher::Fancy(fancy_regex(pattern)?)),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Matcher::Standard(regex) => regex.as_str(),
+            Matcher::Fancy(regex) => regex.as_str(),
+        }
+    }
+
+    /// The text a pattern matches if it is a plain literal, e.g. `timeout` or `(?i)timeout`,
+    /// and whether it matches regardless of case
+    pub fn literal(&self) -> Option<(&str, bool)> {
+        let pattern = self.as_str();
+        let (text, case_insensitive) = match pattern.strip_prefix("(?i)") {
+            Some(text) => (text, true),
+            None => (pattern, false),
+        };
+        (regex::escape(text) == text).then_some((text, case_insensitive))
+    }
+
+    /// Whether the pattern matches; a fancy pattern that gives up after backtracking too
+    /// long counts as not matching
+    pub fn is_match(&self, text: &str) -> bool {
+        match self {
+            Matcher::Standard(regex) => regex.is_match(text),
+            Matcher::Fancy(regex) => regex.is_match(text).unwrap_or(false),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash)]
+enum Key {
+    One(String, RegexFlags),
+    Set(Vec<String>, RegexFlags),
+    Fancy(String),
+}
+
+#[derive(Clone)]
+enum Compiled {
+    One(Arc<Regex>),
+    Set(Arc<RegexSet>),
+    Fancy(Arc<fancy_regex::Regex>),
+}
+
+/// Compiled patterns with the tick each was last used at
+struct Lru {
+    entries: HashMap<Key, (Compiled, u64)>,
+    tick: u64,
+    capacity: usize,
+}
+
+impl Lru {
+    fn new(capacity: usize) -> Self {
+        Lru {
+            entries: HashMap::new(),
+            tick: 0,
+            capacity,
+        }
+    }
+
+    fn get(&mut self, key: &Key) -> Option<Compiled> {
+        self.tick += 1;
+        let (compiled, used) = self.entries.get_mut(key)?;
+        *used = self.tick;
+        Some(compiled.clone())
+    }
+
+    fn insert(&mut self, key: Key, compiled: Compiled) {
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            // Ticks are unique, so this drops exactly the least recently used entry
+            if let Some(oldest) = self.entries.values().map(|(_, used)| *used).min() {
+                self.entries.retain(|_, (_, used)| *used != oldest);
+            }
+        }
+        self.tick += 1;
+        self.entries.insert(key, (compiled, self.tick));
+    }
+}
+
+fn cache() -> &'static Mutex<Lru> {
+    static CACHE: OnceLock<Mutex<Lru>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(Lru::new(REGEX_CACHE_SIZE)))
+}
+
+/// Look a pattern up, compiling it outside the lock on a miss so a slow compile doesn't
+/// hold up other threads; invalid patterns aren't cached
+fn lookup<E>(key: Key, compile: impl FnOnce() -> Result<Compiled, E>) -> Result<Compiled, E> {
+    if let Some(compiled) = cache().lock().get(&key) {
+        return Ok(compiled);
+    }
+    let compiled = compile()?;
+    cache().lock().insert(key, compiled.clone());
+    Ok(compiled)
+}
+
+/// A compiled pattern, shared with every other caller of the same pattern
+pub fn regex(pattern: &str) -> Result<Arc<Regex>, regex::Error> {
+    regex_with(pattern, RegexFlags::default())
+}
+
+/// A compiled pattern with flags, shared with every other caller of the same pattern and flags
+pub fn regex_with(pattern: &str, flags: RegexFlags) -> Result<Arc<Regex>, regex::Error> {
+    let key = Key::One(pattern.to_string(), flags);
+    let compiled = lookup(key, || {
+        RegexBuilder::new(pattern)
+            .case_insensitive(flags.case_insensitive)
+            .build()
+            .map(|regex| Compiled::One(Arc::new(regex)))
+    })?;
+    match compiled {
+        Compiled::One(regex) => Ok(regex),
+        _ => unreachable!("single patterns are cached under their own key"),
+    }
+}
+
+/// Patterns compiled as a set, for finding which of them match a line in one pass
+pub fn regex_set<S: AsRef<str>>(
+    patterns: &[S],
+    flags: RegexFlags,
+) -> Result<Arc<RegexSet>, regex::Error> {
+    let patterns: Vec<String> = patterns.iter().map(|p| p.as_ref().to_string()).collect();
+    let compiled = lookup(Key::Set(patterns.clone(), flags), || {
+        RegexSetBuilder::new(&patterns)
+            .case_insensitive(flags.case_insensitive)
+            .build()
+            .map(|set| Compiled::Set(Arc::new(set)))
+    })?;
+    match compiled {
+        Compiled::Set(set) => Ok(set),
+        _ => unreachable!("sets are cached under their own key"),
+    }
+}
+
+/// A pattern compiled for fancy-regex, shared with every other caller of the same pattern
+pub fn fancy_regex(pattern: &str) -> Result<Arc<fancy_regex::Regex>, Box<fancy_regex::Error>> {
+    let compiled = lookup(Key::Fancy(pattern.to_string()), || {
+        fancy_regex::Regex::new(pattern)
+            .map(|regex| Compiled::Fancy(Arc::new(regex)))
+            .map_err(Box::new)
+    })?;
+    match compiled {
+        Compiled::Fancy(regex) => Ok(regex),
+        _ => unreachable!("fancy patterns are cached under their own key"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_regex_cache() {
+        let first = regex("cache-test-(\\d+)").unwrap();
+        assert!(Arc::ptr_eq(&first, &regex("cache-test-(\\d+)").unwrap()));
+        let flags = RegexFlags {
+            case_insensitive: true,
+        };
+        let insensitive = regex_with("cache-test-(\\d+)", flags).unwrap();
+        assert!(!Arc::ptr_eq(&first, &insensitive));
+        assert!(insensitive.is_match("CACHE-TEST-1"));
+        assert!(regex("cache-test-(").is_err());
+
+        let set = regex_set(&["^a", "b$"], RegexFlags::default()).unwrap();
+        assert_eq!(set.matches("ab").into_iter().collect::<Vec<_>>(), [0, 1]);
+
+        // Lookaround and backreferences need the fancy flavor
+        let pasted = r"(\w+) \1(?= done)";
+        assert!(Matcher::new(pasted, RegexFlavor::Standard).is_err());
+        let fancy = Matcher::new(pasted, RegexFlavor::Fancy).unwrap();
+        assert!(fancy.is_match("retry retry done"));
+        assert!(!fancy.is_match("retry again done"));
+        assert_eq!(fancy.as_str(), pasted);
+
+        let mut lru = Lru::new(2);
+        let entry = Compiled::One(first);
+        lru.insert(Key::One("a".to_string(), flags), entry.clone());
+        lru.insert(Key::One("b".to_string(), flags), entry.clone());
+        assert!(lru.get(&Key::One("a".to_string(), flags)).is_some());
+        lru.insert(Key::One("c".to_string(), flags), entry);
+        assert!(lru.get(&Key::One("b".to_string(), flags)).is_none());
+        assert!(lru.get(&Key::One("a".to_string(), flags)).is_some());
+        assert_eq!(lru.entries.len(), 2);
+    }
+}
@@ -0,0 +1,755 @@
+use crate::charts::Metric;
+use crate::query_engine::QueryEngine;
+use chrono::{SecondsFormat, Utc};
+use datafusion::arrow::array::{make_array, MutableArrayData};
+use datafusion::arrow::datatypes::SchemaRef;
+use datafusion::arrow::error::ArrowError;
+use datafusion::arrow::record_batch::{RecordBatch, RecordBatchOptions};
+use datafusion::parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use datafusion::parquet::arrow::ArrowWriter;
+use datafusion::parquet::errors::ParquetError;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Result sets kept at once; the oldest go first
+pub const KEPT_RESULTS: usize = 8;
+
+/// Arrow memory kept results may take before older ones are let go; the newest is always
+/// kept whatever its size
+pub const KEPT_RESULT_BYTES: usize = 512 * 1024 * 1024;
+
+/// Crosstab columns kept; any more are counted as omitted
+pub const MAX_CROSSTAB_COLUMNS: usize = 200;
+
+/// Rows of each kind a result diff returns; any more are counted as omitted
+pub const MAX_DIFF_ROWS: usize = 10_000;
+
+/// Name prefix of the temporary directories pinned results are kept in, one per run
+const PIN_DIR_PREFIX: &str = "log-microscope-pins-";
+
+/// File in a pin directory that its store holds locked while the app runs
+const PIN_LOCK_FILE: &str = ".lock";
+
+/// Ids of results, unique across the engines of both open files so a pinned result keeps
+/// its id
+static NEXT_RESULT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Errors that can occur pinning results, reading them back or comparing them
+#[derive(Debug, Error)]
+pub enum PinError {
+    #[error("No pinned result {0}")]
+    UnknownPin(u64),
+    #[error("Pick at least one key column to match rows on")]
+    NoKeys,
+    #[error("Both results need the key column \"{0}\"")]
+    MissingKey(String),
+    #[error("Failed to write or read the pinned result: {0}")]
+    Io(#[from] io::Error),
+    #[error("Failed to store the pinned result: {0}")]
+    Parquet(#[from] ParquetError),
+    #[error("Failed to read the pinned result: {0}")]
+    Arrow(#[from] ArrowError),
+}
+
+/// Order a result is sorted in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortDirection {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl SortDirection {
+    pub fn sql(self) -> &'static str {
+        match self {
+            SortDirection::Asc => "ASC",
+            SortDirection::Desc => "DESC",
+        }
+    }
+}
+
+/// The Arrow batches of a query's results, to sort and filter again without rerunning it
+#[derive(Debug)]
+pub struct CachedResult {
+    pub schema: SchemaRef,
+    pub batches: Vec<RecordBatch>,
+}
+
+impl CachedResult {
+    pub fn rows(&self) -> usize {
+        self.batches.iter().map(RecordBatch::num_rows).sum()
+    }
+
+    pub fn columns(&self) -> Vec<String> {
+        self.schema
+            .fields()
+            .iter()
+            .map(|f| f.name().clone())
+            .collect()
+    }
+
+    /// A copy holding only its own rows, as query output can be slices sharing the buffers
+    /// of a whole table, which would stay alive as long as the result is kept
+    pub fn compacted(self) -> Result<Self, ArrowError> {
+        let batches = self
+            .batches
+            .iter()
+            .map(|batch| {
+                let columns = batch
+                    .columns()
+                    .iter()
+                    .map(|column| {
+                        let data = column.to_data();
+                        let mut copy = MutableArrayData::new(vec![&data], false, data.len());
+                        copy.extend(0, 0, data.len());
+                        make_array(copy.freeze())
+                    })
+                    .collect();
+                let options = RecordBatchOptions::new().with_row_count(Some(batch.num_rows()));
+                RecordBatch::try_new_with_options(batch.schema(), columns, &options)
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(CachedResult {
+            schema: self.schema,
+            batches,
+        })
+    }
+
+    /// JSON rows, with values that can't be read as null
+    fn json_rows(&self) -> Vec<Vec<serde_json::Value>> {
+        if self.batches.is_empty() {
+            return Vec::new();
+        }
+        QueryEngine::result_rows(&self.batches).1
+    }
+
+    fn memory_size(&self) -> usize {
+        self.batches
+            .iter()
+            .map(RecordBatch::get_array_memory_size)
+            .sum()
+    }
+}
+
+/// The most recent result sets by id
+#[derive(Debug, Default)]
+pub struct ResultCache {
+    /// Oldest first
+    results: VecDeque<(u64, Arc<CachedResult>)>,
+}
+
+impl ResultCache {
+    /// Keep a result set, letting the oldest go past `KEPT_RESULTS` or `KEPT_RESULT_BYTES`.
+    /// Returns its id
+    pub fn insert(&mut self, result: CachedResult) -> u64 {
+        let id = NEXT_RESULT_ID.fetch_add(1, Ordering::Relaxed);
+        self.results.push_back((id, Arc::new(result)));
+        let mut bytes: usize = self.results.iter().map(|(_, r)| r.memory_size()).sum();
+        while self.results.len() > 1
+            && (self.results.len() > KEPT_RESULTS || bytes > KEPT_RESULT_BYTES)
+        {
+            if let Some((_, evicted)) = self.results.pop_front() {
+                bytes -= evicted.memory_size();
+            }
+        }
+        id
+    }
+
+    pub fn get(&self, id: u64) -> Option<Arc<CachedResult>> {
+        self.results
+            .iter()
+            .find(|(kept, _)| *kept == id)
+            .map(|(_, result)| result.clone())
+    }
+}
+
+/// A result snapshot kept on disk for the rest of the session, to compare against after its
+/// file is closed or tailing has moved on
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PinnedResult {
+    /// The id of the result it was pinned from
+    pub id: u64,
+    pub name: String,
+    pub columns: Vec<String>,
+    pub rows: usize,
+    pub pinned_at: String,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+/// Pinned results, each a Parquet file in a temporary directory removed with the store
+#[derive(Debug)]
+pub struct PinStore {
+    dir: PathBuf,
+    pins: Vec<PinnedResult>,
+    /// Held locked once the directory exists, telling other runs it is still in use
+    lock: Option<File>,
+}
+
+impl Default for PinStore {
+    fn default() -> Self {
+        let dir = std::env::temp_dir().join(format!("{}{}", PIN_DIR_PREFIX, std::process::id()));
+        PinStore::new(dir)
+    }
+}
+
+impl PinStore {
+    pub fn new(dir: PathBuf) -> Self {
+        PinStore {
+            dir,
+            pins: Vec::new(),
+            lock: None,
+        }
+    }
+
+    /// Remove the pin directories under `temp_dir` left by runs that didn't exit cleanly,
+    /// returning how many; a directory whose lock is still held belongs to a running app
+    pub fn remove_stale(temp_dir: &Path) -> usize {
+        let Ok(entries) = fs::read_dir(temp_dir) else {
+            return 0;
+        };
+        entries
+            .filter_map(Result::ok)
+            .filter(|entry| {
+                entry
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with(PIN_DIR_PREFIX)
+            })
+            .filter(|entry| {
+                File::open(entry.path().join(PIN_LOCK_FILE))
+                    .map_or(true, |lock| lock.try_lock().is_ok())
+            })
+            .filter(|entry| fs::remove_dir_all(entry.path()).is_ok())
+            .count()
+    }
+
+    /// Pinned results, in the order they were pinned
+    pub fn pins(&self) -> &[PinnedResult] {
+        &self.pins
+    }
+
+    /// Write a result to disk under its id; pinning it again only renames it
+    pub fn pin(
+        &mut self,
+        id: u64,
+        name: Option<String>,
+        result: &CachedResult,
+    ) -> Result<PinnedResult, PinError> {
+        let name = name.unwrap_or_else(|| format!("Result {}", id));
+        if let Some(pin) = self.pins.iter_mut().find(|pin| pin.id == id) {
+            pin.name = name;
+            return Ok(pin.clone());
+        }
+        if self.lock.is_none() {
+            fs::create_dir_all(&self.dir)?;
+            let lock = File::create(self.dir.join(PIN_LOCK_FILE))?;
+            lock.lock()?;
+            self.lock = Some(lock);
+        }
+        let path = self.dir.join(format!("{}.parquet", id));
+        let mut writer = ArrowWriter::try_new(File::create(&path)?, result.schema.clone(), None)?;
+        for batch in &result.batches {
+            writer.write(batch)?;
+        }
+        writer.close()?;
+        let pin = PinnedResult {
+            id,
+            name,
+            columns: result.columns(),
+            rows: result.rows(),
+            pinned_at: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            path,
+        };
+        self.pins.push(pin.clone());
+        Ok(pin)
+    }
+
+    /// Read a pinned result back from disk
+    pub fn load(&self, id: u64) -> Result<CachedResult, PinError> {
+        let pin = self.find(id)?;
+        let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(&pin.path)?)?;
+        let schema = builder.schema().clone();
+        let batches = builder.build()?.collect::<Result<Vec<_>, _>>()?;
+        Ok(CachedResult { schema, batches })
+    }
+
+    pub fn unpin(&mut self, id: u64) -> Result<(), PinError> {
+        let pin = self.find(id)?.clone();
+        self.pins.retain(|p| p.id != id);
+        fs::remove_file(&pin.path)?;
+        Ok(())
+    }
+
+    fn find(&self, id: u64) -> Result<&PinnedResult, PinError> {
+        self.pins
+            .iter()
+            .find(|pin| pin.id == id)
+            .ok_or(PinError::UnknownPin(id))
+    }
+}
+
+impl Drop for PinStore {
+    fn drop(&mut self) {
+        if self.lock.take().is_some() {
+            fs::remove_dir_all(&self.dir).ok();
+        }
+    }
+}
+
+/// One cell of a result in full, however long
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ResultCell {
+    pub column: String,
+    pub value: serde_json::Value,
+    /// Indented JSON for objects and arrays, including text holding one, such as a JSON
+    /// log line
+    pub pretty: Option<String>,
+}
+
+impl ResultCell {
+    pub fn new(column: &str, value: serde_json::Value) -> Self {
+        let structured = match &value {
+            serde_json::Value::String(text) => serde_json::from_str(text.trim()).ok(),
+            value => Some(value.clone()),
+        };
+        let pretty = structured
+            .filter(|v: &serde_json::Value| v.is_object() || v.is_array())
+            .and_then(|v| serde_json::to_string_pretty(&v).ok());
+        ResultCell {
+            column: column.to_string(),
+            value,
+            pretty,
+        }
+    }
+}
+
+/// A result reshaped into a matrix, one row per value of `row_key` and one column per value
+/// of `col_key`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Crosstab {
+    pub row_key: String,
+    pub col_key: String,
+    /// Values of `col_key`, in order
+    pub columns: Vec<serde_json::Value>,
+    pub rows: Vec<CrosstabRow>,
+    /// Columns left out beyond `MAX_CROSSTAB_COLUMNS`
+    pub omitted_columns: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CrosstabRow {
+    pub key: serde_json::Value,
+    /// Aligned with the crosstab's columns; None where no rows share both keys, except for
+    /// counts, which are 0 there
+    pub values: Vec<Option<f64>>,
+}
+
+/// Lay out grouped rows of row key, column key and aggregate as a crosstab, keys in the
+/// order they first appear
+pub fn crosstab(
+    row_key: &str,
+    col_key: &str,
+    agg: &Metric,
+    grouped: &[Vec<serde_json::Value>],
+) -> Crosstab {
+    let missing = match agg {
+        Metric::Count | Metric::Distinct { .. } => Some(0.0),
+        _ => None,
+    };
+    let mut columns: Vec<serde_json::Value> = Vec::new();
+    // None for the columns left out
+    let mut column_index: HashMap<String, Option<usize>> = HashMap::new();
+    let mut rows: Vec<CrosstabRow> = Vec::new();
+    let mut row_index: HashMap<String, usize> = HashMap::new();
+    let mut omitted = 0;
+    for group in grouped {
+        let [row, col, value] = group.as_slice() else {
+            continue;
+        };
+        let column = *column_index.entry(col.to_string()).or_insert_with(|| {
+            if columns.len() == MAX_CROSSTAB_COLUMNS {
+                omitted += 1;
+                return None;
+            }
+            columns.push(col.clone());
+            for existing in &mut rows {
+                existing.values.push(missing);
+            }
+            Some(columns.len() - 1)
+        });
+        let Some(column) = column else {
+            continue;
+        };
+        let next = rows.len();
+        let index = *row_index.entry(row.to_string()).or_insert(next);
+        if index == next {
+            rows.push(CrosstabRow {
+                key: row.clone(),
+                values: vec![missing; columns.len()],
+            });
+        }
+        rows[index].values[column] = value.as_f64();
+    }
+    Crosstab {
+        row_key: row_key.to_string(),
+        col_key: col_key.to_string(),
+        columns,
+        rows,
+        omitted_columns: omitted,
+    }
+}
+
+/// How the rows of one result differ from another's, matched on key columns
+/// Rows hold the keys and then the compared columns
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ResultDiff {
+    pub keys: Vec<String>,
+    /// Columns of both results besides the keys
+    pub compared: Vec<String>,
+    /// Columns of only one of the results, left out of the comparison
+    pub ignored: Vec<String>,
+    /// Rows of the second result with no match in the first
+    pub added: Vec<Vec<serde_json::Value>>,
+    /// Rows of the first result with no match in the second
+    pub removed: Vec<Vec<serde_json::Value>>,
+    pub changed: Vec<ChangedRow>,
+    pub unchanged: usize,
+    /// Added, removed and changed rows left out beyond `MAX_DIFF_ROWS` of each
+    pub omitted_rows: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChangedRow {
+    pub before: Vec<serde_json::Value>,
+    pub after: Vec<serde_json::Value>,
+    /// The compared columns whose values differ
+    pub columns: Vec<String>,
+}
+
+/// Compare two results row by row, matching rows with equal `keys`; rows repeating a key
+/// are matched in the order they appear. Each kind of difference keeps its first
+/// `MAX_DIFF_ROWS` rows
+pub fn diff(
+    keys: &[String],
+    before: &CachedResult,
+    after: &CachedResult,
+) -> Result<ResultDiff, PinError> {
+    if keys.is_empty() {
+        return Err(PinError::NoKeys);
+    }
+    let (before_columns, after_columns) = (before.columns(), after.columns());
+    let index_in = |columns: &[String], name: &String| columns.iter().position(|c| c == name);
+    let mut before_layout = Vec::new();
+    let mut after_layout = Vec::new();
+    for key in keys {
+        match (
+            index_in(&before_columns, key),
+            index_in(&after_columns, key),
+        ) {
+            (Some(b), Some(a)) => {
+                before_layout.push(b);
+                after_layout.push(a);
+            }
+            _ => return Err(PinError::MissingKey(key.clone())),
+        }
+    }
+    let mut compared = Vec::new();
+    let mut ignored = Vec::new();
+    for (b, column) in before_columns.iter().enumerate() {
+        if keys.contains(column) {
+            continue;
+        }
+        match index_in(&after_columns, column) {
+            Some(a) => {
+                before_layout.push(b);
+                after_layout.push(a);
+                compared.push(column.clone());
+            }
+            None => ignored.push(column.clone()),
+        }
+    }
+    ignored.extend(
+        after_columns
+            .iter()
+            .filter(|c| !keys.contains(c) && !before_columns.contains(c))
+            .cloned(),
+    );
+
+    let project = |row: &[serde_json::Value], layout: &[usize]| -> Vec<serde_json::Value> {
+        layout.iter().map(|&i| row[i].clone()).collect()
+    };
+    let key_of =
+        |row: &[serde_json::Value]| serde_json::Value::from(&row[..keys.len()]).to_string();
+    let after_rows: Vec<_> = after
+        .json_rows()
+        .iter()
+        .map(|row| project(row, &after_layout))
+        .collect();
+    let mut unmatched: HashMap<String, VecDeque<usize>> = HashMap::new();
+    for (index, row) in after_rows.iter().enumerate() {
+        unmatched.entry(key_of(row)).or_default().push_back(index);
+    }
+
+    let mut matched = vec![false; after_rows.len()];
+    let mut removed = Vec::new();
+    let mut changed = Vec::new();
+    let mut unchanged = 0;
+    let mut omitted_rows = 0;
+    for row in before.json_rows() {
+        let row = project(&row, &before_layout);
+        let Some(index) = unmatched
+            .get_mut(&key_of(&row))
+            .and_then(VecDeque::pop_front)
+        else {
+            keep_row(&mut removed, row, &mut omitted_rows);
+            continue;
+        };
+        matched[index] = true;
+        let other = &after_rows[index];
+        let columns: Vec<String> = compared
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| row[keys.len() + i] != other[keys.len() + i])
+            .map(|(_, column)| column.clone())
+            .collect();
+        if columns.is_empty() {
+            unchanged += 1;
+        } else {
+            let row = ChangedRow {
+                before: row,
+                after: other.clone(),
+                columns,
+            };
+            keep_row(&mut changed, row, &mut omitted_rows);
+        }
+    }
+    let mut added = Vec::new();
+    for (row, matched) in after_rows.into_iter().zip(matched) {
+        if !matched {
+            keep_row(&mut added, row, &mut omitted_rows);
+        }
+    }
+    Ok(ResultDiff {
+        keys: keys.to_vec(),
+        compared,
+        ignored,
+        added,
+        removed,
+        changed,
+        unchanged,
+        omitted_rows,
+    })
+}
+
+/// Add a row of a diff unless `MAX_DIFF_ROWS` are already kept, counting it as omitted
+fn keep_row<T>(rows: &mut Vec<T>, row: T, omitted: &mut usize) {
+    if rows.len() < MAX_DIFF_ROWS {
+        rows.push(row);
+    } else {
+        *omitted += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::Int64Array;
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+
+    #[test]
+    fn test_result_cache_keeps_newest() {
+        let schema = Arc::new(Schema::new(vec![Field::new("n", DataType::Int64, false)]));
+        let result = |n: i64| {
+            let batch =
+                RecordBatch::try_new(schema.clone(), vec![Arc::new(Int64Array::from(vec![n]))])
+                    .unwrap();
+            CachedResult {
+                schema: schema.clone(),
+                batches: vec![batch],
+            }
+        };
+        let mut cache = ResultCache::default();
+        let ids: Vec<u64> = (0..=KEPT_RESULTS as i64)
+            .map(|n| cache.insert(result(n)))
+            .collect();
+        assert!(cache.get(ids[0]).is_none());
+        let newest = cache.get(ids[KEPT_RESULTS]).unwrap();
+        assert_eq!(newest.rows(), 1);
+        assert!(cache.get(ids[1]).is_some());
+    }
+
+    #[test]
+    fn test_compacted_result_lets_the_table_go() {
+        let schema = Arc::new(Schema::new(vec![Field::new("n", DataType::Int64, false)]));
+        let table = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int64Array::from_iter_values(0..100_000))],
+        )
+        .unwrap();
+        let result = CachedResult {
+            schema,
+            batches: vec![table.slice(10, 3)],
+        };
+        assert!(result.memory_size() >= 800_000);
+        let compacted = result.compacted().unwrap();
+        assert!(compacted.memory_size() < 1000);
+        let rows: Vec<_> = (10..13).map(|n| vec![serde_json::json!(n)]).collect();
+        assert_eq!(compacted.json_rows(), rows);
+    }
+
+    #[test]
+    fn test_pinned_result_round_trips() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("n", DataType::Int64, false),
+            Field::new("level", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2])),
+                Arc::new(datafusion::arrow::array::StringArray::from(vec![
+                    Some("info"),
+                    None,
+                ])),
+            ],
+        )
+        .unwrap();
+        let result = CachedResult {
+            schema,
+            batches: vec![batch.clone()],
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = PinStore::new(dir.path().join("pins"));
+        let pin = store.pin(7, None, &result).unwrap();
+        assert_eq!((pin.name.as_str(), pin.rows), ("Result 7", 2));
+        let renamed = store.pin(7, Some("before".to_string()), &result).unwrap();
+        assert_eq!(renamed.name, "before");
+        assert_eq!(store.pins().len(), 1);
+
+        let loaded = store.load(7).unwrap();
+        assert_eq!(loaded.batches[0].columns(), batch.columns());
+
+        store.unpin(7).unwrap();
+        assert!(matches!(store.load(7), Err(PinError::UnknownPin(7))));
+
+        // A directory left by a run that didn't exit goes, one still in use stays
+        let stale = dir.path().join(format!("{}1", PIN_DIR_PREFIX));
+        fs::create_dir_all(&stale).unwrap();
+        let mut running = PinStore::new(dir.path().join(format!("{}2", PIN_DIR_PREFIX)));
+        running.pin(8, None, &result).unwrap();
+        assert_eq!(PinStore::remove_stale(dir.path()), 1);
+        assert!(!stale.exists());
+        assert_eq!(running.load(8).unwrap().rows(), 2);
+    }
+
+    #[test]
+    fn test_result_cell_pretty_prints_json() {
+        use serde_json::json;
+        let cell = ResultCell::new("line", json!(r#"{"level":"info","n":1}"#));
+        assert_eq!(
+            cell.pretty.as_deref(),
+            Some("{\n  \"level\": \"info\",\n  \"n\": 1\n}")
+        );
+        assert_eq!(ResultCell::new("line", json!("plain text")).pretty, None);
+        assert_eq!(ResultCell::new("n", json!(42)).pretty, None);
+    }
+
+    #[test]
+    fn test_crosstab() {
+        use serde_json::json;
+        let grouped = vec![
+            vec![json!("ERROR"), json!(9), json!(2)],
+            vec![json!("INFO"), json!(9), json!(40)],
+            vec![json!("INFO"), json!(10), json!(35)],
+            vec![json!("WARN"), json!(10), json!(1)],
+        ];
+        let table = crosstab("level", "hour", &Metric::Count, &grouped);
+        assert_eq!(table.columns, [json!(9), json!(10)]);
+        let rows: Vec<_> = table
+            .rows
+            .iter()
+            .map(|r| (r.key.clone(), r.values.clone()))
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                (json!("ERROR"), vec![Some(2.0), Some(0.0)]),
+                (json!("INFO"), vec![Some(40.0), Some(35.0)]),
+                (json!("WARN"), vec![Some(0.0), Some(1.0)]),
+            ]
+        );
+
+        // Other aggregates leave the gaps empty
+        let avg = Metric::Avg {
+            field: "ms".to_string(),
+        };
+        let table = crosstab("level", "hour", &avg, &grouped);
+        assert_eq!(table.rows[0].values, [Some(2.0), None]);
+    }
+
+    #[test]
+    fn test_diff_results() {
+        use datafusion::arrow::array::StringArray;
+        use serde_json::json;
+        let result = |columns: &[&str], levels: Vec<&str>, counts: Vec<i64>| {
+            let schema = Arc::new(Schema::new(vec![
+                Field::new(columns[0], DataType::Utf8, false),
+                Field::new(columns[1], DataType::Int64, false),
+            ]));
+            let batch = RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(StringArray::from(levels)),
+                    Arc::new(Int64Array::from(counts)),
+                ],
+            )
+            .unwrap();
+            CachedResult {
+                schema,
+                batches: vec![batch],
+            }
+        };
+        let before = result(
+            &["level", "n"],
+            vec!["ERROR", "INFO", "DEBUG"],
+            vec![2, 40, 7],
+        );
+        let after = result(
+            &["level", "n"],
+            vec!["INFO", "ERROR", "WARN"],
+            vec![40, 5, 1],
+        );
+        let keys = vec!["level".to_string()];
+        let changes = diff(&keys, &before, &after).unwrap();
+        assert_eq!(changes.compared, ["n"]);
+        assert_eq!(changes.added, [vec![json!("WARN"), json!(1)]]);
+        assert_eq!(changes.removed, [vec![json!("DEBUG"), json!(7)]]);
+        assert_eq!(
+            changes.changed,
+            [ChangedRow {
+                before: vec![json!("ERROR"), json!(2)],
+                after: vec![json!("ERROR"), json!(5)],
+                columns: vec!["n".to_string()],
+            }]
+        );
+        assert_eq!(changes.unchanged, 1);
+        assert_eq!(changes.omitted_rows, 0);
+
+        // Columns in only one result are left out; keys must be in both
+        let renamed = result(&["level", "count"], vec!["INFO"], vec![40]);
+        let partial = diff(&keys, &before, &renamed).unwrap();
+        assert_eq!(partial.ignored, ["n", "count"]);
+        assert_eq!((partial.unchanged, partial.removed.len()), (1, 2));
+        let missing = diff(&["n".to_string()], &before, &renamed);
+        assert!(matches!(missing, Err(PinError::MissingKey(key)) if key == "n"));
+        assert!(matches!(diff(&[], &before, &after), Err(PinError::NoKeys)));
+    }
+}
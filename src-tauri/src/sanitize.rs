@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fmt::Write;
+
+/// Widest tab stop honoured when expanding tabs, so a hostile setting can't blow lines up
+pub const MAX_TAB_WIDTH: usize = 16;
+
+/// What to do with invisible formatting characters: bidi controls and zero-width characters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InvisibleChars {
+    Keep,
+    Remove,
+    /// Show them as `<U+202E>` so reordered or hidden text is visible
+    #[default]
+    Escape,
+}
+
+/// How lines are cleaned up before they are shown
+/// Only the display is affected; search, SQL and exports see the file as it is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SanitizeOptions {
+    /// Show control characters such as ESC as `\x1B`
+    pub escape_controls: bool,
+    pub invisible: InvisibleChars,
+    /// Expand tabs to spaces at this stop width, capped at `MAX_TAB_WIDTH`; None keeps tabs
+    pub tab_width: Option<usize>,
+}
+
+impl Default for SanitizeOptions {
+    fn default() -> Self {
+        SanitizeOptions {
+            escape_controls: true,
+            invisible: InvisibleChars::Escape,
+            tab_width: None,
+        }
+    }
+}
+
+/// Bidi embeddings, overrides, isolates and marks, and zero-width characters
+fn is_invisible(c: char) -> bool {
+    matches!(
+        c,
+        '\u{061C}'
+            | '\u{200B}'..='\u{200F}'
+            | '\u{202A}'..='\u{202E}'
+            | '\u{2060}'..='\u{2064}'
+            | '\u{2066}'..='\u{2069}'
+            | '\u{FEFF}'
+    )
+}
+
+impl SanitizeOptions {
+    fn affects(&self, c: char) -> bool {
+        match c {
+            '\t' => self.tab_width.is_some(),
+            c if c.is_control() => self.escape_controls,
+            c => self.invisible != InvisibleChars::Keep && is_invisible(c),
+        }
+    }
+
+    /// Clean up one line for display, borrowing it when nothing needs changing
+    pub fn apply<'a>(&self, line: &'a str) -> Cow<'a, str> {
+        let Some(first) = line.find(|c| self.affects(c)) else {
+            return Cow::Borrowed(line);
+        };
+
+        let mut out = String::with_capacity(line.len() + 16);
+        out.push_str(&line[..first]);
+        // Column within the line, for tab stops
+        let mut column = line[..first].chars().count();
+        for c in line[first..].chars() {
+            if let (Some(width), '\t') = (self.tab_width, c) {
+                let width = width.clamp(1, MAX_TAB_WIDTH);
+                let spaces = width - column % width;
+                out.extend(std::iter::repeat_n(' ', spaces));
+                column += spaces;
+                continue;
+            }
+            let start = out.len();
+            match c {
+                c if c.is_control() && c != '\t' && self.escape_controls => {
+                    write!(out, "\\x{:02X}", c as u32).ok();
+                }
+                c if is_invisible(c) => match self.invisible {
+                    InvisibleChars::Keep => out.push(c),
+                    InvisibleChars::Remove => {}
+                    InvisibleChars::Escape => {
+                        write!(out, "<U+{:04X}>", c as u32).ok();
+                    }
+                },
+                c => out.push(c),
+            }
+            column += out[start..].chars().count();
+        }
+        Cow::Owned(out)
+    }
+
+    /// Clean up lines in place
+    pub fn apply_all(&self, lines: &mut [String]) {
+        for line in lines {
+            if let Cow::Owned(clean) = self.apply(line) {
+                *line = clean;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_line() {
+        let options = SanitizeOptions::default();
+        assert!(matches!(options.apply("plain\ttext"), Cow::Borrowed(_)));
+        assert_eq!(
+            options.apply("\u{1b}[31mred\u{1b}[0m\u{7}"),
+            "\\x1B[31mred\\x1B[0m\\x07"
+        );
+        assert_eq!(
+            options.apply("user\u{202E}gnp.exe\u{200B}"),
+            "user<U+202E>gnp.exe<U+200B>"
+        );
+
+        let options = SanitizeOptions {
+            invisible: InvisibleChars::Remove,
+            tab_width: Some(100),
+            ..Default::default()
+        };
+        assert_eq!(
+            options.apply("a\u{FEFF}b\tc"),
+            format!("ab{}c", " ".repeat(14))
+        );
+        let options = SanitizeOptions {
+            tab_width: Some(4),
+            ..Default::default()
+        };
+        assert_eq!(options.apply("\u{1}\tx"), "\\x01    x");
+    }
+}
@@ -0,0 +1,82 @@
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Longest a search may be asked to wait for newer input before scanning
+pub const MAX_SEARCH_DEBOUNCE_MS: u64 = 1_000;
+
+/// Latest search of each session, so starting a search stops the one it replaces
+/// A session is one search box, e.g. the main viewer's or the compare view's
+#[derive(Default)]
+pub struct SearchCoordinator {
+    sessions: Mutex<HashMap<String, Arc<AtomicU64>>>,
+}
+
+/// A search in flight, superseded once a newer search starts in its session
+pub struct SearchTicket {
+    generation: u64,
+    latest: Arc<AtomicU64>,
+}
+
+impl SearchCoordinator {
+    /// Start a search, superseding any search still running in the session
+    pub fn begin(&self, session: &str) -> SearchTicket {
+        let latest = self
+            .sessions
+            .lock()
+            .entry(session.to_string())
+            .or_default()
+            .clone();
+        let generation = latest.fetch_add(1, Ordering::AcqRel) + 1;
+        SearchTicket { generation, latest }
+    }
+
+    /// Stop the search running in a session, e.g. when its search box is cleared
+    pub fn cancel(&self, session: &str) {
+        if let Some(latest) = self.sessions.lock().get(session) {
+            latest.fetch_add(1, Ordering::AcqRel);
+        }
+    }
+}
+
+impl SearchTicket {
+    pub fn is_superseded(&self) -> bool {
+        self.latest.load(Ordering::Acquire) != self.generation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexer::{IndexerError, LogFile};
+    use crate::regex_cache::{Matcher, RegexFlavor};
+
+    #[test]
+    fn test_newer_search_supersedes() {
+        let searches = SearchCoordinator::default();
+        let first = searches.begin("main");
+        let compare = searches.begin("compare");
+        assert!(!first.is_superseded());
+
+        let second = searches.begin("main");
+        assert!(first.is_superseded());
+        assert!(!second.is_superseded());
+        assert!(!compare.is_superseded());
+
+        let data = "ERROR a\nINFO b\n".repeat(50_000).into_bytes();
+        let file = LogFile::from_bytes("app.log", data);
+        let error = Matcher::new("ERROR", RegexFlavor::Standard).unwrap();
+        assert!(matches!(
+            file.search_until(&error, usize::MAX, || first.is_superseded(), |_, _| {}),
+            Err(IndexerError::Cancelled)
+        ));
+        let matches = file
+            .search_until(&error, usize::MAX, || second.is_superseded(), |_, _| {})
+            .unwrap();
+        assert_eq!(matches.len(), 50_000);
+
+        searches.cancel("main");
+        assert!(second.is_superseded());
+    }
+}
@@ -0,0 +1,441 @@
+use crate::field_search::quote_identifier;
+use crate::parsers::{Column, ColumnType};
+use serde::Serialize;
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Errors that can occur rendering a snippet
+#[derive(Debug, Error, PartialEq)]
+pub enum SnippetError {
+    #[error("No snippet named \"{0}\"")]
+    UnknownSnippet(String),
+    #[error("This table has no {0} column for the snippet")]
+    MissingColumns(String),
+    #[error("Parameter \"{name}\" must be {expected}")]
+    InvalidParam {
+        name: &'static str,
+        expected: String,
+    },
+}
+
+/// What a column holds, so one snippet fits every format that has such a column
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColumnRole {
+    Timestamp,
+    Level,
+    ClientIp,
+    Duration,
+    Status,
+    Path,
+}
+
+impl ColumnRole {
+    /// Name the role takes in snippet templates
+    fn placeholder(self) -> &'static str {
+        match self {
+            ColumnRole::Timestamp => "timestamp",
+            ColumnRole::Level => "level",
+            ColumnRole::ClientIp => "client_ip",
+            ColumnRole::Duration => "duration",
+            ColumnRole::Status => "status",
+            ColumnRole::Path => "path",
+        }
+    }
+
+    /// Column names that hold the role across the built-in parsers and common formats,
+    /// most specific first
+    fn names(self) -> &'static [&'static str] {
+        match self {
+            ColumnRole::Timestamp => &["timestamp", "@timestamp", "time", "ts", "datetime", "date"],
+            ColumnRole::Level => &["level", "severity", "log_level", "loglevel", "lvl", "sev"],
+            ColumnRole::ClientIp => &[
+                "client_ip",
+                "c_ip",
+                "remote_addr",
+                "remote_ip",
+                "src_ip",
+                "src",
+                "ip",
+                "client",
+                "x_forwarded_for",
+            ],
+            ColumnRole::Duration => &[
+                "duration",
+                "duration_ms",
+                "time_taken",
+                "elapsed",
+                "latency",
+                "response_time",
+                "query_time",
+                "upstream_service_time",
+                "tt",
+            ],
+            ColumnRole::Status => &[
+                "status",
+                "sc_status",
+                "status_code",
+                "response_code",
+                "http_status",
+            ],
+            ColumnRole::Path => &[
+                "path",
+                "cs_uri_stem",
+                "request_path",
+                "request_uri",
+                "uri",
+                "url",
+            ],
+        }
+    }
+
+    fn accepts(self, column_type: ColumnType) -> bool {
+        match self {
+            ColumnRole::Timestamp => column_type == ColumnType::Timestamp,
+            ColumnRole::Duration => {
+                matches!(column_type, ColumnType::Int64 | ColumnType::Float64)
+            }
+            ColumnRole::Status => column_type == ColumnType::Int64,
+            ColumnRole::Level | ColumnRole::ClientIp | ColumnRole::Path => {
+                column_type == ColumnType::Utf8
+            }
+        }
+    }
+
+    /// Column of a table holding the role; any timestamp column will do for `Timestamp`
+    pub fn find(self, columns: &[Column]) -> Option<&Column> {
+        let fits = |c: &&Column| self.accepts(c.column_type);
+        self.names()
+            .iter()
+            .find_map(|name| {
+                columns
+                    .iter()
+                    .filter(fits)
+                    .find(|c| c.name.eq_ignore_ascii_case(name))
+            })
+            .or_else(|| match self {
+                ColumnRole::Timestamp => columns.iter().find(fits),
+                _ => None,
+            })
+    }
+}
+
+/// A value a snippet is rendered with
+#[derive(Debug, Clone, Serialize)]
+pub struct SnippetParam {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub default: &'static str,
+    /// Values allowed, or empty for a positive whole number
+    pub choices: &'static [&'static str],
+}
+
+const LIMIT: SnippetParam = SnippetParam {
+    name: "limit",
+    description: "Rows to show",
+    default: "10",
+    choices: &[],
+};
+
+const INTERVAL: SnippetParam = SnippetParam {
+    name: "interval",
+    description: "Width of each time bucket",
+    default: "minute",
+    choices: &["second", "minute", "hour", "day"],
+};
+
+/// SQL for the columns a format has; `{table}`, role and parameter names in braces are
+/// filled in when rendered
+struct Variant {
+    roles: &'static [ColumnRole],
+    sql: &'static str,
+}
+
+/// A ready-made query that adapts to the columns of the parsed table
+struct Snippet {
+    id: &'static str,
+    title: &'static str,
+    description: &'static str,
+    params: &'static [SnippetParam],
+    /// Tried in order; the first whose roles the table has is used
+    variants: &'static [Variant],
+}
+
+const SNIPPETS: &[Snippet] = &[
+    Snippet {
+        id: "errors_over_time",
+        title: "Errors per interval",
+        description: "Error entries per time bucket, by level or by 5xx status",
+        params: &[INTERVAL],
+        variants: &[
+            Variant {
+                roles: &[ColumnRole::Timestamp, ColumnRole::Level],
+                sql: "SELECT date_trunc('{interval}', {timestamp}) AS bucket, COUNT(*) AS errors \
+                      FROM {table} WHERE {level} ILIKE 'err%' OR {level} ILIKE 'fatal%' \
+                      OR {level} ILIKE 'crit%' GROUP BY bucket ORDER BY bucket",
+            },
+            Variant {
+                roles: &[ColumnRole::Timestamp, ColumnRole::Status],
+                sql: "SELECT date_trunc('{interval}', {timestamp}) AS bucket, COUNT(*) AS errors \
+                      FROM {table} WHERE {status} >= 500 GROUP BY bucket ORDER BY bucket",
+            },
+        ],
+    },
+    Snippet {
+        id: "entries_over_time",
+        title: "Entries per interval",
+        description: "Entries per time bucket",
+        params: &[INTERVAL],
+        variants: &[Variant {
+            roles: &[ColumnRole::Timestamp],
+            sql: "SELECT date_trunc('{interval}', {timestamp}) AS bucket, COUNT(*) AS entries \
+                  FROM {table} GROUP BY bucket ORDER BY bucket",
+        }],
+    },
+    Snippet {
+        id: "level_breakdown",
+        title: "Entries by level",
+        description: "How many entries each level has",
+        params: &[],
+        variants: &[Variant {
+            roles: &[ColumnRole::Level],
+            sql: "SELECT {level}, COUNT(*) AS entries FROM {table} GROUP BY {level} \
+                  ORDER BY entries DESC",
+        }],
+    },
+    Snippet {
+        id: "top_clients",
+        title: "Top client IPs",
+        description: "Clients with the most requests",
+        params: &[LIMIT],
+        variants: &[Variant {
+            roles: &[ColumnRole::ClientIp],
+            sql: "SELECT {client_ip}, COUNT(*) AS requests FROM {table} \
+                  GROUP BY {client_ip} ORDER BY requests DESC LIMIT {limit}",
+        }],
+    },
+    Snippet {
+        id: "slowest_requests",
+        title: "Slowest requests",
+        description: "Entries with the longest duration",
+        params: &[LIMIT],
+        variants: &[
+            Variant {
+                roles: &[ColumnRole::Duration, ColumnRole::Path],
+                sql: "SELECT line_number, {path}, {duration} FROM {table} \
+                      WHERE {duration} IS NOT NULL ORDER BY {duration} DESC LIMIT {limit}",
+            },
+            Variant {
+                roles: &[ColumnRole::Duration],
+                sql: "SELECT * FROM {table} WHERE {duration} IS NOT NULL \
+                      ORDER BY {duration} DESC LIMIT {limit}",
+            },
+        ],
+    },
+    Snippet {
+        id: "status_codes",
+        title: "Status codes",
+        description: "How many responses each status code has",
+        params: &[],
+        variants: &[Variant {
+            roles: &[ColumnRole::Status],
+            sql: "SELECT {status}, COUNT(*) AS responses FROM {table} GROUP BY {status} \
+                  ORDER BY responses DESC",
+        }],
+    },
+    Snippet {
+        id: "top_paths",
+        title: "Top paths",
+        description: "Most requested paths, with their server errors",
+        params: &[LIMIT],
+        variants: &[
+            Variant {
+                roles: &[ColumnRole::Path, ColumnRole::Status],
+                sql: "SELECT {path}, COUNT(*) AS requests, \
+                      SUM(CASE WHEN {status} >= 500 THEN 1 ELSE 0 END) AS server_errors \
+                      FROM {table} GROUP BY {path} ORDER BY requests DESC LIMIT {limit}",
+            },
+            Variant {
+                roles: &[ColumnRole::Path],
+                sql: "SELECT {path}, COUNT(*) AS requests FROM {table} GROUP BY {path} \
+                      ORDER BY requests DESC LIMIT {limit}",
+            },
+        ],
+    },
+];
+
+/// A snippet as listed for a table
+#[derive(Debug, Clone, Serialize)]
+pub struct SnippetInfo {
+    pub id: &'static str,
+    pub title: &'static str,
+    pub description: &'static str,
+    pub params: &'static [SnippetParam],
+    /// Whether the table has the columns for some variant of the snippet
+    pub available: bool,
+    /// Roles the table lacks for the simplest variant, when unavailable
+    pub missing: Vec<ColumnRole>,
+}
+
+/// Every snippet, with whether it fits a table with the given columns
+pub fn list_snippets(columns: &[Column]) -> Vec<SnippetInfo> {
+    SNIPPETS
+        .iter()
+        .map(|snippet| {
+            let missing: Vec<ColumnRole> = snippet
+                .variants
+                .last()
+                .map(|variant| {
+                    variant
+                        .roles
+                        .iter()
+                        .copied()
+                        .filter(|role| role.find(columns).is_none())
+                        .collect()
+                })
+                .unwrap_or_default();
+            SnippetInfo {
+                id: snippet.id,
+                title: snippet.title,
+                description: snippet.description,
+                params: snippet.params,
+                available: missing.is_empty(),
+                missing,
+            }
+        })
+        .collect()
+}
+
+/// SQL of a snippet for `table`, using the first variant its columns fit
+/// Parameters not given take their default
+pub fn render_snippet(
+    id: &str,
+    params: &HashMap<String, String>,
+    table: &str,
+    columns: &[Column],
+) -> Result<String, SnippetError> {
+    let snippet = SNIPPETS
+        .iter()
+        .find(|snippet| snippet.id == id)
+        .ok_or_else(|| SnippetError::UnknownSnippet(id.to_string()))?;
+    let (variant, roles) = snippet
+        .variants
+        .iter()
+        .find_map(|variant| {
+            let roles: Option<Vec<(ColumnRole, &Column)>> = variant
+                .roles
+                .iter()
+                .map(|&role| Some((role, role.find(columns)?)))
+                .collect();
+            Some((variant, roles?))
+        })
+        .ok_or_else(|| {
+            let missing: Vec<&str> = list_snippets(columns)
+                .into_iter()
+                .find(|info| info.id == snippet.id)
+                .map(|info| info.missing.iter().map(|r| r.placeholder()).collect())
+                .unwrap_or_default();
+            SnippetError::MissingColumns(missing.join(" or "))
+        })?;
+
+    let mut values: HashMap<&str, String> = HashMap::new();
+    values.insert("table", quote_identifier(table));
+    for (role, column) in roles {
+        values.insert(role.placeholder(), quote_identifier(&column.name));
+    }
+    for param in snippet.params {
+        let value = params.get(param.name).map_or(param.default, String::as_str);
+        let valid = if param.choices.is_empty() {
+            value.parse::<u64>().is_ok_and(|n| n > 0)
+        } else {
+            param.choices.contains(&value)
+        };
+        if !valid {
+            return Err(SnippetError::InvalidParam {
+                name: param.name,
+                expected: if param.choices.is_empty() {
+                    "a positive whole number".to_string()
+                } else {
+                    format!("one of {}", param.choices.join(", "))
+                },
+            });
+        }
+        values.insert(param.name, value.to_string());
+    }
+
+    let mut sql = variant.sql.to_string();
+    for (name, value) in &values {
+        sql = sql.replace(&format!("{{{}}}", name), value);
+    }
+    Ok(sql)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn columns(spec: &[(&str, ColumnType)]) -> Vec<Column> {
+        spec.iter()
+            .map(|&(name, column_type)| Column {
+                name: name.to_string(),
+                column_type,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_snippets_adapt_to_columns() {
+        let haproxy = columns(&[
+            ("line_number", ColumnType::Int64),
+            ("timestamp", ColumnType::Timestamp),
+            ("client_ip", ColumnType::Utf8),
+            ("status", ColumnType::Int64),
+            ("tt", ColumnType::Int64),
+            ("path", ColumnType::Utf8),
+        ]);
+        let sql = render_snippet("errors_over_time", &HashMap::new(), "parsed", &haproxy).unwrap();
+        assert_eq!(
+            sql,
+            "SELECT date_trunc('minute', \"timestamp\") AS bucket, COUNT(*) AS errors \
+             FROM \"parsed\" WHERE \"status\" >= 500 GROUP BY bucket ORDER BY bucket"
+        );
+        let params = HashMap::from([("limit".to_string(), "5".to_string())]);
+        assert!(
+            render_snippet("slowest_requests", &params, "parsed", &haproxy)
+                .unwrap()
+                .ends_with("ORDER BY \"tt\" DESC LIMIT 5")
+        );
+
+        let json = columns(&[
+            ("line_number", ColumnType::Int64),
+            ("Level", ColumnType::Utf8),
+            ("ts", ColumnType::Timestamp),
+        ]);
+        let info = list_snippets(&json);
+        let top_clients = info.iter().find(|i| i.id == "top_clients").unwrap();
+        assert!(!top_clients.available);
+        assert_eq!(top_clients.missing, [ColumnRole::ClientIp]);
+        assert_eq!(
+            render_snippet("top_clients", &HashMap::new(), "parsed", &json),
+            Err(SnippetError::MissingColumns("client_ip".to_string()))
+        );
+        let params = HashMap::from([("interval".to_string(), "hour".to_string())]);
+        assert!(render_snippet("errors_over_time", &params, "parsed", &json)
+            .unwrap()
+            .contains("date_trunc('hour', \"ts\")"));
+    }
+
+    #[test]
+    fn test_snippet_params_are_checked() {
+        let table = columns(&[("client_ip", ColumnType::Utf8)]);
+        let params = HashMap::from([("limit".to_string(), "5; DROP".to_string())]);
+        assert!(matches!(
+            render_snippet("top_clients", &params, "parsed", &table),
+            Err(SnippetError::InvalidParam { name: "limit", .. })
+        ));
+        assert_eq!(
+            render_snippet("nope", &HashMap::new(), "parsed", &table),
+            Err(SnippetError::UnknownSnippet("nope".to_string()))
+        );
+    }
+}
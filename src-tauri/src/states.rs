@@ -0,0 +1,346 @@
+use crate::indexer::LogFile;
+use crate::metrics::{Extractor, MetricError};
+use crate::regex_cache;
+use crate::timeline::TimeRange;
+use crate::timestamp::parse_timestamp_bytes;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use thiserror::Error;
+
+/// Errors that can occur setting up a state machine
+#[derive(Debug, Error)]
+pub enum StateError {
+    #[error("A state machine needs at least one state")]
+    NoStates,
+    #[error("No state named \"{0}\"")]
+    UnknownState(String),
+    #[error("Invalid pattern for state \"{state}\": {source}")]
+    Pattern { state: String, source: regex::Error },
+    #[error(transparent)]
+    Metric(#[from] MetricError),
+}
+
+/// A state an entity enters on a line matching the pattern
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StateRule {
+    pub name: String,
+    pub pattern: String,
+}
+
+/// A transition the entities may make
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Transition {
+    pub from: String,
+    pub to: String,
+}
+
+fn default_max_entities() -> usize {
+    500
+}
+
+/// An entity's states and the rules its timeline is checked against
+#[derive(Debug, Clone, Deserialize)]
+pub struct StateMachineOptions {
+    /// Where the entity a line is about is, such as an order or job id
+    pub key: Extractor,
+    /// Checked in order; a line enters the first state it matches
+    pub states: Vec<StateRule>,
+    /// Transitions allowed; empty allows any
+    #[serde(default)]
+    pub transitions: Vec<Transition>,
+    /// States an entity may start in; empty allows any
+    #[serde(default)]
+    pub initial: Vec<String>,
+    /// States an entity is done in, never counted as stuck
+    #[serde(default)]
+    pub terminal: Vec<String>,
+    /// Seconds in a state other than a terminal one after which an entity counts as stuck
+    #[serde(default)]
+    pub stuck_after_secs: Option<u64>,
+    #[serde(default)]
+    pub range: Option<TimeRange>,
+    /// Entity timelines returned, in order of first appearance
+    #[serde(default = "default_max_entities")]
+    pub max_entities: usize,
+}
+
+/// A stay of an entity in a state
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StateVisit {
+    pub state: String,
+    /// 0-based index of the line entering it
+    pub line: u64,
+    pub time: Option<i64>,
+    /// Until the next state, or until the last timestamp in the file for the current one
+    pub duration_ms: Option<i64>,
+}
+
+/// One entity's states in order
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EntityTimeline {
+    pub key: String,
+    pub visits: Vec<StateVisit>,
+}
+
+/// A transition the rules don't allow, or a start in a state that isn't initial
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InvalidTransition {
+    pub key: String,
+    /// None when the entity started in a state that isn't initial
+    pub from: Option<String>,
+    pub to: String,
+    pub line: u64,
+    pub time: Option<i64>,
+}
+
+/// An entity that stayed in a state longer than the threshold
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StuckEntity {
+    pub key: String,
+    pub state: String,
+    /// 0-based index of the line entering the state
+    pub line: u64,
+    pub duration_ms: i64,
+    /// Still in the state at the end of the file
+    pub ongoing: bool,
+}
+
+/// Entity timelines with the problems found in them
+#[derive(Debug, Clone, Serialize)]
+pub struct StateMachineReport {
+    pub entity_count: usize,
+    pub entities: Vec<EntityTimeline>,
+    /// In file order
+    pub invalid: Vec<InvalidTransition>,
+    /// Longest first
+    pub stuck: Vec<StuckEntity>,
+}
+
+/// Rebuild each entity's timeline of states and flag transitions the rules don't allow and
+/// stays that went on too long, with the lines that show them
+pub fn analyse_states(
+    file: &LogFile,
+    options: &StateMachineOptions,
+) -> Result<StateMachineReport, StateError> {
+    if options.states.is_empty() {
+        return Err(StateError::NoStates);
+    }
+    if let Some(range) = &options.range {
+        range.validate().map_err(MetricError::from)?;
+    }
+    let names: HashSet<&str> = options.states.iter().map(|s| s.name.as_str()).collect();
+    let mut referenced = options
+        .transitions
+        .iter()
+        .flat_map(|t| [&t.from, &t.to])
+        .chain(&options.initial)
+        .chain(&options.terminal);
+    if let Some(unknown) = referenced.find(|n| !names.contains(n.as_str())) {
+        return Err(StateError::UnknownState(unknown.clone()));
+    }
+    let patterns = options
+        .states
+        .iter()
+        .map(|state| {
+            regex_cache::regex(&state.pattern).map_err(|source| StateError::Pattern {
+                state: state.name.clone(),
+                source,
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let key = options.key.compile()?;
+    let allowed = |from: &str, to: &str| {
+        options.transitions.is_empty()
+            || options
+                .transitions
+                .iter()
+                .any(|t| t.from == from && t.to == to)
+    };
+
+    // Entities in order of first appearance, and their visits
+    let mut order: Vec<String> = Vec::new();
+    let mut timelines: HashMap<String, Vec<StateVisit>> = HashMap::new();
+    let mut invalid = Vec::new();
+    let mut latest: Option<i64> = None;
+    for line in 0..file.line_count() {
+        let Some(bytes) = file.line_bytes(line) else {
+            continue;
+        };
+        let time = parse_timestamp_bytes(bytes);
+        if let Some(range) = options.range {
+            if !time.is_some_and(|time| range.contains(time)) {
+                continue;
+            }
+        }
+        latest = latest.max(time);
+        let text = String::from_utf8_lossy(bytes);
+        let Some(state) = patterns.iter().position(|p| p.is_match(&text)) else {
+            continue;
+        };
+        let Some(id) = key.extract(&text) else {
+            continue;
+        };
+        let state = &options.states[state].name;
+        let visits = timelines.entry(id.clone()).or_insert_with(|| {
+            order.push(id.clone());
+            Vec::new()
+        });
+        let from = visits.last().map(|v| &v.state);
+        if from == Some(state) {
+            continue;
+        }
+        let valid = match from {
+            Some(from) => allowed(from, state),
+            None => options.initial.is_empty() || options.initial.contains(state),
+        };
+        if !valid {
+            invalid.push(InvalidTransition {
+                key: id,
+                from: from.cloned(),
+                to: state.clone(),
+                line,
+                time,
+            });
+        }
+        visits.push(StateVisit {
+            state: state.clone(),
+            line,
+            time,
+            duration_ms: None,
+        });
+    }
+
+    let mut stuck = Vec::new();
+    for (id, visits) in &mut timelines {
+        let ends: Vec<Option<i64>> = visits
+            .iter()
+            .skip(1)
+            .map(|v| v.time)
+            .chain([latest])
+            .collect();
+        let last = visits.len() - 1;
+        for (index, (visit, end)) in visits.iter_mut().zip(ends).enumerate() {
+            let (Some(start), Some(end)) = (visit.time, end) else {
+                continue;
+            };
+            let duration = end - start;
+            visit.duration_ms = Some(duration);
+            let too_long = options
+                .stuck_after_secs
+                .is_some_and(|secs| duration > secs as i64 * 1000);
+            if too_long && !options.terminal.contains(&visit.state) {
+                stuck.push(StuckEntity {
+                    key: id.clone(),
+                    state: visit.state.clone(),
+                    line: visit.line,
+                    duration_ms: duration,
+                    ongoing: index == last,
+                });
+            }
+        }
+    }
+    stuck.sort_by_key(|s| (std::cmp::Reverse(s.duration_ms), s.line));
+
+    let entity_count = order.len();
+    let entities = order
+        .into_iter()
+        .take(options.max_entities)
+        .filter_map(|key| {
+            let visits = timelines.remove(&key)?;
+            Some(EntityTimeline { key, visits })
+        })
+        .collect();
+    Ok(StateMachineReport {
+        entity_count,
+        entities,
+        invalid,
+        stuck,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexer::open_test_file;
+
+    #[test]
+    fn test_analyse_states() {
+        let (_file, log_file) = open_test_file(
+            "2024-05-01T10:00:00Z order=1 created\n\
+             2024-05-01T10:00:10Z order=2 created\n\
+             2024-05-01T10:00:20Z order=1 paid\n\
+             2024-05-01T10:00:30Z order=1 shipped\n\
+             2024-05-01T10:01:00Z order=3 shipped\n\
+             2024-05-01T10:20:00Z order=1 shipped again\n\
+             2024-05-01T10:30:00Z heartbeat\n",
+        );
+
+        let rule = |name: &str, pattern: &str| StateRule {
+            name: name.to_string(),
+            pattern: pattern.to_string(),
+        };
+        let transition = |from: &str, to: &str| Transition {
+            from: from.to_string(),
+            to: to.to_string(),
+        };
+        let options = StateMachineOptions {
+            key: Extractor::Regex {
+                pattern: r"order=(\d+)".to_string(),
+            },
+            states: vec![
+                rule("created", "created"),
+                rule("paid", "paid"),
+                rule("shipped", "shipped"),
+            ],
+            transitions: vec![transition("created", "paid"), transition("paid", "shipped")],
+            initial: vec!["created".to_string()],
+            terminal: vec!["shipped".to_string()],
+            stuck_after_secs: Some(600),
+            range: None,
+            max_entities: 10,
+        };
+        let report = analyse_states(&log_file, &options).unwrap();
+        assert_eq!(report.entity_count, 3);
+        let states: Vec<_> = report.entities[0]
+            .visits
+            .iter()
+            .map(|v| (v.state.as_str(), v.duration_ms))
+            .collect();
+        assert_eq!(
+            states,
+            [
+                ("created", Some(20_000)),
+                ("paid", Some(10_000)),
+                ("shipped", Some(1_770_000))
+            ]
+        );
+
+        // Order 3 shipped without being created
+        assert_eq!(report.invalid.len(), 1);
+        assert_eq!(
+            (report.invalid[0].key.as_str(), report.invalid[0].line),
+            ("3", 4)
+        );
+
+        // Order 2 has waited for payment since 10:00:10
+        assert_eq!(
+            report.stuck,
+            [StuckEntity {
+                key: "2".to_string(),
+                state: "created".to_string(),
+                line: 1,
+                duration_ms: 1_790_000,
+                ongoing: true,
+            }]
+        );
+
+        let bad = StateMachineOptions {
+            terminal: vec!["delivered".to_string()],
+            ..options
+        };
+        assert!(matches!(
+            analyse_states(&log_file, &bad),
+            Err(StateError::UnknownState(_))
+        ));
+    }
+}
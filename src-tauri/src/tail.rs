@@ -0,0 +1,642 @@
+use crate::indexer::{Appended, IndexerError, LogFile};
+use crate::regex_cache;
+use notify::{RecursiveMode, Watcher};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// How the follower detects that the file has grown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FollowMode {
+    /// Use filesystem notifications, falling back to polling if they turn out not to fire
+    #[default]
+    Auto,
+    /// Filesystem notifications only; polling is used solely if no watcher can be created
+    Watch,
+    /// Periodically stat the file
+    Poll,
+}
+
+/// Options for following a growing file
+#[derive(Debug, Clone, Deserialize)]
+pub struct FollowOptions {
+    #[serde(default)]
+    pub mode: FollowMode,
+    /// Polling interval used right after new data arrived
+    #[serde(default = "default_min_interval_ms")]
+    pub min_interval_ms: u64,
+    /// Polling interval the follower backs off to while the file is idle
+    #[serde(default = "default_max_interval_ms")]
+    pub max_interval_ms: u64,
+    /// Minimum time between `new-lines` events; appends in between are coalesced
+    #[serde(default = "default_batch_interval_ms")]
+    pub batch_interval_ms: u64,
+}
+
+fn default_min_interval_ms() -> u64 {
+    100
+}
+
+fn default_max_interval_ms() -> u64 {
+    2000
+}
+
+fn default_batch_interval_ms() -> u64 {
+    250
+}
+
+impl Default for FollowOptions {
+    fn default() -> Self {
+        FollowOptions {
+            mode: FollowMode::Auto,
+            min_interval_ms: default_min_interval_ms(),
+            max_interval_ms: default_max_interval_ms(),
+            batch_interval_ms: default_batch_interval_ms(),
+        }
+    }
+}
+
+/// Events produced while following a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FollowEvent {
+    /// Lines in the range were appended or extended (coalesced across a batch interval)
+    Appended(Range<u64>),
+    /// The file was rotated; the given line is the marker separating old and new content
+    Rotated(u64),
+    /// The follower switched detection strategy (e.g. watcher fell back to polling)
+    ModeChanged(FollowMode),
+    /// Retention dropped this many lines from the front; remaining lines were renumbered
+    Evicted(u64),
+    /// Refreshing the file failed; the follower keeps running
+    Error(String),
+}
+
+/// Polling interval that tightens while data flows and backs off exponentially when idle
+#[derive(Debug, Clone)]
+pub struct AdaptiveInterval {
+    current: Duration,
+    min: Duration,
+    max: Duration,
+}
+
+impl AdaptiveInterval {
+    pub fn new(min: Duration, max: Duration) -> Self {
+        let max = max.max(min);
+        AdaptiveInterval {
+            current: min,
+            min,
+            max,
+        }
+    }
+
+    pub fn current(&self) -> Duration {
+        self.current
+    }
+
+    /// New data arrived: poll again quickly
+    pub fn on_activity(&mut self) {
+        self.current = self.min;
+    }
+
+    /// Nothing changed: double the wait up to the maximum
+    pub fn on_idle(&mut self) {
+        self.current = std::cmp::min(self.current * 2, self.max);
+    }
+}
+
+/// A search attached to follow mode so only matching new lines are surfaced
+#[derive(Debug, Clone)]
+pub struct FollowFilter {
+    regex: Arc<Regex>,
+    suppressed: u64,
+}
+
+/// Appended lines after filtering
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FilteredAppend {
+    pub matches: Vec<u64>,
+    /// Total lines suppressed since the filter was attached
+    pub suppressed: u64,
+}
+
+impl FollowFilter {
+    pub fn new(pattern: &str) -> Result<Self, regex::Error> {
+        Ok(FollowFilter {
+            regex: regex_cache::regex(pattern)?,
+            suppressed: 0,
+        })
+    }
+
+    pub fn pattern(&self) -> &str {
+        self.regex.as_str()
+    }
+
+    /// Filter an appended range, counting lines that did not match
+    /// `already_seen` is the number of leading lines in the range that were reported before
+    /// (an extended last line), which are not counted as suppressed twice
+    pub fn apply(&mut self, file: &LogFile, range: Range<u64>, already_seen: u64) -> FilteredAppend {
+        let matches = file.matching_lines(&self.regex, range.clone());
+        let fresh = (range.end - range.start).saturating_sub(already_seen);
+        let fresh_matches = matches
+            .iter()
+            .filter(|&&line| line >= range.start + already_seen)
+            .count() as u64;
+        self.suppressed += fresh - fresh_matches;
+        FilteredAppend {
+            matches,
+            suppressed: self.suppressed,
+        }
+    }
+}
+
+/// Lines surfaced to the frontend after an append or when resuming
+/// Carries only the affected range; the frontend fetches the lines it actually displays
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LineUpdate {
+    pub start: u64,
+    pub count: u64,
+    pub total_lines: u64,
+    /// Matching lines in the range when a follow filter is attached
+    pub matches: Option<Vec<u64>>,
+    /// Lines suppressed by the follow filter since it was attached
+    pub suppressed: u64,
+}
+
+/// View-side state of a follow session: attached filter, pause marker, and what was surfaced
+#[derive(Debug, Default)]
+pub struct FollowSession {
+    filter: Option<FollowFilter>,
+    /// End of the line range already surfaced to the frontend
+    reported_end: u64,
+    /// Line count at the moment the view was paused
+    paused_from: Option<u64>,
+}
+
+impl FollowSession {
+    /// Start surfacing appends after `line_count`, keeping any attached filter
+    pub fn restart(&mut self, line_count: u64) {
+        self.reported_end = line_count;
+        self.paused_from = None;
+    }
+
+    pub fn set_filter(&mut self, filter: Option<FollowFilter>) {
+        self.filter = filter;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused_from.is_some()
+    }
+
+    /// Freeze the view; appended data keeps being indexed but is not surfaced
+    pub fn pause(&mut self) {
+        if self.paused_from.is_none() {
+            self.paused_from = Some(self.reported_end);
+        }
+    }
+
+    /// Unfreeze the view, returning the range of lines that arrived while paused
+    pub fn resume(&mut self, file: &LogFile) -> Option<LineUpdate> {
+        let from = self.paused_from.take()?;
+        let total = file.line_count();
+        if total <= from {
+            return None;
+        }
+        Some(self.surface(file, from..total))
+    }
+
+    /// Shift surfaced positions after lines were evicted from the front of the view
+    pub fn on_evict(&mut self, count: u64) {
+        self.reported_end = self.reported_end.saturating_sub(count);
+        if let Some(from) = self.paused_from.as_mut() {
+            *from = from.saturating_sub(count);
+        }
+    }
+
+    /// Handle an appended range; returns None while paused
+    pub fn on_append(&mut self, file: &LogFile, range: Range<u64>) -> Option<LineUpdate> {
+        if self.is_paused() {
+            return None;
+        }
+        Some(self.surface(file, range))
+    }
+
+    fn surface(&mut self, file: &LogFile, range: Range<u64>) -> LineUpdate {
+        // An extended last line was already surfaced by an earlier update
+        let already_seen = self.reported_end.saturating_sub(range.start);
+        self.reported_end = range.end;
+
+        let filtered = self
+            .filter
+            .as_mut()
+            .map(|filter| filter.apply(file, range.clone(), already_seen));
+
+        LineUpdate {
+            start: range.start,
+            count: range.end - range.start,
+            total_lines: range.end,
+            suppressed: filtered.as_ref().map_or(0, |f| f.suppressed),
+            matches: filtered.map(|f| f.matches),
+        }
+    }
+}
+
+/// Handle to a background thread following a file
+pub struct Follower {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Follower {
+    /// Start following `path`
+    /// `refresh` re-indexes the file and reports appended lines; `on_event` receives follow events
+    pub fn spawn<R, E>(path: PathBuf, options: FollowOptions, refresh: R, on_event: E) -> Self
+    where
+        R: FnMut() -> Result<Option<Appended>, IndexerError> + Send + 'static,
+        E: FnMut(FollowEvent) + Send + 'static,
+    {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let handle = std::thread::spawn(move || {
+            let batcher = AppendBatcher::new(Duration::from_millis(options.batch_interval_ms));
+            let mut worker = FollowWorker {
+                path,
+                options,
+                refresh,
+                on_event,
+                stop: thread_stop,
+                batcher,
+            };
+            worker.run();
+        });
+
+        Follower {
+            stop,
+            handle: Some(handle),
+        }
+    }
+
+    /// Stop following and wait for the background thread to exit
+    pub fn stop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            handle.join().ok();
+        }
+    }
+}
+
+impl Drop for Follower {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Coalesces appended line ranges so bursts produce at most one event per interval
+#[derive(Debug)]
+pub struct AppendBatcher {
+    pending: Option<Range<u64>>,
+    interval: Duration,
+    last_flush: Option<Instant>,
+}
+
+impl AppendBatcher {
+    pub fn new(interval: Duration) -> Self {
+        AppendBatcher {
+            pending: None,
+            interval,
+            last_flush: None,
+        }
+    }
+
+    /// Merge a newly appended range into the pending batch
+    pub fn push(&mut self, range: Range<u64>) {
+        self.pending = Some(match self.pending.take() {
+            Some(pending) => pending.start.min(range.start)..pending.end.max(range.end),
+            None => range,
+        });
+    }
+
+    /// How long until the pending batch may be emitted; None when nothing is pending
+    pub fn time_until_due(&self, now: Instant) -> Option<Duration> {
+        self.pending.as_ref()?;
+        Some(match self.last_flush {
+            Some(last) => (last + self.interval).saturating_duration_since(now),
+            None => Duration::ZERO,
+        })
+    }
+
+    /// Take the pending batch if the rate limit allows emitting it now
+    pub fn take_due(&mut self, now: Instant) -> Option<Range<u64>> {
+        if self.time_until_due(now)? > Duration::ZERO {
+            return None;
+        }
+        self.last_flush = Some(now);
+        self.pending.take()
+    }
+
+    /// Take the pending batch regardless of the rate limit
+    pub fn take(&mut self) -> Option<Range<u64>> {
+        self.pending.take()
+    }
+}
+
+struct FollowWorker<R, E> {
+    path: PathBuf,
+    options: FollowOptions,
+    refresh: R,
+    on_event: E,
+    stop: Arc<AtomicBool>,
+    batcher: AppendBatcher,
+}
+
+impl<R, E> FollowWorker<R, E>
+where
+    R: FnMut() -> Result<Option<Appended>, IndexerError>,
+    E: FnMut(FollowEvent),
+{
+    fn run(&mut self) {
+        if self.options.mode != FollowMode::Poll {
+            if self.watch(self.options.mode) {
+                self.flush(true);
+                return;
+            }
+            // The watcher could not be set up or missed growth; keep going by polling
+            (self.on_event)(FollowEvent::ModeChanged(FollowMode::Poll));
+        }
+        self.poll();
+        self.flush(true);
+    }
+
+    /// Follow via filesystem notifications
+    /// Returns true when stopped normally, false when the caller should fall back to polling
+    fn watch(&mut self, mode: FollowMode) -> bool {
+        let (tx, rx) = mpsc::channel();
+        let mut watcher = match notify::recommended_watcher(tx) {
+            Ok(watcher) => watcher,
+            Err(err) => {
+                (self.on_event)(FollowEvent::Error(format!("File watcher unavailable: {}", err)));
+                return false;
+            }
+        };
+
+        // Watch the parent directory so renames and recreation of the file are seen too
+        let watch_target = self
+            .path
+            .parent()
+            .filter(|p| !p.as_os_str().is_empty())
+            .unwrap_or(Path::new("."))
+            .to_path_buf();
+        if let Err(err) = watcher.watch(&watch_target, RecursiveMode::NonRecursive) {
+            (self.on_event)(FollowEvent::Error(format!("Failed to watch file: {}", err)));
+            return false;
+        }
+
+        // The watcher is cross-checked against the file size at the slowest polling rate
+        let check_interval = Duration::from_millis(self.options.max_interval_ms.max(1));
+        // Notifications during a burst only mark the file dirty; refreshes are throttled
+        let refresh_interval = Duration::from_millis(self.options.min_interval_ms);
+        let mut known_size = file_len(&self.path);
+        let mut next_check = Instant::now() + check_interval;
+        let mut dirty_since: Option<Instant> = None;
+
+        while !self.stop.load(Ordering::SeqCst) {
+            let now = Instant::now();
+            let mut wait = next_check.saturating_duration_since(now);
+            if let Some(since) = dirty_since {
+                wait = wait.min((since + refresh_interval).saturating_duration_since(now));
+            }
+            if let Some(due) = self.batcher.time_until_due(now) {
+                wait = wait.min(due);
+            }
+            // Wake up regularly so stop requests are honored
+            wait = wait.min(Duration::from_millis(250));
+
+            match rx.recv_timeout(wait) {
+                Ok(Ok(event)) => {
+                    if event.paths.iter().any(|p| p.file_name() == self.path.file_name()) {
+                        dirty_since.get_or_insert_with(Instant::now);
+                    }
+                }
+                Ok(Err(err)) => (self.on_event)(FollowEvent::Error(err.to_string())),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return false,
+            }
+
+            let now = Instant::now();
+            if dirty_since.is_some_and(|since| now >= since + refresh_interval) {
+                dirty_since = None;
+                self.refresh_once();
+                known_size = file_len(&self.path);
+            }
+
+            if now >= next_check {
+                next_check = now + check_interval;
+                let size = file_len(&self.path);
+                if size > known_size && dirty_since.is_none() {
+                    // The file grew but no notification arrived (NFS, some container mounts)
+                    self.refresh_once();
+                    known_size = size;
+                    if mode == FollowMode::Auto {
+                        return false;
+                    }
+                }
+            }
+
+            self.flush(false);
+        }
+        true
+    }
+
+    /// Follow by periodically re-checking the file with an adaptive interval
+    fn poll(&mut self) {
+        let mut interval = AdaptiveInterval::new(
+            Duration::from_millis(self.options.min_interval_ms),
+            Duration::from_millis(self.options.max_interval_ms),
+        );
+
+        while !self.stop.load(Ordering::SeqCst) {
+            if self.refresh_once() {
+                interval.on_activity();
+            } else {
+                interval.on_idle();
+            }
+            self.flush(false);
+
+            let mut wait = interval.current();
+            if let Some(due) = self.batcher.time_until_due(Instant::now()) {
+                wait = wait.min(due);
+            }
+            sleep_unless_stopped(wait, &self.stop);
+        }
+    }
+
+    /// Refresh the file once, returning whether new data was found
+    fn refresh_once(&mut self) -> bool {
+        match (self.refresh)() {
+            Ok(Some(appended)) => {
+                if let Some(marker) = appended.rotation_marker {
+                    (self.on_event)(FollowEvent::Rotated(marker));
+                }
+                self.batcher.push(appended.lines);
+                true
+            }
+            Ok(None) => false,
+            Err(err) => {
+                (self.on_event)(FollowEvent::Error(err.to_string()));
+                false
+            }
+        }
+    }
+
+    /// Emit the pending batch if due, or unconditionally when `force` is set
+    fn flush(&mut self, force: bool) {
+        let batch = if force {
+            self.batcher.take()
+        } else {
+            self.batcher.take_due(Instant::now())
+        };
+        if let Some(range) = batch {
+            (self.on_event)(FollowEvent::Appended(range));
+        }
+    }
+}
+
+fn file_len(path: &Path) -> u64 {
+    std::fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+/// Sleep in short slices so a stop request is honored promptly
+fn sleep_unless_stopped(duration: Duration, stop: &AtomicBool) {
+    const SLICE: Duration = Duration::from_millis(50);
+    let mut remaining = duration;
+    while !remaining.is_zero() && !stop.load(Ordering::SeqCst) {
+        let step = remaining.min(SLICE);
+        std::thread::sleep(step);
+        remaining -= step;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexer::LogFile;
+    use parking_lot::Mutex;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_adaptive_interval() {
+        let mut interval =
+            AdaptiveInterval::new(Duration::from_millis(100), Duration::from_millis(350));
+        interval.on_idle();
+        assert_eq!(interval.current(), Duration::from_millis(200));
+        interval.on_idle();
+        interval.on_idle();
+        assert_eq!(interval.current(), Duration::from_millis(350));
+        interval.on_activity();
+        assert_eq!(interval.current(), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_batcher_coalesces_within_interval() {
+        let start = Instant::now();
+        let mut batcher = AppendBatcher::new(Duration::from_millis(100));
+        assert_eq!(batcher.time_until_due(start), None);
+
+        batcher.push(10..12);
+        assert_eq!(batcher.take_due(start), Some(10..12));
+
+        // Further appends inside the interval are held back and merged
+        batcher.push(11..20);
+        batcher.push(19..25);
+        assert_eq!(batcher.take_due(start + Duration::from_millis(50)), None);
+        assert_eq!(
+            batcher.time_until_due(start + Duration::from_millis(50)),
+            Some(Duration::from_millis(50))
+        );
+        assert_eq!(batcher.take_due(start + Duration::from_millis(100)), Some(11..25));
+        assert_eq!(batcher.take(), None);
+    }
+
+    #[test]
+    fn test_follow_filter_counts_suppressed() {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(b"INFO a\nERROR b\nINFO c\nERROR d\n").unwrap();
+        file.flush().unwrap();
+        let log_file = LogFile::open(file.path()).unwrap();
+
+        let mut filter = FollowFilter::new("ERROR").unwrap();
+        let first = filter.apply(&log_file, 0..2, 0);
+        assert_eq!(first.matches, vec![1]);
+        assert_eq!(first.suppressed, 1);
+
+        let second = filter.apply(&log_file, 1..4, 1);
+        assert_eq!(second.matches, vec![1, 3]);
+        assert_eq!(second.suppressed, 2);
+    }
+
+    #[test]
+    fn test_session_pause_and_resume() {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(b"a\n").unwrap();
+        file.flush().unwrap();
+        let mut log_file = LogFile::open(file.path()).unwrap();
+
+        let mut session = FollowSession::default();
+        session.restart(log_file.line_count());
+        session.pause();
+
+        file.write_all(b"b\nc\n").unwrap();
+        file.flush().unwrap();
+        let appended = log_file.refresh().unwrap().unwrap();
+        assert_eq!(session.on_append(&log_file, appended.lines), None);
+
+        let catch_up = session.resume(&log_file).unwrap();
+        assert_eq!((catch_up.start, catch_up.count, catch_up.total_lines), (1, 2, 3));
+        assert!(!session.is_paused());
+        assert_eq!(session.resume(&log_file), None);
+    }
+
+    #[test]
+    fn test_poll_follower_reports_appends() {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(b"first\n").unwrap();
+        file.flush().unwrap();
+
+        let log_file = Arc::new(Mutex::new(LogFile::open(file.path()).unwrap()));
+        let events = Arc::new(Mutex::new(Vec::new()));
+
+        let options = FollowOptions {
+            mode: FollowMode::Poll,
+            min_interval_ms: 10,
+            max_interval_ms: 20,
+            batch_interval_ms: 10,
+        };
+        let refresh_file = log_file.clone();
+        let sink = events.clone();
+        let mut follower = Follower::spawn(
+            file.path().to_path_buf(),
+            options,
+            move || refresh_file.lock().refresh(),
+            move |event| sink.lock().push(event),
+        );
+
+        file.write_all(b"second\nthird\n").unwrap();
+        file.flush().unwrap();
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while events.lock().is_empty() && std::time::Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        follower.stop();
+
+        assert_eq!(events.lock().first(), Some(&FollowEvent::Appended(1..3)));
+        assert_eq!(log_file.lock().line_count(), 3);
+    }
+}
@@ -0,0 +1,933 @@
+use crate::analysis::{normalize_line, preview, CHUNK_LINES};
+use crate::indexer::LogFile;
+use crate::livestats::{line_level, TemplateCount};
+use crate::timestamp::parse_timestamp_bytes;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use thiserror::Error;
+
+/// Levels from most to least severe, the order rates are listed in
+const LEVELS: [&str; 6] = ["fatal", "error", "warn", "info", "debug", "trace"];
+
+/// Errors that can occur analysing a file over time
+#[derive(Debug, Error)]
+pub enum TimelineError {
+    #[error("Time ranges must end after they start")]
+    EmptyRange,
+    #[error("Buckets must be 1 to {max} seconds wide, not {0}", max = MAX_BUCKET_SECONDS)]
+    InvalidBucket(u64),
+}
+
+/// A span of time in milliseconds since the Unix epoch, from `start` up to but not
+/// including `end`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeRange {
+    pub start: i64,
+    pub end: i64,
+}
+
+impl TimeRange {
+    pub fn validate(&self) -> Result<(), TimelineError> {
+        if self.end <= self.start {
+            return Err(TimelineError::EmptyRange);
+        }
+        Ok(())
+    }
+
+    pub fn contains(&self, millis: i64) -> bool {
+        self.start <= millis && millis < self.end
+    }
+
+    fn minutes(&self) -> f64 {
+        (self.end - self.start) as f64 / 60_000.0
+    }
+}
+
+/// A line starting a log entry, i.e. one with a timestamp
+/// Lines without one, such as stack frames, continue the entry above and aren't visited
+struct Entry {
+    line: u64,
+    time: i64,
+    level: Option<&'static str>,
+    template: String,
+}
+
+/// Fold the entries of a file whose time is `wanted`, in parallel chunks
+fn fold_entries<T: Send>(
+    file: &LogFile,
+    wanted: impl Fn(i64) -> bool + Sync,
+    identity: impl Fn() -> T + Sync + Send,
+    visit: impl Fn(&mut T, Entry) + Sync,
+    merge: impl Fn(T, T) -> T + Sync + Send,
+) -> T {
+    let line_count = file.line_count();
+    (0..line_count.div_ceil(CHUNK_LINES))
+        .into_par_iter()
+        .map(|chunk| {
+            let mut acc = identity();
+            let end = ((chunk + 1) * CHUNK_LINES).min(line_count);
+            for line in chunk * CHUNK_LINES..end {
+                let Some(bytes) = file.line_bytes(line) else {
+                    continue;
+                };
+                let Some(time) = parse_timestamp_bytes(bytes).filter(|&time| wanted(time)) else {
+                    continue;
+                };
+                let text = preview(bytes);
+                let entry = Entry {
+                    line,
+                    time,
+                    level: line_level(&text),
+                    template: normalize_line(&text),
+                };
+                visit(&mut acc, entry);
+            }
+            acc
+        })
+        .reduce(&identity, merge)
+}
+
+fn default_top_n() -> usize {
+    20
+}
+
+/// Two time ranges to compare, such as before and after a deploy
+#[derive(Debug, Clone, Deserialize)]
+pub struct RateCompareOptions {
+    pub before: TimeRange,
+    pub after: TimeRange,
+    /// Templates listed in each direction
+    #[serde(default = "default_top_n")]
+    pub top_n: usize,
+}
+
+/// How often a message template occurs in each range
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RateChange {
+    pub template: String,
+    /// 0-based index of its first occurrence in either range
+    pub line: u64,
+    pub before: u64,
+    pub after: u64,
+    pub before_per_minute: f64,
+    pub after_per_minute: f64,
+}
+
+/// How often entries of a level occur in each range
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LevelRate {
+    pub level: String,
+    pub before: u64,
+    pub after: u64,
+    pub before_per_minute: f64,
+    pub after_per_minute: f64,
+}
+
+/// Rates of the two ranges side by side
+#[derive(Debug, Clone, Serialize)]
+pub struct RateComparison {
+    pub before: TimeRange,
+    pub after: TimeRange,
+    pub before_entries: u64,
+    pub after_entries: u64,
+    /// Most severe first
+    pub levels: Vec<LevelRate>,
+    /// Templates whose rate rose most first, including ones new in the later range
+    pub increases: Vec<RateChange>,
+    /// Templates whose rate fell most first, including ones gone from the later range
+    pub decreases: Vec<RateChange>,
+}
+
+/// Entries counted in each of the two ranges
+#[derive(Default)]
+struct RateTally {
+    entries: [u64; 2],
+    levels: BTreeMap<&'static str, [u64; 2]>,
+    /// First line and counts by template
+    templates: HashMap<String, (u64, [u64; 2])>,
+}
+
+impl RateTally {
+    fn merge(mut self, other: RateTally) -> Self {
+        let add = |a: &mut [u64; 2], b: [u64; 2]| {
+            a[0] += b[0];
+            a[1] += b[1];
+        };
+        add(&mut self.entries, other.entries);
+        for (level, counts) in other.levels {
+            add(self.levels.entry(level).or_default(), counts);
+        }
+        for (template, (line, counts)) in other.templates {
+            let entry = self.templates.entry(template).or_insert((line, [0; 2]));
+            entry.0 = entry.0.min(line);
+            add(&mut entry.1, counts);
+        }
+        self
+    }
+}
+
+/// Compare how often each level and message template occurs in two time ranges, to see what
+/// got worse after a change
+pub fn compare_rates(
+    file: &LogFile,
+    options: &RateCompareOptions,
+) -> Result<RateComparison, TimelineError> {
+    options.before.validate()?;
+    options.after.validate()?;
+    let ranges = [options.before, options.after];
+    let tally = fold_entries(
+        file,
+        |time| ranges.iter().any(|range| range.contains(time)),
+        RateTally::default,
+        |tally, entry| {
+            let hits = ranges.map(|range| u64::from(range.contains(entry.time)));
+            tally.entries[0] += hits[0];
+            tally.entries[1] += hits[1];
+            if let Some(level) = entry.level {
+                let counts = tally.levels.entry(level).or_default();
+                counts[0] += hits[0];
+                counts[1] += hits[1];
+            }
+            let counts = tally
+                .templates
+                .entry(entry.template)
+                .or_insert((entry.line, [0; 2]));
+            counts.1[0] += hits[0];
+            counts.1[1] += hits[1];
+        },
+        RateTally::merge,
+    );
+
+    let minutes = ranges.map(|range| range.minutes());
+    let levels = LEVELS
+        .iter()
+        .filter_map(|&level| {
+            let counts = tally.levels.get(level)?;
+            Some(LevelRate {
+                level: level.to_string(),
+                before: counts[0],
+                after: counts[1],
+                before_per_minute: counts[0] as f64 / minutes[0],
+                after_per_minute: counts[1] as f64 / minutes[1],
+            })
+        })
+        .collect();
+
+    let (mut increases, mut decreases): (Vec<_>, Vec<_>) = tally
+        .templates
+        .into_iter()
+        .map(|(template, (line, counts))| {
+            let change = RateChange {
+                template,
+                line,
+                before: counts[0],
+                after: counts[1],
+                before_per_minute: counts[0] as f64 / minutes[0],
+                after_per_minute: counts[1] as f64 / minutes[1],
+            };
+            (change.after_per_minute - change.before_per_minute, change)
+        })
+        .filter(|(delta, _)| *delta != 0.0)
+        .partition(|(delta, _)| *delta > 0.0);
+    increases.sort_by(|(a, x), (b, y)| b.total_cmp(a).then(x.line.cmp(&y.line)));
+    decreases.sort_by(|(a, x), (b, y)| a.total_cmp(b).then(x.line.cmp(&y.line)));
+    let top = |changes: Vec<(f64, RateChange)>| -> Vec<RateChange> {
+        changes
+            .into_iter()
+            .take(options.top_n)
+            .map(|(_, change)| change)
+            .collect()
+    };
+
+    Ok(RateComparison {
+        before: options.before,
+        after: options.after,
+        before_entries: tally.entries[0],
+        after_entries: tally.entries[1],
+        levels,
+        increases: top(increases),
+        decreases: top(decreases),
+    })
+}
+
+/// Templates listed for each burst
+const BURST_TEMPLATES: usize = 3;
+
+/// Standard deviations above the mean a bucket's count must be to be part of a burst
+const BURST_SIGMAS: f64 = 3.0;
+
+/// Fewest entries a bucket of a burst holds, so a quiet file's odd error isn't a burst
+const MIN_BURST_COUNT: u64 = 3;
+
+fn default_burst_levels() -> Vec<String> {
+    vec!["fatal".to_string(), "error".to_string(), "warn".to_string()]
+}
+
+/// Widest bucket bursts are counted in, a week
+const MAX_BUCKET_SECONDS: u64 = 7 * 24 * 60 * 60;
+
+fn default_bucket_seconds() -> u64 {
+    60
+}
+
+fn default_max_windows() -> usize {
+    3
+}
+
+/// How bursts are looked for
+#[derive(Debug, Clone, Deserialize)]
+pub struct BurstOptions {
+    /// Levels of the entries counted, as `line_level` names them
+    #[serde(default = "default_burst_levels")]
+    pub levels: Vec<String>,
+    /// Width of the fixed buckets entries are counted in, aligned to the epoch
+    #[serde(default = "default_bucket_seconds")]
+    pub bucket_seconds: u64,
+    /// Bursts suggested, largest first
+    #[serde(default = "default_max_windows")]
+    pub max_windows: usize,
+    /// Part of the file looked at; None looks at all of it
+    #[serde(default)]
+    pub range: Option<TimeRange>,
+}
+
+impl Default for BurstOptions {
+    fn default() -> Self {
+        BurstOptions {
+            levels: default_burst_levels(),
+            bucket_seconds: default_bucket_seconds(),
+            max_windows: default_max_windows(),
+            range: None,
+        }
+    }
+}
+
+/// A span where entries arrived much faster than usual, worth investigating
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BurstWindow {
+    pub range: TimeRange,
+    pub count: u64,
+    /// Count of the busiest bucket, per minute
+    pub peak_per_minute: f64,
+    /// 0-based index of the first entry in the window, to jump to
+    pub first_line: u64,
+    /// Most frequent templates in the window
+    pub templates: Vec<TemplateCount>,
+}
+
+/// Bursts found, with the usual rate they stand out from
+#[derive(Debug, Clone, Serialize)]
+pub struct BurstReport {
+    pub bucket_seconds: u64,
+    /// Mean count per minute over the span of the counted entries
+    pub baseline_per_minute: f64,
+    /// In time order
+    pub windows: Vec<BurstWindow>,
+}
+
+/// Count the entries of the given levels by bucket, find runs of buckets far above the
+/// usual count, and suggest the largest as windows to investigate
+pub fn find_bursts(file: &LogFile, options: &BurstOptions) -> Result<BurstReport, TimelineError> {
+    if let Some(range) = &options.range {
+        range.validate()?;
+    }
+    if !(1..=MAX_BUCKET_SECONDS).contains(&options.bucket_seconds) {
+        return Err(TimelineError::InvalidBucket(options.bucket_seconds));
+    }
+    let bucket_millis = options.bucket_seconds as i64 * 1000;
+    let in_range = |time: i64| options.range.is_none_or(|range| range.contains(time));
+    let counted = |entry: &Entry| {
+        entry
+            .level
+            .is_some_and(|level| options.levels.iter().any(|l| l == level))
+    };
+    let buckets = fold_entries(
+        file,
+        in_range,
+        HashMap::new,
+        |buckets: &mut HashMap<i64, u64>, entry| {
+            if counted(&entry) {
+                *buckets
+                    .entry(entry.time.div_euclid(bucket_millis))
+                    .or_default() += 1;
+            }
+        },
+        |mut a, b| {
+            for (bucket, count) in b {
+                *a.entry(bucket).or_default() += count;
+            }
+            a
+        },
+    );
+
+    let (Some(&first), Some(&last)) = (buckets.keys().min(), buckets.keys().max()) else {
+        return Ok(BurstReport {
+            bucket_seconds: options.bucket_seconds,
+            baseline_per_minute: 0.0,
+            windows: Vec::new(),
+        });
+    };
+    // Mean and deviation over every bucket of the span, the empty ones included
+    let n = (last - first + 1) as f64;
+    let mean = buckets.values().sum::<u64>() as f64 / n;
+    let variance = buckets.values().map(|&c| (c as f64).powi(2)).sum::<f64>() / n - mean * mean;
+    let threshold = (mean + BURST_SIGMAS * variance.max(0.0).sqrt()).max(MIN_BURST_COUNT as f64);
+
+    // Runs of hot buckets, bridging single quiet buckets between them
+    let mut hot: Vec<i64> = buckets
+        .iter()
+        .filter(|(_, &count)| count as f64 >= threshold)
+        .map(|(&bucket, _)| bucket)
+        .collect();
+    hot.sort_unstable();
+    let mut runs: Vec<(i64, i64)> = Vec::new();
+    for bucket in hot {
+        match runs.last_mut() {
+            Some((_, end)) if bucket - *end <= 2 => *end = bucket,
+            _ => runs.push((bucket, bucket)),
+        }
+    }
+    let count_of =
+        |(start, end): (i64, i64)| -> u64 { (start..=end).filter_map(|b| buckets.get(&b)).sum() };
+    runs.sort_by_key(|&run| (std::cmp::Reverse(count_of(run)), run.0));
+    runs.truncate(options.max_windows);
+    runs.sort_unstable();
+
+    let ranges: Vec<TimeRange> = runs
+        .iter()
+        .map(|&(start, end)| TimeRange {
+            start: start * bucket_millis,
+            end: (end + 1) * bucket_millis,
+        })
+        .collect();
+    let templates = fold_entries(
+        file,
+        |time| in_range(time) && ranges.iter().any(|range| range.contains(time)),
+        || vec![HashMap::new(); ranges.len()],
+        |windows: &mut Vec<HashMap<String, (u64, u64)>>, entry| {
+            if !counted(&entry) {
+                return;
+            }
+            let Some(index) = ranges.iter().position(|range| range.contains(entry.time)) else {
+                return;
+            };
+            let counts = windows[index]
+                .entry(entry.template)
+                .or_insert((entry.line, 0));
+            counts.1 += 1;
+        },
+        |mut a, b| {
+            for (window, other) in a.iter_mut().zip(b) {
+                for (template, (line, count)) in other {
+                    let counts = window.entry(template).or_insert((line, 0));
+                    counts.0 = counts.0.min(line);
+                    counts.1 += count;
+                }
+            }
+            a
+        },
+    );
+
+    let per_minute = 60_000.0 / bucket_millis as f64;
+    let windows = runs
+        .into_iter()
+        .zip(ranges)
+        .zip(templates)
+        .map(|((run, range), counts)| {
+            let first_line = counts.values().map(|&(line, _)| line).min().unwrap_or(0);
+            let mut counts: Vec<(String, u64, u64)> = counts
+                .into_iter()
+                .map(|(template, (line, count))| (template, line, count))
+                .collect();
+            counts.sort_by_key(|(_, line, count)| (std::cmp::Reverse(*count), *line));
+            let peak = (run.0..=run.1).filter_map(|b| buckets.get(&b)).max();
+            BurstWindow {
+                range,
+                count: count_of(run),
+                peak_per_minute: peak.copied().unwrap_or(0) as f64 * per_minute,
+                first_line,
+                templates: counts
+                    .into_iter()
+                    .take(BURST_TEMPLATES)
+                    .map(|(template, _, count)| TemplateCount { template, count })
+                    .collect(),
+            }
+        })
+        .collect();
+
+    Ok(BurstReport {
+        bucket_seconds: options.bucket_seconds,
+        baseline_per_minute: mean * per_minute,
+        windows,
+    })
+}
+
+fn default_max_results() -> usize {
+    500
+}
+
+/// Which first occurrences to list
+#[derive(Debug, Clone, Deserialize)]
+pub struct FirstOccurrenceOptions {
+    /// Time looked at; None looks at the whole file
+    #[serde(default)]
+    pub range: Option<TimeRange>,
+    /// Only templates that never occur before the range, i.e. what's new in it
+    #[serde(default)]
+    pub new_only: bool,
+    /// Levels of the entries included, as `line_level` names them; empty includes all
+    #[serde(default)]
+    pub levels: Vec<String>,
+    #[serde(default = "default_max_results")]
+    pub max_results: usize,
+}
+
+impl Default for FirstOccurrenceOptions {
+    fn default() -> Self {
+        FirstOccurrenceOptions {
+            range: None,
+            new_only: false,
+            levels: Vec::new(),
+            max_results: default_max_results(),
+        }
+    }
+}
+
+/// The first entry of a message template
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FirstOccurrence {
+    pub template: String,
+    /// 0-based index of the entry
+    pub line: u64,
+    pub time: i64,
+    pub level: Option<String>,
+    pub text: String,
+    /// Entries of the template in the range
+    pub count: u64,
+}
+
+/// First occurrences in time order
+#[derive(Debug, Clone, Serialize)]
+pub struct FirstOccurrenceReport {
+    pub occurrences: Vec<FirstOccurrence>,
+    /// Templates found beyond `max_results`
+    pub truncated: bool,
+}
+
+/// Where a template was first seen in the range, and whether it was seen before it
+struct Sighting {
+    time: i64,
+    line: u64,
+    level: Option<&'static str>,
+    count: u64,
+    before: bool,
+}
+
+impl Sighting {
+    fn merge(&mut self, other: Sighting) {
+        if (other.count > 0 && (other.time, other.line) < (self.time, self.line)) || self.count == 0
+        {
+            self.time = other.time;
+            self.line = other.line;
+            self.level = other.level;
+        }
+        self.count += other.count;
+        self.before |= other.before;
+    }
+}
+
+/// List the first entry of each distinct message template in a time range, to show what
+/// started happening when, such as the errors that appeared after an incident began
+pub fn first_occurrences(
+    file: &LogFile,
+    options: &FirstOccurrenceOptions,
+) -> Result<FirstOccurrenceReport, TimelineError> {
+    if let Some(range) = &options.range {
+        range.validate()?;
+    }
+    let start = options.range.map_or(i64::MIN, |range| range.start);
+    let end = options.range.map_or(i64::MAX, |range| range.end);
+    let sightings = fold_entries(
+        file,
+        // Earlier entries are only needed to tell which templates are new
+        |time| time < end && (options.new_only || time >= start),
+        HashMap::new,
+        |sightings: &mut HashMap<String, Sighting>, entry| {
+            let level_wanted = options.levels.is_empty()
+                || entry
+                    .level
+                    .is_some_and(|level| options.levels.iter().any(|l| l == level));
+            if !level_wanted {
+                return;
+            }
+            let before = entry.time < start;
+            let sighting = Sighting {
+                time: entry.time,
+                line: entry.line,
+                level: entry.level,
+                count: u64::from(!before),
+                before,
+            };
+            match sightings.get_mut(&entry.template) {
+                Some(existing) => existing.merge(sighting),
+                None => {
+                    sightings.insert(entry.template, sighting);
+                }
+            }
+        },
+        |mut a, b| {
+            for (template, sighting) in b {
+                match a.get_mut(&template) {
+                    Some(existing) => existing.merge(sighting),
+                    None => {
+                        a.insert(template, sighting);
+                    }
+                }
+            }
+            a
+        },
+    );
+
+    let mut found: Vec<(String, Sighting)> = sightings
+        .into_iter()
+        .filter(|(_, s)| s.count > 0 && !(options.new_only && s.before))
+        .collect();
+    found.sort_unstable_by_key(|(_, s)| (s.time, s.line));
+    let truncated = found.len() > options.max_results;
+    let occurrences = found
+        .into_iter()
+        .take(options.max_results)
+        .map(|(template, s)| FirstOccurrence {
+            template,
+            line: s.line,
+            time: s.time,
+            level: s.level.map(str::to_string),
+            text: file.line_bytes(s.line).map(preview).unwrap_or_default(),
+            count: s.count,
+        })
+        .collect();
+    Ok(FirstOccurrenceReport {
+        occurrences,
+        truncated,
+    })
+}
+
+/// Which window to look for rare lines in
+#[derive(Debug, Clone, Deserialize)]
+pub struct RarityOptions {
+    /// Window looked at; None looks at the whole file
+    #[serde(default)]
+    pub range: Option<TimeRange>,
+    /// Only templates never seen before the window
+    #[serde(default)]
+    pub new_only: bool,
+    #[serde(default = "default_top_n")]
+    pub top_n: usize,
+}
+
+impl Default for RarityOptions {
+    fn default() -> Self {
+        RarityOptions {
+            range: None,
+            new_only: false,
+            top_n: default_top_n(),
+        }
+    }
+}
+
+/// A line in the window whose template is rare in the file
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RareLine {
+    pub template: String,
+    /// 0-based index of the template's first entry in the window
+    pub line: u64,
+    pub time: i64,
+    pub level: Option<String>,
+    pub text: String,
+    /// Bits of surprise, -log2 of the template's share of the file's entries
+    pub score: f64,
+    /// Entries of the template in the whole file
+    pub file_count: u64,
+    /// Entries of the template in the window
+    pub window_count: u64,
+    /// Never seen in the file before the window
+    pub new: bool,
+}
+
+/// Rarest lines in a window, most surprising first
+#[derive(Debug, Clone, Serialize)]
+pub struct RarityReport {
+    /// Entries in the whole file, the rarity of templates is relative to
+    pub entries: u64,
+    pub lines: Vec<RareLine>,
+}
+
+/// Score each template in a window by how rare it is across the whole file and list the
+/// rarest, to surface the one odd line among millions of routine ones
+pub fn rare_lines(file: &LogFile, options: &RarityOptions) -> Result<RarityReport, TimelineError> {
+    if let Some(range) = &options.range {
+        range.validate()?;
+    }
+    let start = options.range.map_or(i64::MIN, |range| range.start);
+    let end = options.range.map_or(i64::MAX, |range| range.end);
+    let (entries, sightings) = fold_entries(
+        file,
+        |_| true,
+        || (0u64, HashMap::new()),
+        |(entries, sightings): &mut (u64, HashMap<String, (Sighting, u64)>), entry| {
+            *entries += 1;
+            let in_window = start <= entry.time && entry.time < end;
+            let sighting = Sighting {
+                time: entry.time,
+                line: entry.line,
+                level: entry.level,
+                count: u64::from(in_window),
+                before: entry.time < start,
+            };
+            match sightings.get_mut(&entry.template) {
+                Some((existing, total)) => {
+                    existing.merge(sighting);
+                    *total += 1;
+                }
+                None => {
+                    sightings.insert(entry.template, (sighting, 1));
+                }
+            }
+        },
+        |(entries, mut a), (other, b)| {
+            for (template, (sighting, total)) in b {
+                match a.get_mut(&template) {
+                    Some(existing) => {
+                        existing.0.merge(sighting);
+                        existing.1 += total;
+                    }
+                    None => {
+                        a.insert(template, (sighting, total));
+                    }
+                }
+            }
+            (entries + other, a)
+        },
+    );
+
+    let mut found: Vec<(String, Sighting, u64)> = sightings
+        .into_iter()
+        .filter(|(_, (s, _))| s.count > 0 && !(options.new_only && s.before))
+        .map(|(template, (s, total))| (template, s, total))
+        .collect();
+    found.sort_unstable_by_key(|(_, s, total)| (*total, s.time, s.line));
+    let lines = found
+        .into_iter()
+        .take(options.top_n)
+        .map(|(template, s, total)| RareLine {
+            template,
+            line: s.line,
+            time: s.time,
+            level: s.level.map(str::to_string),
+            text: file.line_bytes(s.line).map(preview).unwrap_or_default(),
+            score: (entries as f64 / total as f64).log2(),
+            file_count: total,
+            window_count: s.count,
+            new: !s.before,
+        })
+        .collect();
+    Ok(RarityReport { entries, lines })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexer::open_test_file;
+    use crate::timestamp::parse_timestamp;
+
+    fn range(start: &str, end: &str) -> TimeRange {
+        TimeRange {
+            start: parse_timestamp(start).unwrap(),
+            end: parse_timestamp(end).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_compare_rates() {
+        let (_file, log_file) = open_test_file(
+            "2024-05-01T10:00:00Z INFO request 1 served\n\
+             2024-05-01T10:01:00Z INFO request 2 served\n\
+             2024-05-01T10:02:00Z WARN cache miss for key 7\n\
+             2024-05-01T10:10:00Z INFO request 3 served\n\
+             2024-05-01T10:11:00Z ERROR db timeout after 30s\n\
+             \tat db.query\n\
+             2024-05-01T10:12:00Z ERROR db timeout after 31s\n",
+        );
+        let options = RateCompareOptions {
+            before: range("2024-05-01T10:00:00Z", "2024-05-01T10:05:00Z"),
+            after: range("2024-05-01T10:10:00Z", "2024-05-01T10:15:00Z"),
+            top_n: 5,
+        };
+        let comparison = compare_rates(&log_file, &options).unwrap();
+        assert_eq!(
+            (comparison.before_entries, comparison.after_entries),
+            (3, 3)
+        );
+
+        let levels: Vec<_> = comparison
+            .levels
+            .iter()
+            .map(|l| (l.level.as_str(), l.before, l.after))
+            .collect();
+        assert_eq!(levels, [("error", 0, 2), ("warn", 1, 0), ("info", 2, 1)]);
+
+        let increases: Vec<_> = comparison.increases.iter().map(|c| c.line).collect();
+        assert_eq!(increases, [4]);
+        assert_eq!(comparison.increases[0].after_per_minute, 0.4);
+        let decreases: Vec<_> = comparison
+            .decreases
+            .iter()
+            .map(|c| (c.line, c.before, c.after))
+            .collect();
+        assert_eq!(decreases, [(0, 2, 1), (2, 1, 0)]);
+
+        let empty = RateCompareOptions {
+            after: range("2024-05-01T10:10:00Z", "2024-05-01T10:10:00Z"),
+            ..options
+        };
+        assert!(compare_rates(&log_file, &empty).is_err());
+    }
+
+    #[test]
+    fn test_find_bursts() {
+        // An error a minute, then a storm of timeouts in minute 10
+        let mut log = String::new();
+        for minute in 0..20 {
+            log.push_str(&format!(
+                "2024-05-01T10:{:02}:00Z ERROR disk slow\n",
+                minute
+            ));
+            log.push_str(&format!("2024-05-01T10:{:02}:05Z INFO tick\n", minute));
+        }
+        for second in 10..19 {
+            log.push_str(&format!(
+                "2024-05-01T10:10:{}Z ERROR upstream timeout after {}ms\n",
+                second,
+                second * 100
+            ));
+        }
+        let (_file, log_file) = open_test_file(&log);
+        let report = find_bursts(&log_file, &BurstOptions::default()).unwrap();
+        assert_eq!(report.baseline_per_minute, 29.0 / 20.0);
+        assert_eq!(report.windows.len(), 1);
+
+        let window = &report.windows[0];
+        assert_eq!(
+            window.range,
+            range("2024-05-01T10:10:00Z", "2024-05-01T10:11:00Z")
+        );
+        assert_eq!((window.count, window.peak_per_minute), (10, 10.0));
+        assert_eq!(window.first_line, 20);
+        assert_eq!(
+            window.templates[0],
+            TemplateCount {
+                template: "?-?-?:?:? ERROR upstream timeout after ?".to_string(),
+                count: 9
+            }
+        );
+
+        for bucket_seconds in [0, u64::MAX] {
+            let options = BurstOptions {
+                bucket_seconds,
+                ..BurstOptions::default()
+            };
+            assert!(matches!(
+                find_bursts(&log_file, &options),
+                Err(TimelineError::InvalidBucket(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_first_occurrences() {
+        let (_file, log_file) = open_test_file(
+            "2024-05-01T14:00:00Z ERROR cache miss for key 1\n\
+             2024-05-01T14:01:00Z INFO request 1 served\n\
+             2024-05-01T14:03:00Z ERROR cache miss for key 2\n\
+             2024-05-01T14:04:00Z ERROR db pool exhausted at 50 connections\n\
+             2024-05-01T14:05:00Z INFO request 2 served\n\
+             2024-05-01T14:06:00Z ERROR db pool exhausted at 51 connections\n",
+        );
+        let options = FirstOccurrenceOptions {
+            range: Some(range("2024-05-01T14:02:00Z", "2024-05-01T15:00:00Z")),
+            max_results: 10,
+            ..Default::default()
+        };
+        let report = first_occurrences(&log_file, &options).unwrap();
+        let lines: Vec<_> = report
+            .occurrences
+            .iter()
+            .map(|o| (o.line, o.count))
+            .collect();
+        assert_eq!(lines, [(2, 1), (3, 2), (4, 1)]);
+
+        // What errors are new since 14:02
+        let options = FirstOccurrenceOptions {
+            new_only: true,
+            levels: vec!["error".to_string()],
+            ..options
+        };
+        let report = first_occurrences(&log_file, &options).unwrap();
+        assert_eq!(report.occurrences.len(), 1);
+        let first = &report.occurrences[0];
+        assert_eq!((first.line, first.level.as_deref()), (3, Some("error")));
+        assert!(first.text.contains("exhausted at 50"));
+        assert!(!report.truncated);
+    }
+
+    #[test]
+    fn test_rare_lines() {
+        let mut log = String::new();
+        for minute in 0..10 {
+            log.push_str(&format!(
+                "2024-05-01T10:{:02}:00Z INFO tick {}\n",
+                minute, minute
+            ));
+            log.push_str(&format!(
+                "2024-05-01T10:{:02}:30Z WARN slow query\n",
+                minute
+            ));
+        }
+        log.push_str("2024-05-01T09:59:00Z ERROR checksum mismatch in block 4\n");
+        log.push_str("2024-05-01T10:05:10Z ERROR checksum mismatch in block 9\n");
+        log.push_str("2024-05-01T10:05:20Z FATAL heap corrupted\n");
+        let (_file, log_file) = open_test_file(&log);
+
+        let options = RarityOptions {
+            range: Some(range("2024-05-01T10:05:00Z", "2024-05-01T10:06:00Z")),
+            new_only: false,
+            top_n: 10,
+        };
+        let report = rare_lines(&log_file, &options).unwrap();
+        assert_eq!(report.entries, 23);
+        let lines: Vec<_> = report
+            .lines
+            .iter()
+            .map(|l| (l.line, l.file_count, l.window_count, l.new))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                (22, 1, 1, true),
+                (21, 2, 1, false),
+                (10, 10, 1, false),
+                (11, 10, 1, false)
+            ]
+        );
+        assert_eq!(report.lines[0].score, 23f64.log2());
+
+        let new_only = RarityOptions {
+            new_only: true,
+            ..options
+        };
+        let report = rare_lines(&log_file, &new_only).unwrap();
+        let lines: Vec<_> = report.lines.iter().map(|l| l.line).collect();
+        assert_eq!(lines, [22]);
+    }
+}
@@ -0,0 +1,199 @@
+use chrono::{Datelike, FixedOffset, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// How far into a line we look for a timestamp
+const SCAN_WINDOW: usize = 256;
+
+fn iso_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            r"(\d{4})-(\d{2})-(\d{2})[T ](\d{2}):(\d{2}):(\d{2})(?:[.,](\d{1,9}))?\s?(Z|[+-]\d{2}:?\d{2})?",
+        )
+        .unwrap()
+    })
+}
+
+fn clf_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"\[(\d{2})/([A-Za-z]{3})/(\d{4}):(\d{2}):(\d{2}):(\d{2}) ([+-]\d{4})\]").unwrap()
+    })
+}
+
+fn syslog_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^([A-Za-z]{3}) +(\d{1,2}) (\d{2}):(\d{2}):(\d{2})").unwrap())
+}
+
+fn epoch_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^(\d{10})(?:\.(\d{1,6})|(\d{3}))?\b").unwrap())
+}
+
+/// Parse the first recognizable timestamp in a log line
+/// Returns milliseconds since the Unix epoch (UTC); timestamps without a zone are treated as UTC
+pub fn parse_timestamp(line: &str) -> Option<i64> {
+    let window = truncate_to_boundary(line, SCAN_WINDOW);
+
+    parse_iso(window)
+        .or_else(|| parse_clf(window))
+        .or_else(|| parse_syslog(window))
+        .or_else(|| parse_epoch(window))
+}
+
+/// Parse a timestamp from raw line bytes, tolerating invalid UTF-8
+pub fn parse_timestamp_bytes(bytes: &[u8]) -> Option<i64> {
+    let window = &bytes[..bytes.len().min(SCAN_WINDOW)];
+    parse_timestamp(&String::from_utf8_lossy(window))
+}
+
+/// Format epoch milliseconds as an RFC 3339 string
+pub fn format_timestamp(millis: i64) -> String {
+    Utc.timestamp_millis_opt(millis)
+        .single()
+        .map(|dt| dt.to_rfc3339_opts(chrono::SecondsFormat::Millis, true))
+        .unwrap_or_else(|| millis.to_string())
+}
+
+fn truncate_to_boundary(s: &str, max: usize) -> &str {
+    if s.len() <= max {
+        return s;
+    }
+    let mut end = max;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+fn parse_iso(s: &str) -> Option<i64> {
+    let caps = iso_regex().captures(s)?;
+    let num = |i: usize| caps.get(i).and_then(|m| m.as_str().parse::<u32>().ok());
+
+    let date = NaiveDate::from_ymd_opt(num(1)? as i32, num(2)?, num(3)?)?;
+    let nanos = caps
+        .get(7)
+        .map(|m| fraction_to_nanos(m.as_str()))
+        .unwrap_or(0);
+    let naive = date.and_hms_nano_opt(num(4)?, num(5)?, num(6)?, nanos)?;
+
+    let offset_secs = match caps.get(8).map(|m| m.as_str()) {
+        None | Some("Z") => 0,
+        Some(tz) => parse_offset(tz)?,
+    };
+
+    Some(naive.and_utc().timestamp_millis() - offset_secs as i64 * 1000)
+}
+
+fn parse_clf(s: &str) -> Option<i64> {
+    let caps = clf_regex().captures(s)?;
+    let day: u32 = caps[1].parse().ok()?;
+    let month = month_from_abbrev(&caps[2])?;
+    let year: i32 = caps[3].parse().ok()?;
+    let naive = NaiveDate::from_ymd_opt(year, month, day)?.and_hms_opt(
+        caps[4].parse().ok()?,
+        caps[5].parse().ok()?,
+        caps[6].parse().ok()?,
+    )?;
+    let offset = FixedOffset::east_opt(parse_offset(&caps[7])?)?;
+    offset
+        .from_local_datetime(&naive)
+        .single()
+        .map(|dt| dt.timestamp_millis())
+}
+
+fn parse_syslog(s: &str) -> Option<i64> {
+    let caps = syslog_regex().captures(s)?;
+    let month = month_from_abbrev(&caps[1])?;
+    let day: u32 = caps[2].parse().ok()?;
+    // Syslog timestamps carry no year; assume the current one so files compare consistently
+    let year = Utc::now().year();
+    let naive: NaiveDateTime = NaiveDate::from_ymd_opt(year, month, day)?.and_hms_opt(
+        caps[3].parse().ok()?,
+        caps[4].parse().ok()?,
+        caps[5].parse().ok()?,
+    )?;
+    Some(naive.and_utc().timestamp_millis())
+}
+
+fn parse_epoch(s: &str) -> Option<i64> {
+    let caps = epoch_regex().captures(s)?;
+    let secs: i64 = caps[1].parse().ok()?;
+    let millis = if let Some(frac) = caps.get(2) {
+        (fraction_to_nanos(frac.as_str()) / 1_000_000) as i64
+    } else if let Some(ms) = caps.get(3) {
+        ms.as_str().parse().ok()?
+    } else {
+        0
+    };
+    Some(secs * 1000 + millis)
+}
+
+fn fraction_to_nanos(frac: &str) -> u32 {
+    let digits = &frac[..frac.len().min(9)];
+    let value: u32 = digits.parse().unwrap_or(0);
+    value * 10u32.pow(9 - digits.len() as u32)
+}
+
+fn parse_offset(tz: &str) -> Option<i32> {
+    let sign = if tz.starts_with('-') { -1 } else { 1 };
+    let digits: String = tz[1..].chars().filter(|c| c.is_ascii_digit()).collect();
+    if digits.len() != 4 {
+        return None;
+    }
+    let hours: i32 = digits[..2].parse().ok()?;
+    let minutes: i32 = digits[2..].parse().ok()?;
+    Some(sign * (hours * 3600 + minutes * 60))
+}
+
+fn month_from_abbrev(abbrev: &str) -> Option<u32> {
+    const MONTHS: [&str; 12] = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ];
+    let lower = abbrev.to_ascii_lowercase();
+    MONTHS.iter().position(|m| *m == lower).map(|i| i as u32 + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_iso_variants() {
+        let base = parse_timestamp("2024-01-01T00:00:00Z started").unwrap();
+        assert_eq!(base, 1_704_067_200_000);
+        assert_eq!(parse_timestamp("2024-01-01 00:00:00,250 INFO x"), Some(base + 250));
+        assert_eq!(parse_timestamp("2024-01-01T01:00:00+01:00 x"), Some(base));
+        assert_eq!(
+            parse_timestamp(r#"{"ts":"2024-01-01T00:00:01.5Z","level":"info"}"#),
+            Some(base + 1500)
+        );
+    }
+
+    #[test]
+    fn test_parse_clf() {
+        let ts = parse_timestamp(r#"127.0.0.1 - - [01/Jan/2024:01:00:00 +0100] "GET / HTTP/1.1" 200"#);
+        assert_eq!(ts, Some(1_704_067_200_000));
+    }
+
+    #[test]
+    fn test_parse_epoch() {
+        assert_eq!(parse_timestamp("1704067200 event"), Some(1_704_067_200_000));
+        assert_eq!(parse_timestamp("1704067200123 event"), Some(1_704_067_200_123));
+        assert_eq!(parse_timestamp("1704067200.5 event"), Some(1_704_067_200_500));
+    }
+
+    #[test]
+    fn test_parse_syslog_orders_within_year() {
+        let a = parse_timestamp("Jan  2 15:04:05 host sshd[1]: accepted").unwrap();
+        let b = parse_timestamp("Jan  2 15:04:06 host sshd[1]: closed").unwrap();
+        assert_eq!(b - a, 1000);
+    }
+
+    #[test]
+    fn test_no_timestamp() {
+        assert_eq!(parse_timestamp("    at com.example.Foo.bar(Foo.java:42)"), None);
+    }
+}
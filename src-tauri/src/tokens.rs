@@ -0,0 +1,326 @@
+use crate::livestats::word_level;
+use serde::Serialize;
+
+/// Bytes of a line tokenized; the rest of a longer line is left plain
+pub const MAX_TOKENIZED_BYTES: usize = 64 * 1024;
+
+/// Month abbreviations that start a syslog timestamp such as `Jan  2 15:04:05`
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// What a span of a line is, for coloring it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenKind {
+    Timestamp,
+    /// The first level word of the line, such as `ERROR` or `warn`
+    Level,
+    Number,
+    /// IPv4 address, with the port if one follows
+    Ip,
+    /// Text in double or single quotes, quotes included
+    String,
+    /// JSON brace or bracket
+    Brace,
+    Uuid,
+}
+
+/// A span of a line and its kind
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Token {
+    /// Start and end in UTF-16 code units, as JavaScript indexes strings
+    pub start: usize,
+    pub end: usize,
+    pub kind: TokenKind,
+    /// For level tokens, the level named, as in live statistics
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub level: Option<&'static str>,
+}
+
+/// Tokens of one displayed line
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LineTokens {
+    pub line: u64,
+    pub tokens: Vec<Token>,
+}
+
+/// Split a line into colored spans, in order; text between them is plain
+/// Tokens start and end at word boundaries, so `abc123` holds no number
+pub fn tokenize(text: &str) -> Vec<Token> {
+    let b = &text.as_bytes()[..text.len().min(MAX_TOKENIZED_BYTES)];
+    let mut spans: Vec<(usize, usize, TokenKind, Option<&'static str>)> = Vec::new();
+    let mut level_seen = false;
+    let mut i = 0;
+    while i < b.len() {
+        let c = b[i];
+        if c == b'"' || (c == b'\'' && starts_word(b, i)) {
+            if let Some(end) = quoted(b, i) {
+                spans.push((i, end, TokenKind::String, None));
+                i = end;
+            } else {
+                i += 1;
+            }
+            continue;
+        }
+        if matches!(c, b'{' | b'}' | b'[' | b']') {
+            spans.push((i, i + 1, TokenKind::Brace, None));
+            i += 1;
+            continue;
+        }
+        if !c.is_ascii_alphanumeric() || !starts_word(b, i) {
+            i += 1;
+            continue;
+        }
+
+        let token = uuid(b, i)
+            .map(|end| (end, TokenKind::Uuid, None))
+            .or_else(|| timestamp(b, i).map(|end| (end, TokenKind::Timestamp, None)))
+            .or_else(|| ip(b, i).map(|end| (end, TokenKind::Ip, None)))
+            .or_else(|| number(b, i).map(|end| (end, TokenKind::Number, None)))
+            .or_else(|| {
+                let end = word_end(b, i);
+                let level = word_level(std::str::from_utf8(&b[i..end]).ok()?)?;
+                (!level_seen).then_some((end, TokenKind::Level, Some(level)))
+            });
+        match token {
+            Some((end, kind, level)) => {
+                level_seen |= kind == TokenKind::Level;
+                spans.push((i, end, kind, level));
+                i = end;
+            }
+            // Skip the rest of the word, and dotted runs like versions along with it
+            None => {
+                i += 1;
+                while i < b.len() && (is_word_byte(b[i]) || b[i] == b'.') {
+                    i += 1;
+                }
+            }
+        }
+    }
+    to_utf16(text, spans)
+}
+
+/// Convert byte spans, in order, to UTF-16 offsets
+fn to_utf16(text: &str, spans: Vec<(usize, usize, TokenKind, Option<&'static str>)>) -> Vec<Token> {
+    let mut chars = text.char_indices().peekable();
+    let mut units = 0;
+    let mut utf16_at = |byte: usize| {
+        while let Some((_, c)) = chars.next_if(|&(at, _)| at < byte) {
+            units += c.len_utf16();
+        }
+        units
+    };
+    spans
+        .into_iter()
+        .map(|(start, end, kind, level)| Token {
+            start: utf16_at(start),
+            end: utf16_at(end),
+            kind,
+            level,
+        })
+        .collect()
+}
+
+fn is_word_byte(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_' || !c.is_ascii()
+}
+
+fn starts_word(b: &[u8], i: usize) -> bool {
+    i == 0 || !is_word_byte(b[i - 1])
+}
+
+fn ends_word(b: &[u8], i: usize) -> bool {
+    i == b.len() || !is_word_byte(b[i])
+}
+
+fn word_end(b: &[u8], mut i: usize) -> usize {
+    while i < b.len() && is_word_byte(b[i]) {
+        i += 1;
+    }
+    i
+}
+
+/// End of a quoted string starting at `i`, past the closing quote; backslashes escape
+fn quoted(b: &[u8], i: usize) -> Option<usize> {
+    let quote = b[i];
+    let mut j = i + 1;
+    while j < b.len() {
+        match b[j] {
+            b'\\' => j += 2,
+            c if c == quote => return Some(j + 1),
+            _ => j += 1,
+        }
+    }
+    None
+}
+
+/// End of a run of `min..=max` bytes matching `pred` starting at `i`
+fn run(b: &[u8], i: usize, min: usize, max: usize, pred: fn(&u8) -> bool) -> Option<usize> {
+    let len = b[i.min(b.len())..]
+        .iter()
+        .take(max)
+        .take_while(|c| pred(c))
+        .count();
+    (len >= min).then_some(i + len)
+}
+
+fn digits(b: &[u8], i: usize, n: usize) -> Option<usize> {
+    run(b, i, n, n, u8::is_ascii_digit)
+}
+
+fn byte(b: &[u8], i: usize, expected: u8) -> Option<usize> {
+    (b.get(i) == Some(&expected)).then_some(i + 1)
+}
+
+/// `8-4-4-4-12` hex digits
+fn uuid(b: &[u8], i: usize) -> Option<usize> {
+    let mut j = i;
+    for (n, len) in [8, 4, 4, 4, 12].into_iter().enumerate() {
+        if n > 0 {
+            j = byte(b, j, b'-')?;
+        }
+        j = run(b, j, len, len, u8::is_ascii_hexdigit)?;
+    }
+    ends_word(b, j).then_some(j)
+}
+
+/// ISO-style date with an optional time and zone, a time of day, or a syslog timestamp
+fn timestamp(b: &[u8], i: usize) -> Option<usize> {
+    let end = date(b, i)
+        .map(|j| {
+            match b.get(j) {
+                Some(b'T' | b' ') => time(b, j + 1).map(|k| zone(b, k)),
+                _ => None,
+            }
+            .unwrap_or(j)
+        })
+        .or_else(|| time(b, i))
+        .or_else(|| syslog(b, i))?;
+    ends_word(b, end).then_some(end)
+}
+
+/// `yyyy-mm-dd` or `yyyy/mm/dd`
+fn date(b: &[u8], i: usize) -> Option<usize> {
+    let j = digits(b, i, 4)?;
+    let sep = *b.get(j).filter(|c| matches!(c, b'-' | b'/'))?;
+    let j = digits(b, j + 1, 2)?;
+    digits(b, byte(b, j, sep)?, 2)
+}
+
+/// `hh:mm:ss` with optional fractional seconds
+fn time(b: &[u8], i: usize) -> Option<usize> {
+    let j = digits(b, i, 2)?;
+    let j = digits(b, byte(b, j, b':')?, 2)?;
+    let j = digits(b, byte(b, j, b':')?, 2)?;
+    Some(match b.get(j) {
+        Some(b'.' | b',') => run(b, j + 1, 1, 9, u8::is_ascii_digit).unwrap_or(j),
+        _ => j,
+    })
+}
+
+/// `Z` or a `+hh:mm`-style offset after a time, if there is one
+fn zone(b: &[u8], i: usize) -> usize {
+    match b.get(i) {
+        Some(b'Z') => i + 1,
+        Some(b'+' | b'-') => digits(b, i + 1, 2)
+            .map(|j| digits(b, byte(b, j, b':').unwrap_or(j), 2).unwrap_or(j))
+            .unwrap_or(i),
+        _ => i,
+    }
+}
+
+/// `Mmm dd hh:mm:ss`, with the day padded by a space or not
+fn syslog(b: &[u8], i: usize) -> Option<usize> {
+    let month = b.get(i..i + 3)?;
+    if !MONTHS.iter().any(|m| m.as_bytes() == month) {
+        return None;
+    }
+    let mut j = byte(b, i + 3, b' ')?;
+    if b.get(j) == Some(&b' ') {
+        j += 1;
+    }
+    let j = run(b, j, 1, 2, u8::is_ascii_digit)?;
+    time(b, byte(b, j, b' ')?)
+}
+
+/// Dotted IPv4 address with an optional port
+fn ip(b: &[u8], i: usize) -> Option<usize> {
+    let mut j = i;
+    for n in 0..4 {
+        if n > 0 {
+            j = byte(b, j, b'.')?;
+        }
+        let end = run(b, j, 1, 3, u8::is_ascii_digit)?;
+        let octet: u32 = std::str::from_utf8(&b[j..end]).ok()?.parse().ok()?;
+        if octet > 255 {
+            return None;
+        }
+        j = end;
+    }
+    if let Some(port) = byte(b, j, b':').and_then(|k| run(b, k, 1, 5, u8::is_ascii_digit)) {
+        j = port;
+    }
+    ends_word(b, j).then_some(j)
+}
+
+/// Decimal number with an optional fraction, or `0x` hex
+fn number(b: &[u8], i: usize) -> Option<usize> {
+    let j = if b[i..].starts_with(b"0x") {
+        run(b, i + 2, 1, usize::MAX, u8::is_ascii_hexdigit)?
+    } else {
+        let j = run(b, i, 1, usize::MAX, u8::is_ascii_digit)?;
+        match b.get(j) {
+            Some(b'.') => run(b, j + 1, 1, usize::MAX, u8::is_ascii_digit).unwrap_or(j),
+            _ => j,
+        }
+    };
+    let dotted = b.get(j) == Some(&b'.') && b.get(j + 1).is_some_and(u8::is_ascii_digit);
+    (ends_word(b, j) && !dotted).then_some(j)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spans(text: &str) -> Vec<(String, TokenKind)> {
+        let units: Vec<u16> = text.encode_utf16().collect();
+        tokenize(text)
+            .into_iter()
+            .map(|t| (String::from_utf16(&units[t.start..t.end]).unwrap(), t.kind))
+            .collect()
+    }
+
+    #[test]
+    fn test_tokenize() {
+        use TokenKind::*;
+        let line = "2024-03-01T12:00:05.123Z ERROR [worker-2] error from 10.0.0.7:8080 \
+                    id=1b4e28ba-2fa1-11d2-883f-0016d3cca427 took 42.5ms v1.2.3 \"it's done\"";
+        assert_eq!(
+            spans(line),
+            vec![
+                ("2024-03-01T12:00:05.123Z".to_string(), Timestamp),
+                ("ERROR".to_string(), Level),
+                ("[".to_string(), Brace),
+                ("2".to_string(), Number),
+                ("]".to_string(), Brace),
+                ("10.0.0.7:8080".to_string(), Ip),
+                ("1b4e28ba-2fa1-11d2-883f-0016d3cca427".to_string(), Uuid),
+                ("\"it's done\"".to_string(), String),
+            ]
+        );
+        assert_eq!(tokenize(line)[1].level, Some("error"));
+
+        assert_eq!(
+            spans("Jan  2 15:04:05 host {\"n\": 0x1F} résumé 7"),
+            vec![
+                ("Jan  2 15:04:05".to_string(), Timestamp),
+                ("{".to_string(), Brace),
+                ("\"n\"".to_string(), String),
+                ("0x1F".to_string(), Number),
+                ("}".to_string(), Brace),
+                ("7".to_string(), Number),
+            ]
+        );
+    }
+}
@@ -0,0 +1,179 @@
+use crate::indexer::{IndexerError, LogFile};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::ops::Range;
+
+/// Bytes of text each block of the index covers, rounded to whole lines
+const BLOCK_BYTES: u64 = 1024 * 1024;
+/// Bits in each block's trigram filter; a 1 MiB block of log text sets about a third of them
+const FILTER_BITS: usize = 1 << 16;
+const FILTER_WORDS: usize = FILTER_BITS / 64;
+
+/// Which blocks of lines may contain each trigram (three consecutive bytes), so a literal
+/// search can skip the blocks that lack one of its trigrams
+/// Each block keeps a one-hash Bloom filter of its trigrams with ASCII letters lowercased,
+/// so it serves case-sensitive and case-insensitive searches alike
+pub struct TrigramIndex {
+    /// First line of each block; a block ends where the next starts, the last at `lines`
+    starts: Vec<u64>,
+    filters: Vec<Box<[u64; FILTER_WORDS]>>,
+    /// Complete lines when the index was built; lines after them are always searched
+    lines: u64,
+    /// Bytes of those lines, to tell whether the file still starts with them
+    bytes: usize,
+}
+
+/// Size of a trigram index
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct TrigramIndexInfo {
+    pub blocks: usize,
+    pub lines: u64,
+    pub memory_bytes: usize,
+}
+
+impl TrigramIndex {
+    /// Index the complete lines of a file, one block per `BLOCK_BYTES` in parallel
+    /// Gives up with `Cancelled` once `cancelled` returns true
+    pub fn build(
+        file: &LogFile,
+        cancelled: impl Fn() -> bool + Sync,
+    ) -> Result<Self, IndexerError> {
+        let lines = file.complete_line_count();
+        let bytes = file.byte_range(0..lines).end;
+
+        let mut starts: Vec<u64> = (0..bytes as u64)
+            .step_by(BLOCK_BYTES as usize)
+            .map(|offset| file.first_line_from_offset(offset))
+            .collect();
+        starts.dedup();
+        let filters = (0..starts.len())
+            .into_par_iter()
+            .map(|block| {
+                if cancelled() {
+                    return Err(IndexerError::Cancelled);
+                }
+                let end = starts.get(block + 1).copied().unwrap_or(lines);
+                let mut filter = Box::new([0u64; FILTER_WORDS]);
+                for trigram in file.data()[file.byte_range(starts[block]..end)].windows(3) {
+                    let bit = trigram_bit(trigram);
+                    filter[bit / 64] |= 1 << (bit % 64);
+                }
+                Ok(filter)
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(TrigramIndex {
+            starts,
+            filters,
+            lines,
+            bytes,
+        })
+    }
+
+    /// Whether the index still describes the start of `file`, which it stops doing once
+    /// lines are evicted from the front
+    pub fn matches(&self, file: &LogFile) -> bool {
+        self.lines <= file.line_count() && file.byte_range(0..self.lines).end == self.bytes
+    }
+
+    pub fn info(&self) -> TrigramIndexInfo {
+        TrigramIndexInfo {
+            blocks: self.starts.len(),
+            lines: self.lines,
+            memory_bytes: self.filters.len() * FILTER_WORDS * 8 + self.starts.len() * 8,
+        }
+    }
+
+    /// Lines that may contain `literal`, as sorted ranges ending with every line added since
+    /// the index was built up to `line_count`; None when the literal is too short to filter on
+    /// A case-insensitive literal must be ASCII, since only ASCII letters are folded
+    pub fn candidates(
+        &self,
+        literal: &str,
+        case_insensitive: bool,
+        line_count: u64,
+    ) -> Option<Vec<Range<u64>>> {
+        if literal.len() < 3 || (case_insensitive && !literal.is_ascii()) {
+            return None;
+        }
+        let bits: Vec<usize> = literal.as_bytes().windows(3).map(trigram_bit).collect();
+        let mut ranges: Vec<Range<u64>> = Vec::new();
+        let mut push = |range: Range<u64>| match ranges.last_mut() {
+            Some(last) if last.end == range.start => last.end = range.end,
+            _ if range.is_empty() => {}
+            _ => ranges.push(range),
+        };
+        for (block, filter) in self.filters.iter().enumerate() {
+            if bits
+                .iter()
+                .all(|&bit| filter[bit / 64] & (1 << (bit % 64)) != 0)
+            {
+                let end = self.starts.get(block + 1).copied().unwrap_or(self.lines);
+                push(self.starts[block]..end);
+            }
+        }
+        push(self.lines..line_count.max(self.lines));
+        Some(ranges)
+    }
+}
+
+/// Filter bit of a trigram, with ASCII letters lowercased
+fn trigram_bit(trigram: &[u8]) -> usize {
+    let key = u32::from_le_bytes([
+        trigram[0].to_ascii_lowercase(),
+        trigram[1].to_ascii_lowercase(),
+        trigram[2].to_ascii_lowercase(),
+        0,
+    ]);
+    (key.wrapping_mul(0x9E37_79B1) >> 16) as usize % FILTER_BITS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trigram_candidates() {
+        // Three blocks of filler with a rare word in the middle one
+        let line = format!("{}\n", "routine heartbeat ok ".repeat(4));
+        let block_lines = BLOCK_BYTES as usize / line.len() + 1;
+        let mut data = line.repeat(block_lines * 3);
+        let rare_line = block_lines * 3 / 2;
+        data.replace_range(
+            rare_line * line.len()..rare_line * line.len() + 9,
+            "Quarantin",
+        );
+        let mut file = LogFile::from_bytes("app.log", data.into_bytes());
+
+        assert!(matches!(
+            TrigramIndex::build(&file, || true),
+            Err(IndexerError::Cancelled)
+        ));
+        let index = TrigramIndex::build(&file, || false).unwrap();
+        assert!(index.matches(&file));
+        assert!(index.info().blocks >= 3);
+        let candidates = index
+            .candidates("QUARANTIN", true, file.line_count())
+            .unwrap();
+        let covered: u64 = candidates.iter().map(|r| r.end - r.start).sum();
+        assert!(candidates.iter().any(|r| r.contains(&(rare_line as u64))));
+        assert!(covered < file.line_count() / 2);
+        assert!(index.candidates("ok", false, file.line_count()).is_none());
+
+        // Lines added after the build are always candidates
+        let candidates = index.candidates("Quarantin", false, file.line_count() + 5);
+        assert_eq!(
+            candidates.unwrap().last().unwrap().end,
+            file.line_count() + 5
+        );
+
+        // Searches through the index find the same lines
+        assert!(file.set_trigram_index(index));
+        assert!(file.search("quarantin", 10).unwrap().is_empty());
+        assert_eq!(
+            file.search("(?i)quarantin", 10).unwrap(),
+            [rare_line as u64]
+        );
+        assert_eq!(file.search("heartbeat ok", 2).unwrap(), [0, 1]);
+    }
+}
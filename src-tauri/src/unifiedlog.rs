@@ -0,0 +1,255 @@
+use crate::listeners::json_line;
+use crate::live::{ChunkSender, RetentionOptions};
+use crate::timestamp::{format_timestamp, parse_timestamp};
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+
+/// Whether to read stored entries or follow new ones
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UnifiedLogMode {
+    /// `log stream`: follow entries as they are emitted
+    #[default]
+    Stream,
+    /// `log show`: read stored entries or a `.logarchive`
+    Show,
+}
+
+/// Options for reading the macOS unified log
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct UnifiedLogOptions {
+    #[serde(default)]
+    pub mode: UnifiedLogMode,
+    /// Filter predicate, e.g. `subsystem == "com.example.app" AND messageType == error`
+    #[serde(default)]
+    pub predicate: Option<String>,
+    /// Minimum level to include: `default`, `info` or `debug`
+    #[serde(default)]
+    pub level: Option<String>,
+    /// Show mode: only entries from the last interval, e.g. `1h` or `30m`
+    #[serde(default)]
+    pub last: Option<String>,
+    /// Show mode: start date, e.g. `2024-01-01 09:00:00`
+    #[serde(default)]
+    pub start: Option<String>,
+    /// Show mode: end date
+    #[serde(default)]
+    pub end: Option<String>,
+    /// Show mode: read a `.logarchive` instead of the local store
+    #[serde(default)]
+    pub archive: Option<String>,
+    #[serde(default)]
+    pub batch_interval_ms: Option<u64>,
+    #[serde(default)]
+    pub retention: RetentionOptions,
+}
+
+impl UnifiedLogOptions {
+    /// Name shown for the live view
+    pub fn display_name(&self) -> String {
+        match (&self.archive, &self.predicate) {
+            (Some(archive), _) => format!("oslog://{}", archive),
+            (None, Some(predicate)) => format!("oslog://{}", predicate),
+            (None, None) => "oslog://local".to_string(),
+        }
+    }
+}
+
+/// Arguments for the `log` tool, always requesting NDJSON output
+pub fn log_args(options: &UnifiedLogOptions) -> Vec<String> {
+    let mut args = vec![
+        match options.mode {
+            UnifiedLogMode::Stream => "stream".to_string(),
+            UnifiedLogMode::Show => "show".to_string(),
+        },
+        "--style".to_string(),
+        "ndjson".to_string(),
+    ];
+    let mut push = |flag: &str, value: &Option<String>| {
+        if let Some(value) = value.as_ref().filter(|v| !v.is_empty()) {
+            args.push(flag.to_string());
+            args.push(value.clone());
+        }
+    };
+    push("--predicate", &options.predicate);
+    push("--level", &options.level);
+    if options.mode == UnifiedLogMode::Show {
+        push("--last", &options.last);
+        push("--start", &options.start);
+        push("--end", &options.end);
+        push("--archive", &options.archive);
+    }
+    args
+}
+
+/// Convert one NDJSON entry from `log` into a JSON line with timestamp, level, process,
+/// subsystem and category columns; returns None for banner lines and non-log events
+pub fn entry_to_line(line: &str) -> Option<String> {
+    let JsonValue::Object(entry) = serde_json::from_str::<JsonValue>(line.trim()).ok()? else {
+        return None;
+    };
+    // Activity and signpost events carry no message worth a row
+    if let Some(event_type) = entry.get("eventType").and_then(JsonValue::as_str) {
+        if event_type != "logEvent" {
+            return None;
+        }
+    }
+
+    let field = |key: &str| entry.get(key).cloned().unwrap_or(JsonValue::Null);
+    let basename = |key: &str| {
+        entry
+            .get(key)
+            .and_then(JsonValue::as_str)
+            .map_or(JsonValue::Null, |path| {
+                JsonValue::from(path.rsplit('/').next().unwrap_or(path))
+            })
+    };
+    let timestamp = entry
+        .get("timestamp")
+        .and_then(JsonValue::as_str)
+        .and_then(parse_timestamp)
+        .map_or(JsonValue::Null, |ms| JsonValue::from(format_timestamp(ms)));
+
+    Some(json_line(vec![
+        ("timestamp".to_string(), timestamp),
+        ("level".to_string(), field("messageType")),
+        ("process".to_string(), basename("processImagePath")),
+        ("pid".to_string(), field("processID")),
+        ("thread".to_string(), field("threadID")),
+        ("subsystem".to_string(), field("subsystem")),
+        ("category".to_string(), field("category")),
+        ("sender".to_string(), basename("senderImagePath")),
+        ("message".to_string(), field("eventMessage")),
+    ]))
+}
+
+/// Run `log` and feed its entries into `sender` until the live source stops or the tool exits
+#[cfg(target_os = "macos")]
+pub fn spawn_log_reader<F>(
+    options: &UnifiedLogOptions,
+    sender: ChunkSender,
+    on_error: F,
+) -> std::io::Result<()>
+where
+    F: Fn(String) + Send + 'static,
+{
+    use std::io::{BufRead, BufReader};
+    use std::process::{Command, Stdio};
+    use std::time::Duration;
+
+    let mut child = Command::new("/usr/bin/log")
+        .args(log_args(options))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let (Some(stdout), Some(mut stderr)) = (child.stdout.take(), child.stderr.take()) else {
+        child.kill().ok();
+        return Err(std::io::Error::other("log output is unavailable"));
+    };
+
+    let reader_sender = sender.clone();
+    std::thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            let Ok(line) = line else { break };
+            if let Some(mut entry) = entry_to_line(&line) {
+                entry.push('\n');
+                if !reader_sender.send(entry.into_bytes()) {
+                    break;
+                }
+            }
+        }
+    });
+
+    // Stop the tool with the live source, and report why it exited on its own
+    std::thread::spawn(move || loop {
+        if sender.is_stopped() {
+            child.kill().ok();
+            child.wait().ok();
+            return;
+        }
+        match child.try_wait() {
+            Ok(Some(status)) => {
+                if !status.success() {
+                    let mut message = String::new();
+                    std::io::Read::read_to_string(&mut stderr, &mut message).ok();
+                    on_error(format!("log exited with {}: {}", status, message.trim()));
+                }
+                return;
+            }
+            Ok(None) => std::thread::sleep(Duration::from_millis(200)),
+            Err(err) => {
+                on_error(format!("Failed to wait for log: {}", err));
+                return;
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn spawn_log_reader<F>(
+    _options: &UnifiedLogOptions,
+    _sender: ChunkSender,
+    _on_error: F,
+) -> std::io::Result<()>
+where
+    F: Fn(String) + Send + 'static,
+{
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "The unified log is only available on macOS",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_args() {
+        let options = UnifiedLogOptions {
+            mode: UnifiedLogMode::Show,
+            predicate: Some("subsystem == \"com.example\"".to_string()),
+            last: Some("1h".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            log_args(&options),
+            vec![
+                "show",
+                "--style",
+                "ndjson",
+                "--predicate",
+                "subsystem == \"com.example\"",
+                "--last",
+                "1h"
+            ]
+        );
+
+        // Show-only flags are dropped when streaming
+        let options = UnifiedLogOptions {
+            last: Some("1h".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(log_args(&options), vec!["stream", "--style", "ndjson"]);
+    }
+
+    #[test]
+    fn test_entry_to_line() {
+        let entry = r#"{"timestamp":"2024-01-01 08:00:00.250000+0800","messageType":"Error","eventType":"logEvent","eventMessage":"connection reset","processImagePath":"/usr/libexec/nsurlsessiond","processID":312,"threadID":4411,"subsystem":"com.apple.network","category":"connection","senderImagePath":"/System/Library/Frameworks/Network.framework/Network"}"#;
+        assert_eq!(
+            entry_to_line(entry).unwrap(),
+            r#"{"timestamp":"2024-01-01T00:00:00.250Z","level":"Error","process":"nsurlsessiond","pid":312,"thread":4411,"subsystem":"com.apple.network","category":"connection","sender":"Network","message":"connection reset"}"#
+        );
+
+        assert_eq!(
+            entry_to_line("Filtering the log data using \"subsystem\""),
+            None
+        );
+        assert_eq!(
+            entry_to_line(r#"{"eventType":"activityCreateEvent","eventMessage":"x"}"#),
+            None
+        );
+    }
+}
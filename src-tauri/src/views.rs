@@ -0,0 +1,65 @@
+use crate::parsers::{load_json, save_json};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// File saved views are persisted to, inside the app config directory
+const VIEWS_FILE: &str = "saved_views.json";
+
+/// A named SQL query usable as a table, e.g. `errors` for `SELECT * FROM logs WHERE ...`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedView {
+    pub name: String,
+    pub sql: String,
+}
+
+/// Whether a name can be used as a table name without quoting
+pub fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Views saved in the app config directory, kept in the order they were first saved
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ViewStore {
+    #[serde(skip)]
+    path: PathBuf,
+    #[serde(default)]
+    views: Vec<SavedView>,
+}
+
+impl ViewStore {
+    /// Load the saved views from a config directory; a missing file has none
+    pub fn load(dir: &Path) -> io::Result<Self> {
+        let path = dir.join(VIEWS_FILE);
+        let mut store: ViewStore = load_json(&path)?;
+        store.path = path;
+        Ok(store)
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        save_json(&self.path, self)
+    }
+
+    pub fn views(&self) -> &[SavedView] {
+        &self.views
+    }
+
+    /// Add a view, or replace the query of the view with the same name
+    pub fn upsert(&mut self, view: SavedView) {
+        match self.views.iter_mut().find(|v| v.name == view.name) {
+            Some(existing) => *existing = view,
+            None => self.views.push(view),
+        }
+    }
+
+    /// Remove a view, returning whether it existed
+    pub fn remove(&mut self, name: &str) -> bool {
+        let before = self.views.len();
+        self.views.retain(|v| v.name != name);
+        self.views.len() != before
+    }
+}
@@ -0,0 +1,411 @@
+use crate::alerts::{Alert, AlertMonitor, AlertRule};
+use crate::livestats::LiveStats;
+use crate::parsers::library::glob_regex;
+use chrono::{SecondsFormat, Utc};
+use parking_lot::Mutex;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// Most findings kept; the oldest are dropped first
+const MAX_FINDINGS: usize = 1_000;
+
+/// Most bytes read from one file per scan, so a burst doesn't hold everything in memory
+const MAX_READ_PER_SCAN: u64 = 16 * 1024 * 1024;
+
+/// Errors that can occur starting a folder watch
+#[derive(Debug, Error)]
+pub enum WatchError {
+    #[error("{0} is not a directory")]
+    NotADirectory(String),
+    #[error("Invalid file pattern: {0}")]
+    InvalidPattern(String),
+}
+
+/// Which files of a folder are watched and how often they are scanned
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WatchOptions {
+    pub directory: String,
+    /// Glob matched against file names, e.g. `*.log`
+    #[serde(default = "default_pattern")]
+    pub pattern: String,
+    /// Also watch files in subdirectories
+    #[serde(default)]
+    pub recursive: bool,
+    #[serde(default = "default_interval_secs")]
+    pub interval_secs: u64,
+}
+
+fn default_pattern() -> String {
+    "*.log".to_string()
+}
+
+fn default_interval_secs() -> u64 {
+    5
+}
+
+/// An alert raised by the folder watch
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Finding {
+    pub alert: Alert,
+    /// When the alert fired, as RFC 3339
+    pub time: String,
+}
+
+/// State of a running folder watch
+#[derive(Debug, Clone, Serialize)]
+pub struct WatchStatus {
+    pub options: WatchOptions,
+    /// Files matching the pattern, as of the last scan
+    pub files: usize,
+    pub findings: usize,
+}
+
+/// Read position and alert state of one watched file
+struct WatchedFile {
+    offset: u64,
+    line_count: u64,
+    stats: LiveStats,
+    monitor: AlertMonitor,
+}
+
+impl WatchedFile {
+    /// Start at `offset`, which holds `line_count` lines already
+    fn new(offset: u64, line_count: u64, rules: &[AlertRule], now: Instant) -> Self {
+        let mut monitor = AlertMonitor::default();
+        // Broken rules were reported when they were loaded
+        monitor.set_rules(rules).ok();
+        monitor.restart(now);
+        WatchedFile {
+            offset,
+            line_count,
+            stats: LiveStats::default(),
+            monitor,
+        }
+    }
+
+    /// Check the lines appended since the last scan against the rules, or for a stall
+    fn scan(&mut self, path: &Path, now: Instant) -> io::Result<Vec<Alert>> {
+        let name = path.to_string_lossy();
+        let mut file = File::open(path)?;
+        let len = file.metadata()?.len();
+        if len < self.offset {
+            // Truncated or replaced: read it again from the start
+            self.offset = 0;
+            self.line_count = 0;
+        }
+        let mut data = Vec::new();
+        file.seek(SeekFrom::Start(self.offset))?;
+        file.take((len - self.offset).min(MAX_READ_PER_SCAN))
+            .read_to_end(&mut data)?;
+        // A line still being written is left for the next scan
+        let complete = match memchr::memrchr(b'\n', &data) {
+            Some(end) => &data[..=end],
+            None => &[][..],
+        };
+        if complete.is_empty() {
+            return Ok(self
+                .monitor
+                .check_stalled(now, &name, self.line_count.checked_sub(1)));
+        }
+        self.offset += complete.len() as u64;
+
+        let lines: Vec<_> = complete[..complete.len() - 1]
+            .split(|&b| b == b'\n')
+            .map(|line| String::from_utf8_lossy(line.strip_suffix(b"\r").unwrap_or(line)))
+            .collect();
+        let first_line = self.line_count;
+        self.line_count += lines.len() as u64;
+        self.stats
+            .record(now, lines.iter().map(|line| line.as_ref()));
+        let window = self.stats.window_errors(now);
+        Ok(self
+            .monitor
+            .on_append(now, &name, first_line, &lines, window))
+    }
+}
+
+/// Scans the matching files of a folder on a schedule, checking new content against the
+/// alert rules and keeping what fired as findings
+pub struct FolderWatch {
+    options: WatchOptions,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+    /// Rules to arm at the next scan, after they changed
+    pending_rules: Arc<Mutex<Option<Vec<AlertRule>>>>,
+    findings: Arc<Mutex<VecDeque<Finding>>>,
+    files: Arc<AtomicUsize>,
+}
+
+impl FolderWatch {
+    /// Start watching; files already in the folder are checked from their current end, and
+    /// files that appear later from their start
+    pub fn start<F>(
+        options: WatchOptions,
+        rules: Vec<AlertRule>,
+        mut on_finding: F,
+    ) -> Result<Self, WatchError>
+    where
+        F: FnMut(&Finding) + Send + 'static,
+    {
+        if !Path::new(&options.directory).is_dir() {
+            return Err(WatchError::NotADirectory(options.directory));
+        }
+        let pattern = glob_regex(&options.pattern).map_err(WatchError::InvalidPattern)?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let pending_rules = Arc::new(Mutex::new(None));
+        let findings = Arc::new(Mutex::new(VecDeque::new()));
+        let files = Arc::new(AtomicUsize::new(0));
+
+        let mut scanner = Scanner {
+            directory: PathBuf::from(&options.directory),
+            pattern,
+            recursive: options.recursive,
+            rules,
+            watched: HashMap::new(),
+        };
+        let interval = Duration::from_secs(options.interval_secs.max(1));
+        let thread_stop = stop.clone();
+        let thread_rules = pending_rules.clone();
+        let thread_findings = findings.clone();
+        let thread_files = files.clone();
+        let handle = std::thread::spawn(move || {
+            scanner.prime(Instant::now());
+            thread_files.store(scanner.watched.len(), Ordering::Relaxed);
+            let mut next_scan = Instant::now() + interval;
+            while !thread_stop.load(Ordering::SeqCst) {
+                // Wake up regularly so stop requests are honored
+                std::thread::sleep(Duration::from_millis(250));
+                let now = Instant::now();
+                if now < next_scan {
+                    continue;
+                }
+                next_scan = now + interval;
+                let rules = thread_rules.lock().take();
+                if let Some(rules) = rules {
+                    scanner.set_rules(rules);
+                }
+                for alert in scanner.scan(now) {
+                    let finding = Finding {
+                        alert,
+                        time: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+                    };
+                    on_finding(&finding);
+                    let mut findings = thread_findings.lock();
+                    if findings.len() == MAX_FINDINGS {
+                        findings.pop_front();
+                    }
+                    findings.push_back(finding);
+                }
+                thread_files.store(scanner.watched.len(), Ordering::Relaxed);
+            }
+        });
+
+        Ok(FolderWatch {
+            options,
+            stop,
+            handle: Some(handle),
+            pending_rules,
+            findings,
+            files,
+        })
+    }
+
+    /// Check new content against changed rules from the next scan on
+    pub fn set_rules(&self, rules: Vec<AlertRule>) {
+        *self.pending_rules.lock() = Some(rules);
+    }
+
+    /// Findings so far, oldest first
+    pub fn findings(&self) -> Vec<Finding> {
+        self.findings.lock().iter().cloned().collect()
+    }
+
+    pub fn clear_findings(&self) {
+        self.findings.lock().clear();
+    }
+
+    pub fn status(&self) -> WatchStatus {
+        WatchStatus {
+            options: self.options.clone(),
+            files: self.files.load(Ordering::Relaxed),
+            findings: self.findings.lock().len(),
+        }
+    }
+
+    /// Stop watching and wait for the background thread to exit
+    pub fn stop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            handle.join().ok();
+        }
+    }
+}
+
+impl Drop for FolderWatch {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// The background side of a folder watch
+struct Scanner {
+    directory: PathBuf,
+    pattern: Regex,
+    recursive: bool,
+    rules: Vec<AlertRule>,
+    watched: HashMap<PathBuf, WatchedFile>,
+}
+
+impl Scanner {
+    /// Track the files already there from their current end
+    fn prime(&mut self, now: Instant) {
+        for path in self.matching_files() {
+            let Ok((offset, line_count)) = count_lines(&path) else {
+                continue;
+            };
+            let watched = WatchedFile::new(offset, line_count, &self.rules, now);
+            self.watched.insert(path, watched);
+        }
+    }
+
+    fn set_rules(&mut self, rules: Vec<AlertRule>) {
+        for watched in self.watched.values_mut() {
+            watched.monitor.set_rules(&rules).ok();
+        }
+        self.rules = rules;
+    }
+
+    /// Check every matching file, picking up new ones and forgetting removed ones
+    fn scan(&mut self, now: Instant) -> Vec<Alert> {
+        let files = self.matching_files();
+        self.watched.retain(|path, _| files.contains(path));
+        let mut alerts = Vec::new();
+        for path in files {
+            let watched = self
+                .watched
+                .entry(path.clone())
+                .or_insert_with(|| WatchedFile::new(0, 0, &self.rules, now));
+            match watched.scan(&path, now) {
+                Ok(fired) => alerts.extend(fired),
+                Err(e) => tracing::debug!(path = %path.display(), error = %e, "watch scan failed"),
+            }
+        }
+        alerts
+    }
+
+    fn matching_files(&self) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        let mut directories = vec![self.directory.clone()];
+        while let Some(directory) = directories.pop() {
+            let Ok(entries) = fs::read_dir(&directory) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let Ok(file_type) = entry.file_type() else {
+                    continue;
+                };
+                let path = entry.path();
+                if file_type.is_dir() {
+                    if self.recursive {
+                        directories.push(path);
+                    }
+                } else if file_type.is_file()
+                    && self.pattern.is_match(&entry.file_name().to_string_lossy())
+                {
+                    files.push(path);
+                }
+            }
+        }
+        files.sort();
+        files
+    }
+}
+
+/// Size of a file up to its last complete line, and the number of lines before it
+fn count_lines(path: &Path) -> io::Result<(u64, u64)> {
+    let mut file = File::open(path)?;
+    let mut buf = vec![0; 1024 * 1024];
+    let (mut read, mut last_newline, mut lines) = (0u64, 0u64, 0u64);
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            return Ok((last_newline, lines));
+        }
+        for i in memchr::memchr_iter(b'\n', &buf[..n]) {
+            lines += 1;
+            last_newline = read + i as u64 + 1;
+        }
+        read += n as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alerts::AlertCondition;
+    use crate::regex_cache::RegexFlavor;
+    use std::io::Write;
+
+    #[test]
+    fn test_scan_checks_new_content_of_matching_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = dir.path().join("app.log");
+        fs::write(&old, "boot\nERROR old failure\n").unwrap();
+        fs::write(dir.path().join("notes.txt"), "").unwrap();
+
+        let mut scanner = Scanner {
+            directory: dir.path().to_path_buf(),
+            pattern: glob_regex("*.log").unwrap(),
+            recursive: false,
+            rules: vec![AlertRule {
+                name: "failures".to_string(),
+                condition: AlertCondition::Pattern {
+                    pattern: "failure".to_string(),
+                    flavor: RegexFlavor::Standard,
+                },
+                enabled: true,
+                notify: false,
+                cooldown_secs: 0,
+                actions: Vec::new(),
+            }],
+            watched: HashMap::new(),
+        };
+        let now = Instant::now();
+        scanner.prime(now);
+        assert_eq!(scanner.watched.len(), 1);
+        // What was there before the watch started doesn't fire
+        assert!(scanner.scan(now).is_empty());
+
+        let mut file = fs::OpenOptions::new().append(true).open(&old).unwrap();
+        write!(file, "ok\nnew failure\npartial failure").unwrap();
+        fs::write(dir.path().join("worker.log"), "failure at start\n").unwrap();
+        let alerts = scanner.scan(now);
+        let fired: Vec<_> = alerts
+            .iter()
+            .map(|a| (Path::new(&a.file).file_name().unwrap(), a.line))
+            .collect();
+        assert_eq!(
+            fired,
+            vec![
+                (std::ffi::OsStr::new("app.log"), Some(3)),
+                (std::ffi::OsStr::new("worker.log"), Some(0)),
+            ]
+        );
+
+        // The partial line is checked once it's complete
+        writeln!(file).unwrap();
+        let alerts = scanner.scan(now);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].line, Some(4));
+    }
+}
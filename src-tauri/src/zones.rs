@@ -0,0 +1,484 @@
+use async_trait::async_trait;
+use datafusion::arrow::array::{
+    Array, ArrayRef, BooleanArray, Int64Array, TimestampMillisecondArray,
+};
+use datafusion::arrow::compute::{filter_record_batch, max, min};
+use datafusion::arrow::datatypes::{DataType, SchemaRef, TimeUnit};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::catalog::Session;
+use datafusion::common::{plan_err, Statistics};
+use datafusion::datasource::{TableProvider, TableType};
+use datafusion::error::Result;
+use datafusion::execution::{RecordBatchStream, SendableRecordBatchStream, TaskContext};
+use datafusion::logical_expr::{Between, BinaryExpr, Expr, Operator, TableProviderFilterPushDown};
+use datafusion::physical_expr::expressions::Column;
+use datafusion::physical_expr::{LexOrdering, PhysicalSortExpr};
+use datafusion::physical_plan::memory::MemoryExec;
+use datafusion::physical_plan::metrics::{
+    BaselineMetrics, ExecutionPlanMetricsSet, MetricBuilder, MetricsSet,
+};
+use datafusion::physical_plan::{DisplayAs, DisplayFormatType, ExecutionPlan, PlanProperties};
+use datafusion::scalar::ScalarValue;
+use futures::{Stream, StreamExt};
+use std::any::Any;
+use std::fmt;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+/// Rows per zone, the granularity scans skip at
+pub const ZONE_ROWS: usize = 8192;
+
+/// Column a table's rows are in ascending order of, when it has one
+const ORDER_COLUMN: &str = "line_number";
+
+/// A slice of a table with the smallest and largest value of each column it can be pruned
+/// on, such as `line_number` and timestamps
+struct Zone {
+    batch: RecordBatch,
+    /// By column index; None for columns of other types or holding only nulls
+    ranges: Vec<Option<(i64, i64)>>,
+}
+
+impl Zone {
+    fn new(batch: RecordBatch) -> Self {
+        let ranges = batch.columns().iter().map(column_range).collect();
+        Zone { batch, ranges }
+    }
+}
+
+impl fmt::Debug for Zone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Zone")
+            .field("rows", &self.batch.num_rows())
+            .field("ranges", &self.ranges)
+            .finish()
+    }
+}
+
+/// In-memory table whose scans skip the zones that filters on integer and millisecond
+/// timestamp columns rule out, so `WHERE line_number BETWEEN a AND b` or `WHERE ts >= x`
+/// reads only the rows around the match
+/// Filters are pushed down inexactly, so DataFusion still applies them to the zones read
+/// Scans split the zones into one partition per core, each declared sorted by `line_number`
+/// when the table has it, so `ORDER BY line_number` merges the partitions as they stream
+/// rather than sorting; without it rows come back in no particular order
+#[derive(Debug)]
+pub struct ZonedTable {
+    schema: SchemaRef,
+    zones: Vec<Zone>,
+}
+
+impl ZonedTable {
+    pub fn try_new(schema: SchemaRef, batches: Vec<RecordBatch>) -> Result<Self> {
+        let mut zones = Vec::new();
+        for batch in batches {
+            if !schema.contains(&batch.schema()) {
+                return plan_err!("Batch schema doesn't match the table's");
+            }
+            for offset in (0..batch.num_rows()).step_by(ZONE_ROWS) {
+                let batch = batch.slice(offset, ZONE_ROWS.min(batch.num_rows() - offset));
+                zones.push(Zone::new(batch));
+            }
+        }
+        Ok(ZonedTable { schema, zones })
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.zones.iter().map(|zone| zone.batch.num_rows()).sum()
+    }
+
+    /// The rows whose `line_number` `keep` holds for, filtered zone by zone without
+    /// scanning the rest of the table
+    pub fn sample(&self, keep: impl Fn(i64) -> bool) -> Result<ZonedTable> {
+        let Ok(index) = self.schema.index_of(ORDER_COLUMN) else {
+            return plan_err!("Only tables with a {} column can be sampled", ORDER_COLUMN);
+        };
+        let mut zones = Vec::new();
+        for zone in &self.zones {
+            let Some(lines) = zone
+                .batch
+                .column(index)
+                .as_any()
+                .downcast_ref::<Int64Array>()
+            else {
+                return plan_err!("{} isn't an integer column", ORDER_COLUMN);
+            };
+            let kept: BooleanArray = lines
+                .iter()
+                .map(|line| Some(line.is_some_and(&keep)))
+                .collect();
+            let batch = filter_record_batch(&zone.batch, &kept)?;
+            if batch.num_rows() > 0 {
+                zones.push(Zone::new(batch));
+            }
+        }
+        Ok(ZonedTable {
+            schema: self.schema.clone(),
+            zones,
+        })
+    }
+}
+
+/// Smallest and largest value of a column that can be pruned on
+fn column_range(array: &ArrayRef) -> Option<(i64, i64)> {
+    match array.data_type() {
+        DataType::Int64 => {
+            let array = array.as_any().downcast_ref::<Int64Array>()?;
+            Some((min(array)?, max(array)?))
+        }
+        DataType::Timestamp(TimeUnit::Millisecond, _) => {
+            let array = array.as_any().downcast_ref::<TimestampMillisecondArray>()?;
+            Some((min(array)?, max(array)?))
+        }
+        _ => None,
+    }
+}
+
+/// A literal's value when it compares directly to a column of `data_type`
+fn literal_value(value: &ScalarValue, data_type: &DataType) -> Option<i64> {
+    match (value, data_type) {
+        (ScalarValue::Int64(value), DataType::Int64) => *value,
+        (
+            ScalarValue::TimestampMillisecond(value, _),
+            DataType::Timestamp(TimeUnit::Millisecond, _),
+        ) => *value,
+        _ => None,
+    }
+}
+
+/// The column a filter bounds and the inclusive bounds it puts on it, for comparisons and
+/// `BETWEEN` of a column with literals
+fn filter_bounds(schema: &SchemaRef, filter: &Expr) -> Option<(usize, i64, i64)> {
+    let literal = |column: &Expr, value: &Expr| {
+        let (Expr::Column(column), Expr::Literal(value)) = (column, value) else {
+            return None;
+        };
+        let index = schema.index_of(&column.name).ok()?;
+        Some((
+            index,
+            literal_value(value, schema.field(index).data_type())?,
+        ))
+    };
+    match filter {
+        Expr::BinaryExpr(BinaryExpr { left, op, right }) => {
+            let (index, value, op) = match literal(left, right) {
+                Some((index, value)) => (index, value, *op),
+                None => {
+                    let (index, value) = literal(right, left)?;
+                    (index, value, op.swap()?)
+                }
+            };
+            let (low, high) = match op {
+                Operator::Eq => (value, value),
+                Operator::Lt => (i64::MIN, value.checked_sub(1)?),
+                Operator::LtEq => (i64::MIN, value),
+                Operator::Gt => (value.checked_add(1)?, i64::MAX),
+                Operator::GtEq => (value, i64::MAX),
+                _ => return None,
+            };
+            Some((index, low, high))
+        }
+        Expr::Between(Between {
+            expr,
+            negated: false,
+            low,
+            high,
+        }) => {
+            let (index, low) = literal(expr, low)?;
+            let (_, high) = literal(expr, high)?;
+            Some((index, low, high))
+        }
+        _ => None,
+    }
+}
+
+#[async_trait]
+impl TableProvider for ZonedTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Base
+    }
+
+    fn supports_filters_pushdown(
+        &self,
+        filters: &[&Expr],
+    ) -> Result<Vec<TableProviderFilterPushDown>> {
+        Ok(vec![TableProviderFilterPushDown::Inexact; filters.len()])
+    }
+
+    async fn scan(
+        &self,
+        state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let bounds: Vec<_> = filters
+            .iter()
+            .filter_map(|filter| filter_bounds(&self.schema, filter))
+            .collect();
+        let mut rows_pruned = 0;
+        let mut batches = Vec::new();
+        for zone in &self.zones {
+            let kept = bounds.iter().all(|&(index, low, high)| {
+                zone.ranges[index].is_some_and(|(min, max)| min <= high && max >= low)
+            });
+            if kept {
+                batches.push(zone.batch.clone());
+            } else {
+                rows_pruned += zone.batch.num_rows();
+            }
+        }
+        // Contiguous runs of zones, one per core, so scans, filters and partial aggregates
+        // run in parallel like the raw search does over line blocks
+        let per_partition = batches
+            .len()
+            .div_ceil(state.config().target_partitions().max(1))
+            .max(1);
+        let mut partitions: Vec<Vec<RecordBatch>> = batches
+            .chunks(per_partition)
+            .map(<[RecordBatch]>::to_vec)
+            .collect();
+        if partitions.is_empty() {
+            partitions.push(Vec::new());
+        }
+        let mut inner = MemoryExec::try_new(&partitions, self.schema.clone(), projection.cloned())?;
+        // Zones hold rows in file order, and each partition a contiguous run of them
+        if let Ok(index) = self.schema.index_of(ORDER_COLUMN) {
+            let line_number = Arc::new(Column::new(ORDER_COLUMN, index));
+            let ordering = LexOrdering::new(vec![PhysicalSortExpr::new_default(line_number)]);
+            inner = inner.try_with_sort_information(vec![ordering])?;
+        }
+        Ok(Arc::new(ZonedScanExec::new(Arc::new(inner), rows_pruned)))
+    }
+}
+
+/// Scan of the zones a `ZonedTable` kept, reporting the rows it skipped as `rows_pruned`
+/// and, per partition, the rows read so far and when it was read to the end
+#[derive(Debug)]
+struct ZonedScanExec {
+    inner: Arc<dyn ExecutionPlan>,
+    rows_pruned: usize,
+    metrics: ExecutionPlanMetricsSet,
+}
+
+impl ZonedScanExec {
+    fn new(inner: Arc<dyn ExecutionPlan>, rows_pruned: usize) -> Self {
+        let metrics = ExecutionPlanMetricsSet::new();
+        MetricBuilder::new(&metrics)
+            .global_counter("rows_pruned")
+            .add(rows_pruned);
+        ZonedScanExec {
+            inner,
+            rows_pruned,
+            metrics,
+        }
+    }
+}
+
+impl DisplayAs for ZonedScanExec {
+    fn fmt_as(&self, _format: DisplayFormatType, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ZonedScanExec: rows_pruned={}", self.rows_pruned)
+    }
+}
+
+impl ExecutionPlan for ZonedScanExec {
+    fn name(&self) -> &str {
+        "ZonedScanExec"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        self.inner.properties()
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        Vec::new()
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        _children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(self)
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        Ok(Box::pin(MeteredStream {
+            inner: self.inner.execute(partition, context)?,
+            baseline: BaselineMetrics::new(&self.metrics, partition),
+        }))
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+
+    fn statistics(&self) -> Result<Statistics> {
+        self.inner.statistics()
+    }
+}
+
+/// A scan partition recording its output rows and end time as it is read
+struct MeteredStream {
+    inner: SendableRecordBatchStream,
+    baseline: BaselineMetrics,
+}
+
+impl Stream for MeteredStream {
+    type Item = Result<RecordBatch>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let poll = self.inner.poll_next_unpin(cx);
+        self.baseline.record_poll(poll)
+    }
+}
+
+impl RecordBatchStream for MeteredStream {
+    fn schema(&self) -> SchemaRef {
+        self.inner.schema()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::datatypes::{Field, Schema};
+    use datafusion::common::stats::Precision;
+    use datafusion::prelude::*;
+
+    #[tokio::test]
+    async fn test_zoned_scan() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("line_number", DataType::Int64, false),
+            Field::new("ts", DataType::Timestamp(TimeUnit::Millisecond, None), true),
+        ]));
+        let lines: Vec<i64> = (1..=20_000).collect();
+        let times: Vec<i64> = lines.iter().map(|line| line * 1000).collect();
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int64Array::from(lines)),
+                Arc::new(TimestampMillisecondArray::from(times)),
+            ],
+        )
+        .unwrap();
+        let table = Arc::new(ZonedTable::try_new(schema, vec![batch]).unwrap());
+        let ctx = SessionContext::new_with_config(SessionConfig::new().with_target_partitions(4));
+        ctx.register_table("logs", table.clone()).unwrap();
+
+        // Only the first zone can hold lines 100 to 200
+        let filter = col("line_number").between(lit(100i64), lit(200i64));
+        let plan = table
+            .scan(&ctx.state(), None, &[filter], None)
+            .await
+            .unwrap();
+        assert_eq!(plan.statistics().unwrap().num_rows, Precision::Exact(8192));
+        let pruned = plan.metrics().unwrap().sum_by_name("rows_pruned");
+        assert_eq!(pruned.map(|m| m.as_usize()), Some(20_000 - 8192));
+
+        let ts = ScalarValue::TimestampMillisecond(Some(15_000_000), None);
+        let plan = table
+            .scan(&ctx.state(), None, &[col("ts").gt_eq(lit(ts))], None)
+            .await
+            .unwrap();
+        assert_eq!(
+            plan.statistics().unwrap().num_rows,
+            Precision::Exact(20_000 - 8192)
+        );
+
+        // Each of the three zones is a partition of its own, sorted by line number
+        let plan = table.scan(&ctx.state(), None, &[], None).await.unwrap();
+        assert_eq!(plan.properties().output_partitioning().partition_count(), 3);
+        assert!(plan.properties().output_ordering().is_some());
+        datafusion::physical_plan::collect(plan.clone(), ctx.task_ctx())
+            .await
+            .unwrap();
+        let metrics = plan.metrics().unwrap();
+        assert_eq!(metrics.output_rows(), Some(20_000));
+        let ended = metrics
+            .iter()
+            .filter(|metric| metric.value().name() == "end_timestamp")
+            .count();
+        assert_eq!(ended, 3);
+
+        // Ordering by line number merges the partitions instead of sorting them
+        let df = ctx
+            .sql("SELECT line_number FROM logs ORDER BY line_number LIMIT 3")
+            .await
+            .unwrap();
+        let physical = df.clone().create_physical_plan().await.unwrap();
+        let shown = datafusion::physical_plan::displayable(physical.as_ref())
+            .indent(true)
+            .to_string();
+        assert!(shown.contains("SortPreservingMergeExec"), "{}", shown);
+        assert!(!shown.contains("SortExec"), "{}", shown);
+        let batches = df.collect().await.unwrap();
+        let first = batches[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(first.values(), &[1, 2, 3]);
+
+        // Pruning never changes results
+        let batches = ctx
+            .sql("SELECT COUNT(*) FROM logs WHERE line_number BETWEEN 100 AND 200 OR line_number > 19990")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let count = batches[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap()
+            .value(0);
+        assert_eq!(count, 111);
+        let batches = ctx
+            .sql("SELECT COUNT(*) FROM logs WHERE line_number >= 8000 AND line_number < 8500")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let count = batches[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap()
+            .value(0);
+        assert_eq!(count, 500);
+
+        // A sample keeps its rows whichever zone they sit in, and its zones stay prunable
+        let sample = table.sample(|line| line % 10 == 0).unwrap();
+        assert_eq!(table.num_rows(), 20_000);
+        assert_eq!(sample.num_rows(), 2000);
+        let plan = sample
+            .scan(
+                &ctx.state(),
+                None,
+                &[col("line_number").gt(lit(19_000i64))],
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(plan.statistics().unwrap().num_rows, Precision::Exact(362));
+    }
+}
@@ -12,17 +12,17 @@ const SQL_EXAMPLES = [
   {
     name: 'Basic Selection',
     description: 'Get first 100 log lines',
-    query: 'SELECT * FROM logs LIMIT 100',
+    query: 'SELECT * FROM logs ORDER BY line_number LIMIT 100',
   },
   {
     name: 'Filter by Pattern',
     description: 'Find lines containing specific text',
-    query: "SELECT * FROM logs WHERE line LIKE '%ERROR%' LIMIT 50",
+    query: "SELECT * FROM logs WHERE line LIKE '%ERROR%' ORDER BY line_number LIMIT 50",
   },
   {
     name: 'Regex Search',
     description: 'Use regex to find patterns',
-    query: "SELECT * FROM logs WHERE regex_match(line, '(?i)(error|exception)') LIMIT 50",
+    query: "SELECT * FROM logs WHERE regex_match(line, '(?i)(error|exception)') ORDER BY line_number LIMIT 50",
   },
   {
     name: 'Count by Level',
@@ -58,12 +58,13 @@ GROUP BY level`,
     description: 'Filter by timestamp in JSON logs',
     query: `SELECT * FROM logs 
 WHERE json_extract(line, 'timestamp') >= '2024-01-01'
+ORDER BY line_number
 LIMIT 50`,
   },
   {
     name: 'Specific Field',
     description: 'Extract a specific JSON field',
-    query: "SELECT line_number, json_extract(line, 'message') as message FROM logs LIMIT 50",
+    query: "SELECT line_number, json_extract(line, 'message') as message FROM logs ORDER BY line_number LIMIT 50",
   },
 ];
 
@@ -26,7 +26,7 @@ export function QueryPanel({
   externalQuery,
   onQueryConsumed,
 }: QueryPanelProps) {
-  const [query, setQuery] = useState('SELECT * FROM logs LIMIT 100');
+  const [query, setQuery] = useState('SELECT * FROM logs ORDER BY line_number LIMIT 100');
   const [result, setResult] = useState<QueryResult | null>(null);
   const [error, setError] = useState<string | null>(null);
   const [loading, setLoading] = useState(false);
@@ -130,7 +130,7 @@ export function QueryPanel({
         </div>
 
         <div className="query-help-inline">
-          <span><strong>Table:</strong> <code>logs</code> (line_number, line)</span>
+          <span><strong>Table:</strong> <code>logs</code> (line_number, line, line_format, byte_offset, byte_len)</span>
           <span><strong>UDFs:</strong> <code>regex_match(col, 'pattern')</code>, <code>json_extract(col, 'key')</code></span>
         </div>
 
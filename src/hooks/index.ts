@@ -1,2 +1,9 @@
 export { useLogFile } from './useLogFile';
-export type { FileInfo, IndexProgress, UseLogFileReturn } from './useLogFile';
+export type {
+  FileInfo,
+  IndexProgress,
+  LineEstimate,
+  OperationProgress,
+  RegexFlavor,
+  UseLogFileReturn,
+} from './useLogFile';
@@ -1,7 +1,7 @@
-import { useState, useCallback, useEffect } from 'react';
+import { useState, useCallback, useEffect, useRef } from 'react';
 import { invoke } from '@tauri-apps/api/core';
 import { listen } from '@tauri-apps/api/event';
-import { open } from '@tauri-apps/plugin-dialog';
+import { ask, open } from '@tauri-apps/plugin-dialog';
 
 export interface FileInfo {
   path: string;
@@ -16,15 +16,39 @@ export interface IndexProgress {
   message: string;
 }
 
+export interface OperationProgress extends IndexProgress {
+  id: number;
+  kind: 'index' | 'search' | 'sql' | 'export' | 'parse';
+  elapsed_ms: number;
+  eta_ms: number | null;
+  done: boolean;
+}
+
+/** A path refused because its directory hasn't been approved for opening files from */
+export interface PathApproval {
+  path: string;
+  directory: string;
+}
+
+/** Approximate line count while a file is indexed; `exact` once indexing completes */
+export interface LineEstimate {
+  lines: number;
+  exact: boolean;
+}
+
+/** Regex engine a search runs on; `fancy` adds lookaround and backreferences but is slower */
+export type RegexFlavor = 'standard' | 'fancy';
+
 export interface UseLogFileReturn {
   fileInfo: FileInfo | null;
   isLoading: boolean;
   progress: IndexProgress | null;
+  lineEstimate: LineEstimate | null;
   error: string | null;
   openFile: () => Promise<void>;
   openFilePath: (path: string) => Promise<void>;
   closeFile: () => Promise<void>;
-  search: (pattern: string, maxResults?: number) => Promise<number[]>;
+  search: (pattern: string, maxResults?: number, flavor?: RegexFlavor) => Promise<number[]>;
   getLines: (start: number, count: number) => Promise<string[]>;
 }
 
@@ -32,16 +56,72 @@ export function useLogFile(): UseLogFileReturn {
   const [fileInfo, setFileInfo] = useState<FileInfo | null>(null);
   const [isLoading, setIsLoading] = useState(false);
   const [progress, setProgress] = useState<IndexProgress | null>(null);
+  const [lineEstimate, setLineEstimate] = useState<LineEstimate | null>(null);
   const [error, setError] = useState<string | null>(null);
+  // Path being opened, kept when refused to retry once its directory is approved
+  const openingPath = useRef<string | null>(null);
+
+  // Open a file by path directly
+  const openFilePath = useCallback(async (path: string) => {
+    try {
+      setIsLoading(true);
+      setError(null);
+      setProgress(null);
+      openingPath.current = path;
+
+      const info = await invoke<FileInfo>('open_file', { path });
+      setFileInfo(info);
+      openingPath.current = null;
+    } catch (err) {
+      const message = err instanceof Error ? err.message : String(err);
+      setError(message);
+      console.error('Failed to open file:', err);
+    } finally {
+      setIsLoading(false);
+      setProgress(null);
+    }
+  }, []);
+
+  // Ask before approving the directory of a refused path, then open the file again
+  useEffect(() => {
+    const unlisten = listen<PathApproval>('path-approval-required', async (event) => {
+      const { directory } = event.payload;
+      const approved = await ask(
+        `Log Microscope can only open files from folders you allow. Allow opening files from ${directory}?`,
+        { title: 'Allow folder', kind: 'warning' }
+      );
+      if (!approved) return;
+      try {
+        await invoke('approve_directory', { directory });
+      } catch (err) {
+        setError(err instanceof Error ? err.message : String(err));
+        return;
+      }
+      const path = openingPath.current;
+      if (path) {
+        await openFilePath(path);
+      }
+    });
+
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, [openFilePath]);
 
   // Listen for progress events
   useEffect(() => {
-    const unlisten = listen<IndexProgress>('index-progress', (event) => {
-      setProgress(event.payload);
+    const unlisten = listen<OperationProgress>('operation-progress', (event) => {
+      if (event.payload.kind === 'index') {
+        setProgress(event.payload);
+      }
+    });
+    const unlistenEstimate = listen<LineEstimate>('line-count-estimate', (event) => {
+      setLineEstimate(event.payload);
     });
 
     return () => {
       unlisten.then((fn) => fn());
+      unlistenEstimate.then((fn) => fn());
     };
   }, []);
 
@@ -60,41 +140,14 @@ export function useLogFile(): UseLogFileReturn {
 
       if (!selected) return;
 
-      setIsLoading(true);
-      setError(null);
-      setProgress(null);
-
       const path = typeof selected === 'string' ? selected : selected;
-      const info = await invoke<FileInfo>('open_file', { path });
-      setFileInfo(info);
-    } catch (err) {
-      const message = err instanceof Error ? err.message : String(err);
-      setError(message);
-      console.error('Failed to open file:', err);
-    } finally {
-      setIsLoading(false);
-      setProgress(null);
-    }
-  }, []);
-
-  // Open a file by path directly
-  const openFilePath = useCallback(async (path: string) => {
-    try {
-      setIsLoading(true);
-      setError(null);
-      setProgress(null);
-
-      const info = await invoke<FileInfo>('open_file', { path });
-      setFileInfo(info);
+      await openFilePath(path);
     } catch (err) {
       const message = err instanceof Error ? err.message : String(err);
       setError(message);
       console.error('Failed to open file:', err);
-    } finally {
-      setIsLoading(false);
-      setProgress(null);
     }
-  }, []);
+  }, [openFilePath]);
 
   // Close the current file
   const closeFile = useCallback(async () => {
@@ -111,13 +164,18 @@ export function useLogFile(): UseLogFileReturn {
 
   // Search for a pattern in the file
   const search = useCallback(
-    async (pattern: string, maxResults: number = 1000): Promise<number[]> => {
+    async (
+      pattern: string,
+      maxResults: number = 1000,
+      flavor: RegexFlavor = 'standard'
+    ): Promise<number[]> => {
       if (!fileInfo) return [];
 
       try {
         const results = await invoke<number[]>('search', {
           pattern,
           maxResults,
+          flavor,
         });
         return results;
       } catch (err) {
@@ -150,6 +208,7 @@ export function useLogFile(): UseLogFileReturn {
     fileInfo,
     isLoading,
     progress,
+    lineEstimate,
     error,
     openFile,
     openFilePath,